@@ -0,0 +1,39 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use esbuild_rs::bundler::{self, BundleOptions};
+use esbuild_rs::cache::{decode_ast, encode_ast};
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::{self, ParseOptions};
+
+mod common;
+
+fn bench_ast_cache(c: &mut Criterion) {
+    // Every case works on the same file, so that decoding a cached tree can
+    // be compared with parsing the file again, which is what the cache saves
+    let contents = bundler::print_file(common::synthetic_ast(10_000), &BundleOptions::default());
+    let source = Source {
+        index: 1,
+        pretty_path: "synthetic.js".to_owned(),
+        contents,
+        ..Source::default()
+    };
+    let options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    let parse = || parser::parse(&Log::default(), black_box(&source), &options).unwrap();
+    let ast = parse();
+    let bytes = encode_ast(&ast);
+
+    let mut group = c.benchmark_group("ast_cache");
+    group.throughput(Throughput::Bytes(source.contents.len() as u64));
+    group.bench_function("encode", |b| b.iter(|| encode_ast(black_box(&ast))));
+    group.bench_function("decode", |b| {
+        b.iter(|| decode_ast(black_box(&bytes)).unwrap())
+    });
+    group.bench_function("clone", |b| b.iter(|| black_box(&ast).clone()));
+    group.bench_function("parse", |b| b.iter(parse));
+    group.finish();
+}
+
+criterion_group!(benches, bench_ast_cache);
+criterion_main!(benches);
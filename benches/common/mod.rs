@@ -0,0 +1,107 @@
+// Helpers shared by the benchmarks. Each benchmark is its own crate, so this
+// is included with "mod common;" instead of being a library.
+
+use esbuild_rs::ast::{
+    Binding, BindingKind, Decl, DeclaredSymbol, Expr, ExprKind, LocalKind, OperatorCode, Part,
+    Reference, Scope, ScopeKind, ScopeTree, Stmt, StmtKind, StrictModeKind, Symbol, SymbolKind,
+    SymbolMap, AST, INVALID_REF,
+};
+use std::collections::HashMap;
+
+fn symbol(kind: SymbolKind, name: String) -> Symbol {
+    Symbol {
+        use_count_estimate: 1,
+        ..Symbol::new(kind, name)
+    }
+}
+
+fn expr(data: ExprKind) -> Expr {
+    Expr {
+        location: 0,
+        data: Box::new(data),
+    }
+}
+
+// Builds a module with one part per top-level statement, each of which looks
+// like "var vN = vM + 'string N' * N;" where "vM" is an earlier variable. This
+// is roughly the shape of a large bundled dependency.
+pub fn synthetic_ast(part_count: usize) -> AST {
+    let mut symbols = SymbolMap::new(1);
+    let mut members = HashMap::new();
+    let mut parts = Vec::new();
+
+    for i in 0..part_count {
+        let name = format!("v{}", i);
+        let reference = Reference::new(0, i);
+        symbols.outer[0].push(symbol(SymbolKind::Hoisted, name.clone()));
+        members.insert(name, reference);
+
+        let text = format!("string {}", i);
+        let value = expr(ExprKind::Binary {
+            op_code: OperatorCode::BinOpAdd,
+            left: expr(ExprKind::Identifier {
+                reference: Reference::new(0, i / 2),
+            }),
+            right: expr(ExprKind::Binary {
+                op_code: OperatorCode::BinOpMul,
+                left: expr(ExprKind::String {
+                    value: text.encode_utf16().collect(),
+                }),
+                right: expr(ExprKind::Number { value: i as f64 }),
+            }),
+        });
+        let stmt = Stmt {
+            location: i * 32,
+            data: Box::new(StmtKind::Local {
+                decls: vec![Decl {
+                    binding: Binding {
+                        location: i * 32 + 4,
+                        data: Box::new(BindingKind::Identifier { reference }),
+                    },
+                    value: Some(value),
+                }],
+                kind: LocalKind::Var,
+                is_export: false,
+                was_ts_import_equals_in_namespace: false,
+            }),
+        };
+
+        let mut use_count_estimates = HashMap::new();
+        use_count_estimates.insert(Reference::new(0, i / 2), 1);
+        parts.push(Part {
+            import_paths: vec![],
+            stmts: vec![stmt],
+            declared_symbols: vec![DeclaredSymbol {
+                reference,
+                is_top_level: true,
+            }],
+            use_count_estimates,
+            local_dependencies: HashMap::new(),
+            can_be_removed_if_unused: true,
+            is_namespace_export: false,
+            force_tree_shaking: false,
+        });
+    }
+
+    let mut scope = Scope::new(ScopeKind::Entry, StrictModeKind::Sloppy);
+    scope.members = members;
+    AST {
+        was_typescript: false,
+        has_top_level_return: false,
+        uses_exports_ref: false,
+        uses_module_ref: false,
+        has_es6_imports: false,
+        has_es6_exports: false,
+        hash_bang: String::new(),
+        parts,
+        symbols,
+        scopes: ScopeTree::new(scope),
+        exports_ref: INVALID_REF,
+        module_ref: INVALID_REF,
+        wrapper_ref: INVALID_REF,
+        named_imports: HashMap::new(),
+        named_exports: HashMap::new(),
+        top_level_symbol_to_parts: HashMap::new(),
+        export_stars: vec![],
+    }
+}
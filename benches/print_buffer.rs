@@ -0,0 +1,95 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use esbuild_rs::ast::{BindingKind, ExprKind, OperatorCode, StmtKind, AST};
+use esbuild_rs::printer::{estimate_output_size, PrintBuffer};
+use std::fmt::Write;
+
+mod common;
+
+// Prints the statements of the synthetic module ("var vN = vM + 'string N' *
+// N;") the way the printer appends tokens, one piece at a time
+fn print_synthetic(ast: &AST, js: &mut PrintBuffer) {
+    for part in &ast.parts {
+        for stmt in &part.stmts {
+            if let StmtKind::Local { decls, .. } = stmt.data.as_ref() {
+                for decl in decls {
+                    js.print("var ");
+                    if let BindingKind::Identifier { reference } = decl.binding.data.as_ref() {
+                        js.print(&ast.symbols[*reference].name);
+                    }
+                    js.print(" = ");
+                    if let Some(value) = &decl.value {
+                        print_expr(ast, value.data.as_ref(), js);
+                    }
+                    js.print_ascii(b';');
+                    js.print_ascii(b'\n');
+                }
+            }
+        }
+    }
+}
+
+fn print_expr(ast: &AST, expr: &ExprKind, js: &mut PrintBuffer) {
+    match expr {
+        ExprKind::Binary {
+            op_code,
+            left,
+            right,
+        } => {
+            print_expr(ast, left.data.as_ref(), js);
+            js.print(if *op_code == OperatorCode::BinOpAdd {
+                " + "
+            } else {
+                " * "
+            });
+            print_expr(ast, right.data.as_ref(), js);
+        }
+        ExprKind::Identifier { reference } => js.print(&ast.symbols[*reference].name),
+        ExprKind::String { value } => {
+            js.print_ascii(b'"');
+            for c in std::char::decode_utf16(value.iter().copied()) {
+                js.print_char(c.unwrap_or(std::char::REPLACEMENT_CHARACTER));
+            }
+            js.print_ascii(b'"');
+        }
+        ExprKind::Number { value } => {
+            let mut text = String::new();
+            write!(text, "{}", value).unwrap();
+            js.print(&text);
+        }
+        _ => {}
+    }
+}
+
+fn bench_print_buffer(c: &mut Criterion) {
+    // About 4.5mb of output
+    let ast = common::synthetic_ast(100_000);
+    let estimate = estimate_output_size(&ast, true);
+    let mut actual = PrintBuffer::new();
+    print_synthetic(&ast, &mut actual);
+    println!(
+        "estimated {} bytes, printed {} bytes",
+        estimate,
+        actual.len()
+    );
+
+    let mut group = c.benchmark_group("print_buffer");
+    group.throughput(Throughput::Bytes(actual.len() as u64));
+    group.bench_function(BenchmarkId::new("growing", actual.len()), |b| {
+        b.iter(|| {
+            let mut js = PrintBuffer::new();
+            print_synthetic(black_box(&ast), &mut js);
+            js.into_string()
+        })
+    });
+    group.bench_function(BenchmarkId::new("preallocated", actual.len()), |b| {
+        b.iter(|| {
+            let mut js = PrintBuffer::with_capacity(estimate_output_size(black_box(&ast), true));
+            print_synthetic(black_box(&ast), &mut js);
+            js.into_string()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_print_buffer);
+criterion_main!(benches);
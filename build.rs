@@ -0,0 +1,6 @@
+// The Node.js addon needs the linker flags that let it use the symbols of the
+// node binary that loads it
+fn main() {
+    #[cfg(feature = "napi")]
+    napi_build::setup();
+}
@@ -0,0 +1,30 @@
+// Feeds arbitrary bytes to the lexer in both the JavaScript and the JSON
+// mode. It must never panic, and every token but the last one must consume at
+// least one character so that scanning a file always ends.
+
+#![no_main]
+
+use esbuild_rs::lexer::Lexer;
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::tables::Token;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = Source {
+        contents: String::from_utf8_lossy(data).into_owned(),
+        ..Source::default()
+    };
+    for &json in &[false, true] {
+        let log = Log::default();
+        let mut lexer = Lexer::new(&log, &source);
+        lexer.json.parse = json;
+        lexer.next();
+
+        let mut tokens = 0;
+        while lexer.token != Token::EndOfFile && lexer.token != Token::SyntaxError {
+            assert!(tokens <= source.contents.len(), "the lexer is stuck");
+            tokens += 1;
+            lexer.next();
+        }
+    }
+});
@@ -0,0 +1,256 @@
+// This reports how every symbol in a parsed file is used, for tools such as
+// linters and codemods that need scope information but don't want to redo
+// the work of the parser. Identifiers have already been bound to symbols by
+// the parser, so this is only a walk over the tree that collects the
+// locations where each symbol appears.
+
+use crate::ast::{
+    Binding, BindingKind, Class, Expr, ExprKind, Function, Location, LocationRef, NamespaceSymbol,
+    Property, Reference, Stmt, StmtKind, SymbolKind, SymbolMap, AST, INVALID_REF,
+};
+use crate::visit::Visit;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub struct SymbolUsage {
+    pub reference: Reference,
+    pub name: String,
+    pub kind: SymbolKind,
+
+    // A symbol can be declared more than once, as in "var a; var a". This is
+    // empty for symbols that aren't declared in the file, such as globals.
+    pub declarations: Vec<Location>,
+
+    // Every location that reads or writes the symbol, in source order
+    pub references: Vec<Location>,
+
+    // The symbol is exported from the file using an ES6 export
+    pub is_exported: bool,
+
+    // The symbol is used from inside a function (or an arrow function or a
+    // class field initializer) other than the one that declares it, so the
+    // function holds on to it after the declaring function has returned
+    pub is_captured_by_closure: bool,
+}
+
+// Returns the usage of every symbol that appears in the tree, ordered by
+// where the symbol first appears. Symbols that were generated by the parser
+// and never appear in the tree are left out.
+pub fn analyze_symbols(ast: &AST) -> Vec<SymbolUsage> {
+    let mut analyzer = UsageAnalyzer {
+        symbols: &ast.symbols,
+        function_id: 0,
+        function_count: 0,
+        declarations: HashMap::new(),
+        references: HashMap::new(),
+    };
+    for part in &ast.parts {
+        analyzer.visit_stmts(&part.stmts);
+    }
+
+    let exported: HashSet<Reference> = ast
+        .named_exports
+        .values()
+        .map(|reference| follow(&ast.symbols, *reference))
+        .collect();
+    let mut found: HashSet<Reference> = analyzer.declarations.keys().copied().collect();
+    found.extend(analyzer.references.keys().copied());
+
+    let mut result: Vec<SymbolUsage> = found
+        .into_iter()
+        .map(|reference| {
+            let symbol = &ast.symbols[reference];
+            let declarations = analyzer.declarations.remove(&reference).unwrap_or_default();
+            let uses = analyzer.references.remove(&reference).unwrap_or_default();
+
+            // Globals aren't declared by any function, so they can't be captured
+            let is_captured_by_closure = declarations.first().is_some_and(|(_, declared_in)| {
+                uses.iter().any(|(_, used_in)| used_in != declared_in)
+            });
+
+            let mut references: Vec<Location> = uses.into_iter().map(|(loc, _)| loc).collect();
+            references.sort_unstable();
+            let mut declarations: Vec<Location> =
+                declarations.into_iter().map(|(loc, _)| loc).collect();
+            declarations.sort_unstable();
+
+            SymbolUsage {
+                reference,
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                declarations,
+                references,
+                is_exported: exported.contains(&reference),
+                is_captured_by_closure,
+            }
+        })
+        .collect();
+    result.sort_by_key(|usage| {
+        let first_declaration = usage.declarations.first().copied();
+        let first_reference = usage.references.first().copied();
+        (
+            first_declaration.into_iter().chain(first_reference).min(),
+            usage.reference.outer,
+            usage.reference.inner,
+        )
+    });
+    result
+}
+
+// Symbols that were merged by the parser (e.g. a "var" that is hoisted into a
+// catch clause's variable) are reported as the symbol they were merged into
+fn follow(symbols: &SymbolMap, mut reference: Reference) -> Reference {
+    while symbols[reference].link != INVALID_REF {
+        reference = symbols[reference].link;
+    }
+    reference
+}
+
+struct UsageAnalyzer<'a> {
+    symbols: &'a SymbolMap,
+
+    // Every function body gets its own id. Comparing the id of the function
+    // that declares a symbol with the id of the function that uses it tells
+    // whether the use is from a closure.
+    function_id: usize,
+    function_count: usize,
+
+    // Each location is stored with the id of the function it's in
+    declarations: HashMap<Reference, Vec<(Location, usize)>>,
+    references: HashMap<Reference, Vec<(Location, usize)>>,
+}
+
+impl<'a> UsageAnalyzer<'a> {
+    fn declare(&mut self, reference: Reference, location: Location) {
+        let reference = follow(self.symbols, reference);
+        self.declarations
+            .entry(reference)
+            .or_default()
+            .push((location, self.function_id));
+    }
+
+    fn record_use(&mut self, reference: Reference, location: Location) {
+        let reference = follow(self.symbols, reference);
+        self.references
+            .entry(reference)
+            .or_default()
+            .push((location, self.function_id));
+    }
+
+    fn declare_name(&mut self, name: &Option<LocationRef>) {
+        if let Some(name) = name {
+            self.declare(name.reference, name.loc);
+        }
+    }
+
+    // Runs "visit" as if it was inside a new function
+    fn visit_closure<V: FnOnce(&mut Self)>(&mut self, visit: V) {
+        let parent = self.function_id;
+        self.function_count += 1;
+        self.function_id = self.function_count;
+        visit(self);
+        self.function_id = parent;
+    }
+}
+
+impl<'a> Visit for UsageAnalyzer<'a> {
+    fn visit_binding(&mut self, binding: &Binding) {
+        match binding.data.as_ref() {
+            BindingKind::Identifier { reference } => self.declare(*reference, binding.location),
+            _ => self.walk_binding(binding),
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt.data.as_ref() {
+            StmtKind::Import {
+                namespace_symbol,
+                default_name,
+                ..
+            } => {
+                self.declare_name(default_name);
+                match namespace_symbol {
+                    NamespaceSymbol::Clause { items } => {
+                        for item in items {
+                            self.declare(item.name.reference, item.name.loc);
+                        }
+                    }
+                    NamespaceSymbol::Star {
+                        location,
+                        namespace_ref,
+                    } => self.declare(*namespace_ref, *location),
+                }
+            }
+            StmtKind::ExportClause { items } => {
+                for item in items {
+                    self.record_use(item.name.reference, item.name.loc);
+                }
+            }
+            StmtKind::Namespace { name, .. } | StmtKind::Label { name, .. } => {
+                self.declare(name.reference, name.loc)
+            }
+            StmtKind::Break { name: Some(name) } | StmtKind::Continue { name: Some(name) } => {
+                self.record_use(name.reference, name.loc)
+            }
+            StmtKind::Enum { name, values, .. } => {
+                self.declare(name.reference, name.loc);
+                for value in values {
+                    self.declare(value.reference, value.location);
+                }
+            }
+
+            // The name of a function statement is declared in the enclosing
+            // function, unlike the name of a function expression
+            StmtKind::Function { function, .. } => self.declare_name(&function.name),
+            _ => {}
+        }
+        self.walk_stmt(stmt);
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        self.visit_closure(|analyzer| analyzer.walk_function(function));
+    }
+
+    fn visit_class(&mut self, class: &Class) {
+        self.declare_name(&class.name);
+        self.walk_class(class);
+    }
+
+    fn visit_property(&mut self, property: &Property, is_class: bool) {
+        match property.key.data.as_ref() {
+            ExprKind::PrivateIdentifier { reference } if is_class => {
+                self.declare(*reference, property.key.location)
+            }
+            _ => self.visit_expr(&property.key),
+        }
+        if let Some(value) = &property.value {
+            self.visit_expr(value);
+        }
+
+        // Class fields are initialized by the constructor, not where the
+        // class is declared
+        if let Some(initializer) = &property.initializer {
+            if is_class {
+                self.visit_closure(|analyzer| analyzer.visit_expr(initializer));
+            } else {
+                self.visit_expr(initializer);
+            }
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr.data.as_ref() {
+            ExprKind::Identifier { reference }
+            | ExprKind::ImportIdentifier { reference }
+            | ExprKind::PrivateIdentifier { reference } => {
+                self.record_use(*reference, expr.location)
+            }
+            ExprKind::Arrow { .. } => self.visit_closure(|analyzer| analyzer.walk_expr(expr)),
+            ExprKind::Function { function } => self.visit_closure(|analyzer| {
+                analyzer.declare_name(&function.name);
+                analyzer.walk_function(function);
+            }),
+            _ => self.walk_expr(expr),
+        }
+    }
+}
@@ -0,0 +1,335 @@
+// This is the API for building from code instead of from the command line. A
+// one-shot "build" does everything from scratch, while a "BuildContext" keeps
+// what it learned around for the next build:
+//
+//   - The resolver remembers the contents of every directory it has listed
+//   - Every parsed tree is kept and is reused if its file didn't change
+//   - The module graph of the last build tells "watch" which files to watch,
+//     and which files a change affects (see "BuildContext::subscribe")
+//
+// A rebuild after a small change then only reads the files of the graph again
+// and parses the ones that actually changed.
+
+use crate::bundler::{
+    self, AstCache, Bundle, BundleOptions, EntryPointExports, InvalidationEvent, ModuleGraph,
+    OutputFile,
+};
+use crate::fs::{expand_glob, is_glob, FileSystem, RealFileSystem};
+use crate::logging::{Log, LogLevel, Msg, MsgId, MsgKind, Source};
+use crate::parser::ParseOptions;
+use crate::paths;
+use crate::resolver::{ResolveOptions, Resolver};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::mpsc::Receiver;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    // Relative paths are relative to the current directory. These can be glob
+    // patterns, which are expanded by "fs::expand_glob".
+    pub entry_paths: Vec<String>,
+    pub parse_options: ParseOptions,
+    pub resolve_options: ResolveOptions,
+    pub bundle_options: BundleOptions,
+
+    // Writes the output files to disk. They are returned either way.
+    pub write: bool,
+
+    // Changes how the warnings with these names are reported
+    pub log_overrides: HashMap<MsgId, LogLevel>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BuildResult {
+    // These are empty if there were any errors
+    pub output_files: Vec<OutputFile>,
+    pub entry_point_exports: Vec<EntryPointExports>,
+
+    // The JSON description of the build (see "Bundle::metafile"), if the
+    // options ask for one and there were no errors
+    pub metafile: Option<String>,
+
+    pub errors: Vec<Msg>,
+    pub warnings: Vec<Msg>,
+}
+
+pub fn build(options: BuildOptions) -> BuildResult {
+    BuildContext::new(options).rebuild()
+}
+
+pub struct BuildContext<F: FileSystem = RealFileSystem> {
+    options: BuildOptions,
+    resolver: Resolver<F>,
+    asts: Mutex<AstCache>,
+
+    // The files found by the last build, and how they import each other
+    previous: Option<Bundle>,
+    graph: ModuleGraph,
+    is_disposed: bool,
+
+    // When each file of the last build was last changed, as of the first
+    // check for changes after it (see "rebuild_if_changed")
+    stamps: Option<WatchStamps>,
+}
+
+impl BuildContext<RealFileSystem> {
+    pub fn new(options: BuildOptions) -> Self {
+        Self::with_fs(RealFileSystem::new(), options)
+    }
+
+    // Builds, and then builds again every time one of the files of the last
+    // build changes, until "on_rebuild" returns false. Changes are found by
+    // checking the modification time of every file in the module graph and of
+    // the directories they are in, once every "interval".
+    pub fn watch<C>(&mut self, interval: Duration, mut on_rebuild: C)
+    where
+        C: FnMut(&BuildResult) -> bool,
+    {
+        let mut result = self.rebuild();
+        loop {
+            if !on_rebuild(&result) {
+                return;
+            }
+            result = loop {
+                if let Some(result) = self.rebuild_if_changed() {
+                    break result;
+                }
+                std::thread::sleep(interval);
+            };
+        }
+    }
+
+    // Rebuilds if one of the files of the last build changed since the last
+    // call, which is what "watch" does every interval. The first call after a
+    // build only notes when each file was changed, and so does a call before
+    // any build at all. Returns None if there wasn't a rebuild.
+    pub fn rebuild_if_changed(&mut self) -> Option<BuildResult> {
+        let current = self.watched_stamps();
+        let stamps = self.stamps.replace(current)?;
+        let current = self.stamps.as_ref().unwrap();
+        if *current == stamps {
+            return None;
+        }
+
+        // The subscribers hear about the change before the rebuild, from the
+        // graph of the build that the change made stale
+        let changed: Vec<String> = current
+            .files
+            .iter()
+            .filter(|(path, stamp)| stamps.files.get(*path) != Some(stamp))
+            .map(|(path, _)| path.clone())
+            .collect();
+        self.graph.invalidate(&changed);
+
+        // Adding or removing a file changes its directory, and then what the
+        // resolver remembers about the directory is stale
+        if current.dirs != stamps.dirs {
+            self.resolver.clear_cache();
+        }
+        Some(self.rebuild())
+    }
+
+    fn watched_stamps(&self) -> WatchStamps {
+        let mut stamps = WatchStamps::default();
+        let bundle = match &self.previous {
+            Some(bundle) => bundle,
+            None => return stamps,
+        };
+
+        // The runtime and disabled files have no file on disk
+        for source in &bundle.sources {
+            if !paths::is_absolute(&source.absolute_path) {
+                continue;
+            }
+            let dir = paths::dir(&source.absolute_path).to_owned();
+            stamps.files.insert(
+                source.absolute_path.clone(),
+                modified(&source.absolute_path),
+            );
+            stamps
+                .dirs
+                .entry(dir)
+                .or_insert_with_key(|dir| modified(dir));
+        }
+        stamps
+    }
+}
+
+impl<F: FileSystem> BuildContext<F> {
+    pub fn with_fs(fs: F, options: BuildOptions) -> Self {
+        let resolver = Resolver::new(fs, options.resolve_options.clone());
+        Self {
+            options,
+            resolver,
+            asts: Mutex::new(AstCache::default()),
+            previous: None,
+            graph: ModuleGraph::default(),
+            is_disposed: false,
+            stamps: None,
+        }
+    }
+
+    // The module graph of the last build, which is empty before the first one
+    pub fn module_graph(&self) -> &ModuleGraph {
+        &self.graph
+    }
+
+    // Every time "watch" or "rebuild_if_changed" finds changed files, the
+    // receiver gets the changed files of the last build and every file that
+    // imports them. This stays subscribed across rebuilds.
+    pub fn subscribe(&mut self) -> Receiver<InvalidationEvent> {
+        self.graph.subscribe()
+    }
+
+    pub fn rebuild(&mut self) -> BuildResult {
+        let log = Log::with_overrides(self.options.log_overrides.clone());
+        if self.is_disposed {
+            log.add_error(
+                &Source::default(),
+                0,
+                "Cannot rebuild after the build context has been disposed".to_owned(),
+            );
+            return build_result(log.take_msgs(), Default::default());
+        }
+
+        // Invalid combinations of options would otherwise be ignored or make
+        // output that doesn't work, such as CommonJS chunks
+        if let Err(err) = self.options.bundle_options.validate() {
+            log.add_error(&Source::default(), 0, err.to_string());
+            return build_result(log.take_msgs(), Default::default());
+        }
+
+        // Patterns are expanded again for every build since files may have
+        // been added or removed in the meantime
+        let fs = self.resolver.fs();
+        let mut entry_paths = vec![];
+        for path in &self.options.entry_paths {
+            let path = match fs.abs(path) {
+                Some(path) => paths::to_slash(&path.to_string_lossy()),
+                None => path.clone(),
+            };
+            if !is_glob(&path) {
+                entry_paths.push(path);
+                continue;
+            }
+            let matches = expand_glob(fs, &path);
+            if matches.is_empty() {
+                log.add_error(
+                    &Source::default(),
+                    0,
+                    format!("No files match the pattern: {}", path),
+                );
+            }
+            entry_paths.extend(matches);
+        }
+
+        // Each stage only runs if the one before it had no errors
+        let bundle = bundler::scan_bundle(
+            &log,
+            &self.resolver,
+            &entry_paths,
+            &self.options.parse_options,
+            Some(&self.asts),
+        );
+        self.graph.replace_modules(bundle.module_graph());
+        let mut msgs = log.take_msgs();
+        let mut compiled = bundler::BuildResult::default();
+        let mut metafile = None;
+        if !has_errors(&msgs) {
+            compiled = bundle.compile(&log, &self.options.bundle_options);
+            self.graph.set_output_files(&compiled.output_files);
+            msgs.extend(log.take_msgs());
+            if self.options.write && !has_errors(&msgs) {
+                write_output_files(&log, &compiled.output_files);
+                msgs.extend(log.take_msgs());
+            }
+            if self.options.bundle_options.metafile {
+                let cwd = fs
+                    .abs(".")
+                    .map(|cwd| paths::to_slash(&cwd.to_string_lossy()))
+                    .unwrap_or_default();
+                metafile = Some(bundle.metafile(&compiled, &cwd));
+            }
+        }
+
+        // Files that are no longer part of the build don't need their trees
+        let files: HashSet<&str> = bundle
+            .sources
+            .iter()
+            .map(|source| source.absolute_path.as_str())
+            .collect();
+        self.asts
+            .lock()
+            .unwrap()
+            .retain(|path| files.contains(path));
+        self.previous = Some(bundle);
+        self.stamps = None;
+        let mut result = build_result(msgs, compiled);
+        if result.errors.is_empty() {
+            result.metafile = metafile;
+        }
+        result
+    }
+
+    // Drops everything the context remembers. Rebuilding afterward is an
+    // error.
+    pub fn dispose(&mut self) {
+        self.is_disposed = true;
+        self.previous = None;
+        self.graph = ModuleGraph::default();
+        self.stamps = None;
+        self.asts = Mutex::new(AstCache::default());
+        self.resolver.clear_cache();
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct WatchStamps {
+    files: BTreeMap<String, Option<SystemTime>>,
+    dirs: BTreeMap<String, Option<SystemTime>>,
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// An output file without a path goes to stdout on the command line, which
+// doesn't apply here
+fn write_output_files(log: &Log, output_files: &[OutputFile]) {
+    for file in output_files {
+        if file.path.is_empty() {
+            continue;
+        }
+        let written = std::fs::create_dir_all(paths::dir(&file.path))
+            .and_then(|_| std::fs::write(&file.path, &file.contents));
+        if let Err(err) = written {
+            log.add_error(
+                &Source::default(),
+                0,
+                format!("Failed to write to {} ({})", file.path, err),
+            );
+        }
+    }
+}
+
+fn has_errors(msgs: &[Msg]) -> bool {
+    msgs.iter().any(|msg| msg.kind == MsgKind::Error)
+}
+
+fn build_result(msgs: Vec<Msg>, compiled: bundler::BuildResult) -> BuildResult {
+    let mut result = BuildResult::default();
+    for msg in msgs {
+        match msg.kind {
+            MsgKind::Error => result.errors.push(msg),
+            MsgKind::Warning => result.warnings.push(msg),
+        }
+    }
+    if result.errors.is_empty() {
+        result.output_files = compiled.output_files;
+        result.entry_point_exports = compiled.entry_point_exports;
+    }
+    result
+}
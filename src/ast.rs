@@ -13,13 +13,52 @@
 // has been parsed should create a copy of the mutated parts of the tree
 // instead of mutating the original tree.
 
+use crate::lexer::is_identifier;
+use crate::stack::ensure_sufficient_stack;
+use crate::strings::string_to_utf16;
+use rayon::prelude::*;
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::{Index, IndexMut};
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Bindings, expressions, and statements nest as deeply as the code does, and
+// cloning or serializing them recurses once per level. These are written out
+// instead of derived so that they can grow the stack like the passes over the
+// tree do. They're serialized the same way a derived struct would be.
+macro_rules! node_impls {
+    ($node:ident, $kind:ident) => {
+        impl Clone for $node {
+            fn clone(&self) -> Self {
+                ensure_sufficient_stack(|| $node {
+                    location: self.location,
+                    data: self.data.clone(),
+                })
+            }
+        }
+
+        impl Serialize for $node {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                ensure_sufficient_stack(|| (self.location, &self.data).serialize(serializer))
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $node {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                ensure_sufficient_stack(|| {
+                    let (location, data) = Deserialize::deserialize(deserializer)?;
+                    Ok($node { location, data })
+                })
+            }
+        }
+    };
+}
+
 // https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Operators/Operator_Precedence
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum Operator {
     Lowest = 0,
     Comma,
@@ -45,7 +84,7 @@ pub enum Operator {
     Call,
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum OperatorCode {
     // If you add a new token, remember to add it to "OpTable" too
 
@@ -134,6 +173,10 @@ impl OperatorCode {
     pub fn is_binary_assign(self) -> bool {
         self >= OperatorCode::BinOpAssign
     }
+
+    pub fn entry(self) -> &'static OperatorTableEntry {
+        &OPERATOR_TABLE[self as usize]
+    }
 }
 
 pub struct OperatorTableEntry {
@@ -215,13 +258,13 @@ pub type Location = usize;
 
 // use std::ops::Range
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LocationRef {
     pub loc: Location,
     pub reference: Reference,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Path {
     pub loc: Location,
     pub text: String,
@@ -238,15 +281,20 @@ pub struct Path {
 // all code that references this index can be discovered easily.
 pub const RUNTIME_SOURCE_INDEX: usize = 0;
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum PropertyKind {
     PropertyNormal = 0,
     PropertyGet,
     PropertySet,
     PropertySpread,
+
+    // "static { ... }" in a class body. The key is missing and the value is a
+    // function without arguments holding the statements, which is how the
+    // block behaves: it has its own scope and "this" is the class.
+    PropertyStaticBlock,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Property {
     pub kind: PropertyKind,
     pub is_computed: bool,
@@ -254,6 +302,11 @@ pub struct Property {
     pub is_static: bool,
     pub key: Expr,
 
+    // This is set for string keys that were written with quotes, as in
+    // "{'a': 1}". The printer keeps the quotes unless the minifier removed
+    // this flag.
+    pub prefer_quoted_key: bool,
+
     // This is omitted for class fields
     pub value: Option<Expr>,
 
@@ -266,10 +319,10 @@ pub struct Property {
     //
     //   class Foo { a = 1 }
     //
-    initializer: Option<Expr>,
+    pub initializer: Option<Expr>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PropertyBinding {
     pub is_computed: bool,
     pub is_spread: bool,
@@ -278,7 +331,7 @@ pub struct PropertyBinding {
     pub default_value: Option<Expr>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Arg {
     // "constructor(public x: boolean) {}"
     pub is_typescript_ctor_field: bool,
@@ -286,7 +339,7 @@ pub struct Arg {
     pub default_: Option<Expr>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Function {
     pub name: Option<LocationRef>,
     pub args: Vec<Arg>,
@@ -296,32 +349,34 @@ pub struct Function {
     pub body: FunctionBody,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionBody {
     pub location: Location,
     pub stmts: Vec<Stmt>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Class {
-    pub name: LocationRef,
-    pub extends: Expr,
+    pub name: Option<LocationRef>,
+    pub extends: Option<Expr>,
     pub properties: Vec<Property>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArrayBinding {
     pub binding: Binding,
     pub default_value: Option<Expr>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Binding {
     pub location: Location,
     pub data: Box<BindingKind>,
 }
 
-#[derive(Debug, Clone)]
+node_impls!(Binding, BindingKind);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BindingKind {
     Missing,
     Identifier {
@@ -336,13 +391,35 @@ pub enum BindingKind {
     },
 }
 
-#[derive(Debug, Clone)]
+impl Binding {
+    pub fn identifier(location: Location, reference: Reference) -> Binding {
+        Binding {
+            location,
+            data: Box::new(BindingKind::Identifier { reference }),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct Expr {
     pub location: Location,
     pub data: Box<ExprKind>,
 }
 
-#[derive(Debug, Clone)]
+node_impls!(Expr, ExprKind);
+
+// Chains like "a.b.c" or "a + b + c" are as deep as they are long, and the
+// parser doesn't limit how long they can be since it loops over them. Each
+// expression is dropped on a stack that can grow, like the passes over the
+// tree run, so that dropping a long chain doesn't overflow.
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let data = std::mem::replace(self.data.as_mut(), ExprKind::Missing);
+        ensure_sufficient_stack(|| drop(data));
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ExprKind {
     Array {
         items: Vec<Expr>,
@@ -366,15 +443,23 @@ pub enum ExprKind {
     New {
         target: Expr,
         args: Vec<Expr>,
+
+        // See the comment on "Call"
+        can_be_unwrapped_if_unused: bool,
     },
     NewTarget,
     ImportMeta,
     Call {
         target: Expr,
         args: Vec<Expr>,
-        is_optional_chain: bool,
+        optional_chain: OptionalChain,
         is_parenthesized: bool,
         is_direct_eval: bool,
+
+        // True if there was a "/* @__PURE__ */" comment before the call. The
+        // call can then be removed if its result isn't used, although the
+        // arguments still have to be evaluated.
+        can_be_unwrapped_if_unused: bool,
     },
     RuntimeCall {
         sym: u16, // TODO: fix me --> runtime.Sym
@@ -384,24 +469,26 @@ pub enum ExprKind {
         target: Expr,
         name: String,
         name_location: Location,
-        is_optional_chain: bool,
+        optional_chain: OptionalChain,
         is_parenthesized: bool,
     },
     Index {
         target: Expr,
         index: Expr,
-        is_optional_chain: bool,
+        optional_chain: OptionalChain,
         is_parenthesized: bool,
     },
     Arrow {
         is_async: bool,
-        args: Vec<Expr>,
+        args: Vec<Arg>,
         has_rest_arg: bool,
         is_parenthesized: bool,
         prefer_expr: bool,
         body: FunctionBody,
     },
-    Function {},
+    Function {
+        function: Function,
+    },
     Class {
         class: Class,
     },
@@ -409,6 +496,13 @@ pub enum ExprKind {
         reference: Reference,
     },
 
+    // A name such as "#a" that is only visible inside the class body that
+    // declares it. It's the key of a class member, the index of an "Index"
+    // for "a.#b", or the left side of "#a in b".
+    PrivateIdentifier {
+        reference: Reference,
+    },
+
     // This is similar to an Identifier but it represents a reference to an ES6
     // import item.
     //
@@ -431,7 +525,14 @@ pub enum ExprKind {
     ImportIdentifier {
         reference: Reference,
     },
-    JSXElement {},
+
+    // A fragment has no tag. Elements only exist between the two passes of
+    // the parser, which turns them into calls (see "JSXOptions").
+    JSXElement {
+        tag: Option<Expr>,
+        properties: Vec<Property>,
+        children: Vec<Expr>,
+    },
     Missing,
     Number {
         value: f64,
@@ -478,7 +579,7 @@ pub enum ExprKind {
     },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TemplatePart {
     pub value: Expr,
     pub tail: Vec<u16>,
@@ -497,26 +598,153 @@ pub fn join_with_comma(a: Expr, b: Expr) -> Expr {
 }
 
 pub fn join_all_with_comma<I: Iterator<Item = Expr>>(mut all: I) -> Option<Expr> {
-    if let Some(first) = all.next() {
-        Some(all.fold(first, join_with_comma))
-    } else {
-        None
+    all.next().map(|first| all.fold(first, join_with_comma))
+}
+
+// These build trees in code instead of parsing them. The ones that take
+// operators or names check them the way the parser would and return "None"
+// for something the parser could never have produced, so that anything built
+// with them can be printed and printed code parses back to the same tree.
+impl Expr {
+    pub fn new(location: Location, data: ExprKind) -> Expr {
+        Expr {
+            location,
+            data: Box::new(data),
+        }
+    }
+
+    // Moves the data out for code that rebuilds the expression from its parts
+    pub fn into_data(mut self) -> ExprKind {
+        std::mem::replace(&mut self.data, ExprKind::Missing)
+    }
+
+    pub fn identifier(location: Location, reference: Reference) -> Expr {
+        Expr::new(location, ExprKind::Identifier { reference })
+    }
+
+    pub fn string(location: Location, text: &str) -> Expr {
+        Expr::new(
+            location,
+            ExprKind::String {
+                value: string_to_utf16(text),
+            },
+        )
+    }
+
+    pub fn number(location: Location, value: f64) -> Expr {
+        Expr::new(location, ExprKind::Number { value })
+    }
+
+    // Prefix operators and updates like "x++". Updates need something that
+    // can be assigned to.
+    pub fn unary(op_code: OperatorCode, value: Expr) -> Option<Expr> {
+        if op_code >= OperatorCode::BinOpAdd
+            || (op_code.is_unary_update() && !value.is_assign_target(false))
+        {
+            return None;
+        }
+        Some(Expr::new(
+            value.location,
+            ExprKind::Unary { op_code, value },
+        ))
+    }
+
+    // Assignments need something on the left that can be assigned to. Only
+    // "=" can destructure an array or object.
+    pub fn binary(op_code: OperatorCode, left: Expr, right: Expr) -> Option<Expr> {
+        if op_code < OperatorCode::BinOpAdd
+            || (op_code.is_binary_assign()
+                && !left.is_assign_target(op_code == OperatorCode::BinOpAssign))
+        {
+            return None;
+        }
+        Some(Expr::new(
+            left.location,
+            ExprKind::Binary {
+                op_code,
+                left,
+                right,
+            },
+        ))
+    }
+
+    // "None" if there are no expressions
+    pub fn comma<I: IntoIterator<Item = Expr>>(all: I) -> Option<Expr> {
+        join_all_with_comma(all.into_iter())
+    }
+
+    // "target.name", where the name must be an identifier. Keywords are
+    // allowed, so "a.if" is fine.
+    pub fn dot(target: Expr, name: &str) -> Option<Expr> {
+        if !is_identifier(name) {
+            return None;
+        }
+        let location = target.location;
+        Some(Expr::new(
+            location,
+            ExprKind::Dot {
+                target,
+                name: name.to_owned(),
+                name_location: location,
+                optional_chain: OptionalChain::None,
+                is_parenthesized: false,
+            },
+        ))
+    }
+
+    pub fn index(target: Expr, index: Expr) -> Expr {
+        Expr::new(
+            target.location,
+            ExprKind::Index {
+                target,
+                index,
+                optional_chain: OptionalChain::None,
+                is_parenthesized: false,
+            },
+        )
+    }
+
+    pub fn call(target: Expr, args: Vec<Expr>) -> Expr {
+        Expr::new(
+            target.location,
+            ExprKind::Call {
+                target,
+                args,
+                optional_chain: OptionalChain::None,
+                is_parenthesized: false,
+                is_direct_eval: false,
+                can_be_unwrapped_if_unused: false,
+            },
+        )
+    }
+
+    fn is_assign_target(&self, can_destructure: bool) -> bool {
+        match self.data.as_ref() {
+            ExprKind::Identifier { .. } => true,
+            ExprKind::Dot { optional_chain, .. } | ExprKind::Index { optional_chain, .. } => {
+                *optional_chain == OptionalChain::None
+            }
+            ExprKind::Array { .. } | ExprKind::Object { .. } => can_destructure,
+            _ => false,
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ExprOrStmt {
     Expr(Expr),
-    Stmt,
+    Stmt(Stmt),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Stmt {
     pub location: Location,
     pub data: Box<StmtKind>,
 }
 
-#[derive(Debug, Clone)]
+node_impls!(Stmt, StmtKind);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum StmtKind {
     Block {
         stmts: Vec<Stmt>,
@@ -524,9 +752,22 @@ pub enum StmtKind {
     Empty,
     TypeScript,
     Debugger,
+
+    // Stands in for a statement that failed to parse. The parser skips ahead
+    // to the end of the statement and keeps going so that later errors are
+    // reported too, but a file with one of these never gets past parsing.
+    SyntaxError,
     Directive {
         value: Vec<u16>,
     },
+
+    // A comment that's kept in the output like "/*! license */". A trailing
+    // comment was on the same line as the end of the statement before it and
+    // is printed there too.
+    Comment {
+        text: String,
+        is_trailing: bool,
+    },
     ExportClause {
         items: Vec<ClauseItem>,
     },
@@ -653,7 +894,8 @@ pub enum StmtKind {
         kind: LocalKind,
         is_export: bool,
         // The TypeScript compiler doesn't generate code for "import foo = bar"
-        // statements inside namespaces where the import is never used.
+        // statements where the import is never used. This started out being
+        // about namespaces but holds at the top level too.
         was_ts_import_equals_in_namespace: bool,
     },
     Break {
@@ -664,14 +906,29 @@ pub enum StmtKind {
     },
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+// Where a property access or call is in an optional chain. The chain ends at
+// the first link that isn't part of it, which is how "(a?.b).c" differs from
+// "a?.b.c": the ".c" is "None" in the first and "Continue" in the second.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum OptionalChain {
+    // "a.b"
+    None,
+
+    // "a?.b"
+    Start,
+
+    // "a?.b.c" => ".c" is "Continue"
+    Continue,
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum LocalKind {
     Var = 0,
     Let,
     Const,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NamespaceSymbol {
     Clause {
         items: Vec<ClauseItem>,
@@ -682,26 +939,26 @@ pub enum NamespaceSymbol {
     },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Catch {
     pub location: Location,
     pub binding: Option<Binding>,
     pub body: Vec<Stmt>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Finally {
     pub location: Location,
     pub stmts: Vec<Stmt>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Case {
     pub value: Option<Expr>,
     pub body: Vec<Stmt>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnumValue {
     pub location: Location,
     pub reference: Reference,
@@ -720,7 +977,205 @@ pub struct EnumValue {
 // 	return false
 // }
 
+impl Expr {
+    pub fn is_primitive_literal(&self) -> bool {
+        matches!(
+            self.data.as_ref(),
+            ExprKind::Null
+                | ExprKind::Undefined
+                | ExprKind::Boolean { .. }
+                | ExprKind::Number { .. }
+                | ExprKind::BigInt { .. }
+                | ExprKind::String { .. }
+        )
+    }
+
+    // Whether evaluating the expression could do something observable. This
+    // doesn't know what identifiers refer to, so reading one counts as a side
+    // effect since reading a global that doesn't exist throws. The answer is
+    // conservative: "true" only means that it isn't known to be free.
+    pub fn has_side_effects(&self) -> bool {
+        !can_be_removed(self, None)
+    }
+
+    // Whether the expression can be left out if its value isn't used. This is
+    // like "has_side_effects" except that identifiers that are declared in the
+    // file can be read without side effects.
+    pub fn can_be_removed_if_unused(&self, symbols: &SymbolMap) -> bool {
+        can_be_removed(self, Some(symbols))
+    }
+}
+
+impl Class {
+    pub fn can_be_removed_if_unused(&self, symbols: &SymbolMap) -> bool {
+        class_can_be_removed(self, Some(symbols))
+    }
+}
+
+fn can_be_removed(expr: &Expr, symbols: Option<&SymbolMap>) -> bool {
+    ensure_sufficient_stack(|| {
+        match expr.data.as_ref() {
+            ExprKind::Null
+            | ExprKind::Undefined
+            | ExprKind::Boolean { .. }
+            | ExprKind::Number { .. }
+            | ExprKind::BigInt { .. }
+            | ExprKind::String { .. }
+            | ExprKind::RegExp { .. }
+            | ExprKind::This
+            | ExprKind::Missing
+            | ExprKind::Arrow { .. }
+            | ExprKind::Function { .. }
+            | ExprKind::ImportIdentifier { .. } => true,
+
+            // Reading a global that doesn't exist throws
+            ExprKind::Identifier { reference } => {
+                symbols.is_some_and(|symbols| symbols[*reference].kind != SymbolKind::Unbound)
+            }
+
+            ExprKind::Class { class } => class_can_be_removed(class, symbols),
+            ExprKind::Array { items } => items.iter().all(|item| can_be_removed(item, symbols)),
+            ExprKind::Object { properties } => properties.iter().all(|property| {
+                property.kind != PropertyKind::PropertySpread
+                    && (!property.is_computed || property.key.is_primitive_literal())
+                    && match &property.value {
+                        Some(value) => can_be_removed(value, symbols),
+                        None => true,
+                    }
+            }),
+            ExprKind::Template { tag, parts, .. } => {
+                matches!(tag.data.as_ref(), ExprKind::Missing)
+                    && parts
+                        .iter()
+                        .all(|part| can_be_removed(&part.value, symbols))
+            }
+            ExprKind::If { test, yes, no } => {
+                can_be_removed(test, symbols)
+                    && can_be_removed(yes, symbols)
+                    && can_be_removed(no, symbols)
+            }
+
+            // "typeof" doesn't throw for a global that doesn't exist. The other
+            // operators that convert their operand to a number or a string could
+            // call "valueOf" or "toString" unless it's a primitive, and "+"
+            // throws for a BigInt.
+            ExprKind::Unary { op_code, value } => match op_code {
+                OperatorCode::UnOpTypeof
+                    if matches!(value.data.as_ref(), ExprKind::Identifier { .. }) =>
+                {
+                    true
+                }
+                OperatorCode::UnOpNot | OperatorCode::UnOpVoid | OperatorCode::UnOpTypeof => {
+                    can_be_removed(value, symbols)
+                }
+                OperatorCode::UnOpNeg | OperatorCode::UnOpCpl => value.is_primitive_literal(),
+                OperatorCode::UnOpPos => {
+                    value.is_primitive_literal()
+                        && !matches!(value.data.as_ref(), ExprKind::BigInt { .. })
+                }
+                _ => false,
+            },
+
+            // These operators never convert their operands
+            ExprKind::Binary {
+                op_code:
+                    OperatorCode::BinOpStrictEq
+                    | OperatorCode::BinOpStrictNe
+                    | OperatorCode::BinOpComma
+                    | OperatorCode::BinOpLogicalOr
+                    | OperatorCode::BinOpLogicalAnd
+                    | OperatorCode::BinOpNullishCoalescing,
+                left,
+                right,
+            } => can_be_removed(left, symbols) && can_be_removed(right, symbols),
+
+            // A call marked with a "__PURE__" comment can be removed, but the
+            // arguments still have to be evaluated
+            ExprKind::Call {
+                args,
+                can_be_unwrapped_if_unused: true,
+                ..
+            }
+            | ExprKind::New {
+                args,
+                can_be_unwrapped_if_unused: true,
+                ..
+            } => args.iter().all(|arg| can_be_removed(arg, symbols)),
+
+            // Spread elements iterate over their value
+            _ => false,
+        }
+    })
+}
+
+fn class_can_be_removed(class: &Class, symbols: Option<&SymbolMap>) -> bool {
+    // The base class is evaluated when the class is
+    if let Some(extends) = &class.extends {
+        if !can_be_removed(extends, symbols) {
+            return false;
+        }
+    }
+
+    class.properties.iter().all(|property| {
+        // Computed keys are evaluated when the class is, and so are the values
+        // of static fields and static blocks. Instance fields are only
+        // evaluated by the constructor.
+        property.kind != PropertyKind::PropertyStaticBlock
+            && (!property.is_computed || property.key.is_primitive_literal())
+            && match &property.value {
+                Some(value) if property.is_static || property.is_method => {
+                    can_be_removed(value, symbols)
+                }
+                _ => true,
+            }
+            && match &property.initializer {
+                Some(initializer) if property.is_static => can_be_removed(initializer, symbols),
+                _ => true,
+            }
+    })
+}
+
 impl Stmt {
+    pub fn new(location: Location, data: StmtKind) -> Stmt {
+        Stmt {
+            location,
+            data: Box::new(data),
+        }
+    }
+
+    pub fn block(location: Location, stmts: Vec<Stmt>) -> Stmt {
+        Stmt::new(location, StmtKind::Block { stmts })
+    }
+
+    pub fn expr(value: Expr) -> Stmt {
+        Stmt::new(value.location, StmtKind::Expr { value })
+    }
+
+    pub fn return_(location: Location, value: Option<Expr>) -> Stmt {
+        Stmt::new(location, StmtKind::Return { value })
+    }
+
+    pub fn if_(test: Expr, yes: Stmt, no: Option<Stmt>) -> Stmt {
+        Stmt::new(test.location, StmtKind::If { test, yes, no })
+    }
+
+    // "None" if there are no declarations or a "const" has no value
+    pub fn local(kind: LocalKind, decls: Vec<Decl>) -> Option<Stmt> {
+        let location = decls.first()?.binding.location;
+        if kind == LocalKind::Const && decls.iter().any(|decl| decl.value.is_none()) {
+            return None;
+        }
+        Some(Stmt::new(
+            location,
+            StmtKind::Local {
+                decls,
+                kind,
+                is_export: false,
+                was_ts_import_equals_in_namespace: false,
+            },
+        ))
+    }
+
     pub fn is_super_call(&self) -> bool {
         if let StmtKind::Expr { value } = self.data.as_ref() {
             if let ExprKind::Call { target, .. } = value.data.as_ref() {
@@ -734,27 +1189,27 @@ impl Stmt {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClauseItem {
     pub alias: String,
     pub alias_location: Location,
     pub name: LocationRef,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Decl {
     pub binding: Binding,
     pub value: Option<Expr>,
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum ImportItemStatus {
     None = 0,
     Generated,
     Missing,
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum SymbolKind {
     // An unbound symbol is one that isn't declared in the file it's referenced
     // in. For example, using "window" without declaring it will be unbound.
@@ -782,7 +1237,10 @@ pub enum SymbolKind {
     //     var e = 2
     //     print(e) // 2
     //   }
-    //   print(e) // 0 (since the hoisting stops at the catch block boundary)
+    //   print(e) // 0 (since "var e = 2" assigns to the catch variable)
+    //
+    // The "var" still declares "e" in the enclosing function like any other
+    // "var", even if nothing else does.
     //
     // However, other forms are still a syntax error:
     //
@@ -807,6 +1265,14 @@ pub enum SymbolKind {
     // the module. Presumably this is because the imports may be type-only.
     TSImport,
 
+    // Private names of class members. A getter and a setter with the same
+    // name are a pair, and any other name can only be declared once.
+    PrivateField,
+    PrivateMethod,
+    PrivateGet,
+    PrivateSet,
+    PrivateGetSetPair,
+
     // This annotates all other symbols that don't have special behavior.
     Other,
 }
@@ -815,6 +1281,17 @@ impl SymbolKind {
     pub fn is_hoisted(self) -> bool {
         self == SymbolKind::Hoisted || self == SymbolKind::HoistedFunction
     }
+
+    pub fn is_private(self) -> bool {
+        matches!(
+            self,
+            SymbolKind::PrivateField
+                | SymbolKind::PrivateMethod
+                | SymbolKind::PrivateGet
+                | SymbolKind::PrivateSet
+                | SymbolKind::PrivateGetSetPair
+        )
+    }
 }
 
 pub const INVALID_REF: Reference = Reference { outer: 0, inner: 0 };
@@ -830,7 +1307,7 @@ pub const INVALID_REF: Reference = Reference { outer: 0, inner: 0 };
 // be an array of arrays indexed first by outer index, then by inner index.
 // The maps can be merged quickly by creating a single outer array containing
 // all inner arrays from all parsed files.
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash, Serialize, Deserialize)]
 pub struct Reference {
     pub outer: usize,
     pub inner: usize,
@@ -842,13 +1319,13 @@ impl Reference {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NamespaceAlias {
     pub namespace_ref: Reference,
     pub alias: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Symbol {
     pub kind: SymbolKind,
 
@@ -894,17 +1371,31 @@ pub struct Symbol {
     // ES6 import statement. These should always be referenced by EImportIdentifier
     // instead of an EIdentifier. When this is present, the expression should
     // be printed as a property access off the namespace instead of as a bare
-    // identifier.
+    // identifier. Other symbols don't have one.
     //
     // For correctness, this must be stored on the symbol instead of indirectly
     // associated with the Ref for the symbol somehow. In ES6 "flat bundling"
     // mode, re-exported symbols are collapsed using MergeSymbols() and renamed
     // symbols from other files that end up at this symbol must be able to tell
     // if it has a namespace alias.
-    pub namespace_alias: Arc<NamespaceAlias>,
+    pub namespace_alias: Option<Arc<NamespaceAlias>>,
+}
+
+impl Symbol {
+    pub fn new<S: Into<String>>(kind: SymbolKind, name: S) -> Self {
+        Symbol {
+            kind,
+            must_not_be_renamed: false,
+            import_item_status: ImportItemStatus::None,
+            use_count_estimate: 0,
+            name: name.into(),
+            link: INVALID_REF,
+            namespace_alias: None,
+        }
+    }
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum ScopeKind {
     Block = 0,
     With,
@@ -923,11 +1414,46 @@ impl ScopeKind {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum StrictModeKind {
+    Sloppy = 0,
+    ExplicitStrict,       // There's a "use strict" directive
+    ImplicitStrictModule, // ES6 module code is always strict
+    ImplicitStrictClass,  // Class bodies are always strict
+}
+
+impl StrictModeKind {
+    pub fn is_strict(self) -> bool {
+        self != StrictModeKind::Sloppy
+    }
+}
+
+// Function declarations that are directly inside a block (as opposed to at the
+// top level of a function or module) behave differently depending on the mode:
+//
+//   if (x) { function f() {} }
+//   f()
+//
+// In strict mode "f" is scoped to the block like a "let" and the call throws.
+// In sloppy mode Annex B of the specification additionally gives "f" a "var"
+// binding in the enclosing function, which is assigned when the declaration is
+// evaluated. So the symbol is a HoistedFunction inside the block and is also
+// hoisted out of it like a "var", but only in sloppy mode.
+pub fn is_sloppy_mode_block_level_function(scope_kind: ScopeKind, strict: StrictModeKind) -> bool {
+    !strict.is_strict() && !scope_kind.stops_hoisting()
+}
+
+// Scopes refer to each other by their index in the "ScopeTree" of their file
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash, Serialize, Deserialize)]
+pub struct ScopeId(pub usize);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Scope {
     pub kind: ScopeKind,
-    pub parent: Arc<Scope>,
-    pub children: Vec<Arc<Scope>>,
+
+    // This is "None" for the module scope
+    pub parent: Option<ScopeId>,
+    pub children: Vec<ScopeId>,
     pub members: HashMap<String, Reference>,
     pub generated: Vec<Reference>,
 
@@ -938,9 +1464,151 @@ pub struct Scope {
     // inside that scope can be renamed. We conservatively assume that the
     // evaluated code might reference anything that it has access to.
     pub contains_direct_eval: bool,
+
+    // This is inherited from the parent scope unless a directive or a class
+    // body changes it
+    pub strict_mode: StrictModeKind,
+}
+
+impl Scope {
+    pub fn new(kind: ScopeKind, strict_mode: StrictModeKind) -> Self {
+        Scope {
+            kind,
+            parent: None,
+            children: vec![],
+            members: HashMap::new(),
+            generated: vec![],
+            label_ref: INVALID_REF,
+            contains_direct_eval: false,
+            strict_mode,
+        }
+    }
+}
+
+// All of the scopes of a file in the order they were created, starting with
+// the module scope. Parents and children are linked by index instead of by
+// pointer so that the parser can add to the tree and change any scope in it
+// while it's being built, and so that walking the tree doesn't recurse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScopeTree {
+    scopes: Vec<Scope>,
 }
 
-#[derive(Debug, Clone)]
+impl ScopeTree {
+    pub const ROOT: ScopeId = ScopeId(0);
+
+    pub fn new(root: Scope) -> Self {
+        ScopeTree { scopes: vec![root] }
+    }
+
+    pub fn root(&self) -> &Scope {
+        &self.scopes[0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.scopes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    // Adds a scope as the last child of "parent"
+    pub fn push(&mut self, parent: ScopeId, mut scope: Scope) -> ScopeId {
+        let id = ScopeId(self.scopes.len());
+        scope.parent = Some(parent);
+        self.scopes.push(scope);
+        self.scopes[parent.0].children.push(id);
+        id
+    }
+
+    // Removes every scope that was pushed after the first "len" of them. The
+    // module scope always stays.
+    pub fn truncate(&mut self, len: usize) {
+        let len = len.max(1);
+        let parents: Vec<ScopeId> = self.scopes[len.min(self.scopes.len())..]
+            .iter()
+            .filter_map(|scope| scope.parent)
+            .filter(|parent| parent.0 < len)
+            .collect();
+        self.scopes.truncate(len);
+        for parent in parents {
+            self.scopes[parent.0].children.retain(|child| child.0 < len);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Scope> {
+        self.scopes.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Scope> {
+        self.scopes.iter_mut()
+    }
+
+    // The scope itself, then its parent, and so on up to the module scope
+    pub fn ancestors(&self, id: ScopeId) -> Ancestors<'_> {
+        Ancestors {
+            tree: self,
+            next: Some(id),
+        }
+    }
+
+    // The scope itself and every scope inside it, with each scope before its
+    // children and the children in the order they were created
+    pub fn descendants(&self, id: ScopeId) -> Descendants<'_> {
+        Descendants {
+            tree: self,
+            stack: vec![id],
+        }
+    }
+}
+
+impl Index<ScopeId> for ScopeTree {
+    type Output = Scope;
+
+    fn index(&self, index: ScopeId) -> &Self::Output {
+        &self.scopes[index.0]
+    }
+}
+
+impl IndexMut<ScopeId> for ScopeTree {
+    fn index_mut(&mut self, index: ScopeId) -> &mut Self::Output {
+        &mut self.scopes[index.0]
+    }
+}
+
+pub struct Ancestors<'a> {
+    tree: &'a ScopeTree,
+    next: Option<ScopeId>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = ScopeId;
+
+    fn next(&mut self) -> Option<ScopeId> {
+        let id = self.next?;
+        self.next = self.tree[id].parent;
+        Some(id)
+    }
+}
+
+pub struct Descendants<'a> {
+    tree: &'a ScopeTree,
+    stack: Vec<ScopeId>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = ScopeId;
+
+    fn next(&mut self) -> Option<ScopeId> {
+        let id = self.stack.pop()?;
+        self.stack
+            .extend(self.tree[id].children.iter().rev().copied());
+        Some(id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SymbolMap {
     // This could be represented as a "map[Ref]Symbol" but a two-level array was
     // more efficient in profiles. This appears to be because it doesn't involve
@@ -968,7 +1636,7 @@ impl SymbolMap {
     }
 
     pub fn set_namespace_alias(&mut self, reference: Reference, alias: Arc<NamespaceAlias>) {
-        self[reference].namespace_alias = alias;
+        self[reference].namespace_alias = Some(alias);
     }
 
     pub fn increment_use_count_estimate(&mut self, reference: Reference) {
@@ -990,14 +1658,14 @@ impl IndexMut<Reference> for SymbolMap {
     }
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum ImportKind {
     Stmt = 0,
     Require,
     Dynamic,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImportPath {
     pub path: Path,
     pub kind: ImportKind,
@@ -1007,25 +1675,25 @@ pub struct ImportPath {
     pub does_not_use_exports: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AST {
     pub was_typescript: bool,
 
     // This is a list of CommonJS features. When a file uses CommonJS features,
     // it's not a candidate for "flat bundling" and must be wrapped in its own
     // closure.
-    has_top_level_return: bool,
-    uses_exports_ref: bool,
-    uses_module_ref: bool,
+    pub has_top_level_return: bool,
+    pub uses_exports_ref: bool,
+    pub uses_module_ref: bool,
 
     // This is a list of ES6 features
-    has_es6_imports: bool,
-    has_es6_exports: bool,
+    pub has_es6_imports: bool,
+    pub has_es6_exports: bool,
 
     pub hash_bang: String,
     pub parts: Vec<Part>,
     pub symbols: SymbolMap,
-    pub module_scope: Scope,
+    pub scopes: ScopeTree,
     pub exports_ref: Reference,
     pub module_ref: Reference,
     pub wrapper_ref: Reference,
@@ -1033,13 +1701,17 @@ pub struct AST {
     // These are used when bundling. They are filled in during the parser pass
     // since we already have to traverse the AST then anyway and the parser pass
     // is conveniently fully parallelized.
-    named_imports: HashMap<Reference, NamedImport>,
-    named_exports: HashMap<String, Reference>,
-    top_level_symbol_to_parts: HashMap<Reference, Vec<u32>>,
-    export_stars: Vec<Path>,
+    pub named_imports: HashMap<Reference, NamedImport>,
+    pub named_exports: HashMap<String, Reference>,
+    pub top_level_symbol_to_parts: HashMap<Reference, Vec<u32>>,
+    pub export_stars: Vec<Path>,
 }
 
 impl AST {
+    pub fn module_scope(&self) -> &Scope {
+        self.scopes.root()
+    }
+
     pub fn has_commonjs_features(&self) -> bool {
         self.has_top_level_return || self.uses_exports_ref || self.uses_module_ref
     }
@@ -1053,19 +1725,19 @@ impl AST {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NamedImport {
-    alias: String,
-    alias_loc: Location,
-    import_path: Path,
-    namespace_ref: Reference,
+    pub alias: String,
+    pub alias_loc: Location,
+    pub import_path: Path,
+    pub namespace_ref: Reference,
 
     // Parts within this file that use this import
-    local_parts_with_uses: Vec<u32>,
+    pub local_parts_with_uses: Vec<u32>,
 
     // It's useful to flag exported imports because if they are in a TypeScript
     // file, we can't tell if they are a type or a value.
-    is_exported: bool,
+    pub is_exported: bool,
 }
 
 // Each file is made up of multiple parts, and each part consists of one or
@@ -1073,7 +1745,7 @@ pub struct NamedImport {
 // splitting analysis. Individual parts of a file can be discarded by tree
 // shaking and can be assigned to separate chunks (i.e. output files) by code
 // splitting.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Part {
     pub import_paths: Vec<ImportPath>,
     pub stmts: Vec<Stmt>,
@@ -1107,44 +1779,137 @@ pub struct Part {
     pub force_tree_shaking: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeclaredSymbol {
-    reference: Reference,
-    is_top_level: bool,
+    pub reference: Reference,
+    pub is_top_level: bool,
 }
 
-// Returns the canonical ref that represents the ref for the provided symbol.
-// This may not be the provided ref if the symbol has been merged with another
-// symbol.
-pub fn follow_symbols(symbols: &mut SymbolMap, reference: Reference) -> Reference {
-    let sym_link = symbols[reference].link;
-    if sym_link == INVALID_REF {
-        return reference;
-    }
-
-    let link = follow_symbols(symbols, sym_link);
+// Merging symbols never links one back to itself, so a cycle means that
+// something went wrong while linking. It's reported instead of following the
+// links forever.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolCycle {
+    // Each symbol in the cycle is linked to the next one, and the last one is
+    // linked to the first
+    pub references: Vec<Reference>,
+    pub names: Vec<String>,
+}
 
-    // Only write if needed to avoid concurrent map update hazards
-    if sym_link != link {
-        symbols[reference].link = link;
+impl fmt::Display for SymbolCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Symbols are linked to each other in a cycle: ")?;
+        for name in &self.names {
+            write!(f, "\"{}\" -> ", name)?;
+        }
+        write!(f, "\"{}\"", self.names[0])
     }
+}
 
-    link
+// Returns the canonical ref that represents the ref for the provided symbol.
+// This may not be the provided ref if the symbol has been merged with another
+// symbol. Every symbol on the way is linked straight to it afterwards so the
+// next call doesn't have to walk the chain again.
+pub fn follow_symbols(
+    symbols: &mut SymbolMap,
+    reference: Reference,
+) -> Result<Reference, SymbolCycle> {
+    let end = find_chain_end(symbols, reference, &[])?;
+    let mut current = reference;
+    while current != end {
+        let next = symbols[current].link;
+
+        // Only write if needed to avoid concurrent map update hazards
+        if next != end {
+            symbols[current].link = end;
+        }
+        current = next;
+    }
+    Ok(end)
 }
+
 // Use this before calling "FollowSymbols" from separate threads to avoid
 // concurrent map update hazards. In Go, mutating a map is not threadsafe
 // but reading from a map is. Calling "FollowAllSymbols" first ensures that
-// all mutation is done up front.
-pub fn follow_all_symbols(symbols: &mut SymbolMap) {
-    let outer_len = symbols.outer.len();
-    if outer_len > 0 {
-        for i in 0..outer_len {
-            let inner_len = symbols.outer[i].len();
-            for j in 0..inner_len {
-                follow_symbols(symbols, Reference::new(i, j));
+// all mutation is done up front. The files are split across threads, which
+// first find the ends of the chains while only reading and then link each
+// symbol of their own file to its end.
+pub fn follow_all_symbols(symbols: &mut SymbolMap) -> Result<(), SymbolCycle> {
+    let ends: Vec<Vec<Reference>> = {
+        let symbols = &*symbols;
+        symbols
+            .outer
+            .par_iter()
+            .enumerate()
+            .map(|(outer, inner_symbols)| {
+                let mut ends = Vec::with_capacity(inner_symbols.len());
+                for inner in 0..inner_symbols.len() {
+                    let end = find_chain_end(symbols, Reference::new(outer, inner), &ends)?;
+                    ends.push(end);
+                }
+                Ok(ends)
+            })
+            .collect::<Result<_, _>>()?
+    };
+
+    symbols
+        .outer
+        .par_iter_mut()
+        .zip(ends)
+        .for_each(|(inner_symbols, ends)| {
+            for (symbol, end) in inner_symbols.iter_mut().zip(ends) {
+                if symbol.link != INVALID_REF && symbol.link != end {
+                    symbol.link = end;
+                }
             }
+        });
+    Ok(())
+}
+
+// Follows the links from "reference" without changing anything. The ends of
+// the chains of the first symbols in the same file may already be known, so
+// the walk can stop early at one of them. Cycles are found with Brent's
+// algorithm: a mark is moved to the current symbol after every power of two
+// steps, and the walk only gets back to the mark if it's going in circles.
+fn find_chain_end(
+    symbols: &SymbolMap,
+    reference: Reference,
+    known_ends: &[Reference],
+) -> Result<Reference, SymbolCycle> {
+    let mut current = reference;
+    let mut mark = reference;
+    let mut steps: usize = 0;
+    loop {
+        if current.outer == reference.outer && current.inner < known_ends.len() {
+            return Ok(known_ends[current.inner]);
         }
+        let link = symbols[current].link;
+        if link == INVALID_REF {
+            return Ok(current);
+        }
+        current = link;
+        steps += 1;
+        if current == mark {
+            return Err(symbol_cycle(symbols, mark));
+        }
+        if steps.is_power_of_two() {
+            mark = current;
+        }
+    }
+}
+
+fn symbol_cycle(symbols: &SymbolMap, start: Reference) -> SymbolCycle {
+    let mut references = vec![start];
+    let mut current = symbols[start].link;
+    while current != start {
+        references.push(current);
+        current = symbols[current].link;
     }
+    let names = references
+        .iter()
+        .map(|reference| symbols[*reference].name.clone())
+        .collect();
+    SymbolCycle { references, names }
 }
 
 // Makes "old" point to "new" by joining the linked lists for the two symbols
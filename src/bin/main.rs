@@ -1,53 +1,723 @@
-use esbuild_rs::ast::{join_all_with_comma, Expr, ExprKind};
-use esbuild_rs::logging::MsgCounts;
-use std::fmt::Debug;
+use esbuild_rs::bundler::{self, BundleOptions, ImportPathRewriter, OutputFormat};
+use esbuild_rs::cache::{DiskCache, ParseCache};
+use esbuild_rs::estree;
+use esbuild_rs::fs::{expand_glob, is_glob, FileSystem, RealFileSystem};
+use esbuild_rs::lexer;
+use esbuild_rs::logging::{self, DiagnosticSink, Log, LogLevel, Msg, MsgId, Source, StderrOptions};
+use esbuild_rs::lower::{LowerOptions, Target};
+use esbuild_rs::number::number_to_string;
+use esbuild_rs::parser::{self, DropConsole, JSXRuntime, ParseOptions};
+use esbuild_rs::paths;
+use esbuild_rs::printer::LegalComments;
+use esbuild_rs::resolver::{Platform, ResolveOptions, Resolver};
+use esbuild_rs::sources::SourceRegistry;
+use esbuild_rs::strings::utf16_to_string_lossy;
+use esbuild_rs::tables::Token;
+use esbuild_rs::terminal::{StderrColor, TerminalInfo};
+use esbuild_rs::TransformOptions;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read, Write};
+use std::process;
 
-trait DebugPrintln: Debug {
-    fn debug_println(&self) {
-        println!("{:?}", self);
+const HELP_TEXT: &str = "
+Usage:
+  esbd [options] [entry points]
+  esbd [options] < file Transform the code from stdin without bundling it
+  esbd tokens [file]    Print the tokens in a JavaScript file
+
+Options:
+  --bundle              Bundle all dependencies into the output files
+  --splitting           Put files loaded with import() in separate chunks
+  --hmr                 Register every module with a runtime registry, so
+                        that a development server can swap single modules
+  --outfile=...         The output file (for one entry point)
+  --outdir=...          The output directory (for multiple entry points)
+  --outbase=...         The directory that [dir] in --entry-names is relative
+                        to (default is the lowest common directory of the
+                        entry points)
+  --target=...          Language target (default esnext)
+  --format=...          Output format (esm, iife, or cjs)
+  --platform=...        Platform target (browser, node, or neutral, default
+                        browser)
+  --global-name=...     The name of the global for the exports of an IIFE
+  --public-path=...     The URL prefix that IIFE chunks are loaded from
+  --entry-names=...     Path template for entry point outputs, using [name],
+                        [dir] and [hash] (default [dir]/[name])
+  --chunk-names=...     Path template for chunks (default [name]-[hash])
+  --asset-names=...     Path template for copied files (default [name]-[hash])
+  --legal-comments=...  Where to put comments like /*! ... */ and @license
+                        (none, inline, eof, or external, default inline)
+  --preserve-comments   Keep all comments in front of statements, not just
+                        legal comments (left out by --minify-whitespace)
+  --jsx=...             How JSX elements are compiled (classic calls
+                        React.createElement, automatic imports the functions
+                        from react/jsx-runtime, default classic)
+  --jsx-import-source=...
+                        The package that --jsx=automatic imports from
+                        (default react)
+  --jsx-dev             Use react/jsx-dev-runtime with --jsx=automatic, which
+                        knows where each element is in the source
+  --define:K=V          Substitute K with V while parsing
+  --drop:console        Remove calls to console methods
+  --drop:debugger       Remove debugger statements
+  --drop-labels=A,B     Remove the statements with these labels, like
+                        DEV: { ... }
+  --external:M          Exclude module M from the bundle (can use a * wildcard)
+  --rewrite-import:M=P  Print the external imports of module M and of the
+                        paths in it with the prefix P instead of M
+  --loader:X=L          Use loader L to load file extension X, where L is
+                        js, jsx, ts, tsx, json, css, local-css, text, base64,
+                        dataurl, binary, or file
+  --loader=L            Use loader L for the code from stdin (default js)
+  --minify              Sets all --minify-* flags
+  --minify-whitespace   Remove whitespace
+  --minify-identifiers  Shorten identifiers
+  --minify-syntax       Use equivalent but shorter syntax
+  --preserve-quoted-keys
+                        Keep quoted property names quoted and unquoted ones
+                        unquoted with --minify-syntax, for code that Closure
+                        Compiler renames properties in
+  --error-limit=...     Maximum error count or 0 to disable (default 10)
+  --log-format=...      How to print errors and warnings (text or json)
+  --log-override:X=Y    Report the warning named X as Y instead, where Y is
+                        warning, error, or silent
+  --cache-dir=...       Reuse files parsed by earlier builds from this directory
+  --metafile=...        Write metadata about the build to a JSON file
+  --ast                 Print the syntax tree of each entry point as ESTree
+                        JSON instead of building
+
+Examples:
+  # Produces dist/entry_point.js
+  esbd src/entry_point.js --outdir=dist
+
+  # Produces a file in dist for every file in src, in the same directories
+  esbd 'src/**/*.ts' --outdir=dist --outbase=src
+
+  # Bundles entry_point.js and everything it imports into out.js
+  esbd --bundle entry_point.js --outfile=out.js
+
+  # Produces dist/app.js and dist/app.css with the CSS that app.js imports
+  esbd --bundle app.js --outdir=dist
+
+  # Puts the files that app.js loads with import() in chunks next to it
+  esbd --bundle --splitting app.js --outdir=dist
+
+  # Inlines imported PNG files and copies fonts to dist
+  esbd --bundle app.js --outdir=dist --loader:.png=dataurl --loader:.woff2=file
+
+  # Adds a content hash to the output file names so they can be cached
+  esbd --bundle app.js --outdir=dist --entry-names=[name]-[hash]
+
+  # Leaves react and the node built-in modules out of the bundle
+  esbd --bundle app.js --outfile=out.js --external:react --external:node:*
+
+  # Loads react from a CDN in the browser
+  esbd --bundle app.js --outfile=out.js --external:react \\
+    --rewrite-import:react=https://esm.sh/react@18
+
+  # Fails the build on comparisons with NaN and hides duplicate key warnings
+  esbd app.js --log-override:equality-with-nan=error \\
+    --log-override:duplicate-object-key=silent
+
+  # Removes the code that only runs in development
+  esbd entry_point.js --define:process.env.NODE_ENV=\"production\"
+
+  # Prints the syntax tree of app.ts, one JSON object per entry point
+  esbd --ast app.ts
+
+  # Prints each token in app.js on its own line, such as 3:6 Identifier \"x\"
+  esbd tokens app.js
+";
+
+struct Args {
+    entry_paths: Vec<String>,
+    parse_options: ParseOptions,
+    resolve_options: ResolveOptions,
+    bundle_options: BundleOptions,
+    stderr_options: StderrOptions,
+    cache: Option<DiskCache>,
+
+    // The messages are printed as one JSON array at the end instead of as
+    // text while the build runs
+    log_json: bool,
+    log_overrides: HashMap<MsgId, LogLevel>,
+
+    // Where to write the JSON description of the build, if anywhere
+    abs_metafile: Option<String>,
+
+    // Only parse the entry points and print their trees
+    print_ast: bool,
+
+    // Without entry points, the code from stdin is transformed with this
+    // loader instead
+    stdin_loader: bundler::Loader,
+}
+
+fn default_stderr_options() -> StderrOptions {
+    StderrOptions {
+        include_source: true,
+        error_limit: 10,
+        exit_when_limit_is_hit: true,
+        color: StderrColor::IfTerminal,
     }
 }
 
-impl<T: Debug> DebugPrintln for T {}
+fn parse_args(fs: &RealFileSystem, raw_args: &[String]) -> Result<Args, String> {
+    let mut rewritten_imports = vec![];
+    let mut args = Args {
+        entry_paths: vec![],
+        parse_options: ParseOptions::default(),
+        resolve_options: ResolveOptions::default(),
+        bundle_options: BundleOptions::default(),
+        stderr_options: default_stderr_options(),
+        cache: None,
+        log_json: false,
+        log_overrides: HashMap::new(),
+        abs_metafile: None,
+        print_ast: false,
+        stdin_loader: bundler::Loader::JS,
+    };
+
+    let abs = |path: &str| match fs.abs(path) {
+        Some(path) => Ok(paths::to_slash(&path.to_string_lossy())),
+        None => Err(format!("Invalid path: {}", path)),
+    };
+
+    for arg in raw_args {
+        match arg.as_str() {
+            "--bundle" => {
+                args.parse_options.is_bundling = true;
+                args.bundle_options.bundle = true;
+            }
+            "--minify" => {
+                args.bundle_options.minify.whitespace = true;
+                args.bundle_options.minify.syntax = true;
+                args.bundle_options.minify.identifiers = true;
+            }
+            "--minify-whitespace" => args.bundle_options.minify.whitespace = true,
+            "--minify-syntax" => args.bundle_options.minify.syntax = true,
+            "--minify-identifiers" => args.bundle_options.minify.identifiers = true,
+            "--preserve-quoted-keys" => args.bundle_options.mangle.preserve_quoted_keys = true,
+            // Build scripts often pass this explicitly, and it's already the
+            // case since no source maps are generated
+            "--sourcemap=false" => {}
+            _ if arg == "--sourcemap" || arg.starts_with("--sourcemap=") => {
+                return Err("Source maps aren't supported yet".to_owned());
+            }
+            "--splitting" => args.bundle_options.code_splitting = true,
+            "--hmr" => args.bundle_options.hmr = true,
+            "--ast" => args.print_ast = true,
+            "--preserve-comments" => args.parse_options.preserve_comments = true,
+            "--jsx-dev" => args.parse_options.jsx.development = true,
+            "--drop:console" => args.parse_options.drop.console = DropConsole::All,
+            "--drop:debugger" => args.parse_options.drop.debugger = true,
+
+            _ if arg.starts_with("--outfile=") => {
+                args.bundle_options.abs_output_file = abs(&arg["--outfile=".len()..])?;
+            }
+
+            _ if arg.starts_with("--outdir=") => {
+                args.bundle_options.abs_output_dir = abs(&arg["--outdir=".len()..])?;
+            }
+
+            _ if arg.starts_with("--outbase=") => {
+                args.bundle_options.abs_outbase = abs(&arg["--outbase=".len()..])?;
+            }
+
+            _ if arg.starts_with("--target=") => {
+                let target = Target::from_name(&arg["--target=".len()..]).ok_or_else(|| {
+                    "Valid targets: es5, es6, es2015, es2016, es2017, es2018, es2019, \
+                     es2020, esnext"
+                        .to_owned()
+                })?;
+                args.parse_options.lower = LowerOptions {
+                    target,
+                    ..LowerOptions::default()
+                };
+                args.bundle_options.target = target;
+            }
+
+            _ if arg.starts_with("--format=") => {
+                let format = match &arg["--format=".len()..] {
+                    "esm" => OutputFormat::Esm,
+                    "iife" => OutputFormat::Iife,
+                    "cjs" => OutputFormat::Cjs,
+                    _ => return Err("Valid formats: esm, iife, cjs".to_owned()),
+                };
+                args.parse_options.output_format = format;
+                args.bundle_options.output_format = format;
+            }
+
+            _ if arg.starts_with("--jsx=") => {
+                args.parse_options.jsx.runtime = match &arg["--jsx=".len()..] {
+                    "classic" => JSXRuntime::Classic,
+                    "automatic" => JSXRuntime::Automatic,
+                    _ => return Err("Valid JSX runtimes: classic, automatic".to_owned()),
+                };
+            }
+
+            _ if arg.starts_with("--jsx-import-source=") => {
+                args.parse_options.jsx.import_source =
+                    arg["--jsx-import-source=".len()..].to_owned();
+            }
+
+            _ if arg.starts_with("--platform=") => {
+                args.resolve_options.platform = match &arg["--platform=".len()..] {
+                    "browser" => Platform::Browser,
+                    "node" => Platform::Node,
+                    "neutral" => Platform::Neutral,
+                    _ => return Err("Valid platforms: browser, node, neutral".to_owned()),
+                };
+            }
+
+            _ if arg.starts_with("--external:") => {
+                let path = &arg["--external:".len()..];
+                if path.matches('*').count() > 1 {
+                    return Err(format!("External paths can only have one \"*\": {}", path));
+                }
+                args.resolve_options.external.push(path.to_owned());
+            }
+
+            _ if arg.starts_with("--rewrite-import:") => {
+                let (prefix, replacement) = arg["--rewrite-import:".len()..]
+                    .split_once('=')
+                    .ok_or_else(|| format!("Missing \"=\": {}", arg))?;
+                rewritten_imports.push((prefix.to_owned(), replacement.to_owned()));
+            }
+
+            _ if arg.starts_with("--global-name=") => {
+                args.bundle_options.global_name = arg["--global-name=".len()..].to_owned();
+            }
+
+            _ if arg.starts_with("--public-path=") => {
+                args.bundle_options.public_path = arg["--public-path=".len()..].to_owned();
+            }
+
+            _ if arg.starts_with("--entry-names=") => {
+                args.bundle_options.entry_names = arg["--entry-names=".len()..].to_owned();
+            }
+
+            _ if arg.starts_with("--chunk-names=") => {
+                args.bundle_options.chunk_names = arg["--chunk-names=".len()..].to_owned();
+            }
+
+            _ if arg.starts_with("--asset-names=") => {
+                args.bundle_options.asset_names = arg["--asset-names=".len()..].to_owned();
+            }
+
+            _ if arg.starts_with("--drop:") => {
+                return Err("Valid values for --drop: console, debugger".to_owned());
+            }
+
+            _ if arg.starts_with("--drop-labels=") => {
+                args.parse_options.drop.labels = arg["--drop-labels=".len()..]
+                    .split(',')
+                    .filter(|label| !label.is_empty())
+                    .map(str::to_owned)
+                    .collect();
+            }
+
+            _ if arg.starts_with("--define:") => {
+                let (name, value) = parser::parse_define(&arg["--define:".len()..])
+                    .map_err(|err| err.to_string())?;
+                args.parse_options.defines.insert(name, value);
+            }
+
+            _ if arg.starts_with("--loader:") => {
+                let (ext, name) = arg["--loader:".len()..]
+                    .split_once('=')
+                    .ok_or_else(|| format!("Missing \"=\": {}", arg))?;
+                if !ext.starts_with('.') {
+                    return Err(format!("The extension must start with \".\": {}", ext));
+                }
+                let loader = bundler::Loader::from_name(name)
+                    .ok_or_else(|| format!("Invalid loader: {}", name))?;
+                args.parse_options.loaders.insert(ext.to_owned(), loader);
+            }
+
+            _ if arg.starts_with("--loader=") => {
+                let name = &arg["--loader=".len()..];
+                args.stdin_loader = bundler::Loader::from_name(name)
+                    .ok_or_else(|| format!("Invalid loader: {}", name))?;
+            }
+
+            _ if arg.starts_with("--metafile=") => {
+                args.abs_metafile = Some(abs(&arg["--metafile=".len()..])?);
+                args.bundle_options.metafile = true;
+            }
+
+            _ if arg.starts_with("--legal-comments=") => {
+                args.bundle_options.legal_comments = match &arg["--legal-comments=".len()..] {
+                    "none" => LegalComments::None,
+                    "inline" => LegalComments::Inline,
+                    "eof" => LegalComments::EndOfFile,
+                    "external" => LegalComments::External,
+                    _ => {
+                        return Err(
+                            "Valid legal comment modes: none, inline, eof, external".to_owned()
+                        )
+                    }
+                };
+            }
+
+            _ if arg.starts_with("--cache-dir=") => {
+                args.cache = Some(DiskCache::new(abs(&arg["--cache-dir=".len()..])?));
+            }
+
+            _ if arg.starts_with("--log-format=") => {
+                args.log_json = match &arg["--log-format=".len()..] {
+                    "text" => false,
+                    "json" => true,
+                    _ => return Err("Valid log formats: text, json".to_owned()),
+                };
+            }
+
+            _ if arg.starts_with("--log-override:") => {
+                let (name, level) = arg["--log-override:".len()..]
+                    .split_once('=')
+                    .ok_or_else(|| format!("Missing \"=\" in {}", arg))?;
+                let id = MsgId::from_name(name).ok_or_else(|| {
+                    let names: Vec<&str> = MsgId::ALL.iter().map(|id| id.name()).collect();
+                    format!(
+                        "Invalid warning name: {} (valid names: {})",
+                        name,
+                        names.join(", ")
+                    )
+                })?;
+                let level = LogLevel::from_name(level).ok_or_else(|| {
+                    format!(
+                        "Invalid log level: {} (valid levels: warning, error, silent)",
+                        level
+                    )
+                })?;
+                args.log_overrides.insert(id, level);
+            }
+
+            _ if arg.starts_with("--error-limit=") => {
+                let value = &arg["--error-limit=".len()..];
+                args.stderr_options.error_limit = value
+                    .parse()
+                    .map_err(|_| format!("Invalid error limit: {}", value))?;
+            }
+
+            _ if arg.starts_with('-') => return Err(format!("Invalid flag: {}", arg)),
+
+            // Glob patterns are expanded here instead of by the shell, so
+            // they work the same everywhere
+            _ if is_glob(arg) => {
+                let matches = expand_glob(fs, &abs(arg)?);
+                if matches.is_empty() {
+                    return Err(format!("No files match the pattern: {}", arg));
+                }
+                args.entry_paths.extend(matches);
+            }
+
+            _ => args.entry_paths.push(abs(arg)?),
+        }
+    }
+
+    if !rewritten_imports.is_empty() {
+        args.bundle_options.rewrite_external_paths =
+            Some(ImportPathRewriter::with_prefixes(rewritten_imports));
+    }
+
+    // Code from stdin has no path to resolve its imports from, so it can't
+    // be bundled
+    if args.entry_paths.is_empty() {
+        if std::io::stdin().is_terminal() {
+            return Err("Must provide at least one entry point".to_owned());
+        }
+        if args.bundle_options.bundle || args.print_ast {
+            return Err(
+                "Code from stdin can only be transformed, so --bundle and --ast \
+                        need an entry point"
+                    .to_owned(),
+            );
+        }
+    }
+    if args.entry_paths.len() > 1
+        && args.bundle_options.abs_output_dir.is_empty()
+        && !args.print_ast
+    {
+        return Err("Must provide --outdir when there are multiple input files".to_owned());
+    }
+    args.bundle_options
+        .validate()
+        .map_err(|err| err.to_string())?;
+
+    Ok(args)
+}
+
+// Shows the messages that make it through the sink, either as text as soon as
+// each stage is done or as one JSON array at the end
+struct MsgPrinter {
+    sink: DiagnosticSink,
+    options: StderrOptions,
+    terminal_info: TerminalInfo,
+    json_msgs: Option<Vec<Msg>>,
+}
+
+impl MsgPrinter {
+    fn new(options: &StderrOptions, log_json: bool) -> Self {
+        MsgPrinter {
+            sink: DiagnosticSink::new(options),
+            options: options.clone(),
+            terminal_info: TerminalInfo::for_stderr(),
+            json_msgs: if log_json { Some(vec![]) } else { None },
+        }
+    }
+
+    // Returns true if the build should stop, either because there were errors
+    // or because the error limit was hit
+    fn print_msgs(&mut self, log: &Log) -> bool {
+        for msg in self.sink.drain(log) {
+            self.print_msg(msg);
+        }
+        self.sink.should_stop() || self.sink.counts().errors > 0
+    }
+
+    fn print_msg(&mut self, msg: Msg) {
+        match &mut self.json_msgs {
+            Some(json_msgs) => json_msgs.push(msg),
+            None => {
+                let text = msg.to_terminal_string(&self.options, &self.terminal_info);
+                let _ = std::io::stderr().lock().write_all(text.as_bytes());
+            }
+        }
+    }
+
+    fn finish(mut self) -> ! {
+        if let Some(msg) = self.sink.finish() {
+            self.print_msg(msg);
+        }
+        let counts = self.sink.counts();
+        match &self.json_msgs {
+            Some(json_msgs) => eprintln!("{}", logging::msgs_to_json(json_msgs)),
+            None => eprintln!("{}", counts),
+        }
+        process::exit(if counts.errors == 0 { 0 } else { 1 });
+    }
+}
+
+// Prints one line per token with the 1-based line, the column, the kind of
+// token, its text, and for literals and identifiers the value the lexer read
+fn print_tokens(fs: &RealFileSystem, raw_args: &[String]) -> ! {
+    let path = match raw_args {
+        [path] if !path.starts_with('-') => path,
+        _ => {
+            eprintln!("error: Usage: esbd tokens [file]");
+            process::exit(1);
+        }
+    };
+    let source = match fs
+        .abs(path)
+        .and_then(|abs| fs.read_file(&abs).map(|contents| (abs, contents)))
+    {
+        Some((abs, contents)) => Source {
+            absolute_path: paths::to_slash(&abs.to_string_lossy()),
+            pretty_path: path.clone(),
+            contents,
+            ..Source::default()
+        },
+        None => {
+            eprintln!("error: Could not read from file: {}", path);
+            process::exit(1);
+        }
+    };
+
+    let log = Log::default();
+    let line_offsets = source.line_offset_table();
+    let mut out = String::new();
+    let mut tokens = lexer::tokens(&log, &source);
+    while let Some((token, range)) = tokens.next() {
+        let lexer = tokens.lexer();
+        let (line, column, _) = line_offsets.line_and_column(range.start);
+        out.push_str(&format!(
+            "{}:{} {:?} {:?}",
+            line + 1,
+            column,
+            token,
+            &source.contents[range]
+        ));
+        let value = match token {
+            Token::StringLiteral
+            | Token::NoSubstitutionTemplateLiteral
+            | Token::TemplateHead
+            | Token::TemplateMiddle
+            | Token::TemplateTail => Some(format!(
+                "{:?}",
+                utf16_to_string_lossy(&lexer.string_literal)
+            )),
+            Token::NumericLiteral => Some(number_to_string(lexer.number)),
+            Token::BigIntegerLiteral
+            | Token::Identifier
+            | Token::EscapedKeyword
+            | Token::PrivateIdentifier => Some(lexer.identifier.clone()),
+            _ => None,
+        };
+        if let Some(value) = value {
+            out.push_str(&format!(" = {}", value));
+        }
+        out.push('\n');
+    }
+    let _ = std::io::stdout().lock().write_all(out.as_bytes());
+
+    let mut printer = MsgPrinter::new(&default_stderr_options(), false);
+    printer.print_msgs(&log);
+    printer.finish();
+}
+
+// Transforms the code from stdin like the "transform" API does and writes it to
+// the output file, or to stdout if there isn't one
+fn transform_stdin(args: &Args, log: &Log) {
+    let mut contents = String::new();
+    if let Err(err) = std::io::stdin().read_to_string(&mut contents) {
+        log.add_error(
+            &Source::default(),
+            0,
+            format!("Could not read from stdin ({})", err),
+        );
+        return;
+    }
+    let options = TransformOptions {
+        loader: args.stdin_loader,
+        target: args.bundle_options.target,
+        minify: args.bundle_options.minify,
+        mangle: args.bundle_options.mangle.clone(),
+        defines: args.parse_options.defines.clone(),
+        drop: args.parse_options.drop.clone(),
+        jsx: args.parse_options.jsx.clone(),
+        typescript: args.parse_options.typescript.clone(),
+        sourcefile: "<stdin>".to_owned(),
+        preserve_comments: args.parse_options.preserve_comments,
+        log_overrides: args.log_overrides.clone(),
+        unsupported_syntax: args.parse_options.lower.unsupported_syntax,
+    };
+    let result = esbuild_rs::transform(&contents, options);
+    for msg in result.errors.into_iter().chain(result.warnings) {
+        log.add_msg(msg);
+    }
+
+    let path = &args.bundle_options.abs_output_file;
+    if path.is_empty() {
+        let _ = std::io::stdout().lock().write_all(result.code.as_bytes());
+    } else if let Err(err) = std::fs::write(path, &result.code) {
+        log.add_error(
+            &Source::default(),
+            0,
+            format!("Failed to write to {} ({})", path, err),
+        );
+    }
+}
+
+// Prints the tree of each entry point that parses as one line of JSON
+fn print_asts(fs: &RealFileSystem, args: &Args, log: &Log) {
+    let mut out = String::new();
+    let mut sources = SourceRegistry::new();
+    for path in &args.entry_paths {
+        let (source_index, _) = sources.add_file(fs, path);
+        if !sources.read_contents(fs, source_index) {
+            log.add_error(
+                &Source::default(),
+                0,
+                format!("Could not read from file: {}", path),
+            );
+            continue;
+        }
+        let source = &sources[source_index];
+        let loader =
+            bundler::loader_for_path(path, &args.parse_options).unwrap_or(bundler::Loader::JS);
+        if let Some(ast) = bundler::parse_file(log, source, loader, &args.parse_options) {
+            out.push_str(&estree::ast_to_json(&ast));
+            out.push('\n');
+        }
+    }
+    let _ = std::io::stdout().lock().write_all(out.as_bytes());
+}
 
 fn main() {
-    let msg_count = MsgCounts {
-        errors: 0,
-        warnings: 0,
+    let raw_args: Vec<String> = std::env::args().skip(1).collect();
+    let wants_help = raw_args.is_empty() && std::io::stdin().is_terminal();
+    if wants_help || raw_args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        eprint!("{}", HELP_TEXT);
+        process::exit(0);
+    }
+
+    let fs = RealFileSystem::new();
+    if raw_args.first().map(String::as_str) == Some("tokens") {
+        print_tokens(&fs, &raw_args[1..]);
+    }
+    let args = match parse_args(&fs, &raw_args) {
+        Ok(args) => args,
+        Err(text) => {
+            eprintln!("error: {}", text);
+            process::exit(1);
+        }
     };
 
-    println!("{}", msg_count);
-
-    if let Some((width, height)) = terminal_size::terminal_size() {
-        println!("{} {}", width.0, height.0);
-    } else {
-        println!("no terminal size");
-    }
-
-    if let Some(expr) = {
-        let mut v = Vec::<Expr>::new();
-        v.push(Expr {
-            location: 0,
-            data: Box::new(ExprKind::Null),
-        });
-        v.push(Expr {
-            location: 1,
-            data: Box::new(ExprKind::String { value: vec![1, 2] }),
-        });
-        // v.push(Expr {
-        //     location: 1,
-        //     data: Box::new(ExprKind::String { value: vec![1, 2] }),
-        // });
-        // v.push(Expr {
-        //     location: 1,
-        //     data: Box::new(ExprKind::String { value: vec![1, 2] }),
-        // });
-        //
-
-        join_all_with_comma(v.into_iter())
-    } {
-        println!("{:?}", expr);
-    } else {
-        println!("no expr");
+    let log = Log::with_overrides(args.log_overrides.clone());
+    let mut printer = MsgPrinter::new(&args.stderr_options, args.log_json);
+
+    if args.print_ast {
+        print_asts(&fs, &args, &log);
+        printer.print_msgs(&log);
+        printer.finish();
+    }
+    if args.entry_paths.is_empty() {
+        transform_stdin(&args, &log);
+        printer.print_msgs(&log);
+        printer.finish();
+    }
+
+    // Each stage only runs if the one before it had no errors
+    let resolver = Resolver::new(fs, args.resolve_options.clone());
+    let bundle = bundler::scan_bundle(
+        &log,
+        &resolver,
+        &args.entry_paths,
+        &args.parse_options,
+        args.cache.as_ref().map(|cache| cache as &dyn ParseCache),
+    );
+    if printer.print_msgs(&log) {
+        printer.finish();
+    }
+
+    let result = bundle.compile(&log, &args.bundle_options);
+    if printer.print_msgs(&log) {
+        printer.finish();
     }
+
+    for file in &result.output_files {
+        if file.path.is_empty() {
+            let stdout = std::io::stdout();
+            let _ = stdout.lock().write_all(&file.contents);
+            continue;
+        }
+
+        let written = std::fs::create_dir_all(paths::dir(&file.path))
+            .and_then(|_| std::fs::write(&file.path, &file.contents));
+        if let Err(err) = written {
+            log.add_error(
+                &Source::default(),
+                0,
+                format!("Failed to write to {} ({})", file.path, err),
+            );
+        }
+    }
+
+    if let Some(abs_metafile) = &args.abs_metafile {
+        let cwd = std::env::current_dir()
+            .map(|cwd| paths::to_slash(&cwd.to_string_lossy()))
+            .unwrap_or_default();
+        if let Err(err) = std::fs::write(abs_metafile, bundle.metafile(&result, &cwd)) {
+            log.add_error(
+                &Source::default(),
+                0,
+                format!("Failed to write to {} ({})", abs_metafile, err),
+            );
+        }
+    }
+
+    printer.print_msgs(&log);
+    printer.finish();
 }
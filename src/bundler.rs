@@ -1 +1,3077 @@
+use crate::ast::{
+    Class, Expr, ExprKind, ExprOrStmt, ImportKind, Location, OptionalChain, Path, Property,
+    Reference, Stmt, StmtKind, SymbolMap, AST, RUNTIME_SOURCE_INDEX,
+};
+use crate::cache::{self, ParseCache};
+use crate::cjs;
+use crate::css::{self, Rule, Stylesheet};
+use crate::error::Error;
+use crate::fs::FileSystem;
+use crate::lexer::is_identifier;
+use crate::linker::{self, ChunkId, Chunks, CommonChunk, LinkOutput, LinkerFile};
+use crate::logging::{quote_json, Log, Source};
+use crate::lower::Target;
+use crate::mangler::{self, MangleOptions};
+use crate::parser::{self, ParseOptions};
+use crate::parser_json::{self, JsonOptions};
+use crate::paths;
+use crate::printer::{self, LegalComments, PrintOptions};
+use crate::renamer;
+use crate::resolver::Resolver;
+use crate::runtime::{self, ChunkLoader};
+use crate::sources::SourceRegistry;
+use crate::stack::ensure_sufficient_stack;
+use crate::strings::{string_to_utf16, utf16_to_string_lossy};
+use crate::tables::Token;
+use crate::MinifyOptions;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use xxhash_rust::xxh64::xxh64;
 
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Default)]
+pub enum Loader {
+    #[default]
+    JS = 0,
+    JSX,
+    TS,
+    TSX,
+    JSON,
+    CSS,
+
+    // CSS modules, where the class and ID names are local to the file (see
+    // "css::rename_local_names"). This is the default for ".module.css".
+    LocalCSS,
+
+    // These are for files that aren't code. Each one becomes a module whose
+    // default export is the file's contents as a string, the contents in
+    // base64, a "data:" URL, a "Uint8Array" of the bytes, or for "file" the
+    // URL of a copy of the file in the output directory.
+    Text,
+    Base64,
+    DataURL,
+    Binary,
+    File,
+}
+
+impl Loader {
+    pub fn is_typescript(self) -> bool {
+        self == Loader::TS || self == Loader::TSX
+    }
+
+    pub fn allows_jsx(self) -> bool {
+        self == Loader::JSX || self == Loader::TSX
+    }
+
+    // Picks the loader from the extension of the path, like ".tsx"
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            ".js" | ".mjs" | ".cjs" => Some(Loader::JS),
+            ".jsx" => Some(Loader::JSX),
+            ".ts" => Some(Loader::TS),
+            ".tsx" => Some(Loader::TSX),
+            ".json" => Some(Loader::JSON),
+            ".css" => Some(Loader::CSS),
+            ".txt" => Some(Loader::Text),
+            _ => None,
+        }
+    }
+
+    // Parses the loader name used on the command line, like "dataurl"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "js" => Some(Loader::JS),
+            "jsx" => Some(Loader::JSX),
+            "ts" => Some(Loader::TS),
+            "tsx" => Some(Loader::TSX),
+            "json" => Some(Loader::JSON),
+            "css" => Some(Loader::CSS),
+            "local-css" => Some(Loader::LocalCSS),
+            "text" => Some(Loader::Text),
+            "base64" => Some(Loader::Base64),
+            "dataurl" => Some(Loader::DataURL),
+            "binary" => Some(Loader::Binary),
+            "file" => Some(Loader::File),
+            _ => None,
+        }
+    }
+
+    // These loaders read the file as bytes instead of as UTF-8 text
+    pub fn is_css(self) -> bool {
+        self == Loader::CSS || self == Loader::LocalCSS
+    }
+
+    pub fn is_binary(self) -> bool {
+        matches!(
+            self,
+            Loader::Base64 | Loader::DataURL | Loader::Binary | Loader::File
+        )
+    }
+}
+
+// The loaders configured in the options take precedence over the defaults.
+// ".module.css" is looked up before ".css" since it's more specific.
+pub fn loader_for_path(path: &str, options: &ParseOptions) -> Option<Loader> {
+    if paths::base(path).ends_with(".module.css") {
+        return Some(
+            options
+                .loaders
+                .get(".module.css")
+                .copied()
+                .unwrap_or(Loader::LocalCSS),
+        );
+    }
+    let ext = paths::ext(path);
+    match options.loaders.get(&ext) {
+        Some(&loader) => Some(loader),
+        None => Loader::from_extension(&ext),
+    }
+}
+
+// Every file becomes a module no matter which loader it uses. A JSON file is
+// a module whose default export is the parsed value, so it can be imported
+// with "import data from './data.json'".
+//
+// Returns None if there was an error. The error has already been logged.
+pub fn parse_file(
+    log: &Log,
+    source: &Source,
+    loader: Loader,
+    options: &ParseOptions,
+) -> Option<AST> {
+    match loader {
+        Loader::JS => parser::parse(log, source, options),
+        Loader::JSX | Loader::TS | Loader::TSX => {
+            let mut options = options.clone();
+            options.jsx.parse = loader.allows_jsx();
+            options.typescript.parse = loader.is_typescript();
+            parser::parse(log, source, &options)
+        }
+        Loader::JSON => {
+            let value = parser_json::parse_json(log, source, JsonOptions::default())?;
+            Some(parser::lazy_export_ast(log, source, options, value))
+        }
+
+        // A CSS file is an empty module as far as JavaScript is concerned. The
+        // bundler parses the stylesheet itself and puts it in a separate file.
+        Loader::CSS => {
+            css::parse(log, source);
+            Some(empty_ast(source, options))
+        }
+        Loader::LocalCSS => Some(parse_local_css(log, source, options).2),
+        Loader::Text => {
+            let value = string_expr(0, &source.contents);
+            Some(parser::lazy_export_ast(log, source, options, value))
+        }
+        // There's nothing to copy the file to, so the path is where it would
+        // be copied to without a naming template
+        Loader::File => {
+            let path = format!("./{}", paths::base(&source.absolute_path));
+            Some(parser::lazy_export_ast(
+                log,
+                source,
+                options,
+                string_expr(0, &path),
+            ))
+        }
+        Loader::Base64 | Loader::DataURL | Loader::Binary => Some(asset_ast(
+            log,
+            source,
+            loader,
+            source.contents.as_bytes(),
+            options,
+        )),
+    }
+}
+
+// A CSS modules file is a stylesheet whose local names have been renamed, and
+// a module that exports the new names. Each name ends in a hash of the path of
+// the file, so names can't collide with the ones of other files and are the
+// same in every build.
+pub fn parse_local_css(
+    log: &Log,
+    source: &Source,
+    options: &ParseOptions,
+) -> (Stylesheet, Vec<(String, String)>, AST) {
+    let mut stylesheet = css::parse(log, source);
+    let suffix = hash_string(xxh64(source.pretty_path.as_bytes(), 0));
+    let names = css::rename_local_names(&mut stylesheet, &suffix);
+    let ast = parser::css_module_ast(log, source, options, &names);
+    (stylesheet, names, ast)
+}
+
+// Makes the module for a file that uses one of the binary loaders. A file
+// loaded with the "file" loader exports a placeholder for the path of its
+// copy, since the name of the copy depends on its hash and the path depends
+// on which output file it's imported from. The bundler writes the copy and
+// replaces the placeholder.
+pub fn asset_ast(
+    log: &Log,
+    source: &Source,
+    loader: Loader,
+    contents: &[u8],
+    options: &ParseOptions,
+) -> AST {
+    let value = match loader {
+        Loader::Base64 => base64_encode(contents),
+        Loader::DataURL => format!(
+            "data:{};base64,{}",
+            mime_type(&paths::ext(&source.absolute_path)),
+            base64_encode(contents)
+        ),
+        Loader::File => output_placeholder(OutputKind::Asset, source.index as usize),
+
+        // There's no runtime library to put a helper in, so the decoding is
+        // done inline. This needs "atob", which node has since version 16.
+        Loader::Binary => {
+            let binary_source = Source {
+                contents: format!(
+                    "export default Uint8Array.from(atob(\"{}\"), (c) => c.charCodeAt(0))",
+                    base64_encode(contents)
+                ),
+                line_offsets: Default::default(),
+                ..source.clone()
+            };
+            return parser::parse(log, &binary_source, options)
+                .expect("The decoding code can't have errors");
+        }
+        _ => panic!("The {:?} loader isn't for binary files", loader),
+    };
+    parser::lazy_export_ast(log, source, options, string_expr(0, &value))
+}
+
+fn string_expr(location: Location, text: &str) -> Expr {
+    Expr {
+        location,
+        data: Box::new(ExprKind::String {
+            value: string_to_utf16(text),
+        }),
+    }
+}
+
+// The MIME types of the files that are most often inlined as "data:" URLs.
+// Anything else is treated as an unknown binary file.
+fn mime_type(ext: &str) -> &'static str {
+    match ext {
+        ".png" => "image/png",
+        ".jpg" | ".jpeg" => "image/jpeg",
+        ".gif" => "image/gif",
+        ".webp" => "image/webp",
+        ".svg" => "image/svg+xml",
+        ".ico" => "image/x-icon",
+        ".woff" => "font/woff",
+        ".woff2" => "font/woff2",
+        ".ttf" => "font/ttf",
+        ".otf" => "font/otf",
+        ".txt" => "text/plain",
+        ".html" => "text/html",
+        ".css" => "text/css",
+        ".js" | ".mjs" | ".cjs" => "text/javascript",
+        ".json" => "application/json",
+        ".wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Default)]
+pub enum OutputFormat {
+    // "import" and "export" statements are kept as-is
+    #[default]
+    Esm = 0,
+
+    // The output is wrapped in "(function() { ... })();". The exports are
+    // only kept if there is a global name to assign them to.
+    Iife,
+
+    // Imports use "require()" and exports are getters on "exports"
+    Cjs,
+}
+
+impl OutputFormat {
+    pub fn supports_splitting(self) -> bool {
+        match self {
+            OutputFormat::Esm | OutputFormat::Iife => true,
+            OutputFormat::Cjs => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BundleOptions {
+    pub bundle: bool,
+    pub output_format: OutputFormat,
+
+    // The global variable that the exports of an IIFE are assigned to
+    pub global_name: String,
+
+    // Splitting is opt-in. It's rejected for formats that have no way of
+    // loading another chunk at run time instead of being silently ignored.
+    pub code_splitting: bool,
+
+    // The name of the global object that IIFE chunks register themselves in,
+    // and the URL prefix used to load chunks from
+    pub chunk_registry: String,
+    pub public_path: String,
+
+    // Development mode where every module is registered with a runtime
+    // registry so an external client can hot-swap individual modules
+    pub hmr: bool,
+    pub hmr_registry: String,
+
+    // Names that the minifier must not rename in any file, in addition to the
+    // exports of the entry points which are always kept
+    pub preserve_names: HashSet<String>,
+
+    pub minify: MinifyOptions,
+
+    // How "minify.syntax" rewrites the code (see "MangleOptions")
+    pub mangle: MangleOptions,
+
+    // Nothing makes source maps yet, so "validate" rejects this
+    pub source_map: bool,
+
+    // Records which files each output file is made of, for "Bundle::metafile"
+    pub metafile: bool,
+
+    // This only affects printing. Lowering happens while parsing, which has
+    // its own copy of the target.
+    pub target: Target,
+
+    // Where the output files go. At most one of these can be set, and an
+    // output file can only be used with a single entry point. If neither is
+    // set, the output of the only entry point has an empty path.
+    pub abs_output_file: String,
+    pub abs_output_dir: String,
+
+    // The directory that "[dir]" in a template is relative to. If this is
+    // empty, it's the lowest common directory of the entry points.
+    pub abs_outbase: String,
+
+    // Templates for the paths of output files relative to the output
+    // directory, without the extension. "[name]" is the file name without its
+    // extension, "[dir]" is the directory of the file relative to the lowest
+    // common directory of the entry points, and "[hash]" is a hash of the
+    // contents of the output file and of every output file it refers to.
+    pub entry_names: String,
+    pub chunk_names: String,
+    pub asset_names: String,
+
+    // With "External", the legal comments of each output file go in another
+    // file with ".LEGAL.txt" appended to its path
+    pub legal_comments: LegalComments,
+
+    // Rewrites the import paths that are left external in every output file,
+    // see "ImportPathRewriter"
+    pub rewrite_external_paths: Option<ImportPathRewriter>,
+}
+
+impl Default for BundleOptions {
+    fn default() -> Self {
+        Self {
+            bundle: false,
+            output_format: OutputFormat::Esm,
+            global_name: String::new(),
+            code_splitting: false,
+            chunk_registry: runtime::DEFAULT_CHUNK_REGISTRY.to_owned(),
+            public_path: String::new(),
+            hmr: false,
+            hmr_registry: runtime::DEFAULT_HMR_REGISTRY.to_owned(),
+            preserve_names: HashSet::new(),
+            minify: MinifyOptions::default(),
+            mangle: MangleOptions::default(),
+            source_map: false,
+            metafile: false,
+            target: Target::default(),
+            abs_output_file: String::new(),
+            abs_output_dir: String::new(),
+            abs_outbase: String::new(),
+            entry_names: "[dir]/[name]".to_owned(),
+            chunk_names: "[name]-[hash]".to_owned(),
+            asset_names: "[name]-[hash]".to_owned(),
+            legal_comments: LegalComments::default(),
+            rewrite_external_paths: None,
+        }
+    }
+}
+
+impl BundleOptions {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.source_map {
+            return Err(Error::Config("Source maps aren't supported yet".to_owned()));
+        }
+        if self.hmr {
+            if !self.bundle {
+                return Err(Error::Config(
+                    "HMR mode currently only works when bundling".to_owned(),
+                ));
+            }
+            if self.output_format == OutputFormat::Cjs {
+                return Err(Error::Config(
+                    "HMR mode needs the Esm or Iife format".to_owned(),
+                ));
+            }
+            if self.code_splitting {
+                return Err(Error::Config(
+                    "HMR mode puts every module in the output file of its entry point, \
+                     so it can't be used with splitting"
+                        .to_owned(),
+                ));
+            }
+        }
+        if self.code_splitting {
+            if !self.bundle {
+                return Err(Error::Config(
+                    "Splitting currently only works when bundling".to_owned(),
+                ));
+            }
+            if !self.output_format.supports_splitting() {
+                return Err(Error::Config(format!(
+                    "Splitting is not supported for the {:?} format, only for the Esm and Iife formats",
+                    self.output_format
+                )));
+            }
+        }
+        if !self.global_name.is_empty() {
+            if self.output_format != OutputFormat::Iife {
+                return Err(Error::Config(
+                    "A global name can only be used with the Iife format".to_owned(),
+                ));
+            }
+            if !is_identifier(&self.global_name)
+                || Token::try_from(self.global_name.as_str()).is_ok()
+            {
+                return Err(Error::Config(format!(
+                    "Invalid global name: {}",
+                    self.global_name
+                )));
+            }
+        }
+        if !self.abs_output_file.is_empty() && !self.abs_output_dir.is_empty() {
+            return Err(Error::Config(
+                "Cannot use both an output file and an output directory".to_owned(),
+            ));
+        }
+        for template in &[&self.entry_names, &self.chunk_names, &self.asset_names] {
+            validate_name_template(template)?;
+        }
+        if self.legal_comments == LegalComments::External
+            && self.abs_output_file.is_empty()
+            && self.abs_output_dir.is_empty()
+        {
+            return Err(Error::Config(
+                "Cannot write legal comments to a separate file without an output path \
+                 (use --outfile or --outdir)"
+                    .to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Only IIFE output needs a loader. ESM chunks load each other natively.
+    pub fn chunk_loader(&self) -> Option<ChunkLoader> {
+        if self.code_splitting && self.output_format == OutputFormat::Iife {
+            Some(ChunkLoader::iife(&self.chunk_registry, &self.public_path))
+        } else {
+            None
+        }
+    }
+}
+
+// The public API of an entry point. Comparing this between two builds shows
+// whether a change added or removed an export, without having to diff the
+// generated code.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryPointExports {
+    pub entry_point: String,
+
+    // The export names, sorted so that the list is stable across builds
+    pub exports: BTreeSet<String>,
+
+    // Files re-exported with "export * from", whose exports are only known
+    // after linking
+    pub export_stars: Vec<String>,
+}
+
+impl EntryPointExports {
+    pub fn new(entry_point: String, ast: &AST) -> Self {
+        Self {
+            entry_point,
+            exports: ast.named_exports.keys().cloned().collect(),
+            export_stars: ast.export_stars.iter().map(|p| p.text.clone()).collect(),
+        }
+    }
+
+    // Returns the exports that were added and removed since "previous"
+    pub fn diff<'a>(&'a self, previous: &'a Self) -> (Vec<&'a str>, Vec<&'a str>) {
+        let added = self.exports.difference(&previous.exports);
+        let removed = previous.exports.difference(&self.exports);
+        (
+            added.map(String::as_str).collect(),
+            removed.map(String::as_str).collect(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OutputFile {
+    pub path: String,
+    pub contents: Vec<u8>,
+
+    // These are for the metafile. "bytes_in_output" is how many bytes came
+    // from each file by source index, and is only filled in if the options
+    // ask for a metafile.
+    pub entry_point: Option<usize>,
+    pub bytes_in_output: Vec<(usize, usize)>,
+
+    // The files that have code in this output file, by source index
+    pub source_indices: Vec<usize>,
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+pub enum OutputKind {
+    Entry,
+    Chunk,
+    Asset,
+
+    // The code that several entry points and chunks share. These are
+    // numbered instead of using the source index of a file.
+    Common,
+}
+
+// Output files refer to each other before they have names, since a name can
+// depend on a hash of the contents. This goes where the path of another output
+// file will be, and is replaced once every output file has been named.
+pub fn output_placeholder(kind: OutputKind, source_index: usize) -> String {
+    let kind = match kind {
+        OutputKind::Entry => "ENTRY",
+        OutputKind::Chunk => "CHUNK",
+        OutputKind::Asset => "ASSET",
+        OutputKind::Common => "COMMON",
+    };
+    format!("__ESBUILD_{}_{}__", kind, source_index)
+}
+
+// An output file that doesn't have a path yet
+struct PendingOutput {
+    source_index: usize,
+    kind: OutputKind,
+    ext: String,
+    contents: Vec<u8>,
+    entry_point: Option<usize>,
+    bytes_in_output: Vec<(usize, usize)>,
+    source_indices: Vec<usize>,
+
+    // How other output files refer to this one, if they can
+    placeholder: Option<String>,
+
+    // These go in a file of their own next to this one
+    legal_comments: Vec<String>,
+}
+
+// Where a placeholder is in the contents of an output file, and the output
+// file it stands for
+struct PlaceholderRef {
+    start: usize,
+    end: usize,
+    output: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BuildResult {
+    pub output_files: Vec<OutputFile>,
+
+    // One entry per entry point, in the order the entry points were given
+    pub entry_point_exports: Vec<EntryPointExports>,
+}
+
+// The files of a build after they have been found and parsed. Each file is
+// at its source index in these lists. There's no runtime code yet, but index 0
+// is still reserved for it so that no symbol of a file in the bundle has the
+// same reference as "INVALID_REF".
+#[derive(Debug)]
+pub struct Bundle {
+    pub sources: Vec<Source>,
+
+    // The trees are shared with the cache of a watch mode build, so they are
+    // never changed. The linker changes copies of single parts instead (see
+    // "FileOverlay").
+    pub files: Vec<Arc<AST>>,
+
+    // Where each import path of each file resolved to, including the paths
+    // of "@import" rules in CSS files
+    pub resolved_imports: Vec<HashMap<String, usize>>,
+    pub entry_points: Vec<usize>,
+
+    // CSS files have their stylesheet here and an empty tree in "files"
+    pub stylesheets: Vec<Option<Stylesheet>>,
+
+    // The local names of CSS modules files and what each one was renamed to.
+    // Their trees in "files" export the new names.
+    pub local_names: Vec<Vec<(String, String)>>,
+
+    // Files that use the "file" loader have their contents here since they
+    // are copied to the output directory
+    pub assets: Vec<Option<Vec<u8>>>,
+
+    // The size of each file in bytes. Binary files don't keep their contents
+    // in "sources", so this is where the metafile gets it from.
+    pub file_sizes: Vec<usize>,
+
+    // Files that the "sideEffects" field of their "package.json" file says
+    // can be left out if nothing they export is used
+    pub ignore_if_unused: Vec<bool>,
+
+    // The source maps that extension hooks returned with the code they made
+    // from a file. Nothing reads them until source maps are supported.
+    pub hook_source_maps: Vec<Option<String>>,
+}
+
+// Parses the entry points and, when bundling, every file they import. A file
+// that is imported more than once is only parsed once. Files that couldn't be
+// read or parsed are left empty after logging an error, so the bundle must
+// not be compiled if there were any errors.
+//
+// Files whose tree is in the cache skip parsing entirely.
+pub fn scan_bundle<F: FileSystem>(
+    log: &Log,
+    resolver: &Resolver<F>,
+    entry_paths: &[String],
+    options: &ParseOptions,
+    cache: Option<&dyn ParseCache>,
+) -> Bundle {
+    let mut sources = SourceRegistry::new();
+    let runtime_index = sources.add_virtual("<runtime>", String::new());
+    debug_assert_eq!(runtime_index, RUNTIME_SOURCE_INDEX);
+    let mut s = Scanner {
+        resolver,
+        options,
+        bundle: Bundle {
+            files: vec![Arc::new(empty_ast(&sources[runtime_index], options))],
+            sources: vec![],
+            resolved_imports: vec![HashMap::new()],
+            entry_points: vec![],
+            stylesheets: vec![None],
+            local_names: vec![vec![]],
+            assets: vec![None],
+            file_sizes: vec![0],
+            ignore_if_unused: vec![false],
+            hook_source_maps: vec![None],
+        },
+        tsconfig_options: HashMap::new(),
+        hook_options: HashMap::new(),
+        sources,
+        remaining: vec![],
+    };
+
+    for path in entry_paths {
+        let source_index = s.add_file(path);
+        s.bundle.entry_points.push(source_index);
+    }
+
+    // Files are parsed in waves. Every file found while resolving the imports
+    // of one wave is parsed in the next, and the files of a wave are parsed in
+    // parallel since each one already has its source index.
+    while !s.remaining.is_empty() {
+        let mut wave = std::mem::take(&mut s.remaining);
+
+        // Binary files don't need parsing, so they are done as they are read
+        wave.retain(|&source_index| !s.load_binary_file(log, source_index));
+        wave.retain(|&source_index| s.read_file(log, source_index));
+        wave.retain(|&source_index| s.run_extension_hook(log, source_index));
+        for &source_index in &wave {
+            s.add_tsconfig_options(source_index);
+        }
+
+        // Stylesheets are quick to parse, so they aren't worth a thread
+        wave.retain(|&source_index| {
+            let source = &s.sources[source_index];
+            let options = s.file_options(source);
+            let stylesheet = match loader_for_path(&source.absolute_path, options) {
+                Some(Loader::CSS) => css::parse(log, source),
+                Some(Loader::LocalCSS) => {
+                    let (stylesheet, names, ast) = parse_local_css(log, source, options);
+                    s.bundle.local_names[source_index] = names;
+                    s.bundle.files[source_index] = Arc::new(ast);
+                    stylesheet
+                }
+                _ => return true,
+            };
+            s.resolve_css_imports(log, source_index, &stylesheet);
+            s.bundle.stylesheets[source_index] = Some(stylesheet);
+            false
+        });
+
+        let sources: Vec<(&Source, &ParseOptions)> = wave
+            .iter()
+            .map(|&i| {
+                let source = &s.sources[i];
+                (source, s.file_options(source))
+            })
+            .collect();
+        let asts = parse_sources_parallel(log, &sources, cache);
+        for (source_index, ast) in wave.into_iter().zip(asts) {
+            if let Some(ast) = ast {
+                s.resolve_imports(log, source_index, &ast);
+                s.bundle.files[source_index] = ast;
+            }
+        }
+    }
+
+    s.bundle.sources = s.sources.into_sources();
+    s.bundle
+}
+
+struct Scanner<'a, F: FileSystem> {
+    resolver: &'a Resolver<F>,
+    options: &'a ParseOptions,
+
+    // The options for the files that use each "tsconfig.json", keyed by its
+    // path
+    tsconfig_options: HashMap<String, ParseOptions>,
+
+    // The options for the files that an extension hook turned into code,
+    // which use the loader the hook returned
+    hook_options: HashMap<usize, ParseOptions>,
+    bundle: Bundle,
+
+    // These are moved into the bundle once every file has been found
+    sources: SourceRegistry,
+    remaining: Vec<usize>,
+}
+
+impl<'a, F: FileSystem> Scanner<'a, F> {
+    fn add_file(&mut self, absolute_path: &str) -> usize {
+        let (source_index, is_new) = self.sources.add_file(self.resolver.fs(), absolute_path);
+        if is_new {
+            self.add_empty_file();
+            self.remaining.push(source_index);
+        }
+        source_index
+    }
+
+    // A disabled file is an empty module. It has a source index of its own
+    // since the file itself may also be imported from somewhere the "browser"
+    // map doesn't apply to.
+    fn add_disabled_file(&mut self, path: &str) -> usize {
+        let (source_index, is_new) = self.sources.add_disabled_file(self.resolver.fs(), path);
+        if is_new {
+            self.add_empty_file();
+        }
+        source_index
+    }
+
+    // Makes room in the bundle for the source that was just added
+    fn add_empty_file(&mut self) {
+        let source = &self.sources[self.sources.len() - 1];
+        self.bundle
+            .files
+            .push(Arc::new(empty_ast(source, self.options)));
+        self.bundle.resolved_imports.push(HashMap::new());
+        self.bundle.stylesheets.push(None);
+        self.bundle.local_names.push(vec![]);
+        self.bundle.assets.push(None);
+        self.bundle.file_sizes.push(0);
+        self.bundle.ignore_if_unused.push(false);
+        self.bundle.hook_source_maps.push(None);
+    }
+
+    fn add_tsconfig_options(&mut self, source_index: usize) {
+        let source = &self.sources[source_index];
+        if let Some(tsconfig) = self.resolver.tsconfig(&source.absolute_path) {
+            let options = self.options;
+            self.tsconfig_options
+                .entry(tsconfig.abs_path.clone())
+                .or_insert_with(|| tsconfig.parse_options(options));
+        }
+    }
+
+    fn file_options(&self, source: &Source) -> &ParseOptions {
+        if let Some(options) = self.hook_options.get(&(source.index as usize)) {
+            return options;
+        }
+        match self.resolver.tsconfig(&source.absolute_path) {
+            Some(tsconfig) => &self.tsconfig_options[&tsconfig.abs_path],
+            None => self.options,
+        }
+    }
+
+    // Returns false if the file couldn't be read. The error has already been
+    // logged.
+    fn read_file(&mut self, log: &Log, source_index: usize) -> bool {
+        let is_read = self.sources.read_contents(self.resolver.fs(), source_index);
+        let source = &self.sources[source_index];
+        if !is_read {
+            log.add_error(
+                &Source::default(),
+                0,
+                format!("Could not read from file: {}", source.absolute_path),
+            );
+            return false;
+        }
+        self.bundle.file_sizes[source_index] = source.contents.len();
+        true
+    }
+
+    // Replaces the contents of a file that has an extension hook with the code
+    // that the hook made from them. The file is parsed with the options of its
+    // directory as usual, except that the extension uses the loader the hook
+    // returned. Returns false if the hook failed. The error has already been
+    // logged.
+    fn run_extension_hook(&mut self, log: &Log, source_index: usize) -> bool {
+        let source = &self.sources[source_index];
+        let ext = paths::ext(&source.absolute_path);
+        let hook = match self.options.extension_hooks.get(&ext) {
+            Some(hook) => hook,
+            None => return true,
+        };
+        let output = match hook(&source.absolute_path, &source.contents) {
+            Ok(output) if output.loader.is_binary() => {
+                log.add_error(
+                    &Source::default(),
+                    0,
+                    format!(
+                        "The hook for \"{}\" files returned the {:?} loader, which doesn't read code: {}",
+                        ext, output.loader, source.pretty_path
+                    ),
+                );
+                return false;
+            }
+            Ok(output) => output,
+            Err(text) => {
+                log.add_error(
+                    &Source::default(),
+                    0,
+                    format!("Could not load {}: {}", source.pretty_path, text),
+                );
+                return false;
+            }
+        };
+
+        self.add_tsconfig_options(source_index);
+        let mut options = self.file_options(&self.sources[source_index]).clone();
+        options.loaders.insert(ext, output.loader);
+        self.hook_options.insert(source_index, options);
+        self.sources.set_contents(source_index, output.contents);
+        self.bundle.hook_source_maps[source_index] = output.source_map;
+        true
+    }
+
+    // Returns false if the file doesn't use a binary loader. Binary files
+    // don't import anything, so there's nothing left to do once they're read.
+    fn load_binary_file(&mut self, log: &Log, source_index: usize) -> bool {
+        let source = &self.sources[source_index];
+        let ext = paths::ext(&source.absolute_path);
+        if self.options.extension_hooks.get(&ext).is_some() {
+            return false;
+        }
+        let loader = match loader_for_path(&source.absolute_path, self.options) {
+            Some(loader) if loader.is_binary() => loader,
+            _ => return false,
+        };
+        let contents = match self.resolver.fs().read_file_bytes(&source.absolute_path) {
+            Some(contents) => contents,
+            None => {
+                log.add_error(
+                    &Source::default(),
+                    0,
+                    format!("Could not read from file: {}", source.absolute_path),
+                );
+                return true;
+            }
+        };
+        self.bundle.files[source_index] =
+            Arc::new(asset_ast(log, source, loader, &contents, self.options));
+        self.bundle.file_sizes[source_index] = contents.len();
+        if loader == Loader::File {
+            self.bundle.assets[source_index] = Some(contents);
+        }
+        true
+    }
+
+    // Without bundling, the import paths are left as they are
+    fn resolve_imports(&mut self, log: &Log, source_index: usize, ast: &AST) {
+        if !self.options.is_bundling {
+            return;
+        }
+        let source = &self.sources[source_index];
+
+        let mut resolved_paths = vec![];
+        let mut seen = HashSet::new();
+        for part in &ast.parts {
+            for import_path in &part.import_paths {
+                let text = &import_path.path.text;
+                if !seen.insert(text) {
+                    continue;
+                }
+                match self
+                    .resolver
+                    .resolve(&source.absolute_path, text, import_path.kind)
+                {
+                    // External imports stay unresolved, so the linker leaves
+                    // them in the output
+                    Some(result) if result.is_external => {}
+                    Some(result) => resolved_paths.push((text.clone(), result)),
+                    None => log.add_range_error(
+                        source,
+                        source.range_of_string(import_path.path.loc),
+                        format!("Could not resolve {:?}", text),
+                    ),
+                }
+            }
+        }
+
+        for (text, result) in resolved_paths {
+            let other = if result.is_disabled {
+                self.add_disabled_file(&result.path)
+            } else {
+                self.add_file(&result.path)
+            };
+            self.bundle.ignore_if_unused[other] = result.ignore_if_unused;
+            self.bundle.resolved_imports[source_index].insert(text, other);
+        }
+    }
+
+    // Paths in "@import" rules are relative even without a "./" since they
+    // are URLs. Ones with a scheme such as "https:" are left alone.
+    fn resolve_css_imports(&mut self, log: &Log, source_index: usize, stylesheet: &Stylesheet) {
+        if !self.options.is_bundling {
+            return;
+        }
+        let source = &self.sources[source_index];
+
+        let mut resolved_paths = vec![];
+        for rule in &stylesheet.rules {
+            let (path, range) = match rule {
+                Rule::Import { path, range, .. } if !css::is_external_url(path) => (path, range),
+                _ => continue,
+            };
+            let relative_path = if path.starts_with('.') || path.starts_with('/') {
+                path.clone()
+            } else {
+                format!("./{}", path)
+            };
+            match self
+                .resolver
+                .resolve(&source.absolute_path, &relative_path, ImportKind::Stmt)
+            {
+                Some(result) if result.is_external => {}
+                Some(result) if result.is_disabled || paths::ext(&result.path) == ".css" => {
+                    resolved_paths.push((path.clone(), result))
+                }
+                Some(_) => log.add_range_error(
+                    source,
+                    range.clone(),
+                    format!("Cannot import {:?} into a CSS file", path),
+                ),
+                None => log.add_range_error(
+                    source,
+                    range.clone(),
+                    format!("Could not resolve {:?}", path),
+                ),
+            }
+        }
+
+        for (text, result) in resolved_paths {
+            let other = if result.is_disabled {
+                self.add_disabled_file(&result.path)
+            } else {
+                self.add_file(&result.path)
+            };
+            self.bundle.resolved_imports[source_index].insert(text, other);
+        }
+    }
+}
+
+// Parses files that have already been given their source indices, using the
+// loader that matches each file's extension. Each thread logs to its own copy
+// of the log, which sends the messages on to "log" as soon as they happen.
+//
+// With a cache, each file is looked up in the cache first. A file that is
+// parsed is only stored in the cache if it didn't log anything, so its
+// messages are collected and sent on to "log" once it's done.
+// Each file is parsed with its own options since files can be in directories
+// with different "tsconfig.json" files
+fn parse_sources_parallel(
+    log: &Log,
+    sources: &[(&Source, &ParseOptions)],
+    cache: Option<&dyn ParseCache>,
+) -> Vec<Option<Arc<AST>>> {
+    let sender = log.clone_sender();
+    sources
+        .par_iter()
+        .map_init(
+            || Log::from_sender(sender.clone()),
+            |log, &(source, options)| {
+                let cache = match cache {
+                    Some(cache) => cache,
+                    None => return parse_source(log, source, options).map(Arc::new),
+                };
+                if let Some(ast) = cache.load(source, options) {
+                    return Some(ast);
+                }
+                let file_log = Log::default();
+                let ast = parse_source(&file_log, source, options).map(Arc::new);
+                let msgs = file_log.take_msgs();
+                if let (Some(ast), true) = (&ast, msgs.is_empty()) {
+                    cache.store(source, options, ast);
+                }
+                for msg in msgs {
+                    log.add_msg(msg);
+                }
+                ast
+            },
+        )
+        .collect()
+}
+
+fn parse_source(log: &Log, source: &Source, options: &ParseOptions) -> Option<AST> {
+    match loader_for_path(&source.absolute_path, options) {
+        Some(loader) => parse_file(log, source, loader, options),
+        None => {
+            log.add_error(
+                &Source::default(),
+                0,
+                format!("File extension not supported: {}", source.pretty_path),
+            );
+            None
+        }
+    }
+}
+
+// This is what the "Reference" design is for. The position of each file in
+// "sources" becomes its source index, which is the outer index of every symbol
+// the file declares. Files can then be parsed on a thread pool without ever
+// creating the same reference, and their symbol maps are merged afterward by
+// putting each one at its outer index. Files that couldn't be parsed are left
+// empty after logging an error.
+pub fn parse_files_parallel(
+    log: &Log,
+    mut sources: Vec<Source>,
+    options: &ParseOptions,
+) -> (Vec<AST>, SymbolMap) {
+    for (source_index, source) in sources.iter_mut().enumerate() {
+        source.index = source_index as u32;
+    }
+
+    let source_refs: Vec<(&Source, &ParseOptions)> =
+        sources.iter().map(|source| (source, options)).collect();
+    let files: Vec<AST> = parse_sources_parallel(log, &source_refs, None)
+        .into_iter()
+        .zip(&sources)
+        .map(|(ast, source)| match ast {
+            Some(ast) => Arc::unwrap_or_clone(ast),
+            None => empty_ast(source, options),
+        })
+        .collect();
+
+    let mut symbols = SymbolMap::new(files.len());
+    for (source_index, ast) in files.iter().enumerate() {
+        symbols.outer[source_index] = ast.symbols.outer[source_index].clone();
+    }
+    (files, symbols)
+}
+
+// Stands in for a file that couldn't be parsed
+fn empty_ast(source: &Source, options: &ParseOptions) -> AST {
+    let source = Source {
+        contents: String::new(),
+        line_offsets: Default::default(),
+        ..source.clone()
+    };
+    parser::parse(&Log::default(), &source, options).expect("An empty file can't have errors")
+}
+
+impl Bundle {
+    // Generates one output file per entry point. Without bundling, each entry
+    // point is printed on its own like "transform" does. Otherwise it's linked
+    // together with everything it imports.
+    pub fn compile(&self, log: &Log, options: &BundleOptions) -> BuildResult {
+        let mut result = BuildResult::default();
+
+        let files: Vec<LinkerFile> = self
+            .files
+            .iter()
+            .zip(&self.resolved_imports)
+            .zip(&self.ignore_if_unused)
+            .map(|((ast, resolved_imports), ignore_if_unused)| LinkerFile {
+                ast,
+                resolved_imports: resolved_imports.clone(),
+                ignore_if_unused: *ignore_if_unused,
+            })
+            .collect();
+        let mut chunks = if options.bundle && options.code_splitting {
+            self.compute_chunks(&files, options)
+        } else {
+            Chunks::default()
+        };
+
+        // A common chunk exports whatever the other output files import from
+        // it, so they are all linked once to find that out first
+        if !chunks.common.is_empty() {
+            let mut linked: Vec<ChunkId> = self
+                .entry_points
+                .iter()
+                .chain(chunks.paths.keys())
+                .filter(|&&source_index| self.stylesheets[source_index].is_none())
+                .map(|&source_index| ChunkId::Root(source_index))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            linked.extend((0..chunks.common.len()).map(ChunkId::Common));
+            let imports: Vec<BTreeSet<Reference>> = linked
+                .par_iter()
+                .map(|&chunk| linker::chunk_imports(&self.sources, &files, chunk, &chunks, options))
+                .collect();
+            linker::name_chunk_exports(&files, &mut chunks, imports.into_iter().flatten());
+        }
+
+        // The entry points don't depend on each other, so each one is linked
+        // on its own thread
+        let sender = log.clone_sender();
+        let entry_point_outputs: Vec<_> = self
+            .entry_points
+            .par_iter()
+            .map_init(
+                || Log::from_sender(sender.clone()),
+                |log, &entry_point| {
+                    self.compile_entry_point(log, &files, &chunks, entry_point, options)
+                },
+            )
+            .collect();
+        let mut outputs = vec![];
+        for (entry_outputs, exports) in entry_point_outputs {
+            outputs.extend(entry_outputs);
+            result.entry_point_exports.extend(exports);
+        }
+
+        let output_dir = output_dir(options);
+        let mut chunk_paths: Vec<_> = chunks.paths.iter().collect();
+        chunk_paths.sort();
+        for (&source_index, chunk) in chunk_paths {
+            // An ESM entry point is loaded from its own output file
+            if chunks.registry.is_none() && self.entry_points.contains(&source_index) {
+                continue;
+            }
+            if output_dir.is_empty() {
+                log.add_error(
+                    &Source::default(),
+                    0,
+                    format!(
+                        "Cannot write the chunk for {} without an output path \
+                         (use --outfile or --outdir)",
+                        self.sources[source_index].pretty_path
+                    ),
+                );
+                continue;
+            }
+            let output = match &chunks.registry {
+                // The chunk's module body uses the CommonJS "module" and
+                // "exports" that the registry passes to it
+                Some(registry) => {
+                    let chunk_options = BundleOptions {
+                        output_format: OutputFormat::Cjs,
+                        global_name: String::new(),
+                        ..options.clone()
+                    };
+                    linker::link(
+                        log,
+                        &self.sources,
+                        &files,
+                        ChunkId::Root(source_index),
+                        &chunks,
+                        &chunk_options,
+                    )
+                    .map(|output| LinkOutput {
+                        js: runtime::iife_define_chunk(registry, chunk, &output.js),
+                        ..output
+                    })
+                }
+                None => linker::link(
+                    log,
+                    &self.sources,
+                    &files,
+                    ChunkId::Root(source_index),
+                    &chunks,
+                    options,
+                ),
+            };
+            if let Some(output) = output {
+                outputs.push(PendingOutput {
+                    source_index,
+                    kind: OutputKind::Chunk,
+                    ext: ".js".to_owned(),
+                    contents: output.js.into_bytes(),
+                    entry_point: None,
+                    bytes_in_output: output.bytes_in_output,
+                    source_indices: output.source_indices,
+                    placeholder: Some(chunk.clone()),
+                    legal_comments: output.legal_comments,
+                });
+            }
+        }
+        for (index, common) in chunks.common.iter().enumerate() {
+            if output_dir.is_empty() {
+                log.add_error(
+                    &Source::default(),
+                    0,
+                    "Cannot write the code that output files share without an output path \
+                     (use --outdir)"
+                        .to_owned(),
+                );
+                break;
+            }
+            let chunk = ChunkId::Common(index);
+            if let Some(output) = linker::link(log, &self.sources, &files, chunk, &chunks, options)
+            {
+                outputs.push(PendingOutput {
+                    source_index: common.roots[0],
+                    kind: OutputKind::Common,
+                    ext: ".js".to_owned(),
+                    contents: output.js.into_bytes(),
+                    entry_point: None,
+                    bytes_in_output: output.bytes_in_output,
+                    source_indices: output.source_indices,
+                    placeholder: Some(common.placeholder.clone()),
+                    legal_comments: output.legal_comments,
+                });
+            }
+        }
+
+        // Files that use the "file" loader are copied to the output directory
+        for (source_index, (source, contents)) in self.sources.iter().zip(&self.assets).enumerate()
+        {
+            let contents = match contents {
+                Some(contents) => contents,
+                None => continue,
+            };
+            if output_dir.is_empty() {
+                log.add_error(
+                    &Source::default(),
+                    0,
+                    format!(
+                        "Cannot copy {} without an output path (use --outfile or --outdir)",
+                        source.pretty_path
+                    ),
+                );
+                continue;
+            }
+            outputs.push(PendingOutput {
+                source_index,
+                kind: OutputKind::Asset,
+                ext: paths::ext(&source.absolute_path),
+                contents: contents.clone(),
+                entry_point: None,
+                bytes_in_output: vec![(source_index, contents.len())],
+                source_indices: vec![source_index],
+                placeholder: Some(output_placeholder(OutputKind::Asset, source_index)),
+                legal_comments: vec![],
+            });
+        }
+
+        result.output_files = self.name_output_files(log, outputs, &chunks, options);
+        result
+    }
+
+    // The output files of one entry point: its code, and the CSS it imports
+    // when bundling. The exports are missing if it couldn't be linked.
+    fn compile_entry_point(
+        &self,
+        log: &Log,
+        files: &[LinkerFile],
+        chunks: &Chunks,
+        entry_point: usize,
+        options: &BundleOptions,
+    ) -> (Vec<PendingOutput>, Option<EntryPointExports>) {
+        let mut outputs = vec![];
+        let source = &self.sources[entry_point];
+        let ast = &self.files[entry_point];
+
+        if let Some(stylesheet) = &self.stylesheets[entry_point] {
+            let stylesheet = if options.bundle {
+                self.bundle_css(
+                    &self.css_files_in_import_order(entry_point),
+                    &HashMap::new(),
+                )
+            } else {
+                stylesheet.clone()
+            };
+            let css_files = if options.bundle {
+                self.css_files_in_import_order(entry_point)
+            } else {
+                vec![entry_point]
+            };
+            outputs.push(PendingOutput {
+                source_index: entry_point,
+                kind: OutputKind::Entry,
+                ext: ".css".to_owned(),
+                contents: css::print(&stylesheet, options.minify.whitespace).into_bytes(),
+                entry_point: Some(entry_point),
+                bytes_in_output: self.css_bytes_in_output(&css_files, &HashMap::new(), options),
+                source_indices: css_files,
+                placeholder: None,
+                legal_comments: vec![],
+            });
+            let exports = EntryPointExports::new(source.absolute_path.clone(), ast);
+            return (outputs, Some(exports));
+        }
+
+        let mut output = if options.hmr {
+            let outbase = self.outbase(options);
+            linker::link_hmr(&self.sources, files, entry_point, &outbase, options)
+        } else if options.bundle {
+            let chunk = ChunkId::Root(entry_point);
+            match linker::link(log, &self.sources, files, chunk, chunks, options) {
+                Some(output) => output,
+                None => return (outputs, None),
+            }
+        } else {
+            let js = print_file(AST::clone(ast), options);
+            let bytes_in_output = if options.metafile {
+                vec![(entry_point, js.len())]
+            } else {
+                vec![]
+            };
+            let legal_comments = if options.legal_comments == LegalComments::External {
+                printer::legal_comments(ast.parts.iter().map(|part| part.stmts.as_slice()))
+            } else {
+                vec![]
+            };
+            LinkOutput {
+                js,
+                bytes_in_output,
+                source_indices: vec![entry_point],
+                legal_comments,
+            }
+        };
+
+        // IIFE chunks are loaded through a registry that the entry point
+        // has to install first
+        if let (Some(loader), false) = (options.chunk_loader(), chunks.paths.is_empty()) {
+            output.js.insert_str(0, &loader.code);
+            if options.metafile {
+                output
+                    .bytes_in_output
+                    .insert(0, (RUNTIME_SOURCE_INDEX, loader.len()));
+            }
+        }
+
+        outputs.push(PendingOutput {
+            source_index: entry_point,
+            kind: OutputKind::Entry,
+            ext: ".js".to_owned(),
+            contents: output.js.into_bytes(),
+            entry_point: Some(entry_point),
+            bytes_in_output: output.bytes_in_output,
+            source_indices: output.source_indices,
+            placeholder: Some(output_placeholder(OutputKind::Entry, entry_point)),
+            legal_comments: output.legal_comments,
+        });
+        let exports = Some(EntryPointExports::new(source.absolute_path.clone(), ast));
+
+        // The CSS imported by a bundle goes in a file next to it
+        let css_files = if options.bundle {
+            self.css_files_in_import_order(entry_point)
+        } else {
+            vec![]
+        };
+        if !css_files.is_empty() {
+            if options.abs_output_file.is_empty() && options.abs_output_dir.is_empty() {
+                log.add_error(
+                    &Source::default(),
+                    0,
+                    format!(
+                        "Cannot write the CSS imported by {} without an output path \
+                         (use --outfile or --outdir)",
+                        source.pretty_path
+                    ),
+                );
+                return (outputs, exports);
+            }
+            let shaken = self.shake_local_css(files, entry_point, &css_files);
+            let stylesheet = self.bundle_css(&css_files, &shaken);
+            outputs.push(PendingOutput {
+                source_index: entry_point,
+                kind: OutputKind::Entry,
+                ext: ".css".to_owned(),
+                contents: css::print(&stylesheet, options.minify.whitespace).into_bytes(),
+                entry_point: None,
+                bytes_in_output: self.css_bytes_in_output(&css_files, &shaken, options),
+                source_indices: css_files,
+                placeholder: None,
+                legal_comments: vec![],
+            });
+        }
+        (outputs, exports)
+    }
+
+    // Finds the files that are loaded with "import()" by code that ends up
+    // in an output file, including code in other chunks. Each one gets a
+    // chunk, which is referred to by a placeholder until the output files
+    // are named.
+    fn compute_chunks(&self, files: &[LinkerFile], options: &BundleOptions) -> Chunks {
+        let mut chunks = Chunks {
+            registry: match options.output_format {
+                OutputFormat::Iife => Some(options.chunk_registry.clone()),
+                _ => None,
+            },
+            ..Chunks::default()
+        };
+
+        // The files that the output file of each entry point and chunk needs
+        let mut live_files = vec![];
+        let mut roots = self.entry_points.clone();
+        roots.reverse();
+        while let Some(root) = roots.pop() {
+            let shaking = linker::tree_shake(files, &[root], true);
+            for (source_index, file) in files.iter().enumerate() {
+                for (part_index, part) in file.ast.parts.iter().enumerate() {
+                    if !shaking.is_part_live[source_index][part_index] {
+                        continue;
+                    }
+                    for import_path in &part.import_paths {
+                        if import_path.kind != ImportKind::Dynamic {
+                            continue;
+                        }
+                        let other = match file.resolve(&import_path.path) {
+                            Some(other) => other,
+                            None => continue,
+                        };
+                        if chunks.paths.contains_key(&other) || self.stylesheets[other].is_some() {
+                            continue;
+                        }
+                        // An ESM entry point is a module already, so it can be
+                        // loaded as it is
+                        if chunks.registry.is_none() && self.entry_points.contains(&other) {
+                            let placeholder = output_placeholder(OutputKind::Entry, other);
+                            chunks.paths.insert(other, placeholder);
+                            continue;
+                        }
+                        let placeholder = output_placeholder(OutputKind::Chunk, other);
+                        chunks.paths.insert(other, placeholder);
+                        roots.push(other);
+                    }
+                }
+            }
+            if self.stylesheets[root].is_none() {
+                live_files.push((root, shaking.is_file_live));
+            }
+        }
+
+        if chunks.registry.is_none() {
+            self.compute_common_chunks(files, &live_files, &mut chunks);
+        }
+        chunks
+    }
+
+    // Puts every file that only one output file needs in the chunk of that
+    // output file, and every file that several of them need in the common
+    // chunk of those. A common chunk only imports from common chunks that
+    // more output files need, so they can't import each other in a cycle.
+    //
+    // Files that can be lazy in some output file are left out, and so are the
+    // entry points and split files themselves. See "Chunks".
+    fn compute_common_chunks(
+        &self,
+        files: &[LinkerFile],
+        live_files: &[(usize, Vec<bool>)],
+        chunks: &mut Chunks,
+    ) {
+        let is_wrapped = linker::find_wrapped_files(files, &[], true);
+        let might_be_lazy = linker::find_lazy_files(files, &is_wrapped, &[], true);
+        let mut common_of_roots: BTreeMap<Vec<usize>, usize> = BTreeMap::new();
+        for source_index in 0..files.len() {
+            let is_root = live_files.iter().any(|(root, _)| *root == source_index);
+            if is_root || might_be_lazy[source_index] || self.stylesheets[source_index].is_some() {
+                continue;
+            }
+            let roots: Vec<usize> = live_files
+                .iter()
+                .filter(|(_, is_file_live)| is_file_live[source_index])
+                .map(|(root, _)| *root)
+                .collect();
+            let chunk = match roots.as_slice() {
+                [] => continue,
+                [root] => ChunkId::Root(*root),
+                _ => {
+                    let next_index = chunks.common.len();
+                    let index = *common_of_roots.entry(roots.clone()).or_insert(next_index);
+                    if index == next_index {
+                        chunks.common.push(CommonChunk {
+                            roots,
+                            placeholder: output_placeholder(OutputKind::Common, index),
+                            exports: BTreeMap::new(),
+                        });
+                    }
+                    ChunkId::Common(index)
+                }
+            };
+            chunks.chunk_of_file.insert(source_index, chunk);
+        }
+    }
+
+    // The import graph of the bundle, with the same source indices. Imports
+    // that weren't resolved are external, since any other import that can't
+    // be resolved is an error. Without bundling, every import is external.
+    pub fn module_graph(&self) -> ModuleGraph {
+        let mut graph = ModuleGraph::default();
+        for source in &self.sources {
+            graph.add_module(&source.absolute_path);
+        }
+        for (source_index, ast) in self.files.iter().enumerate() {
+            let resolved_imports = &self.resolved_imports[source_index];
+            let mut dependencies = vec![];
+            let mut external_dependencies = vec![];
+            for part in &ast.parts {
+                for import_path in &part.import_paths {
+                    let kind = import_path.kind;
+                    match resolved_imports.get(&import_path.path.text) {
+                        Some(&other) => {
+                            let dependency = Dependency {
+                                source_index: other,
+                                kind,
+                            };
+                            if !dependencies.contains(&dependency) {
+                                dependencies.push(dependency);
+                            }
+                        }
+                        None => {
+                            let dependency = ExternalDependency {
+                                path: import_path.path.text.clone(),
+                                kind,
+                            };
+                            if !external_dependencies.contains(&dependency) {
+                                external_dependencies.push(dependency);
+                            }
+                        }
+                    }
+                }
+            }
+            graph.set_dependencies(source_index, dependencies);
+            graph.set_external_dependencies(source_index, external_dependencies);
+        }
+        graph
+    }
+
+    // Describes the build in the same JSON format as esbuild's metafile, for
+    // tools that analyze bundle size. Every file is listed under "inputs"
+    // with its size and what it imports, and every output file is listed
+    // under "outputs" with how many bytes each file contributed to it. The
+    // build must have been compiled with "metafile" set in the options.
+    // The chunk loader of an IIFE entry point is listed as "<runtime>".
+    //
+    // Paths are relative to "cwd" like they are in log messages.
+    pub fn metafile(&self, result: &BuildResult, cwd: &str) -> String {
+        let mut inputs = vec![];
+        for (source_index, source) in self.sources.iter().enumerate().skip(1) {
+            let imports: Vec<String> = self
+                .import_records(source_index)
+                .into_iter()
+                .map(|(path, kind, other)| {
+                    let (path, external) = match other {
+                        Some(other) => (self.sources[other].pretty_path.as_str(), ""),
+                        None => (path, ",\n          \"external\": true"),
+                    };
+                    format!(
+                        "\n        {{\n          \"path\": {},\n          \"kind\": \"{}\"{}\n        }}",
+                        quote_json(path),
+                        kind,
+                        external
+                    )
+                })
+                .collect();
+            inputs.push(format!(
+                "\n    {}: {{\n      \"bytes\": {},\n      \"imports\": [{}]\n    }}",
+                quote_json(&source.pretty_path),
+                self.file_sizes[source_index],
+                json_items(&imports, "      ")
+            ));
+        }
+
+        let mut outputs = vec![];
+        for file in &result.output_files {
+            let contributions: Vec<String> = file
+                .bytes_in_output
+                .iter()
+                .map(|&(source_index, bytes)| {
+                    format!(
+                        "\n        {}: {{\n          \"bytesInOutput\": {}\n        }}",
+                        quote_json(&self.sources[source_index].pretty_path),
+                        bytes
+                    )
+                })
+                .collect();
+            let entry_point = match file.entry_point {
+                Some(entry_point) => format!(
+                    ",\n      \"entryPoint\": {}",
+                    quote_json(&self.sources[entry_point].pretty_path)
+                ),
+                None => String::new(),
+            };
+            outputs.push(format!(
+                "\n    {}: {{\n      \"bytes\": {},\n      \"inputs\": {{{}}}{}\n    }}",
+                quote_json(&paths::pretty_path(cwd, &file.path)),
+                file.contents.len(),
+                json_items(&contributions, "      "),
+                entry_point
+            ));
+        }
+
+        format!(
+            "{{\n  \"inputs\": {{{}}},\n  \"outputs\": {{{}}}\n}}\n",
+            json_items(&inputs, "  "),
+            json_items(&outputs, "  ")
+        )
+    }
+
+    // The import paths of a file in the order they appear in, each with the
+    // kind of import as the metafile names it and the file it resolved to.
+    // Paths that appear more than once are only listed the first time.
+    fn import_records(&self, source_index: usize) -> Vec<(&str, &'static str, Option<usize>)> {
+        let resolved_imports = &self.resolved_imports[source_index];
+        let mut records = vec![];
+        if let Some(stylesheet) = &self.stylesheets[source_index] {
+            for rule in &stylesheet.rules {
+                if let Rule::Import { path, .. } = rule {
+                    records.push((path.as_str(), "import-rule"));
+                }
+            }
+        } else {
+            for part in &self.files[source_index].parts {
+                for import_path in &part.import_paths {
+                    let kind = match import_path.kind {
+                        ImportKind::Stmt => "import-statement",
+                        ImportKind::Require => "require-call",
+                        ImportKind::Dynamic => "dynamic-import",
+                    };
+                    records.push((import_path.path.text.as_str(), kind));
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        records
+            .into_iter()
+            .filter(|record| seen.insert(*record))
+            .map(|(path, kind)| (path, kind, resolved_imports.get(path).copied()))
+            .collect()
+    }
+
+    // Each file's rules are printed on their own to measure them. The
+    // "@import" rules of files in the bundle aren't in the output.
+    fn css_bytes_in_output(
+        &self,
+        css_files: &[usize],
+        shaken: &HashMap<usize, Stylesheet>,
+        options: &BundleOptions,
+    ) -> Vec<(usize, usize)> {
+        if !options.metafile {
+            return vec![];
+        }
+        let mut result = vec![];
+        for &source_index in css_files {
+            let stylesheet = match self.stylesheet(source_index, shaken) {
+                Some(stylesheet) => stylesheet,
+                None => continue,
+            };
+            let resolved_imports = &self.resolved_imports[source_index];
+            let rules = stylesheet
+                .rules
+                .iter()
+                .filter(|rule| {
+                    !matches!(rule, Rule::Import { path, .. } if resolved_imports.contains_key(path))
+                })
+                .cloned()
+                .collect();
+            let css = css::print(&Stylesheet { rules }, options.minify.whitespace);
+            result.push((source_index, css.len()));
+        }
+        result
+    }
+
+    // The CSS files reachable from a file in the order their rules apply in.
+    // Each file comes after the files it imports, and files imported more
+    // than once are only included the first time.
+    fn css_files_in_import_order(&self, source_index: usize) -> Vec<usize> {
+        let mut order = vec![];
+        let mut visited = HashSet::new();
+        self.visit_css_files(source_index, &mut visited, &mut order);
+        order
+    }
+
+    fn visit_css_files(
+        &self,
+        source_index: usize,
+        visited: &mut HashSet<usize>,
+        order: &mut Vec<usize>,
+    ) {
+        if !visited.insert(source_index) {
+            return;
+        }
+        let resolved_imports = &self.resolved_imports[source_index];
+        match &self.stylesheets[source_index] {
+            Some(stylesheet) => {
+                for rule in &stylesheet.rules {
+                    if let Rule::Import { path, .. } = rule {
+                        if let Some(&other) = resolved_imports.get(path) {
+                            self.visit_css_files(other, visited, order);
+                        }
+                    }
+                }
+                order.push(source_index);
+            }
+            None => {
+                for part in &self.files[source_index].parts {
+                    for import_path in &part.import_paths {
+                        if let Some(&other) = resolved_imports.get(&import_path.path.text) {
+                            self.visit_css_files(other, visited, order);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // The stylesheets of the CSS modules files that a JavaScript entry point
+    // imports, without the selectors that use a local name that the code in
+    // the bundle never imports. Names are only imported one by one when the
+    // object with all of them isn't used. Files without such names aren't in
+    // the map.
+    fn shake_local_css(
+        &self,
+        files: &[LinkerFile],
+        entry_point: usize,
+        css_files: &[usize],
+    ) -> HashMap<usize, Stylesheet> {
+        let mut result = HashMap::new();
+        let mut shaking = None;
+        for &source_index in css_files {
+            let names = &self.local_names[source_index];
+            let stylesheet = match &self.stylesheets[source_index] {
+                Some(stylesheet) if !names.is_empty() => stylesheet,
+                _ => continue,
+            };
+
+            // The stylesheets of all files are in the output even when code
+            // splitting puts the code of some of them in other chunks
+            let shaking =
+                shaking.get_or_insert_with(|| linker::tree_shake(files, &[entry_point], false));
+            let used = &shaking.used_exports[source_index];
+            if used.contains("default") {
+                continue;
+            }
+            let unused: HashSet<&str> = names
+                .iter()
+                .filter(|(name, _)| !used.contains(name))
+                .map(|(_, local)| local.as_str())
+                .collect();
+            if !unused.is_empty() {
+                let mut stylesheet = stylesheet.clone();
+                css::remove_unused_local_names(&mut stylesheet, &unused);
+                result.insert(source_index, stylesheet);
+            }
+        }
+        result
+    }
+
+    fn stylesheet<'s>(
+        &'s self,
+        source_index: usize,
+        shaken: &'s HashMap<usize, Stylesheet>,
+    ) -> Option<&'s Stylesheet> {
+        shaken
+            .get(&source_index)
+            .or(self.stylesheets[source_index].as_ref())
+    }
+
+    // Joins the rules of several CSS files into one stylesheet. The "@import"
+    // rules that were followed are dropped, and the ones that weren't are
+    // moved to the top since "@import" has to come before any other rules.
+    // An import with media queries wraps the imported rules in "@media".
+    fn bundle_css(&self, css_files: &[usize], shaken: &HashMap<usize, Stylesheet>) -> Stylesheet {
+        let mut conditions_of_file = HashMap::new();
+        for &source_index in css_files {
+            if let Some(stylesheet) = &self.stylesheets[source_index] {
+                for rule in &stylesheet.rules {
+                    if let Rule::Import {
+                        path, conditions, ..
+                    } = rule
+                    {
+                        if let Some(&other) = self.resolved_imports[source_index].get(path) {
+                            if !conditions.is_empty() {
+                                conditions_of_file.entry(other).or_insert(conditions);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut external_imports = vec![];
+        let mut rules = vec![];
+        for &source_index in css_files {
+            let stylesheet = match self.stylesheet(source_index, shaken) {
+                Some(stylesheet) => stylesheet,
+                None => continue,
+            };
+            let mut file_rules = vec![];
+            for rule in &stylesheet.rules {
+                match rule {
+                    Rule::Import { path, .. }
+                        if self.resolved_imports[source_index].contains_key(path) => {}
+                    Rule::Import { .. } => external_imports.push(rule.clone()),
+                    _ => file_rules.push(rule.clone()),
+                }
+            }
+            match conditions_of_file.get(&source_index) {
+                Some(&conditions) => rules.push(Rule::At {
+                    name: "media".to_owned(),
+                    prelude: conditions.clone(),
+                    block: Some(file_rules),
+                }),
+                None => rules.extend(file_rules),
+            }
+        }
+
+        external_imports.extend(rules);
+        Stylesheet {
+            rules: external_imports,
+        }
+    }
+
+    // Gives every output file its final path and replaces the placeholders
+    // that refer to other output files. The hash in a name covers every
+    // output file the file refers to, even indirectly, so the name changes
+    // whenever anything the file loads changes. That's what makes it safe to
+    // cache output files forever.
+    fn name_output_files(
+        &self,
+        log: &Log,
+        outputs: Vec<PendingOutput>,
+        chunks: &Chunks,
+        options: &BundleOptions,
+    ) -> Vec<OutputFile> {
+        let output_of_placeholder: HashMap<&str, usize> = outputs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, output)| Some((output.placeholder.as_deref()?, i)))
+            .collect();
+
+        // Copied files are binary, so they can't refer to anything
+        let references: Vec<Vec<PlaceholderRef>> = outputs
+            .iter()
+            .map(|output| match output.kind {
+                OutputKind::Asset => vec![],
+                _ => find_placeholders(&output.contents, &output_of_placeholder),
+            })
+            .collect();
+
+        let own_hashes: Vec<u64> = outputs
+            .iter()
+            .map(|output| xxh64(&output.contents, 0))
+            .collect();
+        let outbase = self.outbase(options);
+        let output_paths: Vec<String> = outputs
+            .iter()
+            .enumerate()
+            .map(|(i, output)| {
+                let hash = output_hash(i, &references, &own_hashes);
+                self.output_path(output, &outbase, &hash, options)
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        for path in &output_paths {
+            if !path.is_empty() && !seen.insert(path) {
+                log.add_error(
+                    &Source::default(),
+                    0,
+                    format!("Two output files share the same path: {}", path),
+                );
+            }
+        }
+
+        let output_dir = output_dir(options);
+        let kinds: Vec<OutputKind> = outputs.iter().map(|output| output.kind).collect();
+        let mut output_files = vec![];
+        for ((output, references), path) in outputs.into_iter().zip(references).zip(&output_paths) {
+            let from_dir = if path.is_empty() {
+                output_dir.clone()
+            } else {
+                paths::dir(path)
+            };
+            let mut contents = Vec::with_capacity(output.contents.len());
+            let mut end = 0;
+            for reference in references {
+                contents.extend_from_slice(&output.contents[end..reference.start]);
+                let target = &output_paths[reference.output];
+
+                // IIFE chunks are registered under their path in the
+                // output directory, since that's what the loader appends
+                // to the public path. Everything else is relative to the
+                // file it's referred to from.
+                let path = match (&chunks.registry, kinds[reference.output]) {
+                    (Some(_), OutputKind::Chunk) => {
+                        paths::relative(&output_dir, target).unwrap_or_else(|| target.clone())
+                    }
+                    _ => relative_import_path(&from_dir, target),
+                };
+                contents.extend_from_slice(path.as_bytes());
+                end = reference.end;
+            }
+            contents.extend_from_slice(&output.contents[end..]);
+            output_files.push(OutputFile {
+                path: path.clone(),
+                contents,
+                entry_point: output.entry_point,
+                bytes_in_output: output.bytes_in_output,
+                source_indices: output.source_indices,
+            });
+
+            if !output.legal_comments.is_empty() {
+                let mut contents = output.legal_comments.join("\n");
+                contents.push('\n');
+                output_files.push(OutputFile {
+                    path: format!("{}.LEGAL.txt", path),
+                    contents: contents.into_bytes(),
+                    entry_point: None,
+                    bytes_in_output: vec![],
+                    source_indices: vec![],
+                });
+            }
+        }
+        output_files
+    }
+
+    // The path of an output file. An entry point goes to the output file if
+    // there is one, and the CSS for a JavaScript entry point goes next to it
+    // with the extension swapped. Everything else goes in the output
+    // directory.
+    fn output_path(
+        &self,
+        output: &PendingOutput,
+        outbase: &str,
+        hash: &str,
+        options: &BundleOptions,
+    ) -> String {
+        let source = &self.sources[output.source_index];
+        let template = match output.kind {
+            OutputKind::Entry => {
+                if !options.abs_output_file.is_empty() {
+                    let output_file = &options.abs_output_file;
+                    let is_css_of_js_file =
+                        output.ext == ".css" && self.stylesheets[output.source_index].is_none();
+                    if !is_css_of_js_file {
+                        return output_file.clone();
+                    }
+                    let stem_len = output_file.len() - paths::ext(output_file).len();
+                    return format!("{}{}", &output_file[..stem_len], output.ext);
+                }
+                if options.abs_output_dir.is_empty() {
+                    return String::new();
+                }
+                &options.entry_names
+            }
+            OutputKind::Chunk | OutputKind::Common => &options.chunk_names,
+            OutputKind::Asset => &options.asset_names,
+        };
+
+        // A common chunk doesn't come from a file of its own
+        if output.kind == OutputKind::Common {
+            let name = template
+                .replace("[name]", "chunk")
+                .replace("[dir]", ".")
+                .replace("[hash]", hash);
+            return paths::join(&output_dir(options), &format!("{}{}", name, output.ext));
+        }
+
+        // Going up a directory would leave the output directory, so ".."
+        // is replaced like it is in esbuild
+        let dir = paths::relative(outbase, &paths::dir(&source.absolute_path))
+            .unwrap_or_else(|| ".".to_owned())
+            .split('/')
+            .map(|part| if part == ".." { "_.._" } else { part })
+            .collect::<Vec<_>>()
+            .join("/");
+        let name = template
+            .replace("[name]", &file_stem(&source.absolute_path))
+            .replace("[dir]", &dir)
+            .replace("[hash]", hash);
+        paths::join(&output_dir(options), &format!("{}{}", name, output.ext))
+    }
+
+    // The directory that "[dir]" is relative to, which is the lowest common
+    // directory of the entry points unless the options say otherwise
+    fn outbase(&self, options: &BundleOptions) -> String {
+        if !options.abs_outbase.is_empty() {
+            return options.abs_outbase.clone();
+        }
+        let mut dirs = self
+            .entry_points
+            .iter()
+            .map(|&entry_point| paths::dir(&self.sources[entry_point].absolute_path));
+        let first = match dirs.next() {
+            Some(first) => first,
+            None => return ".".to_owned(),
+        };
+        dirs.fold(first, |outbase, dir| {
+            let common: Vec<&str> = outbase
+                .split('/')
+                .zip(dir.split('/'))
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect();
+            match common.join("/") {
+                ref root if root.is_empty() && outbase.starts_with('/') => "/".to_owned(),
+                common => common,
+            }
+        })
+    }
+}
+
+// The items of a JSON array or object, which each start on a new line, with
+// the closing bracket indented on a line of its own
+fn json_items(items: &[String], indent: &str) -> String {
+    if items.is_empty() {
+        return String::new();
+    }
+    format!("{}\n{}", items.join(","), indent)
+}
+
+// The file name without its extension
+fn file_stem(path: &str) -> String {
+    let base = paths::base(path);
+    match base.rfind('.') {
+        Some(i) if i > 0 => base[..i].to_owned(),
+        _ => base,
+    }
+}
+
+// Chunks and copied files go in the output directory, or next to the output
+// file if there's only one
+fn output_dir(options: &BundleOptions) -> String {
+    if options.abs_output_dir.is_empty() && !options.abs_output_file.is_empty() {
+        paths::dir(&options.abs_output_file)
+    } else {
+        options.abs_output_dir.clone()
+    }
+}
+
+fn validate_name_template(template: &str) -> Result<(), Error> {
+    let mut rest = template;
+    while let Some(start) = rest.find('[') {
+        let end = match rest[start..].find(']') {
+            Some(end) => start + end + 1,
+            None => break,
+        };
+        match &rest[start..end] {
+            "[name]" | "[dir]" | "[hash]" => {}
+            placeholder => {
+                return Err(Error::Config(format!(
+                    "Invalid placeholder {} in the name template {} \
+                     (only [name], [dir] and [hash] are supported)",
+                    placeholder, template
+                )))
+            }
+        }
+        rest = &rest[end..];
+    }
+    Ok(())
+}
+
+// Returns the placeholders in the contents of an output file in order. Text
+// that looks like a placeholder but isn't one of "output_of_placeholder" is
+// left alone.
+fn find_placeholders(
+    contents: &[u8],
+    output_of_placeholder: &HashMap<&str, usize>,
+) -> Vec<PlaceholderRef> {
+    const PREFIX: &[u8] = b"__ESBUILD_";
+    let mut result = vec![];
+    let mut i = 0;
+    while let Some(offset) = find_bytes(&contents[i..], PREFIX) {
+        let start = i + offset;
+        let after_prefix = start + PREFIX.len();
+        let end = match find_bytes(&contents[after_prefix..], b"__") {
+            Some(offset) => after_prefix + offset + 2,
+            None => break,
+        };
+        let output = std::str::from_utf8(&contents[start..end])
+            .ok()
+            .and_then(|key| output_of_placeholder.get(key));
+        match output {
+            Some(&output) => {
+                result.push(PlaceholderRef { start, end, output });
+                i = end;
+            }
+            None => i = start + 1,
+        }
+    }
+    result
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// Hashes the output file together with every output file it refers to, even
+// indirectly. Files that refer to each other in a cycle end up with the same
+// set, which is fine since they all change together anyway.
+fn output_hash(output: usize, references: &[Vec<PlaceholderRef>], own_hashes: &[u64]) -> String {
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![output];
+    while let Some(output) = stack.pop() {
+        if visited.insert(output) {
+            stack.extend(references[output].iter().map(|reference| reference.output));
+        }
+    }
+    let mut bytes = Vec::with_capacity(visited.len() * 8);
+    for &output in &visited {
+        bytes.extend_from_slice(&own_hashes[output].to_le_bytes());
+    }
+    hash_string(xxh64(&bytes, 0))
+}
+
+// Eight characters of base32 are 40 bits, which is plenty to tell the
+// versions of a file apart. Only uppercase letters and digits are used so the
+// name is the same on case-insensitive file systems.
+fn hash_string(hash: u64) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    (0..8)
+        .map(|i| ALPHABET[(hash >> (i * 5)) as usize & 31] as char)
+        .collect()
+}
+
+// Import paths need to start with "./" or "../", otherwise they would be
+// looked up as packages
+fn relative_import_path(from_dir: &str, to: &str) -> String {
+    match paths::relative(from_dir, to) {
+        Some(path) if path.starts_with("../") => path,
+        Some(path) => format!("./{}", path),
+        None => to.to_owned(),
+    }
+}
+
+// Prints a file without linking it to anything. The top-level symbols keep
+// their names since other scripts may refer to them.
+pub fn print_file(ast: AST, options: &BundleOptions) -> String {
+    print_module(ast, options, None)
+}
+
+// The same, but "to_module_ref" is a "__toModule" helper that the file declares
+// already, so imports that need one use it instead of declaring another
+pub fn print_module(
+    mut ast: AST,
+    options: &BundleOptions,
+    mut to_module_ref: Option<Reference>,
+) -> String {
+    let minify = &options.minify;
+
+    // Without bundling every import path is external
+    if let Some(rewriter) = &options.rewrite_external_paths {
+        for part in &mut ast.parts {
+            rewrite_import_paths(&mut part.stmts, rewriter);
+        }
+    }
+
+    let named_imports = &ast.named_imports;
+    cjs::convert_to_output_format(
+        ast.parts.iter_mut().map(|part| &mut part.stmts),
+        &mut ast.symbols,
+        ast.exports_ref,
+        |reference| {
+            named_imports
+                .get(&reference)
+                .map(|named| named.namespace_ref)
+        },
+        options,
+        ast.scopes.root().strict_mode.is_strict(),
+        &mut to_module_ref,
+    );
+
+    if minify.syntax {
+        for part in &mut ast.parts {
+            mangler::mangle_stmts(&mut part.stmts, &options.mangle);
+        }
+    }
+
+    let renamer = if minify.identifiers {
+        for reference in ast.scopes.root().members.values() {
+            ast.symbols[*reference].must_not_be_renamed = true;
+        }
+        let scope_trees = [&ast.scopes];
+        let reserved_names = renamer::compute_reserved_names(&scope_trees, &ast.symbols);
+        renamer::minify_all_symbols(&reserved_names, &scope_trees, &ast.symbols)
+    } else {
+        renamer::rename_generated_symbols(&ast.scopes, &ast.symbols, ast.exports_ref.outer)
+    };
+
+    let print_options = PrintOptions {
+        minify_whitespace: minify.whitespace,
+        minify_syntax: minify.syntax,
+        target: options.target,
+        legal_comments: options.legal_comments,
+    };
+    printer::print_ast(&ast, &renamer, print_options)
+}
+
+// Parse trees are immutable once the parser is done with them, so the cache
+// hands out shared references instead of copies. A rebuild in watch mode only
+// re-parses the files whose contents changed and every other file keeps
+// pointing at the exact same tree as the previous build.
+#[derive(Debug, Clone, Default)]
+pub struct AstCache {
+    entries: HashMap<String, CachedAst>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedAst {
+    key: u64,
+    ast: Arc<AST>,
+}
+
+impl AstCache {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Returns the cached tree only if it was parsed from the same contents,
+    // at the same source index, and with the same options
+    pub fn get(&self, source: &Source, options: &ParseOptions) -> Option<Arc<AST>> {
+        let entry = self.entries.get(&source.absolute_path)?;
+        if entry.key == cache::entry_key(source, options) {
+            Some(entry.ast.clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, source: &Source, options: &ParseOptions, ast: Arc<AST>) -> Arc<AST> {
+        self.entries.insert(
+            source.absolute_path.clone(),
+            CachedAst {
+                key: cache::entry_key(source, options),
+                ast: ast.clone(),
+            },
+        );
+        ast
+    }
+
+    pub fn get_or_parse<F: FnOnce() -> AST>(
+        &mut self,
+        source: &Source,
+        options: &ParseOptions,
+        parse: F,
+    ) -> Arc<AST> {
+        if let Some(ast) = self.get(source, options) {
+            return ast;
+        }
+
+        self.insert(source, options, Arc::new(parse()))
+    }
+
+    pub fn invalidate(&mut self, absolute_path: &str) -> bool {
+        self.entries.remove(absolute_path).is_some()
+    }
+
+    // Files that dropped out of the module graph must be evicted or memory use
+    // grows with every rebuild. Call this with the set of files that are still
+    // reachable after each build.
+    pub fn retain<F: FnMut(&str) -> bool>(&mut self, mut keep: F) {
+        self.entries.retain(|path, _| keep(path));
+    }
+}
+
+impl ParseCache for Mutex<AstCache> {
+    fn load(&self, source: &Source, options: &ParseOptions) -> Option<Arc<AST>> {
+        self.lock().unwrap().get(source, options)
+    }
+
+    fn store(&self, source: &Source, options: &ParseOptions, ast: &Arc<AST>) {
+        self.lock().unwrap().insert(source, options, ast.clone());
+    }
+}
+
+// The linker must never mutate a cached tree since the next rebuild may reuse
+// it. Instead each file gets an overlay that holds private copies of only the
+// parts whose statements were actually changed. Untouched parts are read
+// straight from the shared tree.
+#[derive(Debug, Clone)]
+pub struct FileOverlay {
+    pub ast: Arc<AST>,
+    part_stmts: HashMap<usize, Vec<Stmt>>,
+}
+
+impl FileOverlay {
+    pub fn new(ast: Arc<AST>) -> Self {
+        Self {
+            ast,
+            part_stmts: HashMap::new(),
+        }
+    }
+
+    pub fn part_stmts(&self, part_index: usize) -> &[Stmt] {
+        match self.part_stmts.get(&part_index) {
+            Some(stmts) => stmts,
+            None => &self.ast.parts[part_index].stmts,
+        }
+    }
+
+    // The statements are copied out of the shared tree on the first write
+    pub fn part_stmts_mut(&mut self, part_index: usize) -> &mut Vec<Stmt> {
+        let ast = &self.ast;
+        self.part_stmts
+            .entry(part_index)
+            .or_insert_with(|| ast.parts[part_index].stmts.clone())
+    }
+
+    pub fn is_part_modified(&self, part_index: usize) -> bool {
+        self.part_stmts.contains_key(&part_index)
+    }
+
+    // Moves the private copy of a part out of the overlay, if there is one
+    pub fn take_part_stmts(&mut self, part_index: usize) -> Option<Vec<Stmt>> {
+        self.part_stmts.remove(&part_index)
+    }
+}
+
+// Import paths that are left external (i.e. not resolved to a source file in
+// the bundle) are printed verbatim by default. This hook lets the caller
+// rewrite them first, e.g. to map "react" to a CDN URL for browser ESM builds
+// or to append the ".js" extension that node's ESM loader requires. Returning
+// None keeps the original path.
+pub type RewriteImportPath = dyn Fn(&str, ImportKind) -> Option<String> + Send + Sync;
+
+#[derive(Clone)]
+pub struct ImportPathRewriter {
+    callback: Arc<RewriteImportPath>,
+
+    // When set, an "import()" whose path is rewritten becomes a call that
+    // loads the new path through the IIFE chunk registry this refers to
+    chunk_registry: Option<Reference>,
+
+    // A "require()" of one of these paths becomes a call to the closure that
+    // the CommonJS file it resolved to was wrapped in
+    wrappers: HashMap<String, Reference>,
+
+    // The same for lazy ES6 files, which are wrapped in a closure that
+    // initializes them and are read through their namespace object
+    esm_wrappers: HashMap<String, (Reference, Reference)>,
+
+    // When set, an "import()" of one of the wrapped paths above resolves to
+    // what the closure returns. This is the global "Promise" and the helper
+    // that turns the exports of a CommonJS file into a namespace, which is
+    // only there if a CommonJS file is imported this way.
+    dynamic_imports: Option<(Reference, Option<Reference>)>,
+
+    // In the HMR mode, an "import()" with a rewritten path loads the module
+    // with the "require" of the registry instead, in a later tick like a real
+    // one would. This is the global "Promise" and the "__toModule" helper,
+    // since the module may be a CommonJS file.
+    dynamic_requires: Option<(Reference, Reference)>,
+}
+
+impl ImportPathRewriter {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&str, ImportKind) -> Option<String> + Send + Sync + 'static,
+    {
+        Self {
+            callback: Arc::new(callback),
+            chunk_registry: None,
+            wrappers: HashMap::new(),
+            esm_wrappers: HashMap::new(),
+            dynamic_imports: None,
+            dynamic_requires: None,
+        }
+    }
+
+    pub fn with_chunk_registry(mut self, registry: Reference) -> Self {
+        self.chunk_registry = Some(registry);
+        self
+    }
+
+    pub fn with_wrappers(mut self, wrappers: HashMap<String, Reference>) -> Self {
+        self.wrappers = wrappers;
+        self
+    }
+
+    pub fn with_esm_wrappers(
+        mut self,
+        esm_wrappers: HashMap<String, (Reference, Reference)>,
+    ) -> Self {
+        self.esm_wrappers = esm_wrappers;
+        self
+    }
+
+    pub fn with_dynamic_imports(
+        mut self,
+        promise_ref: Reference,
+        helper_ref: Option<Reference>,
+    ) -> Self {
+        self.dynamic_imports = Some((promise_ref, helper_ref));
+        self
+    }
+
+    pub fn with_dynamic_requires(mut self, promise_ref: Reference, helper_ref: Reference) -> Self {
+        self.dynamic_requires = Some((promise_ref, helper_ref));
+        self
+    }
+
+    // Maps a path that is one of the prefixes, or that starts with one of them
+    // followed by "/", to its replacement followed by the rest of the path.
+    // The longest prefix wins, so "react/jsx-runtime" can be mapped
+    // differently from the rest of "react".
+    pub fn with_prefixes(prefixes: Vec<(String, String)>) -> Self {
+        Self::new(move |path, _| {
+            let (prefix, replacement) = prefixes
+                .iter()
+                .filter(|(prefix, _)| {
+                    path.strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                })
+                .max_by_key(|(prefix, _)| prefix.len())?;
+            Some(format!("{}{}", replacement, &path[prefix.len()..]))
+        })
+    }
+
+    // The same callback, except that it leaves these paths alone. The linker
+    // uses this to skip the paths that resolved to a file in the bundle.
+    pub fn skipping(&self, paths: HashSet<String>) -> Self {
+        let callback = self.callback.clone();
+        Self::new(move |path, kind| {
+            if paths.contains(path) {
+                None
+            } else {
+                callback(path, kind)
+            }
+        })
+    }
+
+    pub fn rewrite(&self, path: &Path, kind: ImportKind) -> Option<String> {
+        if path.use_source_index {
+            return None;
+        }
+        (self.callback)(&path.text, kind)
+    }
+}
+
+impl fmt::Debug for ImportPathRewriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ImportPathRewriter")
+    }
+}
+
+// Rewrites the import paths of the statements in place. Returns whether any
+// path was changed.
+pub fn rewrite_import_paths(stmts: &mut [Stmt], rewriter: &ImportPathRewriter) -> bool {
+    rewrite_stmts(stmts, rewriter)
+}
+
+// Makes "registry.import(chunk)", which loads a chunk and resolves to the
+// exports of the file it was split from
+fn chunk_registry_import(location: Location, registry: Reference, chunk: &str) -> ExprKind {
+    let target = Expr {
+        location,
+        data: Box::new(ExprKind::Dot {
+            target: Expr {
+                location,
+                data: Box::new(ExprKind::Identifier {
+                    reference: registry,
+                }),
+            },
+            name: "import".to_owned(),
+            name_location: location,
+            optional_chain: OptionalChain::None,
+            is_parenthesized: false,
+        }),
+    };
+    ExprKind::Call {
+        target,
+        args: vec![string_expr(location, chunk)],
+        optional_chain: OptionalChain::None,
+        is_parenthesized: false,
+        is_direct_eval: false,
+        can_be_unwrapped_if_unused: false,
+    }
+}
+
+// An "import()" of a wrapped file runs its closure, but still resolves
+// asynchronously like the real one would
+fn rewrite_dynamic_import(expr: &mut Expr, text: &str, rewriter: &ImportPathRewriter) -> bool {
+    let (promise_ref, helper_ref) = match rewriter.dynamic_imports {
+        Some(refs) => refs,
+        None => return false,
+    };
+    let location = expr.location;
+    let value =
+        if let (Some(&wrapper_ref), Some(helper_ref)) = (rewriter.wrappers.get(text), helper_ref) {
+            cjs::import_wrapper(location, helper_ref, wrapper_ref)
+        } else if let Some(&(init_ref, namespace_ref)) = rewriter.esm_wrappers.get(text) {
+            cjs::require_esm(location, init_ref, namespace_ref)
+        } else {
+            return false;
+        };
+    *expr = cjs::resolve_later(location, promise_ref, value);
+    true
+}
+
+fn rewrite_path(path: &mut Path, kind: ImportKind, rewriter: &ImportPathRewriter) -> bool {
+    match rewriter.rewrite(path, kind) {
+        Some(text) if text != path.text => {
+            path.text = text;
+            true
+        }
+        _ => false,
+    }
+}
+
+fn rewrite_stmts(stmts: &mut [Stmt], rewriter: &ImportPathRewriter) -> bool {
+    let mut changed = false;
+    for stmt in stmts {
+        changed |= rewrite_stmt(stmt, rewriter);
+    }
+    changed
+}
+
+fn rewrite_stmt(stmt: &mut Stmt, rewriter: &ImportPathRewriter) -> bool {
+    ensure_sufficient_stack(|| match stmt.data.as_mut() {
+        StmtKind::Import { path, .. }
+        | StmtKind::ExportFrom { path, .. }
+        | StmtKind::ExportStar { path, .. } => rewrite_path(path, ImportKind::Stmt, rewriter),
+
+        StmtKind::Block { stmts } | StmtKind::Namespace { stmts, .. } => {
+            rewrite_stmts(stmts, rewriter)
+        }
+        StmtKind::ExportDefault { value, .. } => match value {
+            ExprOrStmt::Expr(expr) => rewrite_expr(expr, rewriter),
+            ExprOrStmt::Stmt(stmt) => rewrite_stmt(stmt, rewriter),
+        },
+        StmtKind::ExportEquals { value } | StmtKind::Expr { value } | StmtKind::Throw { value } => {
+            rewrite_expr(value, rewriter)
+        }
+        StmtKind::Return { value: Some(value) } => rewrite_expr(value, rewriter),
+        StmtKind::Return { value: None } => false,
+        StmtKind::Enum { values, .. } => {
+            let mut changed = false;
+            for value in values.iter_mut().filter_map(|v| v.value.as_mut()) {
+                changed |= rewrite_expr(value, rewriter);
+            }
+            changed
+        }
+        StmtKind::Function { function, .. } => rewrite_stmts(&mut function.body.stmts, rewriter),
+        StmtKind::Class { class, .. } => rewrite_class(class, rewriter),
+        StmtKind::Label { stmt, .. } => rewrite_stmt(stmt, rewriter),
+        StmtKind::If { test, yes, no } => {
+            let mut changed = rewrite_expr(test, rewriter);
+            changed |= rewrite_stmt(yes, rewriter);
+            if let Some(no) = no {
+                changed |= rewrite_stmt(no, rewriter);
+            }
+            changed
+        }
+        StmtKind::For {
+            init,
+            test,
+            update,
+            body,
+        } => {
+            let mut changed = false;
+            if let Some(init) = init {
+                changed |= rewrite_stmt(init, rewriter);
+            }
+            if let Some(test) = test {
+                changed |= rewrite_expr(test, rewriter);
+            }
+            if let Some(update) = update {
+                changed |= rewrite_expr(update, rewriter);
+            }
+            changed | rewrite_stmt(body, rewriter)
+        }
+        StmtKind::ForIn { init, value, body }
+        | StmtKind::ForOf {
+            init, value, body, ..
+        } => {
+            rewrite_stmt(init, rewriter)
+                | rewrite_expr(value, rewriter)
+                | rewrite_stmt(body, rewriter)
+        }
+        StmtKind::DoWhile { body, test } | StmtKind::While { test, body } => {
+            rewrite_stmt(body, rewriter) | rewrite_expr(test, rewriter)
+        }
+        StmtKind::With { value, body, .. } => {
+            rewrite_expr(value, rewriter) | rewrite_stmt(body, rewriter)
+        }
+        StmtKind::Catch(catch) => rewrite_stmts(&mut catch.body, rewriter),
+        StmtKind::Finally(finally) => rewrite_stmts(&mut finally.stmts, rewriter),
+        StmtKind::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            let mut changed = rewrite_stmts(body, rewriter);
+            if let Some(catch) = catch {
+                changed |= rewrite_stmts(&mut catch.body, rewriter);
+            }
+            if let Some(finally) = finally {
+                changed |= rewrite_stmts(&mut finally.stmts, rewriter);
+            }
+            changed
+        }
+        StmtKind::Switch { test, cases, .. } => {
+            let mut changed = rewrite_expr(test, rewriter);
+            for case in cases {
+                if let Some(value) = &mut case.value {
+                    changed |= rewrite_expr(value, rewriter);
+                }
+                changed |= rewrite_stmts(&mut case.body, rewriter);
+            }
+            changed
+        }
+        StmtKind::Local { decls, .. } => {
+            let mut changed = false;
+            for value in decls.iter_mut().filter_map(|d| d.value.as_mut()) {
+                changed |= rewrite_expr(value, rewriter);
+            }
+            changed
+        }
+        StmtKind::Empty
+        | StmtKind::TypeScript
+        | StmtKind::SyntaxError
+        | StmtKind::Debugger
+        | StmtKind::Directive { .. }
+        | StmtKind::Comment { .. }
+        | StmtKind::ExportClause { .. }
+        | StmtKind::Break { .. }
+        | StmtKind::Continue { .. } => false,
+    })
+}
+
+fn rewrite_class(class: &mut Class, rewriter: &ImportPathRewriter) -> bool {
+    let mut changed = match &mut class.extends {
+        Some(extends) => rewrite_expr(extends, rewriter),
+        None => false,
+    };
+    for property in &mut class.properties {
+        changed |= rewrite_property(property, rewriter);
+    }
+    changed
+}
+
+fn rewrite_property(property: &mut Property, rewriter: &ImportPathRewriter) -> bool {
+    let mut changed = rewrite_expr(&mut property.key, rewriter);
+    if let Some(value) = &mut property.value {
+        changed |= rewrite_expr(value, rewriter);
+    }
+    if let Some(initializer) = &mut property.initializer {
+        changed |= rewrite_expr(initializer, rewriter);
+    }
+    changed
+}
+
+fn rewrite_exprs(exprs: &mut [Expr], rewriter: &ImportPathRewriter) -> bool {
+    let mut changed = false;
+    for expr in exprs {
+        changed |= rewrite_expr(expr, rewriter);
+    }
+    changed
+}
+
+fn rewrite_expr(expr: &mut Expr, rewriter: &ImportPathRewriter) -> bool {
+    ensure_sufficient_stack(|| {
+        match expr.data.as_mut() {
+            // Only "import()" with a string literal can be rewritten. Anything else
+            // is computed at run time.
+            ExprKind::Import { expr: value } => {
+                let path = match value.data.as_ref() {
+                    ExprKind::String { value: text } => Path {
+                        loc: value.location,
+                        text: utf16_to_string_lossy(text),
+                        use_source_index: false,
+                        source_index: 0,
+                    },
+                    _ => return rewrite_expr(value, rewriter),
+                };
+                let text = match rewriter.rewrite(&path, ImportKind::Dynamic) {
+                    Some(text) if text != path.text => text,
+                    _ => return rewrite_dynamic_import(expr, &path.text, rewriter),
+                };
+                if let Some((promise_ref, helper_ref)) = rewriter.dynamic_requires {
+                    let value = Expr {
+                        location: path.loc,
+                        data: Box::new(ExprKind::Require {
+                            path: Path { text, ..path },
+                            is_es6_import: false,
+                        }),
+                    };
+                    let value = cjs::to_module(path.loc, helper_ref, value);
+                    *expr = cjs::resolve_later(expr.location, promise_ref, value);
+                    return true;
+                }
+                *expr.data = match rewriter.chunk_registry {
+                    Some(registry) => chunk_registry_import(path.loc, registry, &text),
+                    None => ExprKind::Import {
+                        expr: string_expr(path.loc, &text),
+                    },
+                };
+                true
+            }
+            ExprKind::Require { path, .. } => {
+                if let Some(&wrapper_ref) = rewriter.wrappers.get(&path.text) {
+                    *expr = cjs::call_wrapper(expr.location, wrapper_ref);
+                    return true;
+                }
+                if let Some(&(init_ref, namespace_ref)) = rewriter.esm_wrappers.get(&path.text) {
+                    *expr = cjs::require_esm(expr.location, init_ref, namespace_ref);
+                    return true;
+                }
+                rewrite_path(path, ImportKind::Require, rewriter)
+            }
+
+            ExprKind::Array { items } => rewrite_exprs(items, rewriter),
+            ExprKind::Unary { value, .. }
+            | ExprKind::Spread { value }
+            | ExprKind::Await { value }
+            | ExprKind::Yield { value, .. } => rewrite_expr(value, rewriter),
+            ExprKind::Binary { left, right, .. } => {
+                rewrite_expr(left, rewriter) | rewrite_expr(right, rewriter)
+            }
+            ExprKind::New { target, args, .. } | ExprKind::Call { target, args, .. } => {
+                rewrite_expr(target, rewriter) | rewrite_exprs(args, rewriter)
+            }
+            ExprKind::RuntimeCall { args, .. } => rewrite_exprs(args, rewriter),
+            ExprKind::Dot { target, .. } => rewrite_expr(target, rewriter),
+            ExprKind::Index { target, index, .. } => {
+                rewrite_expr(target, rewriter) | rewrite_expr(index, rewriter)
+            }
+            ExprKind::Arrow { args, body, .. } => {
+                let mut changed = false;
+                for default in args.iter_mut().filter_map(|a| a.default_.as_mut()) {
+                    changed |= rewrite_expr(default, rewriter);
+                }
+                changed | rewrite_stmts(&mut body.stmts, rewriter)
+            }
+            ExprKind::Function { function } => rewrite_stmts(&mut function.body.stmts, rewriter),
+            ExprKind::Class { class } => rewrite_class(class, rewriter),
+            ExprKind::Object { properties } => {
+                let mut changed = false;
+                for property in properties {
+                    changed |= rewrite_property(property, rewriter);
+                }
+                changed
+            }
+            ExprKind::Template { tag, parts, .. } => {
+                let mut changed = rewrite_expr(tag, rewriter);
+                for part in parts {
+                    changed |= rewrite_expr(&mut part.value, rewriter);
+                }
+                changed
+            }
+            ExprKind::If { test, yes, no } => {
+                rewrite_expr(test, rewriter)
+                    | rewrite_expr(yes, rewriter)
+                    | rewrite_expr(no, rewriter)
+            }
+            ExprKind::Boolean { .. }
+            | ExprKind::Super
+            | ExprKind::Null
+            | ExprKind::Undefined
+            | ExprKind::This
+            | ExprKind::NewTarget
+            | ExprKind::ImportMeta
+            | ExprKind::Identifier { .. }
+            | ExprKind::PrivateIdentifier { .. }
+            | ExprKind::ImportIdentifier { .. }
+            | ExprKind::JSXElement { .. }
+            | ExprKind::Missing
+            | ExprKind::Number { .. }
+            | ExprKind::BigInt { .. }
+            | ExprKind::String { .. }
+            | ExprKind::RegExp { .. } => false,
+        }
+    })
+}
+
+// This is the import graph of a build. Files are identified by their source
+// index. It's exposed so that dev servers can be built on top of the bundler
+// without forking the linker: they can ask which files depend on a changed
+// file, which chunks it ended up in, and subscribe to invalidation events
+// emitted by watch mode.
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+    modules: Vec<Module>,
+    path_to_source_index: HashMap<String, usize>,
+    subscribers: Vec<Sender<InvalidationEvent>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub absolute_path: String,
+    pub dependencies: Vec<Dependency>,
+    pub dependents: Vec<usize>,
+
+    // Imports of paths that were marked as external, which aren't modules
+    // in the graph
+    pub external_dependencies: Vec<ExternalDependency>,
+
+    // The indices of the output files that have the code of this module, see
+    // "set_output_files"
+    pub chunks: Vec<usize>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub source_index: usize,
+    pub kind: ImportKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalDependency {
+    pub path: String,
+    pub kind: ImportKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidationEvent {
+    // The files that changed on disk
+    pub changed: Vec<String>,
+
+    // The changed files and every file that imports them, directly or
+    // indirectly. These are the modules an HMR layer needs to re-evaluate.
+    pub affected: Vec<String>,
+}
+
+impl ModuleGraph {
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    pub fn add_module(&mut self, absolute_path: &str) -> usize {
+        if let Some(&source_index) = self.path_to_source_index.get(absolute_path) {
+            return source_index;
+        }
+
+        let source_index = self.modules.len();
+        self.modules.push(Module {
+            absolute_path: absolute_path.to_owned(),
+            dependencies: Vec::new(),
+            dependents: Vec::new(),
+            external_dependencies: Vec::new(),
+            chunks: Vec::new(),
+        });
+        self.path_to_source_index
+            .insert(absolute_path.to_owned(), source_index);
+        source_index
+    }
+
+    pub fn source_index(&self, absolute_path: &str) -> Option<usize> {
+        self.path_to_source_index.get(absolute_path).copied()
+    }
+
+    pub fn module(&self, source_index: usize) -> &Module {
+        &self.modules[source_index]
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = (usize, &Module)> {
+        self.modules.iter().enumerate()
+    }
+
+    // Replaces the outgoing edges of a module, e.g. after it was re-parsed
+    pub fn set_dependencies(&mut self, source_index: usize, dependencies: Vec<Dependency>) {
+        let old = std::mem::take(&mut self.modules[source_index].dependencies);
+        for dependency in old {
+            self.modules[dependency.source_index]
+                .dependents
+                .retain(|&d| d != source_index);
+        }
+
+        for dependency in &dependencies {
+            let dependents = &mut self.modules[dependency.source_index].dependents;
+            if !dependents.contains(&source_index) {
+                dependents.push(source_index);
+            }
+        }
+        self.modules[source_index].dependencies = dependencies;
+    }
+
+    pub fn dependencies(&self, source_index: usize) -> &[Dependency] {
+        &self.modules[source_index].dependencies
+    }
+
+    pub fn set_external_dependencies(
+        &mut self,
+        source_index: usize,
+        dependencies: Vec<ExternalDependency>,
+    ) {
+        self.modules[source_index].external_dependencies = dependencies;
+    }
+
+    pub fn external_dependencies(&self, source_index: usize) -> &[ExternalDependency] {
+        &self.modules[source_index].external_dependencies
+    }
+
+    pub fn dependents(&self, source_index: usize) -> &[usize] {
+        &self.modules[source_index].dependents
+    }
+
+    pub fn set_chunks(&mut self, source_index: usize, chunks: Vec<usize>) {
+        self.modules[source_index].chunks = chunks;
+    }
+
+    pub fn chunks(&self, source_index: usize) -> &[usize] {
+        &self.modules[source_index].chunks
+    }
+
+    // Records which output files of a build have the code of each module.
+    // The graph has the same source indices as the bundle that was compiled.
+    pub fn set_output_files(&mut self, output_files: &[OutputFile]) {
+        let mut chunks = vec![vec![]; self.modules.len()];
+        for (index, file) in output_files.iter().enumerate() {
+            for &source_index in &file.source_indices {
+                chunks[source_index].push(index);
+            }
+        }
+        for (source_index, chunks) in chunks.into_iter().enumerate() {
+            self.set_chunks(source_index, chunks);
+        }
+    }
+
+    // Returns the given modules plus everything that transitively imports them
+    pub fn transitive_dependents(&self, source_indices: &[usize]) -> Vec<usize> {
+        let mut visited = vec![false; self.modules.len()];
+        let mut stack = source_indices.to_vec();
+        let mut result = Vec::new();
+
+        while let Some(source_index) = stack.pop() {
+            if visited[source_index] {
+                continue;
+            }
+            visited[source_index] = true;
+            result.push(source_index);
+            stack.extend_from_slice(&self.modules[source_index].dependents);
+        }
+
+        result.sort_unstable();
+        result
+    }
+
+    // Takes the modules of the graph of a newer build, but keeps the
+    // subscribers of this one
+    pub fn replace_modules(&mut self, graph: ModuleGraph) {
+        self.modules = graph.modules;
+        self.path_to_source_index = graph.path_to_source_index;
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<InvalidationEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    // Called by watch mode when files change. Files that aren't part of the
+    // graph are ignored. Subscribers that went away are dropped.
+    pub fn invalidate(&mut self, changed_paths: &[String]) -> InvalidationEvent {
+        let changed: Vec<usize> = changed_paths
+            .iter()
+            .filter_map(|path| self.source_index(path))
+            .collect();
+        let affected = self.transitive_dependents(&changed);
+        let event = InvalidationEvent {
+            changed: changed
+                .iter()
+                .map(|&i| self.modules[i].absolute_path.clone())
+                .collect(),
+            affected: affected
+                .iter()
+                .map(|&i| self.modules[i].absolute_path.clone())
+                .collect(),
+        };
+
+        if !event.changed.is_empty() {
+            self.subscribers
+                .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        }
+        event
+    }
+}
+
+// HMR clients refer to modules by ID, so IDs must not depend on the order in
+// which files happen to be discovered. The ID of a module is its path relative
+// to a base directory, such as the working directory or the outbase of the
+// build, which stays the same across rebuilds and is still readable in a
+// debugger. Files outside of the base directory fall back to their absolute
+// path.
+#[derive(Debug, Clone)]
+pub struct HmrModuleIds {
+    cwd: String,
+    ids: HashMap<String, String>,
+}
+
+impl HmrModuleIds {
+    pub fn new(cwd: &str) -> Self {
+        Self {
+            cwd: cwd.to_owned(),
+            ids: HashMap::new(),
+        }
+    }
+
+    pub fn id(&mut self, absolute_path: &str) -> &str {
+        let cwd = &self.cwd;
+        self.ids
+            .entry(absolute_path.to_owned())
+            .or_insert_with(|| paths::pretty_path(cwd, absolute_path))
+    }
+
+    // Emits the registry runtime followed by every module wrapped in its
+    // registration call. The entry point is required last.
+    pub fn emit_chunk(
+        &mut self,
+        options: &BundleOptions,
+        modules: &[(&str, &str)],
+        entry_point: &str,
+    ) -> String {
+        let mut result = runtime::hmr_registry(&options.hmr_registry);
+        for (absolute_path, code) in modules {
+            let id = self.id(absolute_path).to_owned();
+            result.push_str(&runtime::hmr_register_module(&id, code));
+        }
+        let entry_id = self.id(entry_point).to_owned();
+        result.push_str(&runtime::hmr_require(&entry_id));
+        result
+    }
+}
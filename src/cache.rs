@@ -0,0 +1,169 @@
+// This is the binary format used to store parsed files in the persistent
+// cache. Deserializing a file is much cheaper than lexing and parsing it again
+// and also skips the scope and binding passes, since the symbol table is
+// stored along with the tree.
+//
+// The layout is a fixed-size header followed by the bincode encoding of the
+// AST:
+//
+//   magic    8 bytes   "ESBDAST\0"
+//   version  4 bytes   little-endian FORMAT_VERSION
+//   checksum 8 bytes   little-endian FNV-1a hash of the payload
+//   payload  N bytes
+//
+// The cache may have been written by an older build or may have been
+// truncated by a crash, so any problem with an entry just turns it into a
+// cache miss and the file is parsed again.
+
+use crate::ast::AST;
+use crate::logging::Source;
+use crate::parser::ParseOptions;
+
+use bincode::Options;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const MAGIC: [u8; 8] = *b"ESBDAST\0";
+const HEADER_LEN: usize = 20;
+
+// This must be incremented whenever the layout of any of the types reachable
+// from "AST" changes, since bincode doesn't store field names or types
+pub const FORMAT_VERSION: u32 = 9;
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .with_little_endian()
+        .reject_trailing_bytes()
+}
+
+// The checksum only guards against corruption, not against tampering. FNV-1a
+// is used instead of "DefaultHasher" because the output of the standard hasher
+// is allowed to change between Rust releases.
+fn checksum(bytes: &[u8]) -> u64 {
+    fnv1a(0xcbf2_9ce4_8422_2325, bytes)
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+pub fn encode_ast(ast: &AST) -> Vec<u8> {
+    let payload = options()
+        .serialize(ast)
+        .expect("Serializing an AST cannot fail");
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&checksum(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+// Returns "None" if the bytes were written by a different version of the
+// format or if they have been corrupted
+pub fn decode_ast(bytes: &[u8]) -> Option<AST> {
+    if bytes.len() < HEADER_LEN || bytes[..8] != MAGIC {
+        return None;
+    }
+
+    let mut version = [0; 4];
+    version.copy_from_slice(&bytes[8..12]);
+    if u32::from_le_bytes(version) != FORMAT_VERSION {
+        return None;
+    }
+
+    let mut expected = [0; 8];
+    expected.copy_from_slice(&bytes[12..20]);
+    let payload = &bytes[HEADER_LEN..];
+    if checksum(payload) != u64::from_le_bytes(expected) {
+        return None;
+    }
+
+    options().deserialize(payload).ok()
+}
+
+// Somewhere to keep parsed trees between builds. Files are parsed on a thread
+// pool, so a cache must be usable from several threads at once. A tree is only
+// stored if parsing it didn't log anything, since a cache hit skips the parser
+// along with everything it would have logged.
+pub trait ParseCache: Sync {
+    fn load(&self, source: &Source, options: &ParseOptions) -> Option<Arc<AST>>;
+    fn store(&self, source: &Source, options: &ParseOptions, ast: &Arc<AST>);
+}
+
+// The persistent cache is a directory with one entry per parsed file. The name
+// of an entry is its "entry_key". An entry is never updated in place, so a
+// changed file just gets a new entry.
+//
+// Errors while reading or writing the directory are ignored. The cache only
+// makes builds faster and a build must never fail because of it.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn entry_path(&self, source: &Source, options: &ParseOptions) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.ast", entry_key(source, options)))
+    }
+}
+
+impl ParseCache for DiskCache {
+    fn load(&self, source: &Source, options: &ParseOptions) -> Option<Arc<AST>> {
+        let bytes = std::fs::read(self.entry_path(source, options)).ok()?;
+        decode_ast(&bytes).map(Arc::new)
+    }
+
+    fn store(&self, source: &Source, options: &ParseOptions, ast: &Arc<AST>) {
+        let path = self.entry_path(source, options);
+        let temp_path = path.with_extension(format!("tmp{}", std::process::id()));
+
+        // Writing to a temporary file first means another build reading the
+        // same entry never sees half of it
+        if std::fs::create_dir_all(&self.dir).is_ok()
+            && std::fs::write(&temp_path, encode_ast(ast)).is_ok()
+            && std::fs::rename(&temp_path, &path).is_err()
+        {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+    }
+}
+
+// Everything a parsed tree depends on: the contents of the file, its path and
+// source index (which end up in the tree as the outer index of every symbol),
+// and the parse options
+pub fn entry_key(source: &Source, options: &ParseOptions) -> u64 {
+    let mut hash = checksum(source.contents.as_bytes());
+    hash = fnv1a(hash, source.absolute_path.as_bytes());
+    hash = fnv1a(hash, &source.index.to_le_bytes());
+    fnv1a(hash, options_fingerprint(options).as_bytes())
+}
+
+// The defines are sorted since the order of a hash map changes from one run
+// to the next
+fn options_fingerprint(options: &ParseOptions) -> String {
+    let mut defines: Vec<_> = options.defines.iter().collect();
+    defines.sort_by(|a, b| a.0.cmp(b.0));
+    let mut loaders: Vec<_> = options.loaders.iter().collect();
+    loaders.sort();
+    let options = ParseOptions {
+        defines: Default::default(),
+        loaders: Default::default(),
+        ..options.clone()
+    };
+    format!(
+        "{} {:?} {:?} {:?}",
+        FORMAT_VERSION, options, defines, loaders
+    )
+}
@@ -0,0 +1,1305 @@
+// The CommonJS and IIFE output formats have no import or export statements, so
+// they are converted into code that uses "require()" and "exports" instead:
+//
+//   import {a as b} from 'path'   =>   var import_path = require('path')
+//   b()                           =>   (0, import_path.a)()
+//   export let c = 1              =>   let c = 1
+//
+// Imported names become property accesses off of the namespace returned by
+// "require()" every time they are used, which keeps ES6 live bindings working.
+// The printer does this for every import item with a namespace alias.
+//
+// A default import or a namespace import also sees the exports of a CommonJS
+// file as its default export, so those go through the same helper that the
+// linker uses for the files in the bundle (see "to_module_helper"):
+//
+//   import fs from 'fs'           =>   var import_fs = __toModule(require('fs'))
+//
+// Exports are getters on the "exports" object so that they are live bindings
+// too. They are all defined at the top of the file before any code runs,
+// which means a module in an import cycle can already see every export of a
+// module that hasn't finished running yet. This is the same code that the
+// TypeScript compiler generates:
+//
+//   Object.defineProperty(exports, "__esModule", { value: true });
+//   Object.defineProperty(exports, "c", { enumerable: true, get: function() { return c; } });
+
+use crate::ast::{
+    Arg, Binding, BindingKind, ClauseItem, Decl, Expr, ExprKind, Function, FunctionBody, LocalKind,
+    Location, NamespaceAlias, NamespaceSymbol, OperatorCode, OptionalChain, Path, Property,
+    PropertyKind, Reference, Stmt, StmtKind, Symbol, SymbolKind, SymbolMap, INVALID_REF,
+};
+use crate::bundler::{BundleOptions, OutputFormat};
+use crate::linker::default_export_declaration;
+use crate::stack::ensure_sufficient_stack;
+use crate::strings::{string_to_utf16, utf16_equals_string};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+// Converts the module syntax that is left in a file or a bundle into what the
+// output format needs. Nothing happens for ESM. "namespace_for_import" returns
+// the namespace symbol that the parser generated for an import item.
+//
+// The IIFE format uses the same conversion since it has no module syntax
+// either. All statements are moved into a closure in the first part. The
+// exports are only kept if there is a global name to assign them to:
+//
+//   var globalName = (function() {
+//     var exports = {};
+//     ...
+//     return exports;
+//   })();
+//
+// Generated symbols are added to the file that "exports_ref" belongs to. They
+// aren't in any scope, so the renamer leaves them alone.
+//
+// "is_strict" is true if all of the code is strict mode code. ES6 modules are
+// strict without saying so, which the converted code has to do explicitly.
+//
+// "to_module_ref" is the "__toModule" helper if the caller has declared one
+// already. Otherwise one is generated and declared at the top if an import
+// needs it, and it's returned through "to_module_ref".
+pub fn convert_to_output_format<'a, F>(
+    parts: impl IntoIterator<Item = &'a mut Vec<Stmt>>,
+    symbols: &mut SymbolMap,
+    exports_ref: Reference,
+    namespace_for_import: F,
+    options: &BundleOptions,
+    is_strict: bool,
+    to_module_ref: &mut Option<Reference>,
+) where
+    F: Fn(Reference) -> Option<Reference>,
+{
+    if options.output_format == OutputFormat::Esm {
+        return;
+    }
+
+    let mut converter = Converter {
+        symbols: &mut *symbols,
+        exports_ref,
+        object_ref: INVALID_REF,
+        to_module_ref: *to_module_ref,
+        declares_to_module: false,
+        namespace_for_import,
+        has_es6_exports: false,
+        exports: vec![],
+    };
+    let mut parts: Vec<&mut Vec<Stmt>> = parts.into_iter().collect();
+    for stmts in &mut parts {
+        let old_stmts = std::mem::take(*stmts);
+        for stmt in old_stmts {
+            converter.convert_stmt(stmt, stmts);
+        }
+    }
+    *to_module_ref = converter.to_module_ref;
+    let helper_stmts: Vec<Stmt> = match converter.to_module_ref {
+        Some(helper_ref) if converter.declares_to_module => {
+            vec![to_module_helper(converter.symbols, helper_ref)]
+        }
+        _ => vec![],
+    };
+    let export_stmts = converter.export_stmts();
+
+    if parts.is_empty() {
+        return;
+    }
+    if options.output_format == OutputFormat::Cjs {
+        insert_after_directives(
+            parts[0],
+            helper_stmts.into_iter().chain(export_stmts).collect(),
+        );
+        if is_strict {
+            add_use_strict(parts[0]);
+        }
+        return;
+    }
+
+    let mut body = vec![];
+    for stmts in &mut parts {
+        body.append(stmts);
+    }
+    insert_after_directives(&mut body, helper_stmts);
+    if is_strict {
+        add_use_strict(&mut body);
+    }
+    let global_name = options.global_name.as_str();
+    let location = body.first().map_or(0, |stmt| stmt.location);
+    if !global_name.is_empty() {
+        let object = expr(location, ExprKind::Object { properties: vec![] });
+        let mut stmts = vec![Stmt {
+            location,
+            data: Box::new(declare_var(location, exports_ref, object)),
+        }];
+        stmts.extend(export_stmts);
+        insert_after_directives(&mut body, stmts);
+        body.push(Stmt {
+            location,
+            data: Box::new(StmtKind::Return {
+                value: Some(identifier(location, exports_ref)),
+            }),
+        });
+    }
+
+    let function = function(location, vec![], body);
+    let wrapper = call(expr(location, ExprKind::Function { function }), vec![]);
+    let data = if global_name.is_empty() {
+        StmtKind::Expr { value: wrapper }
+    } else {
+        let global_ref = generate_symbol(symbols, exports_ref.outer, global_name);
+        declare_var(location, global_ref, wrapper)
+    };
+    parts[0].push(Stmt {
+        location,
+        data: Box::new(data),
+    });
+}
+
+fn insert_after_directives(stmts: &mut Vec<Stmt>, new_stmts: Vec<Stmt>) {
+    let index = stmts
+        .iter()
+        .position(|stmt| {
+            !matches!(
+                stmt.data.as_ref(),
+                StmtKind::Directive { .. } | StmtKind::Comment { .. }
+            )
+        })
+        .unwrap_or(stmts.len());
+    stmts.splice(index..index, new_stmts);
+}
+
+fn add_use_strict(stmts: &mut Vec<Stmt>) {
+    let mut index = None;
+    for (i, stmt) in stmts.iter().enumerate() {
+        match stmt.data.as_ref() {
+            StmtKind::Directive { value } if utf16_equals_string(value, "use strict") => return,
+            StmtKind::Directive { .. } => index = index.or(Some(i)),
+            StmtKind::Comment { .. } => {}
+            _ => break,
+        }
+    }
+
+    // Legal comments stay at the top
+    let index = index.unwrap_or_else(|| {
+        stmts
+            .iter()
+            .position(|stmt| !matches!(stmt.data.as_ref(), StmtKind::Comment { .. }))
+            .unwrap_or(stmts.len())
+    });
+    let location = stmts.get(index).map_or(0, |stmt| stmt.location);
+    stmts.insert(
+        index,
+        Stmt {
+            location,
+            data: Box::new(StmtKind::Directive {
+                value: string_to_utf16("use strict"),
+            }),
+        },
+    );
+}
+
+struct Converter<'a, F> {
+    symbols: &'a mut SymbolMap,
+    exports_ref: Reference,
+    object_ref: Reference,
+    to_module_ref: Option<Reference>,
+    declares_to_module: bool,
+    namespace_for_import: F,
+    has_es6_exports: bool,
+
+    // The alias of each export along with the symbol it refers to
+    exports: Vec<(String, Location, Reference)>,
+}
+
+impl<'a, F> Converter<'a, F>
+where
+    F: Fn(Reference) -> Option<Reference>,
+{
+    fn convert_stmt(&mut self, stmt: Stmt, stmts: &mut Vec<Stmt>) {
+        let location = stmt.location;
+        let data = match *stmt.data {
+            StmtKind::Import {
+                namespace_symbol,
+                default_name,
+                path,
+            } => {
+                let (namespace_ref, mut items) = match namespace_symbol {
+                    NamespaceSymbol::Star { namespace_ref, .. } => (Some(namespace_ref), vec![]),
+                    NamespaceSymbol::Clause { items } => (None, items),
+                };
+                let needs_to_module = namespace_ref.is_some() || default_name.is_some();
+                if let Some(default_name) = default_name {
+                    items.push(ClauseItem {
+                        alias: "default".to_owned(),
+                        alias_location: default_name.loc,
+                        name: default_name,
+                    });
+                }
+
+                // "import 'path'" only runs the file
+                let namespace_ref = namespace_ref.or_else(|| {
+                    items
+                        .first()
+                        .and_then(|item| (self.namespace_for_import)(item.name.reference))
+                });
+                let namespace_ref = match namespace_ref {
+                    Some(namespace_ref) => namespace_ref,
+                    None => {
+                        stmts.push(Stmt {
+                            location,
+                            data: Box::new(StmtKind::Expr {
+                                value: require(path),
+                            }),
+                        });
+                        return;
+                    }
+                };
+
+                for item in items {
+                    self.set_namespace_alias(item.name.reference, namespace_ref, item.alias);
+                }
+                let value = self.require(location, path, needs_to_module);
+                declare_var(location, namespace_ref, value)
+            }
+
+            StmtKind::ExportClause { items } => {
+                self.has_es6_exports = true;
+                for item in items {
+                    self.exports
+                        .push((item.alias, item.alias_location, item.name.reference));
+                }
+                return;
+            }
+
+            StmtKind::ExportFrom {
+                items,
+                namespace,
+                path,
+            } => {
+                self.has_es6_exports = true;
+                let mut needs_to_module = false;
+                for item in items {
+                    let name = self.symbols[item.name.reference].name.clone();
+                    needs_to_module |= name == "default";
+                    self.set_namespace_alias(item.name.reference, namespace, name);
+                    self.exports
+                        .push((item.alias, item.alias_location, item.name.reference));
+                }
+                let value = self.require(location, path, needs_to_module);
+                declare_var(location, namespace, value)
+            }
+
+            StmtKind::ExportStar {
+                item: Some(item),
+                path,
+            } => {
+                self.has_es6_exports = true;
+                self.exports
+                    .push((item.alias, item.alias_location, item.name.reference));
+                let value = self.require(location, path, true);
+                declare_var(location, item.name.reference, value)
+            }
+
+            StmtKind::ExportStar { item: None, path } => {
+                self.has_es6_exports = true;
+                StmtKind::Expr {
+                    value: self.export_star(location, path),
+                }
+            }
+
+            StmtKind::ExportDefault {
+                default_name,
+                value,
+            } => {
+                self.has_es6_exports = true;
+                self.exports.push((
+                    "default".to_owned(),
+                    default_name.loc,
+                    default_name.reference,
+                ));
+                default_export_declaration(&default_name, &value)
+            }
+
+            StmtKind::Local {
+                decls,
+                kind,
+                is_export: true,
+                was_ts_import_equals_in_namespace,
+            } => {
+                self.has_es6_exports = true;
+                for decl in &decls {
+                    self.export_binding(&decl.binding);
+                }
+                StmtKind::Local {
+                    decls,
+                    kind,
+                    is_export: false,
+                    was_ts_import_equals_in_namespace,
+                }
+            }
+
+            StmtKind::Function {
+                function,
+                is_export: true,
+            } => {
+                self.has_es6_exports = true;
+                if let Some(name) = &function.name {
+                    self.export_name(name.loc, name.reference);
+                }
+                StmtKind::Function {
+                    function,
+                    is_export: false,
+                }
+            }
+
+            StmtKind::Class {
+                class,
+                is_export: true,
+            } => {
+                self.has_es6_exports = true;
+                if let Some(name) = &class.name {
+                    self.export_name(name.loc, name.reference);
+                }
+                StmtKind::Class {
+                    class,
+                    is_export: false,
+                }
+            }
+
+            data => data,
+        };
+
+        stmts.push(Stmt {
+            location,
+            data: Box::new(data),
+        });
+    }
+
+    // "require(path)", or "__toModule(require(path))" if the default export
+    // is read from what it returns
+    fn require(&mut self, location: Location, path: Path, needs_to_module: bool) -> Expr {
+        if !needs_to_module {
+            return require(path);
+        }
+        let helper_ref = match self.to_module_ref {
+            Some(helper_ref) => helper_ref,
+            None => {
+                let name = unused_name(self.symbols, self.exports_ref.outer, "__toModule");
+                let helper_ref = self.generate_symbol(&name);
+                self.symbols[helper_ref].must_not_be_renamed = false;
+                self.to_module_ref = Some(helper_ref);
+                self.declares_to_module = true;
+                helper_ref
+            }
+        };
+        to_module(location, helper_ref, require(path))
+    }
+
+    fn set_namespace_alias(
+        &mut self,
+        reference: Reference,
+        namespace_ref: Reference,
+        alias: String,
+    ) {
+        self.symbols.set_namespace_alias(
+            reference,
+            Arc::new(NamespaceAlias {
+                namespace_ref,
+                alias,
+            }),
+        );
+    }
+
+    fn export_name(&mut self, location: Location, reference: Reference) {
+        let alias = self.symbols[reference].name.clone();
+        self.exports.push((alias, location, reference));
+    }
+
+    fn export_binding(&mut self, binding: &Binding) {
+        ensure_sufficient_stack(|| match binding.data.as_ref() {
+            BindingKind::Missing => {}
+            BindingKind::Identifier { reference } => self.export_name(binding.location, *reference),
+            BindingKind::Array { items, .. } => {
+                for item in items {
+                    self.export_binding(&item.binding);
+                }
+            }
+            BindingKind::Object { properties } => {
+                for property in properties {
+                    self.export_binding(&property.value);
+                }
+            }
+        })
+    }
+
+    // The export names of the other file aren't known until it runs, so they
+    // are copied over from its exports object at run time:
+    //
+    //   (function(from) {
+    //     for (var key in from)
+    //       if (key !== "default" && !(key in exports))
+    //         Object.defineProperty(exports, key, Object.getOwnPropertyDescriptor(from, key));
+    //   })(require("path"));
+    //
+    // Copying the property descriptor keeps the getters of a file that was
+    // converted from ES6, so those exports stay live bindings. The exports of
+    // this file are defined before this runs, so they take precedence.
+    fn export_star(&mut self, location: Location, path: Path) -> Expr {
+        let from_ref = self.generate_symbol("from");
+        let key_ref = self.generate_symbol("key");
+        let object = self.object(location);
+        let loop_stmt = copy_exports(
+            location,
+            identifier(location, self.exports_ref),
+            from_ref,
+            key_ref,
+            object,
+        );
+        let function = function(location, vec![arg(location, from_ref)], vec![loop_stmt]);
+        call(
+            expr(location, ExprKind::Function { function }),
+            vec![require(path)],
+        )
+    }
+
+    fn export_stmts(mut self) -> Vec<Stmt> {
+        if !self.has_es6_exports {
+            return vec![];
+        }
+
+        let exports = std::mem::take(&mut self.exports);
+        let mut stmts = vec![self.define_export(
+            0,
+            "__esModule",
+            vec![property(
+                "value",
+                expr(0, ExprKind::Boolean { value: true }),
+            )],
+        )];
+        for (alias, location, reference) in exports {
+            // Imports that are exported again are read off of their namespace
+            let mut target = reference;
+            while self.symbols[target].link != INVALID_REF {
+                target = self.symbols[target].link;
+            }
+            let value = if self.symbols[target].namespace_alias.is_some() {
+                expr(location, ExprKind::ImportIdentifier { reference })
+            } else {
+                identifier(location, reference)
+            };
+            let return_stmt = Stmt {
+                location,
+                data: Box::new(StmtKind::Return { value: Some(value) }),
+            };
+            let getter = function(location, vec![], vec![return_stmt]);
+            stmts.push(self.define_export(
+                location,
+                &alias,
+                vec![
+                    property(
+                        "enumerable",
+                        expr(location, ExprKind::Boolean { value: true }),
+                    ),
+                    property(
+                        "get",
+                        expr(location, ExprKind::Function { function: getter }),
+                    ),
+                ],
+            ));
+        }
+        stmts
+    }
+
+    // Object.defineProperty(exports, "name", { ... });
+    fn define_export(&mut self, location: Location, name: &str, descriptor: Vec<Property>) -> Stmt {
+        let object = self.object(location);
+        let value = call(
+            dot(object, "defineProperty"),
+            vec![
+                identifier(location, self.exports_ref),
+                string(location, name),
+                expr(
+                    location,
+                    ExprKind::Object {
+                        properties: descriptor,
+                    },
+                ),
+            ],
+        );
+        Stmt {
+            location,
+            data: Box::new(StmtKind::Expr { value }),
+        }
+    }
+
+    // The global "Object"
+    fn object(&mut self, location: Location) -> Expr {
+        if self.object_ref == INVALID_REF {
+            self.object_ref = self.generate_symbol("Object");
+            self.symbols[self.object_ref].kind = SymbolKind::Unbound;
+        }
+        identifier(location, self.object_ref)
+    }
+
+    fn generate_symbol(&mut self, name: &str) -> Reference {
+        generate_symbol(self.symbols, self.exports_ref.outer, name)
+    }
+}
+
+// A CommonJS file in a bundle has its own "module" and "exports", and a file
+// that is loaded with "require()" must not run until it's required. These
+// files are wrapped in a closure that runs the file the first time it's
+// called and returns its exports from then on:
+//
+//   var __commonJS = function(cb, mod) {
+//     return function() {
+//       return mod || cb((mod = {exports: {}}).exports, mod), mod.exports;
+//     };
+//   };
+//   var require_foo = __commonJS(function(exports, module) {
+//     module.exports = 123;
+//   });
+//
+// The helpers are ES5 so they don't need to be lowered for any target.
+pub fn commonjs_helper(symbols: &mut SymbolMap, helper_ref: Reference) -> Stmt {
+    let location = 0;
+    let cb_ref = generate_symbol(symbols, helper_ref.outer, "cb");
+    let mod_ref = generate_symbol(symbols, helper_ref.outer, "mod");
+    let module = || identifier(location, mod_ref);
+
+    let new_module = expr(
+        location,
+        ExprKind::Object {
+            properties: vec![property(
+                "exports",
+                expr(location, ExprKind::Object { properties: vec![] }),
+            )],
+        },
+    );
+    let run = call(
+        identifier(location, cb_ref),
+        vec![
+            dot(
+                binary(location, OperatorCode::BinOpAssign, module(), new_module),
+                "exports",
+            ),
+            module(),
+        ],
+    );
+    let value = binary(
+        location,
+        OperatorCode::BinOpComma,
+        binary(location, OperatorCode::BinOpLogicalOr, module(), run),
+        dot(module(), "exports"),
+    );
+    let inner = function(location, vec![], vec![return_stmt(location, value)]);
+    let outer = function(
+        location,
+        vec![arg(location, cb_ref), arg(location, mod_ref)],
+        vec![return_stmt(
+            location,
+            expr(location, ExprKind::Function { function: inner }),
+        )],
+    );
+    Stmt {
+        location,
+        data: Box::new(declare_var(
+            location,
+            helper_ref,
+            expr(location, ExprKind::Function { function: outer }),
+        )),
+    }
+}
+
+// An import statement sees the exports of a CommonJS file as the named
+// exports, and "module.exports" itself as the default export unless the file
+// was converted from ES6 and says so with "__esModule":
+//
+//   var __toModule = function(mod) {
+//     return mod && mod.__esModule ? mod : Object.create(Object(mod), {default: {value: mod, enumerable: true}});
+//   };
+//
+// The named exports are read through the prototype, so they stay live.
+pub fn to_module_helper(symbols: &mut SymbolMap, helper_ref: Reference) -> Stmt {
+    let location = 0;
+    let mod_ref = generate_symbol(symbols, helper_ref.outer, "mod");
+    let object_ref = generate_symbol(symbols, helper_ref.outer, "Object");
+    symbols[object_ref].kind = SymbolKind::Unbound;
+    let module = || identifier(location, mod_ref);
+    let object = || identifier(location, object_ref);
+
+    let test = binary(
+        location,
+        OperatorCode::BinOpLogicalAnd,
+        module(),
+        dot(module(), "__esModule"),
+    );
+    let descriptors = expr(
+        location,
+        ExprKind::Object {
+            properties: vec![property(
+                "default",
+                expr(
+                    location,
+                    ExprKind::Object {
+                        properties: vec![
+                            property("value", module()),
+                            property(
+                                "enumerable",
+                                expr(location, ExprKind::Boolean { value: true }),
+                            ),
+                        ],
+                    },
+                ),
+            )],
+        },
+    );
+    let create = call(
+        dot(object(), "create"),
+        vec![call(object(), vec![module()]), descriptors],
+    );
+    let value = expr(
+        location,
+        ExprKind::If {
+            test,
+            yes: module(),
+            no: create,
+        },
+    );
+    let function = function(
+        location,
+        vec![arg(location, mod_ref)],
+        vec![return_stmt(location, value)],
+    );
+    Stmt {
+        location,
+        data: Box::new(declare_var(
+            location,
+            helper_ref,
+            expr(location, ExprKind::Function { function }),
+        )),
+    }
+}
+
+// "export * from" a wrapped file copies its exports onto the namespace object
+// of the file, or onto the exports of the entry point, once it has run. This
+// is the same loop that "export * from" of an external file becomes:
+//
+//   var __reExport = function(target, from) {
+//     for (var key in from)
+//       if (key !== "default" && !(key in target))
+//         Object.defineProperty(target, key, Object.getOwnPropertyDescriptor(from, key));
+//   };
+pub fn re_export_helper(symbols: &mut SymbolMap, helper_ref: Reference) -> Stmt {
+    let location = 0;
+    let target_ref = generate_symbol(symbols, helper_ref.outer, "target");
+    let from_ref = generate_symbol(symbols, helper_ref.outer, "from");
+    let key_ref = generate_symbol(symbols, helper_ref.outer, "key");
+    let object_ref = generate_symbol(symbols, helper_ref.outer, "Object");
+    symbols[object_ref].kind = SymbolKind::Unbound;
+    let loop_stmt = copy_exports(
+        location,
+        identifier(location, target_ref),
+        from_ref,
+        key_ref,
+        identifier(location, object_ref),
+    );
+    let function = function(
+        location,
+        vec![arg(location, target_ref), arg(location, from_ref)],
+        vec![loop_stmt],
+    );
+    Stmt {
+        location,
+        data: Box::new(declare_var(
+            location,
+            helper_ref,
+            expr(location, ExprKind::Function { function }),
+        )),
+    }
+}
+
+// "__reExport(target, from)"
+pub fn re_export(
+    location: Location,
+    helper_ref: Reference,
+    target_ref: Reference,
+    from_ref: Reference,
+) -> Expr {
+    call(
+        identifier(location, helper_ref),
+        vec![
+            identifier(location, target_ref),
+            identifier(location, from_ref),
+        ],
+    )
+}
+
+// The loop that copies every export of "from" except the default export onto
+// "target", leaving out the names that "target" already has
+fn copy_exports(
+    location: Location,
+    target: Expr,
+    from_ref: Reference,
+    key_ref: Reference,
+    object: Expr,
+) -> Stmt {
+    let from = || identifier(location, from_ref);
+    let key = || identifier(location, key_ref);
+
+    let test = binary(
+        location,
+        OperatorCode::BinOpLogicalAnd,
+        binary(
+            location,
+            OperatorCode::BinOpStrictNe,
+            key(),
+            string(location, "default"),
+        ),
+        expr(
+            location,
+            ExprKind::Unary {
+                op_code: OperatorCode::UnOpNot,
+                value: binary(location, OperatorCode::BinOpIn, key(), target.clone()),
+            },
+        ),
+    );
+    let descriptor = call(
+        dot(object.clone(), "getOwnPropertyDescriptor"),
+        vec![from(), key()],
+    );
+    let define = call(
+        dot(object, "defineProperty"),
+        vec![target, key(), descriptor],
+    );
+    Stmt {
+        location,
+        data: Box::new(StmtKind::ForIn {
+            init: Stmt {
+                location,
+                data: Box::new(StmtKind::Local {
+                    decls: vec![Decl {
+                        binding: binding(location, key_ref),
+                        value: None,
+                    }],
+                    kind: LocalKind::Var,
+                    is_export: false,
+                    was_ts_import_equals_in_namespace: false,
+                }),
+            },
+            value: from(),
+            body: Stmt {
+                location,
+                data: Box::new(StmtKind::If {
+                    test,
+                    yes: Stmt {
+                        location,
+                        data: Box::new(StmtKind::Expr { value: define }),
+                    },
+                    no: None,
+                }),
+            },
+        }),
+    }
+}
+
+// var require_foo = __commonJS(function(exports, module) { ... });
+pub fn wrap_commonjs(
+    helper_ref: Reference,
+    wrapper_ref: Reference,
+    exports_ref: Reference,
+    module_ref: Reference,
+    stmts: Vec<Stmt>,
+) -> Stmt {
+    let location = stmts.first().map_or(0, |stmt| stmt.location);
+    let args = vec![arg(location, exports_ref), arg(location, module_ref)];
+    let closure = expr(
+        location,
+        ExprKind::Function {
+            function: function(location, args, stmts),
+        },
+    );
+    Stmt {
+        location,
+        data: Box::new(declare_var(
+            location,
+            wrapper_ref,
+            call(identifier(location, helper_ref), vec![closure]),
+        )),
+    }
+}
+
+// An ES6 file that is loaded with "require()" can't run in the order of the
+// bundle, since it has to run when it's first required. It's wrapped in a
+// closure that runs it once, the first time it's called:
+//
+//   var __esm = function(fn, res) {
+//     return function() {
+//       return fn && (res = fn(fn = 0)), res;
+//     };
+//   };
+//
+// Clearing "fn" before running it means that a call from inside an import
+// cycle returns right away, like an import of a module that is still being
+// evaluated.
+pub fn esm_helper(symbols: &mut SymbolMap, helper_ref: Reference) -> Stmt {
+    let location = 0;
+    let fn_ref = generate_symbol(symbols, helper_ref.outer, "fn");
+    let res_ref = generate_symbol(symbols, helper_ref.outer, "res");
+    let fn_ = || identifier(location, fn_ref);
+    let res = || identifier(location, res_ref);
+
+    let run = call(
+        fn_(),
+        vec![binary(
+            location,
+            OperatorCode::BinOpAssign,
+            fn_(),
+            expr(location, ExprKind::Number { value: 0.0 }),
+        )],
+    );
+    let value = binary(
+        location,
+        OperatorCode::BinOpComma,
+        binary(
+            location,
+            OperatorCode::BinOpLogicalAnd,
+            fn_(),
+            binary(location, OperatorCode::BinOpAssign, res(), run),
+        ),
+        res(),
+    );
+    let inner = function(location, vec![], vec![return_stmt(location, value)]);
+    let outer = function(
+        location,
+        vec![arg(location, fn_ref), arg(location, res_ref)],
+        vec![return_stmt(
+            location,
+            expr(location, ExprKind::Function { function: inner }),
+        )],
+    );
+    Stmt {
+        location,
+        data: Box::new(declare_var(
+            location,
+            helper_ref,
+            expr(location, ExprKind::Function { function: outer }),
+        )),
+    }
+}
+
+// The top-level declarations of the file are hoisted out of the closure so
+// that the other files in the bundle can still refer to them directly. Only
+// the code that initializes them runs in the closure:
+//
+//   let a = 1;                    var a;
+//   export class B {}       =>    var init_foo = __esm(function() {
+//   export function c() {}          a = 1;
+//                                   B = class B {};
+//                                 });
+//                                 function c() {}
+//
+// Hoisting turns "let", "const" and "class" into "var", so reading one of
+// these before the closure has run gives "undefined" instead of an error.
+pub fn wrap_esm(helper_ref: Reference, init_ref: Reference, stmts: Vec<Stmt>) -> Vec<Stmt> {
+    let location = stmts.first().map_or(0, |stmt| stmt.location);
+    let mut hoisted_refs = vec![];
+    let mut functions = vec![];
+    let mut body = vec![];
+
+    for stmt in stmts {
+        let location = stmt.location;
+        match *stmt.data {
+            StmtKind::Function { .. } => functions.push(stmt),
+            StmtKind::Local { decls, .. } => {
+                let mut values = vec![];
+                for decl in decls {
+                    collect_binding_refs(&decl.binding, &mut hoisted_refs);
+                    if let Some(value) = decl.value {
+                        values.push(binary(
+                            location,
+                            OperatorCode::BinOpAssign,
+                            binding_to_expr(&decl.binding),
+                            value,
+                        ));
+                    }
+                }
+                if let Some(value) = values
+                    .into_iter()
+                    .reduce(|left, right| binary(location, OperatorCode::BinOpComma, left, right))
+                {
+                    body.push(Stmt {
+                        location,
+                        data: Box::new(StmtKind::Expr { value }),
+                    });
+                }
+            }
+            StmtKind::Class { class, .. } if class.name.is_some() => {
+                let name = class.name.clone().expect("Checked above");
+                hoisted_refs.push(name.reference);
+                body.push(Stmt {
+                    location,
+                    data: Box::new(StmtKind::Expr {
+                        value: binary(
+                            location,
+                            OperatorCode::BinOpAssign,
+                            identifier(name.loc, name.reference),
+                            expr(location, ExprKind::Class { class }),
+                        ),
+                    }),
+                });
+            }
+            data => body.push(Stmt {
+                location,
+                data: Box::new(data),
+            }),
+        }
+    }
+
+    let mut result = vec![];
+    if !hoisted_refs.is_empty() {
+        result.push(Stmt {
+            location,
+            data: Box::new(StmtKind::Local {
+                decls: hoisted_refs
+                    .into_iter()
+                    .map(|reference| Decl {
+                        binding: binding(location, reference),
+                        value: None,
+                    })
+                    .collect(),
+                kind: LocalKind::Var,
+                is_export: false,
+                was_ts_import_equals_in_namespace: false,
+            }),
+        });
+    }
+    let closure = expr(
+        location,
+        ExprKind::Function {
+            function: function(location, vec![], body),
+        },
+    );
+    result.push(Stmt {
+        location,
+        data: Box::new(declare_var(
+            location,
+            init_ref,
+            call(identifier(location, helper_ref), vec![closure]),
+        )),
+    });
+    result.extend(functions);
+    result
+}
+
+// "(init_foo(), foo_exports)", which is what "require()" of a wrapped ES6 file
+// turns into
+pub fn require_esm(location: Location, init_ref: Reference, namespace_ref: Reference) -> Expr {
+    binary(
+        location,
+        OperatorCode::BinOpComma,
+        call_wrapper(location, init_ref),
+        identifier(location, namespace_ref),
+    )
+}
+
+// "import()" of a wrapped file in the same bundle. The closure runs in a
+// later tick, like the file would if it were loaded for real:
+//
+//   Promise.resolve().then(function() { return __toModule(require_foo()); })
+pub fn resolve_later(location: Location, promise_ref: Reference, value: Expr) -> Expr {
+    let resolved = call(dot(identifier(location, promise_ref), "resolve"), vec![]);
+    let callback = function(location, vec![], vec![return_stmt(location, value)]);
+    call(
+        dot(resolved, "then"),
+        vec![expr(location, ExprKind::Function { function: callback })],
+    )
+}
+
+fn collect_binding_refs(binding: &Binding, refs: &mut Vec<Reference>) {
+    ensure_sufficient_stack(|| match binding.data.as_ref() {
+        BindingKind::Missing => {}
+        BindingKind::Identifier { reference } => refs.push(*reference),
+        BindingKind::Array { items, .. } => {
+            for item in items {
+                collect_binding_refs(&item.binding, refs);
+            }
+        }
+        BindingKind::Object { properties } => {
+            for property in properties {
+                collect_binding_refs(&property.value, refs);
+            }
+        }
+    })
+}
+
+// Turns a pattern in a declaration into the same pattern in an assignment,
+// as in "let {a, b: [c]} = d" => "({a, b: [c]} = d)"
+fn binding_to_expr(binding: &Binding) -> Expr {
+    ensure_sufficient_stack(|| {
+        let location = binding.location;
+        let with_default = |value: Expr, default_value: &Option<Expr>| match default_value {
+            Some(default_value) => binary(
+                location,
+                OperatorCode::BinOpAssign,
+                value,
+                default_value.clone(),
+            ),
+            None => value,
+        };
+        let data = match binding.data.as_ref() {
+            BindingKind::Missing => ExprKind::Missing,
+            BindingKind::Identifier { reference } => ExprKind::Identifier {
+                reference: *reference,
+            },
+            BindingKind::Array { items, has_spread } => ExprKind::Array {
+                items: items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        let value = binding_to_expr(&item.binding);
+                        if *has_spread && i + 1 == items.len() {
+                            expr(location, ExprKind::Spread { value })
+                        } else {
+                            with_default(value, &item.default_value)
+                        }
+                    })
+                    .collect(),
+            },
+            BindingKind::Object { properties } => ExprKind::Object {
+                properties: properties
+                    .iter()
+                    .map(|property| Property {
+                        kind: if property.is_spread {
+                            PropertyKind::PropertySpread
+                        } else {
+                            PropertyKind::PropertyNormal
+                        },
+                        is_computed: property.is_computed,
+                        is_method: false,
+                        is_static: false,
+                        key: property.key.clone(),
+                        prefer_quoted_key: false,
+                        value: Some(binding_to_expr(&property.value)),
+                        initializer: property.default_value.clone(),
+                    })
+                    .collect(),
+            },
+        };
+        expr(location, data)
+    })
+}
+
+// "require_foo()", which is what "require()" of a wrapped file turns into
+pub fn call_wrapper(location: Location, wrapper_ref: Reference) -> Expr {
+    call(identifier(location, wrapper_ref), vec![])
+}
+
+// "__toModule(require_foo())" for an import statement of a wrapped file
+pub fn import_wrapper(location: Location, helper_ref: Reference, wrapper_ref: Reference) -> Expr {
+    to_module(location, helper_ref, call_wrapper(location, wrapper_ref))
+}
+
+// Declares the helper above after the directives of the file
+pub fn declare_to_module(stmts: &mut Vec<Stmt>, symbols: &mut SymbolMap, helper_ref: Reference) {
+    let helper = to_module_helper(symbols, helper_ref);
+    insert_after_directives(stmts, vec![helper]);
+}
+
+// "__toModule(value)"
+pub fn to_module(location: Location, helper_ref: Reference, value: Expr) -> Expr {
+    call(identifier(location, helper_ref), vec![value])
+}
+
+// The namespace object of a bundled ES6 file, for "import * as ns" and
+// "export * as ns". Each export is a getter so that it stays a live binding:
+//
+//   var foo_exports = {
+//     get a() { return a; },
+//     get default() { return foo_default; }
+//   };
+//
+// The exports are import identifiers since some of them are read off of the
+// namespace of a wrapped file.
+pub fn namespace_object(
+    location: Location,
+    namespace_ref: Reference,
+    exports: impl IntoIterator<Item = (String, Reference)>,
+) -> Stmt {
+    let properties = exports
+        .into_iter()
+        .map(|(alias, reference)| {
+            let value = expr(location, ExprKind::ImportIdentifier { reference });
+            let getter = function(location, vec![], vec![return_stmt(location, value)]);
+            Property {
+                kind: PropertyKind::PropertyGet,
+                is_method: true,
+                ..property(
+                    &alias,
+                    expr(location, ExprKind::Function { function: getter }),
+                )
+            }
+        })
+        .collect();
+    Stmt {
+        location,
+        data: Box::new(declare_var(
+            location,
+            namespace_ref,
+            expr(location, ExprKind::Object { properties }),
+        )),
+    }
+}
+
+// The name, or the name with a number after it if a symbol in the file already
+// has that name. Output that isn't bundled isn't renamed, so a generated symbol
+// that may be seen by code in the file has to get a name of its own.
+pub fn unused_name(symbols: &SymbolMap, source_index: usize, name: &str) -> String {
+    let names: HashSet<&str> = symbols.outer[source_index]
+        .iter()
+        .map(|symbol| symbol.name.as_str())
+        .collect();
+    let mut candidate = name.to_owned();
+    let mut i = 2;
+    while names.contains(candidate.as_str()) {
+        candidate = format!("{}{}", name, i);
+        i += 1;
+    }
+    candidate
+}
+
+pub fn generate_symbol(symbols: &mut SymbolMap, source_index: usize, name: &str) -> Reference {
+    let inner = &mut symbols.outer[source_index];
+    let reference = Reference::new(source_index, inner.len());
+    inner.push(Symbol {
+        must_not_be_renamed: true,
+        ..Symbol::new(SymbolKind::Other, name)
+    });
+    reference
+}
+
+fn expr(location: Location, data: ExprKind) -> Expr {
+    Expr {
+        location,
+        data: Box::new(data),
+    }
+}
+
+fn identifier(location: Location, reference: Reference) -> Expr {
+    expr(location, ExprKind::Identifier { reference })
+}
+
+fn string(location: Location, text: &str) -> Expr {
+    expr(
+        location,
+        ExprKind::String {
+            value: string_to_utf16(text),
+        },
+    )
+}
+
+fn binary(location: Location, op_code: OperatorCode, left: Expr, right: Expr) -> Expr {
+    expr(
+        location,
+        ExprKind::Binary {
+            op_code,
+            left,
+            right,
+        },
+    )
+}
+
+fn dot(target: Expr, name: &str) -> Expr {
+    let location = target.location;
+    expr(
+        location,
+        ExprKind::Dot {
+            target,
+            name: name.to_owned(),
+            name_location: location,
+            optional_chain: OptionalChain::None,
+            is_parenthesized: false,
+        },
+    )
+}
+
+fn call(target: Expr, args: Vec<Expr>) -> Expr {
+    expr(
+        target.location,
+        ExprKind::Call {
+            target,
+            args,
+            optional_chain: OptionalChain::None,
+            is_parenthesized: false,
+            is_direct_eval: false,
+            can_be_unwrapped_if_unused: false,
+        },
+    )
+}
+
+fn require(path: Path) -> Expr {
+    expr(
+        path.loc,
+        ExprKind::Require {
+            path,
+            is_es6_import: true,
+        },
+    )
+}
+
+fn property(key: &str, value: Expr) -> Property {
+    Property {
+        kind: PropertyKind::PropertyNormal,
+        is_computed: false,
+        is_method: false,
+        is_static: false,
+        key: string(value.location, key),
+        prefer_quoted_key: false,
+        value: Some(value),
+        initializer: None,
+    }
+}
+
+fn function(location: Location, args: Vec<Arg>, stmts: Vec<Stmt>) -> Function {
+    Function {
+        name: None,
+        args,
+        is_async: false,
+        is_generator: false,
+        has_rest_arg: false,
+        body: FunctionBody { location, stmts },
+    }
+}
+
+fn arg(location: Location, reference: Reference) -> Arg {
+    Arg {
+        is_typescript_ctor_field: false,
+        binding: binding(location, reference),
+        default_: None,
+    }
+}
+
+fn return_stmt(location: Location, value: Expr) -> Stmt {
+    Stmt {
+        location,
+        data: Box::new(StmtKind::Return { value: Some(value) }),
+    }
+}
+
+fn binding(location: Location, reference: Reference) -> Binding {
+    Binding {
+        location,
+        data: Box::new(BindingKind::Identifier { reference }),
+    }
+}
+
+fn declare_var(location: Location, reference: Reference, value: Expr) -> StmtKind {
+    StmtKind::Local {
+        decls: vec![Decl {
+            binding: binding(location, reference),
+            value: Some(value),
+        }],
+        kind: LocalKind::Var,
+        is_export: false,
+        was_ts_import_equals_in_namespace: false,
+    }
+}
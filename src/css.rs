@@ -0,0 +1,1156 @@
+// CSS support is much simpler than JavaScript support. A stylesheet is a list
+// of rules whose selectors and values are kept as plain token lists, which is
+// enough to bundle files together through "@import" and to minify whitespace
+// without having to understand every property.
+//
+// Parsing follows the error recovery of the CSS syntax specification, so
+// problems are only warnings. Browsers would skip over the same things.
+
+use crate::logging::{Log, Source};
+use std::collections::HashSet;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident,
+    Function, // The text includes the "("
+    AtKeyword,
+    Hash,
+    String, // The text includes the quotes
+    Url,    // An unquoted "url(...)", including "url(" and ")"
+    Number,
+    Percentage,
+    Dimension,
+    Delim,
+    Whitespace,
+    CDO,
+    CDC,
+    Colon,
+    Semicolon,
+    Comma,
+    OpenBracket,
+    CloseBracket,
+    OpenParen,
+    CloseParen,
+    OpenBrace,
+    CloseBrace,
+    EndOfFile,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+
+    // The text as written. Runs of whitespace and comments are a single " ".
+    pub text: String,
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stylesheet {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Rule {
+    // "@import" rules are kept apart from other at-rules since the bundler
+    // follows them. The path is the unquoted text, and the conditions are
+    // the media queries after it, if any.
+    Import {
+        path: String,
+        range: Range<usize>,
+        conditions: Vec<Token>,
+    },
+
+    // Other at-rules such as "@media" and "@font-face". The block holds rules
+    // or declarations depending on the at-rule.
+    At {
+        name: String,
+        prelude: Vec<Token>,
+        block: Option<Vec<Rule>>,
+    },
+
+    // "a, b { color: red }"
+    Qualified {
+        prelude: Vec<Token>,
+        block: Vec<Rule>,
+    },
+
+    Declaration {
+        name: String,
+        value: Vec<Token>,
+        important: bool,
+    },
+}
+
+pub fn is_external_url(path: &str) -> bool {
+    path.starts_with("//") || path.starts_with("data:") || path.contains("://")
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Tokenizer
+
+struct Tokenizer<'a> {
+    log: &'a Log,
+    source: &'a Source,
+    tokens: Vec<Token>,
+    start: usize,
+    end: usize,
+    current: usize,
+
+    // This is "None" at the end of the file
+    code_point: Option<char>,
+}
+
+pub fn tokenize(log: &Log, source: &Source) -> Vec<Token> {
+    let mut t = Tokenizer {
+        log,
+        source,
+        tokens: vec![],
+        start: 0,
+        end: 0,
+        current: 0,
+        code_point: None,
+    };
+    t.step();
+    loop {
+        let kind = t.next();
+        t.push(kind);
+        if kind == TokenKind::EndOfFile {
+            break;
+        }
+    }
+    t.tokens
+}
+
+impl<'a> Tokenizer<'a> {
+    fn step(&mut self) {
+        self.code_point = self.source.contents[self.current..].chars().next();
+        self.end = self.current;
+        self.current += self.code_point.map_or(0, char::len_utf8);
+    }
+
+    fn peek(&self, n: usize) -> Option<char> {
+        self.source.contents[self.current..].chars().nth(n)
+    }
+
+    fn push(&mut self, kind: TokenKind) {
+        let text = if kind == TokenKind::Whitespace {
+            " ".to_owned()
+        } else {
+            self.source.contents[self.start..self.end].to_owned()
+        };
+
+        // A comment between two runs of whitespace shouldn't leave two tokens
+        if kind == TokenKind::Whitespace
+            && self.tokens.last().map(|t| t.kind) == Some(TokenKind::Whitespace)
+        {
+            return;
+        }
+        self.tokens.push(Token {
+            kind,
+            text,
+            range: self.start..self.end,
+        });
+    }
+
+    fn next(&mut self) -> TokenKind {
+        loop {
+            self.start = self.end;
+            let c = match self.code_point {
+                None => return TokenKind::EndOfFile,
+                Some(c) => c,
+            };
+
+            return match c {
+                ' ' | '\t' | '\n' | '\r' | '\x0C' => {
+                    while let Some(' ') | Some('\t') | Some('\n') | Some('\r') | Some('\x0C') =
+                        self.code_point
+                    {
+                        self.step();
+                    }
+                    TokenKind::Whitespace
+                }
+
+                '/' if self.peek(0) == Some('*') => {
+                    self.skip_comment();
+                    continue;
+                }
+
+                '"' | '\'' => self.string(c),
+
+                '#' => {
+                    self.step();
+                    if self.code_point.is_some_and(is_name_char) || self.is_valid_escape() {
+                        self.name();
+                        TokenKind::Hash
+                    } else {
+                        TokenKind::Delim
+                    }
+                }
+
+                '@' => {
+                    self.step();
+                    if self.would_start_identifier() {
+                        self.name();
+                        TokenKind::AtKeyword
+                    } else {
+                        TokenKind::Delim
+                    }
+                }
+
+                '<' if self.source.contents[self.end..].starts_with("<!--") => {
+                    for _ in 0..4 {
+                        self.step();
+                    }
+                    TokenKind::CDO
+                }
+
+                '-' if self.source.contents[self.end..].starts_with("-->") => {
+                    for _ in 0..3 {
+                        self.step();
+                    }
+                    TokenKind::CDC
+                }
+
+                _ if self.would_start_number() => self.numeric(),
+                _ if self.would_start_identifier() => self.ident_like(),
+
+                _ => {
+                    self.step();
+                    match c {
+                        ':' => TokenKind::Colon,
+                        ';' => TokenKind::Semicolon,
+                        ',' => TokenKind::Comma,
+                        '[' => TokenKind::OpenBracket,
+                        ']' => TokenKind::CloseBracket,
+                        '(' => TokenKind::OpenParen,
+                        ')' => TokenKind::CloseParen,
+                        '{' => TokenKind::OpenBrace,
+                        '}' => TokenKind::CloseBrace,
+                        _ => TokenKind::Delim,
+                    }
+                }
+            };
+        }
+    }
+
+    // Comments are dropped, except that "a/**/b" is two identifiers and can't
+    // be printed as "ab". A comment like that becomes whitespace instead.
+    fn skip_comment(&mut self) {
+        let start = self.end;
+        self.step();
+        self.step();
+        loop {
+            match self.code_point {
+                None => {
+                    self.log.add_range_warning(
+                        self.source,
+                        start..start + 2,
+                        "Expected \"*/\" to terminate multi-line comment".to_owned(),
+                    );
+                    return;
+                }
+                Some('*') if self.peek(0) == Some('/') => {
+                    self.step();
+                    self.step();
+                    break;
+                }
+                Some(_) => self.step(),
+            }
+        }
+
+        let prev = self.tokens.last().map(|t| t.kind);
+        let would_run_together = match self.code_point {
+            Some(c) if is_name_char(c) || c == '\\' => matches!(
+                prev,
+                Some(TokenKind::Ident)
+                    | Some(TokenKind::AtKeyword)
+                    | Some(TokenKind::Hash)
+                    | Some(TokenKind::Number)
+                    | Some(TokenKind::Dimension)
+            ),
+            Some('%') | Some('.') => prev == Some(TokenKind::Number),
+            _ => false,
+        };
+        if would_run_together {
+            self.tokens.push(Token {
+                kind: TokenKind::Whitespace,
+                text: " ".to_owned(),
+                range: start..self.end,
+            });
+        }
+    }
+
+    fn string(&mut self, quote: char) -> TokenKind {
+        self.step();
+        loop {
+            match self.code_point {
+                Some(c) if c == quote => {
+                    self.step();
+                    break;
+                }
+                Some('\\') => {
+                    self.step();
+                    if self.code_point.is_some() {
+                        self.step();
+                    }
+                }
+                None | Some('\n') | Some('\r') | Some('\x0C') => {
+                    self.log.add_range_warning(
+                        self.source,
+                        self.start..self.end,
+                        "Unterminated string token".to_owned(),
+                    );
+                    break;
+                }
+                Some(_) => self.step(),
+            }
+        }
+        TokenKind::String
+    }
+
+    fn is_valid_escape(&self) -> bool {
+        self.code_point == Some('\\') && !matches!(self.peek(0), None | Some('\n') | Some('\r'))
+    }
+
+    fn would_start_identifier(&self) -> bool {
+        match self.code_point {
+            Some('-') => match self.peek(0) {
+                Some('-') => true,
+                Some('\\') => !matches!(self.peek(1), None | Some('\n') | Some('\r')),
+                Some(c) => is_name_start_char(c),
+                None => false,
+            },
+            Some('\\') => self.is_valid_escape(),
+            Some(c) => is_name_start_char(c),
+            None => false,
+        }
+    }
+
+    fn would_start_number(&self) -> bool {
+        let is_digit = |c: Option<char>| matches!(c, Some('0'..='9'));
+        match self.code_point {
+            Some('+') | Some('-') => {
+                is_digit(self.peek(0)) || (self.peek(0) == Some('.') && is_digit(self.peek(1)))
+            }
+            Some('.') => is_digit(self.peek(0)),
+            c => is_digit(c),
+        }
+    }
+
+    fn name(&mut self) {
+        loop {
+            if self.is_valid_escape() {
+                self.step();
+                self.step();
+            } else if self.code_point.is_some_and(is_name_char) {
+                self.step();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn numeric(&mut self) -> TokenKind {
+        if let Some('+') | Some('-') = self.code_point {
+            self.step();
+        }
+        self.skip_digits();
+        if self.code_point == Some('.') && matches!(self.peek(0), Some('0'..='9')) {
+            self.step();
+            self.skip_digits();
+        }
+        if let Some('e') | Some('E') = self.code_point {
+            let has_exponent = match self.peek(0) {
+                Some('0'..='9') => true,
+                Some('+') | Some('-') => matches!(self.peek(1), Some('0'..='9')),
+                _ => false,
+            };
+            if has_exponent {
+                self.step();
+                self.step();
+                self.skip_digits();
+            }
+        }
+
+        if self.would_start_identifier() {
+            self.name();
+            TokenKind::Dimension
+        } else if self.code_point == Some('%') {
+            self.step();
+            TokenKind::Percentage
+        } else {
+            TokenKind::Number
+        }
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some('0'..='9') = self.code_point {
+            self.step();
+        }
+    }
+
+    fn ident_like(&mut self) -> TokenKind {
+        self.name();
+        if self.code_point != Some('(') {
+            return TokenKind::Ident;
+        }
+        self.step();
+
+        // "url(" is only a function if its argument is a quoted string
+        if !self.source.contents[self.start..self.end].eq_ignore_ascii_case("url(") {
+            return TokenKind::Function;
+        }
+        let rest = self.source.contents[self.end..].trim_start_matches(is_whitespace);
+        if rest.starts_with('"') || rest.starts_with('\'') {
+            return TokenKind::Function;
+        }
+
+        loop {
+            match self.code_point {
+                Some(')') => {
+                    self.step();
+                    break;
+                }
+                Some('\\') => {
+                    self.step();
+                    if self.code_point.is_some() {
+                        self.step();
+                    }
+                }
+                None => {
+                    self.log.add_range_warning(
+                        self.source,
+                        self.start..self.end,
+                        "Expected \")\" to end URL token".to_owned(),
+                    );
+                    break;
+                }
+                Some(_) => self.step(),
+            }
+        }
+        TokenKind::Url
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0C')
+}
+
+fn is_name_start_char(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c >= '\u{80}'
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c) || c.is_ascii_digit() || c == '-'
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Parser
+
+struct Parser<'a> {
+    log: &'a Log,
+    source: &'a Source,
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+pub fn parse(log: &Log, source: &Source) -> Stylesheet {
+    let mut p = Parser {
+        log,
+        source,
+        tokens: tokenize(log, source),
+        index: 0,
+    };
+    let rules = p.parse_rules(true);
+    Stylesheet { rules }
+}
+
+// The blocks of these at-rules contain rules. The blocks of all others, like
+// "@font-face" and "@page", contain declarations.
+fn at_rule_has_rule_block(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let name = match name.strip_prefix('-') {
+        Some(rest) => rest.split_once('-').map_or(rest, |(_, name)| name),
+        None => &name,
+    };
+    matches!(
+        name,
+        "media" | "supports" | "document" | "layer" | "container" | "keyframes" | "scope"
+    )
+}
+
+impl<'a> Parser<'a> {
+    fn kind(&self) -> TokenKind {
+        self.tokens[self.index].kind
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.index].clone();
+        if token.kind != TokenKind::EndOfFile {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn expected(&self, text: &str) {
+        let token = &self.tokens[self.index];
+        let found = if token.kind == TokenKind::EndOfFile {
+            "end of file".to_owned()
+        } else {
+            format!("{:?}", token.text)
+        };
+        self.log.add_range_warning(
+            self.source,
+            token.range.clone(),
+            format!("Expected {:?} but found {}", text, found),
+        );
+    }
+
+    fn parse_rules(&mut self, is_top_level: bool) -> Vec<Rule> {
+        let mut rules = vec![];
+        loop {
+            match self.kind() {
+                TokenKind::EndOfFile => break,
+                TokenKind::CloseBrace if !is_top_level => break,
+                TokenKind::Whitespace => {
+                    self.advance();
+                }
+
+                // These are only allowed at the top level for old browsers
+                TokenKind::CDO | TokenKind::CDC if is_top_level => {
+                    self.advance();
+                }
+
+                TokenKind::AtKeyword => rules.extend(self.parse_at_rule()),
+                _ => rules.extend(self.parse_qualified_rule()),
+            }
+        }
+        rules
+    }
+
+    fn parse_declarations(&mut self) -> Vec<Rule> {
+        let mut rules = vec![];
+        loop {
+            match self.kind() {
+                TokenKind::EndOfFile | TokenKind::CloseBrace => break,
+                TokenKind::Whitespace | TokenKind::Semicolon => {
+                    self.advance();
+                }
+                TokenKind::AtKeyword => rules.extend(self.parse_at_rule()),
+                TokenKind::Ident => rules.extend(self.parse_declaration()),
+
+                // Nested rules start with a selector such as "&:hover"
+                _ => rules.extend(self.parse_qualified_rule()),
+            }
+        }
+        rules
+    }
+
+    // Returns "None" if the rule was dropped after a warning
+    fn parse_declaration(&mut self) -> Option<Rule> {
+        let name = self.advance().text;
+        while self.kind() == TokenKind::Whitespace {
+            self.advance();
+        }
+        if self.kind() != TokenKind::Colon {
+            self.expected(":");
+            self.parse_component_values(|kind| kind == TokenKind::Semicolon);
+            return None;
+        }
+        self.advance();
+
+        let mut value =
+            trim_whitespace(self.parse_component_values(|kind| kind == TokenKind::Semicolon));
+
+        // "color: red !important"
+        let mut important = false;
+        if let Some(last) = value.last() {
+            if last.kind == TokenKind::Ident && last.text.eq_ignore_ascii_case("important") {
+                let mut i = value.len() - 1;
+                if i > 0 && value[i - 1].kind == TokenKind::Whitespace {
+                    i -= 1;
+                }
+                if i > 0 && value[i - 1].kind == TokenKind::Delim && value[i - 1].text == "!" {
+                    value.truncate(i - 1);
+                    value = trim_whitespace(value);
+                    important = true;
+                }
+            }
+        }
+
+        Some(Rule::Declaration {
+            name,
+            value,
+            important,
+        })
+    }
+
+    fn parse_at_rule(&mut self) -> Option<Rule> {
+        let at_token = self.advance();
+        let name = at_token.text[1..].to_owned();
+        let prelude = trim_whitespace(self.parse_component_values(|kind| {
+            kind == TokenKind::Semicolon || kind == TokenKind::OpenBrace
+        }));
+
+        let block = match self.kind() {
+            TokenKind::OpenBrace => {
+                self.advance();
+                let block = if at_rule_has_rule_block(&name) {
+                    self.parse_rules(false)
+                } else {
+                    self.parse_declarations()
+                };
+                self.expect_close_brace();
+                Some(block)
+            }
+            TokenKind::Semicolon => {
+                self.advance();
+                None
+            }
+            _ => None,
+        };
+
+        if name.eq_ignore_ascii_case("import") && block.is_none() {
+            if let Some(rule) = self.import_rule(&prelude) {
+                return Some(rule);
+            }
+            self.log.add_range_warning(
+                self.source,
+                at_token.range,
+                "Expected a URL after \"@import\"".to_owned(),
+            );
+            return None;
+        }
+
+        Some(Rule::At {
+            name,
+            prelude,
+            block,
+        })
+    }
+
+    // "@import 'a.css'", "@import url(a.css)", and "@import url('a.css')"
+    fn import_rule(&self, prelude: &[Token]) -> Option<Rule> {
+        let first = prelude.first()?;
+        let (path, range, rest) = match first.kind {
+            TokenKind::String => (unquote(&first.text), first.range.clone(), &prelude[1..]),
+            TokenKind::Url => {
+                let inner = &first.text[4..first.text.len() - 1];
+                let inner = inner.trim_matches(is_whitespace);
+                (inner.to_owned(), first.range.clone(), &prelude[1..])
+            }
+            TokenKind::Function if first.text.eq_ignore_ascii_case("url(") => {
+                let mut tokens = prelude[1..]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, t)| t.kind != TokenKind::Whitespace);
+                let (_, string) = tokens.next()?;
+                let (close, paren) = tokens.next()?;
+                if string.kind != TokenKind::String || paren.kind != TokenKind::CloseParen {
+                    return None;
+                }
+                (
+                    unquote(&string.text),
+                    string.range.clone(),
+                    &prelude[close + 2..],
+                )
+            }
+            _ => return None,
+        };
+        Some(Rule::Import {
+            path,
+            range,
+            conditions: trim_whitespace(rest.to_vec()),
+        })
+    }
+
+    fn parse_qualified_rule(&mut self) -> Option<Rule> {
+        let start = self.tokens[self.index].range.start;
+        let prelude =
+            trim_whitespace(self.parse_component_values(|kind| kind == TokenKind::OpenBrace));
+        if self.kind() != TokenKind::OpenBrace {
+            self.log.add_range_warning(
+                self.source,
+                start..self.tokens[self.index].range.start,
+                "Expected \"{\" after the selector".to_owned(),
+            );
+            return None;
+        }
+        self.advance();
+        let block = self.parse_declarations();
+        self.expect_close_brace();
+        Some(Rule::Qualified { prelude, block })
+    }
+
+    fn expect_close_brace(&mut self) {
+        if self.kind() == TokenKind::CloseBrace {
+            self.advance();
+        } else {
+            self.expected("}");
+        }
+    }
+
+    // Collects tokens until one that "stop" matches outside of any nested
+    // block or function, or until a "}" that closes the enclosing block.
+    // Whatever "stop" matched is left for the caller.
+    fn parse_component_values<F: Fn(TokenKind) -> bool>(&mut self, stop: F) -> Vec<Token> {
+        let mut tokens = vec![];
+        let mut stack = vec![];
+        loop {
+            let kind = self.kind();
+            if kind == TokenKind::EndOfFile {
+                break;
+            }
+            if stack.is_empty() && (stop(kind) || kind == TokenKind::CloseBrace) {
+                break;
+            }
+            match kind {
+                TokenKind::OpenParen | TokenKind::Function => stack.push(TokenKind::CloseParen),
+                TokenKind::OpenBracket => stack.push(TokenKind::CloseBracket),
+                TokenKind::OpenBrace => stack.push(TokenKind::CloseBrace),
+                TokenKind::CloseParen | TokenKind::CloseBracket | TokenKind::CloseBrace
+                    if stack.last() == Some(&kind) =>
+                {
+                    stack.pop();
+                }
+                _ => {}
+            }
+            tokens.push(self.advance());
+        }
+        tokens
+    }
+}
+
+fn trim_whitespace(mut tokens: Vec<Token>) -> Vec<Token> {
+    while tokens.last().map(|t| t.kind) == Some(TokenKind::Whitespace) {
+        tokens.pop();
+    }
+    let leading = tokens
+        .iter()
+        .take_while(|t| t.kind == TokenKind::Whitespace)
+        .count();
+    tokens.drain(..leading);
+    tokens
+}
+
+// Escapes are left as they are. They are rare in import paths.
+fn unquote(text: &str) -> String {
+    let quote = text.chars().next().unwrap_or('"');
+    let inner = &text[1..];
+    inner.strip_suffix(quote).unwrap_or(inner).to_owned()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// CSS modules
+
+// The class and ID names in the selectors of a CSS modules file are local to
+// the file. Each one is renamed to a name that no other file uses, and code
+// gets the new names from the file's JavaScript module:
+//
+//   .button { color: red }  =>  .button_SUFFIX { color: red }
+//
+// Names inside ":global(...)", or after ":global" up to the next ",", are
+// left as they are. ":local" does the opposite, which is only needed after
+// ":global". Returns each name along with what it was renamed to, in the order
+// they first appear.
+pub fn rename_local_names(stylesheet: &mut Stylesheet, suffix: &str) -> Vec<(String, String)> {
+    let mut names = vec![];
+    rename_local_names_in_rules(&mut stylesheet.rules, suffix, &mut names);
+    names
+}
+
+fn rename_local_names_in_rules(
+    rules: &mut [Rule],
+    suffix: &str,
+    names: &mut Vec<(String, String)>,
+) {
+    for rule in rules {
+        match rule {
+            Rule::Qualified { prelude, block } => {
+                *prelude =
+                    trim_whitespace(rename_local_names_in_selector(prelude, true, suffix, names));
+                rename_local_names_in_rules(block, suffix, names);
+            }
+            Rule::At {
+                block: Some(block), ..
+            } => rename_local_names_in_rules(block, suffix, names),
+            _ => {}
+        }
+    }
+}
+
+fn rename_local_names_in_selector(
+    tokens: &[Token],
+    is_local_by_default: bool,
+    suffix: &str,
+    names: &mut Vec<(String, String)>,
+) -> Vec<Token> {
+    let mut result = vec![];
+    let mut is_local = is_local_by_default;
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        let next = tokens.get(i + 1);
+        match token.kind {
+            TokenKind::Comma => is_local = is_local_by_default,
+
+            // ":global .a" and ":local .a"
+            TokenKind::Colon if next.is_some_and(|next| local_or_global(next, "").is_some()) => {
+                is_local = local_or_global(&tokens[i + 1], "") == Some(true);
+                i += 2;
+                if tokens.get(i).map(|t| t.kind) == Some(TokenKind::Whitespace) {
+                    i += 1;
+                }
+                continue;
+            }
+
+            // ":global(.a)" and ":local(.a)"
+            TokenKind::Colon if next.is_some_and(|next| local_or_global(next, "(").is_some()) => {
+                let is_local = local_or_global(&tokens[i + 1], "(") == Some(true);
+                let end = closing_paren(tokens, i + 1);
+                let inner = trim_whitespace(tokens[i + 2..end].to_vec());
+                result.extend(rename_local_names_in_selector(
+                    &inner, is_local, suffix, names,
+                ));
+                i = end + 1;
+                continue;
+            }
+
+            TokenKind::Delim if token.text == "." && is_local => {
+                if let Some(next) = next.filter(|next| next.kind == TokenKind::Ident) {
+                    result.push(token.clone());
+                    result.push(Token {
+                        text: local_name(&next.text, suffix, names),
+                        ..next.clone()
+                    });
+                    i += 2;
+                    continue;
+                }
+            }
+
+            TokenKind::Hash if is_local => {
+                result.push(Token {
+                    text: format!("#{}", local_name(&token.text[1..], suffix, names)),
+                    ..token.clone()
+                });
+                i += 1;
+                continue;
+            }
+
+            _ => {}
+        }
+        result.push(token.clone());
+        i += 1;
+    }
+    result
+}
+
+// Returns whether the token is "local" or "global" followed by "after"
+fn local_or_global(token: &Token, after: &str) -> Option<bool> {
+    let kind = if after.is_empty() {
+        TokenKind::Ident
+    } else {
+        TokenKind::Function
+    };
+    if token.kind != kind {
+        return None;
+    }
+    let name = token.text.strip_suffix(after)?;
+    if name.eq_ignore_ascii_case("local") {
+        Some(true)
+    } else if name.eq_ignore_ascii_case("global") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+// Returns the index of the ")" that closes the function at "start", or the
+// end of the tokens if it's missing
+fn closing_paren(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token.kind {
+            TokenKind::Function | TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+fn local_name(name: &str, suffix: &str, names: &mut Vec<(String, String)>) -> String {
+    if let Some((_, local)) = names.iter().find(|(other, _)| other == name) {
+        return local.clone();
+    }
+    let local = format!("{}_{}", name, suffix);
+    names.push((name.to_owned(), local.clone()));
+    local
+}
+
+// Drops the selectors that use one of the given local names, since nothing
+// can have the class or ID once no code imports it. Only names outside of
+// functions count, as ":not(.a)" still matches without ".a". Rules left
+// without any selectors are dropped, and so are at-rules left empty.
+pub fn remove_unused_local_names(stylesheet: &mut Stylesheet, unused: &HashSet<&str>) {
+    remove_unused_local_names_in_rules(&mut stylesheet.rules, unused);
+}
+
+fn remove_unused_local_names_in_rules(rules: &mut Vec<Rule>, unused: &HashSet<&str>) {
+    rules.retain_mut(|rule| match rule {
+        Rule::Qualified { prelude, block } => {
+            let selectors: Vec<Vec<Token>> = split_selectors(prelude)
+                .into_iter()
+                .filter(|selector| !uses_local_name(selector, unused))
+                .collect();
+            if selectors.is_empty() {
+                return false;
+            }
+            let comma = Token {
+                kind: TokenKind::Comma,
+                text: ",".to_owned(),
+                range: 0..0,
+            };
+            let whitespace = Token {
+                kind: TokenKind::Whitespace,
+                text: " ".to_owned(),
+                range: 0..0,
+            };
+            *prelude = selectors.join(&[comma, whitespace][..]);
+            remove_unused_local_names_in_rules(block, unused);
+            true
+        }
+        Rule::At {
+            name,
+            block: Some(block),
+            ..
+        } if at_rule_has_rule_block(name) && !block.is_empty() => {
+            remove_unused_local_names_in_rules(block, unused);
+            !block.is_empty()
+        }
+        _ => true,
+    });
+}
+
+// Splits a selector list at the commas that aren't inside a function
+fn split_selectors(tokens: &[Token]) -> Vec<Vec<Token>> {
+    let mut selectors = vec![vec![]];
+    let mut depth = 0;
+    for token in tokens {
+        match token.kind {
+            TokenKind::Function | TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen => depth -= 1,
+            TokenKind::Comma if depth == 0 => {
+                selectors.push(vec![]);
+                continue;
+            }
+            _ => {}
+        }
+        selectors.last_mut().unwrap().push(token.clone());
+    }
+    selectors.into_iter().map(trim_whitespace).collect()
+}
+
+fn uses_local_name(selector: &[Token], unused: &HashSet<&str>) -> bool {
+    let mut depth = 0;
+    for (i, token) in selector.iter().enumerate() {
+        match token.kind {
+            TokenKind::Function | TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen => depth -= 1,
+            TokenKind::Delim if depth == 0 && token.text == "." => {
+                let next = selector.get(i + 1);
+                if next.is_some_and(|next| unused.contains(next.text.as_str())) {
+                    return true;
+                }
+            }
+            TokenKind::Hash if depth == 0 && unused.contains(&token.text[1..]) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Printer
+
+struct Printer {
+    css: String,
+    minify_whitespace: bool,
+    indent: usize,
+}
+
+pub fn print(stylesheet: &Stylesheet, minify_whitespace: bool) -> String {
+    let mut p = Printer {
+        css: String::new(),
+        minify_whitespace,
+        indent: 0,
+    };
+    for rule in &stylesheet.rules {
+        p.print_rule(rule, true);
+    }
+    p.css
+}
+
+impl Printer {
+    fn print_indent(&mut self) {
+        if !self.minify_whitespace {
+            for _ in 0..self.indent {
+                self.css.push_str("  ");
+            }
+        }
+    }
+
+    fn print_newline(&mut self) {
+        if !self.minify_whitespace {
+            self.css.push('\n');
+        }
+    }
+
+    fn print_space(&mut self) {
+        if !self.minify_whitespace {
+            self.css.push(' ');
+        }
+    }
+
+    // The semicolon after the last declaration in a block is left out when
+    // minifying
+    fn print_rule(&mut self, rule: &Rule, is_last: bool) {
+        match rule {
+            // Empty rules don't do anything
+            Rule::Qualified { block, .. } if block.is_empty() && self.minify_whitespace => {}
+
+            Rule::Import {
+                path, conditions, ..
+            } => {
+                self.print_indent();
+                self.css.push_str("@import");
+                self.print_space();
+                self.css.push('"');
+                for c in path.chars() {
+                    if c == '"' || c == '\\' {
+                        self.css.push('\\');
+                    }
+                    self.css.push(c);
+                }
+                self.css.push('"');
+                if !conditions.is_empty() {
+                    self.css.push(' ');
+                    self.print_tokens(conditions, false);
+                }
+                self.css.push(';');
+                self.print_newline();
+            }
+
+            Rule::At {
+                name,
+                prelude,
+                block,
+            } => {
+                self.print_indent();
+                self.css.push('@');
+                self.css.push_str(name);
+                if !prelude.is_empty() {
+                    self.css.push(' ');
+                    self.print_tokens(prelude, false);
+                }
+                match block {
+                    Some(block) => {
+                        self.print_space();
+                        self.print_block(block);
+                    }
+                    None => {
+                        self.css.push(';');
+                        self.print_newline();
+                    }
+                }
+            }
+
+            Rule::Qualified { prelude, block } => {
+                self.print_indent();
+                self.print_tokens(prelude, true);
+                self.print_space();
+                self.print_block(block);
+            }
+
+            Rule::Declaration {
+                name,
+                value,
+                important,
+            } => {
+                self.print_indent();
+                self.css.push_str(name);
+                self.css.push(':');
+                self.print_space();
+                self.print_tokens(value, false);
+                if *important {
+                    self.print_space();
+                    self.css.push_str("!important");
+                }
+                if !is_last || !self.minify_whitespace {
+                    self.css.push(';');
+                }
+                self.print_newline();
+            }
+        }
+    }
+
+    fn print_block(&mut self, rules: &[Rule]) {
+        self.css.push('{');
+        self.print_newline();
+        self.indent += 1;
+        for (i, rule) in rules.iter().enumerate() {
+            self.print_rule(rule, i + 1 == rules.len());
+        }
+        self.indent -= 1;
+        self.print_indent();
+        self.css.push('}');
+        self.print_newline();
+    }
+
+    // Whitespace is kept as written except that minifying removes it where it
+    // can't matter, such as after a "," or around a ">" in a selector. It's
+    // significant in most other places: "a b" and "a:hover" are different
+    // selectors and "1px -2px" isn't "1px-2px".
+    fn print_tokens(&mut self, tokens: &[Token], is_selector: bool) {
+        let is_combinator = |token: &Token| {
+            is_selector
+                && token.kind == TokenKind::Delim
+                && matches!(token.text.as_str(), ">" | "+" | "~")
+        };
+        let is_removable_after = |token: &Token| {
+            is_combinator(token)
+                || matches!(
+                    token.kind,
+                    TokenKind::Comma
+                        | TokenKind::Colon
+                        | TokenKind::OpenParen
+                        | TokenKind::Function
+                )
+        };
+
+        // "a :hover" and "a:hover" are different selectors
+        let is_removable_before = |token: &Token| {
+            is_combinator(token)
+                || matches!(token.kind, TokenKind::Comma | TokenKind::CloseParen)
+                || (token.kind == TokenKind::Colon && !is_selector)
+        };
+
+        for (i, token) in tokens.iter().enumerate() {
+            if token.kind == TokenKind::Whitespace && self.minify_whitespace {
+                let prev = &tokens[i.saturating_sub(1)];
+                let next = tokens.get(i + 1);
+                if i == 0 || is_removable_after(prev) || next.is_none_or(is_removable_before) {
+                    continue;
+                }
+            }
+            self.css.push_str(&token.text);
+        }
+    }
+}
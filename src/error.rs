@@ -1,14 +1,99 @@
+// Most problems are logged (see "Log") so that a build can report all of them
+// at once. This is for the functions that return a single error instead, for
+// callers that only want to know whether something worked and why not. Errors
+// in the code of a file are the message that would have been logged for them.
+
+use crate::logging::Msg;
 use std::fmt;
+use std::io;
 
 #[derive(Debug)]
 pub enum Error {
+    // A keyword was looked up by a name that isn't one
     NotFound,
+
+    // The text can't be split into tokens, such as an unterminated string
+    Lex(Box<Msg>),
+
+    // The tokens don't make up a valid file
+    Parse(Box<Msg>),
+
+    // An import path that doesn't lead to a file. The importer is the path of
+    // the file it's imported from.
+    Resolve {
+        import_path: String,
+        importer: String,
+    },
+
+    // Reading or writing a file failed. The path is empty if it isn't known.
+    Io {
+        path: String,
+        error: io::Error,
+    },
+
+    // Options that are invalid or that can't be used together
+    Config(String),
+}
+
+impl Error {
+    pub fn io<S: Into<String>>(path: S, error: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            error,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
 }
 
-impl std::error::Error for Error {}
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::io(String::new(), error)
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Error::NotFound => write!(f, "Not found"),
+
+            // Lines start at 1 and columns start at 0, like in the terminal
+            // output of the log
+            Error::Lex(msg) | Error::Parse(msg) => {
+                if msg.source.pretty_path.is_empty() {
+                    return write!(f, "{}", msg.text);
+                }
+                let (line, column, _) = msg.source.line_offset_table().line_and_column(msg.start);
+                write!(
+                    f,
+                    "{}:{}:{}: {}",
+                    msg.source.pretty_path,
+                    line + 1,
+                    column,
+                    msg.text
+                )
+            }
+
+            Error::Resolve {
+                import_path,
+                importer,
+            } => {
+                write!(f, "Could not resolve {:?}", import_path)?;
+                if !importer.is_empty() {
+                    write!(f, " from {:?}", importer)?;
+                }
+                Ok(())
+            }
+            Error::Io { path, error } if path.is_empty() => write!(f, "{}", error),
+            Error::Io { path, error } => write!(f, "{}: {}", path, error),
+            Error::Config(text) => write!(f, "{}", text),
+        }
     }
 }
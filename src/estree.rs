@@ -0,0 +1,1204 @@
+// This writes a parsed tree as JSON in the shape of ESTree, the tree format
+// used by most JavaScript tools (https://github.com/estree/estree). That makes
+// it possible to compare the output of this parser with other parsers and to
+// build tools on top of it in other languages.
+//
+// The tree here is the one the parser hands to the bundler, so it isn't an
+// exact match for other parsers:
+//
+//   - Only the start of each node is known, so nodes have a "start" offset in
+//     bytes but no "end". Nodes that don't have a location in the tree, such
+//     as the elements of a template literal, have neither.
+//   - Types have been removed from TypeScript files, and the syntax that's
+//     left, such as enums, uses the node types of typescript-eslint.
+//   - The left side of an assignment is an expression, so "[a] = b" has an
+//     "ArrayExpression" where ESTree has an "ArrayPattern".
+//   - Identifiers are written with the name of their symbol. Comments and
+//     parentheses aren't part of the tree at all.
+
+use crate::ast::{
+    Arg, Binding, BindingKind, Catch, Class, ClauseItem, Expr, ExprKind, ExprOrStmt, Function,
+    LocalKind, Location, NamespaceSymbol, OperatorCode, OptionalChain, Property, PropertyKind,
+    Reference, Stmt, StmtKind, SymbolMap, TemplatePart, AST,
+};
+use crate::lexer::is_identifier_utf16;
+use crate::logging::quote_json;
+use crate::number::number_to_string;
+use crate::stack::ensure_sufficient_stack;
+use crate::strings::{utf16_equals_string, utf16_to_string_lossy};
+
+pub fn ast_to_json(ast: &AST) -> String {
+    let mut writer = JsonWriter {
+        symbols: &ast.symbols,
+        json: String::new(),
+    };
+    writer.begin("Program", Some(0));
+    let source_type = if ast.has_es6_syntax() {
+        "module"
+    } else {
+        "script"
+    };
+    writer.field_str("sourceType", source_type);
+    if !ast.hash_bang.is_empty() {
+        writer.field_str("hashbang", &ast.hash_bang);
+    }
+    let stmts: Vec<&Stmt> = ast.parts.iter().flat_map(|part| &part.stmts).collect();
+    writer.stmts("body", stmts);
+    writer.end();
+    writer.json
+}
+
+struct JsonWriter<'a> {
+    symbols: &'a SymbolMap,
+    json: String,
+}
+
+impl<'a> JsonWriter<'a> {
+    fn begin(&mut self, kind: &str, start: Option<Location>) {
+        self.json.push_str("{\"type\":");
+        self.json.push_str(&quote_json(kind));
+        if let Some(start) = start {
+            self.json.push_str(&format!(",\"start\":{}", start));
+        }
+    }
+
+    fn end(&mut self) {
+        self.json.push('}');
+    }
+
+    fn key(&mut self, key: &str) {
+        self.json.push(',');
+        self.json.push_str(&quote_json(key));
+        self.json.push(':');
+    }
+
+    fn field_str(&mut self, key: &str, value: &str) {
+        self.key(key);
+        self.json.push_str(&quote_json(value));
+    }
+
+    fn field_bool(&mut self, key: &str, value: bool) {
+        self.key(key);
+        self.json.push_str(if value { "true" } else { "false" });
+    }
+
+    fn field_null(&mut self, key: &str) {
+        self.key(key);
+        self.json.push_str("null");
+    }
+
+    fn list<T, I, F>(&mut self, key: &str, items: I, mut write: F)
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(&mut Self, T),
+    {
+        self.key(key);
+        self.json.push('[');
+        for (i, item) in items.into_iter().enumerate() {
+            if i > 0 {
+                self.json.push(',');
+            }
+            write(self, item);
+        }
+        self.json.push(']');
+    }
+
+    // Comments and the statements left behind by TypeScript declarations
+    // aren't nodes in ESTree
+    fn stmts<'b, I: IntoIterator<Item = &'b Stmt>>(&mut self, key: &str, stmts: I) {
+        let stmts = stmts.into_iter().filter(|stmt| {
+            !matches!(
+                stmt.data.as_ref(),
+                StmtKind::TypeScript | StmtKind::Comment { .. }
+            )
+        });
+        self.list(key, stmts, |writer, stmt| writer.stmt(stmt));
+    }
+
+    fn block(&mut self, key: &str, start: Option<Location>, stmts: &[Stmt]) {
+        self.key(key);
+        self.begin("BlockStatement", start);
+        self.stmts("body", stmts);
+        self.end();
+    }
+
+    fn identifier(&mut self, reference: Reference, start: Location) {
+        let symbols = self.symbols;
+        self.name(&symbols[reference].name, Some(start));
+    }
+
+    fn name(&mut self, name: &str, start: Option<Location>) {
+        self.begin("Identifier", start);
+        self.field_str("name", name);
+        self.end();
+    }
+
+    fn string_literal(&mut self, value: &str, start: Option<Location>) {
+        self.begin("Literal", start);
+        self.field_str("value", value);
+        self.end();
+    }
+
+    // Keys that are valid identifiers are written as identifiers, like they
+    // would have been in the source
+    fn property_key(&mut self, key: &Expr, is_computed: bool) {
+        match key.data.as_ref() {
+            ExprKind::String { value } if !is_computed && is_identifier_utf16(value) => {
+                self.name(&utf16_to_string_lossy(value), Some(key.location))
+            }
+            _ => self.expr(key),
+        }
+    }
+
+    // "{a}" is stored as "{a: a}"
+    fn is_shorthand(&self, key: &Expr, is_computed: bool, reference: Option<Reference>) -> bool {
+        match (key.data.as_ref(), reference) {
+            (ExprKind::String { value }, Some(reference)) => {
+                !is_computed && utf16_equals_string(value, &self.symbols[reference].name)
+            }
+            _ => false,
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        ensure_sufficient_stack(|| self.stmt_inner(stmt))
+    }
+
+    fn stmt_inner(&mut self, stmt: &Stmt) {
+        let start = Some(stmt.location);
+        match stmt.data.as_ref() {
+            StmtKind::Block { stmts } => {
+                self.begin("BlockStatement", start);
+                self.stmts("body", stmts);
+                self.end();
+            }
+            StmtKind::Empty
+            | StmtKind::TypeScript
+            | StmtKind::SyntaxError
+            | StmtKind::Comment { .. } => {
+                self.begin("EmptyStatement", start);
+                self.end();
+            }
+            StmtKind::Debugger => {
+                self.begin("DebuggerStatement", start);
+                self.end();
+            }
+            StmtKind::Directive { value } => {
+                let value = utf16_to_string_lossy(value);
+                self.begin("ExpressionStatement", start);
+                self.key("expression");
+                self.string_literal(&value, start);
+                self.field_str("directive", &value);
+                self.end();
+            }
+            StmtKind::ExportClause { items } => {
+                self.begin("ExportNamedDeclaration", start);
+                self.field_null("declaration");
+                self.export_specifiers(items);
+                self.field_null("source");
+                self.end();
+            }
+            StmtKind::ExportFrom { items, path, .. } => {
+                self.begin("ExportNamedDeclaration", start);
+                self.field_null("declaration");
+                self.export_specifiers(items);
+                self.key("source");
+                self.string_literal(&path.text, Some(path.loc));
+                self.end();
+            }
+            StmtKind::ExportDefault { value, .. } => {
+                self.begin("ExportDefaultDeclaration", start);
+                self.key("declaration");
+                match value {
+                    ExprOrStmt::Expr(value) => self.expr(value),
+                    ExprOrStmt::Stmt(value) => self.stmt(value),
+                }
+                self.end();
+            }
+            StmtKind::ExportStar { item, path } => {
+                self.begin("ExportAllDeclaration", start);
+                self.key("exported");
+                match item {
+                    Some(item) => self.name(&item.alias, Some(item.alias_location)),
+                    None => self.json.push_str("null"),
+                }
+                self.key("source");
+                self.string_literal(&path.text, Some(path.loc));
+                self.end();
+            }
+            StmtKind::ExportEquals { value } => {
+                self.begin("TSExportAssignment", start);
+                self.key("expression");
+                self.expr(value);
+                self.end();
+            }
+            StmtKind::Expr { value } => {
+                self.begin("ExpressionStatement", start);
+                self.key("expression");
+                self.expr(value);
+                self.end();
+            }
+            StmtKind::Enum {
+                name,
+                values,
+                is_export,
+                ..
+            } => self.exported(start, *is_export, |writer| {
+                writer.begin("TSEnumDeclaration", start);
+                writer.key("id");
+                writer.identifier(name.reference, name.loc);
+                writer.list("members", values, |writer, value| {
+                    writer.begin("TSEnumMember", Some(value.location));
+                    writer.key("id");
+                    let name = utf16_to_string_lossy(&value.name);
+                    if is_identifier_utf16(&value.name) {
+                        writer.name(&name, Some(value.location));
+                    } else {
+                        writer.string_literal(&name, Some(value.location));
+                    }
+                    writer.key("initializer");
+                    writer.opt_expr(value.value.as_ref());
+                    writer.end();
+                });
+                writer.end();
+            }),
+            StmtKind::Namespace {
+                name,
+                stmts,
+                is_export,
+                ..
+            } => self.exported(start, *is_export, |writer| {
+                writer.begin("TSModuleDeclaration", start);
+                writer.key("id");
+                writer.identifier(name.reference, name.loc);
+                writer.key("body");
+                writer.begin("TSModuleBlock", None);
+                writer.stmts("body", stmts);
+                writer.end();
+                writer.end();
+            }),
+            StmtKind::Function {
+                function,
+                is_export,
+            } => self.exported(start, *is_export, |writer| {
+                writer.function("FunctionDeclaration", start, function)
+            }),
+            StmtKind::Class { class, is_export } => self.exported(start, *is_export, |writer| {
+                writer.class("ClassDeclaration", start, class)
+            }),
+            StmtKind::Label { name, stmt } => {
+                self.begin("LabeledStatement", start);
+                self.key("label");
+                self.identifier(name.reference, name.loc);
+                self.key("body");
+                self.stmt(stmt);
+                self.end();
+            }
+            StmtKind::If { test, yes, no } => {
+                self.begin("IfStatement", start);
+                self.key("test");
+                self.expr(test);
+                self.key("consequent");
+                self.stmt(yes);
+                self.key("alternate");
+                match no {
+                    Some(no) => self.stmt(no),
+                    None => self.json.push_str("null"),
+                }
+                self.end();
+            }
+            StmtKind::For {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                self.begin("ForStatement", start);
+                self.key("init");
+                match init {
+                    Some(init) => self.for_init(init),
+                    None => self.json.push_str("null"),
+                }
+                self.key("test");
+                self.opt_expr(test.as_ref());
+                self.key("update");
+                self.opt_expr(update.as_ref());
+                self.key("body");
+                self.stmt(body);
+                self.end();
+            }
+            StmtKind::ForIn { init, value, body } => {
+                self.begin("ForInStatement", start);
+                self.key("left");
+                self.for_init(init);
+                self.key("right");
+                self.expr(value);
+                self.key("body");
+                self.stmt(body);
+                self.end();
+            }
+            StmtKind::ForOf {
+                is_await,
+                init,
+                value,
+                body,
+            } => {
+                self.begin("ForOfStatement", start);
+                self.field_bool("await", *is_await);
+                self.key("left");
+                self.for_init(init);
+                self.key("right");
+                self.expr(value);
+                self.key("body");
+                self.stmt(body);
+                self.end();
+            }
+            StmtKind::DoWhile { body, test } => {
+                self.begin("DoWhileStatement", start);
+                self.key("body");
+                self.stmt(body);
+                self.key("test");
+                self.expr(test);
+                self.end();
+            }
+            StmtKind::While { test, body } => {
+                self.begin("WhileStatement", start);
+                self.key("test");
+                self.expr(test);
+                self.key("body");
+                self.stmt(body);
+                self.end();
+            }
+            StmtKind::With { value, body, .. } => {
+                self.begin("WithStatement", start);
+                self.key("object");
+                self.expr(value);
+                self.key("body");
+                self.stmt(body);
+                self.end();
+            }
+            StmtKind::Catch(catch) => self.catch(catch),
+            StmtKind::Finally(finally) => {
+                self.begin("BlockStatement", Some(finally.location));
+                self.stmts("body", &finally.stmts);
+                self.end();
+            }
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.begin("TryStatement", start);
+                self.block("block", None, body);
+                self.key("handler");
+                match catch {
+                    Some(catch) => self.catch(catch),
+                    None => self.json.push_str("null"),
+                }
+                match finally {
+                    Some(finally) => {
+                        self.block("finalizer", Some(finally.location), &finally.stmts)
+                    }
+                    None => self.field_null("finalizer"),
+                }
+                self.end();
+            }
+            StmtKind::Switch { test, cases, .. } => {
+                self.begin("SwitchStatement", start);
+                self.key("discriminant");
+                self.expr(test);
+                self.list("cases", cases, |writer, case| {
+                    writer.begin("SwitchCase", None);
+                    writer.key("test");
+                    writer.opt_expr(case.value.as_ref());
+                    writer.stmts("consequent", &case.body);
+                    writer.end();
+                });
+                self.end();
+            }
+            StmtKind::Import {
+                namespace_symbol,
+                default_name,
+                path,
+            } => {
+                self.begin("ImportDeclaration", start);
+                self.key("specifiers");
+                self.json.push('[');
+                if let Some(default_name) = default_name {
+                    self.begin("ImportDefaultSpecifier", Some(default_name.loc));
+                    self.key("local");
+                    self.identifier(default_name.reference, default_name.loc);
+                    self.end();
+                }
+                match namespace_symbol {
+                    NamespaceSymbol::Star {
+                        location,
+                        namespace_ref,
+                    } => {
+                        if default_name.is_some() {
+                            self.json.push(',');
+                        }
+                        self.begin("ImportNamespaceSpecifier", Some(*location));
+                        self.key("local");
+                        self.identifier(*namespace_ref, *location);
+                        self.end();
+                    }
+                    NamespaceSymbol::Clause { items } => {
+                        for (i, item) in items.iter().enumerate() {
+                            if i > 0 || default_name.is_some() {
+                                self.json.push(',');
+                            }
+                            self.begin("ImportSpecifier", Some(item.alias_location));
+                            self.key("imported");
+                            self.name(&item.alias, Some(item.alias_location));
+                            self.key("local");
+                            self.identifier(item.name.reference, item.name.loc);
+                            self.end();
+                        }
+                    }
+                }
+                self.json.push(']');
+                self.key("source");
+                self.string_literal(&path.text, Some(path.loc));
+                self.end();
+            }
+            StmtKind::Return { value } => {
+                self.begin("ReturnStatement", start);
+                self.key("argument");
+                self.opt_expr(value.as_ref());
+                self.end();
+            }
+            StmtKind::Throw { value } => {
+                self.begin("ThrowStatement", start);
+                self.key("argument");
+                self.expr(value);
+                self.end();
+            }
+            StmtKind::Local {
+                decls,
+                kind,
+                is_export,
+                ..
+            } => self.exported(start, *is_export, |writer| {
+                writer.begin("VariableDeclaration", start);
+                let kind = match kind {
+                    LocalKind::Var => "var",
+                    LocalKind::Let => "let",
+                    LocalKind::Const => "const",
+                };
+                writer.field_str("kind", kind);
+                writer.list("declarations", decls, |writer, decl| {
+                    writer.begin("VariableDeclarator", Some(decl.binding.location));
+                    writer.key("id");
+                    writer.binding(&decl.binding);
+                    writer.key("init");
+                    writer.opt_expr(decl.value.as_ref());
+                    writer.end();
+                });
+                writer.end();
+            }),
+            StmtKind::Break { name } | StmtKind::Continue { name } => {
+                let kind = match stmt.data.as_ref() {
+                    StmtKind::Break { .. } => "BreakStatement",
+                    _ => "ContinueStatement",
+                };
+                self.begin(kind, start);
+                self.key("label");
+                match name {
+                    Some(name) => self.identifier(name.reference, name.loc),
+                    None => self.json.push_str("null"),
+                }
+                self.end();
+            }
+        }
+    }
+
+    fn export_specifiers(&mut self, items: &[ClauseItem]) {
+        self.list("specifiers", items, |writer, item| {
+            writer.begin("ExportSpecifier", Some(item.name.loc));
+            writer.key("local");
+            writer.identifier(item.name.reference, item.name.loc);
+            writer.key("exported");
+            writer.name(&item.alias, Some(item.alias_location));
+            writer.end();
+        });
+    }
+
+    // "export" is a flag on declarations here and a separate node in ESTree
+    fn exported<F: FnOnce(&mut Self)>(
+        &mut self,
+        start: Option<Location>,
+        is_export: bool,
+        write: F,
+    ) {
+        if !is_export {
+            write(self);
+            return;
+        }
+        self.begin("ExportNamedDeclaration", start);
+        self.key("declaration");
+        write(self);
+        self.list("specifiers", std::iter::empty::<()>(), |_, _| {});
+        self.field_null("source");
+        self.end();
+    }
+
+    // The initializer of a "for" loop is either a declaration or an
+    // expression statement, and ESTree doesn't wrap the expression
+    fn for_init(&mut self, init: &Stmt) {
+        match init.data.as_ref() {
+            StmtKind::Expr { value } => self.expr(value),
+            _ => self.stmt(init),
+        }
+    }
+
+    fn catch(&mut self, catch: &Catch) {
+        self.begin("CatchClause", Some(catch.location));
+        self.key("param");
+        match &catch.binding {
+            Some(binding) => self.binding(binding),
+            None => self.json.push_str("null"),
+        }
+        self.block("body", None, &catch.body);
+        self.end();
+    }
+
+    fn function(&mut self, kind: &str, start: Option<Location>, function: &Function) {
+        self.begin(kind, start);
+        self.key("id");
+        match &function.name {
+            Some(name) => self.identifier(name.reference, name.loc),
+            None => self.json.push_str("null"),
+        }
+        self.field_bool("async", function.is_async);
+        self.field_bool("generator", function.is_generator);
+        self.params(&function.args, function.has_rest_arg);
+        self.block("body", Some(function.body.location), &function.body.stmts);
+        self.end();
+    }
+
+    fn params(&mut self, args: &[Arg], has_rest_arg: bool) {
+        let rest = if has_rest_arg {
+            args.len() - 1
+        } else {
+            args.len()
+        };
+        self.list("params", args.iter().enumerate(), |writer, (i, arg)| {
+            if i == rest {
+                writer.begin("RestElement", Some(arg.binding.location));
+                writer.key("argument");
+                writer.binding(&arg.binding);
+                writer.end();
+            } else {
+                writer.pattern_with_default(&arg.binding, arg.default_.as_ref());
+            }
+        });
+    }
+
+    fn class(&mut self, kind: &str, start: Option<Location>, class: &Class) {
+        self.begin(kind, start);
+        self.key("id");
+        match &class.name {
+            Some(name) => self.identifier(name.reference, name.loc),
+            None => self.json.push_str("null"),
+        }
+        self.key("superClass");
+        self.opt_expr(class.extends.as_ref());
+        self.key("body");
+        self.begin("ClassBody", None);
+        self.list("body", &class.properties, |writer, property| {
+            writer.class_member(property)
+        });
+        self.end();
+        self.end();
+    }
+
+    fn class_member(&mut self, property: &Property) {
+        let start = Some(property.key.location);
+        let function = match property.value.as_ref().map(|value| value.data.as_ref()) {
+            Some(ExprKind::Function { function }) => Some(function),
+            _ => None,
+        };
+
+        // A static block is stored as a function without arguments
+        if property.kind == PropertyKind::PropertyStaticBlock {
+            self.begin("StaticBlock", start);
+            let stmts = function.map_or(&[][..], |function| &function.body.stmts);
+            self.stmts("body", stmts);
+            self.end();
+            return;
+        }
+
+        match (function, &property.value) {
+            (Some(function), Some(value)) if property.is_method => {
+                let is_constructor = !property.is_computed
+                    && !property.is_static
+                    && matches!(
+                        property.key.data.as_ref(),
+                        ExprKind::String { value } if utf16_equals_string(value, "constructor")
+                    );
+                let kind = match property.kind {
+                    PropertyKind::PropertyGet => "get",
+                    PropertyKind::PropertySet => "set",
+                    _ if is_constructor => "constructor",
+                    _ => "method",
+                };
+                self.begin("MethodDefinition", start);
+                self.key("key");
+                self.property_key(&property.key, property.is_computed);
+                self.field_bool("computed", property.is_computed);
+                self.field_bool("static", property.is_static);
+                self.field_str("kind", kind);
+                self.key("value");
+                self.function("FunctionExpression", Some(value.location), function);
+                self.end();
+            }
+            _ => {
+                self.begin("PropertyDefinition", start);
+                self.key("key");
+                self.property_key(&property.key, property.is_computed);
+                self.field_bool("computed", property.is_computed);
+                self.field_bool("static", property.is_static);
+                self.key("value");
+                self.opt_expr(property.initializer.as_ref());
+                self.end();
+            }
+        }
+    }
+
+    fn object_property(&mut self, property: &Property) {
+        let start = Some(property.key.location);
+        if property.kind == PropertyKind::PropertySpread {
+            self.begin("SpreadElement", start);
+            self.key("argument");
+            self.opt_expr(property.value.as_ref());
+            self.end();
+            return;
+        }
+
+        let kind = match property.kind {
+            PropertyKind::PropertyGet => "get",
+            PropertyKind::PropertySet => "set",
+            _ => "init",
+        };
+        let reference = match property.value.as_ref().map(|value| value.data.as_ref()) {
+            Some(ExprKind::Identifier { reference })
+            | Some(ExprKind::ImportIdentifier { reference }) => Some(*reference),
+            _ => None,
+        };
+        self.begin("Property", start);
+        self.key("key");
+        self.property_key(&property.key, property.is_computed);
+        self.field_bool("computed", property.is_computed);
+        self.field_bool("method", property.is_method && kind == "init");
+        self.field_bool(
+            "shorthand",
+            self.is_shorthand(&property.key, property.is_computed, reference),
+        );
+        self.field_str("kind", kind);
+        self.key("value");
+
+        // "{a = 1} = b" stores the default value in the initializer
+        match (&property.value, &property.initializer) {
+            (Some(value), Some(initializer)) => {
+                self.begin("AssignmentPattern", Some(value.location));
+                self.key("left");
+                self.expr(value);
+                self.key("right");
+                self.expr(initializer);
+                self.end();
+            }
+            (value, _) => self.opt_expr(value.as_ref()),
+        }
+        self.end();
+    }
+
+    fn binding(&mut self, binding: &Binding) {
+        ensure_sufficient_stack(|| self.binding_inner(binding))
+    }
+
+    fn binding_inner(&mut self, binding: &Binding) {
+        let start = Some(binding.location);
+        match binding.data.as_ref() {
+            BindingKind::Missing => self.json.push_str("null"),
+            BindingKind::Identifier { reference } => self.identifier(*reference, binding.location),
+            BindingKind::Array { items, has_spread } => {
+                let rest = if *has_spread {
+                    items.len() - 1
+                } else {
+                    items.len()
+                };
+                self.begin("ArrayPattern", start);
+                self.list("elements", items.iter().enumerate(), |writer, (i, item)| {
+                    if i == rest {
+                        writer.begin("RestElement", Some(item.binding.location));
+                        writer.key("argument");
+                        writer.binding(&item.binding);
+                        writer.end();
+                    } else {
+                        writer.pattern_with_default(&item.binding, item.default_value.as_ref());
+                    }
+                });
+                self.end();
+            }
+            BindingKind::Object { properties } => {
+                self.begin("ObjectPattern", start);
+                self.list("properties", properties, |writer, property| {
+                    let start = Some(property.key.location);
+                    if property.is_spread {
+                        writer.begin("RestElement", start);
+                        writer.key("argument");
+                        writer.binding(&property.value);
+                        writer.end();
+                        return;
+                    }
+                    let reference = match property.value.data.as_ref() {
+                        BindingKind::Identifier { reference } => Some(*reference),
+                        _ => None,
+                    };
+                    writer.begin("Property", start);
+                    writer.key("key");
+                    writer.property_key(&property.key, property.is_computed);
+                    writer.field_bool("computed", property.is_computed);
+                    writer.field_bool("method", false);
+                    writer.field_bool(
+                        "shorthand",
+                        writer.is_shorthand(&property.key, property.is_computed, reference),
+                    );
+                    writer.field_str("kind", "init");
+                    writer.key("value");
+                    writer.pattern_with_default(&property.value, property.default_value.as_ref());
+                    writer.end();
+                });
+                self.end();
+            }
+        }
+    }
+
+    fn pattern_with_default(&mut self, binding: &Binding, default_value: Option<&Expr>) {
+        match default_value {
+            Some(default_value) => {
+                self.begin("AssignmentPattern", Some(binding.location));
+                self.key("left");
+                self.binding(binding);
+                self.key("right");
+                self.expr(default_value);
+                self.end();
+            }
+            None => self.binding(binding),
+        }
+    }
+
+    fn opt_expr(&mut self, expr: Option<&Expr>) {
+        match expr {
+            Some(expr) => self.expr(expr),
+            None => self.json.push_str("null"),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        self.chain_expr(expr, false)
+    }
+
+    // ESTree wraps a whole optional chain such as "a?.b.c()?.d" in a
+    // "ChainExpression". Parentheses end a chain, so "(a?.b).c" is a chain
+    // inside a member expression.
+    fn chain_expr(&mut self, expr: &Expr, is_in_chain: bool) {
+        ensure_sufficient_stack(|| self.chain_expr_inner(expr, is_in_chain))
+    }
+
+    fn chain_expr_inner(&mut self, expr: &Expr, is_in_chain: bool) {
+        let start = Some(expr.location);
+        let optional_chain = match expr.data.as_ref() {
+            ExprKind::Call { optional_chain, .. }
+            | ExprKind::Dot { optional_chain, .. }
+            | ExprKind::Index { optional_chain, .. } => *optional_chain,
+            _ => OptionalChain::None,
+        };
+        if optional_chain != OptionalChain::None && !is_in_chain {
+            self.begin("ChainExpression", start);
+            self.key("expression");
+            self.chain_expr(expr, true);
+            self.end();
+            return;
+        }
+        let is_target_in_chain = optional_chain != OptionalChain::None
+            && !matches!(
+                expr.data.as_ref(),
+                ExprKind::Call { target, .. } | ExprKind::Dot { target, .. } | ExprKind::Index { target, .. }
+                    if is_parenthesized(target)
+            );
+        let is_optional = optional_chain == OptionalChain::Start;
+
+        match expr.data.as_ref() {
+            ExprKind::Missing => self.json.push_str("null"),
+            ExprKind::Array { items } => {
+                self.begin("ArrayExpression", start);
+                self.list("elements", items, |writer, item| writer.expr(item));
+                self.end();
+            }
+            ExprKind::Unary { op_code, value } => {
+                let kind = if op_code.is_unary_update() {
+                    "UpdateExpression"
+                } else {
+                    "UnaryExpression"
+                };
+                self.begin(kind, start);
+                self.field_str("operator", op_code.entry().text);
+                self.field_bool("prefix", op_code.is_prefix());
+                self.key("argument");
+                self.expr(value);
+                self.end();
+            }
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpComma,
+                ..
+            } => {
+                let mut exprs = vec![];
+                let mut left = expr;
+                while let ExprKind::Binary {
+                    op_code: OperatorCode::BinOpComma,
+                    left: next,
+                    right,
+                } = left.data.as_ref()
+                {
+                    exprs.push(right);
+                    left = next;
+                }
+                exprs.push(left);
+                exprs.reverse();
+                self.begin("SequenceExpression", start);
+                self.list("expressions", exprs, |writer, expr| writer.expr(expr));
+                self.end();
+            }
+            ExprKind::Binary {
+                op_code,
+                left,
+                right,
+            } => {
+                let kind = match op_code {
+                    _ if op_code.is_binary_assign() => "AssignmentExpression",
+                    OperatorCode::BinOpLogicalOr
+                    | OperatorCode::BinOpLogicalAnd
+                    | OperatorCode::BinOpNullishCoalescing => "LogicalExpression",
+                    _ => "BinaryExpression",
+                };
+                self.begin(kind, start);
+                self.field_str("operator", op_code.entry().text);
+                self.key("left");
+                self.expr(left);
+                self.key("right");
+                self.expr(right);
+                self.end();
+            }
+            ExprKind::Boolean { value } => {
+                self.begin("Literal", start);
+                self.field_bool("value", *value);
+                self.end();
+            }
+            ExprKind::Null => {
+                self.begin("Literal", start);
+                self.field_null("value");
+                self.end();
+            }
+            // The parser turns the globals "Infinity" and "NaN" into
+            // numbers, which JSON can't hold
+            ExprKind::Number { value } if value.is_nan() => self.name("NaN", start),
+            ExprKind::Number { value } if *value == f64::INFINITY => self.name("Infinity", start),
+            ExprKind::Number { value } if *value == f64::NEG_INFINITY => {
+                self.begin("UnaryExpression", start);
+                self.field_str("operator", "-");
+                self.field_bool("prefix", true);
+                self.key("argument");
+                self.name("Infinity", None);
+                self.end();
+            }
+            ExprKind::Number { value } => {
+                self.begin("Literal", start);
+                self.key("value");
+                self.json.push_str(&number_to_string(*value));
+                self.end();
+            }
+            ExprKind::BigInt { value } => {
+                self.begin("Literal", start);
+                self.field_null("value");
+                self.field_str("bigint", value);
+                self.end();
+            }
+            ExprKind::String { value } => {
+                self.string_literal(&utf16_to_string_lossy(value), start);
+            }
+            ExprKind::RegExp { value } => {
+                let last_slash = value.rfind('/').unwrap_or(0);
+                self.begin("Literal", start);
+                self.field_null("value");
+                self.field_str("raw", value);
+                self.key("regex");
+                self.json.push_str("{\"pattern\":");
+                self.json
+                    .push_str(&quote_json(value.get(1..last_slash).unwrap_or_default()));
+                self.json.push_str(",\"flags\":");
+                self.json.push_str(&quote_json(&value[last_slash + 1..]));
+                self.json.push('}');
+                self.end();
+            }
+
+            // The parser uses this for "void 0"
+            ExprKind::Undefined => {
+                self.begin("UnaryExpression", start);
+                self.field_str("operator", "void");
+                self.field_bool("prefix", true);
+                self.key("argument");
+                self.begin("Literal", None);
+                self.key("value");
+                self.json.push('0');
+                self.end();
+                self.end();
+            }
+            ExprKind::Super => {
+                self.begin("Super", start);
+                self.end();
+            }
+            ExprKind::This => {
+                self.begin("ThisExpression", start);
+                self.end();
+            }
+            ExprKind::NewTarget | ExprKind::ImportMeta => {
+                let (meta, property) = match expr.data.as_ref() {
+                    ExprKind::NewTarget => ("new", "target"),
+                    _ => ("import", "meta"),
+                };
+                self.begin("MetaProperty", start);
+                self.key("meta");
+                self.name(meta, start);
+                self.key("property");
+                self.name(property, None);
+                self.end();
+            }
+            ExprKind::New { target, args, .. } => {
+                self.begin("NewExpression", start);
+                self.key("callee");
+                self.expr(target);
+                self.list("arguments", args, |writer, arg| writer.expr(arg));
+                self.end();
+            }
+            ExprKind::Call { target, args, .. } => {
+                self.begin("CallExpression", start);
+                self.key("callee");
+                self.chain_expr(target, is_target_in_chain);
+                self.list("arguments", args, |writer, arg| writer.expr(arg));
+                self.field_bool("optional", is_optional);
+                self.end();
+            }
+
+            // The parser never creates these
+            ExprKind::RuntimeCall { args, .. } => {
+                self.begin("CallExpression", start);
+                self.field_null("callee");
+                self.list("arguments", args, |writer, arg| writer.expr(arg));
+                self.field_bool("optional", false);
+                self.end();
+            }
+            ExprKind::Dot {
+                target,
+                name,
+                name_location,
+                ..
+            } => {
+                self.begin("MemberExpression", start);
+                self.key("object");
+                self.chain_expr(target, is_target_in_chain);
+                self.key("property");
+                self.name(name, Some(*name_location));
+                self.field_bool("computed", false);
+                self.field_bool("optional", is_optional);
+                self.end();
+            }
+            ExprKind::Index { target, index, .. } => {
+                let is_private = matches!(index.data.as_ref(), ExprKind::PrivateIdentifier { .. });
+                self.begin("MemberExpression", start);
+                self.key("object");
+                self.chain_expr(target, is_target_in_chain);
+                self.key("property");
+                self.expr(index);
+                self.field_bool("computed", !is_private);
+                self.field_bool("optional", is_optional);
+                self.end();
+            }
+            ExprKind::Arrow {
+                is_async,
+                args,
+                has_rest_arg,
+                prefer_expr,
+                body,
+                ..
+            } => {
+                self.begin("ArrowFunctionExpression", start);
+                self.field_null("id");
+                self.field_bool("async", *is_async);
+                self.field_bool("generator", false);
+                self.params(args, *has_rest_arg);
+
+                // "() => a" is stored as "() => { return a }"
+                match body.stmts.as_slice() {
+                    [Stmt { data, .. }] if *prefer_expr => match data.as_ref() {
+                        StmtKind::Return { value: Some(value) } => {
+                            self.field_bool("expression", true);
+                            self.key("body");
+                            self.expr(value);
+                        }
+                        _ => {
+                            self.field_bool("expression", false);
+                            self.block("body", Some(body.location), &body.stmts);
+                        }
+                    },
+                    _ => {
+                        self.field_bool("expression", false);
+                        self.block("body", Some(body.location), &body.stmts);
+                    }
+                }
+                self.end();
+            }
+            ExprKind::Function { function } => self.function("FunctionExpression", start, function),
+            ExprKind::Class { class } => self.class("ClassExpression", start, class),
+            ExprKind::Identifier { reference } | ExprKind::ImportIdentifier { reference } => {
+                self.identifier(*reference, expr.location)
+            }
+            ExprKind::PrivateIdentifier { reference } => {
+                let symbols = self.symbols;
+                let name = &symbols[*reference].name;
+                self.begin("PrivateIdentifier", start);
+                self.field_str("name", name.strip_prefix('#').unwrap_or(name));
+                self.end();
+            }
+            ExprKind::JSXElement { .. } => {
+                self.begin("JSXElement", start);
+                self.end();
+            }
+            ExprKind::Object { properties } => {
+                self.begin("ObjectExpression", start);
+                self.list("properties", properties, |writer, property| {
+                    writer.object_property(property)
+                });
+                self.end();
+            }
+            ExprKind::Spread { value } => {
+                self.begin("SpreadElement", start);
+                self.key("argument");
+                self.expr(value);
+                self.end();
+            }
+            ExprKind::Template {
+                tag,
+                head,
+                head_raw,
+                parts,
+            } => {
+                if let ExprKind::Missing = tag.data.as_ref() {
+                    self.template_literal(start, head, head_raw, parts);
+                    return;
+                }
+                self.begin("TaggedTemplateExpression", start);
+                self.key("tag");
+                self.expr(tag);
+                self.key("quasi");
+                self.template_literal(None, head, head_raw, parts);
+                self.end();
+            }
+            ExprKind::Await { value } => {
+                self.begin("AwaitExpression", start);
+                self.key("argument");
+                self.expr(value);
+                self.end();
+            }
+            ExprKind::Yield { value, is_star } => {
+                self.begin("YieldExpression", start);
+                self.key("argument");
+                self.expr(value);
+                self.field_bool("delegate", *is_star);
+                self.end();
+            }
+            ExprKind::If { test, yes, no } => {
+                self.begin("ConditionalExpression", start);
+                self.key("test");
+                self.expr(test);
+                self.key("consequent");
+                self.expr(yes);
+                self.key("alternate");
+                self.expr(no);
+                self.end();
+            }
+            ExprKind::Require { path, .. } => {
+                self.begin("CallExpression", start);
+                self.key("callee");
+                self.name("require", start);
+                self.list("arguments", Some(path), |writer, path| {
+                    writer.string_literal(&path.text, Some(path.loc))
+                });
+                self.field_bool("optional", false);
+                self.end();
+            }
+            ExprKind::Import { expr: source } => {
+                self.begin("ImportExpression", start);
+                self.key("source");
+                self.expr(source);
+                self.end();
+            }
+        }
+    }
+
+    // The raw text is only kept for tagged templates, so the raw text of an
+    // untagged template is the cooked text instead
+    fn template_literal(
+        &mut self,
+        start: Option<Location>,
+        head: &[u16],
+        head_raw: &str,
+        parts: &[TemplatePart],
+    ) {
+        self.begin("TemplateLiteral", start);
+        let quasis = std::iter::once((head, head_raw)).chain(
+            parts
+                .iter()
+                .map(|part| (&part.tail[..], &part.tail_raw[..])),
+        );
+        let last = parts.len();
+        self.list(
+            "quasis",
+            quasis.enumerate(),
+            |writer, (i, (cooked, raw))| {
+                let cooked = utf16_to_string_lossy(cooked);
+                writer.begin("TemplateElement", None);
+                writer.key("value");
+                writer.json.push_str("{\"raw\":");
+                writer
+                    .json
+                    .push_str(&quote_json(if raw.is_empty() { &cooked } else { raw }));
+                writer.json.push_str(",\"cooked\":");
+                writer.json.push_str(&quote_json(&cooked));
+                writer.json.push('}');
+                writer.field_bool("tail", i == last);
+                writer.end();
+            },
+        );
+        self.list("expressions", parts, |writer, part| {
+            writer.expr(&part.value)
+        });
+        self.end();
+    }
+}
+
+fn is_parenthesized(expr: &Expr) -> bool {
+    match expr.data.as_ref() {
+        ExprKind::Call {
+            is_parenthesized, ..
+        }
+        | ExprKind::Dot {
+            is_parenthesized, ..
+        }
+        | ExprKind::Index {
+            is_parenthesized, ..
+        } => *is_parenthesized,
+        _ => false,
+    }
+}
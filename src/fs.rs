@@ -1,5 +1,7 @@
-use std::collections::HashMap;
+use crate::paths;
+use std::collections::{BTreeSet, HashMap};
 use std::path::{Path as StdPath, PathBuf};
+use std::sync::Mutex;
 
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
 pub enum EntryKind {
@@ -19,6 +21,9 @@ pub trait FileSystem {
     fn read_directory<P: AsRef<StdPath>>(&self, path: P) -> HashMap<String, Entry>;
     fn read_file<P: AsRef<StdPath>>(&self, path: P) -> Option<String>;
 
+    // Files loaded as assets don't have to be valid UTF-8
+    fn read_file_bytes<P: AsRef<StdPath>>(&self, path: P) -> Option<Vec<u8>>;
+
     // This is part of the interface because the mock interface used for tests
     // should not depend on file system behavior (i.e. different slashes for
     // Windows) while the real interface should.
@@ -26,7 +31,116 @@ pub trait FileSystem {
     fn dir<P: AsRef<StdPath>>(&self, path: P) -> PathBuf;
     fn base<P: AsRef<StdPath>>(&self, path: P) -> PathBuf;
     fn join<P: AsRef<StdPath>>(&self, path: Vec<P>) -> PathBuf;
-    fn relative_to_cwd<P: AsRef<StdPath>>(&self, path: P) -> Option<PathBuf>;
+
+    // The current working directory with forward slashes, or an empty string
+    // if it isn't known, in which case no path is relative to it
+    fn cwd(&self) -> String;
+
+    // Forgets the directories that have been listed, for when files may have
+    // been added or removed since then
+    fn clear_cache(&self) {}
+}
+
+// Entry points can be glob patterns like "src/**/*.ts". Within a segment, "*"
+// is any run of characters and "?" is any one character. A segment that is
+// just "**" is any number of directories, including none. Wildcards never
+// match a name that starts with a "." so hidden files are left alone.
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+// Returns the files that match an absolute pattern, sorted. Everything before
+// the first segment with a wildcard is used as is, and the rest is matched by
+// listing directories.
+pub fn expand_glob<F: FileSystem>(fs: &F, pattern: &str) -> Vec<String> {
+    let pattern = paths::to_slash(pattern);
+    let segments: Vec<&str> = pattern.split('/').collect();
+    let first_glob = segments
+        .iter()
+        .position(|segment| is_glob(segment))
+        .unwrap_or(segments.len());
+    let mut base = segments[..first_glob].join("/");
+    if base.is_empty() || base.ends_with(':') {
+        base.push('/');
+    }
+
+    let mut matches = BTreeSet::new();
+    if first_glob == segments.len() {
+        matches.insert(paths::normalize(&pattern));
+    } else {
+        glob_dir(fs, &base, &segments[first_glob..], &mut matches);
+    }
+    matches.into_iter().collect()
+}
+
+fn glob_dir<F: FileSystem>(fs: &F, dir: &str, segments: &[&str], matches: &mut BTreeSet<String>) {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let entries = fs.read_directory(dir);
+
+    if *segment == "**" {
+        glob_dir(fs, dir, rest, matches);
+        for (name, entry) in &entries {
+            if entry.kind == EntryKind::Dir && !name.starts_with('.') {
+                glob_dir(fs, &paths::join(dir, name), segments, matches);
+            }
+        }
+        return;
+    }
+
+    for (name, entry) in &entries {
+        let is_match = if is_glob(segment) {
+            let pattern: Vec<char> = segment.chars().collect();
+            let name_chars: Vec<char> = name.chars().collect();
+            !name.starts_with('.') && glob_segment_matches(&pattern, &name_chars)
+        } else {
+            name == segment
+        };
+        if !is_match {
+            continue;
+        }
+        match (entry.kind, rest.is_empty()) {
+            (EntryKind::File, true) => {
+                matches.insert(paths::join(dir, name));
+            }
+            (EntryKind::Dir, false) => glob_dir(fs, &paths::join(dir, name), rest, matches),
+            _ => {}
+        }
+    }
+}
+
+// Checks a path against a pattern using the same syntax as entry points,
+// except that hidden files aren't special. Both must use forward slashes.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    glob_segments_match(&pattern, &path)
+}
+
+fn glob_segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| glob_segments_match(rest, &path[i..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                let segment: Vec<char> = segment.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                glob_segment_matches(&segment, &name) && glob_segments_match(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn glob_segment_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob_segment_matches(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob_segment_matches(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && glob_segment_matches(rest, &name[1..]),
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -35,46 +149,225 @@ pub struct MockFileSystem {
     pub files: HashMap<PathBuf, String>,
 }
 
+// The mock file system always uses forward slashes, so tests behave the same
+// on every platform
+fn mock_path<P: AsRef<StdPath>>(path: P) -> String {
+    paths::to_slash(&path.as_ref().to_string_lossy())
+}
+
 impl MockFileSystem {
-    // pub fn new(mut input: HashMap<PathBuf, String>) -> Self {
-    //     let mut dirs = HashMap::new();
-    //     let mut files = HashMap::new();
-    //
-    //     for (k, v) in input.drain() {
-    //         files.insert(k.clone(), v.clone());
-    //         let original = k;
-    //     }
-    //
-    //     Self { dirs, files }
-    // }
-}
-
-// impl FileSystem for MockFileSystem {
-//     fn read_directory<P: AsRef<StdPath>>(&self, path: P) -> HashMap<String, Entry, RandomState> {
-//         unimplemented!()
-//     }
-//
-//     fn read_file<P: AsRef<StdPath>>(&self, path: P) -> Option<String> {
-//         unimplemented!()
-//     }
-//
-//     fn abs<P: AsRef<StdPath>>(&self, path: P) -> Option<PathBuf> {
-//         unimplemented!()
-//     }
-//
-//     fn dir<P: AsRef<StdPath>>(&self, path: P) -> PathBuf {
-//         unimplemented!()
-//     }
-//
-//     fn base<P: AsRef<StdPath>>(&self, path: P) -> PathBuf {
-//         unimplemented!()
-//     }
-//
-//     fn join<P: AsRef<StdPath>>(&self, path: Vec<P>) -> PathBuf {
-//         unimplemented!()
-//     }
-//
-//     fn relative_to_cwd<P: AsRef<StdPath>>(&self, path: P) -> Option<PathBuf> {
-//         unimplemented!()
-//     }
-// }
+    // The input maps absolute file paths to their contents. Every parent
+    // directory of every file is created implicitly.
+    pub fn new(mut input: HashMap<PathBuf, String>) -> Self {
+        let mut dirs: HashMap<PathBuf, HashMap<String, Entry>> = HashMap::new();
+        let mut files = HashMap::new();
+
+        for (k, v) in input.drain() {
+            let original = mock_path(&k);
+            let mut k = original.clone();
+            files.insert(PathBuf::from(&original), v);
+
+            // Build the directory map
+            loop {
+                let k_dir = paths::dir(&k);
+                let dir = dirs.entry(PathBuf::from(&k_dir)).or_default();
+                if k_dir == k {
+                    break;
+                }
+                let kind = if k == original {
+                    EntryKind::File
+                } else {
+                    EntryKind::Dir
+                };
+                dir.insert(
+                    paths::base(&k),
+                    Entry {
+                        kind,
+                        sym_link: String::new(),
+                    },
+                );
+                k = k_dir;
+            }
+        }
+
+        Self { dirs, files }
+    }
+}
+
+impl FileSystem for MockFileSystem {
+    fn read_directory<P: AsRef<StdPath>>(&self, path: P) -> HashMap<String, Entry> {
+        self.dirs
+            .get(StdPath::new(&mock_path(path)))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn read_file<P: AsRef<StdPath>>(&self, path: P) -> Option<String> {
+        self.files.get(StdPath::new(&mock_path(path))).cloned()
+    }
+
+    fn read_file_bytes<P: AsRef<StdPath>>(&self, path: P) -> Option<Vec<u8>> {
+        self.read_file(path).map(String::into_bytes)
+    }
+
+    fn abs<P: AsRef<StdPath>>(&self, path: P) -> Option<PathBuf> {
+        Some(PathBuf::from(paths::join("/", &mock_path(path))))
+    }
+
+    fn dir<P: AsRef<StdPath>>(&self, path: P) -> PathBuf {
+        PathBuf::from(paths::dir(&mock_path(path)))
+    }
+
+    fn base<P: AsRef<StdPath>>(&self, path: P) -> PathBuf {
+        PathBuf::from(paths::base(&mock_path(path)))
+    }
+
+    fn join<P: AsRef<StdPath>>(&self, path: Vec<P>) -> PathBuf {
+        let mut result = String::new();
+        for part in path {
+            result = paths::join(&result, &mock_path(part));
+        }
+        PathBuf::from(result)
+    }
+
+    fn cwd(&self) -> String {
+        String::new()
+    }
+}
+
+pub struct RealFileSystem {
+    // Stores the file entries for directories we've listed before
+    entries: Mutex<HashMap<PathBuf, HashMap<String, Entry>>>,
+
+    // For the current working directory
+    cwd: Option<PathBuf>,
+}
+
+impl RealFileSystem {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            cwd: std::env::current_dir().ok(),
+        }
+    }
+
+    fn read_directory_uncached(path: &StdPath) -> HashMap<String, Entry> {
+        let mut entries = HashMap::new();
+
+        // A directory that can't be read is treated as an empty one
+        let dir = match std::fs::read_dir(path) {
+            Ok(dir) => dir,
+            Err(_) => return entries,
+        };
+
+        for entry in dir.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let entry_path = entry.path();
+            let mut sym_link = String::new();
+
+            // Symbolic links are followed so that the entry has the kind of
+            // whatever it points to. Broken links are skipped.
+            let mut file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+            if file_type.is_symlink() {
+                match std::fs::canonicalize(&entry_path) {
+                    Ok(target) => sym_link = target.to_string_lossy().into_owned(),
+                    Err(_) => continue,
+                }
+                file_type = match std::fs::metadata(&entry_path) {
+                    Ok(metadata) => metadata.file_type(),
+                    Err(_) => continue,
+                };
+            }
+
+            let kind = if file_type.is_dir() {
+                EntryKind::Dir
+            } else if file_type.is_file() {
+                EntryKind::File
+            } else {
+                continue;
+            };
+            entries.insert(name, Entry { kind, sym_link });
+        }
+
+        entries
+    }
+}
+
+impl Default for RealFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for RealFileSystem {
+    fn read_directory<P: AsRef<StdPath>>(&self, path: P) -> HashMap<String, Entry> {
+        let path = path.as_ref();
+
+        // First, check the cache
+        if let Some(entries) = self.entries.lock().unwrap().get(path) {
+            return entries.clone();
+        }
+
+        // Cache miss: read the directory entries without holding the lock
+        let entries = Self::read_directory_uncached(path);
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(path.to_owned(), entries.clone());
+        entries
+    }
+
+    fn read_file<P: AsRef<StdPath>>(&self, path: P) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
+
+    fn read_file_bytes<P: AsRef<StdPath>>(&self, path: P) -> Option<Vec<u8>> {
+        std::fs::read(path).ok()
+    }
+
+    fn abs<P: AsRef<StdPath>>(&self, path: P) -> Option<PathBuf> {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            return Some(path.to_owned());
+        }
+        let cwd = self.cwd.as_ref()?;
+        Some(PathBuf::from(paths::join(
+            &cwd.to_string_lossy(),
+            &path.to_string_lossy(),
+        )))
+    }
+
+    fn dir<P: AsRef<StdPath>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        match path.parent() {
+            Some(parent) => parent.to_owned(),
+            None => path.to_owned(),
+        }
+    }
+
+    fn base<P: AsRef<StdPath>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        match path.file_name() {
+            Some(name) => PathBuf::from(name),
+            None => path.to_owned(),
+        }
+    }
+
+    fn join<P: AsRef<StdPath>>(&self, path: Vec<P>) -> PathBuf {
+        path.iter().collect()
+    }
+
+    fn cwd(&self) -> String {
+        self.cwd
+            .as_ref()
+            .map(|cwd| paths::to_slash(&cwd.to_string_lossy()))
+            .unwrap_or_default()
+    }
+
+    fn clear_cache(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,81 @@
+// Extension hooks load files that no loader understands, such as Vue and
+// Svelte single-file components, without the bundler having to know about
+// them. A hook is registered for an extension and gets the path and contents
+// of every file with that extension before anything is parsed. It returns
+// the code to use instead along with the loader for that code, so the file
+// becomes a JavaScript, TypeScript, or CSS module at the same path:
+//
+//   hooks.register(".vue", |path, contents| {
+//       let code = compile_vue(path, contents)?;
+//       Ok(HookOutput::new(code, Loader::JS))
+//   });
+//
+// A hook takes precedence over the loaders for its extension. Hooks are
+// called from the thread that scans the bundle, one file at a time.
+
+use crate::bundler::Loader;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+// Returns the text of the error to report for the file if it can't be loaded
+pub type ExtensionHook = dyn Fn(&str, &str) -> Result<HookOutput, String> + Send + Sync;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookOutput {
+    pub contents: String,
+
+    // Any loader that reads text, usually JS, JSX, TS, TSX, or CSS
+    pub loader: Loader,
+
+    // Maps the contents back to the original file. It's kept with the bundle
+    // for when source maps are supported.
+    pub source_map: Option<String>,
+}
+
+impl HookOutput {
+    pub fn new(contents: String, loader: Loader) -> Self {
+        HookOutput {
+            contents,
+            loader,
+            source_map: None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ExtensionHooks {
+    // Keyed by the extension including the dot, like ".vue"
+    hooks: BTreeMap<String, Arc<ExtensionHook>>,
+}
+
+impl ExtensionHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replaces the hook that was registered for the extension before, if any
+    pub fn register<H>(&mut self, extension: &str, hook: H)
+    where
+        H: Fn(&str, &str) -> Result<HookOutput, String> + Send + Sync + 'static,
+    {
+        self.hooks.insert(extension.to_owned(), Arc::new(hook));
+    }
+
+    pub fn get(&self, extension: &str) -> Option<&ExtensionHook> {
+        self.hooks.get(extension).map(|hook| &**hook)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+}
+
+// Only the extensions are shown. The persistent cache keys on the debug
+// output of the options, which is fine since it also keys on the contents a
+// hook returned.
+impl fmt::Debug for ExtensionHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.hooks.keys()).finish()
+    }
+}
@@ -1,4 +1,8 @@
-use crate::tables::Token;
+use crate::error::Error;
+use crate::logging::{Log, MsgKind, Source};
+use crate::tables::{jsx_entry, IdContinue, IdStart, RangeTable, Token};
+use std::convert::TryFrom;
+use std::ops::Range;
 
 #[derive(Debug, Clone)]
 pub struct Json {
@@ -6,21 +10,1956 @@ pub struct Json {
     pub allow_comments: bool,
 }
 
-pub struct Lexer {
-    //     log                             logging.Log
-    //     source                          logging.Source
+pub struct Lexer<'a> {
+    log: &'a Log,
+    source: &'a Source,
     pub current: usize,
     pub start: usize,
     pub end: usize,
     pub token: Token,
     pub has_newline_before: bool,
-    pub code_point: char,
+
+    // This is "None" at the end of the file
+    pub code_point: Option<char>,
     pub string_literal: Vec<u16>,
     pub identifier: String,
     pub number: f64,
-    pub rescan_close_brace_as_template_token: bool,
+    pub is_legacy_octal_literal: bool,
+
+    // Template literals can't report invalid escapes right away since they
+    // are allowed in tagged templates. The parser checks this instead.
+    pub invalid_template_escape: Option<Range<usize>>,
     pub json: Json,
 
     // The log is disabled during speculative scans that may backtrack
     pub is_log_disabled: bool,
+
+    // Legal comments between the previous token and this one, and all other
+    // comments too if "preserve_comments" is set. The parser turns the ones
+    // in front of a statement into statements of their own.
+    pub comments_before: Vec<Comment>,
+    pub preserve_comments: bool,
+
+    // There's a "/* @__PURE__ */" or "/* #__PURE__ */" comment between the
+    // previous token and this one. It marks the call or "new" expression
+    // that follows as having no side effects, so it can be removed if its
+    // result isn't used.
+    pub has_pure_comment_before: bool,
+}
+
+// A comment including its "//" or "/* */". It's trailing if it's on the same
+// line as the token before it, as in "a(); // comment".
+#[derive(Debug, Clone)]
+pub struct Comment {
+    pub text: String,
+    pub is_trailing: bool,
+}
+
+// Everything needed to put the lexer back at an earlier token. The parser
+// takes one of these before it tries an ambiguous parse, for example:
+//
+//   (a, b) => c     vs.  (a, b)
+//   f<T>(x)         vs.  f < T > (x)
+//   <T>(x) => x     vs.  <div>(x)</div>
+//
+// Speculation usually starts on a punctuation token such as "(" or "<" which
+// doesn't carry a payload, so taking a checkpoint usually doesn't allocate.
+// The payload buffers are only copied when the current token needs them,
+// which includes keywords since their text is kept as an identifier too.
+#[derive(Debug, Clone)]
+pub struct LexerCheckpoint {
+    current: usize,
+    start: usize,
+    end: usize,
+    token: Token,
+    has_newline_before: bool,
+    has_pure_comment_before: bool,
+    code_point: Option<char>,
+    number: f64,
+    is_legacy_octal_literal: bool,
+    invalid_template_escape: Option<Range<usize>>,
+    is_log_disabled: bool,
+    string_literal: Option<Vec<u16>>,
+    identifier: Option<String>,
+}
+
+impl<'a> Lexer<'a> {
+    // The lexer starts out positioned on the first code point. The parser reads
+    // the first token itself since the mode it should be read in depends on
+    // what the parser expects.
+    pub fn new(log: &'a Log, source: &'a Source) -> Self {
+        let mut lexer = Lexer {
+            log,
+            source,
+            current: 0,
+            start: 0,
+            end: 0,
+            token: Token::EndOfFile,
+            has_newline_before: false,
+            code_point: None,
+            string_literal: vec![],
+            identifier: String::new(),
+            number: 0.0,
+            is_legacy_octal_literal: false,
+            invalid_template_escape: None,
+            json: Json {
+                parse: false,
+                allow_comments: false,
+            },
+            is_log_disabled: false,
+            comments_before: vec![],
+            preserve_comments: false,
+            has_pure_comment_before: false,
+        };
+        lexer.step();
+        lexer
+    }
+
+    pub fn raw(&self) -> &'a str {
+        &self.source.contents[self.start..self.end]
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    fn step(&mut self) {
+        self.code_point = self.source.contents[self.current..].chars().next();
+        self.end = self.current;
+        self.current += self.code_point.map_or(0, char::len_utf8);
+    }
+
+    fn add_range_error(&self, range: Range<usize>, text: String) {
+        if !self.is_log_disabled {
+            self.log.add_range_error(self.source, range, text);
+        }
+    }
+
+    // The lexer doesn't unwind on errors. It reports the error and turns the
+    // current token into a syntax error, which the parser then bails on.
+    pub fn syntax_error(&mut self) {
+        let text = match self.source.contents[self.end..].chars().next() {
+            None => "Unexpected end of file".to_owned(),
+            Some('"') => "Syntax error '\"'".to_owned(),
+            Some(c) if (c as u32) < 0x20 => format!("Syntax error \"\\x{:02X}\"", c as u32),
+            Some(c) if (c as u32) >= 0x80 => format!("Syntax error \"\\u{{{:x}}}\"", c as u32),
+            Some(c) => format!("Syntax error \"{}\"", c),
+        };
+        self.add_range_error(self.end..self.end, text);
+        self.token = Token::SyntaxError;
+    }
+
+    pub fn expected(&mut self, token: Token) {
+        let found = if self.start == self.source.contents.len() {
+            "end of file".to_owned()
+        } else {
+            format!("{:?}", self.raw())
+        };
+        self.add_range_error(
+            self.range(),
+            format!("Expected {} but found {}", token.to_str(), found),
+        );
+        self.token = Token::SyntaxError;
+    }
+
+    pub fn unexpected(&mut self) {
+        let found = if self.start == self.source.contents.len() {
+            "end of file".to_owned()
+        } else {
+            format!("{:?}", self.raw())
+        };
+        self.add_range_error(self.range(), format!("Unexpected {}", found));
+        self.token = Token::SyntaxError;
+    }
+
+    pub fn expect(&mut self, token: Token) {
+        if self.token != token {
+            self.expected(token);
+            return;
+        }
+        self.next();
+    }
+
+    // A semicolon can be left out before "}", at the end of the file, and at
+    // the end of a line
+    pub fn expect_or_insert_semicolon(&mut self) {
+        if self.token == Token::Semicolon
+            || (!self.has_newline_before
+                && self.token != Token::CloseBrace
+                && self.token != Token::EndOfFile)
+        {
+            self.expect(Token::Semicolon);
+        }
+    }
+
+    // Contextual keywords such as "as", "from", and "of" are only keywords in
+    // certain places and are lexed as identifiers. Checking the raw text means
+    // an escaped identifier like "\u0061s" doesn't count.
+    pub fn is_contextual_keyword(&self, text: &str) -> bool {
+        self.token == Token::Identifier && self.raw() == text
+    }
+
+    pub fn expect_contextual_keyword(&mut self, text: &str) {
+        if !self.is_contextual_keyword(text) {
+            self.add_range_error(self.range(), format!("Expected {:?}", text));
+            self.token = Token::SyntaxError;
+            return;
+        }
+        self.next();
+    }
+
+    // The ">" that ends TypeScript type arguments or parameters may be the
+    // first character of a longer token, as in "Array<Array<T>>" or
+    // "let a: Array<T>= []". Only the ">" is consumed then, and the rest of
+    // the token becomes the current one.
+    pub fn expect_greater_than(&mut self) {
+        self.token = match self.token {
+            Token::GreaterThan => {
+                self.next();
+                return;
+            }
+            Token::GreaterThanEquals => Token::Equals,
+            Token::GreaterThanGreaterThan => Token::GreaterThan,
+            Token::GreaterThanGreaterThanEquals => Token::GreaterThanEquals,
+            Token::GreaterThanGreaterThanGreaterThan => Token::GreaterThanGreaterThan,
+            Token::GreaterThanGreaterThanGreaterThanEquals => Token::GreaterThanGreaterThanEquals,
+            _ => {
+                self.expected(Token::GreaterThan);
+                return;
+            }
+        };
+        self.start += 1;
+        self.has_newline_before = false;
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source.contents[self.current..].chars().next()
+    }
+
+    // Reads the next JavaScript token. Regular expressions and the parts of
+    // template literals depend on what the parser expects next, so "/" is
+    // always returned as division here and "}" always as a brace.
+    pub fn next(&mut self) {
+        self.has_newline_before = false;
+        self.has_pure_comment_before = false;
+        self.comments_before.clear();
+
+        loop {
+            self.start = self.end;
+            self.token = Token::EndOfFile;
+
+            match self.code_point {
+                None => {}
+
+                Some('#') => {
+                    if self.start == 0 && self.source.contents.starts_with("#!") {
+                        loop {
+                            self.step();
+                            match self.code_point {
+                                Some(c) if !is_line_terminator(c) => {}
+                                _ => break,
+                            }
+                        }
+                        self.identifier = self.raw().to_owned();
+                        self.token = Token::Hashbang;
+                    } else {
+                        // "#a" is the name of a private class member
+                        self.step();
+                        match self.code_point {
+                            Some(c) if is_identifier_start(c) || c == '\\' => {
+                                self.lex_identifier();
+                                if self.token != Token::SyntaxError {
+                                    self.identifier = format!("#{}", self.identifier);
+                                    self.token = Token::PrivateIdentifier;
+                                }
+                            }
+                            _ => self.syntax_error(),
+                        }
+                    }
+                }
+
+                Some('\r') | Some('\n') | Some('\u{2028}') | Some('\u{2029}') => {
+                    self.step();
+                    self.has_newline_before = true;
+                    continue;
+                }
+
+                Some('\t') | Some(' ') => {
+                    self.step();
+                    continue;
+                }
+
+                Some('(') => {
+                    self.step();
+                    self.token = Token::OpenParen;
+                }
+
+                Some(')') => {
+                    self.step();
+                    self.token = Token::CloseParen;
+                }
+
+                Some('[') => {
+                    self.step();
+                    self.token = Token::OpenBracket;
+                }
+
+                Some(']') => {
+                    self.step();
+                    self.token = Token::CloseBracket;
+                }
+
+                Some('{') => {
+                    self.step();
+                    self.token = Token::OpenBrace;
+                }
+
+                Some('}') => {
+                    self.step();
+                    self.token = Token::CloseBrace;
+                }
+
+                Some(',') => {
+                    self.step();
+                    self.token = Token::Comma;
+                }
+
+                Some(':') => {
+                    self.step();
+                    self.token = Token::Colon;
+                }
+
+                Some(';') => {
+                    self.step();
+                    self.token = Token::Semicolon;
+                }
+
+                Some('@') => {
+                    self.step();
+                    self.token = Token::At;
+                }
+
+                Some('~') => {
+                    self.step();
+                    self.token = Token::Tilde;
+                }
+
+                Some('?') => {
+                    // '?' or '?.' or '??'
+                    self.step();
+                    self.token = Token::Question;
+                    match self.code_point {
+                        Some('?') => {
+                            self.step();
+                            self.token = Token::QuestionQuestion;
+                        }
+
+                        // "a?.5:b" is a conditional, not an optional chain
+                        Some('.') if !matches!(self.peek(), Some('0'..='9')) => {
+                            self.step();
+                            self.token = Token::QuestionDot;
+                        }
+
+                        _ => {}
+                    }
+                }
+
+                Some('%') => {
+                    // '%' or '%='
+                    self.step();
+                    self.token = Token::Percent;
+                    if self.code_point == Some('=') {
+                        self.step();
+                        self.token = Token::PercentEquals;
+                    }
+                }
+
+                Some('&') => {
+                    // '&' or '&=' or '&&'
+                    self.step();
+                    self.token = Token::Ampersand;
+                    match self.code_point {
+                        Some('=') => {
+                            self.step();
+                            self.token = Token::AmpersandEquals;
+                        }
+                        Some('&') => {
+                            self.step();
+                            self.token = Token::AmpersandAmpersand;
+                        }
+                        _ => {}
+                    }
+                }
+
+                Some('|') => {
+                    // '|' or '|=' or '||'
+                    self.step();
+                    self.token = Token::Bar;
+                    match self.code_point {
+                        Some('=') => {
+                            self.step();
+                            self.token = Token::BarEquals;
+                        }
+                        Some('|') => {
+                            self.step();
+                            self.token = Token::BarBar;
+                        }
+                        _ => {}
+                    }
+                }
+
+                Some('^') => {
+                    // '^' or '^='
+                    self.step();
+                    self.token = Token::Caret;
+                    if self.code_point == Some('=') {
+                        self.step();
+                        self.token = Token::CaretEquals;
+                    }
+                }
+
+                Some('+') => {
+                    // '+' or '+=' or '++'
+                    self.step();
+                    self.token = Token::Plus;
+                    match self.code_point {
+                        Some('=') => {
+                            self.step();
+                            self.token = Token::PlusEquals;
+                        }
+                        Some('+') => {
+                            self.step();
+                            self.token = Token::PlusPlus;
+                        }
+                        _ => {}
+                    }
+                }
+
+                Some('-') => {
+                    // '-' or '-=' or '--'
+                    self.step();
+                    self.token = Token::Minus;
+                    match self.code_point {
+                        Some('=') => {
+                            self.step();
+                            self.token = Token::MinusEquals;
+                        }
+                        Some('-') => {
+                            self.step();
+                            self.token = Token::MinusMinus;
+                        }
+                        _ => {}
+                    }
+                }
+
+                Some('*') => {
+                    // '*' or '*=' or '**' or '**='
+                    self.step();
+                    self.token = Token::Asterisk;
+                    match self.code_point {
+                        Some('=') => {
+                            self.step();
+                            self.token = Token::AsteriskEquals;
+                        }
+                        Some('*') => {
+                            self.step();
+                            self.token = Token::AsteriskAsterisk;
+                            if self.code_point == Some('=') {
+                                self.step();
+                                self.token = Token::AsteriskAsteriskEquals;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                Some('/') => {
+                    // '/' or '/=' or '//' or '/* ... */'
+                    let is_trailing = !self.has_newline_before
+                        && !self.source.contents[..self.start].trim().is_empty();
+                    self.step();
+                    match self.code_point {
+                        Some('=') => {
+                            self.step();
+                            self.token = Token::SlashEquals;
+                        }
+
+                        Some('/') => {
+                            loop {
+                                self.step();
+                                match self.code_point {
+                                    Some(c) if !is_line_terminator(c) => {}
+                                    _ => break,
+                                }
+                            }
+                            if self.json.parse && !self.json.allow_comments {
+                                self.add_range_error(
+                                    self.range(),
+                                    "JSON does not support comments".to_owned(),
+                                );
+                            }
+                            self.scan_comment(is_trailing);
+                            continue;
+                        }
+
+                        Some('*') => {
+                            self.step();
+                            if !self.skip_multi_line_comment() {
+                                return;
+                            }
+                            if self.json.parse && !self.json.allow_comments {
+                                self.add_range_error(
+                                    self.range(),
+                                    "JSON does not support comments".to_owned(),
+                                );
+                            }
+                            self.scan_comment(is_trailing);
+                            continue;
+                        }
+
+                        _ => self.token = Token::Slash,
+                    }
+                }
+
+                Some('=') => {
+                    // '=' or '=>' or '==' or '==='
+                    self.step();
+                    self.token = Token::Equals;
+                    match self.code_point {
+                        Some('>') => {
+                            self.step();
+                            self.token = Token::EqualsGreaterThan;
+                        }
+                        Some('=') => {
+                            self.step();
+                            self.token = Token::EqualsEquals;
+                            if self.code_point == Some('=') {
+                                self.step();
+                                self.token = Token::EqualsEqualsEquals;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                Some('<') => {
+                    // '<' or '<<' or '<=' or '<<='
+                    self.step();
+                    self.token = Token::LessThan;
+                    match self.code_point {
+                        Some('=') => {
+                            self.step();
+                            self.token = Token::LessThanEquals;
+                        }
+                        Some('<') => {
+                            self.step();
+                            self.token = Token::LessThanLessThan;
+                            if self.code_point == Some('=') {
+                                self.step();
+                                self.token = Token::LessThanLessThanEquals;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                Some('>') => {
+                    // '>' or '>>' or '>>>' or '>=' or '>>=' or '>>>='
+                    self.step();
+                    self.token = Token::GreaterThan;
+                    match self.code_point {
+                        Some('=') => {
+                            self.step();
+                            self.token = Token::GreaterThanEquals;
+                        }
+                        Some('>') => {
+                            self.step();
+                            self.token = Token::GreaterThanGreaterThan;
+                            match self.code_point {
+                                Some('=') => {
+                                    self.step();
+                                    self.token = Token::GreaterThanGreaterThanEquals;
+                                }
+                                Some('>') => {
+                                    self.step();
+                                    self.token = Token::GreaterThanGreaterThanGreaterThan;
+                                    if self.code_point == Some('=') {
+                                        self.step();
+                                        self.token = Token::GreaterThanGreaterThanGreaterThanEquals;
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                Some('!') => {
+                    // '!' or '!=' or '!=='
+                    self.step();
+                    self.token = Token::Exclamation;
+                    if self.code_point == Some('=') {
+                        self.step();
+                        self.token = Token::ExclamationEquals;
+                        if self.code_point == Some('=') {
+                            self.step();
+                            self.token = Token::ExclamationEqualsEquals;
+                        }
+                    }
+                }
+
+                Some('.') => {
+                    // '.' or '...' or a number like ".5"
+                    if matches!(self.peek(), Some('0'..='9')) {
+                        self.lex_number();
+                    } else {
+                        self.step();
+                        self.token = Token::Dot;
+                        if self.code_point == Some('.') && self.peek() == Some('.') {
+                            self.step();
+                            self.step();
+                            self.token = Token::DotDotDot;
+                        }
+                    }
+                }
+
+                Some('0'..='9') => self.lex_number(),
+
+                Some(quote @ '\'') | Some(quote @ '"') => self.lex_string(quote),
+
+                Some('`') => {
+                    self.step();
+                    self.lex_template(Token::TemplateHead, Token::NoSubstitutionTemplateLiteral);
+                }
+
+                Some(c) => {
+                    // Check for unusual whitespace characters
+                    if is_whitespace(c) {
+                        self.step();
+                        continue;
+                    }
+
+                    if is_identifier_start(c) || c == '\\' {
+                        self.lex_identifier();
+                    } else {
+                        self.syntax_error();
+                    }
+                }
+            }
+
+            return;
+        }
+    }
+
+    // Identifiers can contain "\u" escapes, in which case "identifier" holds
+    // the decoded name. An escaped keyword such as "\u0069f" is still the name
+    // "if" for property access, but it can't be used as the keyword itself.
+    fn lex_identifier(&mut self) {
+        let mut identifier = String::new();
+        let mut has_escape = false;
+
+        loop {
+            let c = match self.code_point {
+                Some('\\') => {
+                    let escape_start = self.end;
+                    has_escape = true;
+                    self.step();
+                    match self.lex_identifier_escape() {
+                        Some(c) => c,
+                        None => {
+                            self.add_range_error(
+                                escape_start..self.end,
+                                "Invalid escape sequence".to_owned(),
+                            );
+                            self.token = Token::SyntaxError;
+                            return;
+                        }
+                    }
+                }
+                Some(c) if is_identifier_continue(c) => {
+                    self.step();
+                    c
+                }
+                _ => break,
+            };
+            identifier.push(c);
+        }
+
+        if !has_escape {
+            self.identifier = identifier;
+            self.token = Token::try_from(self.raw()).unwrap_or(Token::Identifier);
+            return;
+        }
+
+        if !is_identifier(&identifier) {
+            self.add_range_error(
+                self.range(),
+                format!("Invalid identifier: {:?}", identifier),
+            );
+            self.token = Token::SyntaxError;
+            return;
+        }
+
+        // Strict mode reserved words are identifiers in sloppy mode anyway
+        self.token = match Token::try_from(identifier.as_str()) {
+            Ok(token) if token.is_keyword() => Token::EscapedKeyword,
+            _ => Token::Identifier,
+        };
+        self.identifier = identifier;
+    }
+
+    // Called after the backslash has been consumed. Only "\u" escapes are
+    // allowed in identifiers.
+    fn lex_identifier_escape(&mut self) -> Option<char> {
+        if self.code_point != Some('u') {
+            return None;
+        }
+        self.step();
+        let value = if self.code_point == Some('{') {
+            self.step();
+            let digits_start = self.end;
+            let mut value: u32 = 0;
+            while let Some(d) = self.code_point.and_then(|c| c.to_digit(16)) {
+                value = value.saturating_mul(16).saturating_add(d);
+                self.step();
+            }
+            if self.end == digits_start || self.code_point != Some('}') {
+                return None;
+            }
+            self.step();
+            value
+        } else {
+            self.lex_hex_digits(4)?
+        };
+        std::char::from_u32(value)
+    }
+
+    // "/" can start a regular expression or be a division, which only the
+    // parser can tell apart:
+    //
+    //   a / b / c     a divided by b divided by c
+    //   a = /b/ / c   a regular expression divided by c
+    //
+    // The parser calls this when the current token is "/" or "/=" in a place
+    // where an expression can start. The token is extended to the end of the
+    // regular expression, so its text is available with "raw()" afterward.
+    pub fn scan_regexp(&mut self) {
+        let mut is_in_class = false;
+        loop {
+            match self.code_point {
+                Some('/') if !is_in_class => {
+                    self.step();
+                    break;
+                }
+                Some('[') => is_in_class = true,
+                Some(']') => is_in_class = false,
+
+                // An escaped "/" or "]" doesn't end anything
+                Some('\\') => {
+                    self.step();
+                    if matches!(self.code_point, Some(c) if !is_line_terminator(c)) {
+                        self.step();
+                    }
+                    continue;
+                }
+
+                Some(c) if !is_line_terminator(c) => {}
+                _ => {
+                    self.add_range_error(
+                        self.start..self.end,
+                        "Unterminated regular expression".to_owned(),
+                    );
+                    self.token = Token::SyntaxError;
+                    return;
+                }
+            }
+            self.step();
+        }
+
+        // Each flag may only be used once
+        let mut flags = String::new();
+        while let Some(c) = self.code_point {
+            if !is_identifier_continue(c) {
+                break;
+            }
+            let range = self.end..self.current;
+            let text = match c {
+                'g' | 'i' | 'm' | 's' | 'u' | 'y' if !flags.contains(c) => None,
+                'g' | 'i' | 'm' | 's' | 'u' | 'y' => {
+                    Some(format!("Duplicate flag \"{}\" in regular expression", c))
+                }
+                _ => Some(format!("Invalid flag \"{}\" in regular expression", c)),
+            };
+            if let Some(text) = text {
+                self.add_range_error(range, text);
+                self.token = Token::SyntaxError;
+                return;
+            }
+            flags.push(c);
+            self.step();
+        }
+    }
+
+    // Numbers start with a digit or with "." followed by a digit. Only decimal
+    // literals are handled here.
+    // Numbers come in several forms:
+    //
+    //   123  1_000  1.5e3  .5   decimal
+    //   0b101  0o17  0xFF       binary, octal and hexadecimal
+    //   123n  0xFFn             big integers, with the text in "identifier"
+    //   017  08                 legacy octal-like literals
+    //
+    // Legacy literals are errors in strict mode, but the lexer doesn't know
+    // about scopes so it just flags them for the parser.
+    fn lex_number(&mut self) {
+        self.is_legacy_octal_literal = false;
+        let mut radix = match (self.code_point, self.peek()) {
+            (Some('0'), Some('b')) | (Some('0'), Some('B')) => 2,
+            (Some('0'), Some('o')) | (Some('0'), Some('O')) => 8,
+            (Some('0'), Some('x')) | (Some('0'), Some('X')) => 16,
+            _ => 10,
+        };
+        let has_prefix = radix != 10;
+        let mut can_be_big_integer = true;
+
+        if has_prefix {
+            self.step();
+            self.step();
+            if !self.code_point.is_some_and(|c| c.is_digit(radix)) {
+                self.syntax_error();
+                return;
+            }
+            if !self.skip_digits(radix) {
+                return;
+            }
+        } else {
+            if self.code_point == Some('0') && matches!(self.peek(), Some('0'..='9')) {
+                // "017" is octal but "08" is decimal. Neither can have
+                // separators or be a big integer.
+                self.is_legacy_octal_literal = true;
+                can_be_big_integer = false;
+                while let Some('0'..='9') = self.code_point {
+                    self.step();
+                }
+                if self.raw().bytes().all(|c| c < b'8') {
+                    radix = 8;
+                }
+            } else if self.code_point == Some('0') && self.peek() == Some('_') {
+                // "0_1" isn't allowed either
+                self.step();
+                self.syntax_error();
+                return;
+            } else if !self.skip_digits(10) {
+                return;
+            }
+
+            if radix == 10 {
+                if self.code_point == Some('.') {
+                    can_be_big_integer = false;
+                    self.step();
+                    if self.code_point == Some('_') {
+                        self.syntax_error();
+                        return;
+                    }
+                    if !self.skip_digits(10) {
+                        return;
+                    }
+                }
+
+                if let Some('e') | Some('E') = self.code_point {
+                    can_be_big_integer = false;
+                    self.step();
+                    if let Some('+') | Some('-') = self.code_point {
+                        self.step();
+                    }
+                    if !matches!(self.code_point, Some('0'..='9')) {
+                        self.syntax_error();
+                        return;
+                    }
+                    if !self.skip_digits(10) {
+                        return;
+                    }
+                }
+            }
+        }
+
+        let text = self.raw().replace('_', "");
+        let is_big_integer = can_be_big_integer && self.code_point == Some('n');
+        if is_big_integer {
+            self.step();
+        }
+
+        // An identifier can't immediately follow a number ("3in x" and "1.5n"
+        // are both errors here)
+        if let Some(c) = self.code_point {
+            if is_identifier_start(c) || c == '\\' {
+                self.syntax_error();
+                return;
+            }
+        }
+
+        if is_big_integer {
+            self.identifier = text;
+            self.token = Token::BigIntegerLiteral;
+            return;
+        }
+
+        if radix != 10 {
+            let digits = if has_prefix { &text[2..] } else { &text[..] };
+            self.number = digits.chars().fold(0.0, |value, c| {
+                value * radix as f64 + c.to_digit(radix).unwrap() as f64
+            });
+            self.token = Token::NumericLiteral;
+            return;
+        }
+
+        match text.parse() {
+            Ok(number) => {
+                self.number = number;
+                self.token = Token::NumericLiteral;
+            }
+            Err(_) => self.syntax_error(),
+        }
+    }
+
+    // Separators have to be between two digits, so "1_000" is allowed but
+    // "1__000" and "1_" are not. Returns false if an error was logged.
+    fn skip_digits(&mut self, radix: u32) -> bool {
+        loop {
+            match self.code_point {
+                Some('_') => {
+                    let is_after_digit = self.source.contents[..self.end]
+                        .chars()
+                        .next_back()
+                        .is_some_and(|c| c.is_digit(radix));
+                    let is_before_digit = self.peek().is_some_and(|c| c.is_digit(radix));
+                    if !is_after_digit || !is_before_digit {
+                        self.syntax_error();
+                        return false;
+                    }
+                    self.step();
+                }
+                Some(c) if c.is_digit(radix) => self.step(),
+                _ => return true,
+            }
+        }
+    }
+
+    // Strings are stored as UTF-16 since that's what JavaScript strings are,
+    // and "\uD800" escapes can produce unpaired surrogates that UTF-8 can't
+    // represent
+    fn lex_string(&mut self, quote: char) {
+        self.step();
+        self.string_literal.clear();
+
+        loop {
+            match self.code_point {
+                None => {
+                    self.add_range_error(
+                        self.start..self.end,
+                        "Unterminated string literal".to_owned(),
+                    );
+                    self.token = Token::SyntaxError;
+                    return;
+                }
+
+                Some('\r') | Some('\n') => {
+                    self.add_range_error(
+                        self.start..self.end,
+                        "Unterminated string literal".to_owned(),
+                    );
+                    self.token = Token::SyntaxError;
+                    return;
+                }
+
+                Some(c) if c == quote => {
+                    self.step();
+                    break;
+                }
+
+                Some('\\') => {
+                    self.step();
+                    if !self.lex_escape_sequence(false) {
+                        self.token = Token::SyntaxError;
+                        return;
+                    }
+                }
+
+                Some(c) => {
+                    if self.json.parse && (c as u32) < 0x20 {
+                        self.syntax_error();
+                        return;
+                    }
+                    let mut buffer = [0; 2];
+                    self.string_literal
+                        .extend_from_slice(c.encode_utf16(&mut buffer));
+                    self.step();
+                }
+            }
+        }
+
+        if self.json.parse && quote != '"' {
+            self.add_range_error(
+                self.range(),
+                "JSON strings must use double quotes".to_owned(),
+            );
+        }
+        self.token = Token::StringLiteral;
+    }
+
+    // Template literals are lexed one piece at a time since the parser has to
+    // read the substitutions in between as regular expressions:
+    //
+    //   `a${b}c${d}e`
+    //   ^^^^           TemplateHead "a"
+    //        ^^^^      TemplateMiddle "c"
+    //             ^^^  TemplateTail "e"
+    //
+    // The parser is positioned on the "}" that ends a substitution when it
+    // calls this, and the lexer picks the template back up from there.
+    pub fn rescan_close_brace_as_template_token(&mut self) {
+        if self.token != Token::CloseBrace {
+            self.expected(Token::CloseBrace);
+            return;
+        }
+        self.lex_template(Token::TemplateMiddle, Token::TemplateTail);
+    }
+
+    // Called after the opening "`" or "}" has been consumed
+    fn lex_template(&mut self, head: Token, tail: Token) {
+        self.string_literal.clear();
+        self.invalid_template_escape = None;
+
+        loop {
+            match self.code_point {
+                None => {
+                    self.add_range_error(
+                        self.start..self.end,
+                        "Unterminated template literal".to_owned(),
+                    );
+                    self.token = Token::SyntaxError;
+                    return;
+                }
+
+                Some('`') => {
+                    self.step();
+                    self.token = tail;
+                    return;
+                }
+
+                Some('$') if self.peek() == Some('{') => {
+                    self.step();
+                    self.step();
+                    self.token = head;
+                    return;
+                }
+
+                Some('\\') => {
+                    self.step();
+
+                    // Legacy octal escapes are never allowed in templates
+                    if let Some(c @ '0'..='9') = self.code_point {
+                        if c != '0' || matches!(self.peek(), Some('0'..='9')) {
+                            let escape_start = self.end - 1;
+                            self.step();
+                            self.invalid_escape(escape_start, true);
+                            continue;
+                        }
+                    }
+
+                    if !self.lex_escape_sequence(true) {
+                        self.token = Token::SyntaxError;
+                        return;
+                    }
+                }
+
+                // Line endings are normalized to "\n" in template literals
+                Some('\r') => {
+                    self.step();
+                    if self.code_point == Some('\n') {
+                        self.step();
+                    }
+                    self.string_literal.push('\n' as u16);
+                }
+
+                Some(c) => {
+                    let mut buffer = [0; 2];
+                    self.string_literal
+                        .extend_from_slice(c.encode_utf16(&mut buffer));
+                    self.step();
+                }
+            }
+        }
+    }
+
+    // The text of the current template token as written, without the "`",
+    // "${" and "}" around it. Tagged templates can observe this through
+    // "strings.raw", which also sees line endings normalized to "\n".
+    pub fn raw_template_contents(&self) -> String {
+        let text = self.raw();
+        let suffix_len = match self.token {
+            Token::TemplateHead | Token::TemplateMiddle => 2,
+            _ => 1,
+        };
+        text[1..text.len() - suffix_len]
+            .replace("\r\n", "\n")
+            .replace('\r', "\n")
+    }
+
+    // Called after the backslash has been consumed. Appends the decoded code
+    // units to "string_literal" and returns false if an error was logged.
+    fn lex_escape_sequence(&mut self, is_template: bool) -> bool {
+        let escape_start = self.end - 1;
+        let c = match self.code_point {
+            None => {
+                self.syntax_error();
+                return false;
+            }
+            Some(c) => c,
+        };
+
+        // JSON only has a few escape sequences
+        if self.json.parse && !matches!(c, '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | 'u') {
+            self.syntax_error();
+            return false;
+        }
+
+        self.step();
+        let value = match c {
+            'b' => 0x08,
+            'f' => 0x0C,
+            'n' => 0x0A,
+            'r' => 0x0D,
+            't' => 0x09,
+            'v' => 0x0B,
+
+            // Line continuations add nothing to the string
+            '\r' => {
+                if self.code_point == Some('\n') {
+                    self.step();
+                }
+                return true;
+            }
+            '\n' | '\u{2028}' | '\u{2029}' => return true,
+
+            // Legacy octal escapes such as "\0" and "\123"
+            '0'..='7' => {
+                let mut value = c as u32 - '0' as u32;
+                let max_len = if c <= '3' { 3 } else { 2 };
+                let mut len = 1;
+                while len < max_len {
+                    match self.code_point {
+                        Some(d @ '0'..='7') => {
+                            value = value * 8 + (d as u32 - '0' as u32);
+                            self.step();
+                            len += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                value
+            }
+
+            'x' => match self.lex_hex_digits(2) {
+                Some(value) => value,
+                None => return self.invalid_escape(escape_start, is_template),
+            },
+
+            'u' => {
+                let value = if self.code_point == Some('{') && !self.json.parse {
+                    // "\u{10FFFF}"
+                    self.step();
+                    let digits_start = self.end;
+                    let mut value: u32 = 0;
+                    while let Some(d) = self.code_point.and_then(|c| c.to_digit(16)) {
+                        value = value.saturating_mul(16).saturating_add(d);
+                        self.step();
+                    }
+                    if self.end == digits_start || self.code_point != Some('}') {
+                        return self.invalid_escape(escape_start, is_template);
+                    }
+                    self.step();
+                    if value > 0x10FFFF {
+                        return self.invalid_escape(escape_start, is_template);
+                    }
+                    value
+                } else {
+                    match self.lex_hex_digits(4) {
+                        Some(value) => value,
+                        None => return self.invalid_escape(escape_start, is_template),
+                    }
+                };
+
+                // This may be half of a surrogate pair, which is kept as-is
+                if value <= 0xFFFF {
+                    self.string_literal.push(value as u16);
+                } else {
+                    let value = value - 0x10000;
+                    self.string_literal.push((0xD800 + (value >> 10)) as u16);
+                    self.string_literal.push((0xDC00 + (value & 0x3FF)) as u16);
+                }
+                return true;
+            }
+
+            // Any other character escapes itself
+            c => {
+                let mut buffer = [0; 2];
+                self.string_literal
+                    .extend_from_slice(c.encode_utf16(&mut buffer));
+                return true;
+            }
+        };
+
+        self.string_literal.push(value as u16);
+        true
+    }
+
+    fn lex_hex_digits(&mut self, count: usize) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..count {
+            let digit = self.code_point.and_then(|c| c.to_digit(16))?;
+            value = value * 16 + digit;
+            self.step();
+        }
+        Some(value)
+    }
+
+    // Invalid escapes in templates only make the cooked value undefined, so
+    // they are remembered for the parser and lexing continues
+    fn invalid_escape(&mut self, escape_start: usize, is_template: bool) -> bool {
+        let range = escape_start..self.end;
+        if is_template {
+            self.invalid_template_escape.get_or_insert(range);
+            return true;
+        }
+        self.add_range_error(range, "Invalid escape sequence".to_owned());
+        false
+    }
+
+    // Moves on to the next token while the parser is skipping over code after
+    // a syntax error. Nothing is reported, and a character that can't start a
+    // token is stepped over so that this always makes progress.
+    pub fn skip_token(&mut self) {
+        let was_log_disabled = std::mem::replace(&mut self.is_log_disabled, true);
+        self.next();
+        while self.token == Token::SyntaxError && self.end == self.start {
+            if self.code_point.is_none() {
+                self.token = Token::EndOfFile;
+                break;
+            }
+            self.step();
+            self.next();
+        }
+        self.is_log_disabled = was_log_disabled;
+    }
+
+    pub fn checkpoint(&self) -> LexerCheckpoint {
+        let (string_literal, identifier) = match self.token {
+            Token::StringLiteral
+            | Token::NoSubstitutionTemplateLiteral
+            | Token::TemplateHead
+            | Token::TemplateMiddle
+            | Token::TemplateTail => (Some(self.string_literal.clone()), None),
+            token if token >= Token::Identifier => (None, Some(self.identifier.clone())),
+            Token::BigIntegerLiteral => (None, Some(self.identifier.clone())),
+            _ => (None, None),
+        };
+
+        LexerCheckpoint {
+            current: self.current,
+            start: self.start,
+            end: self.end,
+            token: self.token,
+            has_newline_before: self.has_newline_before,
+            has_pure_comment_before: self.has_pure_comment_before,
+            code_point: self.code_point,
+            number: self.number,
+            is_legacy_octal_literal: self.is_legacy_octal_literal,
+            invalid_template_escape: self.invalid_template_escape.clone(),
+            is_log_disabled: self.is_log_disabled,
+            string_literal,
+            identifier,
+        }
+    }
+
+    pub fn restore(&mut self, checkpoint: LexerCheckpoint) {
+        self.current = checkpoint.current;
+        self.start = checkpoint.start;
+        self.end = checkpoint.end;
+        self.token = checkpoint.token;
+        self.has_newline_before = checkpoint.has_newline_before;
+        self.has_pure_comment_before = checkpoint.has_pure_comment_before;
+        self.code_point = checkpoint.code_point;
+        self.number = checkpoint.number;
+        self.is_legacy_octal_literal = checkpoint.is_legacy_octal_literal;
+        self.invalid_template_escape = checkpoint.invalid_template_escape;
+        self.is_log_disabled = checkpoint.is_log_disabled;
+        if let Some(string_literal) = checkpoint.string_literal {
+            self.string_literal = string_literal;
+        }
+        if let Some(identifier) = checkpoint.identifier {
+            self.identifier = identifier;
+        }
+    }
+
+    // Runs "parse" with the log disabled. If it returns None, the lexer is put
+    // back where it was before so the caller can try another interpretation.
+    // Errors found while speculating are never reported since they may just
+    // mean that the guess was wrong.
+    pub fn speculate<T, F>(&mut self, parse: F) -> Option<T>
+    where
+        F: FnOnce(&mut Self) -> Option<T>,
+    {
+        let checkpoint = self.checkpoint();
+        self.is_log_disabled = true;
+
+        match parse(self) {
+            Some(result) => {
+                self.is_log_disabled = checkpoint.is_log_disabled;
+                Some(result)
+            }
+            None => {
+                self.restore(checkpoint);
+                None
+            }
+        }
+    }
+
+    // JSX is lexed differently depending on where the parser is. Between "<"
+    // and ">" the lexer is in the element mode, where names may contain dashes
+    // and a namespace ("xlink:href"), and strings have no escape sequences but
+    // may contain entities. The parser calls this for every token of the tag:
+    //
+    //   <div class="a" {...props}>
+    //    ^^^ ^^^^^^^^^ ^^^^^^^^^^^
+    //
+    // Everything after the ">" of an opening tag is read with "lex_jsx_text"
+    // until the parser sees "<" or "{". The contents of "{...}" are ordinary
+    // JavaScript and are read with the normal lexer.
+    pub fn enter_jsx_element(&mut self) {
+        self.has_newline_before = false;
+
+        loop {
+            self.start = self.end;
+            self.token = Token::EndOfFile;
+
+            match self.code_point {
+                None => {}
+
+                Some('\r') | Some('\n') | Some('\u{2028}') | Some('\u{2029}') => {
+                    self.step();
+                    self.has_newline_before = true;
+                    continue;
+                }
+
+                Some('\t') | Some(' ') => {
+                    self.step();
+                    continue;
+                }
+
+                Some('.') => {
+                    self.step();
+                    self.token = Token::Dot;
+                }
+
+                Some('=') => {
+                    self.step();
+                    self.token = Token::Equals;
+                }
+
+                Some('{') => {
+                    self.step();
+                    self.token = Token::OpenBrace;
+                }
+
+                Some('}') => {
+                    self.step();
+                    self.token = Token::CloseBrace;
+                }
+
+                Some('<') => {
+                    self.step();
+                    self.token = Token::LessThan;
+                }
+
+                Some('>') => {
+                    self.step();
+                    self.token = Token::GreaterThan;
+                }
+
+                Some('/') => {
+                    // '/' or '//' or '/* ... */'
+                    self.step();
+                    match self.code_point {
+                        Some('/') => {
+                            loop {
+                                self.step();
+                                match self.code_point {
+                                    Some('\r') | Some('\n') | Some('\u{2028}')
+                                    | Some('\u{2029}') | None => break,
+                                    _ => {}
+                                }
+                            }
+                            continue;
+                        }
+
+                        Some('*') => {
+                            self.step();
+                            if !self.skip_multi_line_comment() {
+                                return;
+                            }
+                            continue;
+                        }
+
+                        _ => self.token = Token::Slash,
+                    }
+                }
+
+                Some(quote @ '\'') | Some(quote @ '"') => {
+                    let mut needs_decode = false;
+                    self.step();
+
+                    loop {
+                        match self.code_point {
+                            None => {
+                                self.syntax_error();
+                                return;
+                            }
+                            Some(c) if c == quote => {
+                                self.step();
+                                break;
+                            }
+                            Some(c) => {
+                                // Entities and non-ASCII strings need the slow path
+                                if c == '&' || !c.is_ascii() {
+                                    needs_decode = true;
+                                }
+                                self.step();
+                            }
+                        }
+                    }
+
+                    self.token = Token::StringLiteral;
+                    let text = &self.source.contents[self.start + 1..self.end - 1];
+                    self.string_literal = if needs_decode {
+                        decode_jsx_entities(vec![], text)
+                    } else {
+                        text.bytes().map(u16::from).collect()
+                    };
+                }
+
+                Some(c) => {
+                    // Check for unusual whitespace characters
+                    if is_whitespace(c) {
+                        self.step();
+                        continue;
+                    }
+
+                    if is_identifier_start(c) {
+                        self.step();
+                        self.skip_jsx_identifier_continue();
+
+                        // Parse JSX namespaces. These are not supported by React or
+                        // TypeScript but someone using JSX syntax in more obscure ways
+                        // may find a use for them. A namespaced name is just always
+                        // turned into a string so you can't use this feature to
+                        // reference JavaScript identifiers.
+                        if self.code_point == Some(':') {
+                            self.step();
+                            match self.code_point {
+                                Some(c) if is_identifier_start(c) => {
+                                    self.step();
+                                    self.skip_jsx_identifier_continue();
+                                }
+                                _ => self.add_range_error(
+                                    self.end..self.end,
+                                    "Expected identifier after \":\" in namespaced JSX name"
+                                        .to_owned(),
+                                ),
+                            }
+                        }
+
+                        self.identifier = self.raw().to_owned();
+                        self.token = Token::Identifier;
+                    } else {
+                        self.end = self.current;
+                        self.token = Token::SyntaxError;
+                    }
+                }
+            }
+
+            return;
+        }
+    }
+
+    // Reads the next child of an element: "{", "<", or a run of text. Text is
+    // returned as a string literal with the whitespace rules applied, and text
+    // that's only whitespace and newlines is skipped entirely.
+    pub fn lex_jsx_text(&mut self) {
+        self.has_newline_before = false;
+        let original_start = self.end;
+
+        loop {
+            self.start = self.end;
+            self.token = Token::EndOfFile;
+
+            match self.code_point {
+                None => {}
+
+                Some('{') => {
+                    self.step();
+                    self.token = Token::OpenBrace;
+                }
+
+                Some('<') => {
+                    self.step();
+                    self.token = Token::LessThan;
+                }
+
+                Some(_) => {
+                    let mut needs_fixing = false;
+
+                    loop {
+                        match self.code_point {
+                            None => {
+                                self.syntax_error();
+                                return;
+                            }
+
+                            Some('{') | Some('<') => break,
+
+                            // These are only allowed in JSX text as "{'>'}" and "{'}'}".
+                            // Babel and TypeScript both reject them, and a stray one is
+                            // usually a typo for something else.
+                            Some(c @ '>') | Some(c @ '}') => {
+                                self.add_range_error(
+                                    self.end..self.current,
+                                    format!(
+                                        "The character \"{}\" is not valid inside a JSX element, \
+                                         use \"{{'{}'}}\" instead",
+                                        c, c
+                                    ),
+                                );
+                                self.step();
+                            }
+
+                            Some(c) => {
+                                if c == '&' || is_line_terminator(c) || !c.is_ascii() {
+                                    needs_fixing = true;
+                                }
+                                self.step();
+                            }
+                        }
+                    }
+
+                    self.token = Token::StringLiteral;
+                    let text = &self.source.contents[original_start..self.end];
+                    if needs_fixing {
+                        self.string_literal = fix_whitespace_and_decode_jsx_entities(text);
+
+                        // Skip this token if it turned out to be empty after trimming
+                        if self.string_literal.is_empty() {
+                            self.has_newline_before = true;
+                            continue;
+                        }
+                    } else {
+                        self.string_literal = text.bytes().map(u16::from).collect();
+                    }
+                }
+            }
+
+            return;
+        }
+    }
+
+    pub fn expect_inside_jsx_element(&mut self, token: Token) {
+        if self.token != token {
+            self.expected(token);
+            return;
+        }
+        self.enter_jsx_element();
+    }
+
+    pub fn expect_jsx_element_child(&mut self, token: Token) {
+        if self.token != token {
+            self.expected(token);
+            return;
+        }
+        self.lex_jsx_text();
+    }
+
+    fn skip_jsx_identifier_continue(&mut self) {
+        while let Some(c) = self.code_point {
+            if !is_identifier_continue(c) && c != '-' {
+                break;
+            }
+            self.step();
+        }
+    }
+
+    // Legal comments are the ones that start with "//!" or "/*!" or contain
+    // "@license" or "@preserve". Licenses often require them to be kept.
+    fn scan_comment(&mut self, is_trailing: bool) {
+        if self.json.parse {
+            return;
+        }
+        let text = self.raw();
+        // Legal comments keep getting lines of their own unless all comments
+        // are preserved
+        if self.preserve_comments || is_legal_comment(text) {
+            self.comments_before.push(Comment {
+                text: text.to_owned(),
+                is_trailing: is_trailing && self.preserve_comments,
+            });
+        }
+        if text.contains("@__PURE__") || text.contains("#__PURE__") {
+            self.has_pure_comment_before = true;
+        }
+    }
+
+    // Called after the "/*" has been consumed. Returns false if the file ended
+    // before the comment did.
+    fn skip_multi_line_comment(&mut self) -> bool {
+        loop {
+            match self.code_point {
+                Some('*') => {
+                    self.step();
+                    if self.code_point == Some('/') {
+                        self.step();
+                        return true;
+                    }
+                }
+                Some(c) if is_line_terminator(c) => {
+                    self.step();
+                    self.has_newline_before = true;
+                }
+                Some(_) => self.step(),
+                None => {
+                    self.start = self.end;
+                    self.add_range_error(
+                        self.end..self.end,
+                        "Expected \"*/\" to terminate multi-line comment".to_owned(),
+                    );
+                    self.token = Token::SyntaxError;
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+// Reads the one token that starts at "location", for messages that need to
+// underline all of it. Nothing is logged if it isn't a valid token. A "/" is
+// read as division, since regular expressions depend on what comes before.
+pub fn token_at(source: &Source, location: usize) -> (Token, Range<usize>) {
+    if !source.contents.is_char_boundary(location) || location >= source.contents.len() {
+        return (Token::EndOfFile, location..location);
+    }
+    let log = Log::default();
+    let mut lexer = Lexer::new(&log, source);
+    lexer.is_log_disabled = true;
+    lexer.current = location;
+    lexer.step();
+    lexer.next();
+    (lexer.token, lexer.range())
+}
+
+// Reads every token in a file without parsing it, for tools that only need
+// tokens and for debugging the lexer. Some tokens depend on what the parser
+// expects, so the iterator guesses the way a parser would:
+//
+//   "/" starts a regular expression unless the previous token ends an
+//   expression, so "a / b" is division and "(/b/)" is a regular expression.
+//   A regular expression is returned as "Slash" and its range covers the
+//   whole expression, flags included.
+//
+//   "}" continues a template literal when it closes a "${".
+//
+// The guess for "/" can be wrong after ")" as in "if (a) /b/.test(c)", where
+// only the parser knows that the expression ended. The payload of each token
+// (the value of a string, the name of an identifier, and so on) is available
+// from "lexer()" until the next token is read. Iteration stops at the end of
+// the file or after the first syntax error, which has been logged.
+pub fn tokens<'a>(log: &'a Log, source: &'a Source) -> Tokens<'a> {
+    Tokens {
+        lexer: Lexer::new(log, source),
+        previous_ends_expr: false,
+        template_brace_depths: vec![],
+        is_done: false,
+    }
+}
+
+// Like "tokens", but reads the whole file and returns the syntax error that
+// stopped it instead of logging it. Warnings are dropped.
+pub fn try_tokens(source: &Source) -> Result<Vec<(Token, Range<usize>)>, Error> {
+    let log = Log::default();
+    let tokens: Vec<_> = tokens(&log, source).collect();
+    let mut msgs = log.take_msgs().into_iter();
+    match msgs.find(|msg| msg.kind == MsgKind::Error) {
+        Some(msg) => Err(Error::Lex(Box::new(msg))),
+        None => Ok(tokens),
+    }
+}
+
+pub struct Tokens<'a> {
+    lexer: Lexer<'a>,
+    previous_ends_expr: bool,
+
+    // The number of unclosed "{" in each "${" that is still open, innermost
+    // last
+    template_brace_depths: Vec<usize>,
+    is_done: bool,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn lexer(&self) -> &Lexer<'a> {
+        &self.lexer
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (Token, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done {
+            return None;
+        }
+        self.lexer.next();
+
+        let mut is_regexp = false;
+        match self.lexer.token {
+            Token::Slash | Token::SlashEquals if !self.previous_ends_expr => {
+                self.lexer.scan_regexp();
+                is_regexp = true;
+            }
+            Token::OpenBrace => {
+                if let Some(depth) = self.template_brace_depths.last_mut() {
+                    *depth += 1;
+                }
+            }
+            Token::CloseBrace => match self.template_brace_depths.last_mut() {
+                Some(0) => {
+                    self.lexer.rescan_close_brace_as_template_token();
+                    if self.lexer.token == Token::TemplateTail {
+                        self.template_brace_depths.pop();
+                    }
+                }
+                Some(depth) => *depth -= 1,
+                None => {}
+            },
+            Token::TemplateHead => self.template_brace_depths.push(0),
+            _ => {}
+        }
+
+        let token = self.lexer.token;
+        self.previous_ends_expr = is_regexp
+            || matches!(
+                token,
+                Token::NoSubstitutionTemplateLiteral
+                    | Token::NumericLiteral
+                    | Token::StringLiteral
+                    | Token::BigIntegerLiteral
+                    | Token::TemplateTail
+                    | Token::CloseBrace
+                    | Token::CloseBracket
+                    | Token::CloseParen
+                    | Token::PlusPlus
+                    | Token::MinusMinus
+                    | Token::Identifier
+                    | Token::EscapedKeyword
+                    | Token::PrivateIdentifier
+                    | Token::False
+                    | Token::Null
+                    | Token::Super
+                    | Token::This
+                    | Token::True
+            );
+        match token {
+            Token::EndOfFile => {
+                self.is_done = true;
+                None
+            }
+            Token::SyntaxError => {
+                self.is_done = true;
+                Some((token, self.lexer.range()))
+            }
+            _ => Some((token, self.lexer.range())),
+        }
+    }
+}
+
+pub fn is_legal_comment(text: &str) -> bool {
+    text.starts_with("//!")
+        || text.starts_with("/*!")
+        || text.contains("@license")
+        || text.contains("@preserve")
+}
+
+pub fn is_identifier_start(code_point: char) -> bool {
+    match code_point {
+        '_' | '$' | 'a'..='z' | 'A'..='Z' => true,
+
+        // All ASCII identifier start code points are listed above
+        c if c.is_ascii() => false,
+
+        c => IdStart::contains(c),
+    }
+}
+
+pub fn is_identifier_continue(code_point: char) -> bool {
+    match code_point {
+        '_' | '$' | '0'..='9' | 'a'..='z' | 'A'..='Z' => true,
+
+        // All ASCII identifier continue code points are listed above
+        c if c.is_ascii() => false,
+
+        // ZWNJ and ZWJ are allowed in identifiers
+        '\u{200C}' | '\u{200D}' => true,
+
+        c => IdContinue::contains(c),
+    }
+}
+
+pub fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if is_identifier_start(c) => chars.all(is_identifier_continue),
+        _ => false,
+    }
+}
+
+// String literals are stored as UTF-16, which may contain unpaired surrogates.
+// Those can't be part of an identifier.
+pub fn is_identifier_utf16(text: &[u16]) -> bool {
+    let mut chars = std::char::decode_utf16(text.iter().copied());
+    match chars.next() {
+        Some(Ok(c)) if is_identifier_start(c) => {
+            chars.all(|c| matches!(c, Ok(c) if is_identifier_continue(c)))
+        }
+        _ => false,
+    }
+}
+
+// This is the name generator used for compact generated names: "a" through
+// "$" for the first 54 numbers, then two characters, and so on. The first
+// character can't be a digit but the others can.
+pub fn number_to_minified_name(mut i: usize) -> String {
+    const HEAD: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_$";
+    const TAIL: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_$0123456789";
+
+    let mut name = String::new();
+    name.push(HEAD[i % HEAD.len()] as char);
+    i /= HEAD.len();
+    while i > 0 {
+        i -= 1;
+        name.push(TAIL[i % TAIL.len()] as char);
+        i /= TAIL.len();
+    }
+    name
+}
+
+pub fn is_line_terminator(code_point: char) -> bool {
+    matches!(code_point, '\r' | '\n' | '\u{2028}' | '\u{2029}')
+}
+
+// This doesn't include line terminators
+pub fn is_whitespace(code_point: char) -> bool {
+    matches!(
+        code_point,
+        '\u{0009}'
+            | '\u{000B}'
+            | '\u{000C}'
+            | '\u{0020}'
+            | '\u{00A0}'
+            | '\u{FEFF}'
+            | '\u{1680}'
+            | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}'
+    )
+}
+
+// JSX text follows the same rules as React:
+//
+//   - Lines that only contain whitespace are removed
+//   - Whitespace at the start of every line but the first is removed
+//   - Whitespace at the end of every line but the last is removed
+//   - The remaining lines are joined with a single space
+//
+// So "<b>  a\n   b  \n\n  c </b>" has the text "  a b c ".
+fn fix_whitespace_and_decode_jsx_entities(text: &str) -> Vec<u16> {
+    let mut decoded = vec![];
+
+    // Whitespace at the start of the first line is kept
+    let mut first_non_whitespace = Some(0);
+    let mut after_last_non_whitespace = None;
+
+    for (i, c) in text.char_indices() {
+        if is_line_terminator(c) {
+            if let (Some(first), Some(after_last)) =
+                (first_non_whitespace, after_last_non_whitespace)
+            {
+                if first < after_last {
+                    if !decoded.is_empty() {
+                        decoded.push(u16::from(b' '));
+                    }
+
+                    // Trim whitespace off the start and end of lines in the middle
+                    decoded = decode_jsx_entities(decoded, &text[first..after_last]);
+                }
+            }
+
+            // Reset for the next line
+            first_non_whitespace = None;
+        } else if c != '\t' && c != ' ' && !is_whitespace(c) {
+            after_last_non_whitespace = Some(i + c.len_utf8());
+            if first_non_whitespace.is_none() {
+                first_non_whitespace = Some(i);
+            }
+        }
+    }
+
+    if let Some(first) = first_non_whitespace {
+        if !decoded.is_empty() {
+            decoded.push(u16::from(b' '));
+        }
+
+        // Trim whitespace off the start of the last line
+        decoded = decode_jsx_entities(decoded, &text[first..]);
+    }
+
+    decoded
+}
+
+// Replaces "&amp;", "&#123;", and "&#x7B;" style entities. Anything that
+// doesn't parse as an entity is kept as-is, like browsers do.
+fn decode_jsx_entities(mut decoded: Vec<u16>, text: &str) -> Vec<u16> {
+    let mut i = 0;
+
+    while let Some(mut c) = text[i..].chars().next() {
+        i += c.len_utf8();
+
+        if c == '&' {
+            if let Some(length) = text[i..].find(';') {
+                let entity = &text[i..i + length];
+                let value = if let Some(number) = entity.strip_prefix('#') {
+                    match number.strip_prefix('x') {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => number.parse::<u32>().ok(),
+                    }
+                    .and_then(std::char::from_u32)
+                } else {
+                    jsx_entry(entity)
+                };
+                if let Some(value) = value {
+                    c = value;
+                    i += length + 1;
+                }
+            }
+        }
+
+        let mut buffer = [0; 2];
+        decoded.extend_from_slice(c.encode_utf16(&mut buffer));
+    }
+
+    decoded
 }
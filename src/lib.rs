@@ -1,12 +1,154 @@
+pub mod analyze;
+pub mod api;
 pub mod ast;
 pub mod bundler;
+pub mod cache;
+pub mod cjs;
+pub mod css;
 pub mod error;
+pub mod estree;
 pub mod fs;
+pub mod hooks;
 pub mod lexer;
+pub mod linker;
 pub mod logging;
+pub mod lower;
+pub mod mangler;
+#[cfg(feature = "napi")]
+pub mod node;
+pub mod number;
 pub mod parser;
 pub mod parser_json;
+pub mod paths;
+pub mod printer;
 pub mod renamer;
 pub mod resolver;
 pub mod runtime;
+pub mod sources;
+pub mod stack;
+pub mod strings;
 pub mod tables;
+pub mod terminal;
+pub mod tsconfig;
+pub mod visit;
+
+use crate::bundler::{BundleOptions, Loader};
+use crate::logging::{Log, LogLevel, Msg, MsgId, MsgKind, Source};
+use crate::lower::{LowerOptions, Target, UnsupportedSyntax};
+use crate::mangler::MangleOptions;
+use crate::parser::{DefineValue, DropOptions, JSXOptions, ParseOptions, TypeScriptOptions};
+use std::collections::HashMap;
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Default)]
+pub struct MinifyOptions {
+    // Removes whitespace, newlines, and semicolons that aren't needed
+    pub whitespace: bool,
+
+    // Rewrites code into shorter code that does the same thing
+    pub syntax: bool,
+
+    // Gives local variables shorter names. Top-level variables keep their
+    // names since other scripts may use them.
+    pub identifiers: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransformOptions {
+    pub loader: Loader,
+    pub target: Target,
+    pub minify: MinifyOptions,
+
+    // How "minify.syntax" rewrites the code (see "MangleOptions")
+    pub mangle: MangleOptions,
+
+    pub defines: HashMap<String, DefineValue>,
+
+    // Calls to "console", "debugger" statements, and labeled blocks to remove
+    // (see "DropOptions")
+    pub drop: DropOptions,
+
+    // This only matters with the "jsx" loader
+    pub jsx: JSXOptions,
+
+    // This only matters with the "ts" and "tsx" loaders
+    pub typescript: TypeScriptOptions,
+
+    // The path shown in error messages. This doesn't have to exist on disk.
+    pub sourcefile: String,
+
+    // Keeps the comments in front of statements (see "ParseOptions")
+    pub preserve_comments: bool,
+
+    // Changes how the warnings with these names are reported
+    pub log_overrides: HashMap<MsgId, LogLevel>,
+
+    // What happens to syntax that the target doesn't have and that can't be
+    // lowered (see "UnsupportedSyntax")
+    pub unsupported_syntax: UnsupportedSyntax,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransformResult {
+    // This is empty if there were any errors
+    pub code: String,
+    pub errors: Vec<Msg>,
+    pub warnings: Vec<Msg>,
+}
+
+// Transforms a single file without touching the file system. Imports are left
+// as-is since nothing is bundled.
+pub fn transform(contents: &str, options: TransformOptions) -> TransformResult {
+    let log = Log::with_overrides(options.log_overrides.clone());
+    let source = Source {
+        index: 0,
+        is_stdin: false,
+        absolute_path: options.sourcefile.clone(),
+        pretty_path: options.sourcefile.clone(),
+        contents: contents.to_owned(),
+        ..Source::default()
+    };
+
+    let mut code = String::new();
+
+    // The stages are run in order: lexer and parser, then the mangler and the
+    // renamer if minifying, then the printer
+    let parse_options = ParseOptions {
+        lower: LowerOptions {
+            target: options.target,
+            unsupported_syntax: options.unsupported_syntax,
+        },
+        defines: options.defines,
+        drop: options.drop,
+        jsx: options.jsx,
+        typescript: options.typescript,
+        preserve_comments: options.preserve_comments,
+        ..ParseOptions::default()
+    };
+    if options.loader == Loader::CSS {
+        let stylesheet = css::parse(&log, &source);
+        code = css::print(&stylesheet, options.minify.whitespace);
+    } else if options.loader == Loader::LocalCSS {
+        let (stylesheet, _, _) = bundler::parse_local_css(&log, &source, &parse_options);
+        code = css::print(&stylesheet, options.minify.whitespace);
+    } else if let Some(ast) = bundler::parse_file(&log, &source, options.loader, &parse_options) {
+        let bundle_options = BundleOptions {
+            minify: options.minify,
+            mangle: options.mangle,
+            target: options.target,
+            ..BundleOptions::default()
+        };
+        code = bundler::print_file(ast, &bundle_options);
+    }
+
+    let mut result = TransformResult::default();
+    for msg in log.take_msgs() {
+        match msg.kind {
+            MsgKind::Error => result.errors.push(msg),
+            MsgKind::Warning => result.warnings.push(msg),
+        }
+    }
+    if result.errors.is_empty() {
+        result.code = code;
+    }
+    result
+}
@@ -0,0 +1,2584 @@
+// The linker works on the parse trees of all files in a bundle at once. Files
+// must be parsed with "is_bundling" set so that every top-level statement is
+// in its own part, since parts are the unit that tree shaking keeps or drops.
+
+use crate::ast::{
+    follow_all_symbols, generate_non_unique_name_from_path, Binding, BindingKind, ClauseItem, Decl,
+    Expr, ExprKind, ExprOrStmt, ImportKind, LocalKind, Location, LocationRef, NamespaceAlias,
+    NamespaceSymbol, Path, Reference, Scope, ScopeKind, ScopeTree, Stmt, StmtKind, StrictModeKind,
+    SymbolKind, SymbolMap, AST, INVALID_REF,
+};
+use crate::bundler::{
+    self, BundleOptions, FileOverlay, HmrModuleIds, ImportPathRewriter, OutputFormat,
+};
+use crate::cjs;
+use crate::logging::{Log, MsgId, Source};
+use crate::lower::Feature;
+use crate::mangler;
+use crate::printer::{self, LegalComments, PrintOptions};
+use crate::renamer;
+use crate::stack::ensure_sufficient_stack;
+use crate::visit::Visit;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+// A file in the bundle along with where each of its import paths resolved to.
+// Import paths that aren't in "resolved_imports" are external. They are left
+// in the output as-is.
+//
+// The tree may be shared with the next build, so it's never changed. Changes
+// go in a "FileOverlay" of the tree instead.
+#[derive(Debug, Clone)]
+pub struct LinkerFile<'a> {
+    pub ast: &'a Arc<AST>,
+    pub resolved_imports: HashMap<String, usize>,
+
+    // Importing the file doesn't make it part of the bundle by itself. It's
+    // only included if something it exports is used.
+    pub ignore_if_unused: bool,
+}
+
+impl<'a> LinkerFile<'a> {
+    pub fn resolve(&self, path: &Path) -> Option<usize> {
+        if path.use_source_index {
+            return Some(path.source_index);
+        }
+        self.resolved_imports.get(&path.text).copied()
+    }
+}
+
+// With code splitting, every file in the bundle that is loaded with "import()"
+// is split off into a chunk of its own. The chunk is linked as if the file
+// were an entry point.
+//
+// ESM chunks can import from each other, so the code that more than one of
+// these output files needs goes in a common chunk that they import, once. IIFE
+// chunks can't since the registry loads them asynchronously, so they get a
+// copy of every file they need instead.
+#[derive(Debug, Clone, Default)]
+pub struct Chunks {
+    // The placeholder for the path of the chunk of each file that was split
+    // off, keyed by source index. The bundler replaces it once the chunks
+    // have been named.
+    pub paths: HashMap<usize, String>,
+
+    // IIFE chunks aren't modules, so they register themselves in this global
+    // object and are loaded through it. ESM chunks are loaded natively.
+    pub registry: Option<String>,
+
+    // The output file that has the code of each file, keyed by source index.
+    // A file that isn't in here is copied into every output file that needs
+    // it. That's every file without common chunks, and otherwise the entry
+    // points and the files that were split off, since they have an output
+    // file of their own, and lazy files, since their closure can't be shared.
+    pub chunk_of_file: HashMap<usize, ChunkId>,
+
+    pub common: Vec<CommonChunk>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChunkId {
+    // The output file of an entry point or of a file that was split off
+    Root(usize),
+
+    Common(usize),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CommonChunk {
+    // The entry points and split files whose output files need this chunk.
+    // Every file in the chunk is needed by exactly these.
+    pub roots: Vec<usize>,
+
+    // Stands for the path of the chunk, like the ones in "paths"
+    pub placeholder: String,
+
+    // The symbols that other output files import from the chunk, and the
+    // names they are exported under. See "name_chunk_exports".
+    pub exports: BTreeMap<Reference, String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LinkOutput {
+    pub js: String,
+
+    // How many bytes of the output came from each file, by source index. This
+    // is only filled in when the options ask for a metafile.
+    pub bytes_in_output: Vec<(usize, usize)>,
+
+    // The files that have code in the output, by source index
+    pub source_indices: Vec<usize>,
+
+    // The legal comments that were left out of "js", which only happens when
+    // they go in a separate file
+    pub legal_comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TreeShakingResult {
+    // Whether each file is in the bundle at all
+    pub is_file_live: Vec<bool>,
+
+    // Whether each part of each file is in the bundle
+    pub is_part_live: Vec<Vec<bool>>,
+
+    // The names imported from each file by the other files in the bundle. The
+    // exports of an entry point are all used since they are its public API.
+    pub used_exports: Vec<HashSet<String>>,
+
+    // Whether each file is wrapped in a closure, see "find_wrapped_files"
+    pub is_wrapped: Vec<bool>,
+
+    // Whether each file is an ES6 file that runs when it's first needed, see
+    // "find_lazy_files"
+    pub is_lazy: Vec<bool>,
+}
+
+// A CommonJS file can't share the top-level scope of the bundle since it has
+// its own "module" and "exports", and a file that is loaded with "require()"
+// must only run once it's required. These files are wrapped in a closure
+// instead. Entry points are never wrapped since their "module" and "exports"
+// are the real ones.
+//
+// Without code splitting, a file loaded with "import()" is wrapped the same
+// way, since it has nowhere else to go.
+pub fn find_wrapped_files(
+    files: &[LinkerFile],
+    entry_points: &[usize],
+    split_dynamic_imports: bool,
+) -> Vec<bool> {
+    let mut is_wrapped: Vec<bool> = files
+        .iter()
+        .map(|file| !file.ast.has_es6_syntax() && file.ast.has_commonjs_features())
+        .collect();
+    for file in files {
+        for import_path in file.ast.parts.iter().flat_map(|part| &part.import_paths) {
+            if runs_when_loaded(import_path.kind, split_dynamic_imports) {
+                if let Some(other) = file.resolve(&import_path.path) {
+                    is_wrapped[other] |= !files[other].ast.has_es6_syntax();
+                }
+            }
+        }
+    }
+    for &entry_point in entry_points {
+        is_wrapped[entry_point] = false;
+    }
+    is_wrapped
+}
+
+// An ES6 file that is loaded with "require()" has to run when it's required
+// instead of in the order of the bundle, so it's wrapped in a closure that
+// runs it the first time it's called. So are the ES6 files it imports, since
+// they must not run before it does. Entry points are never wrapped.
+pub fn find_lazy_files(
+    files: &[LinkerFile],
+    is_wrapped: &[bool],
+    entry_points: &[usize],
+    split_dynamic_imports: bool,
+) -> Vec<bool> {
+    let mut is_lazy = vec![false; files.len()];
+    let mut work = vec![];
+    for file in files {
+        for import_path in file.ast.parts.iter().flat_map(|part| &part.import_paths) {
+            if runs_when_loaded(import_path.kind, split_dynamic_imports) {
+                if let Some(other) = file.resolve(&import_path.path) {
+                    work.push(other);
+                }
+            }
+        }
+    }
+    while let Some(source_index) = work.pop() {
+        if is_lazy[source_index] || is_wrapped[source_index] || entry_points.contains(&source_index)
+        {
+            continue;
+        }
+        is_lazy[source_index] = true;
+        let file = &files[source_index];
+        for import_path in file.ast.parts.iter().flat_map(|part| &part.import_paths) {
+            if import_path.kind == ImportKind::Stmt {
+                if let Some(other) = file.resolve(&import_path.path) {
+                    work.push(other);
+                }
+            }
+        }
+    }
+    is_lazy
+}
+
+// Whether an import of a file in the same output only runs the file once the
+// import itself runs. That's "require()", and "import()" unless the file is
+// split off into a chunk of its own.
+fn runs_when_loaded(kind: ImportKind, split_dynamic_imports: bool) -> bool {
+    match kind {
+        ImportKind::Stmt => false,
+        ImportKind::Require => true,
+        ImportKind::Dynamic => !split_dynamic_imports,
+    }
+}
+
+enum Work {
+    File(usize),
+    Part(usize, usize),
+    Export(usize, String),
+
+    // All exports of a file are used, e.g. through "import * as ns" or
+    // "require()"
+    AllExports(usize),
+}
+
+// Starting from the entry points, this follows every import to find the
+// parts that are needed. A part is needed if it has side effects or if a
+// needed part uses something it declares, either directly or through an
+// import. Everything else is left out of the bundle, so an export that no
+// file imports doesn't end up in the output.
+//
+// Files loaded with "import()" aren't followed when splitting since they go
+// in a chunk of their own.
+pub fn tree_shake(
+    files: &[LinkerFile],
+    entry_points: &[usize],
+    split_dynamic_imports: bool,
+) -> TreeShakingResult {
+    let is_wrapped = find_wrapped_files(files, entry_points, split_dynamic_imports);
+    let mut result = TreeShakingResult {
+        is_file_live: vec![false; files.len()],
+        is_part_live: files
+            .iter()
+            .map(|file| vec![false; file.ast.parts.len()])
+            .collect(),
+        used_exports: vec![HashSet::new(); files.len()],
+        is_lazy: find_lazy_files(files, &is_wrapped, entry_points, split_dynamic_imports),
+        is_wrapped,
+    };
+
+    // "import * as ns" and "export * as ns" declare a symbol that stands for
+    // every export of another file
+    let namespace_imports: Vec<HashMap<Reference, usize>> =
+        files.iter().map(collect_namespace_imports).collect();
+
+    let mut has_all_exports = vec![false; files.len()];
+    let mut work = vec![];
+    for &entry_point in entry_points {
+        work.push(Work::AllExports(entry_point));
+
+        // The export statements of an entry point are part of its output
+        let file = &files[entry_point];
+        for (part_index, part) in file.ast.parts.iter().enumerate() {
+            let is_export = part.stmts.iter().any(|stmt| {
+                matches!(
+                    stmt.data.as_ref(),
+                    StmtKind::ExportClause { .. }
+                        | StmtKind::ExportFrom { .. }
+                        | StmtKind::ExportStar { .. }
+                )
+            });
+            if is_export {
+                work.push(Work::Part(entry_point, part_index));
+            }
+        }
+    }
+
+    while let Some(item) = work.pop() {
+        match item {
+            Work::File(source_index) => {
+                if result.is_file_live[source_index] {
+                    continue;
+                }
+                result.is_file_live[source_index] = true;
+                let file = &files[source_index];
+
+                for (part_index, part) in file.ast.parts.iter().enumerate() {
+                    // Importing a file runs it, so everything it imports is
+                    // needed too even if none of the imported names are used.
+                    // Files without side effects are the exception.
+                    let mut keeps_import = false;
+                    for import_path in &part.import_paths {
+                        if import_path.kind == ImportKind::Stmt {
+                            match file.resolve(&import_path.path) {
+                                Some(other) => {
+                                    if result.is_wrapped[other] || result.is_lazy[other] {
+                                        work.push(Work::File(other));
+                                        keeps_import = true;
+                                    } else if !files[other].ignore_if_unused {
+                                        work.push(Work::File(other));
+                                    }
+                                }
+                                None => keeps_import = true,
+                            }
+                        }
+                    }
+
+                    // Import statements of files in the bundle are replaced by
+                    // the linker, but external ones must stay. So must imports
+                    // of wrapped and lazy files, which become calls to their
+                    // closure.
+                    let is_import = part
+                        .stmts
+                        .iter()
+                        .any(|stmt| matches!(stmt.data.as_ref(), StmtKind::Import { .. }));
+                    if keeps_import || (!part.can_be_removed_if_unused && !is_import) {
+                        work.push(Work::Part(source_index, part_index));
+                    }
+                }
+            }
+
+            Work::Part(source_index, part_index) => {
+                if result.is_part_live[source_index][part_index] {
+                    continue;
+                }
+                result.is_part_live[source_index][part_index] = true;
+                work.push(Work::File(source_index));
+                let file = &files[source_index];
+                let part = &file.ast.parts[part_index];
+
+                // "require()" and "import()" only run the file if this part runs
+                for import_path in &part.import_paths {
+                    let is_split = split_dynamic_imports && import_path.kind == ImportKind::Dynamic;
+                    if import_path.kind != ImportKind::Stmt && !is_split {
+                        if let Some(other) = file.resolve(&import_path.path) {
+                            work.push(Work::AllExports(other));
+                        }
+                    }
+                }
+
+                for reference in part.use_count_estimates.keys() {
+                    include_symbol(
+                        files,
+                        &namespace_imports,
+                        source_index,
+                        *reference,
+                        &mut work,
+                    );
+                }
+            }
+
+            Work::Export(source_index, alias) => {
+                if !result.used_exports[source_index].insert(alias.clone()) {
+                    continue;
+                }
+                work.push(Work::File(source_index));
+                let file = &files[source_index];
+
+                match file.ast.named_exports.get(&alias) {
+                    Some(&reference) => include_symbol(
+                        files,
+                        &namespace_imports,
+                        source_index,
+                        reference,
+                        &mut work,
+                    ),
+
+                    // The name may come from an "export * from" statement
+                    None => {
+                        if alias != "default" {
+                            for path in &file.ast.export_stars {
+                                if let Some(other) = file.resolve(path) {
+                                    work.push(Work::Export(other, alias.clone()));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            Work::AllExports(source_index) => {
+                // "export * from" statements can form a cycle
+                if std::mem::replace(&mut has_all_exports[source_index], true) {
+                    continue;
+                }
+                work.push(Work::File(source_index));
+                let file = &files[source_index];
+                for alias in file.ast.named_exports.keys() {
+                    work.push(Work::Export(source_index, alias.clone()));
+                }
+                for path in &file.ast.export_stars {
+                    if let Some(other) = file.resolve(path) {
+                        work.push(Work::AllExports(other));
+                    }
+                }
+            }
+        }
+    }
+
+    result
+}
+
+fn collect_namespace_imports(file: &LinkerFile) -> HashMap<Reference, usize> {
+    let mut result = HashMap::new();
+    for part in &file.ast.parts {
+        for stmt in &part.stmts {
+            let (reference, path) = match stmt.data.as_ref() {
+                StmtKind::Import {
+                    namespace_symbol: NamespaceSymbol::Star { namespace_ref, .. },
+                    path,
+                    ..
+                } => (*namespace_ref, path),
+                StmtKind::ExportStar {
+                    item: Some(item),
+                    path,
+                } => (item.name.reference, path),
+                _ => continue,
+            };
+            if let Some(other) = file.resolve(path) {
+                result.insert(reference, other);
+            }
+        }
+    }
+    result
+}
+
+// Includes whatever a use of "reference" in the given file depends on: the
+// parts in the same file that declare it, or the export of another file that
+// it was imported from
+fn include_symbol(
+    files: &[LinkerFile],
+    namespace_imports: &[HashMap<Reference, usize>],
+    source_index: usize,
+    reference: Reference,
+    work: &mut Vec<Work>,
+) {
+    let file = &files[source_index];
+
+    if let Some(part_indices) = file.ast.top_level_symbol_to_parts.get(&reference) {
+        for &part_index in part_indices {
+            work.push(Work::Part(source_index, part_index as usize));
+        }
+    }
+
+    if let Some(named_import) = file.ast.named_imports.get(&reference) {
+        if let Some(other) = file.resolve(&named_import.import_path) {
+            work.push(Work::Export(other, named_import.alias.clone()));
+        }
+    }
+
+    if let Some(&other) = namespace_imports[source_index].get(&reference) {
+        work.push(Work::AllExports(other));
+    }
+}
+
+// Removes the statements of the parts that tree shaking left out. Only the
+// overlay is changed so the cached tree can be reused by the next build.
+pub fn remove_dead_parts(overlay: &mut FileOverlay, is_part_live: &[bool]) {
+    for (part_index, is_live) in is_part_live.iter().enumerate() {
+        if !is_live && !overlay.part_stmts(part_index).is_empty() {
+            overlay.part_stmts_mut(part_index).clear();
+        }
+    }
+}
+
+// Joins an entry point and every file it imports into one ESM file. Imports
+// between files in the bundle are replaced by the symbols they refer to, so
+// the files can share one top-level scope once colliding names are renamed.
+// The files are printed in the order they would run in, with each file after
+// the files it imports. CommonJS files are wrapped in closures that run when
+// they are first required, and those are printed first.
+//
+// A common chunk is linked the same way, from the entry points that need it.
+// Only the files in the chunk are printed, and the code of the other chunks
+// is imported from them.
+//
+// Returns None if there was an error. The error has already been logged.
+pub fn link(
+    log: &Log,
+    sources: &[Source],
+    files: &[LinkerFile],
+    chunk: ChunkId,
+    chunks: &Chunks,
+    options: &BundleOptions,
+) -> Option<LinkOutput> {
+    link_chunk(log, sources, files, chunk, chunks, options, false).map(|(output, _)| output)
+}
+
+// In the HMR mode, every file that the entry point needs is printed on its own
+// as a CommonJS module and registered under its module ID. Imports of other
+// files in the bundle become "require()" calls of their IDs, which the
+// registry resolves, so any module can be swapped by registering it again.
+// Nothing is tree shaken, since an update may use what is unused now.
+pub fn link_hmr(
+    sources: &[Source],
+    files: &[LinkerFile],
+    entry_point: usize,
+    outbase: &str,
+    options: &BundleOptions,
+) -> LinkOutput {
+    let mut module_ids = HmrModuleIds::new(outbase);
+    let mut included = BTreeSet::new();
+    let mut stack = vec![entry_point];
+    while let Some(source_index) = stack.pop() {
+        if included.insert(source_index) {
+            stack.extend(files[source_index].resolved_imports.values().copied());
+        }
+    }
+
+    // Modules are printed like files that aren't bundled, except for the
+    // paths of the files in the bundle
+    let module_options = BundleOptions {
+        output_format: OutputFormat::Cjs,
+        global_name: String::new(),
+        rewrite_external_paths: None,
+        ..options.clone()
+    };
+    let mut modules = vec![];
+    let mut bytes_in_output = vec![];
+    let mut legal_comments = vec![];
+    for &source_index in &included {
+        let file = &files[source_index];
+        let mut ast = AST::clone(file.ast);
+        let ids: HashMap<String, String> = file
+            .resolved_imports
+            .iter()
+            .map(|(text, &other)| {
+                let id = module_ids.id(&sources[other].absolute_path);
+                (text.clone(), id.to_owned())
+            })
+            .collect();
+        let has_dynamic_imports = ast.parts.iter().any(|part| {
+            part.import_paths.iter().any(|import_path| {
+                import_path.kind == ImportKind::Dynamic && file.resolve(&import_path.path).is_some()
+            })
+        });
+        let mut rewriter = ImportPathRewriter::new(move |text, _| ids.get(text).cloned());
+        let mut to_module_ref = None;
+        if has_dynamic_imports {
+            let promise_ref = cjs::generate_symbol(&mut ast.symbols, source_index, "Promise");
+            ast.symbols[promise_ref].kind = SymbolKind::Unbound;
+            let name = cjs::unused_name(&ast.symbols, source_index, "__toModule");
+            let helper_ref = cjs::generate_symbol(&mut ast.symbols, source_index, &name);
+            cjs::declare_to_module(&mut ast.parts[0].stmts, &mut ast.symbols, helper_ref);
+            rewriter = rewriter.with_dynamic_requires(promise_ref, helper_ref);
+            to_module_ref = Some(helper_ref);
+        }
+
+        // External paths go first, while the paths of the files in the bundle
+        // can still be told apart from them
+        let external_rewriter = options
+            .rewrite_external_paths
+            .as_ref()
+            .map(|rewriter| rewriter.skipping(file.resolved_imports.keys().cloned().collect()));
+        for part in &mut ast.parts {
+            if let Some(external_rewriter) = &external_rewriter {
+                bundler::rewrite_import_paths(&mut part.stmts, external_rewriter);
+            }
+            bundler::rewrite_import_paths(&mut part.stmts, &rewriter);
+        }
+        if options.legal_comments == LegalComments::External {
+            legal_comments.extend(printer::legal_comments(
+                ast.parts.iter().map(|part| part.stmts.as_slice()),
+            ));
+        }
+        let js = bundler::print_module(ast, &module_options, to_module_ref);
+        if options.metafile {
+            bytes_in_output.push((source_index, js.len()));
+        }
+        modules.push((sources[source_index].absolute_path.as_str(), js));
+    }
+
+    let modules: Vec<(&str, &str)> = modules
+        .iter()
+        .map(|(absolute_path, js)| (*absolute_path, js.as_str()))
+        .collect();
+    let js = module_ids.emit_chunk(options, &modules, &sources[entry_point].absolute_path);
+    LinkOutput {
+        js,
+        bytes_in_output,
+        source_indices: included.into_iter().collect(),
+        legal_comments,
+    }
+}
+
+// Returns the symbols that the output file of a chunk imports from common
+// chunks, which have to be known before any output file can be printed. The
+// errors of the chunk are logged by "link" instead.
+pub fn chunk_imports(
+    sources: &[Source],
+    files: &[LinkerFile],
+    chunk: ChunkId,
+    chunks: &Chunks,
+    options: &BundleOptions,
+) -> BTreeSet<Reference> {
+    let log = Log::default();
+    link_chunk(&log, sources, files, chunk, chunks, options, true)
+        .map(|(_, imports)| imports)
+        .unwrap_or_default()
+}
+
+// Each common chunk exports the symbols that other output files import from
+// it. They are exported under the name of the symbol, with a number after it
+// if the chunk already exports that name.
+pub fn name_chunk_exports(
+    files: &[LinkerFile],
+    chunks: &mut Chunks,
+    imports: impl IntoIterator<Item = Reference>,
+) {
+    let mut exports: Vec<BTreeSet<Reference>> = vec![BTreeSet::new(); chunks.common.len()];
+    for reference in imports {
+        if let Some(&ChunkId::Common(index)) = chunks.chunk_of_file.get(&reference.outer) {
+            exports[index].insert(reference);
+        }
+    }
+    for (chunk, exports) in chunks.common.iter_mut().zip(exports) {
+        let mut names = HashSet::new();
+        for reference in exports {
+            let name = &files[reference.outer].ast.symbols[reference].name;
+            let mut alias = name.clone();
+            let mut suffix = 1;
+            while !names.insert(alias.clone()) {
+                suffix += 1;
+                alias = format!("{}{}", name, suffix);
+            }
+            chunk.exports.insert(reference, alias);
+        }
+    }
+}
+
+// With "imports_only" set, this stops once it knows what the chunk imports
+// from common chunks and doesn't print anything
+fn link_chunk(
+    log: &Log,
+    sources: &[Source],
+    files: &[LinkerFile],
+    chunk: ChunkId,
+    chunks: &Chunks,
+    options: &BundleOptions,
+    imports_only: bool,
+) -> Option<(LinkOutput, BTreeSet<Reference>)> {
+    let (roots, entry_point) = match chunk {
+        ChunkId::Root(root) => (vec![root], Some(root)),
+        ChunkId::Common(index) => (chunks.common[index].roots.clone(), None),
+    };
+    let shaking = tree_shake(files, &roots, options.code_splitting);
+    let mut live_order = vec![];
+    let mut visited = HashSet::new();
+    for &root in &roots {
+        compute_file_order(
+            files,
+            &shaking.is_file_live,
+            root,
+            options.code_splitting,
+            &mut visited,
+            &mut live_order,
+        );
+    }
+    let is_in_output = files_in_output(files, &shaking, chunk, chunks, options.code_splitting);
+    let mut order: Vec<usize> = live_order
+        .iter()
+        .copied()
+        .filter(|&source_index| is_in_output[source_index])
+        .collect();
+
+    let is_in_cycle = warn_about_import_cycles(log, sources, files, &order);
+
+    // A wrapped or lazy file only defines its closure, which has to happen
+    // before anything can call it
+    order.sort_by_key(|&source_index| {
+        !shaking.is_wrapped[source_index] && !shaking.is_lazy[source_index]
+    });
+
+    // The code of other chunks can refer to the symbols of any file that is
+    // needed, even the ones this output file doesn't have the code of
+    let mut symbols = SymbolMap::new(files.len());
+    for &source_index in &live_order {
+        symbols.outer[source_index] = files[source_index].ast.symbols.outer[source_index].clone();
+    }
+
+    let star_refs = bind_imports(
+        log,
+        sources,
+        files,
+        &shaking,
+        &live_order,
+        options,
+        &mut symbols,
+    )?;
+    if let (Some(entry_point), OutputFormat::Esm) = (entry_point, options.output_format) {
+        if !check_entry_point_export_stars(log, sources, files, &shaking, entry_point) {
+            return None;
+        }
+    }
+
+    let namespace_objects = link_namespace_objects(
+        sources,
+        files,
+        &shaking,
+        &order,
+        options.code_splitting,
+        &mut symbols,
+    );
+    if let Err(cycle) = follow_all_symbols(&mut symbols) {
+        log.add_error(&Source::default(), 0, cycle.to_string());
+        return None;
+    }
+
+    // The "module" and "exports" of a wrapped file are the arguments of its
+    // closure, and the ones of the entry point are the real ones
+    for &source_index in &order {
+        let ast = files[source_index].ast;
+        if shaking.is_wrapped[source_index]
+            || (Some(source_index) == entry_point && ast.has_commonjs_features())
+        {
+            symbols[ast.exports_ref].must_not_be_renamed = true;
+            symbols[ast.module_ref].must_not_be_renamed = true;
+        }
+    }
+
+    // The symbols that the linker generates go with the symbols of the
+    // first root, whose symbols are always there
+    let first_root = roots[0];
+
+    // The registry is a global, so it's generated like the other globals
+    // that the output format needs
+    let chunk_registry = chunks.registry.as_ref().map(|name| {
+        let reference = cjs::generate_symbol(&mut symbols, first_root, name);
+        symbols[reference].kind = SymbolKind::Unbound;
+        reference
+    });
+
+    let entry_ast = files[first_root].ast;
+    // CommonJS exports are properties with string keys, so the symbols behind
+    // them can be renamed
+    if let (Some(_), OutputFormat::Esm) = (entry_point, options.output_format) {
+        renamer::protect_entry_point_exports(entry_ast, &mut symbols);
+    }
+    for &source_index in &order {
+        renamer::preserve_names(&mut symbols, source_index, &options.preserve_names);
+    }
+
+    // An IIFE without a global name has nowhere to put the exports
+    let keeps_exports =
+        options.output_format != OutputFormat::Iife || !options.global_name.is_empty();
+    let mut parts: Vec<Cow<[Stmt]>> = vec![];
+    let mut interop = Interop {
+        is_wrapped: shaking.is_wrapped.clone(),
+        is_lazy: shaking.is_lazy.clone(),
+        needs_tdz: (0..files.len())
+            .map(|source_index| {
+                is_in_cycle[source_index]
+                    && !shaking.is_lazy[source_index]
+                    && options.target.supports(Feature::LetConst)
+            })
+            .collect(),
+        source_index: first_root,
+        promise_ref: None,
+        commonjs_ref: None,
+        to_module_ref: None,
+        esm_ref: None,
+        re_export_ref: None,
+        init_refs: HashMap::new(),
+        namespace_objects,
+        star_refs,
+    };
+    for &source_index in &order {
+        if interop.is_lazy[source_index] {
+            let name = format!(
+                "init_{}",
+                generate_non_unique_name_from_path(&sources[source_index].pretty_path)
+            );
+            let init_ref = generate_helper(&mut symbols, source_index, &name);
+            interop.init_refs.insert(source_index, init_ref);
+        }
+    }
+
+    // The IIFE format moves every statement into one closure, so the bytes
+    // each file contributes are measured by printing its statements again
+    // on their own. This copy of them is only made for the metafile.
+    let mut measured_files: Vec<(usize, Vec<Stmt>)> = vec![];
+    let mut entry_first_part = 0;
+
+    // Namespace objects aren't shared between output files, so the ones of
+    // files in other chunks go first
+    let mut other_namespaces: Vec<usize> = interop
+        .namespace_objects
+        .keys()
+        .copied()
+        .filter(|&source_index| !is_in_output[source_index])
+        .collect();
+    other_namespaces.sort_unstable();
+    for &source_index in &other_namespaces {
+        let exports = collect_all_exports(files, &interop.is_wrapped, source_index);
+        parts.push(Cow::Owned(vec![cjs::namespace_object(
+            0,
+            interop.namespace_objects[&source_index],
+            exports,
+        )]));
+    }
+
+    for &source_index in &order {
+        let file = &files[source_index];
+        if !options.code_splitting {
+            interop.prepare_dynamic_imports(
+                file,
+                &shaking.is_part_live[source_index],
+                &mut symbols,
+            );
+        }
+        let rewriter = import_path_rewriter(files, file, chunks, chunk_registry, &interop);
+        let external_rewriter = options
+            .rewrite_external_paths
+            .as_ref()
+            .map(|rewriter| rewriter.skipping(file.resolved_imports.keys().cloned().collect()));
+        let first_part = parts.len();
+        if Some(source_index) == entry_point {
+            entry_first_part = first_part;
+        }
+
+        // The namespace object only has getters, so it can come before the
+        // exports are declared
+        if let Some(&namespace_ref) = interop.namespace_objects.get(&source_index) {
+            let exports = collect_all_exports(files, &interop.is_wrapped, source_index);
+            parts.push(Cow::Owned(vec![cjs::namespace_object(
+                0,
+                namespace_ref,
+                exports,
+            )]));
+        }
+        let body_first_part = parts.len();
+        let mut overlay = FileOverlay::new(file.ast.clone());
+        for (part_index, part) in file.ast.parts.iter().enumerate() {
+            if !shaking.is_part_live[source_index][part_index] {
+                continue;
+            }
+            if part.stmts.iter().any(needs_conversion) {
+                let mut stmts = vec![];
+                for stmt in &part.stmts {
+                    convert_stmt(
+                        files,
+                        source_index,
+                        Some(source_index) == entry_point && keeps_exports,
+                        stmt,
+                        &mut symbols,
+                        &mut interop,
+                        &mut stmts,
+                    );
+                }
+                *overlay.part_stmts_mut(part_index) = stmts;
+            }
+
+            // External paths go first, while the paths of the files in the
+            // bundle can still be told apart from them
+            if let Some(rewriter) = &external_rewriter {
+                let has_external_paths = part
+                    .import_paths
+                    .iter()
+                    .any(|import_path| file.resolve(&import_path.path).is_none());
+                if has_external_paths {
+                    bundler::rewrite_import_paths(overlay.part_stmts_mut(part_index), rewriter);
+                }
+            }
+            if let Some(rewriter) = &rewriter {
+                let needs_rewrite = part.import_paths.iter().any(|import_path| {
+                    let other = match file.resolve(&import_path.path) {
+                        Some(other) => other,
+                        None => return false,
+                    };
+                    match import_path.kind {
+                        ImportKind::Dynamic if options.code_splitting => {
+                            chunks.paths.contains_key(&other)
+                        }
+                        ImportKind::Stmt => false,
+                        _ => interop.is_wrapped[other] || interop.is_lazy[other],
+                    }
+                });
+                if needs_rewrite {
+                    bundler::rewrite_import_paths(overlay.part_stmts_mut(part_index), rewriter);
+                }
+            }
+            if options.minify.syntax {
+                mangler::mangle_stmts(overlay.part_stmts_mut(part_index), &options.mangle);
+            }
+            parts.push(match overlay.take_part_stmts(part_index) {
+                Some(stmts) => Cow::Owned(stmts),
+                None => Cow::Borrowed(part.stmts.as_slice()),
+            });
+        }
+
+        // Everything that is left of a wrapped file goes in its closure
+        if interop.is_wrapped[source_index] {
+            let stmts = parts
+                .drain(first_part..)
+                .flat_map(|stmts| stmts.into_owned())
+                .collect();
+            let ast = file.ast;
+            let wrapper = cjs::wrap_commonjs(
+                interop.wrap_helper_ref(&mut symbols),
+                ast.wrapper_ref,
+                ast.exports_ref,
+                ast.module_ref,
+                stmts,
+            );
+            parts.push(Cow::Owned(vec![wrapper]));
+        } else if interop.is_lazy[source_index] {
+            let stmts = parts
+                .drain(body_first_part..)
+                .flat_map(|stmts| stmts.into_owned())
+                .collect();
+            let wrapper = cjs::wrap_esm(
+                interop.esm_helper_ref(&mut symbols),
+                interop.init_refs[&source_index],
+                stmts,
+            );
+            parts.push(Cow::Owned(wrapper));
+        }
+        if options.metafile {
+            let stmts = parts[first_part..]
+                .iter()
+                .flat_map(|stmts| stmts.iter().cloned())
+                .collect();
+            measured_files.push((source_index, stmts));
+        }
+    }
+
+    let mut generated_refs = vec![];
+    let (imports, import_stmts) = if chunks.common.is_empty() {
+        (BTreeSet::new(), vec![])
+    } else {
+        link_common_chunks(
+            files,
+            chunk,
+            chunks,
+            &is_in_output,
+            &mut parts,
+            &symbols,
+            &mut interop,
+        )
+    };
+    if imports_only {
+        return Some((LinkOutput::default(), imports));
+    }
+    generated_refs.extend(imports.iter().copied());
+
+    if options.output_format == OutputFormat::Esm {
+        if let Some(stmt) = copy_namespace_exports(
+            files,
+            &interop.is_wrapped,
+            &mut parts[entry_first_part..],
+            &mut symbols,
+            &mut generated_refs,
+        ) {
+            parts.push(Cow::Owned(vec![stmt]));
+        }
+    }
+
+    let helpers = interop.helpers(&mut symbols);
+    if !helpers.is_empty() {
+        parts.insert(0, Cow::Owned(helpers));
+    }
+    if !import_stmts.is_empty() {
+        parts.insert(0, Cow::Owned(import_stmts));
+    }
+
+    // The imports of external files and the exports of the entry point are
+    // the only module syntax left at this point
+    if options.output_format != OutputFormat::Esm {
+        symbols[entry_ast.exports_ref].must_not_be_renamed = true;
+        if options.output_format == OutputFormat::Cjs {
+            symbols[entry_ast.module_ref].must_not_be_renamed = true;
+        }
+        cjs::convert_to_output_format(
+            parts.iter_mut().map(|stmts| stmts.to_mut()),
+            &mut symbols,
+            entry_ast.exports_ref,
+            |reference| {
+                let named_imports = &files[reference.outer].ast.named_imports;
+                named_imports
+                    .get(&reference)
+                    .map(|named| named.namespace_ref)
+            },
+            options,
+            order.iter().all(|&source_index| {
+                files[source_index]
+                    .ast
+                    .module_scope()
+                    .strict_mode
+                    .is_strict()
+            }),
+            &mut interop.to_module_ref,
+        );
+    }
+
+    // The generated top-level symbols come first so they keep their names
+    generated_refs.extend(
+        other_namespaces
+            .iter()
+            .chain(&order)
+            .filter_map(|source_index| interop.namespace_objects.get(source_index).copied()),
+    );
+    let generated_scope = interop.generated_scope(files, &order, generated_refs);
+    let scope_trees: Vec<&ScopeTree> = std::iter::once(&generated_scope)
+        .chain(
+            order
+                .iter()
+                .map(|&source_index| &files[source_index].ast.scopes),
+        )
+        .collect();
+
+    // The files in other chunks don't keep their names from being used,
+    // except for the symbols that are imported from them
+    let reserved_names = renamer::compute_reserved_names_of(&scope_trees, &symbols, |reference| {
+        is_in_output[reference.outer] || !is_declared_in_file(files, reference)
+    });
+    let renamer = if options.minify.identifiers {
+        renamer::minify_all_symbols(&reserved_names, &scope_trees, &symbols)
+    } else {
+        renamer::rename_all_symbols(&reserved_names, &scope_trees, &symbols)
+    };
+
+    let print_options = PrintOptions {
+        minify_whitespace: options.minify.whitespace,
+        minify_syntax: options.minify.syntax,
+        target: options.target,
+        legal_comments: options.legal_comments,
+    };
+    let bytes_in_output = measured_files
+        .iter()
+        .map(|(source_index, stmts)| {
+            let js = printer::print_bundle(
+                &symbols,
+                "",
+                std::iter::once(stmts.as_slice()),
+                &renamer,
+                print_options.clone(),
+            );
+            (*source_index, js.len())
+        })
+        .collect();
+    let hash_bang = match entry_point {
+        Some(_) => entry_ast.hash_bang.as_str(),
+        None => "",
+    };
+    let js = printer::print_bundle(
+        &symbols,
+        hash_bang,
+        parts.iter().map(|stmts| stmts.as_ref()),
+        &renamer,
+        print_options,
+    );
+    let legal_comments = if options.legal_comments == LegalComments::External {
+        printer::legal_comments(parts.iter().map(|stmts| stmts.as_ref()))
+    } else {
+        vec![]
+    };
+    let output = LinkOutput {
+        js,
+        bytes_in_output,
+        source_indices: order,
+        legal_comments,
+    };
+    Some((output, imports))
+}
+
+// The files that the output file of a chunk has the code of. That's the files
+// in the chunk and the ones that are copied into every output file that needs
+// them, see "Chunks".
+fn files_in_output(
+    files: &[LinkerFile],
+    shaking: &TreeShakingResult,
+    chunk: ChunkId,
+    chunks: &Chunks,
+    split_dynamic_imports: bool,
+) -> Vec<bool> {
+    let mut work: Vec<usize> = (0..files.len())
+        .filter(|&source_index| {
+            shaking.is_file_live[source_index]
+                && chunks.chunk_of_file.get(&source_index) == Some(&chunk)
+        })
+        .collect();
+    if let ChunkId::Root(root) = chunk {
+        if !chunks.chunk_of_file.contains_key(&root) {
+            work.push(root);
+        }
+    }
+    let mut result = vec![false; files.len()];
+    while let Some(source_index) = work.pop() {
+        if std::mem::replace(&mut result[source_index], true) {
+            continue;
+        }
+        let file = &files[source_index];
+        for import_path in file.ast.parts.iter().flat_map(|part| &part.import_paths) {
+            if import_path.kind == ImportKind::Dynamic && split_dynamic_imports {
+                continue;
+            }
+            if let Some(other) = file.resolve(&import_path.path) {
+                if shaking.is_file_live[other] && !chunks.chunk_of_file.contains_key(&other) {
+                    work.push(other);
+                }
+            }
+        }
+    }
+    result
+}
+
+// Whether the symbol comes from the file itself rather than the linker, which
+// generates symbols anew every time it runs
+fn is_declared_in_file(files: &[LinkerFile], reference: Reference) -> bool {
+    reference.inner < files[reference.outer].ast.symbols.outer[reference.outer].len()
+}
+
+// Connects the output file of a chunk to the common chunks. It imports what
+// its code uses from them, and a common chunk exports what other output files
+// import from it, see "name_chunk_exports". Returns the imported symbols and
+// the import statements, which go before everything else.
+//
+// Only symbols that the files declare can be imported. The linker generates
+// the rest again for every output file, so a namespace object is made in each
+// output file that needs it, and so is the variable of an "export * from" of a
+// wrapped file.
+fn link_common_chunks(
+    files: &[LinkerFile],
+    chunk: ChunkId,
+    chunks: &Chunks,
+    is_in_output: &[bool],
+    parts: &mut Vec<Cow<[Stmt]>>,
+    symbols: &SymbolMap,
+    interop: &mut Interop,
+) -> (BTreeSet<Reference>, Vec<Stmt>) {
+    let mut collector = ReferenceCollector::default();
+    for stmts in parts.iter() {
+        collector.visit_stmts(stmts);
+    }
+
+    let mut imports = BTreeSet::new();
+    let mut stars = BTreeSet::new();
+    let star_keys: HashMap<Reference, (usize, Location)> = interop
+        .star_refs
+        .iter()
+        .map(|(&key, &reference)| (reference, key))
+        .collect();
+    for reference in collector.references {
+        let reference = match symbols[reference].link {
+            INVALID_REF => reference,
+            link => link,
+        };
+        if let Some(alias) = &symbols[reference].namespace_alias {
+            if let Some(&key) = star_keys.get(&alias.namespace_ref) {
+                if !is_in_output[key.0] {
+                    stars.insert(key);
+                }
+            }
+        } else if !is_in_output[reference.outer] && is_declared_in_file(files, reference) {
+            imports.insert(reference);
+        }
+    }
+
+    // The variables of "export * from" statements in other chunks
+    let mut star_stmts = vec![];
+    for &(source_index, location) in &stars {
+        let file = &files[source_index];
+        let path = file
+            .ast
+            .export_stars
+            .iter()
+            .find(|path| path.loc == location);
+        let other = match path.and_then(|path| file.resolve(path)) {
+            Some(other) => other,
+            None => continue,
+        };
+        let wrapper_ref = files[other].ast.wrapper_ref;
+        if !is_in_output[other] {
+            imports.insert(wrapper_ref);
+        }
+        let namespace_ref = interop.star_refs[&(source_index, location)];
+        star_stmts.push(star_namespace(location, namespace_ref, wrapper_ref));
+    }
+    interop
+        .star_refs
+        .retain(|key, _| is_in_output[key.0] || stars.contains(key));
+    parts.insert(0, Cow::Owned(star_stmts));
+
+    // Importing a file runs it, so its chunk is imported even if nothing
+    // is used from it
+    let mut items_of_chunk: BTreeMap<usize, Vec<ClauseItem>> = BTreeMap::new();
+    for (source_index, file) in files.iter().enumerate() {
+        if !is_in_output[source_index] {
+            continue;
+        }
+        for import_path in file.ast.parts.iter().flat_map(|part| &part.import_paths) {
+            if import_path.kind != ImportKind::Stmt {
+                continue;
+            }
+            let other = file.resolve(&import_path.path);
+            if let Some(&ChunkId::Common(index)) = other.and_then(|other| {
+                chunks
+                    .chunk_of_file
+                    .get(&other)
+                    .filter(|_| !is_in_output[other])
+            }) {
+                items_of_chunk.entry(index).or_default();
+            }
+        }
+    }
+    for &reference in &imports {
+        if let Some(&ChunkId::Common(index)) = chunks.chunk_of_file.get(&reference.outer) {
+            let items = items_of_chunk.entry(index).or_default();
+
+            // The names of the exports are only known once every output file
+            // has been linked once
+            if let Some(alias) = chunks.common[index].exports.get(&reference) {
+                items.push(ClauseItem {
+                    alias: alias.clone(),
+                    alias_location: 0,
+                    name: LocationRef { loc: 0, reference },
+                });
+            }
+        }
+    }
+    let import_stmts = items_of_chunk
+        .into_iter()
+        .map(|(index, items)| Stmt {
+            location: 0,
+            data: Box::new(StmtKind::Import {
+                namespace_symbol: NamespaceSymbol::Clause { items },
+                default_name: None,
+                path: Path {
+                    loc: 0,
+                    text: chunks.common[index].placeholder.clone(),
+                    use_source_index: false,
+                    source_index: 0,
+                },
+            }),
+        })
+        .collect();
+
+    if let ChunkId::Common(index) = chunk {
+        let items: Vec<ClauseItem> = chunks.common[index]
+            .exports
+            .iter()
+            .map(|(&reference, alias)| ClauseItem {
+                alias: alias.clone(),
+                alias_location: 0,
+                name: LocationRef { loc: 0, reference },
+            })
+            .collect();
+        if !items.is_empty() {
+            parts.push(Cow::Owned(vec![Stmt {
+                location: 0,
+                data: Box::new(StmtKind::ExportClause { items }),
+            }]));
+        }
+    }
+
+    (imports, import_stmts)
+}
+
+// Collects every symbol that the statements refer to. Declarations aren't
+// included since they only declare symbols of the file they are in.
+#[derive(Default)]
+struct ReferenceCollector {
+    references: Vec<Reference>,
+}
+
+impl Visit for ReferenceCollector {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if let StmtKind::ExportClause { items } = stmt.data.as_ref() {
+            self.references
+                .extend(items.iter().map(|item| item.name.reference));
+        }
+        self.walk_stmt(stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        ensure_sufficient_stack(|| {
+            if let ExprKind::Identifier { reference } | ExprKind::ImportIdentifier { reference } =
+                expr.data.as_ref()
+            {
+                self.references.push(*reference);
+            }
+            self.walk_expr(expr);
+        })
+    }
+}
+
+// Returns None if the file doesn't import any chunks or wrapped files
+fn import_path_rewriter(
+    files: &[LinkerFile],
+    file: &LinkerFile,
+    chunks: &Chunks,
+    chunk_registry: Option<Reference>,
+    interop: &Interop,
+) -> Option<ImportPathRewriter> {
+    let is_wrapped = &interop.is_wrapped;
+    let chunk_paths: HashMap<String, String> = file
+        .resolved_imports
+        .iter()
+        .filter_map(|(text, other)| Some((text.clone(), chunks.paths.get(other)?.clone())))
+        .collect();
+    let wrappers: HashMap<String, Reference> = file
+        .resolved_imports
+        .iter()
+        .filter(|(_, &other)| is_wrapped[other])
+        .map(|(text, &other)| (text.clone(), files[other].ast.wrapper_ref))
+        .collect();
+    let esm_wrappers: HashMap<String, (Reference, Reference)> = file
+        .resolved_imports
+        .iter()
+        .filter_map(|(text, other)| {
+            let init_ref = *interop.init_refs.get(other)?;
+            let namespace_ref = *interop.namespace_objects.get(other)?;
+            Some((text.clone(), (init_ref, namespace_ref)))
+        })
+        .collect();
+    if chunk_paths.is_empty() && wrappers.is_empty() && esm_wrappers.is_empty() {
+        return None;
+    }
+    let mut rewriter = ImportPathRewriter::new(move |text, kind| {
+        if kind != ImportKind::Dynamic {
+            return None;
+        }
+        chunk_paths.get(text).cloned()
+    })
+    .with_wrappers(wrappers)
+    .with_esm_wrappers(esm_wrappers);
+    if let Some(registry) = chunk_registry {
+        rewriter = rewriter.with_chunk_registry(registry);
+    }
+    if let Some(promise_ref) = interop.promise_ref {
+        rewriter = rewriter.with_dynamic_imports(promise_ref, interop.to_module_ref);
+    }
+    Some(rewriter)
+}
+
+// The helpers that wrapped and lazy files need. They are generated in the
+// symbols of the entry point, and only once something uses them.
+struct Interop {
+    is_wrapped: Vec<bool>,
+    is_lazy: Vec<bool>,
+
+    // Files in an import cycle can see the exports of another file in the
+    // cycle before they are initialized. Their "export default" becomes a
+    // "let" so that reading it too early is still an error.
+    needs_tdz: Vec<bool>,
+
+    source_index: usize,
+    promise_ref: Option<Reference>,
+    commonjs_ref: Option<Reference>,
+    to_module_ref: Option<Reference>,
+    esm_ref: Option<Reference>,
+    re_export_ref: Option<Reference>,
+
+    // The closure of each lazy file, keyed by source index
+    init_refs: HashMap<usize, Reference>,
+
+    // See "link_namespace_objects"
+    namespace_objects: HashMap<usize, Reference>,
+
+    // See "generate_star_namespaces"
+    star_refs: BTreeMap<(usize, Location), Reference>,
+}
+
+impl Interop {
+    // "import()" of a wrapped or lazy file, which is only possible without
+    // code splitting, resolves to its exports through the global "Promise".
+    // The helpers for this are generated before the paths of the file are
+    // rewritten.
+    fn prepare_dynamic_imports(
+        &mut self,
+        file: &LinkerFile,
+        is_part_live: &[bool],
+        symbols: &mut SymbolMap,
+    ) {
+        for (part_index, part) in file.ast.parts.iter().enumerate() {
+            if !is_part_live[part_index] {
+                continue;
+            }
+            for import_path in &part.import_paths {
+                let other = match file.resolve(&import_path.path) {
+                    Some(other) if import_path.kind == ImportKind::Dynamic => other,
+                    _ => continue,
+                };
+                if self.is_wrapped[other] {
+                    self.import_helper_ref(symbols);
+                } else if !self.is_lazy[other] {
+                    continue;
+                }
+                if self.promise_ref.is_none() {
+                    let reference = cjs::generate_symbol(symbols, self.source_index, "Promise");
+                    symbols[reference].kind = SymbolKind::Unbound;
+                    self.promise_ref = Some(reference);
+                }
+            }
+        }
+    }
+
+    fn wrap_helper_ref(&mut self, symbols: &mut SymbolMap) -> Reference {
+        let source_index = self.source_index;
+        *self
+            .commonjs_ref
+            .get_or_insert_with(|| generate_helper(symbols, source_index, "__commonJS"))
+    }
+
+    fn import_helper_ref(&mut self, symbols: &mut SymbolMap) -> Reference {
+        let source_index = self.source_index;
+        *self
+            .to_module_ref
+            .get_or_insert_with(|| generate_helper(symbols, source_index, "__toModule"))
+    }
+
+    fn esm_helper_ref(&mut self, symbols: &mut SymbolMap) -> Reference {
+        let source_index = self.source_index;
+        *self
+            .esm_ref
+            .get_or_insert_with(|| generate_helper(symbols, source_index, "__esm"))
+    }
+
+    fn re_export_helper_ref(&mut self, symbols: &mut SymbolMap) -> Reference {
+        let source_index = self.source_index;
+        *self
+            .re_export_ref
+            .get_or_insert_with(|| generate_helper(symbols, source_index, "__reExport"))
+    }
+
+    fn helpers(&self, symbols: &mut SymbolMap) -> Vec<Stmt> {
+        let mut stmts = vec![];
+        if let Some(helper_ref) = self.commonjs_ref {
+            stmts.push(cjs::commonjs_helper(symbols, helper_ref));
+        }
+        if let Some(helper_ref) = self.to_module_ref {
+            stmts.push(cjs::to_module_helper(symbols, helper_ref));
+        }
+        if let Some(helper_ref) = self.esm_ref {
+            stmts.push(cjs::esm_helper(symbols, helper_ref));
+        }
+        if let Some(helper_ref) = self.re_export_ref {
+            stmts.push(cjs::re_export_helper(symbols, helper_ref));
+        }
+        stmts
+    }
+
+    // The helpers, the closures of wrapped files, and the other symbols that
+    // the linker generates aren't declared in any file, so they are renamed as if they were declared in a scope of their
+    // own
+    fn generated_scope(
+        &self,
+        files: &[LinkerFile],
+        order: &[usize],
+        generated_refs: Vec<Reference>,
+    ) -> ScopeTree {
+        let wrapper_refs = order.iter().filter_map(|&source_index| {
+            if self.is_wrapped[source_index] {
+                Some(files[source_index].ast.wrapper_ref)
+            } else {
+                self.init_refs.get(&source_index).copied()
+            }
+        });
+        let mut scope = Scope::new(ScopeKind::Entry, StrictModeKind::Sloppy);
+        scope.generated = self
+            .commonjs_ref
+            .into_iter()
+            .chain(self.to_module_ref)
+            .chain(self.esm_ref)
+            .chain(self.re_export_ref)
+            .chain(wrapper_refs)
+            .chain(self.star_refs.values().copied())
+            .chain(generated_refs)
+            .collect();
+        ScopeTree::new(scope)
+    }
+}
+
+// Unlike the other generated symbols these are renamed, since they are put
+// in a scope
+fn generate_helper(symbols: &mut SymbolMap, source_index: usize, name: &str) -> Reference {
+    let reference = cjs::generate_symbol(symbols, source_index, name);
+    symbols[reference].must_not_be_renamed = false;
+    reference
+}
+
+// An ES6 export can only refer to a variable, but the names imported from a
+// wrapped file are properties of its namespace. The entry point exports a copy
+// of each of these instead, which is taken once the entry point has run:
+//
+//   import {a} from './cjs'   =>   var import_cjs = __toModule(require_cjs());
+//   export {a}                     var a = import_cjs.a;
+//                                  export {a};
+//
+// Returns the declaration of the copies, if there are any.
+fn copy_namespace_exports(
+    files: &[LinkerFile],
+    is_wrapped: &[bool],
+    parts: &mut [Cow<[Stmt]>],
+    symbols: &mut SymbolMap,
+    generated_refs: &mut Vec<Reference>,
+) -> Option<Stmt> {
+    let mut copies: HashMap<Reference, Reference> = HashMap::new();
+    let mut decls = vec![];
+    for stmts in parts {
+        let needs_copy = stmts.iter().any(|stmt| match stmt.data.as_ref() {
+            StmtKind::ExportClause { items } => items
+                .iter()
+                .any(|item| reads_namespace(files, is_wrapped, symbols, item.name.reference)),
+            _ => false,
+        });
+        if !needs_copy {
+            continue;
+        }
+        for stmt in stmts.to_mut() {
+            let items = match stmt.data.as_mut() {
+                StmtKind::ExportClause { items } => items,
+                _ => continue,
+            };
+            for item in items {
+                let reference = item.name.reference;
+                if !reads_namespace(files, is_wrapped, symbols, reference) {
+                    continue;
+                }
+                item.name.reference = *copies.entry(reference).or_insert_with(|| {
+                    let name = symbols[reference].name.clone();
+                    let copy_ref = generate_helper(symbols, item.name.reference.outer, &name);
+                    generated_refs.push(copy_ref);
+                    decls.push(Decl {
+                        binding: Binding {
+                            location: item.name.loc,
+                            data: Box::new(BindingKind::Identifier {
+                                reference: copy_ref,
+                            }),
+                        },
+                        value: Some(Expr {
+                            location: item.name.loc,
+                            data: Box::new(ExprKind::ImportIdentifier { reference }),
+                        }),
+                    });
+                    copy_ref
+                });
+            }
+        }
+    }
+    if decls.is_empty() {
+        return None;
+    }
+    Some(Stmt {
+        location: decls[0].binding.location,
+        data: Box::new(StmtKind::Local {
+            decls,
+            kind: LocalKind::Var,
+            is_export: false,
+            was_ts_import_equals_in_namespace: false,
+        }),
+    })
+}
+
+// Whether the symbol is an import from a wrapped file, possibly through the
+// re-exports of other files. This includes the names that only an "export *
+// from" of a wrapped file has.
+fn reads_namespace(
+    files: &[LinkerFile],
+    is_wrapped: &[bool],
+    symbols: &SymbolMap,
+    mut reference: Reference,
+) -> bool {
+    while symbols[reference].link != INVALID_REF {
+        reference = symbols[reference].link;
+    }
+    if symbols[reference].namespace_alias.is_some() {
+        return true;
+    }
+    let file = &files[reference.outer];
+    file.ast
+        .named_imports
+        .get(&reference)
+        .is_some_and(|named_import| {
+            file.resolve(&named_import.import_path)
+                .is_some_and(|other| is_wrapped[other])
+        })
+}
+
+// "import * as ns" and "export * as ns" of a bundled ES6 file need an object
+// with a property for each export, and so does loading a lazy file with
+// "require()" or "import()".
+// Each file gets one namespace object, which every namespace symbol for the
+// file is linked to. Returns the namespace object of each file, keyed by
+// source index.
+fn link_namespace_objects(
+    sources: &[Source],
+    files: &[LinkerFile],
+    shaking: &TreeShakingResult,
+    order: &[usize],
+    split_dynamic_imports: bool,
+    symbols: &mut SymbolMap,
+) -> HashMap<usize, Reference> {
+    let mut result = HashMap::new();
+    let mut namespace_object = |symbols: &mut SymbolMap, other: usize| {
+        *result.entry(other).or_insert_with(|| {
+            let name = format!(
+                "{}_exports",
+                generate_non_unique_name_from_path(&sources[other].pretty_path)
+            );
+            generate_helper(symbols, other, &name)
+        })
+    };
+    for &source_index in order {
+        let file = &files[source_index];
+        for (part_index, part) in file.ast.parts.iter().enumerate() {
+            if !shaking.is_part_live[source_index][part_index] {
+                continue;
+            }
+            for import_path in &part.import_paths {
+                if runs_when_loaded(import_path.kind, split_dynamic_imports) {
+                    if let Some(other) = file.resolve(&import_path.path) {
+                        if shaking.is_lazy[other] {
+                            namespace_object(symbols, other);
+                        }
+                    }
+                }
+            }
+            for stmt in &part.stmts {
+                let (reference, path) = match stmt.data.as_ref() {
+                    StmtKind::Import {
+                        namespace_symbol: NamespaceSymbol::Star { namespace_ref, .. },
+                        path,
+                        ..
+                    } if symbols[*namespace_ref].use_count_estimate > 0 => (*namespace_ref, path),
+                    StmtKind::ExportStar {
+                        item: Some(item),
+                        path,
+                    } => (item.name.reference, path),
+                    _ => continue,
+                };
+                let other = match file.resolve(path) {
+                    Some(other) if !shaking.is_wrapped[other] => other,
+                    _ => continue,
+                };
+                symbols[reference].link = namespace_object(symbols, other);
+            }
+        }
+    }
+    result
+}
+
+// The names that "export * from" a wrapped file re-exports aren't known until
+// it runs. A name that no ES6 file exports is read off of the exports of the
+// wrapped file instead, which are kept in a variable:
+//
+//   export * from './cjs'   =>   var import_cjs = require_cjs();
+//
+// Returns that variable for each of these statements, keyed by the source
+// index of the file and the location of the path.
+fn generate_star_namespaces(
+    files: &[LinkerFile],
+    is_wrapped: &[bool],
+    order: &[usize],
+    symbols: &mut SymbolMap,
+) -> BTreeMap<(usize, Location), Reference> {
+    let mut result = BTreeMap::new();
+    for &source_index in order {
+        let file = &files[source_index];
+        for path in &file.ast.export_stars {
+            if file.resolve(path).is_some_and(|other| is_wrapped[other]) {
+                let name = format!("import_{}", generate_non_unique_name_from_path(&path.text));
+                let reference = generate_helper(symbols, source_index, &name);
+                result.insert((source_index, path.loc), reference);
+            }
+        }
+    }
+    result
+}
+
+// "var import_cjs = require_cjs();"
+fn star_namespace(location: Location, namespace_ref: Reference, wrapper_ref: Reference) -> Stmt {
+    Stmt {
+        location,
+        data: Box::new(StmtKind::Local {
+            decls: vec![Decl {
+                binding: Binding {
+                    location,
+                    data: Box::new(BindingKind::Identifier {
+                        reference: namespace_ref,
+                    }),
+                },
+                value: Some(cjs::call_wrapper(location, wrapper_ref)),
+            }],
+            kind: LocalKind::Var,
+            is_export: false,
+            was_ts_import_equals_in_namespace: false,
+        }),
+    }
+}
+
+// Import cycles are allowed, but a file in a cycle can run before a file that
+// it imports, which is often a mistake. This warns once for every group of
+// files that import each other (the strongly connected components of the
+// import graph, found with Tarjan's algorithm), showing one of the cycles in
+// the group. Returns whether each file is in a cycle.
+fn warn_about_import_cycles(
+    log: &Log,
+    sources: &[Source],
+    files: &[LinkerFile],
+    order: &[usize],
+) -> Vec<bool> {
+    let mut finder = CycleFinder {
+        edges: vec![vec![]; files.len()],
+        index: vec![None; files.len()],
+        low_link: vec![0; files.len()],
+        stack: vec![],
+        is_on_stack: vec![false; files.len()],
+        next_index: 0,
+        components: vec![],
+    };
+    let is_live: HashSet<usize> = order.iter().copied().collect();
+    for &source_index in order {
+        let file = &files[source_index];
+        for import_path in file.ast.parts.iter().flat_map(|part| &part.import_paths) {
+            if import_path.kind != ImportKind::Dynamic {
+                if let Some(other) = file.resolve(&import_path.path) {
+                    if is_live.contains(&other) {
+                        finder.edges[source_index].push((other, import_path.path.loc));
+                    }
+                }
+            }
+        }
+    }
+
+    // Starting from the entry point makes the first file of each component
+    // the one that the rest of the bundle imports it through
+    for &source_index in order.iter().rev() {
+        if finder.index[source_index].is_none() {
+            finder.visit(source_index);
+        }
+    }
+
+    let mut is_in_cycle = vec![false; files.len()];
+    for component in finder.components.iter().rev() {
+        let start = component[component.len() - 1];
+        let (path, location) = match finder.shortest_cycle(start, component) {
+            Some(cycle) => cycle,
+            None => continue,
+        };
+        for &source_index in component {
+            is_in_cycle[source_index] = true;
+        }
+        let names: Vec<String> = path
+            .iter()
+            .chain(std::iter::once(&start))
+            .map(|&source_index| format!("{:?}", sources[source_index].pretty_path))
+            .collect();
+        let last = path[path.len() - 1];
+        log.add_id_warning(
+            MsgId::ImportCycle,
+            &sources[last],
+            location..location,
+            format!("Import cycle: {}", names.join(" -> ")),
+        );
+    }
+    is_in_cycle
+}
+
+struct CycleFinder {
+    // The files that each file imports, along with where the import path is
+    edges: Vec<Vec<(usize, usize)>>,
+
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    stack: Vec<usize>,
+    is_on_stack: Vec<bool>,
+    next_index: usize,
+
+    // Each component is in the order its files were popped off of the stack,
+    // so the file it was entered through is last
+    components: Vec<Vec<usize>>,
+}
+
+impl CycleFinder {
+    fn visit(&mut self, source_index: usize) {
+        self.index[source_index] = Some(self.next_index);
+        self.low_link[source_index] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(source_index);
+        self.is_on_stack[source_index] = true;
+
+        for i in 0..self.edges[source_index].len() {
+            let (other, _) = self.edges[source_index][i];
+            match self.index[other] {
+                None => {
+                    self.visit(other);
+                    self.low_link[source_index] =
+                        self.low_link[source_index].min(self.low_link[other]);
+                }
+                Some(index) if self.is_on_stack[other] => {
+                    self.low_link[source_index] = self.low_link[source_index].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low_link[source_index]) == self.index[source_index] {
+            let mut component = vec![];
+            loop {
+                let other = self.stack.pop().expect("The file is on the stack");
+                self.is_on_stack[other] = false;
+                component.push(other);
+                if other == source_index {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+
+    // Returns the files on the shortest path from "start" back to itself
+    // without leaving the component, along with the location of the import
+    // path that closes the cycle. A single file is only a cycle if it
+    // imports itself.
+    fn shortest_cycle(&self, start: usize, component: &[usize]) -> Option<(Vec<usize>, usize)> {
+        let mut previous: HashMap<usize, usize> = HashMap::new();
+        let mut queue = VecDeque::from(vec![start]);
+        while let Some(source_index) = queue.pop_front() {
+            for &(other, location) in &self.edges[source_index] {
+                if other == start {
+                    let mut path = vec![source_index];
+                    while let Some(&prev) = previous.get(&path[path.len() - 1]) {
+                        path.push(prev);
+                    }
+                    path.reverse();
+                    return Some((path, location));
+                }
+                if component.contains(&other) && !previous.contains_key(&other) {
+                    previous.insert(other, source_index);
+                    queue.push_back(other);
+                }
+            }
+        }
+        None
+    }
+}
+
+// Files run after the files they import, so this is a post-order traversal of
+// the import statements. A file that is imported more than once only runs the
+// first time, and a cycle is broken at the file that was reached first. Files
+// loaded with "require()" are wrapped, so where they go doesn't matter. The
+// same goes for "import()" without code splitting.
+fn compute_file_order(
+    files: &[LinkerFile],
+    is_file_live: &[bool],
+    source_index: usize,
+    split_dynamic_imports: bool,
+    visited: &mut HashSet<usize>,
+    order: &mut Vec<usize>,
+) {
+    if !is_file_live[source_index] || !visited.insert(source_index) {
+        return;
+    }
+    let file = &files[source_index];
+    for part in &file.ast.parts {
+        for import_path in &part.import_paths {
+            if import_path.kind != ImportKind::Dynamic || !split_dynamic_imports {
+                if let Some(other) = file.resolve(&import_path.path) {
+                    compute_file_order(
+                        files,
+                        is_file_live,
+                        other,
+                        split_dynamic_imports,
+                        visited,
+                        order,
+                    );
+                }
+            }
+        }
+    }
+    order.push(source_index);
+}
+
+// Links every import of a file in the bundle to the symbol it refers to in
+// the other file. Returns the variables from "generate_star_namespaces", or
+// None if an import couldn't be bound.
+fn bind_imports(
+    log: &Log,
+    sources: &[Source],
+    files: &[LinkerFile],
+    shaking: &TreeShakingResult,
+    order: &[usize],
+    options: &BundleOptions,
+    symbols: &mut SymbolMap,
+) -> Option<BTreeMap<(usize, Location), Reference>> {
+    let mut ok = true;
+    let is_wrapped = &shaking.is_wrapped;
+    let star_refs = generate_star_namespaces(files, is_wrapped, order, symbols);
+
+    for &source_index in order {
+        let file = &files[source_index];
+        let source = &sources[source_index];
+
+        // Sorted so that errors are reported in a stable order
+        let mut named_imports: Vec<_> = file.ast.named_imports.iter().collect();
+        named_imports.sort_by_key(|(reference, _)| **reference);
+
+        for (&import_ref, named_import) in named_imports {
+            // The exports of a wrapped file are only known once it has run,
+            // so they are read off of its exports object instead
+            let other = match file.resolve(&named_import.import_path) {
+                Some(other) if !is_wrapped[other] => other,
+                _ => continue,
+            };
+            let text = match find_export(
+                files,
+                is_wrapped,
+                other,
+                &named_import.alias,
+                &mut HashSet::new(),
+            ) {
+                ExportTarget::Found(export_ref) => {
+                    symbols[import_ref].link = export_ref;
+                    continue;
+                }
+                ExportTarget::Dynamic(star_source_index, star_location) => {
+                    symbols.set_namespace_alias(
+                        import_ref,
+                        Arc::new(NamespaceAlias {
+                            namespace_ref: star_refs[&(star_source_index, star_location)],
+                            alias: named_import.alias.clone(),
+                        }),
+                    );
+                    continue;
+                }
+                ExportTarget::Missing => format!(
+                    "No matching export in {:?} for import {:?}",
+                    sources[other].pretty_path, named_import.alias
+                ),
+                ExportTarget::Ambiguous => format!(
+                    "Ambiguous import {:?} has multiple matching exports",
+                    named_import.alias
+                ),
+            };
+            log.add_error(source, named_import.alias_loc, text);
+            ok = false;
+        }
+
+        for (part_index, part) in file.ast.parts.iter().enumerate() {
+            if !shaking.is_part_live[source_index][part_index] {
+                continue;
+            }
+            for import_path in &part.import_paths {
+                let other = match file.resolve(&import_path.path) {
+                    Some(other) if import_path.kind != ImportKind::Stmt => other,
+                    _ => continue,
+                };
+                let text = match import_path.kind {
+                    ImportKind::Dynamic if options.code_splitting => continue,
+                    _ if is_wrapped[other] || shaking.is_lazy[other] => continue,
+                    ImportKind::Dynamic => {
+                        "Bundling an entry point loaded with \"import()\" needs code splitting"
+                    }
+                    _ => "Bundling an entry point loaded with \"require()\" isn't supported yet",
+                };
+                log.add_error(source, import_path.path.loc, text.to_owned());
+                ok = false;
+            }
+        }
+    }
+
+    ok.then_some(star_refs)
+}
+
+// The names that "export * from" re-exports from a wrapped file aren't known
+// until it runs. The exports of the other output formats are properties that
+// can be copied over at that point, but ESM exports have to be known up front.
+// Returns false if an ESM entry point does this.
+fn check_entry_point_export_stars(
+    log: &Log,
+    sources: &[Source],
+    files: &[LinkerFile],
+    shaking: &TreeShakingResult,
+    entry_point: usize,
+) -> bool {
+    let mut ok = true;
+    let file = &files[entry_point];
+    for (part_index, part) in file.ast.parts.iter().enumerate() {
+        if !shaking.is_part_live[entry_point][part_index] {
+            continue;
+        }
+        for stmt in &part.stmts {
+            let path = match stmt.data.as_ref() {
+                StmtKind::ExportStar { item: None, path } => path,
+                _ => continue,
+            };
+            let has_dynamic_exports = file.resolve(path).is_some_and(|other| {
+                has_dynamic_exports(files, &shaking.is_wrapped, other, &mut HashSet::new())
+            });
+            if has_dynamic_exports {
+                log.add_error(
+                    &sources[entry_point],
+                    stmt.location,
+                    "Re-exporting everything from a CommonJS file isn't possible in an ESM \
+                     entry point since the names aren't known until it runs"
+                        .to_owned(),
+                );
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+enum ExportTarget {
+    Found(Reference),
+
+    // No ES6 file exports the name, but an "export * from" of a wrapped file
+    // might. This is the source index of the file with that statement and
+    // the location of its path, see "generate_star_namespaces".
+    Dynamic(usize, Location),
+
+    Missing,
+
+    // Two "export * from" statements export the name with different symbols.
+    // Importing such a name is an error and "export *" leaves it out.
+    Ambiguous,
+}
+
+// Returns the symbol that the export "alias" of a file refers to. Re-exports
+// are followed to the file that declares the symbol. An export that comes
+// from an external or wrapped file is the import symbol in the file that
+// re-exports it, since it's read off of the namespace of that file. A name
+// that an ES6 file exports takes precedence over one that a wrapped file might
+// export.
+fn find_export(
+    files: &[LinkerFile],
+    is_wrapped: &[bool],
+    source_index: usize,
+    alias: &str,
+    visited: &mut HashSet<(usize, String)>,
+) -> ExportTarget {
+    // Re-exports can form a cycle
+    if !visited.insert((source_index, alias.to_owned())) {
+        return ExportTarget::Missing;
+    }
+    let file = &files[source_index];
+
+    // Names that are exported explicitly hide the ones from "export * from"
+    if let Some(&reference) = file.ast.named_exports.get(alias) {
+        if let Some(named_import) = file.ast.named_imports.get(&reference) {
+            if let Some(other) = file.resolve(&named_import.import_path) {
+                if !is_wrapped[other] {
+                    return find_export(files, is_wrapped, other, &named_import.alias, visited);
+                }
+            }
+        }
+        return ExportTarget::Found(reference);
+    }
+
+    // "export * from" never re-exports the default export
+    let mut result = ExportTarget::Missing;
+    if alias != "default" {
+        for path in &file.ast.export_stars {
+            let target = match file.resolve(path) {
+                Some(other) if is_wrapped[other] => ExportTarget::Dynamic(source_index, path.loc),
+                Some(other) => find_export(files, is_wrapped, other, alias, visited),
+                None => continue,
+            };
+            match (&result, target) {
+                (ExportTarget::Missing, target) => result = target,
+                (_, ExportTarget::Missing | ExportTarget::Dynamic(..)) => {}
+                (ExportTarget::Dynamic(..), target) => result = target,
+                (ExportTarget::Found(a), ExportTarget::Found(b)) if *a == b => {}
+                _ => return ExportTarget::Ambiguous,
+            }
+        }
+    }
+    result
+}
+
+// Returns every export of a file that can be imported, including the ones
+// from "export * from", ordered by name
+fn collect_all_exports(
+    files: &[LinkerFile],
+    is_wrapped: &[bool],
+    source_index: usize,
+) -> BTreeMap<String, Reference> {
+    let mut names = BTreeSet::new();
+    collect_export_names(
+        files,
+        is_wrapped,
+        source_index,
+        &mut HashSet::new(),
+        &mut names,
+    );
+    if files[source_index]
+        .ast
+        .named_exports
+        .contains_key("default")
+    {
+        names.insert("default".to_owned());
+    }
+    names
+        .into_iter()
+        .filter_map(|alias| {
+            match find_export(files, is_wrapped, source_index, &alias, &mut HashSet::new()) {
+                ExportTarget::Found(reference) => Some((alias, reference)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// The names of the exports of wrapped files aren't known until they run, so
+// those are left out
+fn collect_export_names(
+    files: &[LinkerFile],
+    is_wrapped: &[bool],
+    source_index: usize,
+    visited: &mut HashSet<usize>,
+    names: &mut BTreeSet<String>,
+) {
+    if !visited.insert(source_index) {
+        return;
+    }
+    let file = &files[source_index];
+    names.extend(
+        file.ast
+            .named_exports
+            .keys()
+            .filter(|alias| *alias != "default")
+            .cloned(),
+    );
+    for path in &file.ast.export_stars {
+        if let Some(other) = file.resolve(path) {
+            if !is_wrapped[other] {
+                collect_export_names(files, is_wrapped, other, visited, names);
+            }
+        }
+    }
+}
+
+// Whether "export * from" the file re-exports names that are only known once
+// a wrapped file has run
+fn has_dynamic_exports(
+    files: &[LinkerFile],
+    is_wrapped: &[bool],
+    source_index: usize,
+    visited: &mut HashSet<usize>,
+) -> bool {
+    if is_wrapped[source_index] {
+        return true;
+    }
+    if !visited.insert(source_index) {
+        return false;
+    }
+    let file = &files[source_index];
+    file.ast.export_stars.iter().any(|path| {
+        file.resolve(path)
+            .is_some_and(|other| has_dynamic_exports(files, is_wrapped, other, visited))
+    })
+}
+
+fn needs_conversion(stmt: &Stmt) -> bool {
+    match stmt.data.as_ref() {
+        StmtKind::Import { .. }
+        | StmtKind::ExportClause { .. }
+        | StmtKind::ExportFrom { .. }
+        | StmtKind::ExportDefault { .. }
+        | StmtKind::ExportStar { .. } => true,
+        StmtKind::Local { is_export, .. }
+        | StmtKind::Function { is_export, .. }
+        | StmtKind::Class { is_export, .. } => *is_export,
+        _ => false,
+    }
+}
+
+// Import and export statements between files in the bundle are removed since
+// the symbols have been linked together. Only the entry point keeps its
+// exports. Statements involving external files are kept, except that the
+// re-exports of other files become imports.
+fn convert_stmt(
+    files: &[LinkerFile],
+    source_index: usize,
+    is_entry_point: bool,
+    stmt: &Stmt,
+    symbols: &mut SymbolMap,
+    interop: &mut Interop,
+    stmts: &mut Vec<Stmt>,
+) {
+    let file = &files[source_index];
+    let location = stmt.location;
+
+    // Importing a lazy file runs it if it hasn't run yet
+    if let StmtKind::Import { path, .. }
+    | StmtKind::ExportFrom { path, .. }
+    | StmtKind::ExportStar { path, .. } = stmt.data.as_ref()
+    {
+        if let Some(init_ref) = file
+            .resolve(path)
+            .and_then(|other| interop.init_refs.get(&other))
+        {
+            stmts.push(Stmt {
+                location,
+                data: Box::new(StmtKind::Expr {
+                    value: cjs::call_wrapper(location, *init_ref),
+                }),
+            });
+        }
+    }
+
+    let data = match stmt.data.as_ref() {
+        StmtKind::Import {
+            namespace_symbol,
+            default_name,
+            path,
+        } => match file.resolve(path) {
+            Some(other) if interop.is_wrapped[other] => {
+                let wrapper_ref = files[other].ast.wrapper_ref;
+                match bind_to_namespace(file, namespace_symbol, default_name, symbols) {
+                    Some(namespace_ref) => {
+                        let helper_ref = interop.import_helper_ref(symbols);
+                        StmtKind::Local {
+                            decls: vec![Decl {
+                                binding: Binding {
+                                    location,
+                                    data: Box::new(BindingKind::Identifier {
+                                        reference: namespace_ref,
+                                    }),
+                                },
+                                value: Some(cjs::import_wrapper(location, helper_ref, wrapper_ref)),
+                            }],
+                            kind: LocalKind::Var,
+                            is_export: false,
+                            was_ts_import_equals_in_namespace: false,
+                        }
+                    }
+
+                    // "import 'path'" only runs the file
+                    None => StmtKind::Expr {
+                        value: cjs::call_wrapper(location, wrapper_ref),
+                    },
+                }
+            }
+            Some(_) => return,
+            None => stmt.data.as_ref().clone(),
+        },
+
+        StmtKind::ExportClause { items } => {
+            if !is_entry_point {
+                return;
+            }
+            StmtKind::ExportClause {
+                items: items.clone(),
+            }
+        }
+
+        StmtKind::ExportFrom { items, path, .. } => match file.resolve(path) {
+            Some(other) if !interop.is_wrapped[other] => {
+                if !is_entry_point {
+                    return;
+                }
+                StmtKind::ExportClause {
+                    items: items.clone(),
+                }
+            }
+
+            // The printer prints the local name of each item as the name that
+            // is imported, so these names must stay the same
+            None if is_entry_point => {
+                for item in items {
+                    symbols[item.name.reference].must_not_be_renamed = true;
+                }
+                stmt.data.as_ref().clone()
+            }
+
+            // Other files import the names and the linker binds them to the
+            // imports of this file instead. The entry point exports the names
+            // it imports from a wrapped file, see "copy_namespace_exports".
+            _ => {
+                let import_items = items
+                    .iter()
+                    .map(|item| ClauseItem {
+                        alias: file.ast.named_imports[&item.name.reference].alias.clone(),
+                        alias_location: item.alias_location,
+                        name: item.name.clone(),
+                    })
+                    .collect();
+                let import = Stmt {
+                    location,
+                    data: Box::new(StmtKind::Import {
+                        namespace_symbol: NamespaceSymbol::Clause {
+                            items: import_items,
+                        },
+                        default_name: None,
+                        path: path.clone(),
+                    }),
+                };
+                convert_stmt(
+                    files,
+                    source_index,
+                    is_entry_point,
+                    &import,
+                    symbols,
+                    interop,
+                    stmts,
+                );
+                if is_entry_point {
+                    stmts.push(Stmt {
+                        location,
+                        data: Box::new(StmtKind::ExportClause {
+                            items: items.clone(),
+                        }),
+                    });
+                }
+                return;
+            }
+        },
+
+        StmtKind::ExportStar { item, path } => match (file.resolve(path), item) {
+            // Every "export * from" of the entry point is turned into one list
+            // of names, which leaves out the names that are ambiguous
+            (Some(other), None) if !interop.is_wrapped[other] => {
+                let is_wrapped = &interop.is_wrapped;
+                let first_star = file
+                    .ast
+                    .export_stars
+                    .iter()
+                    .find(|path| file.resolve(path).is_some_and(|other| !is_wrapped[other]));
+                if !is_entry_point || first_star.is_some_and(|first| first.loc != path.loc) {
+                    return;
+                }
+                let mut names = BTreeSet::new();
+                collect_export_names(
+                    files,
+                    is_wrapped,
+                    source_index,
+                    &mut HashSet::new(),
+                    &mut names,
+                );
+                let items = names
+                    .into_iter()
+                    .filter(|alias| !file.ast.named_exports.contains_key(alias))
+                    .filter_map(|alias| {
+                        match find_export(
+                            files,
+                            is_wrapped,
+                            source_index,
+                            &alias,
+                            &mut HashSet::new(),
+                        ) {
+                            ExportTarget::Found(reference) => Some(ClauseItem {
+                                alias,
+                                alias_location: path.loc,
+                                name: LocationRef {
+                                    loc: path.loc,
+                                    reference,
+                                },
+                            }),
+                            _ => None,
+                        }
+                    })
+                    .collect();
+                StmtKind::ExportClause { items }
+            }
+
+            // The symbol has been linked to the namespace object of the file
+            (Some(other), Some(item)) if !interop.is_wrapped[other] => {
+                if !is_entry_point {
+                    return;
+                }
+                StmtKind::ExportClause {
+                    items: vec![item.clone()],
+                }
+            }
+
+            // The exports of a wrapped file are copied onto the namespace
+            // object of this file and onto the exports of the entry point once
+            // it has run. The other names are read off of them directly.
+            (Some(other), None) => {
+                let namespace_ref = interop.star_refs[&(source_index, path.loc)];
+                stmts.push(star_namespace(
+                    location,
+                    namespace_ref,
+                    files[other].ast.wrapper_ref,
+                ));
+                let target_refs: Vec<Reference> = interop
+                    .namespace_objects
+                    .get(&source_index)
+                    .copied()
+                    .into_iter()
+                    .chain(is_entry_point.then_some(file.ast.exports_ref))
+                    .collect();
+                for target_ref in target_refs {
+                    let helper_ref = interop.re_export_helper_ref(symbols);
+                    stmts.push(Stmt {
+                        location,
+                        data: Box::new(StmtKind::Expr {
+                            value: cjs::re_export(location, helper_ref, target_ref, namespace_ref),
+                        }),
+                    });
+                }
+
+                // The entry point is still an ES6 module if this is its only
+                // export, which the other output formats mark with "__esModule"
+                if !is_entry_point {
+                    return;
+                }
+                StmtKind::ExportClause { items: vec![] }
+            }
+
+            // The names that come from an external file aren't known, so they
+            // can only be re-exported by the entry point
+            (None, _) if is_entry_point => stmt.data.as_ref().clone(),
+            (None, None) => return,
+
+            // Other files import the namespace and the linker binds it to the
+            // import of this file instead. The entry point exports it too.
+            (_, Some(item)) => {
+                let import = Stmt {
+                    location,
+                    data: Box::new(StmtKind::Import {
+                        namespace_symbol: NamespaceSymbol::Star {
+                            location: item.alias_location,
+                            namespace_ref: item.name.reference,
+                        },
+                        default_name: None,
+                        path: path.clone(),
+                    }),
+                };
+                convert_stmt(
+                    files,
+                    source_index,
+                    is_entry_point,
+                    &import,
+                    symbols,
+                    interop,
+                    stmts,
+                );
+                if is_entry_point {
+                    stmts.push(Stmt {
+                        location,
+                        data: Box::new(StmtKind::ExportClause {
+                            items: vec![item.clone()],
+                        }),
+                    });
+                }
+                return;
+            }
+        },
+
+        StmtKind::ExportDefault {
+            default_name,
+            value,
+        } => {
+            let mut data = default_export_declaration(default_name, value);
+            if let StmtKind::Local { kind, .. } = &mut data {
+                if interop.needs_tdz[source_index] {
+                    *kind = LocalKind::Let;
+                }
+            }
+
+            // The entry point exports the declaration under its own name
+            stmts.push(Stmt {
+                location,
+                data: Box::new(data),
+            });
+            if is_entry_point {
+                stmts.push(Stmt {
+                    location,
+                    data: Box::new(StmtKind::ExportClause {
+                        items: vec![ClauseItem {
+                            alias: "default".to_owned(),
+                            alias_location: default_name.loc,
+                            name: default_name.clone(),
+                        }],
+                    }),
+                });
+            }
+            return;
+        }
+
+        StmtKind::Local {
+            decls,
+            kind,
+            was_ts_import_equals_in_namespace,
+            ..
+        } => StmtKind::Local {
+            decls: decls.clone(),
+            kind: *kind,
+            is_export: is_entry_point,
+            was_ts_import_equals_in_namespace: *was_ts_import_equals_in_namespace,
+        },
+        StmtKind::Function { function, .. } => StmtKind::Function {
+            function: function.clone(),
+            is_export: is_entry_point,
+        },
+        StmtKind::Class { class, .. } => StmtKind::Class {
+            class: class.clone(),
+            is_export: is_entry_point,
+        },
+
+        data => data.clone(),
+    };
+
+    stmts.push(Stmt {
+        location,
+        data: Box::new(data),
+    });
+}
+
+// The names imported from a wrapped file are properties of the object that
+// its closure returns. Returns the symbol for that object, or None if nothing
+// is imported.
+fn bind_to_namespace(
+    file: &LinkerFile,
+    namespace_symbol: &NamespaceSymbol,
+    default_name: &Option<LocationRef>,
+    symbols: &mut SymbolMap,
+) -> Option<Reference> {
+    let mut items: Vec<(Reference, &str)> = default_name
+        .iter()
+        .map(|name| (name.reference, "default"))
+        .collect();
+    let namespace_ref = match namespace_symbol {
+        NamespaceSymbol::Star { namespace_ref, .. } => *namespace_ref,
+        NamespaceSymbol::Clause { items: clause } => {
+            items.extend(
+                clause
+                    .iter()
+                    .map(|item| (item.name.reference, item.alias.as_str())),
+            );
+            let (first, _) = items.first()?;
+            file.ast.named_imports[first].namespace_ref
+        }
+    };
+    for (reference, alias) in items {
+        symbols.set_namespace_alias(
+            reference,
+            Arc::new(NamespaceAlias {
+                namespace_ref,
+                alias: alias.to_owned(),
+            }),
+        );
+    }
+    Some(namespace_ref)
+}
+
+// "export default" declares a symbol for the value even when it has no name,
+// so it can be turned into a regular declaration once the export is handled
+// some other way
+pub fn default_export_declaration(default_name: &LocationRef, value: &ExprOrStmt) -> StmtKind {
+    match value {
+        ExprOrStmt::Expr(value) => StmtKind::Local {
+            decls: vec![Decl {
+                binding: Binding {
+                    location: default_name.loc,
+                    data: Box::new(BindingKind::Identifier {
+                        reference: default_name.reference,
+                    }),
+                },
+                value: Some(value.clone()),
+            }],
+            kind: LocalKind::Var,
+            is_export: false,
+            was_ts_import_equals_in_namespace: false,
+        },
+        ExprOrStmt::Stmt(value) => match value.data.as_ref() {
+            StmtKind::Function { function, .. } => {
+                let mut function = function.clone();
+                function.name = Some(default_name.clone());
+                StmtKind::Function {
+                    function,
+                    is_export: false,
+                }
+            }
+            StmtKind::Class { class, .. } => {
+                let mut class = class.clone();
+                class.name = Some(default_name.clone());
+                StmtKind::Class {
+                    class,
+                    is_export: false,
+                }
+            }
+            _ => unreachable!("Unexpected statement in \"export default\""),
+        },
+    }
+}
@@ -1,7 +1,18 @@
 use crate::ast::Location;
+use crate::lexer;
+use crate::tables::Token;
+use crate::terminal::{
+    StderrColor, TerminalInfo, COLOR_BOLD, COLOR_GREEN, COLOR_MAGENTA, COLOR_RED, COLOR_RESET,
+    COLOR_RESET_BOLD,
+};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::io::{self, Write};
 use std::ops::{Range, RangeFrom, RangeTo};
-use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 // Logging is currently designed to look and feel like clang's error format.
 // Errors are streamed asynchronously as they happen, each error contains the
@@ -9,24 +20,243 @@ use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 // default.
 
 pub struct Log {
-    pub sender: SyncSender<Msg>,
+    // The channel is unbounded since nothing takes messages out of it while
+    // a file is parsed, unless a drain has been started (see "spawn_drain")
+    pub sender: Sender<Msg>,
     pub receiver: Receiver<Msg>,
+
+    // Changes the level of the messages with these names as they're taken out
+    // of the log (see "MsgId")
+    pub overrides: HashMap<MsgId, LogLevel>,
 }
 
 impl Default for Log {
     fn default() -> Self {
-        let (sender, receiver) = sync_channel(1024);
-        Self { sender, receiver }
+        Self::with_overrides(HashMap::new())
     }
 }
 
 impl Log {
-    pub fn clone_sender(&self) -> SyncSender<Msg> {
+    pub fn clone_sender(&self) -> Sender<Msg> {
         self.sender.clone()
     }
+
+    // A log for another thread that sends its messages to the log the sender
+    // came from. Nothing is ever received from this one.
+    pub fn from_sender(sender: Sender<Msg>) -> Self {
+        let (_, receiver) = channel();
+        Self {
+            sender,
+            receiver,
+            overrides: HashMap::new(),
+        }
+    }
+
+    pub fn with_overrides(overrides: HashMap<MsgId, LogLevel>) -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver,
+            overrides,
+        }
+    }
+
+    // Returns the messages that have been logged so far, in the order they were
+    // logged. This is for callers that handle the messages themselves instead
+    // of printing them.
+    pub fn take_msgs(&self) -> Vec<Msg> {
+        self.receiver
+            .try_iter()
+            .filter_map(|msg| apply_override(&self.overrides, msg))
+            .collect()
+    }
+
+    // Prints the messages to stderr on another thread as they're logged. See
+    // "spawn_drain".
+    pub fn spawn_stderr_drain(
+        &mut self,
+        options: &StderrOptions,
+        terminal_info: TerminalInfo,
+    ) -> LogDrain {
+        self.spawn_drain(options, terminal_info, io::stderr())
+    }
+
+    // Writes the messages to "out" on another thread as they're logged,
+    // instead of them being taken out of the log. Nothing is left to take out
+    // of the log afterward. The messages go through a "DiagnosticSink" for
+    // the error limit.
+    //
+    // Messages are written in batches at most once every "DRAIN_INTERVAL",
+    // and the messages of a batch are sorted by file and position. Messages
+    // from the threads of a parallel build arrive interleaved, and this puts
+    // the ones that were logged around the same time back in order.
+    pub fn spawn_drain<W: Write + Send + 'static>(
+        &mut self,
+        options: &StderrOptions,
+        terminal_info: TerminalInfo,
+        mut out: W,
+    ) -> LogDrain {
+        let (_, receiver) = channel();
+        let receiver = std::mem::replace(&mut self.receiver, receiver);
+        let overrides = self.overrides.clone();
+        let options = options.clone();
+        let thread = std::thread::spawn(move || {
+            let mut sink = DiagnosticSink::new(&options);
+            let mut batch = vec![];
+            let mut write_batch = |batch: &mut Vec<Msg>| {
+                // Messages about the build as a whole go first
+                batch.sort_by_key(|msg: &Msg| {
+                    (
+                        !msg.source.pretty_path.is_empty(),
+                        msg.source.index,
+                        msg.start,
+                    )
+                });
+                for msg in batch.drain(..).filter_map(|msg| sink.filter(msg)) {
+                    let _ =
+                        out.write_all(msg.to_terminal_string(&options, &terminal_info).as_bytes());
+                }
+                let _ = out.flush();
+            };
+
+            // The first message of a batch waits for the ones after it until
+            // the interval is up. The thread is done once every sender is gone.
+            let mut deadline = Instant::now();
+            loop {
+                let received = if batch.is_empty() {
+                    receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                } else {
+                    receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                };
+                match received {
+                    Ok(msg) => {
+                        if batch.is_empty() {
+                            deadline = Instant::now() + DRAIN_INTERVAL;
+                        }
+                        batch.extend(apply_override(&overrides, msg));
+                    }
+                    Err(RecvTimeoutError::Timeout) => write_batch(&mut batch),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            write_batch(&mut batch);
+
+            if let Some(msg) = sink.finish() {
+                let _ = out.write_all(msg.to_terminal_string(&options, &terminal_info).as_bytes());
+                let _ = out.flush();
+            }
+            sink.counts().clone()
+        });
+        LogDrain { thread }
+    }
+
+    // Returns the messages that have been logged so far as a JSON array (see
+    // "Msg::to_json")
+    pub fn take_msgs_as_json(&self) -> String {
+        msgs_to_json(&self.take_msgs())
+    }
+
+    pub fn add_msg(&self, msg: Msg) {
+        // This only fails if the receiver is gone, which is the case for
+        // the log of another thread (see "from_sender") once the log it
+        // sends to has been dropped
+        self.sender.send(msg).ok();
+    }
+
+    pub fn add_error(&self, source: &Source, location: Location, text: String) {
+        self.add_range_error(source, location..location, text);
+    }
+
+    pub fn add_range_error(&self, source: &Source, range: Range<usize>, text: String) {
+        self.add_msg(Msg {
+            source: msg_source(source),
+            start: range.start,
+            length: range.end - range.start,
+            text,
+            kind: MsgKind::Error,
+            id: MsgId::None,
+            notes: vec![],
+        });
+    }
+
+    pub fn add_range_error_with_notes(
+        &self,
+        source: &Source,
+        range: Range<usize>,
+        text: String,
+        notes: Vec<MsgNote>,
+    ) {
+        self.add_msg(Msg {
+            source: msg_source(source),
+            start: range.start,
+            length: range.end - range.start,
+            text,
+            kind: MsgKind::Error,
+            id: MsgId::None,
+            notes,
+        });
+    }
+
+    pub fn add_warning(&self, source: &Source, location: Location, text: String) {
+        self.add_range_warning(source, location..location, text);
+    }
+
+    pub fn add_range_warning(&self, source: &Source, range: Range<usize>, text: String) {
+        self.add_id_warning_with_notes(MsgId::None, source, range, text, vec![]);
+    }
+
+    pub fn add_range_warning_with_notes(
+        &self,
+        source: &Source,
+        range: Range<usize>,
+        text: String,
+        notes: Vec<MsgNote>,
+    ) {
+        self.add_id_warning_with_notes(MsgId::None, source, range, text, notes);
+    }
+
+    pub fn add_id_warning(&self, id: MsgId, source: &Source, range: Range<usize>, text: String) {
+        self.add_id_warning_with_notes(id, source, range, text, vec![]);
+    }
+
+    pub fn add_id_warning_with_notes(
+        &self,
+        id: MsgId,
+        source: &Source,
+        range: Range<usize>,
+        text: String,
+        notes: Vec<MsgNote>,
+    ) {
+        self.add_msg(Msg {
+            source: msg_source(source),
+            start: range.start,
+            length: range.end - range.start,
+            text,
+            kind: MsgKind::Warning,
+            id,
+            notes,
+        });
+    }
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+fn apply_override(overrides: &HashMap<MsgId, LogLevel>, mut msg: Msg) -> Option<Msg> {
+    match overrides.get(&msg.id) {
+        Some(LogLevel::Silent) => return None,
+        Some(LogLevel::Warning) => msg.kind = MsgKind::Warning,
+        Some(LogLevel::Error) => msg.kind = MsgKind::Error,
+        None => {}
+    }
+    Some(msg)
+}
+
+// Every message holds its own copy of the source. Building the line table
+// before copying it means that all messages for a source share one table.
+fn msg_source(source: &Source) -> Source {
+    source.line_offset_table();
+    source.clone()
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Copy, Clone)]
 pub enum MsgKind {
     Error = 0,
     Warning,
@@ -34,10 +264,84 @@ pub enum MsgKind {
 
 impl fmt::Display for MsgKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", match self {
-            MsgKind::Error => "error",
-            MsgKind::Warning => "warning",
-        })
+        write!(
+            f,
+            "{}",
+            match self {
+                MsgKind::Error => "error",
+                MsgKind::Warning => "warning",
+            }
+        )
+    }
+}
+
+// Warnings about code that is probably a mistake have a name. The name is
+// shown with the warning and is used to change how the warning is reported:
+// "--log-override:equality-with-nan=error" turns it into an error and
+// "--log-override:equality-with-nan=silent" hides it.
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum MsgId {
+    None = 0,
+    AssignToConstant,
+    AssignToImport,
+    CommonJSVariableInESM,
+    DuplicateObjectKey,
+    EmptyImportMeta,
+    EqualityWithNaN,
+    ImportCycle,
+}
+
+impl MsgId {
+    pub const ALL: [MsgId; 7] = [
+        MsgId::AssignToConstant,
+        MsgId::AssignToImport,
+        MsgId::CommonJSVariableInESM,
+        MsgId::DuplicateObjectKey,
+        MsgId::EmptyImportMeta,
+        MsgId::EqualityWithNaN,
+        MsgId::ImportCycle,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MsgId::None => "",
+            MsgId::AssignToConstant => "assign-to-constant",
+            MsgId::AssignToImport => "assign-to-import",
+            MsgId::CommonJSVariableInESM => "commonjs-variable-in-esm",
+            MsgId::DuplicateObjectKey => "duplicate-object-key",
+            MsgId::EmptyImportMeta => "empty-import-meta",
+            MsgId::EqualityWithNaN => "equality-with-nan",
+            MsgId::ImportCycle => "import-cycle",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MsgId> {
+        MsgId::ALL.iter().copied().find(|id| id.name() == name)
+    }
+}
+
+impl fmt::Display for MsgId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// What a message with a name is reported as instead of its usual kind
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+pub enum LogLevel {
+    Silent = 0,
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name {
+            "silent" => Some(LogLevel::Silent),
+            "warning" => Some(LogLevel::Warning),
+            "error" => Some(LogLevel::Error),
+            _ => None,
+        }
     }
 }
 
@@ -48,21 +352,146 @@ pub struct Msg {
     pub length: usize,
     pub text: String,
     pub kind: MsgKind,
+    pub id: MsgId,
+
+    // Other locations that help explain the message, such as where a symbol
+    // involved in the problem was declared
+    pub notes: Vec<MsgNote>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MsgNote {
+    pub source: Source,
+    pub start: usize,
+    pub length: usize,
+    pub text: String,
+}
+
+impl MsgNote {
+    pub fn new(source: &Source, range: Range<usize>, text: String) -> Self {
+        Self {
+            source: msg_source(source),
+            start: range.start,
+            length: range.end - range.start,
+            text,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{{},\"text\":{}}}",
+            location_to_json(&self.source, self.start, self.length),
+            quote_json(&self.text)
+        )
+    }
+
+    // Notes are indented beneath the message they belong to, with the line
+    // of code they point to if there is one
+    fn to_terminal_string(&self, options: &StderrOptions, terminal_info: &TerminalInfo) -> String {
+        if self.source.pretty_path.is_empty() {
+            return format!("  note: {}\n", self.text);
+        }
+
+        if !options.include_source {
+            return format!("  {}: note: {}\n", self.source.pretty_path, self.text);
+        }
+
+        let detail = MsgDetail::for_range(
+            &self.source,
+            self.start,
+            self.length,
+            "note",
+            &self.text,
+            terminal_info,
+        );
+        if options.color.is_enabled(terminal_info) {
+            format!(
+                "  {}{}:{}:{}: note: {}{}\n  {}{}{}{}{}\n  {}{}{}{}\n",
+                COLOR_BOLD,
+                detail.path,
+                detail.line,
+                detail.column,
+                detail.message,
+                COLOR_RESET,
+                detail.source_before(),
+                COLOR_GREEN,
+                detail.source_marked(),
+                COLOR_RESET,
+                detail.source_after(),
+                COLOR_GREEN,
+                detail.indent,
+                detail.marker,
+                COLOR_RESET
+            )
+        } else {
+            format!(
+                "  {}:{}:{}: note: {}\n  {}\n  {}{}\n",
+                detail.path,
+                detail.line,
+                detail.column,
+                detail.message,
+                detail.source,
+                detail.indent,
+                detail.marker
+            )
+        }
+    }
 }
 
 impl Msg {
+    // This is for tools such as editors that read the messages instead of a
+    // person. Lines start at 1 and columns start at 0, like in the terminal
+    // output. A message about the build as a whole has an empty path.
+    //
+    //   {"kind":"error","id":"","path":"a.js","line":1,"column":4,"length":1,
+    //    "text":"Unexpected \"}\"","notes":[]}
+    //
+    pub fn to_json(&self) -> String {
+        let notes: Vec<String> = self.notes.iter().map(MsgNote::to_json).collect();
+        format!(
+            "{{\"kind\":\"{}\",\"id\":\"{}\",{},\"text\":{},\"notes\":[{}]}}",
+            self.kind,
+            self.id,
+            location_to_json(&self.source, self.start, self.length),
+            quote_json(&self.text),
+            notes.join(",")
+        )
+    }
+
     pub fn to_terminal_string(
         &self,
         options: &StderrOptions,
         terminal_info: &TerminalInfo,
+    ) -> String {
+        // The name is shown so that it's known what to override
+        let mut text = if self.id == MsgId::None {
+            self.msg_to_terminal_string(options, terminal_info)
+        } else {
+            Msg {
+                text: format!("{} [{}]", self.text, self.id),
+                ..self.clone()
+            }
+            .msg_to_terminal_string(options, terminal_info)
+        };
+        for note in &self.notes {
+            text.push_str(&note.to_terminal_string(options, terminal_info));
+        }
+        text
+    }
+
+    fn msg_to_terminal_string(
+        &self,
+        options: &StderrOptions,
+        terminal_info: &TerminalInfo,
     ) -> String {
         let (kind, kind_color) = match self.kind {
             MsgKind::Error => ("error", COLOR_RED),
             MsgKind::Warning => ("warning", COLOR_MAGENTA),
         };
+        let use_color = options.color.is_enabled(terminal_info);
 
         if self.source.pretty_path.is_empty() {
-            if terminal_info.use_color_escapes {
+            if use_color {
                 return format!(
                     "{}{}{}: {}{}{}\n",
                     COLOR_BOLD, kind_color, kind, COLOR_RESET_BOLD, self.text, COLOR_RESET
@@ -73,7 +502,7 @@ impl Msg {
         }
 
         if !options.include_source {
-            if terminal_info.use_color_escapes {
+            if use_color {
                 return format!(
                     "{}{}: {}{}: {}{}{}\n",
                     COLOR_BOLD,
@@ -91,7 +520,7 @@ impl Msg {
 
         let detail = MsgDetail::new(self, terminal_info);
 
-        if terminal_info.use_color_escapes {
+        if use_color {
             format!(
                 "{}{}:{}:{}: {}{}: {}{}\n{}{}{}{}{}{}\n{}{}{}{}\n",
                 COLOR_BOLD,
@@ -129,43 +558,109 @@ impl Msg {
     }
 }
 
-#[derive(Debug, Clone)]
+// Messages about the build as a whole use a default source. They are printed
+// without a file name or a line of code.
+#[derive(Debug, Clone, Default)]
 pub struct Source {
     pub index: u32,
     pub is_stdin: bool,
     pub absolute_path: String,
     pub pretty_path: String,
     pub contents: String,
+
+    // This is filled in by "line_offset_table" and is shared by the copies of
+    // the source that messages hold. Leave it empty when creating a source,
+    // including one that copies another source with different contents.
+    pub line_offsets: OnceLock<Arc<LineOffsetTable>>,
 }
 
 impl Source {
+    pub fn line_offset_table(&self) -> &LineOffsetTable {
+        self.line_offsets
+            .get_or_init(|| Arc::new(LineOffsetTable::new(&self.contents)))
+    }
+
     pub fn text_for_range(&self, range: Range<usize>) -> String {
         self.contents[range].to_owned()
     }
 
+    // These find the range of the token at "location" so that a message can
+    // underline all of it, escapes included. The range is empty if the token
+    // there isn't the right kind.
+    //
+    // Template literals count as strings. Only the part up to the first
+    // "${" is covered if there are substitutions.
     pub fn range_of_string(&self, location: Location) -> Range<usize> {
-        let bytes = self.contents.as_bytes();
-        let text = &bytes[location..];
+        self.range_of_token(location, |token| {
+            matches!(
+                token,
+                Token::StringLiteral | Token::NoSubstitutionTemplateLiteral | Token::TemplateHead
+            )
+        })
+    }
 
-        if text.is_empty() {
-            return location..location;
-        }
+    pub fn range_of_number(&self, location: Location) -> Range<usize> {
+        self.range_of_token(location, |token| {
+            matches!(token, Token::NumericLiteral | Token::BigIntegerLiteral)
+        })
+    }
 
-        let quote = text[0];
-        if quote == b'"' || quote == b'\'' {
-            let mut i = 1;
-            while i < text.len() {
-                let c = text[i];
-                if c == quote {
-                    return location..i + 1;
-                } else if c == b'\\' {
-                    i += 1;
-                }
-            }
+    // Keywords and private names such as "#x" count as identifiers
+    pub fn range_of_identifier(&self, location: Location) -> Range<usize> {
+        self.range_of_token(location, |token| token >= Token::Identifier)
+    }
+
+    pub fn range_of_operator(&self, location: Location) -> Range<usize> {
+        self.range_of_token(location, |token| {
+            token.to_operator_code(true).is_some() || token.to_operator_code(false).is_some()
+        })
+    }
+
+    fn range_of_token(&self, location: Location, is_kind: impl Fn(Token) -> bool) -> Range<usize> {
+        match lexer::token_at(self, location) {
+            (token, range) if is_kind(token) => range,
+            _ => location..location,
         }
+    }
+}
+
+pub fn msgs_to_json(msgs: &[Msg]) -> String {
+    let msgs: Vec<String> = msgs.iter().map(Msg::to_json).collect();
+    format!("[{}]", msgs.join(","))
+}
 
-        location..location
+fn location_to_json(source: &Source, start: usize, length: usize) -> String {
+    let (line, column) = if source.pretty_path.is_empty() {
+        (0, 0)
+    } else {
+        let (line, column, _) = source.line_offset_table().line_and_column(start);
+        (line + 1, column)
+    };
+    format!(
+        "\"path\":{},\"line\":{},\"column\":{},\"length\":{}",
+        quote_json(&source.pretty_path),
+        line,
+        column,
+        length
+    )
+}
+
+pub fn quote_json(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
     }
+    quoted.push('"');
+    quoted
 }
 
 #[derive(Debug, Clone)]
@@ -203,37 +698,124 @@ impl fmt::Display for MsgCounts {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct TerminalInfo {
-    is_tty: bool,
-    use_color_escapes: bool,
-    width: usize,
+// Sits between a log and whatever shows its messages. It counts messages by
+// kind, drops messages that are exact duplicates of earlier ones (the same
+// file can be reported on by more than one part of the build), and stops
+// passing errors on once the error limit is hit. The errors past the limit
+// are still counted, and "finish" returns a message saying how many were
+// left out.
+#[derive(Debug)]
+pub struct DiagnosticSink {
+    error_limit: usize,
+    exit_when_limit_is_hit: bool,
+    counts: MsgCounts,
+    omitted_errors: usize,
+    seen: HashSet<(u32, usize, usize, MsgKind, String)>,
 }
 
-impl Default for TerminalInfo {
-    fn default() -> Self {
+impl DiagnosticSink {
+    pub fn new(options: &StderrOptions) -> Self {
         Self {
-            is_tty: atty::is(atty::Stream::Stderr),
-            use_color_escapes: true,
-            width: terminal_size::terminal_size()
-                .map(|(w, _)| w.0 as usize)
-                .unwrap_or(0),
+            error_limit: options.error_limit,
+            exit_when_limit_is_hit: options.exit_when_limit_is_hit,
+            counts: MsgCounts {
+                errors: 0,
+                warnings: 0,
+            },
+            omitted_errors: 0,
+            seen: HashSet::new(),
         }
     }
+
+    // Returns the messages logged since the last call that should be shown
+    pub fn drain(&mut self, log: &Log) -> Vec<Msg> {
+        log.take_msgs()
+            .into_iter()
+            .filter_map(|msg| self.filter(msg))
+            .collect()
+    }
+
+    // Counts the message, and returns it if it should be shown
+    fn filter(&mut self, msg: Msg) -> Option<Msg> {
+        let key = (
+            msg.source.index,
+            msg.start,
+            msg.length,
+            msg.kind,
+            msg.text.clone(),
+        );
+        if !self.seen.insert(key) {
+            return None;
+        }
+
+        match msg.kind {
+            MsgKind::Error => self.counts.errors += 1,
+            MsgKind::Warning => self.counts.warnings += 1,
+        }
+        if msg.kind == MsgKind::Error && self.is_over_limit() {
+            self.omitted_errors += 1;
+            return None;
+        }
+        Some(msg)
+    }
+
+    fn is_over_limit(&self) -> bool {
+        self.error_limit != 0 && self.counts.errors > self.error_limit
+    }
+
+    // Returns true if the build should stop because of the error limit
+    pub fn should_stop(&self) -> bool {
+        self.exit_when_limit_is_hit && self.is_over_limit()
+    }
+
+    pub fn counts(&self) -> &MsgCounts {
+        &self.counts
+    }
+
+    // Returns a message about the errors that were left out, if there were any
+    pub fn finish(&self) -> Option<Msg> {
+        if self.omitted_errors == 0 {
+            return None;
+        }
+        let shown = self.counts.errors - self.omitted_errors;
+        Some(Msg {
+            source: Source::default(),
+            start: 0,
+            length: 0,
+            text: format!(
+                "{} shown, {} omitted (disable the limit with --error-limit=0)",
+                plural("error", shown),
+                self.omitted_errors
+            ),
+            kind: MsgKind::Error,
+            id: MsgId::None,
+            notes: vec![],
+        })
+    }
 }
 
-pub const COLOR_RESET: &str = "\033[0m";
-pub const COLOR_RED: &str = "\033[31m";
-pub const COLOR_GREEN: &str = "\033[32m";
-pub const COLOR_MAGENTA: &str = "\033[35m";
-pub const COLOR_BOLD: &str = "\033[1m";
-pub const COLOR_RESET_BOLD: &str = "\033[0;1m";
+// How long the messages of a batch wait for the messages after them (see
+// "Log::spawn_drain")
+pub const DRAIN_INTERVAL: Duration = Duration::from_millis(50);
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
-pub enum StderrColor {
-    IfTerminal = 0,
-    Never,
-    Always,
+// The thread that writes the messages of a log
+#[derive(Debug)]
+pub struct LogDrain {
+    thread: JoinHandle<MsgCounts>,
+}
+
+impl LogDrain {
+    // Waits for the rest of the messages to be written, and returns how many
+    // there were of each kind. The thread only stops once every sender of the
+    // log is gone, so the log is dropped here. Logs made from its sender with
+    // "Log::from_sender" have to be dropped first.
+    pub fn finish(self, log: Log) -> MsgCounts {
+        drop(log);
+        match self.thread.join() {
+            Ok(counts) => counts,
+            Err(err) => std::panic::resume_unwind(err),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -244,30 +826,65 @@ pub struct StderrOptions {
     pub color: StderrColor,
 }
 
+// This is a convenience for a single query. Use the table of the source
+// instead when there may be more than one.
 pub fn compute_line_and_column(text: &str) -> (usize, usize, usize) {
-    let mut prev_code = '\0';
-    let mut last_line_start = 0;
-    let mut line_count = 0;
-
-    for (i, code) in text.chars().enumerate() {
-        match code {
-            '\n' => {
-                last_line_start = i + 1;
-                if prev_code != '\r' {
-                    line_count += 1;
+    LineOffsetTable::new(text).line_and_column(text.len())
+}
+
+// Finding the line of a location by scanning the text up to the location is
+// slow when there are many messages for a large file, since each one scans
+// most of the file again. This table holds the offset of the start of every
+// line instead so that a line can be found with a binary search. It's built
+// once per source the first time it's needed.
+//
+// Lines end with "\n", "\r\n", "\r", "\u2028", or "\u2029" like they do in
+// JavaScript. Columns are counted in bytes from the start of the line.
+#[derive(Debug, Clone, Default)]
+pub struct LineOffsetTable {
+    line_starts: Vec<usize>,
+}
+
+impl LineOffsetTable {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\r' => {
+                    if let Some((_, '\n')) = chars.peek() {
+                        chars.next();
+                        line_starts.push(i + 2);
+                    } else {
+                        line_starts.push(i + 1);
+                    }
                 }
+                '\n' => line_starts.push(i + 1),
+                '\u{2028}' | '\u{2029}' => line_starts.push(i + c.len_utf8()),
+                _ => {}
             }
-            '\r' | '\u{2028}' | '\u{2029}' => {
-                last_line_start = i + 1;
-            }
-            _ => {}
         }
-        prev_code = code;
+        Self { line_starts }
     }
 
-    let column_count = text.len() - last_line_start;
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
 
-    (line_count, column_count, last_line_start)
+    // Returns the 0-based line, the column, and the offset of the start of the
+    // line for a byte offset into the text
+    pub fn line_and_column(&self, offset: usize) -> (usize, usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        let line_start = self.line_starts[line];
+        (line, offset - line_start, line_start)
+    }
+
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.line_starts.get(line).copied()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -289,8 +906,26 @@ pub struct MsgDetail {
 
 impl MsgDetail {
     pub fn new(msg: &Msg, terminal_info: &TerminalInfo) -> Self {
-        let contents = &msg.source.contents;
-        let (line_count, col_count, line_start) = compute_line_and_column(&contents[0..msg.start]);
+        Self::for_range(
+            &msg.source,
+            msg.start,
+            msg.length,
+            &msg.kind.to_string(),
+            &msg.text,
+            terminal_info,
+        )
+    }
+
+    pub fn for_range(
+        source: &Source,
+        start: usize,
+        length: usize,
+        kind: &str,
+        text: &str,
+        terminal_info: &TerminalInfo,
+    ) -> Self {
+        let contents = &source.contents;
+        let (line_count, col_count, line_start) = source.line_offset_table().line_and_column(start);
         let mut line_end = contents.len();
 
         'a: for (i, code) in contents[line_start..].chars().enumerate() {
@@ -306,13 +941,13 @@ impl MsgDetail {
         let spaces_per_tab = 2;
         let mut line_text = render_tab_stops(&contents[line_start..line_end], spaces_per_tab);
         let mut indent = " ".repeat(render_tab_stops_len(
-            &contents[line_start..msg.start],
+            &contents[line_start..start],
             spaces_per_tab,
         ));
         let mut marker_start = indent.len();
-        let mut marker_end = if msg.length > 0 {
+        let mut marker_end = if length > 0 {
             // Extend markers to cover the full range of the error
-            render_tab_stops_len(&contents[line_start..msg.start], spaces_per_tab)
+            render_tab_stops_len(&contents[line_start..start], spaces_per_tab)
         } else {
             indent.len()
         };
@@ -358,11 +993,7 @@ impl MsgDetail {
 
             // Slice the line
             let mut sliced_line = line_text[slice_start..slice_end].to_owned();
-            marker_start = if marker_start > slice_start {
-                marker_start - slice_start
-            } else {
-                0
-            };
+            marker_start = marker_start.saturating_sub(slice_start);
             if marker_end > sliced_line.len() {
                 marker_end = sliced_line.len();
             }
@@ -380,11 +1011,11 @@ impl MsgDetail {
         }
 
         MsgDetail {
-            path: msg.source.pretty_path.clone(),
+            path: source.pretty_path.clone(),
             line: line_count + 1,
             column: col_count,
-            kind: msg.kind.to_string(),
-            message: msg.text.to_owned(),
+            kind: kind.to_owned(),
+            message: text.to_owned(),
             source: line_text,
             source_before: ..marker_start,
             source_marked: marker_start..marker_end,
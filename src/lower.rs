@@ -0,0 +1,5161 @@
+// Lowering converts syntax that the configured target doesn't support into
+// equivalent older syntax. Every feature goes through the same dispatcher:
+// if the target supports it nothing happens, if there is a lowering for it
+// the lowering runs, and otherwise the configured UnsupportedSyntax policy
+// decides what happens. Keeping this in one place means every feature fails
+// the same way when it can't be lowered.
+
+use crate::ast::{
+    join_all_with_comma, Arg, Binding, BindingKind, Case, Catch, Class, Decl, Expr, ExprKind,
+    ExprOrStmt, Finally, Function, FunctionBody, LocalKind, Location, LocationRef, OperatorCode,
+    OptionalChain, Property, PropertyKind, Reference, Stmt, StmtKind, Symbol, SymbolKind,
+    SymbolMap,
+};
+use crate::lexer::{is_identifier_utf16, number_to_minified_name};
+use crate::logging::{Log, Source};
+use crate::stack::ensure_sufficient_stack;
+use crate::strings::{string_to_utf16, utf16_equals_string, utf16_to_string_lossy};
+use crate::visit::Visit;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash, Default)]
+pub enum Target {
+    ES5 = 0,
+    ES2015,
+    ES2016,
+    ES2017,
+    ES2018,
+    ES2019,
+    ES2020,
+    #[default]
+    ESNext,
+}
+
+impl Target {
+    pub fn supports(self, feature: Feature) -> bool {
+        self >= feature.since()
+    }
+
+    // The names are the ones "Display" prints, plus "es6" for "es2015"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "esnext" => Some(Target::ESNext),
+            "es5" => Some(Target::ES5),
+            "es6" | "es2015" => Some(Target::ES2015),
+            "es2016" => Some(Target::ES2016),
+            "es2017" => Some(Target::ES2017),
+            "es2018" => Some(Target::ES2018),
+            "es2019" => Some(Target::ES2019),
+            "es2020" => Some(Target::ES2020),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Target::ES5 => "es5",
+                Target::ES2015 => "es2015",
+                Target::ES2016 => "es2016",
+                Target::ES2017 => "es2017",
+                Target::ES2018 => "es2018",
+                Target::ES2019 => "es2019",
+                Target::ES2020 => "es2020",
+                Target::ESNext => "esnext",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum Feature {
+    // ES2015
+    ArraySpread,
+    Arrow,
+    Class,
+    ComputedKey,
+    DefaultArgument,
+    Destructuring,
+    ForOf,
+    Generator,
+    LetConst,
+    ObjectMethod,
+    RestArgument,
+    ShorthandProperty,
+    TemplateLiteral,
+
+    // ES2016
+    ExponentOperator,
+
+    // ES2017
+    AsyncAwait,
+
+    // ES2018
+    ObjectRestSpread,
+
+    // ES2020
+    BigInt,
+    ImportMeta,
+    NullishCoalescing,
+    OptionalChain,
+
+    // ESNext
+    ClassField,
+    ClassPrivateMember,
+    ClassStaticBlock,
+}
+
+impl Feature {
+    pub fn since(self) -> Target {
+        match self {
+            Feature::ArraySpread
+            | Feature::Arrow
+            | Feature::Class
+            | Feature::ComputedKey
+            | Feature::DefaultArgument
+            | Feature::Destructuring
+            | Feature::ForOf
+            | Feature::Generator
+            | Feature::LetConst
+            | Feature::ObjectMethod
+            | Feature::RestArgument
+            | Feature::ShorthandProperty
+            | Feature::TemplateLiteral => Target::ES2015,
+            Feature::ExponentOperator => Target::ES2016,
+            Feature::AsyncAwait => Target::ES2017,
+            Feature::ObjectRestSpread => Target::ES2018,
+            Feature::BigInt
+            | Feature::ImportMeta
+            | Feature::NullishCoalescing
+            | Feature::OptionalChain => Target::ES2020,
+            Feature::ClassField | Feature::ClassPrivateMember | Feature::ClassStaticBlock => {
+                Target::ESNext
+            }
+        }
+    }
+
+    // Async generators are the part of a lowered feature that still can't be
+    // lowered. Shorthand properties are lowered by the printer, which prints
+    // "{a: a}" for older targets.
+    pub fn can_be_lowered(self) -> bool {
+        matches!(
+            self,
+            Feature::ArraySpread
+                | Feature::ComputedKey
+                | Feature::DefaultArgument
+                | Feature::Destructuring
+                | Feature::ObjectMethod
+                | Feature::RestArgument
+                | Feature::ShorthandProperty
+                | Feature::Generator
+                | Feature::ExponentOperator
+                | Feature::ObjectRestSpread
+                | Feature::NullishCoalescing
+                | Feature::OptionalChain
+                | Feature::ClassField
+                | Feature::ClassPrivateMember
+                | Feature::ClassStaticBlock
+        )
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Feature::ArraySpread => "Spread in arrays and calls is",
+            Feature::Arrow => "Arrow functions are",
+            Feature::Class => "Classes are",
+            Feature::ComputedKey => "Computed property keys are",
+            Feature::DefaultArgument => "Default arguments are",
+            Feature::Destructuring => "Destructuring is",
+            Feature::ForOf => "For-of loops are",
+            Feature::Generator => "Generator functions are",
+            Feature::LetConst => "Block-scoped declarations are",
+            Feature::ObjectMethod => "Object literal methods are",
+            Feature::RestArgument => "Rest arguments are",
+            Feature::ShorthandProperty => "Shorthand properties are",
+            Feature::TemplateLiteral => "Template literals are",
+            Feature::ExponentOperator => "The \"**\" operator is",
+            Feature::AsyncAwait => "Async functions are",
+            Feature::ObjectRestSpread => "Object rest and spread is",
+            Feature::BigInt => "Big integer literals are",
+            Feature::ImportMeta => "\"import.meta\" is",
+            Feature::NullishCoalescing => "The \"??\" operator is",
+            Feature::OptionalChain => "Optional chaining is",
+            Feature::ClassField => "Class fields are",
+            Feature::ClassPrivateMember => "Private class members are",
+            Feature::ClassStaticBlock => "Class static blocks are",
+        }
+    }
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Default)]
+pub enum UnsupportedSyntax {
+    // Report an error. This is the default since the output would otherwise
+    // fail to parse in the target environment.
+    #[default]
+    Error = 0,
+
+    // Report a warning and leave the syntax as-is. This is useful when the
+    // target is a lower bound and the code is only reached on newer engines.
+    Warn,
+
+    // Report a warning and replace the syntax with code that throws when it's
+    // evaluated, so the rest of the file still parses on old engines
+    Throw,
+}
+
+// Lowering often needs a temporary variable, for example to evaluate "a.b" only
+// once in "a.b?.c". Every lowering pass in a module draws from the same
+// counter so that temporaries are named "_a", "_b", "_c", ... in the order
+// they were created. The names only depend on the input, which keeps the output
+// stable across runs, and two passes can never pick the same name. A name that
+// a symbol in the source already has is skipped, since temporaries aren't in
+// any scope and the renamer never sees them.
+//
+// Temporaries are declared with a "var" at the top of the closest function
+// that contains them, or at the top of the module.
+#[derive(Debug, Clone)]
+pub struct TempRefs {
+    source_index: usize,
+    count: usize,
+
+    // The names of the symbols in the source, which are only collected once a
+    // temporary is needed
+    used_names: Option<HashSet<String>>,
+
+    // One list of temporaries to declare per function that is being visited
+    scopes: Vec<Vec<Reference>>,
+
+    // Helpers such as "__privateGet" are shared by the whole module, so they
+    // are declared along with the temporaries at the top level
+    helpers: HashMap<&'static str, Reference>,
+    helper_decls: Vec<Decl>,
+}
+
+impl TempRefs {
+    pub fn new(source_index: usize) -> Self {
+        Self {
+            source_index,
+            count: 0,
+            used_names: None,
+            scopes: vec![vec![]],
+            helpers: HashMap::new(),
+            helper_decls: vec![],
+        }
+    }
+
+    pub fn source_index(&self) -> usize {
+        self.source_index
+    }
+
+    pub fn generate(&mut self, symbols: &mut SymbolMap) -> Reference {
+        let reference = self.generate_unhoisted(symbols);
+        self.scopes
+            .last_mut()
+            .expect("The module scope is never exited")
+            .push(reference);
+        reference
+    }
+
+    // Like "generate", but the caller declares the temporary itself. This is
+    // used for an argument that replaces a destructuring pattern, for example.
+    pub fn generate_unhoisted(&mut self, symbols: &mut SymbolMap) -> Reference {
+        let source_index = self.source_index;
+        let used_names = self.used_names.get_or_insert_with(|| {
+            symbols.outer[source_index]
+                .iter()
+                .map(|symbol| symbol.name.clone())
+                .collect()
+        });
+        let name = loop {
+            let name = format!("_{}", number_to_minified_name(self.count));
+            self.count += 1;
+            if !used_names.contains(&name) {
+                break name;
+            }
+        };
+        new_symbol(symbols, self.source_index, SymbolKind::Other, name)
+    }
+
+    pub fn enter_function(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    // Returns the declaration for the temporaries of the function, which goes
+    // at the start of its body
+    pub fn exit_function(&mut self, location: Location) -> Option<Stmt> {
+        assert!(self.scopes.len() > 1, "The module scope can't be exited");
+        let refs = self.scopes.pop().unwrap_or_default();
+        declare_temp_refs(location, refs, vec![])
+    }
+
+    // Returns the declaration for the temporaries and helpers at the top level,
+    // which goes at the start of the module. This can be called more than once.
+    pub fn take_module_decls(&mut self, location: Location) -> Option<Stmt> {
+        let refs = std::mem::take(&mut self.scopes[0]);
+        let helpers = std::mem::take(&mut self.helper_decls);
+        declare_temp_refs(location, refs, helpers)
+    }
+}
+
+fn declare_temp_refs(location: Location, refs: Vec<Reference>, helpers: Vec<Decl>) -> Option<Stmt> {
+    if refs.is_empty() && helpers.is_empty() {
+        return None;
+    }
+
+    let decls = refs
+        .into_iter()
+        .map(|reference| Decl {
+            binding: Binding {
+                location,
+                data: Box::new(BindingKind::Identifier { reference }),
+            },
+            value: None,
+        })
+        .chain(helpers)
+        .collect();
+    Some(Stmt {
+        location,
+        data: Box::new(StmtKind::Local {
+            decls,
+            kind: LocalKind::Var,
+            is_export: false,
+            was_ts_import_equals_in_namespace: false,
+        }),
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LowerOptions {
+    pub target: Target,
+    pub unsupported_syntax: UnsupportedSyntax,
+}
+
+pub fn lower_stmts(
+    stmts: &mut Vec<Stmt>,
+    symbols: &mut SymbolMap,
+    temp_refs: &mut TempRefs,
+    source: &Source,
+    log: &Log,
+    options: &LowerOptions,
+) {
+    if options.target == Target::ESNext {
+        return;
+    }
+
+    let mut lowerer = Lowerer {
+        symbols,
+        temp_refs,
+        source,
+        log,
+        options,
+        globals: HashMap::new(),
+        stmts_before: vec![],
+        stmts_after: vec![],
+        private_members: HashMap::new(),
+        generator_arguments: None,
+    };
+    lowerer.visit_stmts(stmts);
+}
+
+struct Lowerer<'a> {
+    symbols: &'a mut SymbolMap,
+    temp_refs: &'a mut TempRefs,
+    source: &'a Source,
+    log: &'a Log,
+    options: &'a LowerOptions,
+
+    // Unbound references to globals such as "Error" and "Object"
+    globals: HashMap<&'static str, Reference>,
+
+    // Statements that go before and after the statement being visited. The
+    // storage for private members is created before the class, and static
+    // fields are assigned after it, for example.
+    stmts_before: Vec<Stmt>,
+    stmts_after: Vec<Stmt>,
+
+    // The private members of the classes that are being lowered
+    private_members: HashMap<Reference, PrivateMember>,
+
+    // This is set inside the body of a generator that is being lowered, where
+    // "arguments" becomes a temporary that is assigned in the outer function.
+    // The temporary is created the first time "arguments" is used.
+    generator_arguments: Option<Option<Reference>>,
+}
+
+// Where a private member of a lowered class is stored. A field is a WeakMap
+// from each object to its value. Methods and accessors are the same for every
+// object, so a WeakSet only records which objects have them and the functions
+// are stored in temporaries of their own.
+#[derive(Debug, Clone, Copy)]
+struct PrivateMember {
+    storage: Reference,
+    method: Option<Reference>,
+    getter: Option<Reference>,
+    setter: Option<Reference>,
+}
+
+// A static member of a lowered class, which is initialized after the class
+// has been created
+enum StaticMember {
+    Field(Property),
+    PrivateField(Reference, Expr),
+
+    // Adds the class to the WeakSet of a private method or accessor
+    Brand(Reference),
+
+    Block(Function),
+}
+
+// What's left to do once the members of a class have been lowered. The
+// caller decides where these go since that depends on how the class is used.
+#[derive(Default)]
+struct LoweredClass {
+    // These create the storage for private members and must be evaluated
+    // before the class is
+    before: Vec<Expr>,
+
+    static_members: Vec<StaticMember>,
+}
+
+// A destructuring pattern from a declaration or from an assignment, which
+// are lowered the same way
+enum Pattern {
+    Missing,
+
+    // An identifier, or a property access in an assignment
+    Target(Expr),
+
+    Array {
+        items: Vec<PatternItem>,
+        has_rest: bool,
+    },
+    Object {
+        items: Vec<PatternItem>,
+    },
+}
+
+struct PatternItem {
+    // The index in an array or the key in an object. A rest element is the
+    // slice of the array that starts at this index, or the object without the
+    // keys before it.
+    key: Expr,
+    is_rest: bool,
+
+    pattern: Pattern,
+    default_value: Option<Expr>,
+}
+
+// Lowering a pattern turns it into assignments to its targets, along with
+// values that are only evaluated for their side effects
+enum PatternStep {
+    Assign(Expr, Expr),
+    Effect(Expr),
+}
+
+// The instructions that the body of a lowered generator returns to the
+// "__generator" helper as "[op, value]". The helper also uses 0 for "next()",
+// 1 for "throw()", and 6 for an exception on its way to a "catch".
+const GENERATOR_RETURN: usize = 2;
+const GENERATOR_JUMP: usize = 3;
+const GENERATOR_YIELD: usize = 4;
+const GENERATOR_YIELD_STAR: usize = 5;
+const GENERATOR_END_FINALLY: usize = 7;
+
+// A lowered generator is a state machine. Its body is split at every "yield"
+// into the cases of a switch on "_a.label", where "_a" is the state that the
+// helper passes to the body:
+//
+//   function* f(a) { var b = yield a; return b }
+//
+// becomes
+//
+//   function f(a) {
+//     var b;
+//     return __generator(this, function(_a) {
+//       switch (_a.label) {
+//         case 0: return [4, a];
+//         case 1: b = _a.sent(); return [2, b];
+//       }
+//     });
+//   }
+//
+// The body runs again for every step, so its variables and functions are
+// declared in the outer function instead.
+struct GeneratorState {
+    state: Reference,
+    ops: Vec<GeneratorOp>,
+    label_count: usize,
+    jump_targets: Vec<JumpTarget>,
+
+    vars: Vec<Reference>,
+    declared_vars: HashSet<Reference>,
+    functions: Vec<Stmt>,
+
+    // Where the first statement that can't be split is
+    unsupported: Option<Location>,
+}
+
+enum GeneratorOp {
+    Stmt(Stmt),
+
+    // The start of a case that jumps can go to. A label that follows an empty
+    // case shares that case.
+    Label(usize),
+
+    // Enters a "try" statement, which starts a case of its own. The helper
+    // routes exceptions and jumps that leave the statement through the
+    // "catch" and "finally" cases.
+    Try {
+        catch: Option<usize>,
+        finally: Option<usize>,
+        end: usize,
+    },
+}
+
+// A statement with a yield that "break" or "continue" can go to
+struct JumpTarget {
+    names: Vec<Reference>,
+    break_label: usize,
+    continue_label: Option<usize>,
+
+    // An unlabeled "break" only goes to loops and switches
+    is_breakable: bool,
+}
+
+impl GeneratorState {
+    fn new(state: Reference) -> Self {
+        Self {
+            state,
+            ops: vec![],
+            label_count: 0,
+            jump_targets: vec![],
+            vars: vec![],
+            declared_vars: HashSet::new(),
+            functions: vec![],
+            unsupported: None,
+        }
+    }
+
+    fn fail(&mut self, location: Location) {
+        self.unsupported.get_or_insert(location);
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.label_count += 1;
+        self.label_count - 1
+    }
+
+    fn mark(&mut self, label: usize) {
+        self.ops.push(GeneratorOp::Label(label));
+    }
+
+    fn emit(&mut self, stmt: Stmt) {
+        if !matches!(stmt.data.as_ref(), StmtKind::Empty) {
+            self.ops.push(GeneratorOp::Stmt(stmt));
+        }
+    }
+
+    fn jump(&mut self, location: Location, label: usize) {
+        let label = number_expr(location, label);
+        self.emit(generator_instruction(location, GENERATOR_JUMP, Some(label)));
+    }
+
+    // "if (test) return [3, label]"
+    fn jump_if(&mut self, test: Expr, label: usize) {
+        let location = test.location;
+        let label = number_expr(location, label);
+        let jump = generator_instruction(location, GENERATOR_JUMP, Some(label));
+        self.emit(if_stmt(test, jump));
+    }
+
+    // "_a.sent()" is the value the generator was resumed with, or throws the
+    // exception that it was resumed with
+    fn sent(&self, location: Location) -> Expr {
+        let state = identifier(location, self.state);
+        call(dot(state, "sent".to_owned(), location), vec![])
+    }
+
+    fn declare(&mut self, reference: Reference) {
+        if self.declared_vars.insert(reference) {
+            self.vars.push(reference);
+        }
+    }
+
+    // Patterns have been lowered already, since generators and destructuring
+    // are both from ES2015
+    fn hoist_binding(&mut self, binding: &Binding) -> Option<Reference> {
+        match binding.data.as_ref() {
+            BindingKind::Identifier { reference } => {
+                self.declare(*reference);
+                Some(*reference)
+            }
+            _ => {
+                self.fail(binding.location);
+                None
+            }
+        }
+    }
+
+    // "var a = 1, b" => "a = 1"
+    fn hoist_decls(&mut self, decls: Vec<Decl>) -> Option<Expr> {
+        let mut values = vec![];
+        for decl in decls {
+            if let (Some(reference), Some(value)) = (self.hoist_binding(&decl.binding), decl.value)
+            {
+                values.push(assign(identifier(decl.binding.location, reference), value));
+            }
+        }
+        join_all_with_comma(values.into_iter())
+    }
+
+    // "for (var a in b)" => "for (a in b)"
+    fn hoist_loop_binding(&mut self, init: &mut Stmt) {
+        if let StmtKind::Local { decls, .. } = init.data.as_mut() {
+            match decls.as_slice() {
+                [decl] => {
+                    if let Some(reference) = self.hoist_binding(&decl.binding) {
+                        *init = expr_stmt(identifier(decl.binding.location, reference));
+                    }
+                }
+                _ => self.fail(init.location),
+            }
+        }
+    }
+
+    // Statements without a yield run as they are inside a case, apart from
+    // what changes now that they're in another function. Declarations are
+    // hoisted, "return" becomes an instruction, and "break" and "continue"
+    // become jumps when they leave the statement. The loops, switches and
+    // labels inside the statement are counted to know when that happens.
+    fn rewrite_plain_stmt(
+        &mut self,
+        stmt: &mut Stmt,
+        loops: usize,
+        breakables: usize,
+        names: &mut Vec<Reference>,
+    ) {
+        ensure_sufficient_stack(|| self.rewrite_plain_stmt_inner(stmt, loops, breakables, names))
+    }
+
+    fn rewrite_plain_stmt_inner(
+        &mut self,
+        stmt: &mut Stmt,
+        loops: usize,
+        breakables: usize,
+        names: &mut Vec<Reference>,
+    ) {
+        let location = stmt.location;
+        match stmt.data.as_mut() {
+            StmtKind::Local { decls, .. } => {
+                *stmt = match self.hoist_decls(std::mem::take(decls)) {
+                    Some(value) => expr_stmt(value),
+                    None => empty_stmt(location),
+                };
+            }
+            StmtKind::Function { .. } => {
+                let function = std::mem::replace(stmt, empty_stmt(location));
+                self.functions.push(function);
+            }
+            StmtKind::Return { value } => {
+                *stmt = generator_instruction(location, GENERATOR_RETURN, value.take());
+            }
+            StmtKind::Break { name } => {
+                if let Some(label) = self.jump_label(name.as_ref(), false, breakables, names) {
+                    let label = number_expr(location, label);
+                    *stmt = generator_instruction(location, GENERATOR_JUMP, Some(label));
+                }
+            }
+            StmtKind::Continue { name } => {
+                if let Some(label) = self.jump_label(name.as_ref(), true, loops, names) {
+                    let label = number_expr(location, label);
+                    *stmt = generator_instruction(location, GENERATOR_JUMP, Some(label));
+                }
+            }
+            StmtKind::Block { stmts } => {
+                for stmt in stmts.iter_mut() {
+                    self.rewrite_plain_stmt(stmt, loops, breakables, names);
+                }
+                stmts.retain(|stmt| !matches!(stmt.data.as_ref(), StmtKind::Empty));
+            }
+            StmtKind::If { yes, no, .. } => {
+                self.rewrite_plain_stmt(yes, loops, breakables, names);
+                if let Some(no) = no {
+                    self.rewrite_plain_stmt(no, loops, breakables, names);
+                }
+            }
+            StmtKind::For { init, body, .. } => {
+                if let Some(StmtKind::Local { decls, .. }) = init.as_mut().map(|i| i.data.as_mut())
+                {
+                    *init = self.hoist_decls(std::mem::take(decls)).map(expr_stmt);
+                }
+                self.rewrite_plain_stmt(body, loops + 1, breakables + 1, names);
+            }
+            StmtKind::ForIn { init, body, .. } | StmtKind::ForOf { init, body, .. } => {
+                self.hoist_loop_binding(init);
+                self.rewrite_plain_stmt(body, loops + 1, breakables + 1, names);
+            }
+            StmtKind::While { body, .. } | StmtKind::DoWhile { body, .. } => {
+                self.rewrite_plain_stmt(body, loops + 1, breakables + 1, names);
+            }
+            StmtKind::Switch { cases, .. } => {
+                for case in cases {
+                    for stmt in &mut case.body {
+                        self.rewrite_plain_stmt(stmt, loops, breakables + 1, names);
+                    }
+                }
+            }
+            StmtKind::Label { name, stmt } => {
+                names.push(name.reference);
+                self.rewrite_plain_stmt(stmt, loops, breakables, names);
+                names.pop();
+            }
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let catch = catch.iter_mut().flat_map(|catch| &mut catch.body);
+                let finally = finally.iter_mut().flat_map(|finally| &mut finally.stmts);
+                for stmt in body.iter_mut().chain(catch).chain(finally) {
+                    self.rewrite_plain_stmt(stmt, loops, breakables, names);
+                }
+            }
+            StmtKind::With { body, .. } => self.rewrite_plain_stmt(body, loops, breakables, names),
+            _ => {}
+        }
+    }
+
+    // Where "break" or "continue" goes when it leaves the statements that run
+    // as they are. "depth" is how many loops, or loops and switches, they
+    // have inside them.
+    fn jump_label(
+        &self,
+        name: Option<&LocationRef>,
+        is_continue: bool,
+        depth: usize,
+        names: &[Reference],
+    ) -> Option<usize> {
+        let mut targets = self.jump_targets.iter().rev();
+        let target = match name {
+            Some(name) if names.contains(&name.reference) => return None,
+            Some(name) => targets.find(|target| target.names.contains(&name.reference))?,
+            None if depth > 0 => return None,
+            None if is_continue => targets.find(|target| target.continue_label.is_some())?,
+            None => targets.find(|target| target.is_breakable)?,
+        };
+        if is_continue {
+            target.continue_label
+        } else {
+            Some(target.break_label)
+        }
+    }
+
+    // Numbers the cases, which turns the labels into case numbers, and
+    // returns the body of the state machine
+    fn finish(&mut self, location: Location) -> Vec<Stmt> {
+        let mut label_cases = vec![0; self.label_count];
+        let mut case_count = 1;
+        let mut is_case_empty = true;
+        for op in &self.ops {
+            match op {
+                GeneratorOp::Label(label) => {
+                    if !is_case_empty {
+                        case_count += 1;
+                        is_case_empty = true;
+                    }
+                    label_cases[*label] = case_count - 1;
+                }
+                _ => is_case_empty = false,
+            }
+        }
+
+        let state = self.state;
+        let label = || dot(identifier(location, state), "label".to_owned(), location);
+        let mut cases: Vec<Vec<Stmt>> = vec![vec![]];
+        for op in std::mem::take(&mut self.ops) {
+            let case = cases.len() - 1;
+            match op {
+                GeneratorOp::Stmt(stmt) => cases[case].push(stmt),
+                GeneratorOp::Label(label_id) if label_cases[label_id] > case => {
+                    // The helper needs the label of the running case to know
+                    // which part of a "try" statement an exception comes from
+                    if !ends_case(&cases[case]) {
+                        let next = number_expr(location, case + 1);
+                        cases[case].push(expr_stmt(assign(label(), next)));
+                    }
+                    cases.push(vec![]);
+                }
+                GeneratorOp::Label(_) => {}
+
+                // "_a.trys.push([start, catch, finally, end])"
+                GeneratorOp::Try {
+                    catch,
+                    finally,
+                    end,
+                } => {
+                    let region = |label: Option<usize>| match label {
+                        Some(label) => number_expr(location, label_cases[label]),
+                        None => expr_missing(location),
+                    };
+                    let items = vec![
+                        number_expr(location, case),
+                        region(catch),
+                        region(finally),
+                        region(Some(end)),
+                    ];
+                    let trys = dot(
+                        identifier(location, self.state),
+                        "trys".to_owned(),
+                        location,
+                    );
+                    let push = call(
+                        dot(trys, "push".to_owned(), location),
+                        vec![expr(location, ExprKind::Array { items })],
+                    );
+                    cases[case].push(expr_stmt(push));
+                }
+            }
+        }
+        let last = cases.last_mut().expect("There is at least one case");
+        if !ends_case(last) {
+            last.push(generator_instruction(location, GENERATOR_RETURN, None));
+        }
+        for stmt in cases.iter_mut().flatten() {
+            patch_jumps(stmt, &label_cases);
+        }
+
+        if cases.len() == 1 {
+            return cases.pop().unwrap_or_default();
+        }
+        let cases = cases
+            .into_iter()
+            .enumerate()
+            .map(|(i, body)| Case {
+                value: Some(number_expr(location, i)),
+                body,
+            })
+            .collect();
+        vec![Stmt {
+            location,
+            data: Box::new(StmtKind::Switch {
+                test: label(),
+                body_location: location,
+                cases,
+            }),
+        }]
+    }
+}
+
+// The result of the dispatcher for a single use of a feature
+enum Lowered {
+    // The target supports the feature, or it doesn't and the problem has been
+    // reported. The syntax is left as-is.
+    Done,
+
+    // The caller should replace the syntax with equivalent older syntax
+    Lower,
+
+    // The syntax should be replaced by code that throws
+    Throw(String),
+}
+
+impl<'a> Lowerer<'a> {
+    fn lower(&mut self, feature: Feature, location: Location) -> Lowered {
+        if self.options.target.supports(feature) {
+            return Lowered::Done;
+        }
+        if feature.can_be_lowered() {
+            return Lowered::Lower;
+        }
+        self.unsupported(feature, location)
+    }
+
+    fn lowers(&mut self, feature: Feature, location: Location) -> bool {
+        matches!(self.lower(feature, location), Lowered::Lower)
+    }
+
+    // Everything that can't be lowered ends up here
+    fn unsupported(&mut self, feature: Feature, location: Location) -> Lowered {
+        let text = format!(
+            "{} not available in the configured target environment ({})",
+            feature.description(),
+            self.options.target
+        );
+        match self.options.unsupported_syntax {
+            UnsupportedSyntax::Error => {
+                self.log.add_error(self.source, location, text);
+                Lowered::Done
+            }
+            UnsupportedSyntax::Warn => {
+                self.log.add_warning(self.source, location, text);
+                Lowered::Done
+            }
+            UnsupportedSyntax::Throw => {
+                self.log.add_warning(self.source, location, text.clone());
+                Lowered::Throw(text)
+            }
+        }
+    }
+
+    fn global_ref(&mut self, name: &'static str) -> Reference {
+        if let Some(reference) = self.globals.get(name) {
+            return *reference;
+        }
+
+        let source_index = self.temp_refs.source_index();
+        let reference = new_symbol(
+            self.symbols,
+            source_index,
+            SymbolKind::Unbound,
+            name.to_owned(),
+        );
+        self.symbols[reference].must_not_be_renamed = true;
+        self.globals.insert(name, reference);
+        reference
+    }
+
+    // "throw new Error(text)"
+    fn throw_stmt(&mut self, location: Location, text: String) -> Stmt {
+        let error = Expr {
+            location,
+            data: Box::new(ExprKind::Identifier {
+                reference: self.global_ref("Error"),
+            }),
+        };
+        let message = Expr {
+            location,
+            data: Box::new(ExprKind::String {
+                value: string_to_utf16(&text),
+            }),
+        };
+        Stmt {
+            location,
+            data: Box::new(StmtKind::Throw {
+                value: Expr {
+                    location,
+                    data: Box::new(ExprKind::New {
+                        target: error,
+                        args: vec![message],
+                        can_be_unwrapped_if_unused: false,
+                    }),
+                },
+            }),
+        }
+    }
+
+    // "function() { throw new Error(text) }()" which works in every target
+    fn throw_expr(&mut self, location: Location, text: String) -> Expr {
+        let body = self.throw_stmt(location, text);
+        let function = Function {
+            name: None,
+            args: Vec::<Arg>::new(),
+            is_async: false,
+            is_generator: false,
+            has_rest_arg: false,
+            body: FunctionBody {
+                location,
+                stmts: vec![body],
+            },
+        };
+        Expr {
+            location,
+            data: Box::new(ExprKind::Call {
+                target: Expr {
+                    location,
+                    data: Box::new(ExprKind::Function { function }),
+                },
+                args: vec![],
+                optional_chain: OptionalChain::None,
+                is_parenthesized: false,
+                is_direct_eval: false,
+                can_be_unwrapped_if_unused: false,
+            }),
+        }
+    }
+
+    fn visit_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        let mut i = 0;
+        while i < stmts.len() {
+            self.visit_stmt(&mut stmts[i]);
+            if !self.stmts_before.is_empty() {
+                let stmts_before = std::mem::take(&mut self.stmts_before);
+                let count = stmts_before.len();
+                stmts.splice(i..i, stmts_before);
+                i += count;
+            }
+            i += 1;
+            if !self.stmts_after.is_empty() {
+                let stmts_after = std::mem::take(&mut self.stmts_after);
+                let count = stmts_after.len();
+                stmts.splice(i..i, stmts_after);
+                i += count;
+            }
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        ensure_sufficient_stack(|| self.visit_stmt_inner(stmt))
+    }
+
+    fn visit_stmt_inner(&mut self, stmt: &mut Stmt) {
+        let location = stmt.location;
+        let feature = match stmt.data.as_ref() {
+            StmtKind::Local {
+                kind: LocalKind::Let,
+                ..
+            }
+            | StmtKind::Local {
+                kind: LocalKind::Const,
+                ..
+            } => Some(Feature::LetConst),
+            StmtKind::ForOf { .. } => Some(Feature::ForOf),
+            StmtKind::Class { .. } => Some(Feature::Class),
+            _ => None,
+        };
+        if let Some(feature) = feature {
+            if let Lowered::Throw(text) = self.lower(feature, location) {
+                *stmt = self.throw_stmt(location, text);
+                return;
+            }
+        }
+
+        match stmt.data.as_mut() {
+            StmtKind::Block { stmts } | StmtKind::Namespace { stmts, .. } => {
+                self.visit_stmts(stmts)
+            }
+            StmtKind::ExportDefault {
+                value: ExprOrStmt::Expr(value),
+                ..
+            }
+            | StmtKind::ExportEquals { value }
+            | StmtKind::Expr { value }
+            | StmtKind::Throw { value }
+            | StmtKind::Return { value: Some(value) } => self.visit_expr(value),
+            StmtKind::Enum { values, .. } => {
+                for value in values.iter_mut().filter_map(|v| v.value.as_mut()) {
+                    self.visit_expr(value);
+                }
+            }
+            StmtKind::Function { function, .. } => self.visit_function(function),
+            StmtKind::Class { class, .. } => {
+                let lowered = self.visit_class(class);
+                self.stmts_before
+                    .extend(lowered.before.into_iter().map(expr_stmt));
+                if let Some(name) = &class.name {
+                    for member in lowered.static_members {
+                        let target = identifier(location, name.reference);
+                        let value = initialize_static_member(target, member);
+                        self.stmts_after.push(expr_stmt(value));
+                    }
+                }
+            }
+            StmtKind::ExportDefault {
+                default_name,
+                value: ExprOrStmt::Stmt(stmt),
+            } => {
+                // Static members are initialized after the class is declared,
+                // so it needs a name
+                if let StmtKind::Class { class, .. } = stmt.data.as_mut() {
+                    if class.name.is_none()
+                        && class.properties.iter().any(|p| member_feature(p).is_some())
+                        && !self.options.target.supports(Feature::ClassField)
+                    {
+                        class.name = Some(default_name.clone());
+                    }
+                }
+                self.visit_stmt(stmt);
+            }
+            StmtKind::Label { stmt, .. } => self.visit_stmt(stmt),
+            StmtKind::If { test, yes, no } => {
+                self.visit_expr(test);
+                self.visit_stmt(yes);
+                if let Some(no) = no {
+                    self.visit_stmt(no);
+                }
+            }
+            StmtKind::For {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                if let Some(init) = init {
+                    self.visit_stmt(init);
+                }
+                if let Some(test) = test {
+                    self.visit_expr(test);
+                }
+                if let Some(update) = update {
+                    self.visit_expr(update);
+                }
+                self.visit_stmt(body);
+            }
+            StmtKind::ForIn { init, value, body }
+            | StmtKind::ForOf {
+                init, value, body, ..
+            } => {
+                self.move_for_pattern_into_body(init, body);
+                self.visit_stmt(init);
+                self.visit_expr(value);
+                self.visit_stmt(body);
+            }
+            StmtKind::DoWhile { body, test } | StmtKind::While { test, body } => {
+                self.visit_stmt(body);
+                self.visit_expr(test);
+            }
+            StmtKind::With { value, body, .. } => {
+                self.visit_expr(value);
+                self.visit_stmt(body);
+            }
+            StmtKind::Catch(catch) => self.visit_catch(catch),
+            StmtKind::Finally(finally) => self.visit_stmts(&mut finally.stmts),
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.visit_stmts(body);
+                if let Some(catch) = catch {
+                    self.visit_catch(catch);
+                }
+                if let Some(finally) = finally {
+                    self.visit_stmts(&mut finally.stmts);
+                }
+            }
+            StmtKind::Switch { test, cases, .. } => {
+                self.visit_expr(test);
+                for case in cases {
+                    if let Some(value) = &mut case.value {
+                        self.visit_expr(value);
+                    }
+                    self.visit_stmts(&mut case.body);
+                }
+            }
+            StmtKind::Local { decls, .. } => {
+                for decl in decls.iter_mut() {
+                    self.visit_binding(&mut decl.binding);
+                    if let Some(value) = &mut decl.value {
+                        self.visit_expr(value);
+                    }
+                }
+                let is_lowered = decls
+                    .iter()
+                    .any(|decl| decl.value.is_some() && self.lowers_binding(&decl.binding));
+                if is_lowered {
+                    *decls = self.lower_decls(std::mem::take(decls));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // "catch ({a}) {}" => "catch (_a) { let {a} = _a; }"
+    fn visit_catch(&mut self, catch: &mut Catch) {
+        if let Some(binding) = &mut catch.binding {
+            if self.lowers_binding(binding) {
+                let kind = if self.options.target.supports(Feature::LetConst) {
+                    LocalKind::Let
+                } else {
+                    LocalKind::Var
+                };
+                let decl = self.move_binding_into_temp(binding);
+                catch.body.insert(0, local_stmt(kind, vec![decl]));
+            }
+            self.visit_binding(binding);
+        }
+        self.visit_stmts(&mut catch.body);
+    }
+
+    // The loop variable of a for-in or for-of loop is assigned once per
+    // iteration, so a pattern is moved to the start of the body:
+    //
+    //   for (const [a, b] of c) {}  =>  for (const _a of c) { const [a, b] = _a; }
+    //   for ([a, b] of c) {}        =>  for (_a of c) { [a, b] = _a; }
+    //
+    // The pattern is lowered when the body is visited.
+    fn move_for_pattern_into_body(&mut self, init: &mut Stmt, body: &mut Stmt) {
+        let location = init.location;
+        let first = match init.data.as_mut() {
+            StmtKind::Local { decls, kind, .. } => match decls.as_mut_slice() {
+                [decl] if decl.value.is_none() && self.lowers_binding(&decl.binding) => {
+                    let decl = self.move_binding_into_temp(&mut decl.binding);
+                    local_stmt(*kind, vec![decl])
+                }
+                _ => return,
+            },
+            StmtKind::Expr { value } if self.lowers_assign_pattern(value) => {
+                let temp = self.temp_refs.generate(self.symbols);
+                let pattern = std::mem::replace(value, identifier(location, temp));
+                expr_stmt(assign(pattern, identifier(location, temp)))
+            }
+            _ => return,
+        };
+        match body.data.as_mut() {
+            StmtKind::Block { stmts } => stmts.insert(0, first),
+            _ => {
+                let location = body.location;
+                let rest = std::mem::replace(body, empty_stmt(location));
+                *body = Stmt {
+                    location,
+                    data: Box::new(StmtKind::Block {
+                        stmts: vec![first, rest],
+                    }),
+                };
+            }
+        }
+    }
+
+    // Replaces the pattern with a temporary and returns a declaration that
+    // destructures the temporary instead
+    fn move_binding_into_temp(&mut self, binding: &mut Binding) -> Decl {
+        let location = binding.location;
+        let temp = self.temp_refs.generate_unhoisted(self.symbols);
+        let pattern = std::mem::replace(
+            binding,
+            Binding {
+                location,
+                data: Box::new(BindingKind::Identifier { reference: temp }),
+            },
+        );
+        Decl {
+            binding: pattern,
+            value: Some(identifier(location, temp)),
+        }
+    }
+
+    // Patterns are lowered as a whole, either when the target doesn't have
+    // destructuring or when it doesn't have the object rest they use
+    fn lowers_binding(&mut self, binding: &Binding) -> bool {
+        let location = binding.location;
+        matches!(
+            binding.data.as_ref(),
+            BindingKind::Array { .. } | BindingKind::Object { .. }
+        ) && (self.lowers(Feature::Destructuring, location)
+            || binding_has_object_rest(binding) && self.lowers(Feature::ObjectRestSpread, location))
+    }
+
+    fn lowers_assign_pattern(&mut self, pattern: &Expr) -> bool {
+        let location = pattern.location;
+        matches!(
+            pattern.data.as_ref(),
+            ExprKind::Array { .. } | ExprKind::Object { .. }
+        ) && (self.lowers(Feature::Destructuring, location)
+            || assign_pattern_has_object_rest(pattern)
+                && self.lowers(Feature::ObjectRestSpread, location))
+    }
+
+    // Patterns are lowered by whatever contains them, since that decides
+    // where the values are declared
+    fn visit_binding(&mut self, binding: &mut Binding) {
+        ensure_sufficient_stack(|| self.visit_binding_inner(binding))
+    }
+
+    fn visit_binding_inner(&mut self, binding: &mut Binding) {
+        match binding.data.as_mut() {
+            BindingKind::Array { items, .. } => {
+                for item in items {
+                    self.visit_binding(&mut item.binding);
+                    if let Some(default_value) = &mut item.default_value {
+                        self.visit_expr(default_value);
+                    }
+                }
+            }
+            BindingKind::Object { properties } => {
+                for property in properties {
+                    if property.is_computed {
+                        self.visit_expr(&mut property.key);
+                    }
+                    self.visit_binding(&mut property.value);
+                    if let Some(default_value) = &mut property.default_value {
+                        self.visit_expr(default_value);
+                    }
+                }
+            }
+            BindingKind::Missing | BindingKind::Identifier { .. } => {}
+        }
+    }
+
+    // Like "visit_binding" for the left side of a destructuring assignment
+    fn visit_assign_pattern(&mut self, pattern: &mut Expr) {
+        ensure_sufficient_stack(|| self.visit_assign_pattern_inner(pattern))
+    }
+
+    fn visit_assign_pattern_inner(&mut self, pattern: &mut Expr) {
+        match pattern.data.as_mut() {
+            ExprKind::Array { items } => {
+                for item in items {
+                    self.visit_assign_pattern(item);
+                }
+            }
+            ExprKind::Object { properties } => {
+                for property in properties {
+                    if property.is_computed {
+                        self.visit_expr(&mut property.key);
+                    }
+                    if let Some(value) = &mut property.value {
+                        self.visit_assign_pattern(value);
+                    }
+                    if let Some(initializer) = &mut property.initializer {
+                        self.visit_expr(initializer);
+                    }
+                }
+            }
+            ExprKind::Spread { value } => self.visit_assign_pattern(value),
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpAssign,
+                left,
+                right,
+            } => {
+                self.visit_assign_pattern(left);
+                self.visit_expr(right);
+            }
+            _ => self.visit_expr(pattern),
+        }
+    }
+
+    fn visit_function(&mut self, function: &mut Function) {
+        let location = function.body.location;
+        if function.is_async {
+            self.lower(Feature::AsyncAwait, location);
+        }
+
+        // Async generators can't be lowered, but async functions are reported
+        // already
+        let is_lowered_generator = function.is_generator
+            && !function.is_async
+            && self.lowers(Feature::Generator, location);
+        let outer_arguments = std::mem::replace(
+            &mut self.generator_arguments,
+            if is_lowered_generator {
+                Some(None)
+            } else {
+                None
+            },
+        );
+        self.visit_args_and_body(
+            &mut function.args,
+            &mut function.has_rest_arg,
+            &mut function.body,
+            true,
+        );
+        let arguments = std::mem::replace(&mut self.generator_arguments, outer_arguments);
+        if is_lowered_generator {
+            self.lower_generator(function, arguments.flatten());
+        }
+    }
+
+    // Patterns in the arguments are moved to the start of the body, where
+    // they're lowered along with the rest of it:
+    //
+    //   function f({a}, [b]) {}  =>  function f(_a, _b) { var {a} = _a, [b] = _b; }
+    //
+    // Default values and rest arguments are read from "arguments" instead for
+    // targets that don't have them. So are the arguments after the first one
+    // that's read, which keeps the "length" of the function:
+    //
+    //   function f({a} = {}, b, ...c) {}
+    //
+    // becomes
+    //
+    //   function f() {
+    //     var {a} = arguments.length > 0 && arguments[0] !== void 0 ? arguments[0] : {},
+    //       b = arguments.length > 1 ? arguments[1] : void 0,
+    //       c = Array.prototype.slice.call(arguments, 2);
+    //   }
+    //
+    // Arrow functions don't have their own "arguments", but they're reported
+    // for those targets anyway.
+    fn visit_args_and_body(
+        &mut self,
+        args: &mut Vec<Arg>,
+        has_rest_arg: &mut bool,
+        body: &mut FunctionBody,
+        has_arguments: bool,
+    ) {
+        let mut first_read = None;
+        if has_arguments {
+            if let Some(index) = args.iter().position(|arg| arg.default_.is_some()) {
+                if self.lowers(Feature::DefaultArgument, args[index].binding.location) {
+                    first_read = Some(index);
+                }
+            }
+            if let (true, None, Some(rest)) = (*has_rest_arg, first_read, args.last()) {
+                if self.lowers(Feature::RestArgument, rest.binding.location) {
+                    first_read = Some(args.len() - 1);
+                }
+            }
+        }
+        let read = first_read.map_or_else(Vec::new, |first| args.split_off(first));
+
+        let mut decls = vec![];
+        for arg in args.iter_mut() {
+            if self.lowers_binding(&arg.binding) {
+                decls.push(self.move_binding_into_temp(&mut arg.binding));
+            }
+            self.visit_binding(&mut arg.binding);
+            if let Some(default) = &mut arg.default_ {
+                self.visit_expr(default);
+            }
+        }
+
+        // These are visited along with the body
+        let count = read.len();
+        for (i, arg) in read.into_iter().enumerate() {
+            let location = arg.binding.location;
+            let index = first_read.unwrap_or_default() + i;
+            let value = if *has_rest_arg && i + 1 == count {
+                self.rest_arguments(location, index)
+            } else {
+                self.read_argument(location, index, arg.default_)
+            };
+            decls.push(Decl {
+                binding: arg.binding,
+                value: Some(value),
+            });
+        }
+        if first_read.is_some() {
+            *has_rest_arg = false;
+        }
+        if !decls.is_empty() {
+            let index = prologue_len(&body.stmts);
+            body.stmts.insert(index, local_stmt(LocalKind::Var, decls));
+        }
+        self.visit_function_body(body);
+    }
+
+    // "arguments.length > i && arguments[i] !== void 0 ? arguments[i] : value"
+    fn read_argument(&mut self, location: Location, index: usize, default: Option<Expr>) -> Expr {
+        let arguments_ref = self.global_ref("arguments");
+        let arguments = || identifier(location, arguments_ref);
+        let is_passed = binary(
+            OperatorCode::BinOpGt,
+            dot(arguments(), "length".to_owned(), location),
+            number_expr(location, index),
+        );
+        let argument = || index_expr(arguments(), number_expr(location, index));
+        let (test, no) = match default {
+            Some(default) => (
+                binary(
+                    OperatorCode::BinOpLogicalAnd,
+                    is_passed,
+                    binary(
+                        OperatorCode::BinOpStrictNe,
+                        argument(),
+                        expr(location, ExprKind::Undefined),
+                    ),
+                ),
+                default,
+            ),
+            None => (is_passed, expr(location, ExprKind::Undefined)),
+        };
+        expr(
+            location,
+            ExprKind::If {
+                test,
+                yes: argument(),
+                no,
+            },
+        )
+    }
+
+    // "Array.prototype.slice.call(arguments, i)"
+    fn rest_arguments(&mut self, location: Location, index: usize) -> Expr {
+        let array = identifier(location, self.global_ref("Array"));
+        let prototype = dot(array, "prototype".to_owned(), location);
+        let slice = dot(prototype, "slice".to_owned(), location);
+        let arguments = identifier(location, self.global_ref("arguments"));
+        call(
+            dot(slice, "call".to_owned(), location),
+            vec![arguments, number_expr(location, index)],
+        )
+    }
+
+    // Default values of arguments can't see variables declared in the body,
+    // so temporaries they need are declared in the enclosing function instead
+    fn visit_function_body(&mut self, body: &mut FunctionBody) {
+        self.temp_refs.enter_function();
+        self.visit_stmts(&mut body.stmts);
+        if let Some(decls) = self.temp_refs.exit_function(body.location) {
+            // Keep directives such as "use strict" first
+            let index = prologue_len(&body.stmts);
+            body.stmts.insert(index, decls);
+        }
+    }
+
+    // Fields, private members, and static blocks are lowered together since
+    // they're all initialized in order. The private members are known before
+    // the class body is visited so that the body can use their storage.
+    fn visit_class(&mut self, class: &mut Class) -> LoweredClass {
+        let is_lowered = self.should_lower_class(class);
+        let before = if is_lowered {
+            self.declare_private_members(class)
+        } else {
+            vec![]
+        };
+
+        if let Some(extends) = &mut class.extends {
+            self.visit_expr(extends);
+        }
+        for property in &mut class.properties {
+            self.visit_expr(&mut property.key);
+            if let Some(value) = &mut property.value {
+                self.visit_expr(value);
+            }
+            if let Some(initializer) = &mut property.initializer {
+                self.visit_expr(initializer);
+            }
+        }
+
+        if is_lowered {
+            self.lower_class_members(class, before)
+        } else {
+            LoweredClass::default()
+        }
+    }
+
+    // The features are all as new as each other, so a target supports either
+    // all of them or none
+    fn should_lower_class(&mut self, class: &Class) -> bool {
+        let (feature, location) = match class
+            .properties
+            .iter()
+            .find_map(|p| Some((member_feature(p)?, p.key.location)))
+        {
+            Some(found) => found,
+            None => return false,
+        };
+        if !matches!(self.lower(feature, location), Lowered::Lower) {
+            return false;
+        }
+
+        // Computed keys must be evaluated in order with the other keys of the
+        // class, and "super()" calls that aren't at the top level of the
+        // constructor would each need the fields
+        let has_computed_field = class
+            .properties
+            .iter()
+            .any(|p| p.value.is_none() && p.is_computed);
+        let is_derived = class.extends.is_some();
+        let has_nested_super_call = match class.properties.iter().position(is_constructor) {
+            Some(index) => super_call_index(&class.properties[index]).is_none(),
+            None => false,
+        };
+        if has_computed_field || (is_derived && has_nested_super_call) {
+            self.unsupported(feature, location);
+            return false;
+        }
+        true
+    }
+
+    // Creates the storage for the private members of the class:
+    //
+    //   #a = 1;     =>  _a = new WeakMap()
+    //   #b() {}     =>  _b = new WeakSet()  and a temporary for the method
+    //
+    // A getter and a setter with the same name share a WeakSet.
+    fn declare_private_members(&mut self, class: &Class) -> Vec<Expr> {
+        let mut before = vec![];
+        for property in &class.properties {
+            let reference = match private_ref(&property.key) {
+                Some(reference) => reference,
+                None => continue,
+            };
+            let location = property.key.location;
+            let mut member = match self.private_members.get(&reference) {
+                Some(member) => *member,
+                None => {
+                    let storage = self.temp_refs.generate(self.symbols);
+                    let constructor = if property.value.is_none() {
+                        "WeakMap"
+                    } else {
+                        "WeakSet"
+                    };
+                    let constructor = identifier(location, self.global_ref(constructor));
+                    let value = expr(
+                        location,
+                        ExprKind::New {
+                            target: constructor,
+                            args: vec![],
+                            can_be_unwrapped_if_unused: false,
+                        },
+                    );
+                    before.push(assign(identifier(location, storage), value));
+                    PrivateMember {
+                        storage,
+                        method: None,
+                        getter: None,
+                        setter: None,
+                    }
+                }
+            };
+            if property.value.is_some() {
+                let function = Some(self.temp_refs.generate(self.symbols));
+                match property.kind {
+                    PropertyKind::PropertyGet => member.getter = function,
+                    PropertyKind::PropertySet => member.setter = function,
+                    _ => member.method = function,
+                }
+            }
+            self.private_members.insert(reference, member);
+        }
+        before
+    }
+
+    fn visit_exprs(&mut self, exprs: &mut [Expr]) {
+        for expr in exprs {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        ensure_sufficient_stack(|| self.visit_expr_inner(expr))
+    }
+
+    fn visit_expr_inner(&mut self, expr: &mut Expr) {
+        let location = expr.location;
+
+        // An optional chain that uses a private member is lowered first, which
+        // leaves property accesses that can be lowered like any other
+        if self.has_private_link(expr) && has_optional_chain(expr) {
+            let data = std::mem::replace(expr.data.as_mut(), ExprKind::Missing);
+            *expr = self.lower_expr(location, data);
+            return self.visit_expr(expr);
+        }
+
+        let feature = match expr.data.as_ref() {
+            ExprKind::BigInt { .. } => Some(Feature::BigInt),
+            ExprKind::ImportMeta => Some(Feature::ImportMeta),
+            ExprKind::Arrow { .. } => Some(Feature::Arrow),
+            ExprKind::Class { .. } => Some(Feature::Class),
+            ExprKind::Template { .. } => Some(Feature::TemplateLiteral),
+            ExprKind::Await { .. } => Some(Feature::AsyncAwait),
+
+            // A chain such as "a?.b.c" is lowered as a whole from the outermost
+            // expression since "?." skips the rest of the chain
+            ExprKind::Call { .. } | ExprKind::Dot { .. } | ExprKind::Index { .. }
+                if has_optional_chain(expr) =>
+            {
+                Some(Feature::OptionalChain)
+            }
+            ExprKind::Unary {
+                op_code: OperatorCode::UnOpDelete,
+                value,
+            } if has_optional_chain(value) => Some(Feature::OptionalChain),
+
+            ExprKind::Binary { op_code, .. } => match op_code {
+                OperatorCode::BinOpNullishCoalescing => Some(Feature::NullishCoalescing),
+                OperatorCode::BinOpPow | OperatorCode::BinOpPowAssign => {
+                    Some(Feature::ExponentOperator)
+                }
+                _ => None,
+            },
+            ExprKind::Object { properties } => {
+                if properties
+                    .iter()
+                    .any(|p| p.kind == PropertyKind::PropertySpread)
+                {
+                    Some(Feature::ObjectRestSpread)
+                } else if properties.iter().any(|p| p.is_computed) {
+                    Some(Feature::ComputedKey)
+                } else {
+                    None
+                }
+            }
+            ExprKind::Array { items } | ExprKind::New { args: items, .. } if has_spread(items) => {
+                Some(Feature::ArraySpread)
+            }
+            _ => None,
+        };
+        let lowered = match feature {
+            Some(feature) => self.lower(feature, location),
+            None => Lowered::Done,
+        };
+        if let Lowered::Throw(text) = lowered {
+            *expr = self.throw_expr(location, text);
+            return;
+        }
+
+        let mut lowered_class = LoweredClass::default();
+        match expr.data.as_mut() {
+            ExprKind::Call { .. } | ExprKind::Dot { .. } | ExprKind::Index { .. } => {
+                self.visit_chain(expr)
+            }
+            ExprKind::Unary {
+                op_code: OperatorCode::UnOpDelete,
+                value,
+            } => self.visit_chain(value),
+
+            // The private member is written to, so it's lowered as a whole
+            // once the object has been visited
+            ExprKind::Unary { op_code, value }
+                if op_code.is_unary_update() && self.private_member(value).is_some() =>
+            {
+                self.visit_chain_target(optional_chain(value), chain_target_mut(value))
+            }
+            ExprKind::Binary {
+                op_code,
+                left,
+                right,
+            } if op_code.is_binary_assign() && self.private_member(left).is_some() => {
+                self.visit_chain_target(optional_chain(left), chain_target_mut(left));
+                self.visit_expr(right);
+            }
+
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpAssign,
+                left,
+                right,
+            } if matches!(
+                left.data.as_ref(),
+                ExprKind::Array { .. } | ExprKind::Object { .. }
+            ) =>
+            {
+                self.visit_assign_pattern(left);
+                self.visit_expr(right);
+                if self.lowers_assign_pattern(left) {
+                    let pattern = take_expr(left);
+                    let value = take_expr(right);
+                    *expr = self.lower_destructuring_assign(pattern, value);
+                }
+            }
+
+            ExprKind::Array { items } => self.visit_exprs(items),
+            ExprKind::Unary { value, .. }
+            | ExprKind::Spread { value }
+            | ExprKind::Await { value }
+            | ExprKind::Yield { value, .. }
+            | ExprKind::Import { expr: value } => self.visit_expr(value),
+            ExprKind::Binary { left, right, .. } => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+            }
+            ExprKind::New { target, args, .. } => {
+                self.visit_expr(target);
+                self.visit_exprs(args);
+            }
+            ExprKind::RuntimeCall { args, .. } => self.visit_exprs(args),
+            ExprKind::Arrow {
+                is_async,
+                args,
+                has_rest_arg,
+                body,
+                ..
+            } => {
+                if *is_async {
+                    self.lower(Feature::AsyncAwait, location);
+                }
+                self.visit_args_and_body(args, has_rest_arg, body, false);
+            }
+            ExprKind::Function { function } => self.visit_function(function),
+            ExprKind::Class { class } => lowered_class = self.visit_class(class),
+            ExprKind::Object { properties } => {
+                for property in properties.iter_mut() {
+                    self.visit_expr(&mut property.key);
+                    if let Some(value) = &mut property.value {
+                        self.visit_expr(value);
+                    }
+                    if let Some(initializer) = &mut property.initializer {
+                        self.visit_expr(initializer);
+                    }
+                }
+                if let Some(text) = self.lower_object_methods(properties) {
+                    *expr = self.throw_expr(location, text);
+                    return;
+                }
+            }
+            ExprKind::Template { tag, parts, .. } => {
+                self.visit_expr(tag);
+                for part in parts {
+                    self.visit_expr(&mut part.value);
+                }
+            }
+            ExprKind::If { test, yes, no } => {
+                self.visit_expr(test);
+                self.visit_expr(yes);
+                self.visit_expr(no);
+            }
+            ExprKind::Identifier { reference } => {
+                if let Some(temp) = self.generator_arguments_ref(*reference) {
+                    *expr = identifier(location, temp);
+                }
+            }
+            _ => {}
+        }
+
+        self.lower_private_access(expr);
+        self.lower_spread_call(expr);
+
+        // "(_a = new WeakMap(), _b = class {}, _b.c = d, _b)"
+        let LoweredClass {
+            before: mut values,
+            static_members,
+        } = lowered_class;
+        if !static_members.is_empty() {
+            let temp = self.temp_refs.generate(self.symbols);
+            let class = std::mem::replace(expr, identifier(location, temp));
+            values.push(assign(identifier(location, temp), class));
+            for member in static_members {
+                values.push(initialize_static_member(identifier(location, temp), member));
+            }
+            values.push(identifier(location, temp));
+        } else if !values.is_empty() {
+            values.push(std::mem::replace(expr, expr_missing(location)));
+        }
+        if !values.is_empty() {
+            *expr = join_all_with_comma(values.into_iter()).expect("There is at least one value");
+        }
+
+        if let Lowered::Lower = lowered {
+            let data = std::mem::replace(expr.data.as_mut(), ExprKind::Missing);
+            *expr = self.lower_expr(location, data);
+        }
+    }
+
+    fn lower_object_chunk(&mut self, location: Location, properties: Vec<Property>) -> Expr {
+        if self.options.target.supports(Feature::ComputedKey) {
+            expr(location, ExprKind::Object { properties })
+        } else {
+            self.lower_computed_keys(location, properties)
+        }
+    }
+
+    // Visits the parts of a chain of property accesses and calls without
+    // treating the inner links as chains of their own
+    fn visit_chain(&mut self, expr: &mut Expr) {
+        ensure_sufficient_stack(|| self.visit_chain_inner(expr))
+    }
+
+    fn visit_chain_inner(&mut self, expr: &mut Expr) {
+        let link = optional_chain(expr);
+        let (link, target) = match expr.data.as_mut() {
+            ExprKind::Dot { target, .. } => (link, target),
+            ExprKind::Index { target, index, .. } => {
+                self.visit_expr(index);
+                (link, target)
+            }
+            ExprKind::Call { target, args, .. } => {
+                self.visit_exprs(args);
+
+                // "a.#b()" is lowered as a whole so that "a" is the "this"
+                if self.private_member(target).is_some() {
+                    (optional_chain(target), chain_target_mut(target))
+                } else {
+                    (link, target)
+                }
+            }
+            _ => return self.visit_expr(expr),
+        };
+        self.visit_chain_target(link, target);
+    }
+
+    // The link at the top of a chain is lowered by "visit_expr", and the
+    // other links are lowered here
+    fn visit_chain_target(&mut self, link: OptionalChain, target: &mut Expr) {
+        if ends_chain(link, target) {
+            self.visit_expr(target);
+        } else {
+            self.visit_chain(target);
+            self.lower_private_access(target);
+            self.lower_spread_call(target);
+        }
+    }
+
+    // "{a() {}}" => "{a: function() {}}". A method that uses "super" can't be
+    // a function, since only methods know the object they belong to.
+    fn lower_object_methods(&mut self, properties: &mut [Property]) -> Option<String> {
+        if self.options.target.supports(Feature::ObjectMethod) {
+            return None;
+        }
+        for property in properties {
+            let function = match (&property.value, property.is_method, property.kind) {
+                (Some(value), true, PropertyKind::PropertyNormal) => match value.data.as_ref() {
+                    ExprKind::Function { function } => function,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let location = property.key.location;
+            if uses_super(function) {
+                if let Lowered::Throw(text) = self.unsupported(Feature::ObjectMethod, location) {
+                    return Some(text);
+                }
+            } else if self.lowers(Feature::ObjectMethod, location) {
+                property.is_method = false;
+            }
+        }
+        None
+    }
+
+    // Spread arguments are passed as an array with "apply":
+    //
+    //   f(...a)    =>  f.apply(void 0, __spread([], a))
+    //   a.f(...b)  =>  (_a = a).f.apply(_a, __spread([], b))
+    //
+    // Calls in an optional chain are lowered along with the chain, and
+    // "super(...a)" is only in classes, which are reported for these targets.
+    fn lower_spread_call(&mut self, value: &mut Expr) {
+        let location = value.location;
+        match value.data.as_ref() {
+            ExprKind::Call {
+                target,
+                args,
+                optional_chain: OptionalChain::None,
+                ..
+            } if has_spread(args) && !matches!(target.data.as_ref(), ExprKind::Super) => {}
+            _ => return,
+        }
+        if !self.lowers(Feature::ArraySpread, location) {
+            return;
+        }
+        if let ExprKind::Call { target, args, .. } =
+            std::mem::replace(value.data.as_mut(), ExprKind::Missing)
+        {
+            let (method, this) = self.capture_object(target);
+            let args = self.spread(location, args);
+            *value = call(dot(method, "apply".to_owned(), location), vec![this, args]);
+        }
+    }
+
+    // The links after a "?." that has been lowered, which "lower_spread_call"
+    // left alone
+    fn lower_chain_spread(&mut self, link: &mut Expr) {
+        ensure_sufficient_stack(|| self.lower_chain_spread_inner(link))
+    }
+
+    fn lower_chain_spread_inner(&mut self, link: &mut Expr) {
+        if optional_chain(link) != OptionalChain::Continue {
+            return;
+        }
+        self.lower_chain_spread(chain_target_mut(link));
+        if let ExprKind::Call { optional_chain, .. } = link.data.as_mut() {
+            *optional_chain = OptionalChain::None;
+            self.lower_spread_call(link);
+        }
+    }
+
+    // "[a, ...b, c]" => "__spread([a], b, [c])"
+    //
+    // The first array is always a new one, which is the one that's returned.
+    fn spread(&mut self, location: Location, items: Vec<Expr>) -> Expr {
+        let mut args = vec![];
+        let mut chunk = vec![];
+        for item in items {
+            let item_location = item.location;
+            match item.into_data() {
+                ExprKind::Spread { value } => {
+                    if args.is_empty() || !chunk.is_empty() {
+                        let items = std::mem::take(&mut chunk);
+                        args.push(expr(location, ExprKind::Array { items }));
+                    }
+                    args.push(value);
+                }
+                data => chunk.push(expr(item_location, data)),
+            }
+        }
+        if !chunk.is_empty() {
+            args.push(expr(location, ExprKind::Array { items: chunk }));
+        }
+        call(identifier(location, self.helper("__spread")), args)
+    }
+
+    // Properties from the first computed key on are assigned one at a time,
+    // which keeps the order that the keys and values are evaluated in:
+    //
+    //   {a: 1, [b]: 2, c: 3}  =>  (_a = {a: 1}, _a[b] = 2, _a.c = 3, _a)
+    //
+    // Getters and setters are defined with "Object.defineProperty" instead.
+    fn lower_computed_keys(&mut self, location: Location, mut properties: Vec<Property>) -> Expr {
+        let first = match properties.iter().position(|property| property.is_computed) {
+            Some(first) => first,
+            None => return expr(location, ExprKind::Object { properties }),
+        };
+        let rest = properties.split_off(first);
+        let temp = self.temp_refs.generate(self.symbols);
+        let object = || identifier(location, temp);
+        let mut values = vec![assign(
+            object(),
+            expr(location, ExprKind::Object { properties }),
+        )];
+        for property in rest {
+            let value = property.value.unwrap_or_else(|| expr_missing(location));
+            values.push(match property.kind {
+                PropertyKind::PropertyGet | PropertyKind::PropertySet => {
+                    let name = if property.kind == PropertyKind::PropertyGet {
+                        "get"
+                    } else {
+                        "set"
+                    };
+                    let descriptor = object_expr(
+                        location,
+                        vec![
+                            (name, value),
+                            (
+                                "configurable",
+                                expr(location, ExprKind::Boolean { value: true }),
+                            ),
+                            (
+                                "enumerable",
+                                expr(location, ExprKind::Boolean { value: true }),
+                            ),
+                        ],
+                    );
+                    let object_ref = self.global_ref("Object");
+                    let define = dot(
+                        identifier(location, object_ref),
+                        "defineProperty".to_owned(),
+                        location,
+                    );
+                    call(define, vec![object(), property.key, descriptor])
+                }
+                _ => assign(member_access(object(), property.key), value),
+            });
+        }
+        values.push(object());
+        join_all_with_comma(values.into_iter()).expect("There is at least one value")
+    }
+
+    fn lower_expr(&mut self, location: Location, data: ExprKind) -> Expr {
+        match data {
+            // "a ?? b" => "a != null ? a : b"
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpNullishCoalescing,
+                left,
+                right,
+            } => {
+                let (left, left_again) = self.capture(left);
+                let test = binary(
+                    OperatorCode::BinOpLooseNe,
+                    left,
+                    expr(location, ExprKind::Null),
+                );
+                expr(
+                    location,
+                    ExprKind::If {
+                        test,
+                        yes: left_again,
+                        no: right,
+                    },
+                )
+            }
+
+            // "a ** b" => "Math.pow(a, b)"
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpPow,
+                left,
+                right,
+            } => self.math_pow(left, right),
+
+            // "a.b **= c" => "a.b = Math.pow(a.b, c)"
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpPowAssign,
+                left,
+                right,
+            } => {
+                let location = left.location;
+                let (target, target_again) = match left.into_data() {
+                    ExprKind::Dot {
+                        target,
+                        name,
+                        name_location,
+                        ..
+                    } => {
+                        let (object, object_again) = self.capture(target);
+                        (
+                            dot(object, name.clone(), name_location),
+                            dot(object_again, name, name_location),
+                        )
+                    }
+                    ExprKind::Index { target, index, .. } => {
+                        let (object, object_again) = self.capture(target);
+                        let (index, index_again) = self.capture(index);
+                        (
+                            index_expr(object, index),
+                            index_expr(object_again, index_again),
+                        )
+                    }
+                    data => {
+                        let left = expr(location, data);
+                        (left.clone(), left)
+                    }
+                };
+                let value = self.math_pow(target_again, right);
+                assign(target, value)
+            }
+
+            ExprKind::Array { items } => self.spread(location, items),
+
+            // "new A(...b)" => "new (Function.prototype.bind.apply(A, __spread([void 0], b)))()"
+            ExprKind::New {
+                target, mut args, ..
+            } => {
+                args.insert(0, expr(location, ExprKind::Undefined));
+                let args = self.spread(location, args);
+                let function = identifier(location, self.global_ref("Function"));
+                let prototype = dot(function, "prototype".to_owned(), location);
+                let bind = dot(prototype, "bind".to_owned(), location);
+                let apply = dot(bind, "apply".to_owned(), location);
+                expr(
+                    location,
+                    ExprKind::New {
+                        target: call(apply, vec![target, args]),
+                        args: vec![],
+                        can_be_unwrapped_if_unused: false,
+                    },
+                )
+            }
+
+            ExprKind::Object { properties }
+                if !properties
+                    .iter()
+                    .any(|p| p.kind == PropertyKind::PropertySpread) =>
+            {
+                self.lower_computed_keys(location, properties)
+            }
+
+            // "{a, ...b}" => "Object.assign({a}, b)"
+            ExprKind::Object { properties } => {
+                let mut args = vec![];
+                let mut object = vec![];
+                for property in properties {
+                    if property.kind != PropertyKind::PropertySpread {
+                        object.push(property);
+                        continue;
+                    }
+
+                    // The first argument is always a new object, which is the
+                    // one that gets modified
+                    if args.is_empty() || !object.is_empty() {
+                        let properties = std::mem::take(&mut object);
+                        args.push(self.lower_object_chunk(location, properties));
+                    }
+                    if let Some(value) = property.value {
+                        args.push(value);
+                    }
+                }
+                if !object.is_empty() {
+                    args.push(self.lower_object_chunk(location, object));
+                }
+                let object_ref = self.global_ref("Object");
+                let target = dot(
+                    identifier(location, object_ref),
+                    "assign".to_owned(),
+                    location,
+                );
+                call(target, args)
+            }
+
+            ExprKind::Unary {
+                op_code: OperatorCode::UnOpDelete,
+                value,
+            } => self.lower_optional_chain(value, true),
+
+            data => self.lower_optional_chain(expr(location, data), false),
+        }
+    }
+
+    // The innermost "?." is lowered first and the rest of the chain is only
+    // evaluated if the value before it isn't null or undefined:
+    //
+    //   a?.b.c    =>  a == null ? void 0 : a.b.c
+    //   a.b?.()   =>  (_b = (_a = a).b) == null ? void 0 : _b.call(_a)
+    //
+    // Everything after the innermost "?." is lowered again in case it has
+    // another one.
+    fn lower_optional_chain(&mut self, chain: Expr, is_delete: bool) -> Expr {
+        ensure_sufficient_stack(|| self.lower_optional_chain_inner(chain, is_delete))
+    }
+
+    fn lower_optional_chain_inner(&mut self, mut chain: Expr, is_delete: bool) -> Expr {
+        let depth = match optional_chain_depth(&chain) {
+            Some(depth) => depth,
+            None if is_delete => {
+                return expr(
+                    chain.location,
+                    ExprKind::Unary {
+                        op_code: OperatorCode::UnOpDelete,
+                        value: chain,
+                    },
+                )
+            }
+            None => {
+                self.lower_chain_spread(&mut chain);
+                end_optional_chain(&mut chain);
+                return chain;
+            }
+        };
+
+        let mut link = &mut chain;
+        for _ in 0..depth {
+            link = chain_target_mut(link);
+        }
+        let location = link.location;
+        let test = match link.data.as_mut() {
+            ExprKind::Call {
+                target,
+                args,
+                optional_chain,
+                ..
+            } if matches!(
+                target.data.as_ref(),
+                ExprKind::Dot { .. } | ExprKind::Index { .. }
+            ) =>
+            {
+                // Methods are called with the object they were read from as
+                // "this", so that has to be passed to the function explicitly
+                *optional_chain = OptionalChain::None;
+                let method = std::mem::replace(target, expr(location, ExprKind::Missing));
+                let (method, this) = self.capture_object(method);
+                let temp = self.temp_refs.generate(self.symbols);
+                if has_spread(args) && self.lowers(Feature::ArraySpread, location) {
+                    let spread = self.spread(location, std::mem::take(args));
+                    *target = dot(identifier(location, temp), "apply".to_owned(), location);
+                    *args = vec![this, spread];
+                } else {
+                    *target = dot(identifier(location, temp), "call".to_owned(), location);
+                    args.insert(0, this);
+                }
+                assign(identifier(location, temp), method)
+            }
+            ExprKind::Dot {
+                target,
+                optional_chain,
+                ..
+            }
+            | ExprKind::Index {
+                target,
+                optional_chain,
+                ..
+            }
+            | ExprKind::Call {
+                target,
+                optional_chain,
+                ..
+            } => {
+                *optional_chain = OptionalChain::None;
+                let mut value = std::mem::replace(target, expr(location, ExprKind::Missing));
+                self.lower_chain_spread(&mut value);
+                let (value, value_again) = self.capture(value);
+                *target = value_again;
+                value
+            }
+            _ => unreachable!("The depth of the chain was just computed"),
+        };
+        self.lower_spread_call(link);
+
+        let yes = if is_delete {
+            ExprKind::Boolean { value: true }
+        } else {
+            ExprKind::Undefined
+        };
+        expr(
+            location,
+            ExprKind::If {
+                test: binary(
+                    OperatorCode::BinOpLooseEq,
+                    test,
+                    expr(location, ExprKind::Null),
+                ),
+                yes: expr(location, yes),
+                no: self.lower_optional_chain(chain, is_delete),
+            },
+        )
+    }
+
+    // Splits "a.b" into "(_a = a).b" and "_a" so that the object can be used
+    // again after the property has been read
+    fn capture_object(&mut self, method: Expr) -> (Expr, Expr) {
+        let location = method.location;
+        match method.into_data() {
+            ExprKind::Dot {
+                target,
+                name,
+                name_location,
+                ..
+            } => {
+                let (object, this) = self.capture_this(target);
+                (dot(object, name, name_location), this)
+            }
+            ExprKind::Index { target, index, .. } => {
+                let (object, this) = self.capture_this(target);
+                (index_expr(object, index), this)
+            }
+            data => (expr(location, data), expr(location, ExprKind::Undefined)),
+        }
+    }
+
+    fn capture_this(&mut self, object: Expr) -> (Expr, Expr) {
+        if let ExprKind::Super = object.data.as_ref() {
+            let location = object.location;
+            return (object, expr(location, ExprKind::This));
+        }
+        self.capture(object)
+    }
+
+    // Returns the value along with an expression that evaluates to the same
+    // value again later. Values that could change or have side effects are
+    // stored in a temporary.
+    fn capture(&mut self, value: Expr) -> (Expr, Expr) {
+        match value.data.as_ref() {
+            ExprKind::Identifier { .. }
+            | ExprKind::ImportIdentifier { .. }
+            | ExprKind::This
+            | ExprKind::Null
+            | ExprKind::Undefined
+            | ExprKind::Boolean { .. }
+            | ExprKind::Number { .. }
+            | ExprKind::String { .. } => (value.clone(), value),
+            _ => {
+                let location = value.location;
+                let temp = self.temp_refs.generate(self.symbols);
+                (
+                    assign(identifier(location, temp), value),
+                    identifier(location, temp),
+                )
+            }
+        }
+    }
+
+    fn math_pow(&mut self, left: Expr, right: Expr) -> Expr {
+        let location = left.location;
+        let math = identifier(location, self.global_ref("Math"));
+        call(dot(math, "pow".to_owned(), location), vec![left, right])
+    }
+
+    // Every pattern becomes one declaration per name, and the values that are
+    // read more than once are stored in temporaries along the way:
+    //
+    //   var {a, b: [c, d = 1]} = e
+    //
+    // becomes
+    //
+    //   var a = e.a, c = (_a = __read(e.b, 2))[0], d = (_b = _a[1]) === void 0 ? 1 : _b
+    //
+    // Anything that has to be evaluated but isn't declared goes in front of
+    // the next declaration.
+    fn lower_decls(&mut self, decls: Vec<Decl>) -> Vec<Decl> {
+        let mut lowered = vec![];
+        let mut pending = vec![];
+        for decl in decls {
+            let value = match decl.value {
+                Some(value) if self.lowers_binding(&decl.binding) => value,
+                value => {
+                    lowered.push(Decl {
+                        binding: decl.binding,
+                        value: value.map(|value| prepend_exprs(&mut pending, value)),
+                    });
+                    continue;
+                }
+            };
+            let mut steps = vec![];
+            self.lower_pattern(binding_to_pattern(decl.binding), value, &mut steps);
+            for step in steps {
+                match step {
+                    PatternStep::Assign(target, value) => {
+                        let reference = match *target.data {
+                            ExprKind::Identifier { reference } => reference,
+                            _ => unreachable!("Bindings only declare identifiers"),
+                        };
+                        lowered.push(Decl {
+                            binding: Binding {
+                                location: target.location,
+                                data: Box::new(BindingKind::Identifier { reference }),
+                            },
+                            value: Some(prepend_exprs(&mut pending, value)),
+                        });
+                    }
+                    PatternStep::Effect(value) => pending.push(value),
+                }
+            }
+        }
+
+        // "var {a: {}} = b" still reads "b.a" even though nothing is declared
+        if let Some(value) = join_all_with_comma(pending.into_iter()) {
+            let reference = self.temp_refs.generate_unhoisted(self.symbols);
+            lowered.push(Decl {
+                binding: Binding {
+                    location: value.location,
+                    data: Box::new(BindingKind::Identifier { reference }),
+                },
+                value: Some(value),
+            });
+        }
+        lowered
+    }
+
+    // The value of the assignment is the value on the right, so that's stored
+    // in a temporary first:
+    //
+    //   [a, b] = [b, a]  =>  _a = [b, a], a = (_b = __read(_a, 2))[0], b = _b[1], _a
+    fn lower_destructuring_assign(&mut self, pattern: Expr, value: Expr) -> Expr {
+        let location = value.location;
+        let temp = self.temp_refs.generate(self.symbols);
+        let mut steps = vec![PatternStep::Effect(assign(
+            identifier(location, temp),
+            value,
+        ))];
+        self.lower_pattern(
+            expr_to_pattern(pattern),
+            identifier(location, temp),
+            &mut steps,
+        );
+        let values = steps
+            .into_iter()
+            .map(|step| match step {
+                PatternStep::Assign(target, value) => assign(target, value),
+                PatternStep::Effect(value) => value,
+            })
+            .chain(std::iter::once(identifier(location, temp)));
+        join_all_with_comma(values).expect("There is at least one value")
+    }
+
+    fn lower_pattern(&mut self, pattern: Pattern, value: Expr, steps: &mut Vec<PatternStep>) {
+        match pattern {
+            Pattern::Missing => {}
+            Pattern::Target(target) => steps.push(PatternStep::Assign(target, value)),
+
+            // Arrays are read with the iterator protocol, which "__read" turns
+            // into an array of the items that are needed
+            Pattern::Array { items, has_rest } => {
+                let location = value.location;
+                let mut args = vec![value];
+                if !has_rest {
+                    args.push(number_expr(location, items.len()));
+                }
+                let read = call(identifier(location, self.helper("__read")), args);
+                self.lower_pattern_items(read, items, false, steps);
+            }
+            Pattern::Object { items } => self.lower_pattern_items(value, items, true, steps),
+        }
+    }
+
+    // An object rest element copies the object without the keys that have
+    // been read, so those are kept for "__objRest":
+    //
+    //   var {[f()]: a, ...b} = c  =>  var a = c[_a = f()], b = __objRest(c, [_a])
+    fn lower_pattern_items(
+        &mut self,
+        value: Expr,
+        items: Vec<PatternItem>,
+        is_object: bool,
+        steps: &mut Vec<PatternStep>,
+    ) {
+        let location = value.location;
+        let reads = items
+            .iter()
+            .filter(|item| !matches!(item.pattern, Pattern::Missing))
+            .count();
+        let (value, value_again) = if reads > 1 {
+            self.capture(value)
+        } else {
+            (value, expr_missing(location))
+        };
+
+        let has_object_rest = is_object && items.iter().any(|item| item.is_rest);
+        let mut read_keys = vec![];
+        let mut value = Some(value);
+        for item in items {
+            if let Pattern::Missing = item.pattern {
+                continue;
+            }
+            let object = value.take().unwrap_or_else(|| value_again.clone());
+            let mut element = if item.is_rest && is_object {
+                let keys = expr(
+                    location,
+                    ExprKind::Array {
+                        items: std::mem::take(&mut read_keys),
+                    },
+                );
+                call(
+                    identifier(location, self.helper("__objRest")),
+                    vec![object, keys],
+                )
+            } else if item.is_rest {
+                let slice = dot(object, "slice".to_owned(), location);
+                call(slice, vec![item.key])
+            } else if has_object_rest {
+                let (key, key_again) = self.capture(item.key);
+                read_keys.push(key_again);
+                member_access(object, key)
+            } else {
+                member_access(object, item.key)
+            };
+
+            // "a = b" => "(_a = value) === void 0 ? b : _a"
+            if let Some(default_value) = item.default_value {
+                let (test, element_again) = self.capture(element);
+                element = expr(
+                    location,
+                    ExprKind::If {
+                        test: binary(
+                            OperatorCode::BinOpStrictEq,
+                            test,
+                            expr(location, ExprKind::Undefined),
+                        ),
+                        yes: default_value,
+                        no: element_again,
+                    },
+                );
+            }
+            self.lower_pattern(item.pattern, element, steps);
+        }
+
+        // The value is still evaluated if nothing is read from it
+        if let Some(value) = value {
+            steps.push(PatternStep::Effect(value));
+        }
+    }
+
+    // "arguments" inside a generator that is being lowered
+    fn generator_arguments_ref(&mut self, reference: Reference) -> Option<Reference> {
+        let temp = self.generator_arguments?;
+        let symbol = &self.symbols[reference];
+        if symbol.kind != SymbolKind::Unbound || symbol.name != "arguments" {
+            return None;
+        }
+        let temp = temp.unwrap_or_else(|| self.temp_refs.generate_unhoisted(self.symbols));
+        self.generator_arguments = Some(Some(temp));
+        Some(temp)
+    }
+
+    // The body becomes a state machine that is passed to "__generator", and
+    // its variables, its functions, and "arguments" are declared in the
+    // outer function. A generator that uses something that can't be split
+    // is left as-is.
+    fn lower_generator(&mut self, function: &mut Function, arguments: Option<Reference>) {
+        let location = function.body.location;
+        let mut stmts = std::mem::take(&mut function.body.stmts);
+        let body = stmts.split_off(prologue_len(&stmts));
+        let arguments = arguments.map(|temp| {
+            let value = identifier(location, self.global_ref("arguments"));
+            var_decl(location, temp, Some(value))
+        });
+
+        let state = self.temp_refs.generate_unhoisted(self.symbols);
+        let mut gen = GeneratorState::new(state);
+        for stmt in body.clone() {
+            self.split_stmt(&mut gen, stmt);
+        }
+        if let Some(location) = gen.unsupported {
+            self.unsupported(Feature::Generator, location);
+            if let Some(arguments) = arguments {
+                stmts.push(local_stmt(LocalKind::Var, vec![arguments]));
+            }
+            stmts.extend(body);
+            function.body.stmts = stmts;
+            return;
+        }
+
+        let machine = function_expr(location, &[state], gen.finish(location));
+        let helper = identifier(location, self.helper("__generator"));
+        let this = expr(location, ExprKind::This);
+        let decls: Vec<Decl> = gen
+            .vars
+            .iter()
+            .map(|&reference| var_decl(location, reference, None))
+            .chain(arguments)
+            .collect();
+        if !decls.is_empty() {
+            stmts.push(local_stmt(LocalKind::Var, decls));
+        }
+        stmts.append(&mut gen.functions);
+        stmts.push(return_stmt(call(helper, vec![this, machine])));
+        function.body.stmts = stmts;
+        function.is_generator = false;
+    }
+
+    fn generator_temp(&mut self, gen: &mut GeneratorState) -> Reference {
+        let temp = self.temp_refs.generate_unhoisted(self.symbols);
+        gen.declare(temp);
+        temp
+    }
+
+    // Statements with a yield are split into the cases of the state machine,
+    // and everything else goes into the current case
+    fn split_stmt(&mut self, gen: &mut GeneratorState, stmt: Stmt) {
+        self.split_labeled_stmt(gen, stmt, vec![]);
+    }
+
+    // "names" are the labels of the statement
+    fn split_labeled_stmt(&mut self, gen: &mut GeneratorState, stmt: Stmt, names: Vec<Reference>) {
+        ensure_sufficient_stack(|| self.split_labeled_stmt_inner(gen, stmt, names))
+    }
+
+    fn split_labeled_stmt_inner(
+        &mut self,
+        gen: &mut GeneratorState,
+        mut stmt: Stmt,
+        mut names: Vec<Reference>,
+    ) {
+        let location = stmt.location;
+        if !stmt_has_yield(&stmt) {
+            gen.rewrite_plain_stmt(&mut stmt, 0, 0, &mut vec![]);
+            gen.emit(stmt);
+            return;
+        }
+
+        // Only "break" can go to a labeled statement that isn't a loop
+        let is_loop = matches!(
+            stmt.data.as_ref(),
+            StmtKind::Label { .. }
+                | StmtKind::For { .. }
+                | StmtKind::ForIn { .. }
+                | StmtKind::DoWhile { .. }
+                | StmtKind::While { .. }
+        );
+        if !names.is_empty() && !is_loop {
+            let end = gen.new_label();
+            gen.jump_targets.push(JumpTarget {
+                names,
+                break_label: end,
+                continue_label: None,
+                is_breakable: false,
+            });
+            self.split_stmt(gen, stmt);
+            gen.jump_targets.pop();
+            gen.mark(end);
+            return;
+        }
+
+        match *stmt.data {
+            StmtKind::Label { name, stmt } => {
+                names.push(name.reference);
+                self.split_labeled_stmt(gen, stmt, names);
+            }
+            StmtKind::Block { stmts } => {
+                for stmt in stmts {
+                    self.split_stmt(gen, stmt);
+                }
+            }
+            StmtKind::Expr { value } => {
+                let value = self.split_expr(gen, value);
+                gen.emit(expr_stmt(value));
+            }
+            StmtKind::Local { decls, .. } => {
+                for decl in decls {
+                    let reference = gen.hoist_binding(&decl.binding);
+                    if let (Some(reference), Some(value)) = (reference, decl.value) {
+                        let value = self.split_expr(gen, value);
+                        let target = identifier(decl.binding.location, reference);
+                        gen.emit(expr_stmt(assign(target, value)));
+                    }
+                }
+            }
+            StmtKind::Return { value } => {
+                let value = value.map(|value| self.split_expr(gen, value));
+                gen.emit(generator_instruction(location, GENERATOR_RETURN, value));
+            }
+            StmtKind::Throw { value } => {
+                let value = self.split_expr(gen, value);
+                gen.emit(Stmt {
+                    location,
+                    data: Box::new(StmtKind::Throw { value }),
+                });
+            }
+            StmtKind::If { test, yes, no } => {
+                let test = self.split_expr(gen, test);
+                if !stmt_has_yield(&yes) && no.as_ref().is_none_or(|no| !stmt_has_yield(no)) {
+                    let mut stmt = Stmt {
+                        location,
+                        data: Box::new(StmtKind::If { test, yes, no }),
+                    };
+                    gen.rewrite_plain_stmt(&mut stmt, 0, 0, &mut vec![]);
+                    gen.emit(stmt);
+                    return;
+                }
+                let no_label = gen.new_label();
+                gen.jump_if(unary(OperatorCode::UnOpNot, test), no_label);
+                self.split_stmt(gen, yes);
+                match no {
+                    Some(no) => {
+                        let end = gen.new_label();
+                        gen.jump(location, end);
+                        gen.mark(no_label);
+                        self.split_stmt(gen, no);
+                        gen.mark(end);
+                    }
+                    None => gen.mark(no_label),
+                }
+            }
+            StmtKind::While { test, body } => {
+                let start = gen.new_label();
+                let end = gen.new_label();
+                gen.mark(start);
+                let test = self.split_expr(gen, test);
+                gen.jump_if(unary(OperatorCode::UnOpNot, test), end);
+                self.split_loop_body(gen, body, names, end, start);
+                gen.jump(location, start);
+                gen.mark(end);
+            }
+            StmtKind::DoWhile { body, test } => {
+                let start = gen.new_label();
+                let next = gen.new_label();
+                let end = gen.new_label();
+                gen.mark(start);
+                self.split_loop_body(gen, body, names, end, next);
+                gen.mark(next);
+                let test = self.split_expr(gen, test);
+                gen.jump_if(test, start);
+                gen.mark(end);
+            }
+            StmtKind::For {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                if let Some(init) = init {
+                    self.split_stmt(gen, init);
+                }
+                let start = gen.new_label();
+                let next = gen.new_label();
+                let end = gen.new_label();
+                gen.mark(start);
+                if let Some(test) = test {
+                    let test = self.split_expr(gen, test);
+                    gen.jump_if(unary(OperatorCode::UnOpNot, test), end);
+                }
+                self.split_loop_body(gen, body, names, end, next);
+                gen.mark(next);
+                if let Some(update) = update {
+                    let update = self.split_expr(gen, update);
+                    gen.emit(expr_stmt(update));
+                }
+                gen.jump(location, start);
+                gen.mark(end);
+            }
+            StmtKind::ForIn { init, value, body } => {
+                self.split_for_in(gen, init, value, body, names);
+            }
+            StmtKind::Switch { test, cases, .. } => self.split_switch(gen, location, test, cases),
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let start = gen.new_label();
+                let catch_label = catch.as_ref().map(|_| gen.new_label());
+                let finally_label = finally.as_ref().map(|_| gen.new_label());
+                let end = gen.new_label();
+                gen.mark(start);
+                gen.ops.push(GeneratorOp::Try {
+                    catch: catch_label,
+                    finally: finally_label,
+                    end,
+                });
+                for stmt in body {
+                    self.split_stmt(gen, stmt);
+                }
+                gen.jump(location, end);
+                if let (Some(catch), Some(label)) = (catch, catch_label) {
+                    gen.mark(label);
+                    if let Some(binding) = &catch.binding {
+                        if let Some(reference) = gen.hoist_binding(binding) {
+                            let error = gen.sent(catch.location);
+                            let target = identifier(binding.location, reference);
+                            gen.emit(expr_stmt(assign(target, error)));
+                        }
+                    }
+                    for stmt in catch.body {
+                        self.split_stmt(gen, stmt);
+                    }
+                    gen.jump(location, end);
+                }
+                if let (Some(finally), Some(label)) = (finally, finally_label) {
+                    gen.mark(label);
+                    for stmt in finally.stmts {
+                        self.split_stmt(gen, stmt);
+                    }
+                    gen.emit(generator_instruction(location, GENERATOR_END_FINALLY, None));
+                }
+                gen.mark(end);
+            }
+            _ => gen.fail(location),
+        }
+    }
+
+    fn split_loop_body(
+        &mut self,
+        gen: &mut GeneratorState,
+        body: Stmt,
+        names: Vec<Reference>,
+        break_label: usize,
+        continue_label: usize,
+    ) {
+        gen.jump_targets.push(JumpTarget {
+            names,
+            break_label,
+            continue_label: Some(continue_label),
+            is_breakable: true,
+        });
+        self.split_stmt(gen, body);
+        gen.jump_targets.pop();
+    }
+
+    // The keys are collected before the loop starts like "for-in" does, and
+    // each one is skipped if it has been deleted in the meantime:
+    //
+    //   _a = b;
+    //   _b = [];
+    //   for (_c in _a) _b.push(_c);
+    //   _d = 0;
+    //   case 1:
+    //     if (!(_d < _b.length)) return [3, 4];
+    //     _c = _b[_d];
+    //     if (!(_c in _a)) return [3, 3];
+    //     a = _c;
+    //     ...
+    //   case 3:
+    //     _d++;
+    //     return [3, 1];
+    fn split_for_in(
+        &mut self,
+        gen: &mut GeneratorState,
+        init: Stmt,
+        value: Expr,
+        body: Stmt,
+        names: Vec<Reference>,
+    ) {
+        let location = init.location;
+        let target = match *init.data {
+            StmtKind::Local { decls, .. } => match decls.as_slice() {
+                [decl] => gen
+                    .hoist_binding(&decl.binding)
+                    .map(|reference| identifier(decl.binding.location, reference)),
+                _ => None,
+            },
+            StmtKind::Expr { value } if !expr_has_yield(&value) => Some(value),
+            _ => None,
+        };
+        let target = match target {
+            Some(target) => target,
+            None => return gen.fail(location),
+        };
+
+        let object = self.generator_temp(gen);
+        let keys = self.generator_temp(gen);
+        let key = self.generator_temp(gen);
+        let index = self.generator_temp(gen);
+        let temp = |reference| identifier(location, reference);
+
+        let value = self.split_expr(gen, value);
+        gen.emit(expr_stmt(assign(temp(object), value)));
+        let items = expr(location, ExprKind::Array { items: vec![] });
+        gen.emit(expr_stmt(assign(temp(keys), items)));
+        let push = call(
+            dot(temp(keys), "push".to_owned(), location),
+            vec![temp(key)],
+        );
+        gen.emit(Stmt {
+            location,
+            data: Box::new(StmtKind::ForIn {
+                init: expr_stmt(temp(key)),
+                value: temp(object),
+                body: expr_stmt(push),
+            }),
+        });
+        gen.emit(expr_stmt(assign(temp(index), number_expr(location, 0))));
+
+        let start = gen.new_label();
+        let next = gen.new_label();
+        let end = gen.new_label();
+        gen.mark(start);
+        let length = dot(temp(keys), "length".to_owned(), location);
+        let has_next = binary(OperatorCode::BinOpLt, temp(index), length);
+        gen.jump_if(unary(OperatorCode::UnOpNot, has_next), end);
+        let current = index_expr(temp(keys), temp(index));
+        gen.emit(expr_stmt(assign(temp(key), current)));
+        let is_present = binary(OperatorCode::BinOpIn, temp(key), temp(object));
+        gen.jump_if(unary(OperatorCode::UnOpNot, is_present), next);
+        gen.emit(expr_stmt(assign(target, temp(key))));
+        self.split_loop_body(gen, body, names, end, next);
+        gen.mark(next);
+        gen.emit(expr_stmt(unary(OperatorCode::UnOpPostInc, temp(index))));
+        gen.jump(location, start);
+        gen.mark(end);
+    }
+
+    // "switch (a) { case b: ... }" => "_a = a; if (_a === b) return [3, 1]; ..."
+    fn split_switch(
+        &mut self,
+        gen: &mut GeneratorState,
+        location: Location,
+        test: Expr,
+        cases: Vec<Case>,
+    ) {
+        let test = self.split_expr(gen, test);
+        let temp = self.generator_temp(gen);
+        gen.emit(expr_stmt(assign(identifier(location, temp), test)));
+
+        let end = gen.new_label();
+        let mut default_label = end;
+        let mut bodies = vec![];
+        for case in cases {
+            let label = gen.new_label();
+            match case.value {
+                Some(value) => {
+                    let value = self.split_expr(gen, value);
+                    let test = binary(
+                        OperatorCode::BinOpStrictEq,
+                        identifier(location, temp),
+                        value,
+                    );
+                    gen.jump_if(test, label);
+                }
+                None => default_label = label,
+            }
+            bodies.push((label, case.body));
+        }
+        gen.jump(location, default_label);
+
+        gen.jump_targets.push(JumpTarget {
+            names: vec![],
+            break_label: end,
+            continue_label: None,
+            is_breakable: true,
+        });
+        for (label, body) in bodies {
+            gen.mark(label);
+            for stmt in body {
+                self.split_stmt(gen, stmt);
+            }
+        }
+        gen.jump_targets.pop();
+        gen.mark(end);
+    }
+
+    // Emits the parts of the expression up to its last yield and returns the
+    // rest, which doesn't have any yields left
+    fn split_expr(&mut self, gen: &mut GeneratorState, value: Expr) -> Expr {
+        ensure_sufficient_stack(|| self.split_expr_inner(gen, value))
+    }
+
+    fn split_expr_inner(&mut self, gen: &mut GeneratorState, value: Expr) -> Expr {
+        if !expr_has_yield(&value) {
+            return value;
+        }
+        let location = value.location;
+        match value.into_data() {
+            ExprKind::Yield { value, is_star } => {
+                let value = match value.data.as_ref() {
+                    ExprKind::Missing => None,
+                    _ => Some(self.split_expr(gen, value)),
+                };
+                let op = if is_star {
+                    GENERATOR_YIELD_STAR
+                } else {
+                    GENERATOR_YIELD
+                };
+                gen.emit(generator_instruction(location, op, value));
+
+                // The helper resumes the generator at the next case
+                let resume = gen.new_label();
+                gen.mark(resume);
+                gen.sent(location)
+            }
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpComma,
+                left,
+                right,
+            } => {
+                let left = self.split_expr(gen, left);
+                gen.emit(expr_stmt(left));
+                self.split_expr(gen, right)
+            }
+
+            // "a && (yield b)" => "_b = a; if (!_b) return [3, 2]; return [4, b]; ... _b = _a.sent()"
+            ExprKind::Binary {
+                op_code:
+                    op_code @ OperatorCode::BinOpLogicalAnd
+                    | op_code @ OperatorCode::BinOpLogicalOr
+                    | op_code @ OperatorCode::BinOpNullishCoalescing,
+                left,
+                right,
+            } if expr_has_yield(&right) => {
+                let temp = self.generator_temp(gen);
+                let left = self.split_expr(gen, left);
+                gen.emit(expr_stmt(assign(identifier(location, temp), left)));
+                let end = gen.new_label();
+                let skip = match op_code {
+                    OperatorCode::BinOpLogicalAnd => {
+                        unary(OperatorCode::UnOpNot, identifier(location, temp))
+                    }
+                    OperatorCode::BinOpLogicalOr => identifier(location, temp),
+                    _ => binary(
+                        OperatorCode::BinOpLooseNe,
+                        identifier(location, temp),
+                        expr(location, ExprKind::Null),
+                    ),
+                };
+                gen.jump_if(skip, end);
+                let right = self.split_expr(gen, right);
+                gen.emit(expr_stmt(assign(identifier(location, temp), right)));
+                gen.mark(end);
+                identifier(location, temp)
+            }
+            ExprKind::If { test, yes, no } if expr_has_yield(&yes) || expr_has_yield(&no) => {
+                let temp = self.generator_temp(gen);
+                let test = self.split_expr(gen, test);
+                let no_label = gen.new_label();
+                let end = gen.new_label();
+                gen.jump_if(unary(OperatorCode::UnOpNot, test), no_label);
+                let yes = self.split_expr(gen, yes);
+                gen.emit(expr_stmt(assign(identifier(location, temp), yes)));
+                gen.jump(location, end);
+                gen.mark(no_label);
+                let no = self.split_expr(gen, no);
+                gen.emit(expr_stmt(assign(identifier(location, temp), no)));
+                gen.mark(end);
+                identifier(location, temp)
+            }
+            ExprKind::Binary {
+                op_code,
+                left,
+                right,
+            } if op_code.is_binary_assign() => {
+                if !expr_has_yield(&right) {
+                    let left = self.split_assign_target(gen, left, false);
+                    return binary(op_code, left, right);
+                }
+                let left = self.split_assign_target(gen, left, true);
+                if op_code == OperatorCode::BinOpAssign {
+                    let right = self.split_expr(gen, right);
+                    return assign(left, right);
+                }
+
+                // "a += yield b" reads "a" before the generator is suspended
+                let op_code =
+                    compound_assign_op(op_code).expect("Every compound assignment has an operator");
+                let old_value = self.spill(gen, left.clone());
+                let right = self.split_expr(gen, right);
+                assign(left, binary(op_code, old_value, right))
+            }
+
+            // The object of a method is stored so that it's still "this"
+            ExprKind::Call { target, args, .. } if args.iter().any(expr_has_yield) => {
+                let target = self.split_assign_target(gen, target, true);
+                let args = self.split_operands(gen, args, false);
+                call(target, args)
+            }
+
+            data => {
+                let mut value = expr(location, data);
+                let mut slots = operands_mut(&mut value);
+                let operands = slots.iter_mut().map(|slot| take_expr(slot)).collect();
+                let operands = self.split_operands(gen, operands, false);
+                for (slot, operand) in slots.into_iter().zip(operands) {
+                    *slot = operand;
+                }
+                value
+            }
+        }
+    }
+
+    // Operands are evaluated in order, so the ones before the last yield are
+    // stored in temporaries in case the code that runs while the generator is
+    // suspended changes them
+    fn split_operands(
+        &mut self,
+        gen: &mut GeneratorState,
+        operands: Vec<Expr>,
+        spill_all: bool,
+    ) -> Vec<Expr> {
+        let spilled = if spill_all {
+            operands.len()
+        } else {
+            operands.iter().rposition(expr_has_yield).unwrap_or(0)
+        };
+        let mut split = vec![];
+        for (i, operand) in operands.into_iter().enumerate() {
+            let operand = self.split_expr(gen, operand);
+            split.push(if i < spilled {
+                self.spill(gen, operand)
+            } else {
+                operand
+            });
+        }
+        split
+    }
+
+    // The target of an assignment must stay a reference, so only its object
+    // and its key are split
+    fn split_assign_target(&mut self, gen: &mut GeneratorState, target: Expr, spill: bool) -> Expr {
+        let location = target.location;
+        match target.into_data() {
+            ExprKind::Dot {
+                target,
+                name,
+                name_location,
+                ..
+            } => {
+                let object = self.split_operands(gen, vec![target], spill).remove(0);
+                dot(object, name, name_location)
+            }
+            ExprKind::Index { target, index, .. } => {
+                let mut operands = self.split_operands(gen, vec![target, index], spill);
+                let index = operands.pop().expect("There are two operands");
+                let object = operands.pop().expect("There are two operands");
+                index_expr(object, index)
+            }
+            data => self.split_expr(gen, expr(location, data)),
+        }
+    }
+
+    // Values that can't change are used as they are
+    fn spill(&mut self, gen: &mut GeneratorState, value: Expr) -> Expr {
+        ensure_sufficient_stack(|| self.spill_inner(gen, value))
+    }
+
+    fn spill_inner(&mut self, gen: &mut GeneratorState, value: Expr) -> Expr {
+        let location = value.location;
+        match value.into_data() {
+            ExprKind::Spread { value } => {
+                let value = self.spill(gen, value);
+                expr(location, ExprKind::Spread { value })
+            }
+            data @ ExprKind::Missing
+            | data @ ExprKind::This
+            | data @ ExprKind::Super
+            | data @ ExprKind::Null
+            | data @ ExprKind::Undefined
+            | data @ ExprKind::Boolean { .. }
+            | data @ ExprKind::Number { .. }
+            | data @ ExprKind::String { .. }
+            | data @ ExprKind::Function { .. }
+            | data @ ExprKind::Arrow { .. } => expr(location, data),
+            data => {
+                let temp = self.generator_temp(gen);
+                gen.emit(expr_stmt(assign(
+                    identifier(location, temp),
+                    expr(location, data),
+                )));
+                identifier(location, temp)
+            }
+        }
+    }
+
+    // Instance fields are assigned in the constructor. The static members are
+    // returned to the caller.
+    //
+    //   class A extends B { a = 1; #b = 2; static c = 3 }
+    //
+    // becomes
+    //
+    //   _a = new WeakMap();
+    //   class A extends B { constructor() { super(...arguments); this.a = 1; _a.set(this, 2); } }
+    //   A.c = 3;
+    //
+    // Objects get their private methods before any field is initialized.
+    fn lower_class_members(&mut self, class: &mut Class, mut before: Vec<Expr>) -> LoweredClass {
+        let location = class
+            .properties
+            .first()
+            .map_or(0, |property| property.key.location);
+        let constructor_index = class.properties.iter().position(is_constructor);
+        let super_call_index =
+            constructor_index.and_then(|index| super_call_index(&class.properties[index]));
+        let is_derived = class.extends.is_some();
+
+        let mut instance_brands = vec![];
+        let mut instance_fields = vec![];
+        let mut static_brands = vec![];
+        let mut static_members = vec![];
+        let mut properties = vec![];
+        let mut branded = HashSet::new();
+        for property in std::mem::take(&mut class.properties) {
+            let key_location = property.key.location;
+            if property.kind == PropertyKind::PropertyStaticBlock {
+                if let Some(ExprKind::Function { function }) = property.value.map(Expr::into_data) {
+                    static_members.push(StaticMember::Block(function));
+                }
+                continue;
+            }
+
+            if let Some(reference) = private_ref(&property.key) {
+                let member = self.private_members[&reference];
+                let value = match property.value {
+                    Some(value) => value,
+                    None => {
+                        let value = property
+                            .initializer
+                            .unwrap_or_else(|| expr(key_location, ExprKind::Undefined));
+                        if property.is_static {
+                            static_members.push(StaticMember::PrivateField(member.storage, value));
+                        } else {
+                            let this = expr(key_location, ExprKind::This);
+                            instance_fields.push(call(
+                                dot(
+                                    identifier(key_location, member.storage),
+                                    "set".to_owned(),
+                                    key_location,
+                                ),
+                                vec![this, value],
+                            ));
+                        }
+                        continue;
+                    }
+                };
+
+                let function = match property.kind {
+                    PropertyKind::PropertyGet => member.getter,
+                    PropertyKind::PropertySet => member.setter,
+                    _ => member.method,
+                }
+                .expect("Every private method has a temporary");
+                before.push(assign(identifier(key_location, function), value));
+                if branded.insert(reference) {
+                    if property.is_static {
+                        static_brands.push(StaticMember::Brand(member.storage));
+                    } else {
+                        let this = expr(key_location, ExprKind::This);
+                        instance_brands.push(call(
+                            dot(
+                                identifier(key_location, member.storage),
+                                "add".to_owned(),
+                                key_location,
+                            ),
+                            vec![this],
+                        ));
+                    }
+                }
+                continue;
+            }
+
+            if property.value.is_some() {
+                properties.push(property);
+            } else if property.is_static {
+                static_members.push(StaticMember::Field(property));
+            } else {
+                let this = expr(key_location, ExprKind::This);
+                instance_fields.push(assign_field(this, property));
+            }
+        }
+        class.properties = properties;
+        static_brands.append(&mut static_members);
+        let lowered = LoweredClass {
+            before,
+            static_members: static_brands,
+        };
+        if instance_brands.is_empty() && instance_fields.is_empty() {
+            return lowered;
+        }
+
+        let assignments: Vec<Stmt> = instance_brands
+            .into_iter()
+            .chain(instance_fields)
+            .map(expr_stmt)
+            .collect();
+        let constructor_index = class.properties.iter().position(is_constructor);
+        match constructor_index {
+            Some(index) => {
+                if let Some(ExprKind::Function { function }) = class.properties[index]
+                    .value
+                    .as_mut()
+                    .map(|v| v.data.as_mut())
+                {
+                    let stmts = &mut function.body.stmts;
+                    let index = match super_call_index {
+                        Some(index) => index + 1,
+                        None => prologue_len(stmts),
+                    };
+                    stmts.splice(index..index, assignments);
+                }
+            }
+            None => {
+                let mut stmts = vec![];
+                if is_derived {
+                    let arguments = identifier(location, self.global_ref("arguments"));
+                    let spread = expr(location, ExprKind::Spread { value: arguments });
+                    stmts.push(expr_stmt(call(
+                        expr(location, ExprKind::Super),
+                        vec![spread],
+                    )));
+                }
+                stmts.extend(assignments);
+                let function = Function {
+                    name: None,
+                    args: vec![],
+                    is_async: false,
+                    is_generator: false,
+                    has_rest_arg: false,
+                    body: FunctionBody { location, stmts },
+                };
+                class.properties.insert(
+                    0,
+                    Property {
+                        kind: PropertyKind::PropertyNormal,
+                        is_computed: false,
+                        is_method: true,
+                        is_static: false,
+                        key: string_expr(location, "constructor"),
+                        prefer_quoted_key: false,
+                        value: Some(expr(location, ExprKind::Function { function })),
+                        initializer: None,
+                    },
+                );
+            }
+        }
+        lowered
+    }
+
+    fn private_member(&self, member: &Expr) -> Option<PrivateMember> {
+        match member.data.as_ref() {
+            ExprKind::Index {
+                index,
+                optional_chain: OptionalChain::None,
+                ..
+            } => private_ref(index).and_then(|r| self.private_members.get(&r).copied()),
+            _ => None,
+        }
+    }
+
+    fn has_private_link(&self, chain: &Expr) -> bool {
+        if self.private_members.is_empty() {
+            return false;
+        }
+        let mut link = chain;
+        loop {
+            let target = match link.data.as_ref() {
+                ExprKind::Index { target, index, .. } => {
+                    if private_ref(index).is_some_and(|r| self.private_members.contains_key(&r)) {
+                        return true;
+                    }
+                    target
+                }
+                ExprKind::Dot { target, .. } | ExprKind::Call { target, .. } => target,
+                _ => return false,
+            };
+            if ends_chain(optional_chain(link), target) {
+                return false;
+            }
+            link = target;
+        }
+    }
+
+    // Uses of private members go through helpers that check that the object
+    // has the member, which throws a TypeError like the real thing:
+    //
+    //   a.#b        =>  __privateGet(a, _b)
+    //   a.#b = c    =>  __privateSet(a, _b, c)
+    //   a.#b += c   =>  __privateSet(_a = a, _b, __privateGet(_a, _b) + c)
+    //   a.#c()      =>  __privateMethod(a, _c, _d).call(a)
+    //   #b in a     =>  _b.has(a)
+    //
+    // The parts of the expression have already been visited.
+    fn lower_private_access(&mut self, access: &mut Expr) {
+        if self.private_members.is_empty() {
+            return;
+        }
+        if let Some((object, member)) = self.take_private_member(access) {
+            *access = self.private_get(object, member);
+            return;
+        }
+
+        let location = access.location;
+        let lowered = match access.data.as_mut() {
+            ExprKind::Call {
+                target,
+                args,
+                optional_chain,
+                ..
+            } if *optional_chain != OptionalChain::Start => {
+                let (object, member) = match self.take_private_member(target) {
+                    Some(found) => found,
+                    None => return,
+                };
+                let (object, this) = self.capture(object);
+                let function = self.private_get(object, member);
+                let mut call_args = vec![this];
+                call_args.append(args);
+                call(dot(function, "call".to_owned(), location), call_args)
+            }
+            ExprKind::Binary {
+                op_code,
+                left,
+                right,
+            } if op_code.is_binary_assign() => {
+                let (object, member) = match self.take_private_member(left) {
+                    Some(found) => found,
+                    None => return,
+                };
+                let value = take_expr(right);
+                match compound_assign_op(*op_code) {
+                    None => self.private_set(object, member, value),
+                    Some(op_code) => {
+                        let (object, object_again) = self.capture(object);
+                        let current = self.private_get(object_again, member);
+                        let value = if op_code == OperatorCode::BinOpPow
+                            && !self.options.target.supports(Feature::ExponentOperator)
+                        {
+                            self.math_pow(current, value)
+                        } else {
+                            binary(op_code, current, value)
+                        };
+                        self.private_set(object, member, value)
+                    }
+                }
+            }
+
+            // "++a.#b" => "__privateSet(_a = a, _b, +__privateGet(_a, _b) + 1)"
+            // "a.#b++" => "(_c = +__privateGet(_a = a, _b), __privateSet(_a, _b, _c + 1), _c)"
+            ExprKind::Unary { op_code, value } if op_code.is_unary_update() => {
+                let op_code = *op_code;
+                let (object, member) = match self.take_private_member(value) {
+                    Some(found) => found,
+                    None => return,
+                };
+                let (object, object_again) = self.capture(object);
+                let one = expr(location, ExprKind::Number { value: 1.0 });
+                let update = match op_code {
+                    OperatorCode::UnOpPreInc | OperatorCode::UnOpPostInc => OperatorCode::BinOpAdd,
+                    _ => OperatorCode::BinOpSub,
+                };
+                if op_code.is_prefix() {
+                    let current = self.private_get(object_again, member);
+                    let current = unary(OperatorCode::UnOpPos, current);
+                    self.private_set(object, member, binary(update, current, one))
+                } else {
+                    let temp = self.temp_refs.generate(self.symbols);
+                    let current = self.private_get(object, member);
+                    let current = unary(OperatorCode::UnOpPos, current);
+                    let next = binary(update, identifier(location, temp), one);
+                    let values = vec![
+                        assign(identifier(location, temp), current),
+                        self.private_set(object_again, member, next),
+                        identifier(location, temp),
+                    ];
+                    join_all_with_comma(values.into_iter()).expect("There are three values")
+                }
+            }
+
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpIn,
+                left,
+                right,
+            } => {
+                let member = match private_ref(left).and_then(|r| self.private_members.get(&r)) {
+                    Some(member) => *member,
+                    None => return,
+                };
+                let storage = identifier(location, member.storage);
+                call(
+                    dot(storage, "has".to_owned(), location),
+                    vec![take_expr(right)],
+                )
+            }
+
+            _ => return,
+        };
+        *access = lowered;
+    }
+
+    // Returns the object of "a.#b" along with the member, leaving the
+    // expression empty, or None if it's something else
+    fn take_private_member(&self, member: &mut Expr) -> Option<(Expr, PrivateMember)> {
+        let private_member = self.private_member(member)?;
+        Some((take_expr(chain_target_mut(member)), private_member))
+    }
+
+    fn private_get(&mut self, object: Expr, member: PrivateMember) -> Expr {
+        let location = object.location;
+        let storage = identifier(location, member.storage);
+        if let Some(method) = member.method {
+            let helper = self.helper("__privateMethod");
+            return call(
+                identifier(location, helper),
+                vec![object, storage, identifier(location, method)],
+            );
+        }
+        let helper = self.helper("__privateGet");
+        let mut args = vec![object, storage];
+        args.extend(member.getter.map(|getter| identifier(location, getter)));
+        call(identifier(location, helper), args)
+    }
+
+    fn private_set(&mut self, object: Expr, member: PrivateMember, value: Expr) -> Expr {
+        let location = object.location;
+        let helper = self.helper("__privateSet");
+        let mut args = vec![object, identifier(location, member.storage), value];
+        args.extend(member.setter.map(|setter| identifier(location, setter)));
+        call(identifier(location, helper), args)
+    }
+
+    // Helpers are declared at the top of the module the first time they're
+    // used. They're written in ES5:
+    //
+    //   var __privateGet = function(obj, member, getter) {
+    //     if (!member.has(obj)) throw TypeError("Cannot read from private field");
+    //     return getter ? getter.call(obj) : member.get(obj);
+    //   };
+    //   var __privateSet = function(obj, member, value, setter) {
+    //     if (!member.has(obj)) throw TypeError("Cannot write to private field");
+    //     setter ? setter.call(obj, value) : member.set(obj, value);
+    //     return value;
+    //   };
+    //   var __privateMethod = function(obj, member, method) {
+    //     if (!member.has(obj)) throw TypeError("Cannot access private method");
+    //     return method;
+    //   };
+    //   var __read = function(value, count) {
+    //     var method = typeof Symbol == "function" && value[Symbol.iterator];
+    //     if (!method) return value;
+    //     var iterator = method.call(value), items = [], step;
+    //     while ((count === void 0 || count-- > 0) && !(step = iterator.next()).done) items.push(step.value);
+    //     if ((!step || !step.done) && iterator.return) iterator.return();
+    //     return items;
+    //   };
+    //   var __objRest = function(source, exclude) {
+    //     var target = {}, key, symbols, i;
+    //     if (source == null) throw TypeError("Cannot destructure " + source);
+    //     for (i = 0; i < exclude.length; i++) if (typeof exclude[i] != "symbol") exclude[i] += "";
+    //     for (key in source) if (Object.prototype.hasOwnProperty.call(source, key) && exclude.indexOf(key) < 0) target[key] = source[key];
+    //     if (typeof Object.getOwnPropertySymbols == "function")
+    //       for (symbols = Object.getOwnPropertySymbols(source), i = 0; i < symbols.length; i++)
+    //         if (exclude.indexOf(symbols[i]) < 0 && Object.prototype.propertyIsEnumerable.call(source, symbols[i])) target[symbols[i]] = source[symbols[i]];
+    //     return target;
+    //   };
+    //   var __spread = function(items) {
+    //     for (var i = 1, values, j; i < arguments.length; i++)
+    //       for (values = __read(arguments[i]), j = 0; j < values.length; j++) items.push(values[j]);
+    //     return items;
+    //   };
+    //
+    // Without "Symbol" there are no iterators, and "__read" returns the value
+    // as-is so that strings and array-like objects still work. Computed keys
+    // are turned into strings the way property access does, so "__objRest"
+    // can compare them with the keys of the object. The runtime for lowered
+    // generators is in "generator_helper".
+    fn helper(&mut self, name: &'static str) -> Reference {
+        if let Some(reference) = self.temp_refs.helpers.get(name) {
+            return *reference;
+        }
+
+        let location = 0;
+        let (args, stmts) = match name {
+            "__read" => self.read_helper(),
+            "__objRest" => self.obj_rest_helper(),
+            "__spread" => self.spread_helper(),
+            "__generator" => self.generator_helper(),
+            _ => self.private_helper(name),
+        };
+
+        let source_index = self.temp_refs.source_index();
+        let reference = new_symbol(
+            self.symbols,
+            source_index,
+            SymbolKind::Other,
+            name.to_owned(),
+        );
+        self.temp_refs.helpers.insert(name, reference);
+        let function = function_expr(location, &args, stmts);
+        self.temp_refs
+            .helper_decls
+            .push(var_decl(location, reference, Some(function)));
+        reference
+    }
+
+    // The arguments and the other variables of a helper
+    fn helper_symbols(&mut self, names: &[&str]) -> Vec<Reference> {
+        let source_index = self.temp_refs.source_index();
+        names
+            .iter()
+            .map(|name| {
+                new_symbol(
+                    self.symbols,
+                    source_index,
+                    SymbolKind::Hoisted,
+                    (*name).to_owned(),
+                )
+            })
+            .collect()
+    }
+
+    fn private_helper(&mut self, name: &'static str) -> (Vec<Reference>, Vec<Stmt>) {
+        let location = 0;
+        let (arg_names, action): (&[&str], &str) = match name {
+            "__privateGet" => (&["obj", "member", "getter"], "read from private field"),
+            "__privateSet" => (
+                &["obj", "member", "value", "setter"],
+                "write to private field",
+            ),
+            _ => (&["obj", "member", "method"], "access private method"),
+        };
+        let args = self.helper_symbols(arg_names);
+        let arg = |i: usize| identifier(location, args[i]);
+        let method = |target: Expr, name: &str, args: Vec<Expr>| {
+            call(dot(target, name.to_owned(), location), args)
+        };
+
+        let type_error = identifier(location, self.global_ref("TypeError"));
+        let message = string_expr(location, &format!("Cannot {}", action));
+        let check = Stmt {
+            location,
+            data: Box::new(StmtKind::If {
+                test: unary(OperatorCode::UnOpNot, method(arg(1), "has", vec![arg(0)])),
+                yes: Stmt {
+                    location,
+                    data: Box::new(StmtKind::Throw {
+                        value: call(type_error, vec![message]),
+                    }),
+                },
+                no: None,
+            }),
+        };
+        let mut stmts = vec![check];
+        let result = match name {
+            "__privateGet" => expr(
+                location,
+                ExprKind::If {
+                    test: arg(2),
+                    yes: method(arg(2), "call", vec![arg(0)]),
+                    no: method(arg(1), "get", vec![arg(0)]),
+                },
+            ),
+            "__privateSet" => {
+                stmts.push(expr_stmt(expr(
+                    location,
+                    ExprKind::If {
+                        test: arg(3),
+                        yes: method(arg(3), "call", vec![arg(0), arg(2)]),
+                        no: method(arg(1), "set", vec![arg(0), arg(2)]),
+                    },
+                )));
+                arg(2)
+            }
+            _ => arg(2),
+        };
+        stmts.push(return_stmt(result));
+        (args, stmts)
+    }
+
+    fn read_helper(&mut self) -> (Vec<Reference>, Vec<Stmt>) {
+        let location = 0;
+        let symbols =
+            self.helper_symbols(&["value", "count", "method", "iterator", "items", "step"]);
+        let value = || identifier(location, symbols[0]);
+        let count = || identifier(location, symbols[1]);
+        let method = || identifier(location, symbols[2]);
+        let iterator = || identifier(location, symbols[3]);
+        let items = || identifier(location, symbols[4]);
+        let step = || identifier(location, symbols[5]);
+        let symbol_ref = self.global_ref("Symbol");
+        let symbol = || identifier(location, symbol_ref);
+        let declare = |decls: Vec<(Reference, Option<Expr>)>| {
+            let decls = decls
+                .into_iter()
+                .map(|(reference, value)| var_decl(location, reference, value))
+                .collect();
+            local_stmt(LocalKind::Var, decls)
+        };
+        let member = |target: Expr, name: &str| dot(target, name.to_owned(), location);
+        let undefined = || expr(location, ExprKind::Undefined);
+
+        let has_symbol = binary(
+            OperatorCode::BinOpLooseEq,
+            unary(OperatorCode::UnOpTypeof, symbol()),
+            string_expr(location, "function"),
+        );
+        let get_method = binary(
+            OperatorCode::BinOpLogicalAnd,
+            has_symbol,
+            index_expr(value(), member(symbol(), "iterator")),
+        );
+        let has_count = binary(
+            OperatorCode::BinOpLogicalOr,
+            binary(OperatorCode::BinOpStrictEq, count(), undefined()),
+            binary(
+                OperatorCode::BinOpGt,
+                unary(OperatorCode::UnOpPostDec, count()),
+                number_expr(location, 0),
+            ),
+        );
+        let next = assign(step(), call(member(iterator(), "next"), vec![]));
+        let is_done = member(step(), "done");
+        let loop_test = binary(
+            OperatorCode::BinOpLogicalAnd,
+            has_count,
+            unary(OperatorCode::UnOpNot, member(next, "done")),
+        );
+        let push = call(member(items(), "push"), vec![member(step(), "value")]);
+        let is_unfinished = binary(
+            OperatorCode::BinOpLogicalOr,
+            unary(OperatorCode::UnOpNot, step()),
+            unary(OperatorCode::UnOpNot, is_done),
+        );
+        let close_test = binary(
+            OperatorCode::BinOpLogicalAnd,
+            is_unfinished,
+            member(iterator(), "return"),
+        );
+
+        let stmts = vec![
+            declare(vec![(symbols[2], Some(get_method))]),
+            if_stmt(unary(OperatorCode::UnOpNot, method()), return_stmt(value())),
+            declare(vec![
+                (
+                    symbols[3],
+                    Some(call(member(method(), "call"), vec![value()])),
+                ),
+                (
+                    symbols[4],
+                    Some(expr(location, ExprKind::Array { items: vec![] })),
+                ),
+                (symbols[5], None),
+            ]),
+            Stmt {
+                location,
+                data: Box::new(StmtKind::While {
+                    test: loop_test,
+                    body: expr_stmt(push),
+                }),
+            },
+            if_stmt(
+                close_test,
+                expr_stmt(call(member(iterator(), "return"), vec![])),
+            ),
+            return_stmt(items()),
+        ];
+        (symbols[..2].to_vec(), stmts)
+    }
+
+    fn spread_helper(&mut self) -> (Vec<Reference>, Vec<Stmt>) {
+        let location = 0;
+        let read = identifier(location, self.helper("__read"));
+        let symbols = self.helper_symbols(&["items", "i", "values", "j"]);
+        let items = || identifier(location, symbols[0]);
+        let i = || identifier(location, symbols[1]);
+        let values = || identifier(location, symbols[2]);
+        let j = || identifier(location, symbols[3]);
+        let arguments_ref = self.global_ref("arguments");
+        let arguments = || identifier(location, arguments_ref);
+        let length = |array: Expr| dot(array, "length".to_owned(), location);
+        let for_stmt = |init: Stmt, test: Expr, update: Expr, body: Stmt| Stmt {
+            location,
+            data: Box::new(StmtKind::For {
+                init: Some(init),
+                test: Some(test),
+                update: Some(update),
+                body,
+            }),
+        };
+
+        let push = call(
+            dot(items(), "push".to_owned(), location),
+            vec![index_expr(values(), j())],
+        );
+        let copy_values = for_stmt(
+            expr_stmt(binary(
+                OperatorCode::BinOpComma,
+                assign(values(), call(read, vec![index_expr(arguments(), i())])),
+                assign(j(), number_expr(location, 0)),
+            )),
+            binary(OperatorCode::BinOpLt, j(), length(values())),
+            unary(OperatorCode::UnOpPostInc, j()),
+            expr_stmt(push),
+        );
+        let copy_arguments = for_stmt(
+            local_stmt(
+                LocalKind::Var,
+                vec![
+                    var_decl(location, symbols[1], Some(number_expr(location, 1))),
+                    var_decl(location, symbols[2], None),
+                    var_decl(location, symbols[3], None),
+                ],
+            ),
+            binary(OperatorCode::BinOpLt, i(), length(arguments())),
+            unary(OperatorCode::UnOpPostInc, i()),
+            copy_values,
+        );
+        (
+            symbols[..1].to_vec(),
+            vec![copy_arguments, return_stmt(items())],
+        )
+    }
+
+    fn obj_rest_helper(&mut self) -> (Vec<Reference>, Vec<Stmt>) {
+        let location = 0;
+        let symbols = self.helper_symbols(&["source", "exclude", "target", "key", "symbols", "i"]);
+        let source = || identifier(location, symbols[0]);
+        let exclude = || identifier(location, symbols[1]);
+        let target = || identifier(location, symbols[2]);
+        let key = || identifier(location, symbols[3]);
+        let own_symbols = || identifier(location, symbols[4]);
+        let i = || identifier(location, symbols[5]);
+        let object_ref = self.global_ref("Object");
+        let object = || identifier(location, object_ref);
+        let type_error = identifier(location, self.global_ref("TypeError"));
+        let member = |target: Expr, name: &str| dot(target, name.to_owned(), location);
+        let method = |target: Expr, name: &str, args: Vec<Expr>| call(member(target, name), args);
+        let prototype_call = |name: &str, args: Vec<Expr>| {
+            method(member(member(object(), "prototype"), name), "call", args)
+        };
+        let is_excluded = |value: Expr| {
+            binary(
+                OperatorCode::BinOpLt,
+                method(exclude(), "indexOf", vec![value]),
+                number_expr(location, 0),
+            )
+        };
+        let copy = |value: &dyn Fn() -> Expr| {
+            expr_stmt(assign(
+                index_expr(target(), value()),
+                index_expr(source(), value()),
+            ))
+        };
+        let for_stmt = |init: Expr, test: Expr, update: Expr, body: Stmt| Stmt {
+            location,
+            data: Box::new(StmtKind::For {
+                init: Some(expr_stmt(init)),
+                test: Some(test),
+                update: Some(update),
+                body,
+            }),
+        };
+        let is_before = |array: Expr| binary(OperatorCode::BinOpLt, i(), member(array, "length"));
+        let next = || unary(OperatorCode::UnOpPostInc, i());
+
+        let declare = local_stmt(
+            LocalKind::Var,
+            vec![
+                var_decl(
+                    location,
+                    symbols[2],
+                    Some(expr(location, ExprKind::Object { properties: vec![] })),
+                ),
+                var_decl(location, symbols[3], None),
+                var_decl(location, symbols[4], None),
+                var_decl(location, symbols[5], None),
+            ],
+        );
+        let check = if_stmt(
+            binary(
+                OperatorCode::BinOpLooseEq,
+                source(),
+                expr(location, ExprKind::Null),
+            ),
+            Stmt {
+                location,
+                data: Box::new(StmtKind::Throw {
+                    value: call(
+                        type_error,
+                        vec![binary(
+                            OperatorCode::BinOpAdd,
+                            string_expr(location, "Cannot destructure "),
+                            source(),
+                        )],
+                    ),
+                }),
+            },
+        );
+        let excluded = || index_expr(exclude(), i());
+        let to_strings = for_stmt(
+            assign(i(), number_expr(location, 0)),
+            is_before(exclude()),
+            next(),
+            if_stmt(
+                binary(
+                    OperatorCode::BinOpLooseNe,
+                    unary(OperatorCode::UnOpTypeof, excluded()),
+                    string_expr(location, "symbol"),
+                ),
+                expr_stmt(binary(
+                    OperatorCode::BinOpAddAssign,
+                    excluded(),
+                    string_expr(location, ""),
+                )),
+            ),
+        );
+        let copy_keys = Stmt {
+            location,
+            data: Box::new(StmtKind::ForIn {
+                init: expr_stmt(key()),
+                value: source(),
+                body: if_stmt(
+                    binary(
+                        OperatorCode::BinOpLogicalAnd,
+                        prototype_call("hasOwnProperty", vec![source(), key()]),
+                        is_excluded(key()),
+                    ),
+                    copy(&key),
+                ),
+            }),
+        };
+        let symbol = || index_expr(own_symbols(), i());
+        let get_symbols = || method(object(), "getOwnPropertySymbols", vec![source()]);
+        let copy_symbols = if_stmt(
+            binary(
+                OperatorCode::BinOpLooseEq,
+                unary(
+                    OperatorCode::UnOpTypeof,
+                    member(object(), "getOwnPropertySymbols"),
+                ),
+                string_expr(location, "function"),
+            ),
+            for_stmt(
+                binary(
+                    OperatorCode::BinOpComma,
+                    assign(own_symbols(), get_symbols()),
+                    assign(i(), number_expr(location, 0)),
+                ),
+                is_before(own_symbols()),
+                next(),
+                if_stmt(
+                    binary(
+                        OperatorCode::BinOpLogicalAnd,
+                        is_excluded(symbol()),
+                        prototype_call("propertyIsEnumerable", vec![source(), symbol()]),
+                    ),
+                    copy(&symbol),
+                ),
+            ),
+        );
+
+        let stmts = vec![
+            declare,
+            check,
+            to_strings,
+            copy_keys,
+            copy_symbols,
+            return_stmt(target()),
+        ];
+        (symbols[..2].to_vec(), stmts)
+    }
+
+    // The runtime for lowered generators. The body of the generator returns
+    // an instruction every time it stops, and this runs it:
+    //
+    //   var __generator = function(thisArg, body) {
+    //     var sent, delegate, running, started, state = {label: 0, trys: [], ops: [], sent: function() {
+    //       if (sent[0] & 1) throw sent[1];
+    //       return sent[1];
+    //     }};
+    //     var step = function(op) {
+    //       var method, result, iterator, region;
+    //       if (running) throw TypeError("Generator is already running");
+    //       if (!started) {
+    //         started = true;
+    //         if (op[0]) state = null;
+    //       }
+    //       while (state) {
+    //         running = true;
+    //         try {
+    //           if (delegate) {
+    //             method = op[0] & 2 ? delegate.return : op[0] ? delegate.throw : delegate.next;
+    //             if (method) {
+    //               result = method.call(delegate, op[1]);
+    //               if (!result.done) return result;
+    //               op = [op[0] & 2, result.value];
+    //             }
+    //             delegate = null;
+    //           }
+    //           switch (op[0]) {
+    //             case 0: case 1: sent = op; break;
+    //             case 4: state.label++; return {value: op[1], done: false};
+    //             case 5:
+    //               state.label++;
+    //               iterator = typeof Symbol == "function" && op[1][Symbol.iterator];
+    //               delegate = iterator ? iterator.call(op[1]) : op[1];
+    //               op = [0];
+    //               continue;
+    //             case 7: op = state.ops.pop(); state.trys.pop(); continue;
+    //             default:
+    //               region = state.trys[state.trys.length - 1];
+    //               if (!region && (op[0] == 6 || op[0] == 2)) { state = null; continue; }
+    //               if (op[0] == 3 && (!region || op[1] > region[0] && op[1] < region[3])) { state.label = op[1]; break; }
+    //               if (op[0] == 6 && state.label < region[1]) { state.label = region[1]; sent = op; break; }
+    //               if (state.label < region[2]) { state.label = region[2]; state.ops.push(op); break; }
+    //               if (region[2]) state.ops.pop();
+    //               state.trys.pop();
+    //               continue;
+    //           }
+    //           op = body.call(thisArg, state);
+    //         } catch (error) {
+    //           op = [6, error];
+    //           delegate = null;
+    //         } finally {
+    //           running = false;
+    //         }
+    //       }
+    //       if (op[0] & 5) throw op[1];
+    //       return {value: op[0] ? op[1] : void 0, done: true};
+    //     };
+    //     var generator = {
+    //       next: function(value) { return step([0, value]); },
+    //       throw: function(error) { return step([1, error]); },
+    //       return: function(value) { return step([2, value]); }
+    //     };
+    //     if (typeof Symbol == "function") generator[Symbol.iterator] = function() { return this; };
+    //     return generator;
+    //   };
+    //
+    // The routing of exceptions, "return()", and jumps through "try"
+    // statements follows the stack of regions in "state.trys", where a region
+    // is "[start, catch, finally, end]". A jump or a return that leaves a
+    // region with a "finally" is saved in "state.ops" until the "finally" case
+    // ends with instruction 7.
+    fn generator_helper(&mut self) -> (Vec<Reference>, Vec<Stmt>) {
+        let location = 0;
+        let symbols = self.helper_symbols(&[
+            "thisArg",
+            "body",
+            "sent",
+            "delegate",
+            "running",
+            "started",
+            "state",
+            "step",
+            "generator",
+            "op",
+            "method",
+            "result",
+            "iterator",
+            "region",
+            "error",
+            "value",
+            "error",
+            "value",
+        ]);
+        let this_arg = || identifier(location, symbols[0]);
+        let body = || identifier(location, symbols[1]);
+        let sent = || identifier(location, symbols[2]);
+        let delegate = || identifier(location, symbols[3]);
+        let running = || identifier(location, symbols[4]);
+        let started = || identifier(location, symbols[5]);
+        let state = || identifier(location, symbols[6]);
+        let step = || identifier(location, symbols[7]);
+        let generator = || identifier(location, symbols[8]);
+        let op = || identifier(location, symbols[9]);
+        let method = || identifier(location, symbols[10]);
+        let result = || identifier(location, symbols[11]);
+        let iterator = || identifier(location, symbols[12]);
+        let region = || identifier(location, symbols[13]);
+        let symbol_ref = self.global_ref("Symbol");
+        let symbol = || identifier(location, symbol_ref);
+        let type_error = identifier(location, self.global_ref("TypeError"));
+
+        let number = |value: usize| number_expr(location, value);
+        let member = |target: Expr, name: &str| dot(target, name.to_owned(), location);
+        let at = |target: Expr, i: usize| index_expr(target, number(i));
+        let op_code = || at(op(), 0);
+        let op_value = || at(op(), 1);
+        let array = |items: Vec<Expr>| expr(location, ExprKind::Array { items });
+        let set = |target: Expr, value: Expr| expr_stmt(assign(target, value));
+        let block = |stmts: Vec<Stmt>| Stmt {
+            location,
+            data: Box::new(StmtKind::Block { stmts }),
+        };
+        let stmt = |kind: StmtKind| Stmt {
+            location,
+            data: Box::new(kind),
+        };
+        let object = |properties: Vec<(&str, Expr)>| object_expr(location, properties);
+        let has_symbol = || {
+            binary(
+                OperatorCode::BinOpLooseEq,
+                unary(OperatorCode::UnOpTypeof, symbol()),
+                string_expr(location, "function"),
+            )
+        };
+        let is_op = |code: usize| binary(OperatorCode::BinOpLooseEq, op_code(), number(code));
+        let and = |left: Expr, right: Expr| binary(OperatorCode::BinOpLogicalAnd, left, right);
+        let or = |left: Expr, right: Expr| binary(OperatorCode::BinOpLogicalOr, left, right);
+        let not = |value: Expr| unary(OperatorCode::UnOpNot, value);
+        let bit_and = |code: usize| binary(OperatorCode::BinOpBitwiseAnd, op_code(), number(code));
+        let state_label = || member(state(), "label");
+        let trys = || member(state(), "trys");
+        let ops = || member(state(), "ops");
+        let pop = |target: Expr| call(member(target, "pop"), vec![]);
+        let next_label = || expr_stmt(unary(OperatorCode::UnOpPostInc, state_label()));
+        let conditional =
+            |test: Expr, yes: Expr, no: Expr| expr(location, ExprKind::If { test, yes, no });
+
+        // "sent()" returns the value the generator was resumed with
+        let get_sent = function_expr(
+            location,
+            &[],
+            vec![
+                if_stmt(
+                    binary(OperatorCode::BinOpBitwiseAnd, at(sent(), 0), number(1)),
+                    stmt(StmtKind::Throw {
+                        value: at(sent(), 1),
+                    }),
+                ),
+                return_stmt(at(sent(), 1)),
+            ],
+        );
+        let state_object = object(vec![
+            ("label", number(0)),
+            ("trys", array(vec![])),
+            ("ops", array(vec![])),
+            ("sent", get_sent),
+        ]);
+
+        let run_delegate = if_stmt(
+            delegate(),
+            block(vec![
+                set(
+                    method(),
+                    conditional(
+                        bit_and(2),
+                        member(delegate(), "return"),
+                        conditional(
+                            op_code(),
+                            member(delegate(), "throw"),
+                            member(delegate(), "next"),
+                        ),
+                    ),
+                ),
+                if_stmt(
+                    method(),
+                    block(vec![
+                        set(
+                            result(),
+                            call(member(method(), "call"), vec![delegate(), op_value()]),
+                        ),
+                        if_stmt(not(member(result(), "done")), return_stmt(result())),
+                        set(op(), array(vec![bit_and(2), member(result(), "value")])),
+                    ]),
+                ),
+                set(delegate(), expr(location, ExprKind::Null)),
+            ]),
+        );
+
+        let break_stmt = || stmt(StmtKind::Break { name: None });
+        let continue_stmt = || stmt(StmtKind::Continue { name: None });
+        let case = |values: &[usize], body: Vec<Stmt>| {
+            let mut cases: Vec<Case> = values
+                .iter()
+                .map(|&value| Case {
+                    value: Some(number(value)),
+                    body: vec![],
+                })
+                .collect();
+            if let Some(last) = cases.last_mut() {
+                last.body = body;
+            }
+            cases
+        };
+        let get_iterator = and(
+            has_symbol(),
+            index_expr(op_value(), member(symbol(), "iterator")),
+        );
+        let region_end = and(
+            binary(OperatorCode::BinOpGt, op_value(), at(region(), 0)),
+            binary(OperatorCode::BinOpLt, op_value(), at(region(), 3)),
+        );
+        let route = vec![
+            set(
+                region(),
+                index_expr(
+                    trys(),
+                    binary(OperatorCode::BinOpSub, member(trys(), "length"), number(1)),
+                ),
+            ),
+            if_stmt(
+                and(not(region()), or(is_op(6), is_op(GENERATOR_RETURN))),
+                block(vec![
+                    set(state(), expr(location, ExprKind::Null)),
+                    continue_stmt(),
+                ]),
+            ),
+            if_stmt(
+                and(is_op(GENERATOR_JUMP), or(not(region()), region_end)),
+                block(vec![set(state_label(), op_value()), break_stmt()]),
+            ),
+            if_stmt(
+                and(
+                    is_op(6),
+                    binary(OperatorCode::BinOpLt, state_label(), at(region(), 1)),
+                ),
+                block(vec![
+                    set(state_label(), at(region(), 1)),
+                    set(sent(), op()),
+                    break_stmt(),
+                ]),
+            ),
+            if_stmt(
+                binary(OperatorCode::BinOpLt, state_label(), at(region(), 2)),
+                block(vec![
+                    set(state_label(), at(region(), 2)),
+                    expr_stmt(call(member(ops(), "push"), vec![op()])),
+                    break_stmt(),
+                ]),
+            ),
+            if_stmt(at(region(), 2), expr_stmt(pop(ops()))),
+            expr_stmt(pop(trys())),
+            continue_stmt(),
+        ];
+        let mut cases = case(&[0, 1], vec![set(sent(), op()), break_stmt()]);
+        cases.extend(case(
+            &[GENERATOR_YIELD],
+            vec![
+                next_label(),
+                return_stmt(object(vec![
+                    ("value", op_value()),
+                    ("done", expr(location, ExprKind::Boolean { value: false })),
+                ])),
+            ],
+        ));
+        cases.extend(case(
+            &[GENERATOR_YIELD_STAR],
+            vec![
+                next_label(),
+                set(iterator(), get_iterator),
+                set(
+                    delegate(),
+                    conditional(
+                        iterator(),
+                        call(member(iterator(), "call"), vec![op_value()]),
+                        op_value(),
+                    ),
+                ),
+                set(op(), array(vec![number(0)])),
+                continue_stmt(),
+            ],
+        ));
+        cases.extend(case(
+            &[GENERATOR_END_FINALLY],
+            vec![
+                set(op(), pop(ops())),
+                expr_stmt(pop(trys())),
+                continue_stmt(),
+            ],
+        ));
+        cases.push(Case {
+            value: None,
+            body: route,
+        });
+        let dispatch = stmt(StmtKind::Switch {
+            test: op_code(),
+            body_location: location,
+            cases,
+        });
+
+        let try_step = stmt(StmtKind::Try {
+            body: vec![
+                run_delegate,
+                dispatch,
+                set(
+                    op(),
+                    call(member(body(), "call"), vec![this_arg(), state()]),
+                ),
+            ],
+            catch: Some(Catch {
+                location,
+                binding: Some(Binding {
+                    location,
+                    data: Box::new(BindingKind::Identifier {
+                        reference: symbols[14],
+                    }),
+                }),
+                body: vec![
+                    set(
+                        op(),
+                        array(vec![number(6), identifier(location, symbols[14])]),
+                    ),
+                    set(delegate(), expr(location, ExprKind::Null)),
+                ],
+            }),
+            finally: Some(Finally {
+                location,
+                stmts: vec![set(
+                    running(),
+                    expr(location, ExprKind::Boolean { value: false }),
+                )],
+            }),
+        });
+        let step_body = vec![
+            local_stmt(
+                LocalKind::Var,
+                vec![
+                    var_decl(location, symbols[10], None),
+                    var_decl(location, symbols[11], None),
+                    var_decl(location, symbols[12], None),
+                    var_decl(location, symbols[13], None),
+                ],
+            ),
+            if_stmt(
+                running(),
+                stmt(StmtKind::Throw {
+                    value: call(
+                        type_error,
+                        vec![string_expr(location, "Generator is already running")],
+                    ),
+                }),
+            ),
+            if_stmt(
+                not(started()),
+                block(vec![
+                    set(started(), expr(location, ExprKind::Boolean { value: true })),
+                    if_stmt(op_code(), set(state(), expr(location, ExprKind::Null))),
+                ]),
+            ),
+            stmt(StmtKind::While {
+                test: state(),
+                body: block(vec![
+                    set(running(), expr(location, ExprKind::Boolean { value: true })),
+                    try_step,
+                ]),
+            }),
+            if_stmt(bit_and(5), stmt(StmtKind::Throw { value: op_value() })),
+            return_stmt(object(vec![
+                (
+                    "value",
+                    conditional(op_code(), op_value(), expr(location, ExprKind::Undefined)),
+                ),
+                ("done", expr(location, ExprKind::Boolean { value: true })),
+            ])),
+        ];
+
+        let resume = |code: usize, arg: Reference| {
+            let value = identifier(location, arg);
+            let stmts = vec![return_stmt(call(
+                step(),
+                vec![array(vec![number(code), value])],
+            ))];
+            function_expr(location, &[arg], stmts)
+        };
+        let return_this = function_expr(
+            location,
+            &[],
+            vec![return_stmt(expr(location, ExprKind::This))],
+        );
+        let stmts = vec![
+            local_stmt(
+                LocalKind::Var,
+                vec![
+                    var_decl(location, symbols[2], None),
+                    var_decl(location, symbols[3], None),
+                    var_decl(location, symbols[4], None),
+                    var_decl(location, symbols[5], None),
+                    var_decl(location, symbols[6], Some(state_object)),
+                ],
+            ),
+            local_stmt(
+                LocalKind::Var,
+                vec![var_decl(
+                    location,
+                    symbols[7],
+                    Some(function_expr(location, &[symbols[9]], step_body)),
+                )],
+            ),
+            local_stmt(
+                LocalKind::Var,
+                vec![var_decl(
+                    location,
+                    symbols[8],
+                    Some(object(vec![
+                        ("next", resume(0, symbols[15])),
+                        ("throw", resume(1, symbols[16])),
+                        ("return", resume(GENERATOR_RETURN, symbols[17])),
+                    ])),
+                )],
+            ),
+            if_stmt(
+                has_symbol(),
+                set(
+                    index_expr(generator(), member(symbol(), "iterator")),
+                    return_this,
+                ),
+            ),
+            return_stmt(generator()),
+        ];
+        (symbols[..2].to_vec(), stmts)
+    }
+}
+
+fn new_symbol(
+    symbols: &mut SymbolMap,
+    source_index: usize,
+    kind: SymbolKind,
+    name: String,
+) -> Reference {
+    let inner = &mut symbols.outer[source_index];
+    let reference = Reference::new(source_index, inner.len());
+    inner.push(Symbol::new(kind, name));
+    reference
+}
+
+fn binding_has_object_rest(binding: &Binding) -> bool {
+    ensure_sufficient_stack(|| binding_has_object_rest_inner(binding))
+}
+
+fn binding_has_object_rest_inner(binding: &Binding) -> bool {
+    match binding.data.as_ref() {
+        BindingKind::Array { items, .. } => items
+            .iter()
+            .any(|item| binding_has_object_rest(&item.binding)),
+        BindingKind::Object { properties } => properties
+            .iter()
+            .any(|property| property.is_spread || binding_has_object_rest(&property.value)),
+        BindingKind::Missing | BindingKind::Identifier { .. } => false,
+    }
+}
+
+fn assign_pattern_has_object_rest(pattern: &Expr) -> bool {
+    ensure_sufficient_stack(|| assign_pattern_has_object_rest_inner(pattern))
+}
+
+fn assign_pattern_has_object_rest_inner(pattern: &Expr) -> bool {
+    match pattern.data.as_ref() {
+        ExprKind::Array { items } => items.iter().any(assign_pattern_has_object_rest),
+        ExprKind::Object { properties } => properties.iter().any(|property| {
+            property.kind == PropertyKind::PropertySpread
+                || property
+                    .value
+                    .as_ref()
+                    .is_some_and(assign_pattern_has_object_rest)
+        }),
+        ExprKind::Spread { value } => assign_pattern_has_object_rest(value),
+        ExprKind::Binary {
+            op_code: OperatorCode::BinOpAssign,
+            left,
+            ..
+        } => assign_pattern_has_object_rest(left),
+        _ => false,
+    }
+}
+
+fn binding_to_pattern(binding: Binding) -> Pattern {
+    ensure_sufficient_stack(|| binding_to_pattern_inner(binding))
+}
+
+fn binding_to_pattern_inner(binding: Binding) -> Pattern {
+    let location = binding.location;
+    match *binding.data {
+        BindingKind::Missing => Pattern::Missing,
+        BindingKind::Identifier { reference } => Pattern::Target(identifier(location, reference)),
+        BindingKind::Array { items, has_spread } => {
+            let count = items.len();
+            let items = items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| PatternItem {
+                    key: number_expr(item.binding.location, i),
+                    is_rest: has_spread && i + 1 == count,
+                    pattern: binding_to_pattern(item.binding),
+                    default_value: item.default_value,
+                })
+                .collect();
+            Pattern::Array {
+                items,
+                has_rest: has_spread,
+            }
+        }
+        BindingKind::Object { properties } => Pattern::Object {
+            items: properties
+                .into_iter()
+                .map(|property| PatternItem {
+                    key: property.key,
+                    is_rest: property.is_spread,
+                    pattern: binding_to_pattern(property.value),
+                    default_value: property.default_value,
+                })
+                .collect(),
+        },
+    }
+}
+
+// The parser leaves the left side of an assignment as an expression, where a
+// default value is an assignment and a rest element is a spread
+fn expr_to_pattern(pattern: Expr) -> Pattern {
+    ensure_sufficient_stack(|| expr_to_pattern_inner(pattern))
+}
+
+fn expr_to_pattern_inner(pattern: Expr) -> Pattern {
+    let location = pattern.location;
+    match pattern.into_data() {
+        ExprKind::Missing => Pattern::Missing,
+        ExprKind::Array { items } => {
+            let has_rest = items
+                .last()
+                .is_some_and(|item| matches!(item.data.as_ref(), ExprKind::Spread { .. }));
+            let items = items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let key = number_expr(item.location, i);
+                    let (pattern, default_value, is_rest) = match item.into_data() {
+                        ExprKind::Spread { value } => (value, None, true),
+                        ExprKind::Binary {
+                            op_code: OperatorCode::BinOpAssign,
+                            left,
+                            right,
+                        } => (left, Some(right), false),
+                        data => (expr(key.location, data), None, false),
+                    };
+                    PatternItem {
+                        key,
+                        is_rest,
+                        pattern: expr_to_pattern(pattern),
+                        default_value,
+                    }
+                })
+                .collect();
+            Pattern::Array { items, has_rest }
+        }
+        ExprKind::Object { properties } => Pattern::Object {
+            items: properties
+                .into_iter()
+                .map(|property| {
+                    let location = property.key.location;
+                    let value = property.value.unwrap_or_else(|| expr_missing(location));
+                    let value_location = value.location;
+                    let (pattern, default_value) = match value.into_data() {
+                        ExprKind::Binary {
+                            op_code: OperatorCode::BinOpAssign,
+                            left,
+                            right,
+                        } if property.initializer.is_none() => (left, Some(right)),
+                        data => (expr(value_location, data), property.initializer),
+                    };
+                    PatternItem {
+                        key: property.key,
+                        is_rest: property.kind == PropertyKind::PropertySpread,
+                        pattern: expr_to_pattern(pattern),
+                        default_value,
+                    }
+                })
+                .collect(),
+        },
+        data => Pattern::Target(expr(location, data)),
+    }
+}
+
+fn has_spread(items: &[Expr]) -> bool {
+    items
+        .iter()
+        .any(|item| matches!(item.data.as_ref(), ExprKind::Spread { .. }))
+}
+
+// Whether the body or the arguments of a method use "super". Arrow functions
+// use the "super" of the method they're in, but other functions don't.
+fn uses_super(function: &Function) -> bool {
+    struct SuperFinder(bool);
+
+    impl Visit for SuperFinder {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let ExprKind::Super = expr.data.as_ref() {
+                self.0 = true;
+            } else if !self.0 {
+                self.walk_expr(expr);
+            }
+        }
+
+        fn visit_function(&mut self, _function: &Function) {}
+    }
+
+    let mut finder = SuperFinder(false);
+    finder.walk_function(function);
+    finder.0
+}
+
+// "return [op, value]" from the body of a lowered generator
+fn generator_instruction(location: Location, op: usize, value: Option<Expr>) -> Stmt {
+    let mut items = vec![number_expr(location, op)];
+    items.extend(value);
+    return_stmt(expr(location, ExprKind::Array { items }))
+}
+
+// Whether the case doesn't fall through to the next one
+fn ends_case(stmts: &[Stmt]) -> bool {
+    stmts.last().is_some_and(|stmt| {
+        matches!(
+            stmt.data.as_ref(),
+            StmtKind::Return { .. } | StmtKind::Throw { .. }
+        )
+    })
+}
+
+// Jumps are created before the cases are numbered, so they go to labels
+// until this replaces the labels with case numbers. Every other "return" has
+// become "return [2, value]" by then, and functions aren't searched.
+fn patch_jumps(stmt: &mut Stmt, label_cases: &[usize]) {
+    ensure_sufficient_stack(|| patch_jumps_inner(stmt, label_cases))
+}
+
+fn patch_jumps_inner(stmt: &mut Stmt, label_cases: &[usize]) {
+    match stmt.data.as_mut() {
+        StmtKind::Return { value: Some(value) } => {
+            if let ExprKind::Array { items } = value.data.as_mut() {
+                if let [op, target] = items.as_mut_slice() {
+                    let is_jump = matches!(
+                        op.data.as_ref(),
+                        ExprKind::Number { value } if *value == GENERATOR_JUMP as f64
+                    );
+                    if let (true, ExprKind::Number { value }) = (is_jump, target.data.as_mut()) {
+                        *value = label_cases[*value as usize] as f64;
+                    }
+                }
+            }
+        }
+        StmtKind::Block { stmts } => {
+            for stmt in stmts {
+                patch_jumps(stmt, label_cases);
+            }
+        }
+        StmtKind::If { yes, no, .. } => {
+            patch_jumps(yes, label_cases);
+            if let Some(no) = no {
+                patch_jumps(no, label_cases);
+            }
+        }
+        StmtKind::For { body, .. }
+        | StmtKind::ForIn { body, .. }
+        | StmtKind::ForOf { body, .. }
+        | StmtKind::While { body, .. }
+        | StmtKind::DoWhile { body, .. }
+        | StmtKind::With { body, .. }
+        | StmtKind::Label { stmt: body, .. } => patch_jumps(body, label_cases),
+        StmtKind::Switch { cases, .. } => {
+            for stmt in cases.iter_mut().flat_map(|case| &mut case.body) {
+                patch_jumps(stmt, label_cases);
+            }
+        }
+        StmtKind::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            let catch = catch.iter_mut().flat_map(|catch| &mut catch.body);
+            let finally = finally.iter_mut().flat_map(|finally| &mut finally.stmts);
+            for stmt in body.iter_mut().chain(catch).chain(finally) {
+                patch_jumps(stmt, label_cases);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Functions and classes have their own yields, so they aren't searched
+fn stmt_has_yield(stmt: &Stmt) -> bool {
+    ensure_sufficient_stack(|| stmt_has_yield_inner(stmt))
+}
+
+fn stmt_has_yield_inner(stmt: &Stmt) -> bool {
+    let any = |stmts: &[Stmt]| stmts.iter().any(stmt_has_yield);
+    match stmt.data.as_ref() {
+        StmtKind::Block { stmts } => any(stmts),
+        StmtKind::Expr { value }
+        | StmtKind::Throw { value }
+        | StmtKind::Return { value: Some(value) } => expr_has_yield(value),
+        StmtKind::Local { decls, .. } => decls
+            .iter()
+            .any(|decl| decl.value.as_ref().is_some_and(expr_has_yield)),
+        StmtKind::Label { stmt, .. } => stmt_has_yield(stmt),
+        StmtKind::If { test, yes, no } => {
+            expr_has_yield(test) || stmt_has_yield(yes) || no.as_ref().is_some_and(stmt_has_yield)
+        }
+        StmtKind::For {
+            init,
+            test,
+            update,
+            body,
+        } => {
+            init.as_ref().is_some_and(stmt_has_yield)
+                || test.as_ref().is_some_and(expr_has_yield)
+                || update.as_ref().is_some_and(expr_has_yield)
+                || stmt_has_yield(body)
+        }
+        StmtKind::ForIn { init, value, body }
+        | StmtKind::ForOf {
+            init, value, body, ..
+        } => stmt_has_yield(init) || expr_has_yield(value) || stmt_has_yield(body),
+        StmtKind::DoWhile { body, test }
+        | StmtKind::While { test, body }
+        | StmtKind::With {
+            value: test, body, ..
+        } => expr_has_yield(test) || stmt_has_yield(body),
+        StmtKind::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            any(body)
+                || catch.as_ref().is_some_and(|catch| any(&catch.body))
+                || finally.as_ref().is_some_and(|finally| any(&finally.stmts))
+        }
+        StmtKind::Switch { test, cases, .. } => {
+            expr_has_yield(test)
+                || cases
+                    .iter()
+                    .any(|case| case.value.as_ref().is_some_and(expr_has_yield) || any(&case.body))
+        }
+        _ => false,
+    }
+}
+
+fn expr_has_yield(value: &Expr) -> bool {
+    matches!(value.data.as_ref(), ExprKind::Yield { .. })
+        || operands(value).into_iter().any(expr_has_yield)
+}
+
+// The expressions directly inside an expression in the order they're
+// evaluated, leaving out the bodies of functions and classes
+fn operands(value: &Expr) -> Vec<&Expr> {
+    match value.data.as_ref() {
+        ExprKind::Array { items } | ExprKind::RuntimeCall { args: items, .. } => {
+            items.iter().collect()
+        }
+        ExprKind::Unary { value, .. }
+        | ExprKind::Spread { value }
+        | ExprKind::Await { value }
+        | ExprKind::Yield { value, .. }
+        | ExprKind::Import { expr: value }
+        | ExprKind::Dot { target: value, .. } => vec![value],
+        ExprKind::Binary { left, right, .. }
+        | ExprKind::Index {
+            target: left,
+            index: right,
+            ..
+        } => vec![left, right],
+        ExprKind::Call { target, args, .. } | ExprKind::New { target, args, .. } => {
+            std::iter::once(target).chain(args).collect()
+        }
+        ExprKind::If { test, yes, no } => vec![test, yes, no],
+        ExprKind::Object { properties } => properties
+            .iter()
+            .flat_map(|property| {
+                let key = Some(&property.key).filter(|_| property.is_computed);
+                key.into_iter()
+                    .chain(&property.value)
+                    .chain(&property.initializer)
+            })
+            .collect(),
+        ExprKind::Template { tag, parts, .. } => std::iter::once(tag)
+            .chain(parts.iter().map(|part| &part.value))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn operands_mut(value: &mut Expr) -> Vec<&mut Expr> {
+    match value.data.as_mut() {
+        ExprKind::Array { items } | ExprKind::RuntimeCall { args: items, .. } => {
+            items.iter_mut().collect()
+        }
+        ExprKind::Unary { value, .. }
+        | ExprKind::Spread { value }
+        | ExprKind::Await { value }
+        | ExprKind::Yield { value, .. }
+        | ExprKind::Import { expr: value }
+        | ExprKind::Dot { target: value, .. } => vec![value],
+        ExprKind::Binary { left, right, .. }
+        | ExprKind::Index {
+            target: left,
+            index: right,
+            ..
+        } => vec![left, right],
+        ExprKind::Call { target, args, .. } | ExprKind::New { target, args, .. } => {
+            std::iter::once(target).chain(args).collect()
+        }
+        ExprKind::If { test, yes, no } => vec![test, yes, no],
+        ExprKind::Object { properties } => properties
+            .iter_mut()
+            .flat_map(|property| {
+                let is_computed = property.is_computed;
+                let key = Some(&mut property.key).filter(|_| is_computed);
+                key.into_iter()
+                    .chain(&mut property.value)
+                    .chain(&mut property.initializer)
+            })
+            .collect(),
+        ExprKind::Template { tag, parts, .. } => std::iter::once(tag)
+            .chain(parts.iter_mut().map(|part| &mut part.value))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// The feature that a class member needs lowered, if any
+fn member_feature(property: &Property) -> Option<Feature> {
+    if property.kind == PropertyKind::PropertyStaticBlock {
+        Some(Feature::ClassStaticBlock)
+    } else if private_ref(&property.key).is_some() {
+        Some(Feature::ClassPrivateMember)
+    } else if property.value.is_none() {
+        Some(Feature::ClassField)
+    } else {
+        None
+    }
+}
+
+fn private_ref(key: &Expr) -> Option<Reference> {
+    match key.data.as_ref() {
+        ExprKind::PrivateIdentifier { reference } => Some(*reference),
+        _ => None,
+    }
+}
+
+// Where the "super()" call is in the statements of a constructor, if it's at
+// the top level
+fn super_call_index(constructor: &Property) -> Option<usize> {
+    match constructor.value.as_ref().map(|v| v.data.as_ref()) {
+        Some(ExprKind::Function { function }) => {
+            function.body.stmts.iter().position(Stmt::is_super_call)
+        }
+        _ => None,
+    }
+}
+
+// "a += b" is "a = a + b"
+fn compound_assign_op(op_code: OperatorCode) -> Option<OperatorCode> {
+    Some(match op_code {
+        OperatorCode::BinOpAddAssign => OperatorCode::BinOpAdd,
+        OperatorCode::BinOpSubAssign => OperatorCode::BinOpSub,
+        OperatorCode::BinOpMulAssign => OperatorCode::BinOpMul,
+        OperatorCode::BinOpDivAssign => OperatorCode::BinOpDiv,
+        OperatorCode::BinOpRemAssign => OperatorCode::BinOpRem,
+        OperatorCode::BinOpPowAssign => OperatorCode::BinOpPow,
+        OperatorCode::BinOpShlAssign => OperatorCode::BinOpShl,
+        OperatorCode::BinOpShrAssign => OperatorCode::BinOpShr,
+        OperatorCode::BinOpUShrAssign => OperatorCode::BinOpUShr,
+        OperatorCode::BinOpBitwiseOrAssign => OperatorCode::BinOpBitwiseOr,
+        OperatorCode::BinOpBitwiseAndAssign => OperatorCode::BinOpBitwiseAnd,
+        OperatorCode::BinOpBitwiseXorAssign => OperatorCode::BinOpBitwiseXor,
+        _ => return None,
+    })
+}
+
+fn is_constructor(property: &Property) -> bool {
+    let is_named_constructor = match property.key.data.as_ref() {
+        ExprKind::String { value } => utf16_equals_string(value, "constructor"),
+        _ => false,
+    };
+    property.is_method && !property.is_static && !property.is_computed && is_named_constructor
+}
+
+fn optional_chain(expr: &Expr) -> OptionalChain {
+    match expr.data.as_ref() {
+        ExprKind::Dot { optional_chain, .. }
+        | ExprKind::Index { optional_chain, .. }
+        | ExprKind::Call { optional_chain, .. } => *optional_chain,
+        _ => OptionalChain::None,
+    }
+}
+
+// A link that isn't part of a chain ends the chain below it, so "(a?.b).c" is
+// a chain of its own inside a plain property access
+fn ends_chain(link: OptionalChain, target: &Expr) -> bool {
+    link == OptionalChain::None && optional_chain(target) != OptionalChain::None
+}
+
+// Returns how many links into the chain the innermost "?." is
+fn optional_chain_depth(expr: &Expr) -> Option<usize> {
+    let mut innermost = None;
+    let mut depth = 0;
+    let mut link = expr;
+    loop {
+        let target = match link.data.as_ref() {
+            ExprKind::Dot { target, .. }
+            | ExprKind::Index { target, .. }
+            | ExprKind::Call { target, .. } => target,
+            _ => return innermost,
+        };
+        match optional_chain(link) {
+            OptionalChain::None => return innermost,
+            OptionalChain::Start => innermost = Some(depth),
+            OptionalChain::Continue => {}
+        }
+        link = target;
+        depth += 1;
+    }
+}
+
+// Once every "?." in a chain has been lowered, the links that continued it are
+// plain property accesses and calls
+fn end_optional_chain(mut link: &mut Expr) {
+    loop {
+        link = match link.data.as_mut() {
+            ExprKind::Dot {
+                target,
+                optional_chain,
+                ..
+            }
+            | ExprKind::Index {
+                target,
+                optional_chain,
+                ..
+            }
+            | ExprKind::Call {
+                target,
+                optional_chain,
+                ..
+            } if *optional_chain == OptionalChain::Continue => {
+                *optional_chain = OptionalChain::None;
+                target
+            }
+            _ => return,
+        };
+    }
+}
+
+fn has_optional_chain(expr: &Expr) -> bool {
+    optional_chain_depth(expr).is_some()
+}
+
+fn chain_target_mut(link: &mut Expr) -> &mut Expr {
+    match link.data.as_mut() {
+        ExprKind::Dot { target, .. }
+        | ExprKind::Index { target, .. }
+        | ExprKind::Call { target, .. } => target,
+        _ => unreachable!("Only property accesses and calls are links in a chain"),
+    }
+}
+
+fn expr(location: Location, data: ExprKind) -> Expr {
+    Expr {
+        location,
+        data: Box::new(data),
+    }
+}
+
+fn expr_missing(location: Location) -> Expr {
+    expr(location, ExprKind::Missing)
+}
+
+fn take_expr(value: &mut Expr) -> Expr {
+    let location = value.location;
+    std::mem::replace(value, expr_missing(location))
+}
+
+fn empty_stmt(location: Location) -> Stmt {
+    Stmt {
+        location,
+        data: Box::new(StmtKind::Empty),
+    }
+}
+
+fn var_decl(location: Location, reference: Reference, value: Option<Expr>) -> Decl {
+    Decl {
+        binding: Binding {
+            location,
+            data: Box::new(BindingKind::Identifier { reference }),
+        },
+        value,
+    }
+}
+
+fn local_stmt(kind: LocalKind, decls: Vec<Decl>) -> Stmt {
+    Stmt {
+        location: decls.first().map_or(0, |decl| decl.binding.location),
+        data: Box::new(StmtKind::Local {
+            decls,
+            kind,
+            is_export: false,
+            was_ts_import_equals_in_namespace: false,
+        }),
+    }
+}
+
+// The number of directives such as "use strict" at the start of a body, which
+// must stay first
+fn prologue_len(stmts: &[Stmt]) -> usize {
+    stmts
+        .iter()
+        .position(|stmt| {
+            !matches!(
+                stmt.data.as_ref(),
+                StmtKind::Directive { .. } | StmtKind::Comment { .. }
+            )
+        })
+        .unwrap_or(stmts.len())
+}
+
+// "(a, b, value)" for the values in "exprs", which are taken
+fn prepend_exprs(exprs: &mut Vec<Expr>, value: Expr) -> Expr {
+    let values = exprs.drain(..).chain(std::iter::once(value));
+    join_all_with_comma(values).expect("There is at least one value")
+}
+
+fn return_stmt(value: Expr) -> Stmt {
+    Stmt {
+        location: value.location,
+        data: Box::new(StmtKind::Return { value: Some(value) }),
+    }
+}
+
+fn if_stmt(test: Expr, yes: Stmt) -> Stmt {
+    Stmt {
+        location: test.location,
+        data: Box::new(StmtKind::If {
+            test,
+            yes,
+            no: None,
+        }),
+    }
+}
+
+fn expr_stmt(value: Expr) -> Stmt {
+    Stmt {
+        location: value.location,
+        data: Box::new(StmtKind::Expr { value }),
+    }
+}
+
+fn identifier(location: Location, reference: Reference) -> Expr {
+    expr(location, ExprKind::Identifier { reference })
+}
+
+fn string_expr(location: Location, text: &str) -> Expr {
+    expr(
+        location,
+        ExprKind::String {
+            value: string_to_utf16(text),
+        },
+    )
+}
+
+fn number_expr(location: Location, value: usize) -> Expr {
+    expr(
+        location,
+        ExprKind::Number {
+            value: value as f64,
+        },
+    )
+}
+
+fn binary(op_code: OperatorCode, left: Expr, right: Expr) -> Expr {
+    expr(
+        left.location,
+        ExprKind::Binary {
+            op_code,
+            left,
+            right,
+        },
+    )
+}
+
+fn unary(op_code: OperatorCode, value: Expr) -> Expr {
+    expr(value.location, ExprKind::Unary { op_code, value })
+}
+
+fn assign(target: Expr, value: Expr) -> Expr {
+    binary(OperatorCode::BinOpAssign, target, value)
+}
+
+fn dot(target: Expr, name: String, name_location: Location) -> Expr {
+    expr(
+        target.location,
+        ExprKind::Dot {
+            target,
+            name,
+            name_location,
+            optional_chain: OptionalChain::None,
+            is_parenthesized: false,
+        },
+    )
+}
+
+fn index_expr(target: Expr, index: Expr) -> Expr {
+    expr(
+        target.location,
+        ExprKind::Index {
+            target,
+            index,
+            optional_chain: OptionalChain::None,
+            is_parenthesized: false,
+        },
+    )
+}
+
+fn call(target: Expr, args: Vec<Expr>) -> Expr {
+    expr(
+        target.location,
+        ExprKind::Call {
+            target,
+            args,
+            optional_chain: OptionalChain::None,
+            is_parenthesized: false,
+            is_direct_eval: false,
+            can_be_unwrapped_if_unused: false,
+        },
+    )
+}
+
+fn function_expr(location: Location, args: &[Reference], stmts: Vec<Stmt>) -> Expr {
+    let args = args
+        .iter()
+        .map(|&reference| Arg {
+            is_typescript_ctor_field: false,
+            binding: Binding {
+                location,
+                data: Box::new(BindingKind::Identifier { reference }),
+            },
+            default_: None,
+        })
+        .collect();
+    let function = Function {
+        name: None,
+        args,
+        is_async: false,
+        is_generator: false,
+        has_rest_arg: false,
+        body: FunctionBody { location, stmts },
+    };
+    expr(location, ExprKind::Function { function })
+}
+
+// "target.key = value" for a class field, where a missing value means the
+// field is "undefined"
+fn assign_field(target: Expr, field: Property) -> Expr {
+    let location = field.key.location;
+    let member = member_access(target, field.key);
+    let value = field
+        .initializer
+        .unwrap_or_else(|| expr(location, ExprKind::Undefined));
+    assign(member, value)
+}
+
+// "target.key" when the key is a name and "target[key]" otherwise
+fn object_expr(location: Location, properties: Vec<(&str, Expr)>) -> Expr {
+    let properties = properties
+        .into_iter()
+        .map(|(key, value)| Property {
+            kind: PropertyKind::PropertyNormal,
+            is_computed: false,
+            is_method: false,
+            is_static: false,
+            key: string_expr(location, key),
+            prefer_quoted_key: false,
+            value: Some(value),
+            initializer: None,
+        })
+        .collect();
+    expr(location, ExprKind::Object { properties })
+}
+
+fn member_access(target: Expr, key: Expr) -> Expr {
+    let location = key.location;
+    match key.into_data() {
+        ExprKind::String { value } if is_identifier_utf16(&value) => {
+            dot(target, utf16_to_string_lossy(&value), location)
+        }
+        data => index_expr(target, expr(location, data)),
+    }
+}
+
+fn initialize_static_member(class: Expr, member: StaticMember) -> Expr {
+    let location = class.location;
+    match member {
+        StaticMember::Field(field) => assign_field(class, field),
+        StaticMember::PrivateField(storage, value) => call(
+            dot(identifier(location, storage), "set".to_owned(), location),
+            vec![class, value],
+        ),
+        StaticMember::Brand(storage) => call(
+            dot(identifier(location, storage), "add".to_owned(), location),
+            vec![class],
+        ),
+
+        // "static { ... }" => "(function() { ... }).call(A)"
+        StaticMember::Block(function) => {
+            let function = expr(location, ExprKind::Function { function });
+            call(dot(function, "call".to_owned(), location), vec![class])
+        }
+    }
+}
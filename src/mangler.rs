@@ -0,0 +1,712 @@
+// Syntax mangling rewrites the tree into a shorter form with the same meaning.
+// It runs after the parser so that it can rely on every identifier already
+// being bound to a symbol.
+
+use crate::ast::{Case, Expr, ExprKind, OperatorCode, Property, Stmt, StmtKind, TemplatePart};
+use crate::lexer::is_identifier_utf16;
+use crate::number::number_to_string;
+use crate::stack::ensure_sufficient_stack;
+use crate::strings::{string_to_utf16, utf16_concat, utf16_to_string, utf16_to_string_lossy};
+use crate::visit::VisitMut;
+
+#[derive(Debug, Clone, Default)]
+pub struct MangleOptions {
+    // Closure Compiler's advanced mode renames unquoted property names but
+    // never quoted ones, so code written for it uses quotes to mark the
+    // properties that are accessed by name. When this is set, "a['b']" and
+    // "{'b': c}" are never turned into "a.b" and "{b: c}" (or the other way
+    // around) so that distinction survives. Property mangling must also leave
+    // every property alone when this is set.
+    pub preserve_quoted_keys: bool,
+}
+
+pub fn mangle_stmts(stmts: &mut Vec<Stmt>, options: &MangleOptions) {
+    Mangler {
+        options,
+        is_call_target: false,
+    }
+    .visit_stmts(stmts);
+}
+
+struct Mangler<'a> {
+    options: &'a MangleOptions,
+
+    // "(0, a.b)()" calls "a.b" without "a" as "this" and "(0, eval)(a)" is an
+    // indirect "eval", so the comma in the target of a call must stay
+    is_call_target: bool,
+}
+
+impl<'a> Mangler<'a> {
+    // "a['b']" => "a.b"
+    fn mangle_index(&self, expr: &mut Expr) {
+        if self.options.preserve_quoted_keys {
+            return;
+        }
+
+        if let ExprKind::Index {
+            target,
+            index,
+            optional_chain,
+            is_parenthesized,
+        } = expr.data.as_mut()
+        {
+            let name = match index.data.as_ref() {
+                ExprKind::String { value } if is_identifier_utf16(value) => {
+                    utf16_to_string_lossy(value)
+                }
+                _ => return,
+            };
+            let target = std::mem::replace(
+                target,
+                Expr {
+                    location: target.location,
+                    data: Box::new(ExprKind::Missing),
+                },
+            );
+            *expr.data = ExprKind::Dot {
+                target,
+                name,
+                name_location: index.location,
+                optional_chain: *optional_chain,
+                is_parenthesized: *is_parenthesized,
+            };
+        }
+    }
+
+    // "{['a']: b}" => "{a: b}" and "{'a': b}" => "{a: b}"
+    fn mangle_property_key(&self, property: &mut Property, is_class: bool) {
+        let value = match property.key.data.as_ref() {
+            ExprKind::String { value } => value,
+            _ => return,
+        };
+
+        if property.is_computed {
+            // These keys mean something different when they aren't computed:
+            // "{__proto__: a}" sets the prototype and a class member named
+            // "constructor" becomes the constructor
+            let name = utf16_to_string_lossy(value);
+            let is_special = if is_class {
+                name == "constructor" || (property.is_static && name == "prototype")
+            } else {
+                name == "__proto__"
+            };
+            if is_special {
+                return;
+            }
+
+            // A computed string key counts as a quoted key
+            property.is_computed = false;
+            property.prefer_quoted_key = self.options.preserve_quoted_keys;
+            return;
+        }
+
+        if property.prefer_quoted_key
+            && !self.options.preserve_quoted_keys
+            && is_identifier_utf16(value)
+        {
+            property.prefer_quoted_key = false;
+        }
+    }
+
+    // "a = a + b" => "a += b"
+    //
+    // This is only done when the target is an identifier since a property
+    // access would be evaluated once instead of twice. Note that "a += 1"
+    // can't become "++a" because it concatenates when "a" is a string.
+    fn mangle_assign(&self, expr: &mut Expr) {
+        if let ExprKind::Binary {
+            op_code,
+            left,
+            right,
+        } = expr.data.as_mut()
+        {
+            if *op_code != OperatorCode::BinOpAssign {
+                return;
+            }
+            let target = match left.data.as_ref() {
+                ExprKind::Identifier { reference } => *reference,
+                _ => return,
+            };
+            let (assign_op_code, value) = match right.data.as_mut() {
+                ExprKind::Binary {
+                    op_code: right_op_code,
+                    left: right_left,
+                    right: right_right,
+                } => match (
+                    compound_assign_op_code(*right_op_code),
+                    right_left.data.as_ref(),
+                ) {
+                    (Some(assign_op_code), ExprKind::Identifier { reference })
+                        if *reference == target =>
+                    {
+                        let value = std::mem::replace(
+                            right_right,
+                            Expr {
+                                location: right_right.location,
+                                data: Box::new(ExprKind::Missing),
+                            },
+                        );
+                        (assign_op_code, value)
+                    }
+                    _ => return,
+                },
+                _ => return,
+            };
+            *op_code = assign_op_code;
+            *right = value;
+        }
+    }
+
+    // Operators on literals are evaluated at compile time when the result is
+    // known exactly. Only primitives are converted to strings and numbers
+    // since objects can run code when that happens, through "toString",
+    // "valueOf", or "Symbol.toPrimitive".
+    //
+    //   "'a' + 'b'" => "'ab'"
+    //   "`a${1}b`" => "'a1b'"
+    //   "+true" => "1"
+    //   "typeof 'a'" => "'string'"
+    //   "'a' < 'b'" => "true"
+    //
+    fn fold_expr(&self, expr: &mut Expr) {
+        let folded = match expr.data.as_mut() {
+            ExprKind::Unary {
+                op_code: OperatorCode::UnOpPos,
+                value,
+            } => match to_number(value) {
+                Some(value) => ExprKind::Number { value },
+                None => return,
+            },
+
+            ExprKind::Unary {
+                op_code: OperatorCode::UnOpTypeof,
+                value,
+            } => match type_of(value) {
+                Some(name) => ExprKind::String {
+                    value: string_to_utf16(name),
+                },
+                None => return,
+            },
+
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpAdd,
+                left,
+                right,
+            } => {
+                // "a + 'b' + 'c'" => "a + 'bc'" since "a + 'b'" is a string
+                if let ExprKind::Binary {
+                    op_code: OperatorCode::BinOpAdd,
+                    right: inner,
+                    ..
+                } = left.data.as_mut()
+                {
+                    if let (ExprKind::String { value: a }, Some(b)) =
+                        (inner.data.as_ref(), to_string(right))
+                    {
+                        *inner.data = ExprKind::String {
+                            value: utf16_concat(a, &b),
+                        };
+                        let left = std::mem::replace(left.data.as_mut(), ExprKind::Missing);
+                        *expr.data = left;
+                        return;
+                    }
+                }
+
+                let is_string = |expr: &Expr| matches!(expr.data.as_ref(), ExprKind::String { .. });
+                if !is_string(left) && !is_string(right) {
+                    return;
+                }
+                match (to_string(left), to_string(right)) {
+                    (Some(a), Some(b)) => ExprKind::String {
+                        value: utf16_concat(&a, &b),
+                    },
+                    _ => return,
+                }
+            }
+
+            ExprKind::Binary {
+                op_code:
+                    op_code @ (OperatorCode::BinOpLt
+                    | OperatorCode::BinOpGt
+                    | OperatorCode::BinOpLe
+                    | OperatorCode::BinOpGe),
+                left,
+                right,
+            } => {
+                let ordering = match (left.data.as_ref(), right.data.as_ref()) {
+                    (ExprKind::String { value: a }, ExprKind::String { value: b }) => {
+                        a.partial_cmp(b)
+                    }
+                    (ExprKind::Number { value: a }, ExprKind::Number { value: b }) => {
+                        a.partial_cmp(b)
+                    }
+                    _ => return,
+                };
+
+                // Comparisons with NaN are always false
+                let value = ordering.is_some_and(|ordering| match op_code {
+                    OperatorCode::BinOpLt => ordering.is_lt(),
+                    OperatorCode::BinOpGt => ordering.is_gt(),
+                    OperatorCode::BinOpLe => ordering.is_le(),
+                    _ => ordering.is_ge(),
+                });
+                ExprKind::Boolean { value }
+            }
+
+            // Tagged templates see each part separately, so they're left alone
+            ExprKind::Template {
+                tag, head, parts, ..
+            } if matches!(tag.data.as_ref(), ExprKind::Missing) => {
+                let mut kept: Vec<TemplatePart> = Vec::with_capacity(parts.len());
+                for part in parts.drain(..) {
+                    match to_string(&part.value) {
+                        Some(text) => {
+                            let prev = match kept.last_mut() {
+                                Some(prev) => &mut prev.tail,
+                                None => &mut *head,
+                            };
+                            prev.extend_from_slice(&text);
+                            prev.extend_from_slice(&part.tail);
+                        }
+                        None => kept.push(part),
+                    }
+                }
+                *parts = kept;
+                if !parts.is_empty() {
+                    return;
+                }
+                ExprKind::String {
+                    value: std::mem::take(head),
+                }
+            }
+
+            _ => return,
+        };
+        *expr.data = folded;
+    }
+
+    // "(a, b)" => "b" when evaluating "a" does nothing
+    fn mangle_comma(&self, expr: &mut Expr) {
+        if let ExprKind::Binary {
+            op_code: OperatorCode::BinOpComma,
+            left,
+            right,
+        } = expr.data.as_mut()
+        {
+            // A comma on the left has been mangled already, so it's only still
+            // there if it has side effects. Checking it again would go through
+            // the whole chain for every link.
+            let is_comma = matches!(
+                left.data.as_ref(),
+                ExprKind::Binary {
+                    op_code: OperatorCode::BinOpComma,
+                    ..
+                }
+            );
+            if !is_comma && !left.has_side_effects() {
+                let right = std::mem::replace(
+                    right,
+                    Expr {
+                        location: right.location,
+                        data: Box::new(ExprKind::Missing),
+                    },
+                );
+                *expr = right;
+            }
+        }
+    }
+
+    // Cases that can't run are removed from switch statements, and what's
+    // left is turned into simpler statements when possible:
+    //
+    //   "switch (1) { case 0: a(); case 1: b(); break; case 2: c() }" => "{ b() }"
+    //   "switch (a) { case 1: b(); break }" => "if (a === 1) { b() }"
+    //   "switch (a) {}" => "a;"
+    //
+    fn mangle_switch(&self, stmt: &mut Stmt) {
+        let (test, cases) = match stmt.data.as_mut() {
+            StmtKind::Switch { test, cases, .. } => (test, cases),
+            _ => return,
+        };
+
+        // When the value and the case values are literals, the case that
+        // runs is known. The ones before it can't run, and neither can the
+        // ones after the first case it falls through to that jumps away.
+        // Literals have no side effects, so leaving out the values of the
+        // cases before it doesn't change anything either.
+        let matching_case = matching_case(test, cases);
+        match matching_case {
+            Some(Some(index)) => {
+                let end = cases[index..]
+                    .iter()
+                    .position(|case| ends_with_jump(&case.body))
+                    .map_or(cases.len(), |offset| index + offset + 1);
+                cases.truncate(end);
+                cases.drain(..index);
+            }
+            Some(None) => cases.clear(),
+            None => {}
+        }
+
+        // A "break" at the end of the last case does nothing
+        if let Some(last) = cases.last_mut() {
+            if let Some(StmtKind::Break { name: None }) = last.body.last().map(|s| s.data.as_ref())
+            {
+                last.body.pop();
+            }
+        }
+        if (cases.len() > 1 && matching_case.is_none())
+            || cases.iter().any(|case| contains_switch_break(&case.body))
+        {
+            return;
+        }
+
+        let location = stmt.location;
+        let test = std::mem::replace(
+            test,
+            Expr {
+                location: test.location,
+                data: Box::new(ExprKind::Missing),
+            },
+        );
+        let mut cases = std::mem::take(cases);
+        *stmt.data = if cases.is_empty() {
+            if !test.has_side_effects() {
+                StmtKind::Empty
+            } else {
+                StmtKind::Expr { value: test }
+            }
+        } else if matching_case.is_some() {
+            // Every case that's left runs, one after the other
+            StmtKind::Block {
+                stmts: cases.into_iter().flat_map(|case| case.body).collect(),
+            }
+        } else {
+            let case = cases.pop().unwrap();
+            let body = Stmt {
+                location,
+                data: Box::new(StmtKind::Block { stmts: case.body }),
+            };
+            match case.value {
+                Some(value) => StmtKind::If {
+                    test: Expr {
+                        location: test.location,
+                        data: Box::new(ExprKind::Binary {
+                            op_code: OperatorCode::BinOpStrictEq,
+                            left: test,
+                            right: value,
+                        }),
+                    },
+                    yes: body,
+                    no: None,
+                },
+
+                // The value is still evaluated for its side effects
+                None => StmtKind::Block {
+                    stmts: vec![
+                        Stmt {
+                            location: test.location,
+                            data: Box::new(StmtKind::Expr { value: test }),
+                        },
+                        body,
+                    ],
+                },
+            }
+        };
+    }
+
+    // Empty statements are removed and adjacent declarations of the same
+    // kind are joined: "var a = 1; var b = 2" => "var a = 1, b = 2"
+    fn mangle_stmt_list(&self, stmts: &mut Vec<Stmt>) {
+        let mut result: Vec<Stmt> = Vec::with_capacity(stmts.len());
+
+        for mut stmt in stmts.drain(..) {
+            match stmt.data.as_mut() {
+                StmtKind::Empty => continue,
+                StmtKind::Local {
+                    decls,
+                    kind,
+                    is_export,
+                    was_ts_import_equals_in_namespace: false,
+                } => {
+                    if let Some(StmtKind::Local {
+                        decls: prev_decls,
+                        kind: prev_kind,
+                        is_export: prev_is_export,
+                        was_ts_import_equals_in_namespace: false,
+                    }) = result.last_mut().map(|prev| prev.data.as_mut())
+                    {
+                        if prev_kind == kind && prev_is_export == is_export {
+                            prev_decls.append(decls);
+                            continue;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            result.push(stmt);
+        }
+
+        *stmts = result;
+    }
+}
+
+impl<'a> VisitMut for Mangler<'a> {
+    fn visit_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        self.walk_stmts(stmts);
+        self.mangle_stmt_list(stmts);
+    }
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        self.walk_stmt(stmt);
+        self.mangle_switch(stmt);
+    }
+
+    fn visit_property(&mut self, property: &mut Property, is_class: bool) {
+        self.walk_property(property, is_class);
+        self.mangle_property_key(property, is_class);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        ensure_sufficient_stack(|| {
+            let is_call_target = std::mem::replace(&mut self.is_call_target, false);
+            match expr.data.as_mut() {
+                ExprKind::Call { target, args, .. } => {
+                    self.is_call_target = true;
+                    self.visit_expr(target);
+                    for arg in args {
+                        self.visit_expr(arg);
+                    }
+                }
+                ExprKind::Template { tag, parts, .. } => {
+                    self.is_call_target = true;
+                    self.visit_expr(tag);
+                    for part in parts {
+                        self.visit_expr(&mut part.value);
+                    }
+                }
+                _ => self.walk_expr(expr),
+            }
+            self.fold_expr(expr);
+            if !is_call_target {
+                self.mangle_comma(expr);
+            }
+            self.mangle_index(expr);
+            self.mangle_assign(expr);
+        })
+    }
+}
+
+fn compound_assign_op_code(op_code: OperatorCode) -> Option<OperatorCode> {
+    Some(match op_code {
+        OperatorCode::BinOpAdd => OperatorCode::BinOpAddAssign,
+        OperatorCode::BinOpSub => OperatorCode::BinOpSubAssign,
+        OperatorCode::BinOpMul => OperatorCode::BinOpMulAssign,
+        OperatorCode::BinOpDiv => OperatorCode::BinOpDivAssign,
+        OperatorCode::BinOpRem => OperatorCode::BinOpRemAssign,
+        OperatorCode::BinOpPow => OperatorCode::BinOpPowAssign,
+        OperatorCode::BinOpShl => OperatorCode::BinOpShlAssign,
+        OperatorCode::BinOpShr => OperatorCode::BinOpShrAssign,
+        OperatorCode::BinOpUShr => OperatorCode::BinOpUShrAssign,
+        OperatorCode::BinOpBitwiseOr => OperatorCode::BinOpBitwiseOrAssign,
+        OperatorCode::BinOpBitwiseAnd => OperatorCode::BinOpBitwiseAndAssign,
+        OperatorCode::BinOpBitwiseXor => OperatorCode::BinOpBitwiseXorAssign,
+        _ => return None,
+    })
+}
+
+// Returns the index of the case that a switch statement runs first, or None
+// inside if it runs no case at all. Returns None if that isn't known.
+fn matching_case(test: &Expr, cases: &[Case]) -> Option<Option<usize>> {
+    if !is_literal(test) {
+        return None;
+    }
+    for (index, case) in cases.iter().enumerate() {
+        if let Some(value) = &case.value {
+            if literals_are_strictly_equal(test, value)? {
+                return Some(Some(index));
+            }
+        }
+    }
+    Some(cases.iter().position(|case| case.value.is_none()))
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(
+        expr.data.as_ref(),
+        ExprKind::Null
+            | ExprKind::Undefined
+            | ExprKind::Boolean { .. }
+            | ExprKind::Number { .. }
+            | ExprKind::String { .. }
+    )
+}
+
+// This is "===" for literals. Returns None for anything else.
+fn literals_are_strictly_equal(a: &Expr, b: &Expr) -> Option<bool> {
+    if !is_literal(a) || !is_literal(b) {
+        return None;
+    }
+    Some(match (a.data.as_ref(), b.data.as_ref()) {
+        (ExprKind::Null, ExprKind::Null) | (ExprKind::Undefined, ExprKind::Undefined) => true,
+        (ExprKind::Boolean { value: a }, ExprKind::Boolean { value: b }) => a == b,
+        (ExprKind::Number { value: a }, ExprKind::Number { value: b }) => a == b,
+        (ExprKind::String { value: a }, ExprKind::String { value: b }) => a == b,
+        _ => false,
+    })
+}
+
+// Whether control can't fall through to the next case
+fn ends_with_jump(body: &[Stmt]) -> bool {
+    matches!(
+        body.last().map(|stmt| stmt.data.as_ref()),
+        Some(
+            StmtKind::Break { .. }
+                | StmtKind::Continue { .. }
+                | StmtKind::Return { .. }
+                | StmtKind::Throw { .. }
+        )
+    )
+}
+
+// Looks for a "break" without a label that leaves the switch statement these
+// statements are in. Loops, other switch statements, and functions have
+// their own.
+fn contains_switch_break(stmts: &[Stmt]) -> bool {
+    ensure_sufficient_stack(|| {
+        stmts.iter().any(|stmt| match stmt.data.as_ref() {
+            StmtKind::Break { name: None } => true,
+            StmtKind::Block { stmts } => contains_switch_break(stmts),
+            StmtKind::Label { stmt, .. } | StmtKind::With { body: stmt, .. } => {
+                contains_switch_break(std::slice::from_ref(stmt))
+            }
+            StmtKind::If { yes, no, .. } => {
+                contains_switch_break(std::slice::from_ref(yes))
+                    || no
+                        .as_ref()
+                        .is_some_and(|no| contains_switch_break(std::slice::from_ref(no)))
+            }
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                contains_switch_break(body)
+                    || catch
+                        .as_ref()
+                        .is_some_and(|catch| contains_switch_break(&catch.body))
+                    || finally
+                        .as_ref()
+                        .is_some_and(|finally| contains_switch_break(&finally.stmts))
+            }
+            _ => false,
+        })
+    })
+}
+
+// This is "String(expr)" for primitive literals. BigInts are left out since
+// their text may be in another base.
+fn to_string(expr: &Expr) -> Option<Vec<u16>> {
+    let text = match expr.data.as_ref() {
+        ExprKind::String { value } => return Some(value.clone()),
+        ExprKind::Number { value } if value.is_nan() => "NaN".to_owned(),
+        ExprKind::Number { value } if value.is_infinite() => if *value > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }
+        .to_owned(),
+        ExprKind::Number { value } => number_to_string(*value),
+        ExprKind::Boolean { value } => value.to_string(),
+        ExprKind::Null => "null".to_owned(),
+        ExprKind::Undefined => "undefined".to_owned(),
+        _ => return None,
+    };
+    Some(string_to_utf16(&text))
+}
+
+// This is "Number(expr)" for primitive literals
+fn to_number(expr: &Expr) -> Option<f64> {
+    match expr.data.as_ref() {
+        ExprKind::Number { value } => Some(*value),
+        ExprKind::Boolean { value } => Some(if *value { 1.0 } else { 0.0 }),
+        ExprKind::Null => Some(0.0),
+        ExprKind::Undefined => Some(f64::NAN),
+        ExprKind::String { value } => string_to_number(value),
+        _ => None,
+    }
+}
+
+// Strings are converted using the grammar of numeric literals, except that
+// surrounding whitespace is ignored, an empty string is zero, a decimal may
+// have a sign, and anything else is NaN. Only strings made of ASCII
+// characters are converted since JavaScript has more whitespace characters.
+fn string_to_number(value: &[u16]) -> Option<f64> {
+    let text = utf16_to_string(value).filter(|text| text.is_ascii())?;
+    let text = text.trim_matches(|c| matches!(c, ' ' | '\t' | '\n' | '\u{b}' | '\u{c}' | '\r'));
+    if text.is_empty() {
+        return Some(0.0);
+    }
+
+    let radix = match text.get(..2) {
+        Some("0x") | Some("0X") => 16,
+        Some("0o") | Some("0O") => 8,
+        Some("0b") | Some("0B") => 2,
+        _ => 10,
+    };
+    if radix != 10 {
+        let digits = &text[2..];
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return Some(f64::NAN);
+        }
+        return Some(digits.chars().fold(0.0, |value, c| {
+            value * radix as f64 + c.to_digit(radix).unwrap() as f64
+        }));
+    }
+
+    let unsigned = text.strip_prefix(['+', '-']).unwrap_or(text);
+    if unsigned == "Infinity" {
+        return Some(if text.starts_with('-') {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        });
+    }
+    if !is_decimal_literal(unsigned) {
+        return Some(f64::NAN);
+    }
+    text.parse().ok()
+}
+
+// Matches "1", "1.", ".1", "1.5", and any of these followed by an exponent
+fn is_decimal_literal(text: &str) -> bool {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], Some(&text[i + 1..])),
+        None => (text, None),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all_digits = |text: &str| text.bytes().all(|c| c.is_ascii_digit());
+    if whole.is_empty() && fraction.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+        return false;
+    }
+    match exponent {
+        Some(exponent) => {
+            let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            !digits.is_empty() && all_digits(digits)
+        }
+        None => true,
+    }
+}
+
+fn type_of(expr: &Expr) -> Option<&'static str> {
+    Some(match expr.data.as_ref() {
+        ExprKind::String { .. } => "string",
+        ExprKind::Number { .. } => "number",
+        ExprKind::BigInt { .. } => "bigint",
+        ExprKind::Boolean { .. } => "boolean",
+        ExprKind::Undefined => "undefined",
+        ExprKind::Null => "object",
+        ExprKind::Function { .. } | ExprKind::Arrow { .. } => "function",
+        _ => return None,
+    })
+}
@@ -0,0 +1,665 @@
+// Bindings that let Node.js use the port, with the "napi" feature. The
+// functions and their option objects follow esbuild's JavaScript API, so the
+// port can be tried in place of esbuild inside an existing toolchain:
+//
+//   const esbuild = require("./esbuild_rs.node")
+//   const { code } = esbuild.transformSync("<div />", { loader: "jsx" })
+//   await esbuild.build({ entryPoints: ["app.js"], bundle: true, outfile: "out.js" })
+//
+//   const ctx = esbuild.context({ entryPoints: ["app.js"], outdir: "out" })
+//   ctx.watch(result => console.log(result.errors))
+//   ctx.dispose()
+//
+// Only the options that the port supports are accepted, and options with a
+// value it doesn't support are an error instead of being ignored. The addon is
+// the library built as a shared library, renamed to end in ".node":
+//
+//   cargo rustc --release --lib --features napi --crate-type cdylib
+//
+
+use crate::api;
+use crate::bundler::{self, ImportPathRewriter, Loader, OutputFormat};
+use crate::fs::{FileSystem, RealFileSystem};
+use crate::logging::{LogLevel, Msg, MsgId, Source};
+use crate::lower::{LowerOptions, Target, UnsupportedSyntax};
+use crate::mangler::MangleOptions;
+use crate::parser::{self, DefineValue, JSXOptions, JSXRuntime};
+use crate::paths;
+use crate::printer::LegalComments;
+use crate::resolver::Platform;
+use crate::MinifyOptions;
+use napi::bindgen_prelude::{AsyncTask, Buffer};
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, Error, Result, Task};
+use napi_derive::napi;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct TransformOptions {
+    pub loader: Option<String>,
+    pub target: Option<String>,
+    pub minify: Option<bool>,
+    pub minify_whitespace: Option<bool>,
+    pub minify_syntax: Option<bool>,
+    pub minify_identifiers: Option<bool>,
+    pub define: Option<HashMap<String, String>>,
+    pub jsx: Option<String>,
+    pub jsx_factory: Option<String>,
+    pub jsx_fragment: Option<String>,
+    pub jsx_import_source: Option<String>,
+    pub jsx_dev: Option<bool>,
+    pub sourcefile: Option<String>,
+    pub log_override: Option<HashMap<String, String>>,
+
+    // What happens to syntax that can't be lowered for the target: "error"
+    // (the default), "warn", or "throw"
+    pub unsupported_syntax: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct BuildOptions {
+    pub entry_points: Vec<String>,
+    pub bundle: Option<bool>,
+    pub outfile: Option<String>,
+    pub outdir: Option<String>,
+    pub outbase: Option<String>,
+    pub format: Option<String>,
+    pub global_name: Option<String>,
+    pub platform: Option<String>,
+    pub target: Option<String>,
+    pub minify: Option<bool>,
+    pub minify_whitespace: Option<bool>,
+    pub minify_syntax: Option<bool>,
+    pub minify_identifiers: Option<bool>,
+    pub sourcemap: Option<bool>,
+    pub splitting: Option<bool>,
+    pub hmr: Option<bool>,
+    pub define: Option<HashMap<String, String>>,
+    pub loader: Option<HashMap<String, String>>,
+    pub external: Option<Vec<String>>,
+
+    // Maps external module names to the prefix that their imports are
+    // printed with, like "--rewrite-import:M=P"
+    pub rewrite_import: Option<HashMap<String, String>>,
+    pub jsx: Option<String>,
+    pub jsx_factory: Option<String>,
+    pub jsx_fragment: Option<String>,
+    pub jsx_import_source: Option<String>,
+    pub jsx_dev: Option<bool>,
+    pub public_path: Option<String>,
+    pub entry_names: Option<String>,
+    pub chunk_names: Option<String>,
+    pub asset_names: Option<String>,
+    pub legal_comments: Option<String>,
+    pub metafile: Option<bool>,
+
+    // Like in esbuild, the output files are only returned when they aren't
+    // written
+    pub write: Option<bool>,
+    pub log_override: Option<HashMap<String, String>>,
+}
+
+#[napi(object)]
+pub struct Location {
+    pub file: String,
+
+    // Lines start at 1 and columns start at 0, like in esbuild
+    pub line: u32,
+    pub column: u32,
+    pub length: u32,
+    pub line_text: String,
+}
+
+#[napi(object)]
+pub struct Note {
+    pub text: String,
+    pub location: Option<Location>,
+}
+
+#[napi(object)]
+pub struct Message {
+    // The name that "logOverride" uses for the message, if it has one
+    pub id: String,
+    pub text: String,
+    pub location: Option<Location>,
+    pub notes: Vec<Note>,
+}
+
+#[napi(object)]
+pub struct TransformResult {
+    pub code: String,
+    pub warnings: Vec<Message>,
+}
+
+#[napi(object)]
+pub struct OutputFile {
+    pub path: String,
+    pub contents: Buffer,
+    pub text: String,
+}
+
+#[napi(object)]
+pub struct BuildResult {
+    pub errors: Vec<Message>,
+    pub warnings: Vec<Message>,
+    pub output_files: Option<Vec<OutputFile>>,
+
+    // esbuild parses this into an object, while this is the JSON text
+    pub metafile: Option<String>,
+}
+
+#[napi(js_name = "transformSync")]
+pub fn transform_sync(code: String, options: Option<TransformOptions>) -> Result<TransformResult> {
+    let options = transform_options(options.unwrap_or_default())?;
+    transform_result(crate::transform(&code, options))
+}
+
+#[napi]
+pub fn transform(code: String, options: Option<TransformOptions>) -> AsyncTask<TransformTask> {
+    AsyncTask::new(TransformTask {
+        code,
+        options: options.unwrap_or_default(),
+    })
+}
+
+#[napi]
+pub fn build(options: BuildOptions) -> AsyncTask<RebuildTask> {
+    AsyncTask::new(RebuildTask {
+        context: None,
+        options: Some(options),
+    })
+}
+
+// esbuild's "context" returns a promise. This returns the context itself,
+// which "await" also accepts.
+#[napi]
+pub fn context(options: BuildOptions) -> Result<BuildContext> {
+    let write = options.write.unwrap_or(true);
+    Ok(BuildContext {
+        context: Arc::new(Mutex::new(api::BuildContext::new(build_options(options)?))),
+        write,
+        watching: None,
+    })
+}
+
+pub struct TransformTask {
+    code: String,
+    options: TransformOptions,
+}
+
+impl Task for TransformTask {
+    type Output = crate::TransformResult;
+    type JsValue = TransformResult;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let options = transform_options(std::mem::take(&mut self.options))?;
+        Ok(crate::transform(&self.code, options))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        transform_result(output)
+    }
+}
+
+// A one-shot build makes a context of its own, and a rebuild uses the one it
+// was started from
+pub struct RebuildTask {
+    context: Option<(Arc<Mutex<api::BuildContext>>, bool)>,
+    options: Option<BuildOptions>,
+}
+
+impl Task for RebuildTask {
+    type Output = (api::BuildResult, bool);
+    type JsValue = BuildResult;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        if let Some(options) = self.options.take() {
+            let write = options.write.unwrap_or(true);
+            return Ok((api::build(build_options(options)?), write));
+        }
+        let (context, write) = self.context.as_ref().unwrap();
+        Ok((context.lock().unwrap().rebuild(), *write))
+    }
+
+    fn resolve(&mut self, _env: Env, (result, write): Self::Output) -> Result<Self::JsValue> {
+        if !result.errors.is_empty() {
+            return Err(failure("Build", &result.errors));
+        }
+        Ok(build_result(result, write))
+    }
+}
+
+#[napi]
+pub struct BuildContext {
+    context: Arc<Mutex<api::BuildContext>>,
+    write: bool,
+
+    // Set to stop the thread that watches, if there is one
+    watching: Option<Arc<AtomicBool>>,
+}
+
+#[napi]
+impl BuildContext {
+    #[napi]
+    pub fn rebuild(&self) -> AsyncTask<RebuildTask> {
+        AsyncTask::new(RebuildTask {
+            context: Some((self.context.clone(), self.write)),
+            options: None,
+        })
+    }
+
+    // Builds, and then builds again every time one of the files of the last
+    // build changes (see "api::BuildContext::watch"). The callback gets the
+    // result of every build, including the ones that failed, since there's
+    // no promise to reject. The files are checked every "interval"
+    // milliseconds, which is 250 by default.
+    #[napi]
+    pub fn watch(
+        &mut self,
+        callback: ThreadsafeFunction<BuildResult, ErrorStrategy::Fatal>,
+        interval: Option<u32>,
+    ) -> Result<()> {
+        if self.watching.is_some() {
+            return Err(Error::from_reason("Already watching"));
+        }
+        let interval = Duration::from_millis(interval.unwrap_or(250).into());
+        let stop = Arc::new(AtomicBool::new(false));
+        let context = self.context.clone();
+        let write = self.write;
+        self.watching = Some(stop.clone());
+
+        std::thread::spawn(move || {
+            let mut result = Some(context.lock().unwrap().rebuild());
+            while !stop.load(Ordering::Relaxed) {
+                if let Some(result) = result.take() {
+                    callback.call(
+                        build_result(result, write),
+                        ThreadsafeFunctionCallMode::NonBlocking,
+                    );
+                }
+                std::thread::sleep(interval);
+                result = context.lock().unwrap().rebuild_if_changed();
+            }
+        });
+        Ok(())
+    }
+
+    // Stops watching, and drops everything the context remembers. Rebuilding
+    // afterward is an error.
+    #[napi]
+    pub fn dispose(&mut self) {
+        if let Some(stop) = self.watching.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.context.lock().unwrap().dispose();
+    }
+}
+
+fn transform_options(options: TransformOptions) -> Result<crate::TransformOptions> {
+    let loader = match &options.loader {
+        Some(name) => Loader::from_name(name)
+            .ok_or_else(|| Error::from_reason(format!("Invalid loader: {}", name)))?,
+        None => Loader::JS,
+    };
+    Ok(crate::TransformOptions {
+        loader,
+        target: target(options.target.as_deref())?,
+        minify: minify_options(
+            options.minify,
+            options.minify_whitespace,
+            options.minify_syntax,
+            options.minify_identifiers,
+        ),
+        mangle: MangleOptions::default(),
+        defines: defines(options.define)?,
+        drop: parser::DropOptions::default(),
+        jsx: jsx_options(
+            options.jsx.as_deref(),
+            options.jsx_factory.as_deref(),
+            options.jsx_fragment.as_deref(),
+            options.jsx_import_source,
+            options.jsx_dev,
+        )?,
+        typescript: parser::TypeScriptOptions::default(),
+        sourcefile: options.sourcefile.unwrap_or_else(|| "<stdin>".to_owned()),
+        preserve_comments: false,
+        log_overrides: log_overrides(options.log_override)?,
+        unsupported_syntax: unsupported_syntax(options.unsupported_syntax.as_deref())?,
+    })
+}
+
+fn build_options(options: BuildOptions) -> Result<api::BuildOptions> {
+    let fs = RealFileSystem::new();
+    let abs = |path: Option<String>| match path {
+        Some(path) => match fs.abs(&path) {
+            Some(path) => Ok(paths::to_slash(&path.to_string_lossy())),
+            None => Err(Error::from_reason(format!("Invalid path: {}", path))),
+        },
+        None => Ok(String::new()),
+    };
+
+    let mut result = api::BuildOptions {
+        entry_paths: options.entry_points,
+        write: options.write.unwrap_or(true),
+        log_overrides: log_overrides(options.log_override)?,
+        ..api::BuildOptions::default()
+    };
+    let parse_options = &mut result.parse_options;
+    let resolve_options = &mut result.resolve_options;
+    let bundle_options = &mut result.bundle_options;
+
+    let bundle = options.bundle.unwrap_or(false);
+    parse_options.is_bundling = bundle;
+    bundle_options.bundle = bundle;
+    bundle_options.abs_output_file = abs(options.outfile)?;
+    bundle_options.abs_output_dir = abs(options.outdir)?;
+    bundle_options.abs_outbase = abs(options.outbase)?;
+
+    let target = target(options.target.as_deref())?;
+    parse_options.lower = LowerOptions {
+        target,
+        ..LowerOptions::default()
+    };
+    bundle_options.target = target;
+
+    if let Some(format) = &options.format {
+        let format = match format.as_str() {
+            "esm" => OutputFormat::Esm,
+            "iife" => OutputFormat::Iife,
+            "cjs" => OutputFormat::Cjs,
+            _ => return Err(Error::from_reason("Valid formats: esm, iife, cjs")),
+        };
+        parse_options.output_format = format;
+        bundle_options.output_format = format;
+    }
+
+    if let Some(platform) = &options.platform {
+        resolve_options.platform = match platform.as_str() {
+            "browser" => Platform::Browser,
+            "node" => Platform::Node,
+            "neutral" => Platform::Neutral,
+            _ => {
+                return Err(Error::from_reason(
+                    "Valid platforms: browser, node, neutral",
+                ))
+            }
+        };
+    }
+
+    if let Some(mode) = &options.legal_comments {
+        bundle_options.legal_comments = match mode.as_str() {
+            "none" => LegalComments::None,
+            "inline" => LegalComments::Inline,
+            "eof" => LegalComments::EndOfFile,
+            "external" => LegalComments::External,
+            _ => {
+                return Err(Error::from_reason(
+                    "Valid legal comment modes: none, inline, eof, external",
+                ))
+            }
+        };
+    }
+
+    for path in options.external.unwrap_or_default() {
+        if path.matches('*').count() > 1 {
+            return Err(Error::from_reason(format!(
+                "External paths can only have one \"*\": {}",
+                path
+            )));
+        }
+        resolve_options.external.push(path);
+    }
+
+    for (ext, name) in options.loader.unwrap_or_default() {
+        if !ext.starts_with('.') {
+            return Err(Error::from_reason(format!(
+                "The extension must start with \".\": {}",
+                ext
+            )));
+        }
+        let loader = bundler::Loader::from_name(&name)
+            .ok_or_else(|| Error::from_reason(format!("Invalid loader: {}", name)))?;
+        parse_options.loaders.insert(ext, loader);
+    }
+
+    if let Some(prefixes) = options.rewrite_import {
+        bundle_options.rewrite_external_paths = Some(ImportPathRewriter::with_prefixes(
+            prefixes.into_iter().collect(),
+        ));
+    }
+
+    parse_options.defines = defines(options.define)?;
+    parse_options.jsx = jsx_options(
+        options.jsx.as_deref(),
+        options.jsx_factory.as_deref(),
+        options.jsx_fragment.as_deref(),
+        options.jsx_import_source,
+        options.jsx_dev,
+    )?;
+    bundle_options.minify = minify_options(
+        options.minify,
+        options.minify_whitespace,
+        options.minify_syntax,
+        options.minify_identifiers,
+    );
+    bundle_options.source_map = options.sourcemap.unwrap_or(false);
+    bundle_options.code_splitting = options.splitting.unwrap_or(false);
+    bundle_options.hmr = options.hmr.unwrap_or(false);
+    bundle_options.metafile = options.metafile.unwrap_or(false);
+    if let Some(name) = options.global_name {
+        bundle_options.global_name = name;
+    }
+    if let Some(path) = options.public_path {
+        bundle_options.public_path = path;
+    }
+    if let Some(template) = options.entry_names {
+        bundle_options.entry_names = template;
+    }
+    if let Some(template) = options.chunk_names {
+        bundle_options.chunk_names = template;
+    }
+    if let Some(template) = options.asset_names {
+        bundle_options.asset_names = template;
+    }
+
+    if result.entry_paths.len() > 1 && result.bundle_options.abs_output_dir.is_empty() {
+        return Err(Error::from_reason(
+            "Must provide \"outdir\" when there are multiple input files",
+        ));
+    }
+    result
+        .bundle_options
+        .validate()
+        .map_err(|err| Error::from_reason(err.to_string()))?;
+    Ok(result)
+}
+
+fn target(name: Option<&str>) -> Result<Target> {
+    match name {
+        Some(name) => Target::from_name(name).ok_or_else(|| {
+            Error::from_reason(
+                "Valid targets: es5, es6, es2015, es2016, es2017, es2018, es2019, es2020, esnext",
+            )
+        }),
+        None => Ok(Target::default()),
+    }
+}
+
+fn unsupported_syntax(mode: Option<&str>) -> Result<UnsupportedSyntax> {
+    match mode {
+        None | Some("error") => Ok(UnsupportedSyntax::Error),
+        Some("warn") => Ok(UnsupportedSyntax::Warn),
+        Some("throw") => Ok(UnsupportedSyntax::Throw),
+        Some(_) => Err(Error::from_reason(
+            "Valid unsupported syntax modes: error, warn, throw",
+        )),
+    }
+}
+
+// "minify" turns on all three, and each of the others can turn one of them
+// back off
+fn minify_options(
+    minify: Option<bool>,
+    whitespace: Option<bool>,
+    syntax: Option<bool>,
+    identifiers: Option<bool>,
+) -> MinifyOptions {
+    let minify = minify.unwrap_or(false);
+    MinifyOptions {
+        whitespace: whitespace.unwrap_or(minify),
+        syntax: syntax.unwrap_or(minify),
+        identifiers: identifiers.unwrap_or(minify),
+    }
+}
+
+// The values are the same JSON or names that "--define" takes
+fn defines(define: Option<HashMap<String, String>>) -> Result<HashMap<String, DefineValue>> {
+    let mut defines = HashMap::new();
+    for (name, value) in define.unwrap_or_default() {
+        let (name, value) = parser::parse_define(&format!("{}={}", name, value))
+            .map_err(|err| Error::from_reason(err.to_string()))?;
+        defines.insert(name, value);
+    }
+    Ok(defines)
+}
+
+// esbuild calls the classic runtime "transform". There's no "preserve" since
+// JSX is always turned into calls.
+fn jsx_options(
+    jsx: Option<&str>,
+    factory: Option<&str>,
+    fragment: Option<&str>,
+    import_source: Option<String>,
+    development: Option<bool>,
+) -> Result<JSXOptions> {
+    let property_chain = |name: Option<&str>| match name {
+        Some(name) => name.split('.').map(str::to_owned).collect(),
+        None => vec![],
+    };
+    Ok(JSXOptions {
+        parse: false,
+        runtime: match jsx {
+            Some("transform") | None => JSXRuntime::Classic,
+            Some("automatic") => JSXRuntime::Automatic,
+            Some(_) => return Err(Error::from_reason("Valid JSX modes: transform, automatic")),
+        },
+        factory: property_chain(factory),
+        fragment: property_chain(fragment),
+        import_source: import_source.unwrap_or_default(),
+        development: development.unwrap_or(false),
+    })
+}
+
+fn log_overrides(
+    log_override: Option<HashMap<String, String>>,
+) -> Result<HashMap<MsgId, LogLevel>> {
+    let mut overrides = HashMap::new();
+    for (name, level) in log_override.unwrap_or_default() {
+        let id = MsgId::from_name(&name)
+            .ok_or_else(|| Error::from_reason(format!("Invalid warning name: {}", name)))?;
+        let level = LogLevel::from_name(&level).ok_or_else(|| {
+            Error::from_reason(format!(
+                "Invalid log level: {} (valid levels: warning, error, silent)",
+                level
+            ))
+        })?;
+        overrides.insert(id, level);
+    }
+    Ok(overrides)
+}
+
+fn transform_result(result: crate::TransformResult) -> Result<TransformResult> {
+    if !result.errors.is_empty() {
+        return Err(failure("Transform", &result.errors));
+    }
+    Ok(TransformResult {
+        code: result.code,
+        warnings: result.warnings.iter().map(message).collect(),
+    })
+}
+
+fn build_result(result: api::BuildResult, write: bool) -> BuildResult {
+    let output_files = if write {
+        None
+    } else {
+        let files = result.output_files.into_iter().map(|file| OutputFile {
+            text: String::from_utf8_lossy(&file.contents).into_owned(),
+            path: file.path,
+            contents: file.contents.into(),
+        });
+        Some(files.collect())
+    };
+    BuildResult {
+        errors: result.errors.iter().map(message).collect(),
+        warnings: result.warnings.iter().map(message).collect(),
+        output_files,
+        metafile: result.metafile,
+    }
+}
+
+// The error that a failed build rejects with has every error in its text,
+// like esbuild's:
+//
+//   Build failed with 1 error:
+//   a.js:1:4: ERROR: Unexpected "}"
+//
+fn failure(what: &str, errors: &[Msg]) -> Error {
+    let mut text = format!(
+        "{} failed with {} error{}:",
+        what,
+        errors.len(),
+        if errors.len() == 1 { "" } else { "s" }
+    );
+    for msg in errors {
+        text.push('\n');
+        if let Some(location) = location(&msg.source, msg.start, msg.length) {
+            text.push_str(&format!(
+                "{}:{}:{}: ",
+                location.file, location.line, location.column
+            ));
+        }
+        text.push_str("ERROR: ");
+        text.push_str(&msg.text);
+    }
+    Error::from_reason(text)
+}
+
+fn message(msg: &Msg) -> Message {
+    Message {
+        id: msg.id.to_string(),
+        text: msg.text.clone(),
+        location: location(&msg.source, msg.start, msg.length),
+        notes: msg
+            .notes
+            .iter()
+            .map(|note| Note {
+                text: note.text.clone(),
+                location: location(&note.source, note.start, note.length),
+            })
+            .collect(),
+    }
+}
+
+// A message about the build as a whole has no location
+fn location(source: &Source, start: usize, length: usize) -> Option<Location> {
+    if source.pretty_path.is_empty() {
+        return None;
+    }
+    let (line, column, line_start) = source.line_offset_table().line_and_column(start);
+    let line_text = source.contents[line_start..]
+        .lines()
+        .next()
+        .unwrap_or_default();
+    Some(Location {
+        file: source.pretty_path.clone(),
+        line: line as u32 + 1,
+        column: column as u32,
+        length: length as u32,
+        line_text: line_text.to_owned(),
+    })
+}
@@ -0,0 +1,91 @@
+// Numbers are printed with as few digits as possible while still parsing back
+// to exactly the same f64. The digits themselves come from Rust's formatting,
+// which already produces the shortest digits that round-trip. What's left is
+// choosing where the decimal point goes and whether to use an exponent.
+
+// Converts a finite number to a string the same way "String(value)" does in
+// JavaScript, so "1e21" is printed as "1e+21" and "0.000001" stays as it is
+pub fn number_to_string(value: f64) -> String {
+    if value < 0.0 {
+        return format!("-{}", number_to_string(-value));
+    }
+    let (digits, point) = shortest_digits(value);
+    let count = digits.len() as i32;
+
+    if count <= point && point <= 21 {
+        format!("{}{}", digits, "0".repeat((point - count) as usize))
+    } else if 0 < point && point <= 21 {
+        let (whole, fraction) = digits.split_at(point as usize);
+        format!("{}.{}", whole, fraction)
+    } else if -6 < point && point <= 0 {
+        format!("0.{}{}", "0".repeat(-point as usize), digits)
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let exponent = point - 1;
+        format!(
+            "{}{}{}e{}{}",
+            first,
+            if rest.is_empty() { "" } else { "." },
+            rest,
+            if exponent < 0 { "-" } else { "+" },
+            exponent.abs()
+        )
+    }
+}
+
+// Returns the shortest way to write a finite, non-negative number in
+// JavaScript. This tries a plain decimal without the leading zero (".5"), an
+// exponent on the digits without a decimal point ("15e-8" instead of
+// "0.00000015" or "1.5e-7"), and hexadecimal for large integers ("0xffffffffffff").
+// Ties go to the plain decimal.
+pub fn shortest_number_to_string(value: f64) -> String {
+    let (digits, point) = shortest_digits(value);
+    let count = digits.len() as i32;
+
+    let decimal = if count <= point {
+        format!("{}{}", digits, "0".repeat((point - count) as usize))
+    } else if 0 < point {
+        let (whole, fraction) = digits.split_at(point as usize);
+        format!("{}.{}", whole, fraction)
+    } else {
+        format!(".{}{}", "0".repeat(-point as usize), digits)
+    };
+    let mut shortest = decimal;
+
+    let exponent = point - count;
+    if exponent != 0 {
+        let candidate = format!("{}e{}", digits, exponent);
+        if candidate.len() < shortest.len() {
+            shortest = candidate;
+        }
+    }
+
+    // Integers above this can't be written exactly in hexadecimal by every
+    // tool that reads the output
+    if value.fract() == 0.0 && value <= MAX_SAFE_INTEGER {
+        let candidate = format!("0x{:x}", value as u64);
+        if candidate.len() < shortest.len() {
+            shortest = candidate;
+        }
+    }
+
+    shortest
+}
+
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+// Splits a finite, non-negative number into its shortest significant digits
+// and the position of the decimal point relative to them, so the value is
+// "0.<digits>" times 10 to the power of the position
+fn shortest_digits(value: f64) -> (String, i32) {
+    if value == 0.0 {
+        return ("0".to_owned(), 1);
+    }
+
+    // This is something like "1.2345e-7"
+    let text = format!("{:e}", value);
+    let (mantissa, exponent) = text.split_once('e').expect("Always has an exponent");
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let exponent: i32 = exponent.parse().expect("The exponent is an integer");
+    (digits, exponent + 1)
+}
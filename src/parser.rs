@@ -11,3 +11,8407 @@
 // the number of full-tree passes to improve performance. However, we need
 // to have at least two separate passes to handle variable hoisting. See the
 // comment about scopesInOrder below for more information.
+
+use crate::ast::{
+    generate_non_unique_name_from_path, is_sloppy_mode_block_level_function, Arg, ArrayBinding,
+    Binding, BindingKind, Case, Catch, Class, ClauseItem, Decl, DeclaredSymbol, Expr, ExprKind,
+    ExprOrStmt, Finally, Function, FunctionBody, ImportKind, ImportPath, LocalKind, Location,
+    LocationRef, NamedImport, NamespaceSymbol, Operator, OperatorCode, OptionalChain, Part, Path,
+    Property, PropertyBinding, PropertyKind, Reference, Scope, ScopeId, ScopeKind, ScopeTree, Stmt,
+    StmtKind, StrictModeKind, Symbol, SymbolKind, SymbolMap, TemplatePart, AST, INVALID_REF,
+};
+use crate::bundler::{loader_for_path, Loader, OutputFormat};
+use crate::error::Error;
+use crate::hooks::ExtensionHooks;
+use crate::lexer::{is_identifier, is_identifier_continue, Lexer, LexerCheckpoint};
+use crate::logging::{Log, MsgId, MsgKind, MsgNote, Source};
+use crate::lower::{lower_stmts, LowerOptions, Target, TempRefs};
+use crate::parser_json::{parse_json, JsonOptions};
+use crate::stack::ensure_sufficient_stack;
+use crate::strings::{string_to_utf16, utf16_equals_string, utf16_to_string_lossy};
+use crate::tables::Token;
+use crate::visit::Visit;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    // When bundling, every top-level statement goes in its own part so that
+    // unused parts can be removed later, and "require()" and "import()" calls
+    // with a string argument are recorded as import paths
+    pub is_bundling: bool,
+    pub output_format: OutputFormat,
+    pub drop: DropOptions,
+    pub lower: LowerOptions,
+    pub typescript: TypeScriptOptions,
+    pub jsx: JSXOptions,
+
+    // Global names and property chains to replace at compile time, keyed by
+    // the dotted name (see "DefineValue")
+    pub defines: HashMap<String, DefineValue>,
+
+    // Loaders to use instead of the default one for a file extension, keyed
+    // by the extension including the dot, like ".png"
+    pub loaders: HashMap<String, Loader>,
+
+    // Callbacks that turn files with an extension into code that one of the
+    // loaders reads before they are parsed (see "ExtensionHooks")
+    pub extension_hooks: ExtensionHooks,
+
+    // Keeps every comment that's in front of a statement or at the end of a
+    // block, not just legal comments, so that the output is easier to match
+    // up with the input. Comments inside expressions are still dropped.
+    pub preserve_comments: bool,
+}
+
+// How JSX elements become calls. The classic runtime calls a factory that
+// must be in scope, and the automatic one imports its functions:
+//
+//   <div a={b}>c</div>  =>  React.createElement("div", {a: b}, "c")
+//   <div a={b}>c</div>  =>  _jsx("div", {a: b, children: "c"})
+//
+#[derive(Debug, Clone, Default)]
+pub struct JSXOptions {
+    // Only files that use the "jsx" loader are parsed as JSX
+    pub parse: bool,
+    pub runtime: JSXRuntime,
+
+    // The functions that elements and fragments become calls to with the
+    // classic runtime, as property chains like ["React", "createElement"].
+    // Empty chains mean the React ones.
+    pub factory: Vec<String>,
+    pub fragment: Vec<String>,
+
+    // The package that the automatic runtime is imported from. Empty means
+    // "react", which has the functions in "react/jsx-runtime".
+    pub import_source: String,
+
+    // The automatic runtime calls "jsxDEV" from "react/jsx-dev-runtime"
+    // instead, which also gets where each element is in the source
+    pub development: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JSXRuntime {
+    #[default]
+    Classic,
+    Automatic,
+}
+
+// Returns None if there was a syntax error. The error has already been logged.
+pub fn parse(log: &Log, source: &Source, options: &ParseOptions) -> Option<AST> {
+    let mut p = Parser::new(log, source, options);
+
+    // Pass 1: parse and declare
+    p.lexer.next();
+    let mut stmts = p.parse_module().ok()?;
+    p.apply_module_strict_mode().ok()?;
+    p.hoist_block_level_functions();
+    p.add_jsx_runtime_imports(&mut stmts);
+
+    // Pass 2: bind identifiers and collect what the bundler needs. This also
+    // runs after a syntax error to check the statements that did parse, such
+    // as where "break" and "continue" go, but the tree isn't returned.
+    let has_syntax_error = p.has_syntax_error;
+    let ast = p.into_ast(stmts);
+    if has_syntax_error {
+        return None;
+    }
+    Some(ast)
+}
+
+// Like "parse", but returns the first error instead of logging it. Warnings
+// are dropped.
+pub fn try_parse(source: &Source, options: &ParseOptions) -> Result<AST, Error> {
+    let log = Log::default();
+    let ast = parse(&log, source, options);
+    let mut msgs = log.take_msgs().into_iter();
+    if let Some(msg) = msgs.find(|msg| msg.kind == MsgKind::Error) {
+        return Err(Error::Parse(Box::new(msg)));
+    }
+    Ok(ast.expect("Parsing only fails after logging an error"))
+}
+
+// Makes a module whose default export is the given value. This is used for
+// files that aren't JavaScript but can be imported as if they were, such as
+// JSON files.
+pub fn lazy_export_ast(log: &Log, source: &Source, options: &ParseOptions, value: Expr) -> AST {
+    let mut p = Parser::new(log, source, options);
+    let name = format!(
+        "{}_default",
+        generate_non_unique_name_from_path(&source.pretty_path)
+    );
+    let default_name = LocationRef {
+        loc: value.location,
+        reference: p.generate_symbol(SymbolKind::Other, &name),
+    };
+    p.named_exports
+        .insert("default".to_owned(), default_name.reference);
+    p.has_es6_exports = true;
+
+    let location = value.location;
+    let export = stmt(
+        location,
+        StmtKind::ExportDefault {
+            default_name,
+            value: ExprOrStmt::Expr(value),
+        },
+    );
+    p.into_ast(vec![export])
+}
+
+// Makes the module of a CSS modules file from its local names and what they
+// were renamed to. Each name is exported on its own, and the default export
+// is an object with all of them:
+//
+//   var button = "button_SUFFIX";
+//   export default { button };
+//
+// Names that aren't identifiers, like "a-b", are still exported under their
+// own name even though only the object can be used to get them.
+pub fn css_module_ast(
+    log: &Log,
+    source: &Source,
+    options: &ParseOptions,
+    names: &[(String, String)],
+) -> AST {
+    let mut p = Parser::new(log, source, options);
+    let string = |text: &str| {
+        expr(
+            0,
+            ExprKind::String {
+                value: string_to_utf16(text),
+            },
+        )
+    };
+    let mut stmts = vec![];
+    let mut properties = vec![];
+    for (name, local_name) in names {
+        let mut symbol_name: String = name
+            .chars()
+            .map(|c| if is_identifier_continue(c) { c } else { '_' })
+            .collect();
+        while !is_identifier(&symbol_name)
+            || Token::try_from(symbol_name.as_str()).is_ok()
+            || p.scopes[ScopeTree::ROOT].members.contains_key(&symbol_name)
+        {
+            symbol_name.insert(0, '_');
+        }
+        let reference = p.declare_symbol(SymbolKind::Hoisted, 0, &symbol_name);
+        p.named_exports.insert(name.clone(), reference);
+        let decl = Decl {
+            binding: Binding::identifier(0, reference),
+            value: Some(string(local_name)),
+        };
+        stmts.push(stmt(
+            0,
+            StmtKind::Local {
+                decls: vec![decl],
+                kind: LocalKind::Var,
+                is_export: false,
+                was_ts_import_equals_in_namespace: false,
+            },
+        ));
+        let value = expr(
+            0,
+            ExprKind::Identifier {
+                reference: p.store_name_in_ref(symbol_name),
+            },
+        );
+        properties.push(jsx_property(
+            PropertyKind::PropertyNormal,
+            string(name),
+            value,
+        ));
+    }
+
+    let name = format!(
+        "{}_default",
+        generate_non_unique_name_from_path(&source.pretty_path)
+    );
+    let default_name = LocationRef {
+        loc: 0,
+        reference: p.generate_symbol(SymbolKind::Other, &name),
+    };
+    p.named_exports
+        .insert("default".to_owned(), default_name.reference);
+    p.has_es6_exports = true;
+    stmts.push(stmt(
+        0,
+        StmtKind::ExportDefault {
+            default_name,
+            value: ExprOrStmt::Expr(expr(0, ExprKind::Object { properties })),
+        },
+    ));
+    p.into_ast(stmts)
+}
+
+// The lexer has already logged the error when this is returned, so all that's
+// left to do is to stop parsing
+#[derive(Debug)]
+struct SyntaxError;
+
+type PResult<T> = Result<T, SyntaxError>;
+
+// What the first pass has built up so far, so that the parser can try parsing
+// something and then go back as if it never had. Symbols, names, and scopes
+// are only ever added during the first pass, so their lists are put back by
+// truncating them.
+struct ParserCheckpoint {
+    lexer: LexerCheckpoint,
+    symbol_count: usize,
+    stored_name_count: usize,
+    scope_count: usize,
+    scope_order_count: usize,
+    current_scope: ScopeId,
+    block_level_function_count: usize,
+    sloppy_mode_feature_count: usize,
+    es6_keyword: Option<(Location, &'static str)>,
+    fn_flags: FnFlags,
+    is_inside_function: bool,
+    is_inside_static_block: bool,
+    is_inside_loop: bool,
+    is_inside_switch: bool,
+    allow_in: bool,
+    has_syntax_error: bool,
+}
+
+// Code nested deeper than this is an error. Every pass over the tree recurses
+// once per level, and while the passes that are written out grow the stack as
+// needed, the ones that Rust generates, such as dropping statements, can't.
+// This leaves room for any real code. Brackets, blocks, functions, and classes
+// count as a level, but operators don't (see "parse_nested_expr").
+const MAX_NESTING_DEPTH: usize = 2_500;
+
+// Identifiers in expressions can't be bound during the first pass since the
+// declaration may come later in the scope (e.g. a function declared at the
+// bottom). Until the second pass, the reference holds an index into
+// "stored_names" with this outer index.
+const STORED_NAME: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FnFlags {
+    is_async: bool,
+    is_generator: bool,
+}
+
+// Everything the bundler needs to know about one part, collected while
+// binding its statements
+#[derive(Default)]
+struct PartInfo {
+    import_paths: Vec<ImportPath>,
+    declared_symbols: Vec<DeclaredSymbol>,
+    use_count_estimates: HashMap<Reference, u32>,
+}
+
+struct Parser<'a> {
+    log: &'a Log,
+    source: &'a Source,
+    lexer: Lexer<'a>,
+    options: &'a ParseOptions,
+    source_index: usize,
+    symbols: Vec<Symbol>,
+    stored_names: Vec<String>,
+
+    // Where each declared symbol was first declared, by inner index, so that
+    // a conflicting declaration can point to it
+    declared_locations: HashMap<usize, Location>,
+
+    // Function declarations directly inside a block, with the scope of the
+    // block. Whether they are also hoisted depends on strict mode, which is
+    // only known for sure once the whole file has been parsed (see
+    // "is_sloppy_mode_block_level_function").
+    block_level_functions: Vec<(ScopeId, Reference)>,
+
+    // The first pass pushes every scope it creates here. The second pass
+    // visits the tree in the same order and takes the scopes from this list
+    // instead of creating them again.
+    scopes: ScopeTree,
+    scopes_in_order: Vec<ScopeId>,
+    next_scope_in_order: usize,
+    current_scope: ScopeId,
+
+    // Label scopes whose statement is a loop. Only those can be the target of
+    // "continue".
+    loop_labels: HashSet<ScopeId>,
+
+    fn_flags: FnFlags,
+    is_inside_function: bool,
+
+    // A class static block is like a function body, except that it can't
+    // "return"
+    is_inside_static_block: bool,
+
+    // Where an unlabeled "break" or "continue" can go. Neither crosses a
+    // function boundary.
+    is_inside_loop: bool,
+    is_inside_switch: bool,
+
+    // This is false while parsing the initializer of a "for" loop so that
+    // "for (a in b)" isn't parsed as the expression "a in b"
+    allow_in: bool,
+
+    hash_bang: String,
+    has_top_level_return: bool,
+    has_es6_imports: bool,
+    has_es6_exports: bool,
+
+    // Code in an ES6 module is strict, but a file is only known to be one once
+    // an "import" or "export" statement is found. Syntax that isn't allowed in
+    // strict mode is recorded until then, along with the first keyword that
+    // made the file a module.
+    sloppy_mode_features: Vec<(Range<usize>, String)>,
+    es6_keyword: Option<(Location, &'static str)>,
+
+    uses_exports_ref: bool,
+    uses_module_ref: bool,
+    exports_ref: Reference,
+    module_ref: Reference,
+
+    // The object that stands in for "import.meta" in the formats that don't
+    // have it. It's only generated once something uses it.
+    import_meta_ref: Reference,
+
+    import_items: HashSet<Reference>,
+    named_imports: HashMap<Reference, NamedImport>,
+    named_exports: HashMap<String, Reference>,
+    export_stars: Vec<Path>,
+
+    // This is the part whose statements are being bound in the second pass
+    part_index: u32,
+    part: PartInfo,
+
+    // This is true while visiting the target of an assignment, which must not
+    // be replaced by a define
+    is_assign_target: bool,
+
+    // Set once a statement fails to parse. Parsing goes on to find more
+    // errors, and each statement that failed is left out of the tree.
+    has_syntax_error: bool,
+
+    // Whether the "(" at each location starts the arguments of an arrow
+    // function. Looking ahead parses the arguments, which may contain more
+    // parentheses that are looked ahead at themselves, so remembering the
+    // answers keeps nested parentheses from taking exponential time.
+    arrow_args_ahead: HashMap<Location, bool>,
+
+    // How many expressions, statements, and bindings the one being parsed is
+    // nested in
+    nesting_depth: usize,
+
+    // The functions of the automatic JSX runtime that the elements in the
+    // file call, by the names they're exported as. Their symbols are only
+    // made once the whole file has been parsed (see
+    // "add_jsx_runtime_imports").
+    jsx_runtime_refs: BTreeMap<&'static str, Reference>,
+
+    // Whether "this" belongs to a function or class rather than being the one
+    // at the top level, which JSX elements pass on in development
+    is_this_nested: bool,
+
+    // The imports that stand in for properties of the default import of a CSS
+    // modules file, keyed by the default import and the property name (see
+    // "css_module_item")
+    css_module_items: HashMap<(Reference, String), Reference>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(log: &'a Log, source: &'a Source, options: &'a ParseOptions) -> Self {
+        let mut p = Parser {
+            log,
+            source,
+            lexer: Lexer::new(log, source),
+            options,
+            source_index: source.index as usize,
+            symbols: vec![],
+            stored_names: vec![],
+            declared_locations: HashMap::new(),
+            block_level_functions: vec![],
+            scopes: ScopeTree::new(Scope::new(ScopeKind::Entry, StrictModeKind::Sloppy)),
+            scopes_in_order: vec![],
+            next_scope_in_order: 0,
+            current_scope: ScopeTree::ROOT,
+            loop_labels: HashSet::new(),
+            fn_flags: FnFlags::default(),
+            is_inside_function: false,
+            is_inside_static_block: false,
+            is_inside_loop: false,
+            is_inside_switch: false,
+            allow_in: true,
+            hash_bang: String::new(),
+            has_top_level_return: false,
+            has_es6_imports: false,
+            has_es6_exports: false,
+            sloppy_mode_features: vec![],
+            es6_keyword: None,
+            uses_exports_ref: false,
+            uses_module_ref: false,
+            exports_ref: INVALID_REF,
+            module_ref: INVALID_REF,
+            import_meta_ref: INVALID_REF,
+            import_items: HashSet::new(),
+            named_imports: HashMap::new(),
+            named_exports: HashMap::new(),
+            export_stars: vec![],
+            part_index: 0,
+            part: PartInfo::default(),
+            is_assign_target: false,
+            has_syntax_error: false,
+            arrow_args_ahead: HashMap::new(),
+            nesting_depth: 0,
+            jsx_runtime_refs: BTreeMap::new(),
+            is_this_nested: false,
+            css_module_items: HashMap::new(),
+        };
+
+        p.lexer.preserve_comments = options.preserve_comments;
+
+        // These are the CommonJS variables. Outside of a bundle they refer to
+        // the real ones, so they have to keep their names.
+        p.exports_ref = p.declare_symbol(SymbolKind::Hoisted, 0, "exports");
+        p.module_ref = p.declare_symbol(SymbolKind::Hoisted, 0, "module");
+        for reference in &[p.exports_ref, p.module_ref] {
+            p.symbols[reference.inner].must_not_be_renamed = !options.is_bundling;
+        }
+        p
+    }
+
+    fn check(&self) -> PResult<()> {
+        if self.lexer.token == Token::SyntaxError {
+            return Err(SyntaxError);
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> PResult<()> {
+        self.lexer.next();
+        self.check()
+    }
+
+    fn expect(&mut self, token: Token) -> PResult<()> {
+        self.lexer.expect(token);
+        self.check()
+    }
+
+    fn expect_or_insert_semicolon(&mut self) -> PResult<()> {
+        self.lexer.expect_or_insert_semicolon();
+        self.check()
+    }
+
+    fn expect_contextual_keyword(&mut self, text: &str) -> PResult<()> {
+        self.lexer.expect_contextual_keyword(text);
+        self.check()
+    }
+
+    fn unexpected<T>(&mut self) -> PResult<T> {
+        self.lexer.unexpected();
+        Err(SyntaxError)
+    }
+
+    // Errors aren't reported while looking ahead, the same as in the lexer
+    fn add_range_error(&self, range: Range<usize>, text: String) {
+        if !self.lexer.is_log_disabled {
+            self.log.add_range_error(self.source, range, text);
+        }
+    }
+
+    fn add_range_error_with_notes(&self, range: Range<usize>, text: String, notes: Vec<MsgNote>) {
+        if !self.lexer.is_log_disabled {
+            self.log
+                .add_range_error_with_notes(self.source, range, text, notes);
+        }
+    }
+
+    // The words that are only reserved in strict mode can be used as names in
+    // sloppy mode, and "yield" can be used as a name outside of generators
+    fn is_identifier(&self) -> bool {
+        match self.lexer.token {
+            Token::Identifier
+            | Token::Implements
+            | Token::Interface
+            | Token::Let
+            | Token::Package
+            | Token::Private
+            | Token::Protected
+            | Token::Public
+            | Token::Static => true,
+            Token::Yield => !self.fn_flags.is_generator,
+            _ => false,
+        }
+    }
+
+    // Parses something that is one level further down in the tree
+    fn nested<T, F>(&mut self, parse: F) -> PResult<T>
+    where
+        F: FnOnce(&mut Self) -> PResult<T>,
+    {
+        let old_depth = self.nesting_depth;
+        self.enter_nesting_level()?;
+        let result = ensure_sufficient_stack(|| parse(self));
+        self.nesting_depth = old_depth;
+        result
+    }
+
+    fn enter_nesting_level(&mut self) -> PResult<()> {
+        if self.nesting_depth == MAX_NESTING_DEPTH {
+            self.add_range_error(self.lexer.range(), "Too many levels of nesting".to_owned());
+            return Err(SyntaxError);
+        }
+        self.nesting_depth += 1;
+        Ok(())
+    }
+
+    fn check_legacy_octal_literal(&mut self) -> PResult<()> {
+        if self.lexer.is_legacy_octal_literal {
+            self.mark_strict_mode_feature(
+                self.lexer.range(),
+                "Legacy octal literals are not allowed".to_owned(),
+            )?;
+        }
+        Ok(())
+    }
+
+    // The words that are only reserved in strict mode are checked when they
+    // are used as a name
+    fn check_strict_mode_reserved_word(&mut self) -> PResult<()> {
+        if self.lexer.token != Token::Identifier {
+            let text = format!("Using \"{}\" as a name is not allowed", self.lexer.raw());
+            self.mark_strict_mode_feature(self.lexer.range(), text)?;
+        }
+        Ok(())
+    }
+
+    // "text" is the start of the error message, which is completed with the
+    // reason the code is strict
+    fn mark_strict_mode_feature(&mut self, range: Range<usize>, text: String) -> PResult<()> {
+        if self.scopes[self.current_scope].strict_mode.is_strict() {
+            self.add_range_error(range, format!("{} in strict mode", text));
+            return Err(SyntaxError);
+        }
+        self.sloppy_mode_features.push((range, text));
+        Ok(())
+    }
+
+    // Called once the whole file has been parsed. A module is strict from the
+    // start, so everything that was parsed as sloppy mode code becomes strict.
+    fn apply_module_strict_mode(&mut self) -> PResult<()> {
+        let (location, keyword) = match self.es6_keyword {
+            Some(keyword) => keyword,
+            None => return Ok(()),
+        };
+        for scope in self.scopes.iter_mut() {
+            if scope.strict_mode == StrictModeKind::Sloppy {
+                scope.strict_mode = StrictModeKind::ImplicitStrictModule;
+            }
+        }
+
+        let features = std::mem::take(&mut self.sloppy_mode_features);
+        for (range, text) in &features {
+            let note = MsgNote::new(
+                self.source,
+                location..location + keyword.len(),
+                format!(
+                    "This file is implicitly in strict mode because of the \"{}\" keyword here",
+                    keyword
+                ),
+            );
+            self.add_range_error_with_notes(
+                range.clone(),
+                format!("{} in an ECMAScript module", text),
+                vec![note],
+            );
+        }
+        if features.is_empty() {
+            Ok(())
+        } else {
+            Err(SyntaxError)
+        }
+    }
+
+    // Property names and import and export aliases can be any identifier
+    // including keywords. Keywords come after "Identifier" in the token list.
+    fn is_identifier_or_keyword(&self) -> bool {
+        self.lexer.token >= Token::Identifier
+    }
+
+    fn expect_identifier(&mut self) -> PResult<String> {
+        if !self.is_identifier() {
+            self.lexer.expected(Token::Identifier);
+            return Err(SyntaxError);
+        }
+        self.check_strict_mode_reserved_word()?;
+        let name = self.lexer.identifier.clone();
+        self.next()?;
+        Ok(name)
+    }
+
+    fn store_name_in_ref(&mut self, name: String) -> Reference {
+        self.stored_names.push(name);
+        Reference::new(STORED_NAME, self.stored_names.len() - 1)
+    }
+
+    fn load_name_from_ref(&self, reference: Reference) -> Option<&str> {
+        if reference.outer != STORED_NAME {
+            return None;
+        }
+        Some(&self.stored_names[reference.inner])
+    }
+
+    fn new_symbol(&mut self, kind: SymbolKind, name: &str) -> Reference {
+        let reference = Reference::new(self.source_index, self.symbols.len());
+        self.symbols.push(Symbol::new(kind, name));
+        reference
+    }
+
+    // Generated symbols aren't visible to code in the file
+    fn generate_symbol(&mut self, kind: SymbolKind, name: &str) -> Reference {
+        let reference = self.new_symbol(kind, name);
+        self.scopes[self.current_scope].generated.push(reference);
+        reference
+    }
+
+    fn push_scope_for_parse_pass(&mut self, kind: ScopeKind) {
+        let parent = self.current_scope;
+        let strict_mode = if kind == ScopeKind::ClassName {
+            StrictModeKind::ImplicitStrictClass
+        } else {
+            self.scopes[parent].strict_mode
+        };
+        let id = self.scopes.push(parent, Scope::new(kind, strict_mode));
+        self.scopes_in_order.push(id);
+        self.current_scope = id;
+    }
+
+    fn pop_scope(&mut self) {
+        self.current_scope = self.scopes[self.current_scope]
+            .parent
+            .expect("The module scope is never popped");
+    }
+
+    fn checkpoint(&self) -> ParserCheckpoint {
+        ParserCheckpoint {
+            lexer: self.lexer.checkpoint(),
+            symbol_count: self.symbols.len(),
+            stored_name_count: self.stored_names.len(),
+            scope_count: self.scopes.len(),
+            scope_order_count: self.scopes_in_order.len(),
+            current_scope: self.current_scope,
+            block_level_function_count: self.block_level_functions.len(),
+            sloppy_mode_feature_count: self.sloppy_mode_features.len(),
+            es6_keyword: self.es6_keyword,
+            fn_flags: self.fn_flags,
+            is_inside_function: self.is_inside_function,
+            is_inside_static_block: self.is_inside_static_block,
+            is_inside_loop: self.is_inside_loop,
+            is_inside_switch: self.is_inside_switch,
+            allow_in: self.allow_in,
+            has_syntax_error: self.has_syntax_error,
+        }
+    }
+
+    fn restore(&mut self, checkpoint: ParserCheckpoint) {
+        let symbol_count = checkpoint.symbol_count;
+        self.lexer.restore(checkpoint.lexer);
+        self.symbols.truncate(symbol_count);
+        self.declared_locations
+            .retain(|&inner, _| inner < symbol_count);
+        self.stored_names.truncate(checkpoint.stored_name_count);
+        let scope_count = checkpoint.scope_count;
+        self.scopes.truncate(scope_count);
+        self.scopes_in_order.truncate(checkpoint.scope_order_count);
+        self.loop_labels.retain(|label| label.0 < scope_count);
+        self.current_scope = checkpoint.current_scope;
+        self.block_level_functions
+            .truncate(checkpoint.block_level_function_count);
+        self.sloppy_mode_features
+            .truncate(checkpoint.sloppy_mode_feature_count);
+        self.es6_keyword = checkpoint.es6_keyword;
+        self.fn_flags = checkpoint.fn_flags;
+        self.is_inside_function = checkpoint.is_inside_function;
+        self.is_inside_static_block = checkpoint.is_inside_static_block;
+        self.is_inside_loop = checkpoint.is_inside_loop;
+        self.is_inside_switch = checkpoint.is_inside_switch;
+        self.allow_in = checkpoint.allow_in;
+        self.has_syntax_error = checkpoint.has_syntax_error;
+    }
+
+    // Called at a "(" that may be a parenthesized expression or the arguments
+    // of an arrow function. That isn't known until after the ")", so this
+    // tries parsing arguments and checks for a "=>" after them, then goes back
+    // to the "(". Errors are left for the real parse to report, which is why a
+    // "=>" on the next line still counts here.
+    fn is_arrow_args_ahead(&mut self) -> bool {
+        let location = self.lexer.start;
+        if let Some(&is_arrow) = self.arrow_args_ahead.get(&location) {
+            return is_arrow;
+        }
+
+        let checkpoint = self.checkpoint();
+        self.lexer.is_log_disabled = true;
+        self.push_scope_for_parse_pass(ScopeKind::FunctionArgs);
+        let is_arrow = self.parse_fn_args().is_ok()
+            && self.skip_typescript_colon_type().is_ok()
+            && self.lexer.token == Token::EqualsGreaterThan;
+        self.restore(checkpoint);
+
+        self.arrow_args_ahead.insert(location, is_arrow);
+        is_arrow
+    }
+
+    // Labels are only visible inside the statement they label, so a label can
+    // only clash with the ones around it in the same function
+    fn check_duplicate_label(&self, location: Location, name: &str) -> PResult<()> {
+        for scope in self.scopes.ancestors(self.current_scope) {
+            let data = &self.scopes[scope];
+            if data.kind == ScopeKind::Label && self.symbols[data.label_ref.inner].name == name {
+                let mut notes = vec![];
+                if let Some(&original) = self.declared_locations.get(&data.label_ref.inner) {
+                    notes.push(MsgNote::new(
+                        self.source,
+                        self.source.range_of_identifier(original),
+                        format!("The original label \"{}\" is here:", name),
+                    ));
+                }
+                self.add_range_error_with_notes(
+                    self.source.range_of_identifier(location),
+                    format!("Duplicate label \"{}\"", name),
+                    notes,
+                );
+                return Err(SyntaxError);
+            }
+            if data.kind.stops_hoisting() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn already_declared(&self, location: Location, name: &str, existing: Reference) {
+        let text = format!("\"{}\" has already been declared", name);
+        let range = self.source.range_of_identifier(location);
+
+        // Symbols that were declared implicitly, such as "exports", don't have
+        // a declaration to point to
+        let original = self
+            .declared_locations
+            .get(&existing.inner)
+            .filter(|&&original| self.source.contents[original..].starts_with(name));
+        match original {
+            Some(&original) => self.add_range_error_with_notes(
+                range,
+                text,
+                vec![MsgNote::new(
+                    self.source,
+                    original..original + name.len(),
+                    format!("\"{}\" was originally declared here:", name),
+                )],
+            ),
+            None => self.add_range_error(range, text),
+        }
+    }
+
+    fn declare_symbol(&mut self, kind: SymbolKind, location: Location, name: &str) -> Reference {
+        if kind == SymbolKind::Hoisted {
+            return self.declare_hoisted_symbol(location, name);
+        }
+
+        let scope = self.current_scope;
+        if let Some(&existing) = self.scopes[scope].members.get(name) {
+            let existing_kind = self.symbols[existing.inner].kind;
+
+            // An import in TypeScript may only be a type, so another
+            // declaration with the same name takes its place
+            if existing_kind != SymbolKind::TSImport {
+                if kind.is_hoisted() && existing_kind.is_hoisted() {
+                    self.symbols[existing.inner].kind = kind;
+                } else {
+                    self.already_declared(location, name, existing);
+                }
+                return existing;
+            }
+        }
+
+        let reference = self.new_symbol(kind, name);
+        self.declared_locations.insert(reference.inner, location);
+        self.scopes[scope]
+            .members
+            .insert(name.to_owned(), reference);
+        reference
+    }
+
+    // A "var" belongs to the closest function or module scope but is visible
+    // from every scope in between, so it's added to all of them. It conflicts
+    // with a lexical declaration of the same name in any of those scopes.
+    fn declare_hoisted_symbol(&mut self, location: Location, name: &str) -> Reference {
+        let mut path: Vec<ScopeId> = vec![];
+        let mut scope = self.current_scope;
+        let mut catch_ref = None;
+        let existing = loop {
+            match self.scopes[scope].members.get(name) {
+                Some(&existing) => match self.symbols[existing.inner].kind {
+                    SymbolKind::Hoisted | SymbolKind::HoistedFunction => break Some(existing),
+
+                    // "try {} catch (e) { var e }" is allowed. Inside the catch
+                    // clause the "var" refers to the catch variable, but it
+                    // still declares a variable in the enclosing function.
+                    SymbolKind::CatchIdentifier => {
+                        for scope in path.drain(..) {
+                            self.scopes[scope].members.insert(name.to_owned(), existing);
+                        }
+                        catch_ref.get_or_insert(existing);
+                    }
+
+                    SymbolKind::TSImport => path.push(scope),
+
+                    _ => {
+                        self.already_declared(location, name, existing);
+                        break Some(existing);
+                    }
+                },
+                None => path.push(scope),
+            }
+            if self.scopes[scope].kind.stops_hoisting() {
+                break None;
+            }
+            scope = self.scopes[scope]
+                .parent
+                .expect("The module scope stops hoisting");
+        };
+
+        // Arguments can be declared again with "var" in the function body and
+        // the "var" refers to the argument
+        let existing = existing.or_else(|| {
+            let scope = &self.scopes[scope];
+            match scope.parent {
+                Some(parent)
+                    if scope.kind == ScopeKind::FunctionBody
+                        && self.scopes[parent].kind == ScopeKind::FunctionArgs =>
+                {
+                    self.scopes[parent].members.get(name).copied()
+                }
+                _ => None,
+            }
+        });
+
+        let reference = existing.unwrap_or_else(|| {
+            let reference = self.new_symbol(SymbolKind::Hoisted, name);
+            self.declared_locations.insert(reference.inner, location);
+            reference
+        });
+        for scope in path {
+            self.scopes[scope]
+                .members
+                .insert(name.to_owned(), reference);
+        }
+
+        // The "var" is printed with the name of the catch variable, so both
+        // symbols must keep the same name
+        match catch_ref {
+            Some(catch_ref) => {
+                self.symbols[catch_ref.inner].must_not_be_renamed = true;
+                self.symbols[reference.inner].must_not_be_renamed = true;
+                catch_ref
+            }
+            None => reference,
+        }
+    }
+
+    // Gives the function declarations in blocks of sloppy mode code their
+    // "var" binding in the enclosing function. Annex B of the specification
+    // leaves the binding out if it would conflict with a lexical declaration
+    // in any of the scopes in between, instead of making that an error. If
+    // the function already has a "var" binding, which can come from another
+    // block too, the declaration is linked to it since they are one variable
+    // outside of the block.
+    fn hoist_block_level_functions(&mut self) {
+        for (block, reference) in std::mem::take(&mut self.block_level_functions) {
+            let block_scope = &self.scopes[block];
+            if !is_sloppy_mode_block_level_function(block_scope.kind, block_scope.strict_mode) {
+                continue;
+            }
+            let name = self.symbols[reference.inner].name.clone();
+            let mut path = vec![];
+            let mut scope = block;
+            let target = loop {
+                scope = self.scopes[scope]
+                    .parent
+                    .expect("The module scope stops hoisting");
+                match self.scopes[scope].members.get(&name) {
+                    Some(&existing) => match self.symbols[existing.inner].kind {
+                        SymbolKind::Hoisted | SymbolKind::HoistedFunction => break Some(existing),
+
+                        // "try {} catch (f) { { function f() {} } }" is allowed
+                        // and doesn't change what "f" is in the catch clause
+                        SymbolKind::CatchIdentifier => {}
+                        _ => break None,
+                    },
+                    None => path.push(scope),
+                }
+                if self.scopes[scope].kind.stops_hoisting() {
+                    break Some(reference);
+                }
+            };
+
+            if let Some(target) = target {
+                if target != reference {
+                    self.symbols[reference.inner].link = target;
+                }
+                for scope in path {
+                    self.scopes[scope].members.insert(name.clone(), target);
+                }
+            }
+        }
+    }
+
+    fn declare_binding(&mut self, kind: SymbolKind, binding: &mut Binding) {
+        ensure_sufficient_stack(|| self.declare_binding_inner(kind, binding))
+    }
+
+    fn declare_binding_inner(&mut self, kind: SymbolKind, binding: &mut Binding) {
+        match binding.data.as_mut() {
+            BindingKind::Missing => {}
+            BindingKind::Identifier { reference } => {
+                let name = self
+                    .load_name_from_ref(*reference)
+                    .expect("Bindings are declared once")
+                    .to_owned();
+                *reference = self.declare_symbol(kind, binding.location, &name);
+            }
+            BindingKind::Array { items, .. } => {
+                for item in items {
+                    self.declare_binding(kind, &mut item.binding);
+                }
+            }
+            BindingKind::Object { properties } => {
+                for property in properties {
+                    self.declare_binding(kind, &mut property.value);
+                }
+            }
+        }
+    }
+
+    fn parse_module(&mut self) -> PResult<Vec<Stmt>> {
+        self.check()?;
+        if self.lexer.token == Token::Hashbang {
+            self.hash_bang = self.lexer.identifier.clone();
+            self.next()?;
+        }
+        self.parse_stmts_up_to(Token::EndOfFile, true)
+    }
+
+    fn parse_stmts_up_to(&mut self, end: Token, allow_directives: bool) -> PResult<Vec<Stmt>> {
+        let mut stmts = vec![];
+        let mut is_directive_prologue = allow_directives;
+
+        // Legal comments are only kept at the top level of the file, while
+        // preserved comments are kept in every list of statements
+        let keep_comments = end == Token::EndOfFile || self.lexer.preserve_comments;
+
+        while self.lexer.token != end {
+            if keep_comments {
+                self.take_comments(&mut stmts);
+            }
+            let mut stmt = self.parse_stmt_or_recover(end)?;
+
+            // A directive is a string literal on its own at the start of the
+            // body. "('use strict')" isn't one, which is why the source is
+            // checked for a quote.
+            if is_directive_prologue {
+                is_directive_prologue = false;
+                if let StmtKind::Expr { value } = stmt.data.as_mut() {
+                    if let ExprKind::String { value } = value.data.as_mut() {
+                        let text = &self.source.contents[stmt.location..];
+                        if text.starts_with('"') || text.starts_with('\'') {
+                            if utf16_equals_string(value, "use strict") {
+                                self.scopes[self.current_scope].strict_mode =
+                                    StrictModeKind::ExplicitStrict;
+                            }
+                            let value = std::mem::take(value);
+                            *stmt.data = StmtKind::Directive { value };
+                            is_directive_prologue = true;
+                        }
+                    }
+                }
+            }
+
+            stmts.push(stmt);
+        }
+
+        if keep_comments {
+            self.take_comments(&mut stmts);
+        }
+        Ok(stmts)
+    }
+
+    // After a syntax error, the rest of the statement is skipped and replaced
+    // by a placeholder so that the statements after it are still checked. The
+    // state that the failed statement may have changed without restoring it
+    // is put back first. That includes the scopes it made, which the second
+    // pass would otherwise expect to find in the statements that did parse.
+    fn parse_stmt_or_recover(&mut self, end: Token) -> PResult<Stmt> {
+        let location = self.lexer.start;
+        let scope_count = self.scopes.len();
+        let scope_order_count = self.scopes_in_order.len();
+        let block_level_function_count = self.block_level_functions.len();
+        let current_scope = self.current_scope;
+        let fn_flags = self.fn_flags;
+        let is_inside_function = self.is_inside_function;
+        let is_inside_static_block = self.is_inside_static_block;
+        let is_inside_loop = self.is_inside_loop;
+        let is_inside_switch = self.is_inside_switch;
+
+        if let Ok(stmt) = self.parse_stmt() {
+            return Ok(stmt);
+        }
+
+        self.has_syntax_error = true;
+        self.scopes.truncate(scope_count);
+        self.scopes_in_order.truncate(scope_order_count);
+        self.loop_labels.retain(|label| label.0 < scope_count);
+        self.block_level_functions
+            .truncate(block_level_function_count);
+        self.current_scope = current_scope;
+        self.fn_flags = fn_flags;
+        self.is_inside_function = is_inside_function;
+        self.is_inside_static_block = is_inside_static_block;
+        self.is_inside_loop = is_inside_loop;
+        self.is_inside_switch = is_inside_switch;
+        self.allow_in = true;
+        self.skip_to_end_of_stmt(end, location)?;
+        Ok(stmt(location, StmtKind::SyntaxError))
+    }
+
+    // Skips tokens up to and including the next ";" or a block in the
+    // statement, or up to the "}" that ends the list of statements. The
+    // ones inside nested braces and templates are ignored. A token on a new
+    // line starts the next statement too, once the statement that failed at
+    // "start" has been skipped past. A token that caused an error has been
+    // turned into a syntax error token, so it's recognized by its text.
+    // Running into the end of the file in a nested list is still an error
+    // since there's nothing left to recover.
+    fn skip_to_end_of_stmt(&mut self, end: Token, start: Location) -> PResult<()> {
+        let mut depth = 0;
+
+        // The brace depth that each template substitution started at
+        let mut template_depths = vec![];
+
+        // A block that closes can end the statement, unless it was nested in
+        // something the statement started before the error, like a class body
+        let mut closed_block = false;
+
+        loop {
+            let token = match (self.lexer.token, self.lexer.raw()) {
+                (Token::SyntaxError, ";") => Token::Semicolon,
+                (Token::SyntaxError, "{") => Token::OpenBrace,
+                (Token::SyntaxError, "}") => Token::CloseBrace,
+                (token, _) => token,
+            };
+            if depth == 0
+                && template_depths.is_empty()
+                && self.lexer.has_newline_before
+                && self.lexer.start > start
+                || closed_block && token != Token::CloseBrace
+            {
+                break;
+            }
+            match token {
+                Token::EndOfFile => break,
+                Token::Semicolon if depth == 0 => {
+                    self.lexer.skip_token();
+                    break;
+                }
+                Token::OpenBrace => depth += 1,
+                Token::TemplateHead => template_depths.push(depth),
+                Token::CloseBrace if template_depths.last() == Some(&depth) => {
+                    self.lexer.token = Token::CloseBrace;
+                    let was_log_disabled = std::mem::replace(&mut self.lexer.is_log_disabled, true);
+                    self.lexer.rescan_close_brace_as_template_token();
+                    self.lexer.is_log_disabled = was_log_disabled;
+                    if self.lexer.token != Token::TemplateMiddle {
+                        template_depths.pop();
+                    }
+                }
+                Token::CloseBrace if depth == 0 => {
+                    // A stray "}" at the top level is skipped like a ";"
+                    if end == Token::CloseBrace {
+                        self.lexer.token = Token::CloseBrace;
+                    } else {
+                        self.lexer.skip_token();
+                    }
+                    break;
+                }
+                Token::CloseBrace => {
+                    depth -= 1;
+                    closed_block = depth == 0;
+                }
+                _ => {}
+            }
+            self.lexer.skip_token();
+        }
+
+        if self.lexer.token == Token::EndOfFile && end != Token::EndOfFile {
+            return Err(SyntaxError);
+        }
+        Ok(())
+    }
+
+    // Comments in the middle of a statement are dropped since there's nowhere
+    // to put them, so only the ones in front of a statement or at the end of
+    // the file are taken
+    fn take_comments(&mut self, stmts: &mut Vec<Stmt>) {
+        let location = self.lexer.start;
+        for comment in self.lexer.comments_before.drain(..) {
+            stmts.push(Stmt {
+                location,
+                data: Box::new(StmtKind::Comment {
+                    text: comment.text,
+                    is_trailing: comment.is_trailing,
+                }),
+            });
+        }
+    }
+
+    fn parse_block_body(&mut self) -> PResult<Vec<Stmt>> {
+        self.expect(Token::OpenBrace)?;
+        let stmts = self.parse_stmts_up_to(Token::CloseBrace, false)?;
+        self.next()?;
+        Ok(stmts)
+    }
+
+    fn parse_stmt(&mut self) -> PResult<Stmt> {
+        self.nested(Self::parse_stmt_inner)
+    }
+
+    fn parse_stmt_inner(&mut self) -> PResult<Stmt> {
+        let location = self.lexer.start;
+
+        let data = match self.lexer.token {
+            Token::Semicolon => {
+                self.next()?;
+                StmtKind::Empty
+            }
+
+            Token::OpenBrace => {
+                self.push_scope_for_parse_pass(ScopeKind::Block);
+                let stmts = self.parse_block_body()?;
+                self.pop_scope();
+                StmtKind::Block { stmts }
+            }
+
+            Token::Var => {
+                self.next()?;
+                let decls = self.parse_and_declare_decls(SymbolKind::Hoisted)?;
+                self.expect_or_insert_semicolon()?;
+                local(decls, LocalKind::Var, false)
+            }
+
+            Token::Const => {
+                self.next()?;
+                if self.options.typescript.parse && self.lexer.token == Token::Enum {
+                    return self.parse_typescript_enum();
+                }
+                let decls = self.parse_and_declare_decls(SymbolKind::Other)?;
+                self.require_initializers(&decls);
+                self.expect_or_insert_semicolon()?;
+                local(decls, LocalKind::Const, false)
+            }
+
+            Token::Let => {
+                self.next()?;
+                if self.is_let_declaration() {
+                    let decls = self.parse_and_declare_decls(SymbolKind::Other)?;
+                    self.expect_or_insert_semicolon()?;
+                    local(decls, LocalKind::Let, false)
+                } else {
+                    let target = self.identifier_expr(location, "let");
+                    let value = self.parse_suffix(target, Operator::Lowest)?;
+                    self.expect_or_insert_semicolon()?;
+                    StmtKind::Expr { value }
+                }
+            }
+
+            Token::Function => return self.parse_fn_stmt(location, false, false, false),
+
+            Token::Class => return self.parse_class_stmt(location, false, false),
+
+            Token::If => {
+                self.next()?;
+                self.expect(Token::OpenParen)?;
+                let test = self.parse_expr(Operator::Lowest)?;
+                self.expect(Token::CloseParen)?;
+                let yes = self.parse_stmt()?;
+                let no = if self.lexer.token == Token::Else {
+                    self.next()?;
+                    Some(self.parse_stmt()?)
+                } else {
+                    None
+                };
+                StmtKind::If { test, yes, no }
+            }
+
+            Token::Do => {
+                self.next()?;
+                let body = self.parse_loop_body()?;
+                self.expect(Token::While)?;
+                self.expect(Token::OpenParen)?;
+                let test = self.parse_expr(Operator::Lowest)?;
+                self.expect(Token::CloseParen)?;
+
+                // A semicolon is always inserted after "do {} while (x)"
+                if self.lexer.token == Token::Semicolon {
+                    self.next()?;
+                }
+                StmtKind::DoWhile { body, test }
+            }
+
+            Token::While => {
+                self.next()?;
+                self.expect(Token::OpenParen)?;
+                let test = self.parse_expr(Operator::Lowest)?;
+                self.expect(Token::CloseParen)?;
+                let body = self.parse_loop_body()?;
+                StmtKind::While { test, body }
+            }
+
+            Token::With => {
+                self.mark_strict_mode_feature(
+                    self.lexer.range(),
+                    "With statements are not allowed".to_owned(),
+                )?;
+                self.next()?;
+                self.expect(Token::OpenParen)?;
+                let value = self.parse_expr(Operator::Lowest)?;
+                self.expect(Token::CloseParen)?;
+                let body_location = self.lexer.start;
+                self.push_scope_for_parse_pass(ScopeKind::With);
+                let body = self.parse_stmt()?;
+                self.pop_scope();
+                StmtKind::With {
+                    value,
+                    body_location,
+                    body,
+                }
+            }
+
+            Token::For => return self.parse_for_stmt(location),
+
+            Token::Return => {
+                self.next()?;
+                let value = if self.lexer.token != Token::Semicolon
+                    && !self.lexer.has_newline_before
+                    && self.lexer.token != Token::CloseBrace
+                    && self.lexer.token != Token::EndOfFile
+                {
+                    Some(self.parse_expr(Operator::Lowest)?)
+                } else {
+                    None
+                };
+                self.expect_or_insert_semicolon()?;
+
+                if self.is_inside_static_block {
+                    self.add_range_error(
+                        location..location + "return".len(),
+                        "A return statement cannot be used inside a class static block".to_owned(),
+                    );
+                }
+
+                // CommonJS modules are allowed to return from the top level
+                if !self.is_inside_function {
+                    self.has_top_level_return = true;
+                }
+                StmtKind::Return { value }
+            }
+
+            Token::Throw => {
+                self.next()?;
+                if self.lexer.has_newline_before {
+                    self.add_range_error(
+                        location..location + "throw".len(),
+                        "Unexpected newline after \"throw\"".to_owned(),
+                    );
+                    return Err(SyntaxError);
+                }
+                let value = self.parse_expr(Operator::Lowest)?;
+                self.expect_or_insert_semicolon()?;
+                StmtKind::Throw { value }
+            }
+
+            Token::Break | Token::Continue => {
+                let is_break = self.lexer.token == Token::Break;
+                let keyword_range = self.lexer.range();
+                self.next()?;
+                let name = if self.is_identifier() && !self.lexer.has_newline_before {
+                    let loc = self.lexer.start;
+                    let name = self.expect_identifier()?;
+                    Some(LocationRef {
+                        loc,
+                        reference: self.store_name_in_ref(name),
+                    })
+                } else {
+                    // Labels are checked once they are bound
+                    let is_allowed = self.is_inside_loop || (is_break && self.is_inside_switch);
+                    if !is_allowed {
+                        let keyword = if is_break { "break" } else { "continue" };
+                        self.add_range_error(
+                            keyword_range,
+                            format!("Cannot use \"{}\" here", keyword),
+                        );
+                        return Err(SyntaxError);
+                    }
+                    None
+                };
+                self.expect_or_insert_semicolon()?;
+                if is_break {
+                    StmtKind::Break { name }
+                } else {
+                    StmtKind::Continue { name }
+                }
+            }
+
+            Token::Debugger => {
+                self.next()?;
+                self.expect_or_insert_semicolon()?;
+                StmtKind::Debugger
+            }
+
+            Token::Try => return self.parse_try_stmt(location),
+
+            Token::Switch => {
+                self.next()?;
+                self.expect(Token::OpenParen)?;
+                let test = self.parse_expr(Operator::Lowest)?;
+                self.expect(Token::CloseParen)?;
+                let body_location = self.lexer.start;
+                self.expect(Token::OpenBrace)?;
+                self.push_scope_for_parse_pass(ScopeKind::Block);
+                let was_inside_switch = std::mem::replace(&mut self.is_inside_switch, true);
+
+                let mut cases = vec![];
+                while self.lexer.token != Token::CloseBrace {
+                    let value = if self.lexer.token == Token::Default {
+                        self.next()?;
+                        None
+                    } else {
+                        self.expect(Token::Case)?;
+                        Some(self.parse_expr(Operator::Lowest)?)
+                    };
+                    self.expect(Token::Colon)?;
+
+                    let mut body = vec![];
+                    loop {
+                        if self.lexer.preserve_comments {
+                            self.take_comments(&mut body);
+                        }
+                        if matches!(
+                            self.lexer.token,
+                            Token::Case | Token::Default | Token::CloseBrace
+                        ) {
+                            break;
+                        }
+                        body.push(self.parse_stmt_or_recover(Token::CloseBrace)?);
+                    }
+                    cases.push(Case { value, body });
+                }
+
+                self.is_inside_switch = was_inside_switch;
+                self.pop_scope();
+                self.next()?;
+                StmtKind::Switch {
+                    test,
+                    body_location,
+                    cases,
+                }
+            }
+
+            Token::Import => {
+                self.next()?;
+
+                // "import()" and "import.meta" are expressions
+                if self.lexer.token == Token::OpenParen || self.lexer.token == Token::Dot {
+                    let target = self.parse_import_expr(location)?;
+                    let value = self.parse_suffix(target, Operator::Lowest)?;
+                    self.expect_or_insert_semicolon()?;
+                    StmtKind::Expr { value }
+                } else if self.options.typescript.parse
+                    && self.is_identifier()
+                    && self.next_token_is(|p| p.lexer.token == Token::Equals)
+                {
+                    // "import a = require('path')" is a CommonJS import, so it
+                    // doesn't make the file an ES6 module. Aliases can also be
+                    // declared in namespaces.
+                    if !self.is_namespace_scope() {
+                        self.check_module_scope(location, "import")?;
+                    }
+                    self.parse_typescript_import_equals(false)?
+                } else {
+                    self.check_module_scope(location, "import")?;
+                    self.es6_keyword.get_or_insert((location, "import"));
+                    self.parse_import_stmt()?
+                }
+            }
+
+            Token::Export => {
+                if self.is_namespace_scope() {
+                    return self.parse_namespace_export_stmt(location);
+                }
+                self.check_module_scope(location, "export")?;
+                self.next()?;
+
+                // "export = value" is a CommonJS export in TypeScript
+                if self.options.typescript.parse && self.lexer.token == Token::Equals {
+                    self.next()?;
+                    let value = self.parse_expr(Operator::Lowest)?;
+                    self.expect_or_insert_semicolon()?;
+                    return Ok(stmt(location, StmtKind::ExportEquals { value }));
+                }
+                self.es6_keyword.get_or_insert((location, "export"));
+                return self.parse_export_stmt(location);
+            }
+
+            _ => {
+                if self.options.typescript.parse {
+                    if let Some(stmt) = self.parse_typescript_stmt(location, false)? {
+                        return Ok(stmt);
+                    }
+                }
+                if self.lexer.is_contextual_keyword("async") {
+                    let async_range = self.lexer.range();
+                    self.next()?;
+                    if self.lexer.token == Token::Function && !self.lexer.has_newline_before {
+                        return self.parse_fn_stmt(location, true, false, false);
+                    }
+                    let target = self.parse_async_prefix_expr(async_range)?;
+                    let value = self.parse_suffix(target, Operator::Lowest)?;
+                    self.expect_or_insert_semicolon()?;
+                    StmtKind::Expr { value }
+                } else {
+                    let is_identifier = self.is_identifier();
+                    let value = self.parse_expr(Operator::Lowest)?;
+
+                    // "a: b" is a label
+                    match value.data.as_ref() {
+                        ExprKind::Identifier { reference }
+                            if is_identifier && self.lexer.token == Token::Colon =>
+                        {
+                            let name = self
+                                .load_name_from_ref(*reference)
+                                .unwrap_or_default()
+                                .to_owned();
+                            self.next()?;
+                            self.check_duplicate_label(location, &name)?;
+                            self.push_scope_for_parse_pass(ScopeKind::Label);
+                            let label_ref = self.new_symbol(SymbolKind::Other, &name);
+                            self.declared_locations.insert(label_ref.inner, location);
+                            self.scopes[self.current_scope].label_ref = label_ref;
+                            let stmt = self.parse_stmt()?;
+                            if is_loop_stmt(&stmt) {
+                                self.loop_labels.insert(self.current_scope);
+                            }
+                            self.pop_scope();
+                            StmtKind::Label {
+                                name: LocationRef {
+                                    loc: location,
+                                    reference: label_ref,
+                                },
+                                stmt,
+                            }
+                        }
+                        _ => {
+                            self.expect_or_insert_semicolon()?;
+                            StmtKind::Expr { value }
+                        }
+                    }
+                }
+            }
+        };
+
+        Ok(stmt(location, data))
+    }
+
+    fn check_module_scope(&mut self, location: Location, keyword: &str) -> PResult<()> {
+        if self.current_scope != ScopeTree::ROOT {
+            self.add_range_error(
+                location..location + keyword.len(),
+                format!("Unexpected \"{}\"", keyword),
+            );
+            return Err(SyntaxError);
+        }
+        Ok(())
+    }
+
+    fn is_namespace_scope(&self) -> bool {
+        self.current_scope != ScopeTree::ROOT
+            && self.scopes[self.current_scope].kind == ScopeKind::Entry
+    }
+
+    // Only types can be exported from a namespace for now, see
+    // "parse_typescript_namespace"
+    fn parse_namespace_export_stmt(&mut self, location: Location) -> PResult<Stmt> {
+        self.next()?;
+        if let Some(stmt) = self.parse_typescript_stmt(location, true)? {
+            return Ok(stmt);
+        }
+        self.add_range_error(
+            location..location + "export".len(),
+            "TypeScript namespaces with values are not supported yet".to_owned(),
+        );
+        Err(SyntaxError)
+    }
+
+    // "let" is only a declaration when it's followed by a binding
+    fn is_let_declaration(&self) -> bool {
+        self.is_identifier()
+            || self.lexer.token == Token::OpenBracket
+            || self.lexer.token == Token::OpenBrace
+    }
+
+    fn require_initializers(&self, decls: &[Decl]) {
+        for decl in decls {
+            if decl.value.is_none() {
+                let name = match decl.binding.data.as_ref() {
+                    BindingKind::Identifier { reference } => {
+                        self.symbols[reference.inner].name.clone()
+                    }
+                    _ => "destructuring pattern".to_owned(),
+                };
+                self.add_range_error(
+                    decl.binding.location..decl.binding.location,
+                    format!("The constant \"{}\" must be initialized", name),
+                );
+            }
+        }
+    }
+
+    fn parse_and_declare_decls(&mut self, kind: SymbolKind) -> PResult<Vec<Decl>> {
+        let mut decls = vec![];
+        loop {
+            let mut binding = self.parse_binding()?;
+            self.declare_binding(kind, &mut binding);
+
+            // "let a!: T" says "a" is assigned before it's used
+            if self.options.typescript.parse && self.lexer.token == Token::Exclamation {
+                self.next()?;
+            }
+            self.skip_typescript_colon_type()?;
+            let value = if self.lexer.token == Token::Equals {
+                self.next()?;
+                Some(self.parse_expr(Operator::Comma)?)
+            } else {
+                None
+            };
+            decls.push(Decl { binding, value });
+
+            if self.lexer.token != Token::Comma {
+                break;
+            }
+            self.next()?;
+        }
+        Ok(decls)
+    }
+
+    // Identifiers in the returned binding still hold their names. They are
+    // declared with "declare_binding" once the kind of declaration is known.
+    fn parse_binding(&mut self) -> PResult<Binding> {
+        self.nested(Self::parse_binding_inner)
+    }
+
+    fn parse_binding_inner(&mut self) -> PResult<Binding> {
+        let location = self.lexer.start;
+
+        let data = match self.lexer.token {
+            Token::OpenBracket => {
+                self.next()?;
+                let mut items = vec![];
+                let mut has_spread = false;
+
+                while self.lexer.token != Token::CloseBracket {
+                    if self.lexer.token == Token::Comma {
+                        items.push(ArrayBinding {
+                            binding: binding(self.lexer.start, BindingKind::Missing),
+                            default_value: None,
+                        });
+                    } else {
+                        if self.lexer.token == Token::DotDotDot {
+                            self.next()?;
+                            has_spread = true;
+                        }
+                        let binding = self.parse_binding()?;
+                        let default_value = if !has_spread && self.lexer.token == Token::Equals {
+                            self.next()?;
+                            Some(self.parse_expr(Operator::Comma)?)
+                        } else {
+                            None
+                        };
+                        items.push(ArrayBinding {
+                            binding,
+                            default_value,
+                        });
+
+                        // The rest element must be last
+                        if has_spread {
+                            break;
+                        }
+                    }
+
+                    if self.lexer.token != Token::Comma {
+                        break;
+                    }
+                    self.next()?;
+                }
+
+                self.expect(Token::CloseBracket)?;
+                BindingKind::Array { items, has_spread }
+            }
+
+            Token::OpenBrace => {
+                self.next()?;
+                let mut properties = vec![];
+
+                while self.lexer.token != Token::CloseBrace {
+                    let property = self.parse_property_binding()?;
+                    let is_spread = property.is_spread;
+                    properties.push(property);
+
+                    // The rest property must be last
+                    if is_spread || self.lexer.token != Token::Comma {
+                        break;
+                    }
+                    self.next()?;
+                }
+
+                self.expect(Token::CloseBrace)?;
+                BindingKind::Object { properties }
+            }
+
+            _ => {
+                let name = self.expect_identifier()?;
+                BindingKind::Identifier {
+                    reference: self.store_name_in_ref(name),
+                }
+            }
+        };
+
+        Ok(binding(location, data))
+    }
+
+    fn parse_property_binding(&mut self) -> PResult<PropertyBinding> {
+        let location = self.lexer.start;
+
+        if self.lexer.token == Token::DotDotDot {
+            self.next()?;
+            let value = self.parse_binding()?;
+            return Ok(PropertyBinding {
+                is_computed: false,
+                is_spread: true,
+                key: expr(location, ExprKind::Missing),
+                value,
+                default_value: None,
+            });
+        }
+
+        let mut is_computed = false;
+        let key = match self.lexer.token {
+            Token::StringLiteral => {
+                let value = std::mem::take(&mut self.lexer.string_literal);
+                self.next()?;
+                expr(location, ExprKind::String { value })
+            }
+            Token::NumericLiteral => {
+                self.check_legacy_octal_literal()?;
+                let value = self.lexer.number;
+                self.next()?;
+                expr(location, ExprKind::Number { value })
+            }
+            Token::BigIntegerLiteral => {
+                let value = std::mem::take(&mut self.lexer.identifier);
+                self.next()?;
+                expr(location, ExprKind::BigInt { value })
+            }
+            Token::OpenBracket => {
+                self.next()?;
+                is_computed = true;
+                let key = self.parse_expr(Operator::Comma)?;
+                self.expect(Token::CloseBracket)?;
+                key
+            }
+            _ if self.is_identifier_or_keyword() => {
+                let name = self.lexer.identifier.clone();
+                let is_identifier = self.is_identifier();
+                self.next()?;
+
+                // "{a}" and "{a = b}"
+                if self.lexer.token != Token::Colon {
+                    if !is_identifier {
+                        self.lexer.expected(Token::Colon);
+                        return Err(SyntaxError);
+                    }
+                    let value = binding(
+                        location,
+                        BindingKind::Identifier {
+                            reference: self.store_name_in_ref(name.clone()),
+                        },
+                    );
+                    let default_value = self.parse_default_value()?;
+                    return Ok(PropertyBinding {
+                        is_computed: false,
+                        is_spread: false,
+                        key: expr(
+                            location,
+                            ExprKind::String {
+                                value: string_to_utf16(&name),
+                            },
+                        ),
+                        value,
+                        default_value,
+                    });
+                }
+                expr(
+                    location,
+                    ExprKind::String {
+                        value: string_to_utf16(&name),
+                    },
+                )
+            }
+            _ => return self.unexpected(),
+        };
+
+        self.expect(Token::Colon)?;
+        let value = self.parse_binding()?;
+        let default_value = self.parse_default_value()?;
+        Ok(PropertyBinding {
+            is_computed,
+            is_spread: false,
+            key,
+            value,
+            default_value,
+        })
+    }
+
+    fn parse_default_value(&mut self) -> PResult<Option<Expr>> {
+        if self.lexer.token != Token::Equals {
+            return Ok(None);
+        }
+        self.next()?;
+        Ok(Some(self.parse_expr(Operator::Comma)?))
+    }
+
+    fn parse_for_stmt(&mut self, location: Location) -> PResult<Stmt> {
+        self.next()?;
+
+        // "for await (x of y)"
+        let is_await = self.fn_flags.is_async && self.lexer.is_contextual_keyword("await");
+        if is_await {
+            self.next()?;
+        }
+
+        self.expect(Token::OpenParen)?;
+        self.push_scope_for_parse_pass(ScopeKind::Block);
+
+        // "in" can't be an operator in the initializer
+        self.allow_in = false;
+        let init_location = self.lexer.start;
+        let init = match self.lexer.token {
+            Token::Semicolon => None,
+            Token::Var => {
+                self.next()?;
+                let decls = self.parse_and_declare_decls(SymbolKind::Hoisted)?;
+                Some(local(decls, LocalKind::Var, false))
+            }
+            Token::Const => {
+                self.next()?;
+                let decls = self.parse_and_declare_decls(SymbolKind::Other)?;
+                Some(local(decls, LocalKind::Const, false))
+            }
+            Token::Let => {
+                self.next()?;
+                if self.is_let_declaration() {
+                    let decls = self.parse_and_declare_decls(SymbolKind::Other)?;
+                    Some(local(decls, LocalKind::Let, false))
+                } else {
+                    let target = self.identifier_expr(init_location, "let");
+                    let value = self.parse_suffix(target, Operator::Lowest)?;
+                    Some(StmtKind::Expr { value })
+                }
+            }
+            _ => {
+                let value = self.parse_expr(Operator::Lowest)?;
+                Some(StmtKind::Expr { value })
+            }
+        };
+        self.allow_in = true;
+        let init = init.map(|data| stmt(init_location, data));
+
+        let is_of = self.lexer.is_contextual_keyword("of");
+        if let Some(init) = init {
+            if is_of || self.lexer.token == Token::In {
+                if let StmtKind::Local { decls, kind, .. } = init.data.as_ref() {
+                    let has_initializer = decls.iter().any(|decl| decl.value.is_some());
+                    if decls.len() != 1 || (has_initializer && (is_of || *kind != LocalKind::Var)) {
+                        self.add_range_error(
+                            init_location..init_location,
+                            format!(
+                                "The for-{} loop variable can't have an initializer",
+                                if is_of { "of" } else { "in" }
+                            ),
+                        );
+                    }
+                }
+
+                self.next()?;
+                let value = if is_of {
+                    self.parse_expr(Operator::Comma)?
+                } else {
+                    self.parse_expr(Operator::Lowest)?
+                };
+                self.expect(Token::CloseParen)?;
+                let body = self.parse_loop_body()?;
+                self.pop_scope();
+
+                let data = if is_of {
+                    StmtKind::ForOf {
+                        is_await,
+                        init,
+                        value,
+                        body,
+                    }
+                } else {
+                    StmtKind::ForIn { init, value, body }
+                };
+                return Ok(stmt(location, data));
+            }
+
+            self.expect(Token::Semicolon)?;
+            return self.parse_for_loop_rest(location, Some(init));
+        }
+
+        self.expect(Token::Semicolon)?;
+        self.parse_for_loop_rest(location, None)
+    }
+
+    fn parse_for_loop_rest(&mut self, location: Location, init: Option<Stmt>) -> PResult<Stmt> {
+        let test = if self.lexer.token != Token::Semicolon {
+            Some(self.parse_expr(Operator::Lowest)?)
+        } else {
+            None
+        };
+        self.expect(Token::Semicolon)?;
+        let update = if self.lexer.token != Token::CloseParen {
+            Some(self.parse_expr(Operator::Lowest)?)
+        } else {
+            None
+        };
+        self.expect(Token::CloseParen)?;
+        let body = self.parse_loop_body()?;
+        self.pop_scope();
+
+        Ok(stmt(
+            location,
+            StmtKind::For {
+                init,
+                test,
+                update,
+                body,
+            },
+        ))
+    }
+
+    fn parse_try_stmt(&mut self, location: Location) -> PResult<Stmt> {
+        self.next()?;
+        self.push_scope_for_parse_pass(ScopeKind::Block);
+        let body = self.parse_block_body()?;
+        self.pop_scope();
+
+        let catch = if self.lexer.token == Token::Catch {
+            let location = self.lexer.start;
+            self.push_scope_for_parse_pass(ScopeKind::Block);
+            self.next()?;
+
+            // The binding is optional: "try {} catch {}"
+            let binding = if self.lexer.token == Token::OpenParen {
+                self.next()?;
+                let mut binding = self.parse_binding()?;
+                self.skip_typescript_colon_type()?;
+                let kind = match binding.data.as_ref() {
+                    BindingKind::Identifier { .. } => SymbolKind::CatchIdentifier,
+                    _ => SymbolKind::Other,
+                };
+                self.declare_binding(kind, &mut binding);
+                self.expect(Token::CloseParen)?;
+                Some(binding)
+            } else {
+                None
+            };
+
+            let body = self.parse_block_body()?;
+            self.pop_scope();
+            Some(Catch {
+                location,
+                binding,
+                body,
+            })
+        } else {
+            None
+        };
+
+        let finally = if catch.is_none() || self.lexer.token == Token::Finally {
+            let location = self.lexer.start;
+            self.expect(Token::Finally)?;
+            self.push_scope_for_parse_pass(ScopeKind::Block);
+            let stmts = self.parse_block_body()?;
+            self.pop_scope();
+            Some(Finally { location, stmts })
+        } else {
+            None
+        };
+
+        Ok(stmt(
+            location,
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            },
+        ))
+    }
+
+    fn parse_fn_stmt(
+        &mut self,
+        location: Location,
+        is_async: bool,
+        is_export: bool,
+        allow_anonymous: bool,
+    ) -> PResult<Stmt> {
+        self.expect(Token::Function)?;
+        let is_generator = self.lexer.token == Token::Asterisk;
+        if is_generator {
+            self.next()?;
+        }
+
+        // An overload in TypeScript has no body and is thrown away along with
+        // what it declared
+        let checkpoint = if self.options.typescript.parse {
+            Some(self.checkpoint())
+        } else {
+            None
+        };
+
+        let name = if self.is_identifier() || !allow_anonymous {
+            let loc = self.lexer.start;
+            let name = self.expect_identifier()?;
+            let reference = self.declare_symbol(SymbolKind::HoistedFunction, loc, &name);
+
+            // Async functions and generators are always scoped to the block
+            let scope = self.current_scope;
+            if !self.scopes[scope].kind.stops_hoisting() && !is_async && !is_generator {
+                self.block_level_functions.push((scope, reference));
+            }
+            Some(LocationRef { loc, reference })
+        } else {
+            None
+        };
+
+        self.push_scope_for_parse_pass(ScopeKind::FunctionArgs);
+        let flags = FnFlags {
+            is_async,
+            is_generator,
+        };
+        let function = match self.parse_fn_or_signature(name, flags)? {
+            Some(function) => function,
+            None => {
+                self.discard_declarations(checkpoint.expect("Only TypeScript has overloads"));
+                self.expect_or_insert_semicolon()?;
+                return Ok(stmt(location, StmtKind::TypeScript));
+            }
+        };
+        if is_export {
+            if let Some(name) = &function.name {
+                let alias = self.symbols[name.reference.inner].name.clone();
+                self.named_exports.insert(alias, name.reference);
+            }
+        }
+        Ok(stmt(
+            location,
+            StmtKind::Function {
+                function,
+                is_export,
+            },
+        ))
+    }
+
+    fn parse_fn_expr(&mut self, location: Location, is_async: bool) -> PResult<Expr> {
+        self.expect(Token::Function)?;
+        let is_generator = self.lexer.token == Token::Asterisk;
+        if is_generator {
+            self.next()?;
+        }
+
+        // The name of a function expression is only visible inside of it
+        self.push_scope_for_parse_pass(ScopeKind::FunctionArgs);
+        let name = if self.is_identifier() {
+            let loc = self.lexer.start;
+            let name = self.expect_identifier()?;
+            Some(LocationRef {
+                loc,
+                reference: self.declare_symbol(SymbolKind::Other, loc, &name),
+            })
+        } else {
+            None
+        };
+
+        let function = self.parse_fn(
+            name,
+            FnFlags {
+                is_async,
+                is_generator,
+            },
+        )?;
+        Ok(expr(location, ExprKind::Function { function }))
+    }
+
+    // The caller has pushed the scope for the arguments, which is popped here
+    fn parse_fn(&mut self, name: Option<LocationRef>, flags: FnFlags) -> PResult<Function> {
+        match self.parse_fn_or_signature(name, flags)? {
+            Some(function) => Ok(function),
+            None => {
+                self.lexer.expected(Token::OpenBrace);
+                Err(SyntaxError)
+            }
+        }
+    }
+
+    // Like "parse_fn", but a TypeScript function without a body, such as an
+    // overload or an abstract method, returns None. The caller has to throw
+    // away what it declared.
+    fn parse_fn_or_signature(
+        &mut self,
+        name: Option<LocationRef>,
+        flags: FnFlags,
+    ) -> PResult<Option<Function>> {
+        let old_flags = std::mem::replace(&mut self.fn_flags, flags);
+        let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+        if self.options.typescript.parse {
+            self.skip_typescript_type_parameters()?;
+        }
+        let (args, has_rest_arg) = self.parse_fn_args()?;
+        self.skip_typescript_colon_type()?;
+        let body = if self.options.typescript.parse && self.lexer.token != Token::OpenBrace {
+            None
+        } else {
+            Some(self.parse_fn_body()?)
+        };
+        self.pop_scope();
+
+        self.fn_flags = old_flags;
+        self.allow_in = old_allow_in;
+        Ok(body.map(|body| Function {
+            name,
+            args,
+            is_async: flags.is_async,
+            is_generator: flags.is_generator,
+            has_rest_arg,
+            body,
+        }))
+    }
+
+    // Parses the arguments in parentheses of a function or an arrow function
+    // and declares them in the current scope. Returns whether the last one is
+    // a rest argument.
+    fn parse_fn_args(&mut self) -> PResult<(Vec<Arg>, bool)> {
+        let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+        self.expect(Token::OpenParen)?;
+        let mut args = vec![];
+        let mut has_rest_arg = false;
+        while self.lexer.token != Token::CloseParen {
+            let is_typescript_ctor_field = if self.options.typescript.parse {
+                // "this: T" only gives the type of "this"
+                if self.lexer.token == Token::This {
+                    self.next()?;
+                    self.skip_typescript_colon_type()?;
+                    if self.lexer.token != Token::Comma {
+                        break;
+                    }
+                    self.next()?;
+                    continue;
+                }
+                self.skip_typescript_arg_modifiers()?
+            } else {
+                false
+            };
+
+            if self.lexer.token == Token::DotDotDot {
+                self.next()?;
+                has_rest_arg = true;
+            }
+
+            let mut binding = self.parse_binding()?;
+            self.declare_binding(SymbolKind::Hoisted, &mut binding);
+            if self.options.typescript.parse && self.lexer.token == Token::Question {
+                self.next()?;
+            }
+            self.skip_typescript_colon_type()?;
+            let default_ = if has_rest_arg {
+                None
+            } else {
+                self.nested(Self::parse_default_value)?
+            };
+            args.push(Arg {
+                is_typescript_ctor_field,
+                binding,
+                default_,
+            });
+
+            // The rest argument must be last
+            if has_rest_arg || self.lexer.token != Token::Comma {
+                break;
+            }
+            self.next()?;
+        }
+        self.expect(Token::CloseParen)?;
+        self.allow_in = old_allow_in;
+        Ok((args, has_rest_arg))
+    }
+
+    // "constructor(private readonly a: A)". Returns whether there were any
+    // modifiers, which make the argument a field of the class too.
+    fn skip_typescript_arg_modifiers(&mut self) -> PResult<bool> {
+        let mut has_modifiers = false;
+        while matches!(
+            self.lexer.token,
+            Token::Public | Token::Private | Token::Protected
+        ) || self.lexer.is_contextual_keyword("readonly")
+            || self.lexer.is_contextual_keyword("override")
+        {
+            let is_modifier = self.next_token_is(|p| {
+                p.is_identifier() || matches!(p.lexer.token, Token::OpenBrace | Token::OpenBracket)
+            });
+            if !is_modifier {
+                break;
+            }
+            self.next()?;
+            has_modifiers = true;
+        }
+        Ok(has_modifiers)
+    }
+
+    fn parse_fn_body(&mut self) -> PResult<FunctionBody> {
+        self.parse_body(false)
+    }
+
+    fn parse_body(&mut self, is_static_block: bool) -> PResult<FunctionBody> {
+        let location = self.lexer.start;
+        let was_inside_function = std::mem::replace(&mut self.is_inside_function, true);
+        let was_inside_static_block =
+            std::mem::replace(&mut self.is_inside_static_block, is_static_block);
+        let was_inside_loop = std::mem::replace(&mut self.is_inside_loop, false);
+        let was_inside_switch = std::mem::replace(&mut self.is_inside_switch, false);
+        self.expect(Token::OpenBrace)?;
+        self.push_scope_for_parse_pass(ScopeKind::FunctionBody);
+        let stmts = self.parse_stmts_up_to(Token::CloseBrace, true)?;
+        self.pop_scope();
+        self.next()?;
+        self.is_inside_function = was_inside_function;
+        self.is_inside_static_block = was_inside_static_block;
+        self.is_inside_loop = was_inside_loop;
+        self.is_inside_switch = was_inside_switch;
+        Ok(FunctionBody { location, stmts })
+    }
+
+    fn parse_loop_body(&mut self) -> PResult<Stmt> {
+        let was_inside_loop = std::mem::replace(&mut self.is_inside_loop, true);
+        let body = self.parse_stmt();
+        self.is_inside_loop = was_inside_loop;
+        body
+    }
+
+    // The caller has pushed the scope for the arguments, which is popped here
+    fn parse_arrow_body(
+        &mut self,
+        location: Location,
+        args: Vec<Arg>,
+        has_rest_arg: bool,
+        is_async: bool,
+    ) -> PResult<Expr> {
+        if self.lexer.has_newline_before {
+            self.add_range_error(
+                self.lexer.range(),
+                "Unexpected newline before \"=>\"".to_owned(),
+            );
+            return Err(SyntaxError);
+        }
+        self.expect(Token::EqualsGreaterThan)?;
+
+        let old_flags = std::mem::replace(
+            &mut self.fn_flags,
+            FnFlags {
+                is_async,
+                is_generator: false,
+            },
+        );
+        let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+
+        let (body, prefer_expr) = if self.lexer.token == Token::OpenBrace {
+            (self.parse_fn_body()?, false)
+        } else {
+            // "() => x" is the same as "() => { return x }"
+            let body_location = self.lexer.start;
+            let was_inside_function = std::mem::replace(&mut self.is_inside_function, true);
+            self.push_scope_for_parse_pass(ScopeKind::FunctionBody);
+            let value = self.parse_nested_expr(Operator::Comma)?;
+            self.pop_scope();
+            self.is_inside_function = was_inside_function;
+            let body = FunctionBody {
+                location: body_location,
+                stmts: vec![stmt(body_location, StmtKind::Return { value: Some(value) })],
+            };
+            (body, true)
+        };
+        self.pop_scope();
+
+        self.fn_flags = old_flags;
+        self.allow_in = old_allow_in;
+        Ok(expr(
+            location,
+            ExprKind::Arrow {
+                is_async,
+                args,
+                has_rest_arg,
+                is_parenthesized: false,
+                prefer_expr,
+                body,
+            },
+        ))
+    }
+
+    fn parse_class_stmt(
+        &mut self,
+        location: Location,
+        is_export: bool,
+        allow_anonymous: bool,
+    ) -> PResult<Stmt> {
+        self.expect(Token::Class)?;
+
+        let name = if (self.is_identifier() && !self.lexer.is_contextual_keyword("extends"))
+            || !allow_anonymous
+        {
+            let loc = self.lexer.start;
+            let name = self.expect_identifier()?;
+            let reference = self.declare_symbol(SymbolKind::Class, loc, &name);
+            if is_export {
+                self.named_exports.insert(name, reference);
+            }
+            Some(LocationRef { loc, reference })
+        } else {
+            None
+        };
+
+        self.push_scope_for_parse_pass(ScopeKind::ClassName);
+        let class = self.parse_class(name)?;
+        Ok(stmt(location, StmtKind::Class { class, is_export }))
+    }
+
+    fn parse_class_expr(&mut self, location: Location) -> PResult<Expr> {
+        self.expect(Token::Class)?;
+
+        // The name of a class expression is only visible inside of it
+        self.push_scope_for_parse_pass(ScopeKind::ClassName);
+        let name = if self.is_identifier() {
+            let loc = self.lexer.start;
+            let name = self.expect_identifier()?;
+            Some(LocationRef {
+                loc,
+                reference: self.declare_symbol(SymbolKind::Class, loc, &name),
+            })
+        } else {
+            None
+        };
+
+        let class = self.parse_class(name)?;
+        Ok(expr(location, ExprKind::Class { class }))
+    }
+
+    // The caller has pushed the class name scope, which is popped here
+    fn parse_class(&mut self, name: Option<LocationRef>) -> PResult<Class> {
+        let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+        if self.options.typescript.parse {
+            self.skip_typescript_type_parameters()?;
+        }
+
+        let extends = if self.lexer.token == Token::Extends {
+            self.next()?;
+            let extends = self.parse_nested_expr(Operator::New)?;
+
+            // "class A extends B<T> {}"
+            if self.options.typescript.parse {
+                self.skip_typescript_type_arguments()?;
+            }
+            Some(extends)
+        } else {
+            None
+        };
+
+        // "class A implements B, C {}"
+        if self.options.typescript.parse && self.lexer.token == Token::Implements {
+            self.next()?;
+            loop {
+                self.skip_typescript_type(Operator::Lowest)?;
+                if self.lexer.token != Token::Comma {
+                    break;
+                }
+                self.next()?;
+            }
+        }
+
+        self.expect(Token::OpenBrace)?;
+        let mut properties = vec![];
+        while self.lexer.token != Token::CloseBrace {
+            if self.lexer.token == Token::Semicolon {
+                self.next()?;
+                continue;
+            }
+            if let Some(property) = self.nested(|p| p.parse_property(true))? {
+                properties.push(property);
+            }
+        }
+        self.next()?;
+        self.pop_scope();
+
+        self.allow_in = old_allow_in;
+        Ok(Class {
+            name,
+            extends,
+            properties,
+        })
+    }
+
+    // "static { ... }", where the caller has consumed "static"
+    fn parse_class_static_block(&mut self, location: Location) -> PResult<Property> {
+        let old_flags = std::mem::take(&mut self.fn_flags);
+        let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+        self.push_scope_for_parse_pass(ScopeKind::FunctionArgs);
+        let body = self.parse_body(true)?;
+        self.pop_scope();
+        self.fn_flags = old_flags;
+        self.allow_in = old_allow_in;
+
+        let function = Function {
+            name: None,
+            args: vec![],
+            is_async: false,
+            is_generator: false,
+            has_rest_arg: false,
+            body,
+        };
+        Ok(Property {
+            kind: PropertyKind::PropertyStaticBlock,
+            is_computed: false,
+            is_method: false,
+            is_static: true,
+            key: expr(location, ExprKind::Missing),
+            prefer_quoted_key: false,
+            value: Some(expr(location, ExprKind::Function { function })),
+            initializer: None,
+        })
+    }
+
+    // The name is looked up when the tree is visited since a private name can
+    // be used before the member that declares it
+    fn parse_private_identifier(&mut self) -> PResult<Expr> {
+        let location = self.lexer.start;
+        let name = std::mem::take(&mut self.lexer.identifier);
+        self.next()?;
+        Ok(expr(
+            location,
+            ExprKind::PrivateIdentifier {
+                reference: self.store_name_in_ref(name),
+            },
+        ))
+    }
+
+    // Private names are declared in the scope of the class body. Only a getter
+    // and a setter may share a name.
+    fn declare_private_name(&mut self, key: &mut Expr, kind: SymbolKind) {
+        let reference = match key.data.as_mut() {
+            ExprKind::PrivateIdentifier { reference } => reference,
+            _ => return,
+        };
+        let name = match self.load_name_from_ref(*reference) {
+            Some(name) => name.to_owned(),
+            None => return,
+        };
+        let scope = self.current_scope;
+        if let Some(&existing) = self.scopes[scope].members.get(&name) {
+            let existing_kind = self.symbols[existing.inner].kind;
+            if matches!(
+                (existing_kind, kind),
+                (SymbolKind::PrivateGet, SymbolKind::PrivateSet)
+                    | (SymbolKind::PrivateSet, SymbolKind::PrivateGet)
+            ) {
+                self.symbols[existing.inner].kind = SymbolKind::PrivateGetSetPair;
+            } else {
+                self.already_declared(key.location, &name, existing);
+            }
+            *reference = existing;
+            return;
+        }
+
+        // Nothing outside of the class can see the name, so it never collides
+        // with another one
+        *reference = self.declare_symbol(kind, key.location, &name);
+        self.symbols[reference.inner].must_not_be_renamed = true;
+    }
+
+    // Parses a property of an object literal or a member of a class body.
+    // Returns None for a class member that only exists in TypeScript, like an
+    // index signature or an abstract method.
+    fn parse_property(&mut self, is_class: bool) -> PResult<Option<Property>> {
+        let mut kind = PropertyKind::PropertyNormal;
+        let mut is_static = false;
+        let mut flags = FnFlags::default();
+
+        // Members that are "declare" or "abstract" only describe types. The
+        // checkpoint is for throwing away a method without a body.
+        let is_typescript_class = is_class && self.options.typescript.parse;
+        let mut is_type_only = false;
+        let checkpoint = if is_typescript_class {
+            Some(self.checkpoint())
+        } else {
+            None
+        };
+
+        if !is_class && self.lexer.token == Token::DotDotDot {
+            let location = self.lexer.start;
+            self.next()?;
+            let value = self.parse_expr(Operator::Comma)?;
+            return Ok(Some(Property {
+                kind: PropertyKind::PropertySpread,
+                is_computed: false,
+                is_method: false,
+                is_static: false,
+                key: expr(location, ExprKind::Missing),
+                prefer_quoted_key: false,
+                value: Some(value),
+                initializer: None,
+            }));
+        }
+
+        // Modifiers such as "static", "get", and "async" are also valid names,
+        // so they are only modifiers if another name follows them
+        let (mut key, is_computed, prefer_quoted_key, shorthand) = loop {
+            let location = self.lexer.start;
+
+            if self.lexer.token == Token::Asterisk && !flags.is_generator {
+                self.next()?;
+                flags.is_generator = true;
+                continue;
+            }
+
+            match self.lexer.token {
+                Token::StringLiteral => {
+                    let value = std::mem::take(&mut self.lexer.string_literal);
+                    self.next()?;
+                    break (
+                        expr(location, ExprKind::String { value }),
+                        false,
+                        true,
+                        None,
+                    );
+                }
+                Token::NumericLiteral => {
+                    self.check_legacy_octal_literal()?;
+                    let value = self.lexer.number;
+                    self.next()?;
+                    break (
+                        expr(location, ExprKind::Number { value }),
+                        false,
+                        false,
+                        None,
+                    );
+                }
+                Token::BigIntegerLiteral => {
+                    let value = std::mem::take(&mut self.lexer.identifier);
+                    self.next()?;
+                    break (
+                        expr(location, ExprKind::BigInt { value }),
+                        false,
+                        false,
+                        None,
+                    );
+                }
+                Token::OpenBracket => {
+                    // "[key: string]: T" is an index signature
+                    let is_index_signature = is_typescript_class
+                        && self.try_skip_typescript(|p| {
+                            p.next()?;
+                            p.skip_typescript_identifier()?;
+                            Ok(p.lexer.token == Token::Colon)
+                        });
+                    if is_index_signature {
+                        self.skip_typescript_colon_type()?;
+                        self.expect(Token::CloseBracket)?;
+                        self.skip_typescript_colon_type()?;
+                        self.expect_or_insert_semicolon()?;
+                        return Ok(None);
+                    }
+
+                    self.next()?;
+                    let key = self.parse_expr(Operator::Comma)?;
+                    self.expect(Token::CloseBracket)?;
+                    break (key, true, false, None);
+                }
+                Token::PrivateIdentifier if is_class => {
+                    if self.lexer.identifier == "#constructor" {
+                        self.add_range_error(
+                            self.lexer.range(),
+                            "Invalid field name \"#constructor\"".to_owned(),
+                        );
+                    }
+                    break (self.parse_private_identifier()?, false, false, None);
+                }
+                _ if self.is_identifier_or_keyword() => {
+                    let name = self.lexer.identifier.clone();
+                    let is_identifier = self.is_identifier();
+                    let is_modifier_position = kind == PropertyKind::PropertyNormal
+                        && !flags.is_async
+                        && !flags.is_generator;
+                    self.next()?;
+
+                    let is_typescript_suffix = self.options.typescript.parse
+                        && matches!(
+                            self.lexer.token,
+                            Token::Question | Token::Exclamation | Token::LessThan
+                        );
+                    let is_followed_by_name = !is_typescript_suffix
+                        && !matches!(
+                            self.lexer.token,
+                            Token::OpenParen
+                                | Token::Colon
+                                | Token::Comma
+                                | Token::CloseBrace
+                                | Token::Equals
+                                | Token::Semicolon
+                        );
+                    if is_followed_by_name && is_modifier_position {
+                        match name.as_str() {
+                            "static" if is_class && !is_static => {
+                                if self.lexer.token == Token::OpenBrace {
+                                    return self.parse_class_static_block(location).map(Some);
+                                }
+                                is_static = true;
+                                continue;
+                            }
+                            "public" | "private" | "protected" | "readonly" | "override"
+                                if is_typescript_class =>
+                            {
+                                continue;
+                            }
+                            "declare" | "abstract" if is_typescript_class => {
+                                is_type_only = true;
+                                continue;
+                            }
+                            "get" => {
+                                kind = PropertyKind::PropertyGet;
+                                continue;
+                            }
+                            "set" => {
+                                kind = PropertyKind::PropertySet;
+                                continue;
+                            }
+                            "async" if !self.lexer.has_newline_before => {
+                                flags.is_async = true;
+                                continue;
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    let key = expr(
+                        location,
+                        ExprKind::String {
+                            value: string_to_utf16(&name),
+                        },
+                    );
+                    let shorthand = if is_identifier { Some(name) } else { None };
+                    break (key, false, false, shorthand);
+                }
+                _ => return self.unexpected(),
+            }
+        };
+
+        // "a?: T", "a!: T", and "a?(): T"
+        if is_typescript_class && matches!(self.lexer.token, Token::Question | Token::Exclamation) {
+            self.next()?;
+        }
+
+        // Methods, which may have type parameters in TypeScript
+        if self.lexer.token == Token::OpenParen
+            || (self.options.typescript.parse && self.lexer.token == Token::LessThan)
+        {
+            let private_kind = match kind {
+                PropertyKind::PropertyGet => SymbolKind::PrivateGet,
+                PropertyKind::PropertySet => SymbolKind::PrivateSet,
+                _ => SymbolKind::PrivateMethod,
+            };
+            self.declare_private_name(&mut key, private_kind);
+            let location = self.lexer.start;
+            self.push_scope_for_parse_pass(ScopeKind::FunctionArgs);
+            let mut function = match (self.parse_fn_or_signature(None, flags)?, checkpoint) {
+                (Some(function), _) => function,
+                (None, Some(checkpoint)) => {
+                    self.discard_declarations(checkpoint);
+                    self.expect_or_insert_semicolon()?;
+                    return Ok(None);
+                }
+                (None, None) => {
+                    self.lexer.expected(Token::OpenBrace);
+                    return Err(SyntaxError);
+                }
+            };
+            let is_constructor = matches!(
+                key.data.as_ref(),
+                ExprKind::String { value } if utf16_equals_string(value, "constructor")
+            );
+            if is_typescript_class && is_constructor && !is_static && !is_computed {
+                self.add_typescript_ctor_field_assignments(&mut function);
+            }
+            return Ok(Some(Property {
+                kind,
+                is_computed,
+                is_method: true,
+                is_static,
+                key,
+                prefer_quoted_key,
+                value: Some(expr(location, ExprKind::Function { function })),
+                initializer: None,
+            }));
+        }
+
+        if kind != PropertyKind::PropertyNormal || flags.is_async || flags.is_generator {
+            self.lexer.expected(Token::OpenParen);
+            return Err(SyntaxError);
+        }
+
+        // Class fields
+        if is_class {
+            self.skip_typescript_colon_type()?;
+            if is_type_only {
+                self.expect_or_insert_semicolon()?;
+                return Ok(None);
+            }
+            self.declare_private_name(&mut key, SymbolKind::PrivateField);
+            let initializer = self.parse_default_value()?;
+            self.expect_or_insert_semicolon()?;
+            return Ok(Some(Property {
+                kind,
+                is_computed,
+                is_method: false,
+                is_static,
+                key,
+                prefer_quoted_key,
+                value: None,
+                initializer,
+            }));
+        }
+
+        // "{a: b}"
+        if self.lexer.token == Token::Colon {
+            self.next()?;
+            let value = self.parse_expr(Operator::Comma)?;
+            return Ok(Some(Property {
+                kind,
+                is_computed,
+                is_method: false,
+                is_static,
+                key,
+                prefer_quoted_key,
+                value: Some(value),
+                initializer: None,
+            }));
+        }
+
+        // "{a}" and "{a = b}", where the initializer is only valid if the
+        // object turns out to be a destructuring pattern
+        match shorthand {
+            Some(name) => {
+                let value = self.identifier_expr(key.location, &name);
+                let initializer = self.parse_default_value()?;
+                Ok(Some(Property {
+                    kind,
+                    is_computed,
+                    is_method: false,
+                    is_static,
+                    key,
+                    prefer_quoted_key,
+                    value: Some(value),
+                    initializer,
+                }))
+            }
+            None => {
+                self.lexer.expected(Token::Colon);
+                Err(SyntaxError)
+            }
+        }
+    }
+
+    fn parse_import_stmt(&mut self) -> PResult<StmtKind> {
+        self.has_es6_imports = true;
+
+        // "import type A from 'path'" only imports types. Without a name after
+        // it, "type" is the name of a default import.
+        let is_type_only = self.options.typescript.parse
+            && self.lexer.is_contextual_keyword("type")
+            && self.next_token_is(|p| {
+                matches!(p.lexer.token, Token::OpenBrace | Token::Asterisk)
+                    || p.is_identifier() && !p.lexer.is_contextual_keyword("from")
+            });
+        if is_type_only {
+            let checkpoint = self.checkpoint();
+            self.next()?;
+            self.parse_import_stmt()?;
+            self.discard_declarations(checkpoint);
+            return Ok(StmtKind::TypeScript);
+        }
+
+        let mut default_name = None;
+        let mut namespace_symbol = NamespaceSymbol::Clause { items: vec![] };
+        let kind = self.import_symbol_kind();
+
+        if self.lexer.token != Token::StringLiteral {
+            if self.is_identifier() {
+                let loc = self.lexer.start;
+                let name = self.expect_identifier()?;
+                let reference = self.declare_symbol(kind, loc, &name);
+                default_name = Some(LocationRef { loc, reference });
+
+                if self.lexer.token == Token::Comma {
+                    self.next()?;
+                } else {
+                    self.expect_contextual_keyword("from")?;
+                }
+            }
+
+            if self.lexer.token == Token::Asterisk {
+                let location = self.lexer.start;
+                self.next()?;
+                self.expect_contextual_keyword("as")?;
+                let loc = self.lexer.start;
+                let name = self.expect_identifier()?;
+                namespace_symbol = NamespaceSymbol::Star {
+                    location,
+                    namespace_ref: self.declare_symbol(kind, loc, &name),
+                };
+                self.expect_contextual_keyword("from")?;
+            } else if self.lexer.token == Token::OpenBrace {
+                let items = self.parse_clause(true)?;
+                namespace_symbol = NamespaceSymbol::Clause { items };
+                self.expect_contextual_keyword("from")?;
+            } else if default_name.is_none() {
+                return self.unexpected();
+            }
+        }
+
+        let path = self.parse_path()?;
+        self.expect_or_insert_semicolon()?;
+
+        // Every imported name refers to a property of the module's namespace
+        let namespace_ref = match &namespace_symbol {
+            NamespaceSymbol::Star { namespace_ref, .. } => *namespace_ref,
+            NamespaceSymbol::Clause { .. } => {
+                let name = format!("import_{}", generate_non_unique_name_from_path(&path.text));
+                self.generate_symbol(SymbolKind::Other, &name)
+            }
+        };
+        let add_named_import = |p: &mut Self, alias: &str, alias_loc, reference| {
+            p.import_items.insert(reference);
+            p.named_imports.insert(
+                reference,
+                NamedImport {
+                    alias: alias.to_owned(),
+                    alias_loc,
+                    import_path: path.clone(),
+                    namespace_ref,
+                    local_parts_with_uses: vec![],
+                    is_exported: false,
+                },
+            );
+        };
+        if let Some(name) = &default_name {
+            add_named_import(self, "default", name.loc, name.reference);
+        }
+        if let NamespaceSymbol::Clause { items } = &namespace_symbol {
+            for item in items {
+                add_named_import(self, &item.alias, item.alias_location, item.name.reference);
+            }
+        }
+
+        Ok(StmtKind::Import {
+            namespace_symbol,
+            default_name,
+            path,
+        })
+    }
+
+    // Imported names in TypeScript may turn out to be types, which another
+    // declaration with the same name is allowed to replace
+    fn import_symbol_kind(&self) -> SymbolKind {
+        if self.options.typescript.parse {
+            SymbolKind::TSImport
+        } else {
+            SymbolKind::Other
+        }
+    }
+
+    // "{a, b as c}" in an import or export clause. The local names of an
+    // import clause are declared. The local names of an export clause are
+    // bound in the second pass since they may be declared later in the file.
+    fn parse_clause(&mut self, is_import: bool) -> PResult<Vec<ClauseItem>> {
+        self.expect(Token::OpenBrace)?;
+        let mut items = vec![];
+
+        while self.lexer.token != Token::CloseBrace {
+            if self.skip_typescript_type_clause_item()? {
+                if self.lexer.token != Token::Comma {
+                    break;
+                }
+                self.next()?;
+                continue;
+            }
+            if !self.is_identifier_or_keyword() {
+                return self.unexpected();
+            }
+            let alias_location = self.lexer.start;
+            let mut alias = self.lexer.identifier.clone();
+            let mut name_location = alias_location;
+            let mut name = alias.clone();
+            let mut is_identifier = self.is_identifier();
+            self.next()?;
+
+            if self.lexer.is_contextual_keyword("as") {
+                self.next()?;
+                if !self.is_identifier_or_keyword() {
+                    return self.unexpected();
+                }
+                if is_import {
+                    // "import {a as b}" declares "b"
+                    name_location = self.lexer.start;
+                    name = self.lexer.identifier.clone();
+                    is_identifier = self.is_identifier();
+                } else {
+                    // "export {a as b}" exports "a" under the name "b"
+                    alias = self.lexer.identifier.clone();
+                }
+                self.next()?;
+            }
+
+            let reference = if is_import {
+                if !is_identifier {
+                    self.add_range_error(
+                        name_location..name_location + name.len(),
+                        format!("Expected identifier but found \"{}\"", name),
+                    );
+                    return Err(SyntaxError);
+                }
+                let kind = self.import_symbol_kind();
+                self.declare_symbol(kind, name_location, &name)
+            } else {
+                self.store_name_in_ref(name)
+            };
+            items.push(ClauseItem {
+                alias,
+                alias_location,
+                name: LocationRef {
+                    loc: name_location,
+                    reference,
+                },
+            });
+
+            if self.lexer.token != Token::Comma {
+                break;
+            }
+            self.next()?;
+        }
+
+        self.expect(Token::CloseBrace)?;
+        Ok(items)
+    }
+
+    fn parse_path(&mut self) -> PResult<Path> {
+        if self.lexer.token != Token::StringLiteral {
+            self.lexer.expected(Token::StringLiteral);
+            return Err(SyntaxError);
+        }
+        let path = Path {
+            loc: self.lexer.start,
+            text: utf16_to_string_lossy(&self.lexer.string_literal),
+            use_source_index: false,
+            source_index: 0,
+        };
+        self.next()?;
+        Ok(path)
+    }
+
+    fn parse_export_stmt(&mut self, location: Location) -> PResult<Stmt> {
+        self.has_es6_exports = true;
+
+        let data = match self.lexer.token {
+            Token::Var | Token::Let | Token::Const => {
+                let kind = match self.lexer.token {
+                    Token::Var => LocalKind::Var,
+                    Token::Let => LocalKind::Let,
+                    _ => LocalKind::Const,
+                };
+                self.next()?;
+                let symbol_kind = if kind == LocalKind::Var {
+                    SymbolKind::Hoisted
+                } else {
+                    SymbolKind::Other
+                };
+                let decls = self.parse_and_declare_decls(symbol_kind)?;
+                if kind == LocalKind::Const {
+                    self.require_initializers(&decls);
+                }
+                self.expect_or_insert_semicolon()?;
+                for decl in &decls {
+                    self.export_binding(&decl.binding);
+                }
+                local(decls, kind, true)
+            }
+
+            Token::Function => return self.parse_fn_stmt(location, false, true, false),
+
+            Token::Class => return self.parse_class_stmt(location, true, false),
+
+            Token::Default => {
+                let default_location = self.lexer.start;
+                self.next()?;
+
+                // "export default interface A {}"
+                if self.options.typescript.parse && self.lexer.token == Token::Interface {
+                    if let Some(stmt) = self.parse_typescript_stmt(location, false)? {
+                        return Ok(stmt);
+                    }
+                }
+                let value = self.parse_export_default_value()?;
+                let default_name = match &value {
+                    ExprOrStmt::Stmt(stmt) => match stmt.data.as_ref() {
+                        StmtKind::Function {
+                            function:
+                                Function {
+                                    name: Some(name), ..
+                                },
+                            ..
+                        }
+                        | StmtKind::Class {
+                            class:
+                                Class {
+                                    name: Some(name), ..
+                                },
+                            ..
+                        } => Some(name.clone()),
+                        _ => None,
+                    },
+                    ExprOrStmt::Expr(_) => None,
+                };
+                let default_name = default_name.unwrap_or_else(|| {
+                    let name = format!(
+                        "{}_default",
+                        generate_non_unique_name_from_path(&self.source.pretty_path)
+                    );
+                    LocationRef {
+                        loc: default_location,
+                        reference: self.generate_symbol(SymbolKind::Other, &name),
+                    }
+                });
+                self.named_exports
+                    .insert("default".to_owned(), default_name.reference);
+                StmtKind::ExportDefault {
+                    default_name,
+                    value,
+                }
+            }
+
+            Token::Asterisk => {
+                self.next()?;
+                let item = if self.lexer.is_contextual_keyword("as") {
+                    // "export * as ns from 'path'"
+                    self.next()?;
+                    if !self.is_identifier_or_keyword() {
+                        return self.unexpected();
+                    }
+                    let alias_location = self.lexer.start;
+                    let alias = self.lexer.identifier.clone();
+                    self.next()?;
+                    let reference = self.generate_symbol(SymbolKind::Other, &alias);
+                    self.named_exports.insert(alias.clone(), reference);
+                    Some(ClauseItem {
+                        alias,
+                        alias_location,
+                        name: LocationRef {
+                            loc: alias_location,
+                            reference,
+                        },
+                    })
+                } else {
+                    None
+                };
+                self.expect_contextual_keyword("from")?;
+                let path = self.parse_path()?;
+                self.expect_or_insert_semicolon()?;
+                if item.is_none() {
+                    self.export_stars.push(path.clone());
+                }
+                StmtKind::ExportStar { item, path }
+            }
+
+            Token::OpenBrace => {
+                let mut items = self.parse_clause(false)?;
+
+                if self.lexer.is_contextual_keyword("from") {
+                    // "export {a as b} from 'path'" imports "a" and exports it
+                    // as "b" without declaring anything in this file
+                    self.next()?;
+                    let path = self.parse_path()?;
+                    self.expect_or_insert_semicolon()?;
+                    let name = format!("import_{}", generate_non_unique_name_from_path(&path.text));
+                    let namespace = self.generate_symbol(SymbolKind::Other, &name);
+                    for item in &mut items {
+                        let name = self
+                            .load_name_from_ref(item.name.reference)
+                            .unwrap_or_default()
+                            .to_owned();
+                        let reference = self.generate_symbol(SymbolKind::Other, &name);
+                        item.name.reference = reference;
+                        self.import_items.insert(reference);
+                        self.named_imports.insert(
+                            reference,
+                            NamedImport {
+                                alias: name,
+                                alias_loc: item.name.loc,
+                                import_path: path.clone(),
+                                namespace_ref: namespace,
+                                local_parts_with_uses: vec![],
+                                is_exported: true,
+                            },
+                        );
+                        self.named_exports.insert(item.alias.clone(), reference);
+                    }
+                    StmtKind::ExportFrom {
+                        items,
+                        namespace,
+                        path,
+                    }
+                } else {
+                    self.expect_or_insert_semicolon()?;
+                    StmtKind::ExportClause { items }
+                }
+            }
+
+            // "export import a = b.c"
+            Token::Import if self.options.typescript.parse => {
+                self.next()?;
+                self.parse_typescript_import_equals(true)?
+            }
+
+            _ => {
+                if self.options.typescript.parse {
+                    if let Some(stmt) = self.parse_typescript_export_stmt(location)? {
+                        return Ok(stmt);
+                    }
+                }
+                if self.lexer.is_contextual_keyword("async") {
+                    let async_location = self.lexer.start;
+                    self.next()?;
+                    if self.lexer.token == Token::Function && !self.lexer.has_newline_before {
+                        return self.parse_fn_stmt(async_location, true, true, false);
+                    }
+                    self.lexer.expected(Token::Function);
+                    return Err(SyntaxError);
+                }
+                return self.unexpected();
+            }
+        };
+
+        Ok(stmt(location, data))
+    }
+
+    // Called after "export" for the statements that only exist in TypeScript
+    fn parse_typescript_export_stmt(&mut self, location: Location) -> PResult<Option<Stmt>> {
+        // "export type {A}" and "export type * from 'path'" only export types
+        let is_type_only = self.lexer.is_contextual_keyword("type")
+            && self.next_token_is(|p| matches!(p.lexer.token, Token::OpenBrace | Token::Asterisk));
+        if is_type_only {
+            let checkpoint = self.checkpoint();
+            let export_star_count = self.export_stars.len();
+            self.next()?;
+            self.parse_export_stmt(location)?;
+            self.export_stars.truncate(export_star_count);
+            self.discard_declarations(checkpoint);
+            return Ok(Some(stmt(location, StmtKind::TypeScript)));
+        }
+
+        // "export as namespace A" is for scripts that use the module as a
+        // global variable
+        if self.lexer.is_contextual_keyword("as") {
+            self.next()?;
+            self.expect_contextual_keyword("namespace")?;
+            self.skip_typescript_identifier()?;
+            self.expect_or_insert_semicolon()?;
+            return Ok(Some(stmt(location, StmtKind::TypeScript)));
+        }
+
+        self.parse_typescript_stmt(location, true)
+    }
+
+    // Function and class declarations can be anonymous after "export default"
+    fn parse_export_default_value(&mut self) -> PResult<ExprOrStmt> {
+        let location = self.lexer.start;
+        let is_abstract_class = self.options.typescript.parse
+            && self.lexer.is_contextual_keyword("abstract")
+            && self.next_token_is(|p| p.lexer.token == Token::Class && !p.lexer.has_newline_before);
+        if is_abstract_class {
+            self.next()?;
+        }
+        match self.lexer.token {
+            Token::Function => {
+                return Ok(ExprOrStmt::Stmt(
+                    self.parse_fn_stmt(location, false, false, true)?,
+                ))
+            }
+            Token::Class => {
+                return Ok(ExprOrStmt::Stmt(
+                    self.parse_class_stmt(location, false, true)?,
+                ))
+            }
+            _ => {}
+        }
+
+        let value = if self.lexer.is_contextual_keyword("async") {
+            let async_range = self.lexer.range();
+            self.next()?;
+            if self.lexer.token == Token::Function && !self.lexer.has_newline_before {
+                return Ok(ExprOrStmt::Stmt(
+                    self.parse_fn_stmt(location, true, false, true)?,
+                ));
+            }
+            let target = self.parse_async_prefix_expr(async_range)?;
+            self.parse_suffix(target, Operator::Comma)?
+        } else {
+            self.parse_expr(Operator::Comma)?
+        };
+        self.expect_or_insert_semicolon()?;
+        Ok(ExprOrStmt::Expr(value))
+    }
+
+    fn export_binding(&mut self, binding: &Binding) {
+        ensure_sufficient_stack(|| self.export_binding_inner(binding))
+    }
+
+    fn export_binding_inner(&mut self, binding: &Binding) {
+        match binding.data.as_ref() {
+            BindingKind::Missing => {}
+            BindingKind::Identifier { reference } => {
+                let name = self.symbols[reference.inner].name.clone();
+                self.named_exports.insert(name, *reference);
+            }
+            BindingKind::Array { items, .. } => {
+                for item in items {
+                    self.export_binding(&item.binding);
+                }
+            }
+            BindingKind::Object { properties } => {
+                for property in properties {
+                    self.export_binding(&property.value);
+                }
+            }
+        }
+    }
+
+    fn identifier_expr(&mut self, location: Location, name: &str) -> Expr {
+        let reference = self.store_name_in_ref(name.to_owned());
+        expr(location, ExprKind::Identifier { reference })
+    }
+
+    fn parse_expr(&mut self, level: Operator) -> PResult<Expr> {
+        ensure_sufficient_stack(|| self.parse_expr_inner(level))
+    }
+
+    // Parses an expression inside brackets, or anything else that nests the
+    // way they do. Operators don't count, since an operand is only nested as
+    // deep as the chain of operators that it's in (see "parse_suffix").
+    fn parse_nested_expr(&mut self, level: Operator) -> PResult<Expr> {
+        self.nested(|p| p.parse_expr_inner(level))
+    }
+
+    fn parse_expr_inner(&mut self, level: Operator) -> PResult<Expr> {
+        let has_pure_comment_before = self.lexer.has_pure_comment_before;
+        let mut left = self.parse_prefix(level)?;
+
+        // There's no specification for "__PURE__" comments, but other tools
+        // apply them to the call or "new" expression that starts right after
+        // the comment. In "/* @__PURE__ */ a().b() + c()" that's "a().b()".
+        if has_pure_comment_before && level < Operator::Call {
+            left = self.parse_suffix(left, Operator::New)?;
+            match left.data.as_mut() {
+                ExprKind::Call {
+                    can_be_unwrapped_if_unused,
+                    ..
+                }
+                | ExprKind::New {
+                    can_be_unwrapped_if_unused,
+                    ..
+                } => *can_be_unwrapped_if_unused = true,
+                _ => {}
+            }
+        }
+
+        self.parse_suffix(left, level)
+    }
+
+    fn parse_prefix(&mut self, level: Operator) -> PResult<Expr> {
+        let location = self.lexer.start;
+
+        let data = match self.lexer.token {
+            Token::Super => {
+                self.next()?;
+                ExprKind::Super
+            }
+
+            // "#a in b" checks if "b" has the private member "#a"
+            Token::PrivateIdentifier => {
+                let range = self.lexer.range();
+                let text = format!("Unexpected \"{}\"", self.lexer.identifier);
+                let key = self.parse_private_identifier()?;
+                if level >= Operator::Compare || !self.allow_in || self.lexer.token != Token::In {
+                    self.add_range_error(range, text);
+                    return Err(SyntaxError);
+                }
+                return Ok(key);
+            }
+
+            Token::Null => {
+                self.next()?;
+                ExprKind::Null
+            }
+
+            Token::This => {
+                self.next()?;
+                ExprKind::This
+            }
+
+            Token::True | Token::False => {
+                let value = self.lexer.token == Token::True;
+                self.next()?;
+                ExprKind::Boolean { value }
+            }
+
+            Token::StringLiteral => {
+                let value = std::mem::take(&mut self.lexer.string_literal);
+                self.next()?;
+                ExprKind::String { value }
+            }
+
+            Token::NumericLiteral => {
+                self.check_legacy_octal_literal()?;
+                let value = self.lexer.number;
+                self.next()?;
+                ExprKind::Number { value }
+            }
+
+            Token::BigIntegerLiteral => {
+                let value = std::mem::take(&mut self.lexer.identifier);
+                self.next()?;
+                ExprKind::BigInt { value }
+            }
+
+            Token::NoSubstitutionTemplateLiteral | Token::TemplateHead => {
+                let (head, head_raw, parts) = self.parse_template_parts(false)?;
+                ExprKind::Template {
+                    tag: expr(location, ExprKind::Missing),
+                    head,
+                    head_raw,
+                    parts,
+                }
+            }
+
+            Token::Slash | Token::SlashEquals => {
+                self.lexer.scan_regexp();
+                self.check()?;
+                let value = self.lexer.raw().to_owned();
+                self.next()?;
+                ExprKind::RegExp { value }
+            }
+
+            Token::OpenParen => {
+                if self.is_arrow_args_ahead() {
+                    return self.parse_paren_arrow_fn(location, false);
+                }
+                return self.parse_paren_expr();
+            }
+
+            Token::Yield if self.fn_flags.is_generator => {
+                if level > Operator::Assign {
+                    self.add_range_error(
+                        self.lexer.range(),
+                        "Cannot use a \"yield\" expression here without parentheses".to_owned(),
+                    );
+                    return Err(SyntaxError);
+                }
+                self.next()?;
+                let is_star = self.lexer.token == Token::Asterisk;
+                if is_star {
+                    self.next()?;
+                }
+                let has_value = is_star
+                    || (!self.lexer.has_newline_before
+                        && !matches!(
+                            self.lexer.token,
+                            Token::CloseParen
+                                | Token::CloseBracket
+                                | Token::CloseBrace
+                                | Token::Comma
+                                | Token::Colon
+                                | Token::Semicolon
+                                | Token::EndOfFile
+                        ));
+                let value = if has_value {
+                    self.parse_expr(Operator::Yield)?
+                } else {
+                    expr(location, ExprKind::Missing)
+                };
+                ExprKind::Yield { value, is_star }
+            }
+
+            Token::Exclamation
+            | Token::Tilde
+            | Token::Plus
+            | Token::Minus
+            | Token::PlusPlus
+            | Token::MinusMinus
+            | Token::Typeof
+            | Token::Void
+            | Token::Delete => {
+                let op_code = self
+                    .lexer
+                    .token
+                    .to_operator_code(true)
+                    .expect("These tokens are all prefix operators");
+                self.next()?;
+                let value = self.parse_expr(Operator::Prefix)?;
+                ExprKind::Unary { op_code, value }
+            }
+
+            Token::Function => return self.parse_fn_expr(location, false),
+
+            Token::Class => return self.parse_class_expr(location),
+
+            Token::New => {
+                self.next()?;
+
+                // "new.target"
+                if self.lexer.token == Token::Dot {
+                    self.next()?;
+                    self.expect_contextual_keyword("target")?;
+                    ExprKind::NewTarget
+                } else {
+                    // Calls are not part of the target: "new a.b()" calls
+                    // "a.b" as a constructor
+                    let target = self.parse_expr(Operator::Call)?;
+                    let args = if self.lexer.token == Token::OpenParen {
+                        self.parse_call_args()?
+                    } else {
+                        vec![]
+                    };
+                    ExprKind::New {
+                        target,
+                        args,
+                        can_be_unwrapped_if_unused: false,
+                    }
+                }
+            }
+
+            Token::OpenBracket => {
+                self.next()?;
+                let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+                let mut items = vec![];
+
+                while self.lexer.token != Token::CloseBracket {
+                    match self.lexer.token {
+                        Token::Comma => items.push(expr(self.lexer.start, ExprKind::Missing)),
+                        Token::DotDotDot => {
+                            let location = self.lexer.start;
+                            self.next()?;
+                            let value = self.parse_nested_expr(Operator::Comma)?;
+                            items.push(expr(location, ExprKind::Spread { value }));
+                        }
+                        _ => items.push(self.parse_nested_expr(Operator::Comma)?),
+                    }
+
+                    if self.lexer.token != Token::Comma {
+                        break;
+                    }
+                    self.next()?;
+                }
+
+                self.expect(Token::CloseBracket)?;
+                self.allow_in = old_allow_in;
+                ExprKind::Array { items }
+            }
+
+            Token::OpenBrace => {
+                self.next()?;
+                let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+                let mut properties = vec![];
+
+                while self.lexer.token != Token::CloseBrace {
+                    if let Some(property) = self.nested(|p| p.parse_property(false))? {
+                        properties.push(property);
+                    }
+                    if self.lexer.token != Token::Comma {
+                        break;
+                    }
+                    self.next()?;
+                }
+
+                self.expect(Token::CloseBrace)?;
+                self.allow_in = old_allow_in;
+                ExprKind::Object { properties }
+            }
+
+            Token::Import => {
+                self.next()?;
+                return self.parse_import_expr(location);
+            }
+
+            // "<" only starts an expression in files that are parsed as JSX
+            // or TypeScript
+            Token::LessThan if self.options.jsx.parse || self.options.typescript.parse => {
+                if self.options.typescript.parse {
+                    if let Some(value) = self.parse_typescript_angle_brackets(location)? {
+                        return Ok(value);
+                    }
+                }
+                self.next_inside_jsx_element()?;
+                let element = self.parse_jsx_element(location)?;
+                self.next()?;
+                return Ok(element);
+            }
+
+            _ if self.is_identifier() => {
+                self.check_strict_mode_reserved_word()?;
+                let name = self.lexer.identifier.clone();
+                let name_range = self.lexer.range();
+                self.next()?;
+
+                if name == "async" {
+                    return self.parse_async_prefix_expr(name_range);
+                }
+
+                if name == "await" && self.fn_flags.is_async {
+                    let value = self.parse_expr(Operator::Prefix)?;
+                    return Ok(expr(location, ExprKind::Await { value }));
+                }
+
+                // "x => y"
+                if self.lexer.token == Token::EqualsGreaterThan {
+                    return self.parse_identifier_arrow_fn(location, location, &name, false);
+                }
+
+                let reference = self.store_name_in_ref(name);
+                ExprKind::Identifier { reference }
+            }
+
+            _ => return self.unexpected(),
+        };
+
+        Ok(expr(location, data))
+    }
+
+    // Called after "import" has been consumed
+    fn parse_import_expr(&mut self, location: Location) -> PResult<Expr> {
+        // "import.meta"
+        if self.lexer.token == Token::Dot {
+            self.next()?;
+            self.expect_contextual_keyword("meta")?;
+            return Ok(expr(location, ExprKind::ImportMeta));
+        }
+
+        self.expect(Token::OpenParen)?;
+        let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+        let value = self.parse_nested_expr(Operator::Comma)?;
+        self.allow_in = old_allow_in;
+        self.expect(Token::CloseParen)?;
+        Ok(expr(location, ExprKind::Import { expr: value }))
+    }
+
+    // Called after "async" has been consumed. It's only a keyword if it's
+    // followed by a function or by arrow function arguments on the same line.
+    fn parse_async_prefix_expr(&mut self, async_range: Range<usize>) -> PResult<Expr> {
+        let location = async_range.start;
+
+        // "async => x" is an arrow function with an argument named "async"
+        if self.lexer.token == Token::EqualsGreaterThan {
+            return self.parse_identifier_arrow_fn(location, location, "async", false);
+        }
+
+        if !self.lexer.has_newline_before {
+            match self.lexer.token {
+                // "async function() {}"
+                Token::Function => return self.parse_fn_expr(location, true),
+
+                // "async x => y"
+                _ if self.is_identifier() => {
+                    let arg_location = self.lexer.start;
+                    let name = self.expect_identifier()?;
+                    return self.parse_identifier_arrow_fn(location, arg_location, &name, true);
+                }
+
+                // "async <T>(x: T) => y"
+                Token::LessThan if self.options.typescript.parse => {
+                    if let Some(arrow) =
+                        self.try_parse_typescript_generic_arrow_fn(location, true)?
+                    {
+                        return Ok(arrow);
+                    }
+                }
+
+                Token::OpenParen => {
+                    // "async (x) => y"
+                    if self.is_arrow_args_ahead() {
+                        return self.parse_paren_arrow_fn(location, true);
+                    }
+
+                    // A call to a function named "async"
+                    let target = self.identifier_expr(location, "async");
+                    let args = self.parse_call_args()?;
+                    return Ok(expr(
+                        location,
+                        ExprKind::Call {
+                            target,
+                            args,
+                            optional_chain: OptionalChain::None,
+                            is_parenthesized: false,
+                            is_direct_eval: false,
+                            can_be_unwrapped_if_unused: false,
+                        },
+                    ));
+                }
+
+                _ => {}
+            }
+        }
+
+        Ok(self.identifier_expr(location, "async"))
+    }
+
+    // Called at the "=>" of an arrow function whose only argument is a name
+    // without parentheses
+    fn parse_identifier_arrow_fn(
+        &mut self,
+        location: Location,
+        arg_location: Location,
+        name: &str,
+        is_async: bool,
+    ) -> PResult<Expr> {
+        self.push_scope_for_parse_pass(ScopeKind::FunctionArgs);
+        let reference = self.declare_symbol(SymbolKind::Hoisted, arg_location, name);
+        let arg = Arg {
+            is_typescript_ctor_field: false,
+            binding: binding(arg_location, BindingKind::Identifier { reference }),
+            default_: None,
+        };
+        self.parse_arrow_body(location, vec![arg], false, is_async)
+    }
+
+    fn parse_paren_arrow_fn(&mut self, location: Location, is_async: bool) -> PResult<Expr> {
+        self.push_scope_for_parse_pass(ScopeKind::FunctionArgs);
+        let (args, has_rest_arg) = self.parse_fn_args()?;
+        self.skip_typescript_colon_type()?;
+        let mut arrow = self.parse_arrow_body(location, args, has_rest_arg, is_async)?;
+        if let ExprKind::Arrow {
+            is_parenthesized, ..
+        } = arrow.data.as_mut()
+        {
+            *is_parenthesized = true;
+        }
+        Ok(arrow)
+    }
+
+    // Called at a "(" that isn't the start of the arguments of an arrow
+    // function
+    fn parse_paren_expr(&mut self) -> PResult<Expr> {
+        self.next()?;
+
+        // "()" and "(...a)" can only be arguments
+        if self.lexer.token == Token::CloseParen {
+            self.next()?;
+            self.lexer.expected(Token::EqualsGreaterThan);
+            return Err(SyntaxError);
+        }
+        if self.lexer.token == Token::DotDotDot {
+            return self.unexpected();
+        }
+
+        let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+        let mut value = self.parse_nested_expr(Operator::Lowest)?;
+        self.allow_in = old_allow_in;
+        self.expect(Token::CloseParen)?;
+
+        // Something like "(a.b) => c" that didn't parse as arguments
+        if self.lexer.token == Token::EqualsGreaterThan {
+            return self.unexpected();
+        }
+
+        match value.data.as_mut() {
+            ExprKind::Call {
+                is_parenthesized, ..
+            }
+            | ExprKind::Dot {
+                is_parenthesized, ..
+            }
+            | ExprKind::Index {
+                is_parenthesized, ..
+            } => *is_parenthesized = true,
+            _ => {}
+        }
+        Ok(value)
+    }
+
+    fn parse_call_args(&mut self) -> PResult<Vec<Expr>> {
+        self.expect(Token::OpenParen)?;
+        let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+        let mut args = vec![];
+
+        while self.lexer.token != Token::CloseParen {
+            if self.lexer.token == Token::DotDotDot {
+                let location = self.lexer.start;
+                self.next()?;
+                let value = self.parse_nested_expr(Operator::Comma)?;
+                args.push(expr(location, ExprKind::Spread { value }));
+            } else {
+                args.push(self.parse_nested_expr(Operator::Comma)?);
+            }
+
+            if self.lexer.token != Token::Comma {
+                break;
+            }
+            self.next()?;
+        }
+
+        self.expect(Token::CloseParen)?;
+        self.allow_in = old_allow_in;
+        Ok(args)
+    }
+
+    // The raw text is only kept for tagged templates since the tag is the only
+    // thing that can observe it. Invalid escapes are allowed there too, since
+    // they just make the cooked value undefined.
+    fn parse_template_parts(
+        &mut self,
+        is_tagged: bool,
+    ) -> PResult<(Vec<u16>, String, Vec<TemplatePart>)> {
+        let (head, head_raw) = self.take_template_text(is_tagged)?;
+        let mut parts = vec![];
+        if self.lexer.token == Token::TemplateHead {
+            loop {
+                self.next()?;
+                let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+                let value = self.parse_nested_expr(Operator::Lowest)?;
+                self.allow_in = old_allow_in;
+                self.lexer.rescan_close_brace_as_template_token();
+                self.check()?;
+                let (tail, tail_raw) = self.take_template_text(is_tagged)?;
+                parts.push(TemplatePart {
+                    value,
+                    tail,
+                    tail_raw,
+                });
+                if self.lexer.token == Token::TemplateTail {
+                    break;
+                }
+            }
+        }
+        self.next()?;
+        Ok((head, head_raw, parts))
+    }
+
+    fn take_template_text(&mut self, is_tagged: bool) -> PResult<(Vec<u16>, String)> {
+        if !is_tagged {
+            if let Some(range) = self.lexer.invalid_template_escape.clone() {
+                self.add_range_error(range, "Invalid escape sequence".to_owned());
+                return Err(SyntaxError);
+            }
+        }
+        let raw = if is_tagged {
+            self.lexer.raw_template_contents()
+        } else {
+            String::new()
+        };
+        Ok((std::mem::take(&mut self.lexer.string_literal), raw))
+    }
+
+    // Chains like "a + b + c" or "a.b.c" are parsed in a loop, so they can be
+    // as long as the code makes them without running into the nesting limit.
+    // Dropping them can grow the stack too (see "Expr").
+    fn parse_suffix(&mut self, mut left: Expr, level: Operator) -> PResult<Expr> {
+        // The links after the first "?." continue the chain until something
+        // that isn't a property access or call ends it
+        let mut optional_chain = OptionalChain::None;
+        loop {
+            let location = left.location;
+            if self.options.typescript.parse && self.skip_typescript_suffix(level)? {
+                continue;
+            }
+
+            let data = match self.lexer.token {
+                Token::Dot => {
+                    self.next()?;
+                    if self.lexer.token == Token::PrivateIdentifier {
+                        ExprKind::Index {
+                            target: left,
+                            index: self.parse_private_identifier()?,
+                            optional_chain,
+                            is_parenthesized: false,
+                        }
+                    } else {
+                        let (name, name_location) = self.parse_dot_name()?;
+                        ExprKind::Dot {
+                            target: left,
+                            name,
+                            name_location,
+                            optional_chain,
+                            is_parenthesized: false,
+                        }
+                    }
+                }
+
+                Token::QuestionDot => {
+                    // "new a?.b()" is a syntax error
+                    if level >= Operator::Call {
+                        self.add_range_error(
+                            self.lexer.range(),
+                            "Invalid optional chain from new expression".to_owned(),
+                        );
+                        return Err(SyntaxError);
+                    }
+                    self.next()?;
+                    optional_chain = OptionalChain::Continue;
+                    match self.lexer.token {
+                        Token::OpenBracket => {
+                            self.next()?;
+                            let index = self.parse_index()?;
+                            ExprKind::Index {
+                                target: left,
+                                index,
+                                optional_chain: OptionalChain::Start,
+                                is_parenthesized: false,
+                            }
+                        }
+                        Token::OpenParen => ExprKind::Call {
+                            target: left,
+                            args: self.parse_call_args()?,
+                            optional_chain: OptionalChain::Start,
+                            is_parenthesized: false,
+                            is_direct_eval: false,
+                            can_be_unwrapped_if_unused: false,
+                        },
+                        Token::PrivateIdentifier => ExprKind::Index {
+                            target: left,
+                            index: self.parse_private_identifier()?,
+                            optional_chain: OptionalChain::Start,
+                            is_parenthesized: false,
+                        },
+                        _ => {
+                            let (name, name_location) = self.parse_dot_name()?;
+                            ExprKind::Dot {
+                                target: left,
+                                name,
+                                name_location,
+                                optional_chain: OptionalChain::Start,
+                                is_parenthesized: false,
+                            }
+                        }
+                    }
+                }
+
+                Token::OpenBracket => {
+                    self.next()?;
+                    let index = self.parse_index()?;
+                    ExprKind::Index {
+                        target: left,
+                        index,
+                        optional_chain,
+                        is_parenthesized: false,
+                    }
+                }
+
+                Token::OpenParen => {
+                    if level >= Operator::Call {
+                        return Ok(left);
+                    }
+                    ExprKind::Call {
+                        target: left,
+                        args: self.parse_call_args()?,
+                        optional_chain,
+                        is_parenthesized: false,
+                        is_direct_eval: false,
+                        can_be_unwrapped_if_unused: false,
+                    }
+                }
+
+                Token::NoSubstitutionTemplateLiteral | Token::TemplateHead => {
+                    if optional_chain != OptionalChain::None {
+                        self.add_range_error(
+                            self.lexer.range(),
+                            "Template literals cannot have an optional chain as a tag".to_owned(),
+                        );
+                        return Err(SyntaxError);
+                    }
+                    let (head, head_raw, parts) = self.parse_template_parts(true)?;
+                    ExprKind::Template {
+                        tag: left,
+                        head,
+                        head_raw,
+                        parts,
+                    }
+                }
+
+                Token::PlusPlus | Token::MinusMinus => {
+                    if self.lexer.has_newline_before || level >= Operator::Postfix {
+                        return Ok(left);
+                    }
+                    let op_code = if self.lexer.token == Token::PlusPlus {
+                        OperatorCode::UnOpPostInc
+                    } else {
+                        OperatorCode::UnOpPostDec
+                    };
+                    self.next()?;
+                    ExprKind::Unary {
+                        op_code,
+                        value: left,
+                    }
+                }
+
+                Token::Question => {
+                    if level >= Operator::Conditional {
+                        return Ok(left);
+                    }
+                    self.next()?;
+                    let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+                    let yes = self.parse_nested_expr(Operator::Comma)?;
+                    self.allow_in = old_allow_in;
+                    self.expect(Token::Colon)?;
+                    let no = self.parse_expr(Operator::Comma)?;
+                    ExprKind::If {
+                        test: left,
+                        yes,
+                        no,
+                    }
+                }
+
+                token => {
+                    let (op_code, op_level) = match binary_operator(token) {
+                        Some(operator) => operator,
+                        None => return Ok(left),
+                    };
+                    if level >= op_level || (token == Token::In && !self.allow_in) {
+                        return Ok(left);
+                    }
+                    self.next()?;
+
+                    // Right-associative operators bind the right side at a
+                    // lower level, so "a = b = c" is "a = (b = c)"
+                    let right_level = if op_code.is_right_associative() {
+                        level_below(op_level)
+                    } else {
+                        op_level
+                    };
+                    let right = self.parse_expr(right_level)?;
+                    ExprKind::Binary {
+                        op_code,
+                        left,
+                        right,
+                    }
+                }
+            };
+
+            if !matches!(
+                data,
+                ExprKind::Dot { .. } | ExprKind::Index { .. } | ExprKind::Call { .. }
+            ) {
+                optional_chain = OptionalChain::None;
+            }
+            left = expr(location, data);
+        }
+    }
+
+    fn parse_dot_name(&mut self) -> PResult<(String, Location)> {
+        if !self.is_identifier_or_keyword() {
+            self.lexer.expected(Token::Identifier);
+            return Err(SyntaxError);
+        }
+        let name = self.lexer.identifier.clone();
+        let location = self.lexer.start;
+        self.next()?;
+        Ok((name, location))
+    }
+
+    // Called after "[" has been consumed
+    fn parse_index(&mut self) -> PResult<Expr> {
+        let old_allow_in = std::mem::replace(&mut self.allow_in, true);
+        let index = self.parse_nested_expr(Operator::Lowest)?;
+        self.allow_in = old_allow_in;
+        self.expect(Token::CloseBracket)?;
+        Ok(index)
+    }
+
+    // The lexer reads JSX differently depending on where the parser is inside
+    // an element (see "enter_jsx_element")
+    fn next_inside_jsx_element(&mut self) -> PResult<()> {
+        self.lexer.enter_jsx_element();
+        self.check()
+    }
+
+    fn expect_inside_jsx_element(&mut self, token: Token) -> PResult<()> {
+        self.lexer.expect_inside_jsx_element(token);
+        self.check()
+    }
+
+    fn next_jsx_element_child(&mut self) -> PResult<()> {
+        self.lexer.lex_jsx_text();
+        self.check()
+    }
+
+    fn expect_jsx_element_child(&mut self, token: Token) -> PResult<()> {
+        self.lexer.expect_jsx_element_child(token);
+        self.check()
+    }
+
+    // Called after the "<" that starts the element. The ">" that ends it is
+    // left for the caller, which knows how to read what comes after it.
+    fn parse_jsx_element(&mut self, location: Location) -> PResult<Expr> {
+        self.nested(|p| {
+            let old_allow_in = std::mem::replace(&mut p.allow_in, true);
+            let element = p.parse_jsx_element_inner(location)?;
+            p.allow_in = old_allow_in;
+            Ok(element)
+        })
+    }
+
+    fn parse_jsx_element_inner(&mut self, location: Location) -> PResult<Expr> {
+        let (tag, start_range, start_text) = self.parse_jsx_tag()?;
+
+        let mut properties = vec![];
+        if tag.is_some() {
+            loop {
+                match self.lexer.token {
+                    Token::Identifier => {
+                        let key_range = self.lexer.range();
+                        let key = expr(
+                            key_range.start,
+                            ExprKind::String {
+                                value: string_to_utf16(&self.lexer.identifier),
+                            },
+                        );
+                        self.next_inside_jsx_element()?;
+
+                        // An attribute without a value is true
+                        let value = if self.lexer.token != Token::Equals {
+                            expr(key_range.end, ExprKind::Boolean { value: true })
+                        } else {
+                            self.next_inside_jsx_element()?;
+                            match self.lexer.token {
+                                Token::StringLiteral => {
+                                    let value = std::mem::take(&mut self.lexer.string_literal);
+                                    let value = expr(self.lexer.start, ExprKind::String { value });
+                                    self.next_inside_jsx_element()?;
+                                    value
+                                }
+                                Token::LessThan => {
+                                    let location = self.lexer.start;
+                                    self.next_inside_jsx_element()?;
+                                    let value = self.parse_jsx_element(location)?;
+                                    self.next_inside_jsx_element()?;
+                                    value
+                                }
+                                _ => {
+                                    // The braces hold ordinary JavaScript
+                                    self.expect(Token::OpenBrace)?;
+                                    let value = self.parse_expr(Operator::Lowest)?;
+                                    self.expect_inside_jsx_element(Token::CloseBrace)?;
+                                    value
+                                }
+                            }
+                        };
+                        properties.push(jsx_property(PropertyKind::PropertyNormal, key, value));
+                    }
+
+                    // "{...props}"
+                    Token::OpenBrace => {
+                        let location = self.lexer.start;
+                        self.next()?;
+                        self.expect(Token::DotDotDot)?;
+                        let value = self.parse_expr(Operator::Comma)?;
+                        self.expect_inside_jsx_element(Token::CloseBrace)?;
+                        let key = expr(location, ExprKind::Missing);
+                        properties.push(jsx_property(PropertyKind::PropertySpread, key, value));
+                    }
+
+                    _ => break,
+                }
+            }
+        }
+
+        // "<div />" has no children
+        if self.lexer.token == Token::Slash {
+            self.next_inside_jsx_element()?;
+            if self.lexer.token != Token::GreaterThan {
+                self.lexer.expected(Token::GreaterThan);
+                return Err(SyntaxError);
+            }
+            return Ok(self.jsx_element(location, tag, properties, vec![]));
+        }
+
+        self.expect_jsx_element_child(Token::GreaterThan)?;
+        let mut children = vec![];
+        loop {
+            match self.lexer.token {
+                Token::StringLiteral => {
+                    let value = std::mem::take(&mut self.lexer.string_literal);
+                    children.push(expr(self.lexer.start, ExprKind::String { value }));
+                    self.next_jsx_element_child()?;
+                }
+
+                // The expression can be left out, as in "{/* comment */}"
+                Token::OpenBrace => {
+                    self.next()?;
+                    if self.lexer.token != Token::CloseBrace {
+                        children.push(self.parse_expr(Operator::Lowest)?);
+                    }
+                    self.expect_jsx_element_child(Token::CloseBrace)?;
+                }
+
+                Token::LessThan => {
+                    let less_than_location = self.lexer.start;
+                    self.next_inside_jsx_element()?;
+                    if self.lexer.token != Token::Slash {
+                        children.push(self.parse_jsx_element(less_than_location)?);
+                        self.next_jsx_element_child()?;
+                        continue;
+                    }
+
+                    // The closing tag
+                    self.next_inside_jsx_element()?;
+                    let (_, end_range, end_text) = self.parse_jsx_tag()?;
+                    if end_text != start_text {
+                        self.add_range_error_with_notes(
+                            end_range,
+                            format!(
+                                "Unexpected closing \"{}\" tag does not match opening \"{}\" tag",
+                                end_text, start_text
+                            ),
+                            vec![MsgNote::new(
+                                self.source,
+                                start_range,
+                                format!("The opening \"{}\" tag is here:", start_text),
+                            )],
+                        );
+                    }
+                    if self.lexer.token != Token::GreaterThan {
+                        self.lexer.expected(Token::GreaterThan);
+                        return Err(SyntaxError);
+                    }
+                    return Ok(self.jsx_element(location, tag, properties, children));
+                }
+
+                _ => return self.unexpected(),
+            }
+        }
+    }
+
+    // Returns the tag with its range and its text, which the closing tag has
+    // to match. A fragment has no tag.
+    fn parse_jsx_tag(&mut self) -> PResult<(Option<Expr>, Range<usize>, String)> {
+        let location = self.lexer.start;
+        if self.lexer.token == Token::GreaterThan {
+            return Ok((None, location..location, String::new()));
+        }
+
+        let mut range = self.lexer.range();
+        let mut text = self.lexer.identifier.clone();
+        self.expect_inside_jsx_element(Token::Identifier)?;
+
+        // Lowercase names are HTML elements, which are passed as strings. So
+        // are names with a dash or a namespace, which can't be identifiers.
+        if text.contains(['-', ':'])
+            || (self.lexer.token != Token::Dot
+                && text.starts_with(|c: char| c.is_ascii_lowercase()))
+        {
+            let tag = expr(
+                location,
+                ExprKind::String {
+                    value: string_to_utf16(&text),
+                },
+            );
+            return Ok((Some(tag), range, text));
+        }
+
+        let reference = self.store_name_in_ref(text.clone());
+        let mut tag = expr(location, ExprKind::Identifier { reference });
+
+        // "<a.b.c>"
+        while self.lexer.token == Token::Dot {
+            self.next_inside_jsx_element()?;
+            let name_range = self.lexer.range();
+            let name = self.lexer.identifier.clone();
+            self.expect_inside_jsx_element(Token::Identifier)?;
+            if let Some(index) = name.find('-') {
+                let start = name_range.start + index;
+                self.add_range_error(start..start + 1, "Unexpected \"-\"".to_owned());
+                return Err(SyntaxError);
+            }
+            text.push('.');
+            text.push_str(&name);
+            range.end = name_range.end;
+            tag = expr(
+                location,
+                ExprKind::Dot {
+                    target: tag,
+                    name,
+                    name_location: name_range.start,
+                    optional_chain: OptionalChain::None,
+                    is_parenthesized: false,
+                },
+            );
+        }
+        Ok((Some(tag), range, text))
+    }
+
+    // The automatic runtime imports the functions that the elements call,
+    // so those are noted while parsing
+    fn jsx_element(
+        &mut self,
+        location: Location,
+        tag: Option<Expr>,
+        properties: Vec<Property>,
+        children: Vec<Expr>,
+    ) -> Expr {
+        let jsx = &self.options.jsx;
+        if jsx.runtime == JSXRuntime::Automatic {
+            let function = automatic_jsx_function(&properties, children.len(), jsx.development);
+            self.jsx_runtime_refs.entry(function).or_insert(INVALID_REF);
+            if tag.is_none() {
+                self.jsx_runtime_refs
+                    .entry("Fragment")
+                    .or_insert(INVALID_REF);
+            }
+        }
+        expr(
+            location,
+            ExprKind::JSXElement {
+                tag,
+                properties,
+                children,
+            },
+        )
+    }
+
+    // The functions of the automatic runtime are imported by statements that
+    // go at the top, after the directives:
+    //
+    //   import {Fragment as _Fragment, jsx as _jsx} from "react/jsx-runtime";
+    //
+    fn add_jsx_runtime_imports(&mut self, stmts: &mut Vec<Stmt>) {
+        if self.jsx_runtime_refs.is_empty() {
+            return;
+        }
+        self.has_es6_imports = true;
+
+        let mut imports: Vec<(Path, Vec<ClauseItem>)> = vec![];
+        let names: Vec<&'static str> = self.jsx_runtime_refs.keys().copied().collect();
+        for name in names {
+            let text = self.jsx_import_path(name);
+            let index = match imports.iter().position(|(path, _)| path.text == text) {
+                Some(index) => index,
+                None => {
+                    let path = Path {
+                        loc: 0,
+                        text,
+                        use_source_index: false,
+                        source_index: 0,
+                    };
+                    imports.push((path, vec![]));
+                    imports.len() - 1
+                }
+            };
+            let reference = self.generate_symbol(SymbolKind::Other, &format!("_{}", name));
+            self.jsx_runtime_refs.insert(name, reference);
+            imports[index].1.push(ClauseItem {
+                alias: name.to_owned(),
+                alias_location: 0,
+                name: LocationRef { loc: 0, reference },
+            });
+        }
+
+        let index = stmts
+            .iter()
+            .position(|stmt| {
+                !matches!(
+                    stmt.data.as_ref(),
+                    StmtKind::Directive { .. } | StmtKind::Comment { .. }
+                )
+            })
+            .unwrap_or(stmts.len());
+        for (path, items) in imports.into_iter().rev() {
+            let name = format!("import_{}", generate_non_unique_name_from_path(&path.text));
+            let namespace_ref = self.generate_symbol(SymbolKind::Other, &name);
+            for item in &items {
+                self.import_items.insert(item.name.reference);
+                self.named_imports.insert(
+                    item.name.reference,
+                    NamedImport {
+                        alias: item.alias.clone(),
+                        alias_loc: 0,
+                        import_path: path.clone(),
+                        namespace_ref,
+                        local_parts_with_uses: vec![],
+                        is_exported: false,
+                    },
+                );
+            }
+            let import = StmtKind::Import {
+                namespace_symbol: NamespaceSymbol::Clause { items },
+                default_name: None,
+                path,
+            };
+            stmts.insert(index, stmt(0, import));
+        }
+    }
+
+    // "createElement" is the only function that comes from the package itself
+    fn jsx_import_path(&self, name: &str) -> String {
+        let jsx = &self.options.jsx;
+        let source = if jsx.import_source.is_empty() {
+            "react"
+        } else {
+            &jsx.import_source
+        };
+        if name == "createElement" {
+            source.to_owned()
+        } else if jsx.development {
+            format!("{}/jsx-dev-runtime", source)
+        } else {
+            format!("{}/jsx-runtime", source)
+        }
+    }
+
+    // TypeScript syntax that only describes types is skipped without building
+    // anything, since none of it ends up in the output. The functions below
+    // find where a type ends the way the TypeScript compiler does, but don't
+    // check that the type makes sense.
+
+    // Tries skipping something that may not be TypeScript syntax at all, and
+    // goes back to where it started if "skip" fails or returns false. Nothing
+    // is reported while trying. Types don't declare anything, so only the
+    // lexer needs to be put back.
+    fn try_skip_typescript<F>(&mut self, skip: F) -> bool
+    where
+        F: FnOnce(&mut Self) -> PResult<bool>,
+    {
+        let checkpoint = self.lexer.checkpoint();
+        let was_log_disabled = std::mem::replace(&mut self.lexer.is_log_disabled, true);
+        if skip(self).unwrap_or(false) {
+            self.lexer.is_log_disabled = was_log_disabled;
+            return true;
+        }
+        self.lexer.restore(checkpoint);
+        false
+    }
+
+    // Checks the token after the current one without moving past it
+    fn next_token_is<F>(&mut self, test: F) -> bool
+    where
+        F: FnOnce(&Self) -> bool,
+    {
+        let checkpoint = self.lexer.checkpoint();
+        self.lexer.is_log_disabled = true;
+        self.lexer.next();
+        let result = test(self);
+        self.lexer.restore(checkpoint);
+        result
+    }
+
+    // Keywords like "interface" and "type" only start a declaration when a
+    // name follows them on the same line
+    fn is_name_on_same_line_next(&mut self) -> bool {
+        self.next_token_is(|p| p.is_identifier() && !p.lexer.has_newline_before)
+    }
+
+    fn skip_typescript_identifier(&mut self) -> PResult<()> {
+        if !self.is_identifier() {
+            self.lexer.expected(Token::Identifier);
+            return Err(SyntaxError);
+        }
+        self.next()
+    }
+
+    // ": T" after a binding, an argument, a class field, or the arguments of a
+    // function
+    fn skip_typescript_colon_type(&mut self) -> PResult<()> {
+        if self.options.typescript.parse && self.lexer.token == Token::Colon {
+            self.next()?;
+            self.skip_typescript_type(Operator::Lowest)?;
+        }
+        Ok(())
+    }
+
+    // "level" works like it does for expressions. The types in a union or an
+    // intersection are skipped at the levels of "|" and "&", and a level of
+    // "Conditional" stops before the "extends" of a conditional type.
+    fn skip_typescript_type(&mut self, level: Operator) -> PResult<()> {
+        self.nested(|p| p.skip_typescript_type_inner(level))
+    }
+
+    fn skip_typescript_type_inner(&mut self, level: Operator) -> PResult<()> {
+        // "type A = | B | C"
+        while matches!(self.lexer.token, Token::Bar | Token::Ampersand) {
+            self.next()?;
+        }
+
+        match self.lexer.token {
+            Token::NumericLiteral
+            | Token::BigIntegerLiteral
+            | Token::StringLiteral
+            | Token::NoSubstitutionTemplateLiteral
+            | Token::True
+            | Token::False
+            | Token::Null
+            | Token::Void
+            | Token::Const => self.next()?,
+
+            Token::This => {
+                self.next()?;
+
+                // "(): this is T"
+                if self.lexer.is_contextual_keyword("is") && !self.lexer.has_newline_before {
+                    self.next()?;
+                    return self.skip_typescript_type(Operator::Lowest);
+                }
+            }
+
+            // "-1" and "-1n"
+            Token::Minus => {
+                self.next()?;
+                if self.lexer.token == Token::BigIntegerLiteral {
+                    self.next()?;
+                } else {
+                    self.expect(Token::NumericLiteral)?;
+                }
+            }
+
+            // "import('path').A"
+            Token::Import => {
+                self.next()?;
+                self.expect(Token::OpenParen)?;
+                self.expect(Token::StringLiteral)?;
+                self.expect(Token::CloseParen)?;
+            }
+
+            // "new (a: A) => B"
+            Token::New => {
+                self.next()?;
+                self.skip_typescript_type_parameters()?;
+                self.skip_typescript_paren_or_fn_type()?;
+            }
+
+            // "<T>(a: T) => T"
+            Token::LessThan => {
+                self.skip_typescript_type_parameters()?;
+                self.skip_typescript_paren_or_fn_type()?;
+            }
+
+            Token::OpenParen => self.skip_typescript_paren_or_fn_type()?,
+
+            // "typeof a.b" and "typeof import('path')"
+            Token::Typeof => {
+                self.next()?;
+                if self.lexer.token == Token::Import {
+                    return self.skip_typescript_type(level);
+                }
+                if !self.is_identifier_or_keyword() {
+                    self.lexer.expected(Token::Identifier);
+                    return Err(SyntaxError);
+                }
+                self.next()?;
+                while self.lexer.token == Token::Dot {
+                    self.next()?;
+                    self.parse_dot_name()?;
+                }
+                if !self.lexer.has_newline_before {
+                    self.skip_typescript_type_arguments()?;
+                }
+            }
+
+            // "[A, B?, ...C]" and "[a: A, b?: B]"
+            Token::OpenBracket => {
+                self.next()?;
+                while self.lexer.token != Token::CloseBracket {
+                    if self.lexer.token == Token::DotDotDot {
+                        self.next()?;
+                    }
+                    self.skip_typescript_type(Operator::Lowest)?;
+                    if self.lexer.token == Token::Question {
+                        self.next()?;
+                    }
+                    if self.lexer.token == Token::Colon {
+                        self.next()?;
+                        self.skip_typescript_type(Operator::Lowest)?;
+                    }
+                    if self.lexer.token != Token::Comma {
+                        break;
+                    }
+                    self.next()?;
+                }
+                self.expect(Token::CloseBracket)?;
+            }
+
+            Token::OpenBrace => self.skip_typescript_object_type()?,
+
+            // "`a-${B}`"
+            Token::TemplateHead => loop {
+                self.next()?;
+                self.skip_typescript_type(Operator::Lowest)?;
+                self.lexer.rescan_close_brace_as_template_token();
+                self.check()?;
+                if self.lexer.token == Token::TemplateTail {
+                    self.next()?;
+                    break;
+                }
+            },
+
+            _ if self.is_identifier() => {
+                match self.lexer.raw() {
+                    // "keyof A" and "readonly A[]"
+                    "keyof" | "readonly" => {
+                        self.next()?;
+                        self.skip_typescript_type(Operator::Prefix)?;
+                    }
+
+                    // "unique symbol"
+                    "unique" => {
+                        self.next()?;
+                        if self.lexer.is_contextual_keyword("symbol") {
+                            self.next()?;
+                        }
+                    }
+
+                    // "infer U" and "infer U extends V". An "extends" that is
+                    // followed by "?" belongs to a conditional type around
+                    // this one instead.
+                    "infer" => {
+                        self.next()?;
+                        self.skip_typescript_identifier()?;
+                        if self.lexer.token == Token::Extends {
+                            self.try_skip_typescript(|p| {
+                                p.next()?;
+                                p.skip_typescript_type(Operator::Prefix)?;
+                                Ok(p.lexer.token != Token::Question)
+                            });
+                        }
+                    }
+
+                    // "asserts a" and "asserts this is T"
+                    "asserts" => {
+                        self.next()?;
+                        if (self.is_identifier() || self.lexer.token == Token::This)
+                            && !self.lexer.has_newline_before
+                        {
+                            self.next()?;
+                        }
+                    }
+
+                    // "abstract new () => T"
+                    "abstract" => {
+                        self.next()?;
+                        if self.lexer.token == Token::New {
+                            return self.skip_typescript_type(level);
+                        }
+                    }
+
+                    _ => self.next()?,
+                }
+
+                // "(a): a is T"
+                if self.lexer.is_contextual_keyword("is") && !self.lexer.has_newline_before {
+                    self.next()?;
+                    return self.skip_typescript_type(Operator::Lowest);
+                }
+                if !self.lexer.has_newline_before {
+                    self.skip_typescript_type_arguments()?;
+                }
+            }
+
+            _ => return self.unexpected(),
+        }
+
+        loop {
+            match self.lexer.token {
+                Token::Bar if level < Operator::BitwiseOr => {
+                    self.next()?;
+                    self.skip_typescript_type(Operator::BitwiseOr)?;
+                }
+                Token::Ampersand if level < Operator::BitwiseAnd => {
+                    self.next()?;
+                    self.skip_typescript_type(Operator::BitwiseAnd)?;
+                }
+
+                // "A.B<C>"
+                Token::Dot => {
+                    self.next()?;
+                    self.parse_dot_name()?;
+                    if !self.lexer.has_newline_before {
+                        self.skip_typescript_type_arguments()?;
+                    }
+                }
+
+                // "A[]" and "A[K]". On the next line a "[" starts an index
+                // signature in an object type instead.
+                Token::OpenBracket if !self.lexer.has_newline_before => {
+                    self.next()?;
+                    if self.lexer.token != Token::CloseBracket {
+                        self.skip_typescript_type(Operator::Lowest)?;
+                    }
+                    self.expect(Token::CloseBracket)?;
+                }
+
+                // "A extends B ? C : D"
+                Token::Extends
+                    if !self.lexer.has_newline_before && level < Operator::Conditional =>
+                {
+                    self.next()?;
+                    self.skip_typescript_type(Operator::Conditional)?;
+                    self.expect(Token::Question)?;
+                    self.skip_typescript_type(Operator::Lowest)?;
+                    self.expect(Token::Colon)?;
+                    self.skip_typescript_type(Operator::Lowest)?;
+                }
+
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    // "(A)" or a function type like "(a: A) => B". Both start with "(", so the
+    // arguments are tried first.
+    fn skip_typescript_paren_or_fn_type(&mut self) -> PResult<()> {
+        let is_fn_type = self.try_skip_typescript(|p| {
+            p.skip_typescript_fn_args()?;
+            Ok(p.lexer.token == Token::EqualsGreaterThan)
+        });
+        if is_fn_type {
+            self.next()?;
+            return self.skip_typescript_type(Operator::Lowest);
+        }
+        self.expect(Token::OpenParen)?;
+        self.skip_typescript_type(Operator::Lowest)?;
+        self.expect(Token::CloseParen)
+    }
+
+    fn skip_typescript_fn_args(&mut self) -> PResult<()> {
+        self.expect(Token::OpenParen)?;
+        while self.lexer.token != Token::CloseParen {
+            if self.lexer.token == Token::DotDotDot {
+                self.next()?;
+            }
+            self.skip_typescript_binding()?;
+            if self.lexer.token == Token::Question {
+                self.next()?;
+            }
+            if self.lexer.token == Token::Colon {
+                self.next()?;
+                self.skip_typescript_type(Operator::Lowest)?;
+            }
+            if self.lexer.token != Token::Comma {
+                break;
+            }
+            self.next()?;
+        }
+        self.expect(Token::CloseParen)
+    }
+
+    // The names of the arguments of a function type, which may be
+    // destructuring patterns without default values
+    fn skip_typescript_binding(&mut self) -> PResult<()> {
+        match self.lexer.token {
+            Token::This => self.next(),
+
+            Token::OpenBracket => {
+                self.next()?;
+                while self.lexer.token != Token::CloseBracket {
+                    if self.lexer.token == Token::Comma {
+                        self.next()?;
+                        continue;
+                    }
+                    if self.lexer.token == Token::DotDotDot {
+                        self.next()?;
+                    }
+                    self.nested(Self::skip_typescript_binding)?;
+                    if self.lexer.token != Token::Comma {
+                        break;
+                    }
+                    self.next()?;
+                }
+                self.expect(Token::CloseBracket)
+            }
+
+            Token::OpenBrace => {
+                self.next()?;
+                while self.lexer.token != Token::CloseBrace {
+                    if self.lexer.token == Token::DotDotDot {
+                        self.next()?;
+                    }
+                    let is_shorthand = self.is_identifier();
+                    if !self.is_identifier_or_keyword()
+                        && !matches!(
+                            self.lexer.token,
+                            Token::StringLiteral | Token::NumericLiteral
+                        )
+                    {
+                        return self.unexpected();
+                    }
+                    self.next()?;
+                    if self.lexer.token == Token::Colon || !is_shorthand {
+                        self.expect(Token::Colon)?;
+                        self.nested(Self::skip_typescript_binding)?;
+                    }
+                    if self.lexer.token != Token::Comma {
+                        break;
+                    }
+                    self.next()?;
+                }
+                self.expect(Token::CloseBrace)
+            }
+
+            _ => self.skip_typescript_identifier(),
+        }
+    }
+
+    // "{a: A; b?(): B; readonly [key: string]: C}" and mapped types like
+    // "{-readonly [K in keyof T]+?: T[K]}"
+    fn skip_typescript_object_type(&mut self) -> PResult<()> {
+        self.expect(Token::OpenBrace)?;
+        while self.lexer.token != Token::CloseBrace {
+            if matches!(self.lexer.token, Token::Plus | Token::Minus) {
+                self.next()?;
+            }
+
+            // Modifiers like "readonly" and "get" come before the name, and
+            // any of them may be the name itself
+            let mut has_key = false;
+            while self.is_identifier_or_keyword()
+                || matches!(
+                    self.lexer.token,
+                    Token::StringLiteral | Token::NumericLiteral
+                )
+            {
+                self.next()?;
+                has_key = true;
+            }
+
+            // An index signature, a mapped type, or a computed name
+            if self.lexer.token == Token::OpenBracket {
+                self.next()?;
+                self.skip_typescript_type(Operator::Lowest)?;
+                match self.lexer.token {
+                    Token::Colon => {
+                        self.next()?;
+                        self.skip_typescript_type(Operator::Lowest)?;
+                    }
+                    Token::In => {
+                        self.next()?;
+                        self.skip_typescript_type(Operator::Lowest)?;
+                        if self.lexer.is_contextual_keyword("as") {
+                            self.next()?;
+                            self.skip_typescript_type(Operator::Lowest)?;
+                        }
+                    }
+                    _ => {}
+                }
+                self.expect(Token::CloseBracket)?;
+                if matches!(self.lexer.token, Token::Plus | Token::Minus) {
+                    self.next()?;
+                }
+                has_key = true;
+            }
+
+            if has_key && matches!(self.lexer.token, Token::Question | Token::Exclamation) {
+                self.next()?;
+            }
+
+            // Call, construct, and method signatures
+            self.skip_typescript_type_parameters()?;
+            if self.lexer.token == Token::OpenParen {
+                self.skip_typescript_fn_args()?;
+                self.skip_typescript_colon_type()?;
+            } else if has_key && self.lexer.token == Token::Colon {
+                self.skip_typescript_colon_type()?;
+            } else if !has_key {
+                return self.unexpected();
+            }
+
+            match self.lexer.token {
+                Token::Comma | Token::Semicolon => self.next()?,
+                Token::CloseBrace => {}
+                _ if self.lexer.has_newline_before => {}
+                _ => return self.unexpected(),
+            }
+        }
+        self.next()
+    }
+
+    // "<T, U extends A = B>" after the name of a function, class, or type,
+    // which may also have "in", "out", and "const" modifiers. A comma can end
+    // the list, which is how ".tsx" files write "<T,>() => {}".
+    fn skip_typescript_type_parameters(&mut self) -> PResult<()> {
+        if self.lexer.token != Token::LessThan {
+            return Ok(());
+        }
+        self.next()?;
+        while self.lexer.token != Token::GreaterThan {
+            while self.lexer.token == Token::In || self.lexer.token == Token::Const {
+                self.next()?;
+            }
+            if self.lexer.is_contextual_keyword("out") && self.is_name_on_same_line_next() {
+                self.next()?;
+            }
+            self.skip_typescript_identifier()?;
+            if self.lexer.token == Token::Extends {
+                self.next()?;
+                self.skip_typescript_type(Operator::Lowest)?;
+            }
+            if self.lexer.token == Token::Equals {
+                self.next()?;
+                self.skip_typescript_type(Operator::Lowest)?;
+            }
+            if self.lexer.token != Token::Comma {
+                break;
+            }
+            self.next()?;
+        }
+        self.lexer.expect_greater_than();
+        self.check()
+    }
+
+    // "<A, B>" after the name of a type
+    fn skip_typescript_type_arguments(&mut self) -> PResult<()> {
+        if self.lexer.token != Token::LessThan {
+            return Ok(());
+        }
+        self.next()?;
+        loop {
+            self.skip_typescript_type(Operator::Lowest)?;
+            if self.lexer.token != Token::Comma {
+                break;
+            }
+            self.next()?;
+        }
+        self.lexer.expect_greater_than();
+        self.check()
+    }
+
+    // A "<" after an expression may start type arguments, as in "f<T>(x)", or
+    // be a comparison, as in "a < b > c". Like the TypeScript compiler, this
+    // takes it as type arguments if they parse and are followed by something
+    // that can't continue a comparison. Only a ">" by itself ends them here,
+    // so "a < b >= c" is always a comparison.
+    fn try_skip_typescript_type_arguments_in_expr(&mut self) -> bool {
+        self.try_skip_typescript(|p| {
+            p.next()?;
+            loop {
+                p.skip_typescript_type(Operator::Lowest)?;
+                if p.lexer.token != Token::Comma {
+                    break;
+                }
+                p.next()?;
+            }
+            p.expect(Token::GreaterThan)?;
+            Ok(p.can_follow_typescript_type_arguments())
+        })
+    }
+
+    fn can_follow_typescript_type_arguments(&self) -> bool {
+        match self.lexer.token {
+            // "f<T>(x)" and "f<T>`x`"
+            Token::OpenParen | Token::NoSubstitutionTemplateLiteral | Token::TemplateHead => true,
+
+            // "a < b > < c" and "a < b > +c" are comparisons
+            Token::LessThan | Token::GreaterThan | Token::Plus | Token::Minus => false,
+
+            // "f<T>;" and "f<T> == g" are instantiation expressions, but the
+            // "c" in "a < b > c" must be the right side of a comparison
+            _ => {
+                self.lexer.has_newline_before
+                    || binary_operator(self.lexer.token).is_some()
+                    || self.lexer.is_contextual_keyword("as")
+                    || self.lexer.is_contextual_keyword("satisfies")
+                    || !self.is_start_of_expr()
+            }
+        }
+    }
+
+    fn is_start_of_expr(&self) -> bool {
+        self.is_identifier()
+            || matches!(
+                self.lexer.token,
+                Token::This
+                    | Token::Super
+                    | Token::Null
+                    | Token::True
+                    | Token::False
+                    | Token::NumericLiteral
+                    | Token::BigIntegerLiteral
+                    | Token::StringLiteral
+                    | Token::NoSubstitutionTemplateLiteral
+                    | Token::TemplateHead
+                    | Token::PrivateIdentifier
+                    | Token::OpenParen
+                    | Token::OpenBracket
+                    | Token::OpenBrace
+                    | Token::Function
+                    | Token::Class
+                    | Token::New
+                    | Token::Import
+                    | Token::Slash
+                    | Token::SlashEquals
+                    | Token::Plus
+                    | Token::Minus
+                    | Token::Tilde
+                    | Token::Exclamation
+                    | Token::Delete
+                    | Token::Typeof
+                    | Token::Void
+                    | Token::PlusPlus
+                    | Token::MinusMinus
+                    | Token::LessThan
+                    | Token::Yield
+            )
+    }
+
+    // Type arguments, "!", "as", and "satisfies" after an expression only
+    // matter for type checking. Returns whether one of them was skipped.
+    fn skip_typescript_suffix(&mut self, level: Operator) -> PResult<bool> {
+        match self.lexer.token {
+            Token::LessThan => Ok(self.try_skip_typescript_type_arguments_in_expr()),
+
+            // "a!" asserts that "a" isn't null or undefined
+            Token::Exclamation if !self.lexer.has_newline_before => {
+                self.next()?;
+                Ok(true)
+            }
+
+            // "a as T" and "a satisfies T" bind like a comparison
+            Token::Identifier
+                if !self.lexer.has_newline_before
+                    && level < Operator::Compare
+                    && (self.lexer.is_contextual_keyword("as")
+                        || self.lexer.is_contextual_keyword("satisfies")) =>
+            {
+                self.next()?;
+                self.skip_typescript_type(Operator::Lowest)?;
+                Ok(true)
+            }
+
+            _ => Ok(false),
+        }
+    }
+
+    // "<T>(a: T) => a" is a generic arrow function and "<T>a" is a type cast.
+    // Casts can't be written this way in ".tsx" files since they would look
+    // like elements, and a generic arrow function there needs type parameters
+    // that can't be a tag, like "<T,>" (see "is_typescript_arrow_fn_in_jsx").
+    // This returns None when the "<" starts an element.
+    fn parse_typescript_angle_brackets(&mut self, location: Location) -> PResult<Option<Expr>> {
+        if self.options.jsx.parse {
+            if !self.is_typescript_arrow_fn_in_jsx() {
+                return Ok(None);
+            }
+            self.skip_typescript_type_parameters()?;
+            return self.parse_paren_arrow_fn(location, false).map(Some);
+        }
+
+        if let Some(arrow) = self.try_parse_typescript_generic_arrow_fn(location, false)? {
+            return Ok(Some(arrow));
+        }
+        self.next()?;
+        self.skip_typescript_type(Operator::Lowest)?;
+        self.lexer.expect_greater_than();
+        self.check()?;
+        self.parse_expr(Operator::Prefix).map(Some)
+    }
+
+    // In ".tsx" files "<T>(a) => a" is an element, so the TypeScript compiler
+    // only takes "<" as the start of type parameters when the name after it is
+    // followed by "," or "=", or by an "extends" that can't be an attribute
+    // like in "<T extends>" or "<T extends/>"
+    fn is_typescript_arrow_fn_in_jsx(&mut self) -> bool {
+        let checkpoint = self.lexer.checkpoint();
+        self.lexer.is_log_disabled = true;
+        self.lexer.next();
+        if self.lexer.token == Token::Const {
+            self.lexer.next();
+        }
+        let mut is_arrow = false;
+        if self.is_identifier() {
+            self.lexer.next();
+            is_arrow = match self.lexer.token {
+                Token::Comma | Token::Equals => true,
+                Token::Extends => {
+                    self.lexer.next();
+                    !matches!(
+                        self.lexer.token,
+                        Token::Equals | Token::GreaterThan | Token::Slash
+                    )
+                }
+                _ => false,
+            };
+        }
+        self.lexer.restore(checkpoint);
+        is_arrow
+    }
+
+    // Returns the arrow function if the type parameters at the "<" are
+    // followed by its arguments. Otherwise nothing has been consumed.
+    fn try_parse_typescript_generic_arrow_fn(
+        &mut self,
+        location: Location,
+        is_async: bool,
+    ) -> PResult<Option<Expr>> {
+        let is_arrow = self.try_skip_typescript(|p| {
+            p.skip_typescript_type_parameters()?;
+            Ok(p.lexer.token == Token::OpenParen && p.is_arrow_args_ahead())
+        });
+        if !is_arrow {
+            return Ok(None);
+        }
+        self.parse_paren_arrow_fn(location, is_async).map(Some)
+    }
+
+    // Throws away the symbols and scopes declared since "checkpoint", but
+    // keeps the position of the lexer. Declarations that turn out to only
+    // describe types, like a function without a body, are parsed like the
+    // code they describe and then dropped this way. Symbols can only have
+    // been added to the scopes that enclose the one at the checkpoint, or to
+    // scopes created since, which are removed.
+    fn discard_declarations(&mut self, checkpoint: ParserCheckpoint) {
+        let lexer = self.lexer.checkpoint();
+        let has_syntax_error = self.has_syntax_error;
+        let symbol_count = checkpoint.symbol_count;
+        self.restore(checkpoint);
+        self.lexer.restore(lexer);
+        self.has_syntax_error = has_syntax_error;
+
+        let scopes: Vec<ScopeId> = self.scopes.ancestors(self.current_scope).collect();
+        for scope in scopes {
+            let scope = &mut self.scopes[scope];
+            scope
+                .members
+                .retain(|_, reference| reference.inner < symbol_count);
+            scope
+                .generated
+                .retain(|reference| reference.inner < symbol_count);
+        }
+        self.named_exports
+            .retain(|_, reference| reference.inner < symbol_count);
+        self.import_items
+            .retain(|reference| reference.inner < symbol_count);
+        self.named_imports
+            .retain(|reference, _| reference.inner < symbol_count);
+    }
+
+    // Statements that only exist in TypeScript. Returns None if the current
+    // token doesn't start one, in which case nothing has been consumed.
+    fn parse_typescript_stmt(
+        &mut self,
+        location: Location,
+        is_export: bool,
+    ) -> PResult<Option<Stmt>> {
+        let token = self.lexer.token;
+        match token {
+            // "interface A<T> extends B, C {}"
+            Token::Interface if self.is_name_on_same_line_next() => {
+                self.next()?;
+                self.next()?;
+                self.skip_typescript_type_parameters()?;
+                if self.lexer.token == Token::Extends {
+                    self.next()?;
+                    loop {
+                        self.skip_typescript_type(Operator::Lowest)?;
+                        if self.lexer.token != Token::Comma {
+                            break;
+                        }
+                        self.next()?;
+                    }
+                }
+                self.skip_typescript_object_type()?;
+            }
+
+            Token::Enum => return self.parse_typescript_enum(),
+
+            Token::Identifier => match self.lexer.raw() {
+                // "type A<T> = B"
+                "type" if self.is_name_on_same_line_next() => {
+                    self.next()?;
+                    self.next()?;
+                    self.skip_typescript_type_parameters()?;
+                    self.expect(Token::Equals)?;
+                    self.skip_typescript_type(Operator::Lowest)?;
+                    self.expect_or_insert_semicolon()?;
+                }
+
+                "abstract"
+                    if self.next_token_is(|p| {
+                        p.lexer.token == Token::Class && !p.lexer.has_newline_before
+                    }) =>
+                {
+                    self.next()?;
+                    return self.parse_class_stmt(location, is_export, false).map(Some);
+                }
+
+                "declare" if self.is_declare_ahead() => {
+                    let checkpoint = self.checkpoint();
+                    self.next()?;
+                    self.parse_typescript_declare()?;
+                    self.discard_declarations(checkpoint);
+                }
+
+                "namespace" | "module"
+                    if self.next_token_is(|p| p.is_identifier() && !p.lexer.has_newline_before) =>
+                {
+                    return self
+                        .parse_typescript_namespace(location, is_export)
+                        .map(Some);
+                }
+
+                "module"
+                    if self.next_token_is(|p| {
+                        p.lexer.token == Token::StringLiteral && !p.lexer.has_newline_before
+                    }) =>
+                {
+                    self.add_range_error(
+                        self.lexer.range(),
+                        "TypeScript namespaces are not supported yet".to_owned(),
+                    );
+                    return Err(SyntaxError);
+                }
+
+                _ => return Ok(None),
+            },
+
+            _ => return Ok(None),
+        }
+        Ok(Some(stmt(location, StmtKind::TypeScript)))
+    }
+
+    // "namespace A.B { ... }" is only supported if nothing but types is left in
+    // it once the aliases that aren't used have been removed, since that kind
+    // of namespace has no code (see "remove_type_only_namespaces"). Its name
+    // isn't declared for the same reason.
+    fn parse_typescript_namespace(&mut self, location: Location, is_export: bool) -> PResult<Stmt> {
+        if self.scopes[self.current_scope].kind != ScopeKind::Entry {
+            self.add_range_error(
+                self.lexer.range(),
+                "A namespace declaration is only allowed at the top level of a namespace or \
+                 module"
+                    .to_owned(),
+            );
+            return Err(SyntaxError);
+        }
+        self.next()?;
+        let name_location = self.lexer.start;
+        let name = self.expect_identifier()?;
+        while self.lexer.token == Token::Dot {
+            self.next()?;
+            self.expect_identifier()?;
+        }
+
+        self.push_scope_for_parse_pass(ScopeKind::Entry);
+        let stmts = self.parse_block_body()?;
+        self.pop_scope();
+
+        let reference = self.new_symbol(SymbolKind::Other, &name);
+        let arg = self.new_symbol(SymbolKind::Other, &name);
+        Ok(stmt(
+            location,
+            StmtKind::Namespace {
+                name: LocationRef {
+                    loc: name_location,
+                    reference,
+                },
+                arg,
+                stmts,
+                is_export,
+            },
+        ))
+    }
+
+    // Enums need code to be generated for them, which isn't done yet
+    fn parse_typescript_enum<T>(&mut self) -> PResult<T> {
+        self.add_range_error(
+            self.lexer.range(),
+            "TypeScript enums are not supported yet".to_owned(),
+        );
+        Err(SyntaxError)
+    }
+
+    fn is_declare_ahead(&mut self) -> bool {
+        self.next_token_is(|p| {
+            !p.lexer.has_newline_before
+                && (matches!(
+                    p.lexer.token,
+                    Token::Var
+                        | Token::Let
+                        | Token::Const
+                        | Token::Function
+                        | Token::Class
+                        | Token::Enum
+                        | Token::Interface
+                ) || p.lexer.token == Token::Identifier
+                    && matches!(
+                        p.lexer.raw(),
+                        "abstract" | "async" | "global" | "module" | "namespace" | "type"
+                    ))
+        })
+    }
+
+    // Called after "declare". The declaration is parsed like one without it
+    // and then thrown away by the caller, since it describes something that
+    // exists somewhere else. Enums, namespaces, and modules are skipped as a
+    // whole instead since they aren't supported otherwise.
+    fn parse_typescript_declare(&mut self) -> PResult<()> {
+        let is_block = match self.lexer.token {
+            Token::Enum => true,
+            Token::Const => self.next_token_is(|p| p.lexer.token == Token::Enum),
+            Token::Identifier => matches!(self.lexer.raw(), "global" | "module" | "namespace"),
+            _ => false,
+        };
+        if !is_block {
+            // "declare const a: A" has no initializer
+            if matches!(self.lexer.token, Token::Var | Token::Let | Token::Const) {
+                self.next()?;
+                self.parse_and_declare_decls(SymbolKind::Other)?;
+                return self.expect_or_insert_semicolon();
+            }
+            self.parse_stmt()?;
+            return Ok(());
+        }
+
+        // "declare module 'path';" has no body
+        loop {
+            let token = self.lexer.token;
+            if matches!(
+                token,
+                Token::OpenBrace | Token::Semicolon | Token::EndOfFile
+            ) || (self.lexer.has_newline_before && token != Token::Dot)
+            {
+                break;
+            }
+            self.next()?;
+        }
+        if self.lexer.token != Token::OpenBrace {
+            return self.expect_or_insert_semicolon();
+        }
+        self.skip_typescript_block()
+    }
+
+    // Skips over the braces at the current token and everything inside them
+    fn skip_typescript_block(&mut self) -> PResult<()> {
+        let mut depth = 0;
+
+        // The brace depth that each template substitution started at
+        let mut template_depths = vec![];
+        loop {
+            match self.lexer.token {
+                Token::EndOfFile => return self.unexpected(),
+                Token::OpenBrace => depth += 1,
+                Token::TemplateHead => template_depths.push(depth),
+                Token::CloseBrace if template_depths.last() == Some(&depth) => {
+                    self.lexer.rescan_close_brace_as_template_token();
+                    self.check()?;
+                    if self.lexer.token != Token::TemplateMiddle {
+                        template_depths.pop();
+                    }
+                }
+                Token::CloseBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.next();
+                    }
+                }
+                _ => {}
+            }
+            self.next()?;
+        }
+    }
+
+    // "import a = require('path')" and "import a = b.c". The first is a
+    // CommonJS import, which becomes a "const" with a "require()" call. The
+    // second is an alias, which is removed if it's never used since it may
+    // be an alias of a type (see "lower_typescript_equals").
+    fn parse_typescript_import_equals(&mut self, is_export: bool) -> PResult<StmtKind> {
+        let loc = self.lexer.start;
+        let name = self.expect_identifier()?;
+        let reference = self.declare_symbol(SymbolKind::Other, loc, &name);
+        if is_export {
+            self.named_exports.insert(name, reference);
+        }
+        self.expect(Token::Equals)?;
+
+        let location = self.lexer.start;
+        let is_require = self.lexer.is_contextual_keyword("require")
+            && self.next_token_is(|p| p.lexer.token == Token::OpenParen);
+        let value = if is_require {
+            self.next()?;
+            self.expect(Token::OpenParen)?;
+            let path_location = self.lexer.start;
+            if self.lexer.token != Token::StringLiteral {
+                self.lexer.expected(Token::StringLiteral);
+                return Err(SyntaxError);
+            }
+            let path = std::mem::take(&mut self.lexer.string_literal);
+            self.next()?;
+            self.expect(Token::CloseParen)?;
+            let target = self.identifier_expr(location, "require");
+            expr(
+                location,
+                ExprKind::Call {
+                    target,
+                    args: vec![expr(path_location, ExprKind::String { value: path })],
+                    optional_chain: OptionalChain::None,
+                    is_parenthesized: false,
+                    is_direct_eval: false,
+                    can_be_unwrapped_if_unused: false,
+                },
+            )
+        } else {
+            let name = self.expect_identifier()?;
+            let mut value = self.identifier_expr(location, &name);
+            while self.lexer.token == Token::Dot {
+                self.next()?;
+                let (name, name_location) = self.parse_dot_name()?;
+                value = expr(
+                    location,
+                    ExprKind::Dot {
+                        target: value,
+                        name,
+                        name_location,
+                        optional_chain: OptionalChain::None,
+                        is_parenthesized: false,
+                    },
+                );
+            }
+            value
+        };
+        self.expect_or_insert_semicolon()?;
+
+        Ok(StmtKind::Local {
+            decls: vec![Decl {
+                binding: Binding::identifier(loc, reference),
+                value: Some(value),
+            }],
+            kind: LocalKind::Const,
+            is_export,
+            was_ts_import_equals_in_namespace: !is_require,
+        })
+    }
+
+    // "import {type A}" and "export {type A as B}" name a type, which is left
+    // out of the clause. Returns whether an item was skipped.
+    fn skip_typescript_type_clause_item(&mut self) -> PResult<bool> {
+        let is_type_only = self.options.typescript.parse
+            && self.lexer.is_contextual_keyword("type")
+            && self.next_token_is(|p| {
+                p.is_identifier_or_keyword() && !p.lexer.is_contextual_keyword("as")
+            });
+        if !is_type_only {
+            return Ok(false);
+        }
+        self.next()?;
+        self.next()?;
+        if self.lexer.is_contextual_keyword("as") {
+            self.next()?;
+            if !self.is_identifier_or_keyword() {
+                return self.unexpected();
+            }
+            self.next()?;
+        }
+        Ok(true)
+    }
+
+    // Constructor arguments with a modifier like "private" are also fields of
+    // the class, which the constructor assigns when it's called. That happens
+    // after "super()" if the constructor calls it.
+    fn add_typescript_ctor_field_assignments(&mut self, function: &mut Function) {
+        let mut assignments = vec![];
+        for arg in function
+            .args
+            .iter()
+            .filter(|arg| arg.is_typescript_ctor_field)
+        {
+            let reference = match arg.binding.data.as_ref() {
+                BindingKind::Identifier { reference } => *reference,
+                _ => continue,
+            };
+            let location = arg.binding.location;
+            let name = self.symbols[reference.inner].name.clone();
+            let target = expr(
+                location,
+                ExprKind::Dot {
+                    target: expr(location, ExprKind::This),
+                    name: name.clone(),
+                    name_location: location,
+                    optional_chain: OptionalChain::None,
+                    is_parenthesized: false,
+                },
+            );
+            let value = ExprKind::Binary {
+                op_code: OperatorCode::BinOpAssign,
+                left: target,
+                right: self.identifier_expr(location, &name),
+            };
+            assignments.push(stmt(
+                location,
+                StmtKind::Expr {
+                    value: expr(location, value),
+                },
+            ));
+        }
+
+        let stmts = &mut function.body.stmts;
+        let super_call = stmts.iter().position(|stmt| match stmt.data.as_ref() {
+            StmtKind::Expr { value } => matches!(
+                value.data.as_ref(),
+                ExprKind::Call { target, .. } if matches!(target.data.as_ref(), ExprKind::Super)
+            ),
+            _ => false,
+        });
+        let index = match super_call {
+            Some(index) => index + 1,
+            None => stmts
+                .iter()
+                .position(|stmt| {
+                    !matches!(
+                        stmt.data.as_ref(),
+                        StmtKind::Directive { .. } | StmtKind::Comment { .. }
+                    )
+                })
+                .unwrap_or(stmts.len()),
+        };
+        stmts.splice(index..index, assignments);
+    }
+
+    fn into_ast(mut self, mut stmts: Vec<Stmt>) -> AST {
+        self.current_scope = ScopeTree::ROOT;
+
+        // Pass 2: bind identifiers, one top-level statement at a time so that
+        // what each statement uses and declares can be tracked for its part
+        let mut infos = vec![];
+        for (i, stmt) in stmts.iter_mut().enumerate() {
+            self.part_index = if self.options.is_bundling {
+                i as u32
+            } else {
+                0
+            };
+            self.visit_stmt(stmt);
+            infos.push(std::mem::take(&mut self.part));
+        }
+        self.add_css_module_import_items(&mut stmts);
+        debug_assert_eq!(self.next_scope_in_order, self.scopes_in_order.len());
+        let import_meta_shim = self.import_meta_shim();
+
+        let mut symbols = SymbolMap::new(self.source_index + 1);
+        symbols.outer[self.source_index] = std::mem::take(&mut self.symbols);
+        check_assignments(&stmts, &symbols, self.source, self.log);
+
+        // Without bundling the whole file is one part
+        let mut parts = vec![];
+        if self.options.is_bundling {
+            for (stmt, info) in stmts.into_iter().zip(infos) {
+                parts.push(new_part(vec![stmt], info));
+            }
+        } else {
+            let mut merged = PartInfo::default();
+            for info in infos {
+                merged.import_paths.extend(info.import_paths);
+                merged.declared_symbols.extend(info.declared_symbols);
+                for (reference, count) in info.use_count_estimates {
+                    *merged.use_count_estimates.entry(reference).or_insert(0) += count;
+                }
+            }
+            parts.push(new_part(stmts, merged));
+        }
+
+        // The remaining passes transform the bound tree
+        let strict = self.scopes[ScopeTree::ROOT].strict_mode;
+        let mut temp_refs = TempRefs::new(self.source_index);
+        for part in &mut parts {
+            drop_stmts(&mut part.stmts, &symbols, &self.options.drop);
+            if self.options.lower.target < Target::ES2015 {
+                lower_block_level_functions(&mut part.stmts, strict);
+            }
+            lower_stmts(
+                &mut part.stmts,
+                &mut symbols,
+                &mut temp_refs,
+                self.source,
+                self.log,
+                &self.options.lower,
+            );
+        }
+        if self.options.is_bundling {
+            for part in &mut parts {
+                part.can_be_removed_if_unused = part
+                    .stmts
+                    .iter()
+                    .all(|stmt| stmt_can_be_removed_if_unused(stmt, &symbols));
+            }
+        }
+        if let Some(decls) = temp_refs.take_module_decls(0) {
+            // Other parts may use these temporaries without declaring them
+            let part = &mut parts[0];
+            part.can_be_removed_if_unused = false;
+            let index = part
+                .stmts
+                .iter()
+                .position(|stmt| {
+                    !matches!(
+                        stmt.data.as_ref(),
+                        StmtKind::Directive { .. } | StmtKind::Comment { .. }
+                    )
+                })
+                .unwrap_or(part.stmts.len());
+            part.stmts.insert(index, decls);
+        }
+
+        // The shim for "import.meta" must exist before any code that uses it
+        // runs, so it goes at the top too
+        if let Some(shim) = import_meta_shim {
+            let part = &mut parts[0];
+            part.can_be_removed_if_unused = false;
+            part.declared_symbols.push(DeclaredSymbol {
+                reference: self.import_meta_ref,
+                is_top_level: true,
+            });
+            let index = part
+                .stmts
+                .iter()
+                .position(|stmt| {
+                    !matches!(
+                        stmt.data.as_ref(),
+                        StmtKind::Directive { .. } | StmtKind::Comment { .. }
+                    )
+                })
+                .unwrap_or(part.stmts.len());
+            part.stmts.insert(index, shim);
+        }
+
+        let mut top_level_symbol_to_parts: HashMap<Reference, Vec<u32>> = HashMap::new();
+        for (i, part) in parts.iter().enumerate() {
+            for declared in part.declared_symbols.iter().filter(|d| d.is_top_level) {
+                let part_indices = top_level_symbol_to_parts
+                    .entry(declared.reference)
+                    .or_default();
+                if part_indices.last() != Some(&(i as u32)) {
+                    part_indices.push(i as u32);
+                }
+            }
+        }
+
+        let wrapper_name = format!(
+            "require_{}",
+            generate_non_unique_name_from_path(&self.source.pretty_path)
+        );
+        let wrapper_ref = Reference::new(self.source_index, symbols.outer[self.source_index].len());
+        symbols.outer[self.source_index].push(Symbol::new(SymbolKind::Other, wrapper_name));
+
+        let mut ast = AST {
+            was_typescript: self.options.typescript.parse,
+            has_top_level_return: self.has_top_level_return,
+            uses_exports_ref: self.uses_exports_ref,
+            uses_module_ref: self.uses_module_ref,
+            has_es6_imports: self.has_es6_imports,
+            has_es6_exports: self.has_es6_exports,
+            hash_bang: self.hash_bang,
+            parts,
+            symbols,
+            scopes: self.scopes,
+            exports_ref: self.exports_ref,
+            module_ref: self.module_ref,
+            wrapper_ref,
+            named_imports: self.named_imports,
+            named_exports: self.named_exports,
+            top_level_symbol_to_parts,
+            export_stars: self.export_stars,
+        };
+        if ast.was_typescript {
+            for part in &mut ast.parts {
+                lower_typescript_equals(
+                    &mut part.stmts,
+                    &ast.symbols,
+                    ast.module_ref,
+                    self.options.output_format,
+                    self.source,
+                    self.log,
+                );
+            }
+        }
+        elide_type_only_imports(&mut ast, self.source, self.log, &self.options.typescript);
+        ast
+    }
+
+    fn push_scope_for_visit_pass(&mut self) {
+        self.current_scope = self.scopes_in_order[self.next_scope_in_order];
+        self.next_scope_in_order += 1;
+    }
+
+    fn find_symbol(&mut self, name: &str) -> Reference {
+        let mut is_inside_with_scope = false;
+        let mut found = None;
+        for scope in self.scopes.ancestors(self.current_scope) {
+            let data = &self.scopes[scope];
+
+            // Track if we're inside a "with" statement body
+            if data.kind == ScopeKind::With {
+                is_inside_with_scope = true;
+            }
+
+            if let Some(&reference) = data.members.get(name) {
+                found = Some(reference);
+                break;
+            }
+        }
+
+        // Names that aren't declared anywhere refer to globals
+        let reference = found.unwrap_or_else(|| {
+            let reference = self.new_symbol(SymbolKind::Unbound, name);
+            self.scopes[ScopeTree::ROOT]
+                .members
+                .insert(name.to_owned(), reference);
+            reference
+        });
+
+        // If the name was found through a "with" statement body, it may also
+        // refer to a property of the "with" object, so it can't be renamed
+        if is_inside_with_scope {
+            self.symbols[reference.inner].must_not_be_renamed = true;
+        }
+
+        self.record_usage(reference);
+        reference
+    }
+
+    // Private names are only visible inside the class bodies that declare
+    // them, so there's no global to fall back to
+    fn find_private_symbol(&mut self, location: Location, name: &str) -> Reference {
+        for scope in self.scopes.ancestors(self.current_scope) {
+            if let Some(&reference) = self.scopes[scope].members.get(name) {
+                return reference;
+            }
+        }
+
+        self.add_range_error(
+            self.source.range_of_identifier(location),
+            format!(
+                "Private name \"{}\" must be declared in an enclosing class",
+                name
+            ),
+        );
+        self.new_symbol(SymbolKind::Unbound, name)
+    }
+
+    fn record_usage(&mut self, reference: Reference) {
+        self.symbols[reference.inner].use_count_estimate += 1;
+        *self.part.use_count_estimates.entry(reference).or_insert(0) += 1;
+
+        if reference == self.exports_ref {
+            self.uses_exports_ref = true;
+        } else if reference == self.module_ref {
+            self.uses_module_ref = true;
+        }
+
+        if let Some(named_import) = self.named_imports.get_mut(&reference) {
+            if named_import.local_parts_with_uses.last() != Some(&self.part_index) {
+                named_import.local_parts_with_uses.push(self.part_index);
+            }
+        }
+    }
+
+    // Undoes "record_usage" for a use that was replaced by another one
+    fn ignore_usage(&mut self, reference: Reference) {
+        let symbol = &mut self.symbols[reference.inner];
+        symbol.use_count_estimate = symbol.use_count_estimate.saturating_sub(1);
+        if let Some(count) = self.part.use_count_estimates.get_mut(&reference) {
+            *count -= 1;
+            if *count == 0 {
+                self.part.use_count_estimates.remove(&reference);
+                if let Some(named_import) = self.named_imports.get_mut(&reference) {
+                    if named_import.local_parts_with_uses.last() == Some(&self.part_index) {
+                        named_import.local_parts_with_uses.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    fn record_declared_symbol(&mut self, reference: Reference) {
+        let module_scope = &self.scopes[ScopeTree::ROOT];
+        let name = &self.symbols[reference.inner].name;
+        let is_top_level = module_scope.members.get(name) == Some(&reference)
+            || module_scope.generated.contains(&reference);
+        self.part.declared_symbols.push(DeclaredSymbol {
+            reference,
+            is_top_level,
+        });
+    }
+
+    fn record_import_path(&mut self, path: &Path, kind: ImportKind, does_not_use_exports: bool) {
+        self.part.import_paths.push(ImportPath {
+            path: path.clone(),
+            kind,
+            does_not_use_exports,
+        });
+    }
+
+    fn visit_stmts(&mut self, stmts: &mut [Stmt]) {
+        for stmt in stmts {
+            self.visit_stmt(stmt);
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        ensure_sufficient_stack(|| self.visit_stmt_inner(stmt))
+    }
+
+    fn visit_stmt_inner(&mut self, stmt: &mut Stmt) {
+        match stmt.data.as_mut() {
+            StmtKind::Empty
+            | StmtKind::TypeScript
+            | StmtKind::SyntaxError
+            | StmtKind::Debugger
+            | StmtKind::Directive { .. }
+            | StmtKind::Comment { .. }
+            | StmtKind::Enum { .. }
+            | StmtKind::Catch(_)
+            | StmtKind::Finally(_) => {}
+
+            StmtKind::Block { stmts } | StmtKind::Namespace { stmts, .. } => {
+                self.push_scope_for_visit_pass();
+                self.visit_stmts(stmts);
+                self.pop_scope();
+            }
+
+            StmtKind::Import {
+                namespace_symbol,
+                default_name,
+                path,
+            } => {
+                if let Some(name) = default_name {
+                    self.record_declared_symbol(name.reference);
+                }
+                let does_not_use_exports = match namespace_symbol {
+                    NamespaceSymbol::Star { namespace_ref, .. } => {
+                        self.record_declared_symbol(*namespace_ref);
+                        false
+                    }
+                    NamespaceSymbol::Clause { items } => {
+                        for item in items.iter() {
+                            self.record_declared_symbol(item.name.reference);
+                        }
+                        items.is_empty() && default_name.is_none()
+                    }
+                };
+                self.record_import_path(path, ImportKind::Stmt, does_not_use_exports);
+            }
+
+            StmtKind::ExportClause { items } => {
+                for item in items {
+                    let name = self
+                        .load_name_from_ref(item.name.reference)
+                        .unwrap_or_default()
+                        .to_owned();
+                    let reference = self.find_symbol(&name);
+                    item.name.reference = reference;
+                    self.named_exports.insert(item.alias.clone(), reference);
+                    if let Some(named_import) = self.named_imports.get_mut(&reference) {
+                        named_import.is_exported = true;
+                    }
+                }
+            }
+
+            StmtKind::ExportFrom { items, path, .. } => {
+                for item in items.iter() {
+                    self.record_declared_symbol(item.name.reference);
+                }
+                self.record_import_path(path, ImportKind::Stmt, false);
+            }
+
+            StmtKind::ExportStar { item, path } => {
+                if let Some(item) = item {
+                    self.record_declared_symbol(item.name.reference);
+                }
+                self.record_import_path(path, ImportKind::Stmt, false);
+            }
+
+            StmtKind::ExportDefault {
+                default_name,
+                value,
+            } => {
+                self.record_declared_symbol(default_name.reference);
+                match value {
+                    ExprOrStmt::Expr(value) => self.visit_expr(value),
+                    ExprOrStmt::Stmt(stmt) => self.visit_stmt(stmt),
+                }
+            }
+
+            // This becomes an assignment to "module.exports" once the format
+            // is known (see "lower_typescript_equals")
+            StmtKind::ExportEquals { value } => {
+                self.record_usage(self.module_ref);
+                self.visit_expr(value);
+            }
+
+            StmtKind::Expr { value } | StmtKind::Throw { value } => self.visit_expr(value),
+
+            StmtKind::Return { value } => {
+                if let Some(value) = value {
+                    self.visit_expr(value);
+                }
+            }
+
+            StmtKind::Function { function, .. } => {
+                if let Some(name) = &function.name {
+                    self.record_declared_symbol(name.reference);
+                }
+                self.visit_fn(function);
+            }
+
+            StmtKind::Class { class, .. } => {
+                if let Some(name) = &class.name {
+                    self.record_declared_symbol(name.reference);
+                }
+                self.visit_class(class);
+            }
+
+            StmtKind::Label { stmt, .. } => {
+                self.push_scope_for_visit_pass();
+                self.visit_stmt(stmt);
+                self.pop_scope();
+            }
+
+            StmtKind::If { test, yes, no } => {
+                self.visit_expr(test);
+                self.visit_stmt(yes);
+                if let Some(no) = no {
+                    self.visit_stmt(no);
+                }
+
+                // "if (true) a(); else b();" => "a();"
+                if let Some(boolean) = to_boolean(test) {
+                    let (taken, dead) = if boolean {
+                        (Some(&mut *yes), no.as_mut())
+                    } else {
+                        (no.as_mut(), Some(&mut *yes))
+                    };
+                    if !dead.is_some_and(|dead| has_hoisted_decls(dead)) {
+                        let location = stmt.location;
+                        let taken = match taken {
+                            Some(taken) => {
+                                std::mem::replace(taken, self::stmt(location, StmtKind::Empty))
+                            }
+                            None => self::stmt(location, StmtKind::Empty),
+                        };
+                        *stmt = match taken.data.as_ref() {
+                            // Declarations can't be statements of their own
+                            // in every position an "if" can be in
+                            StmtKind::Local { .. }
+                            | StmtKind::Function { .. }
+                            | StmtKind::Class { .. } => {
+                                self::stmt(location, StmtKind::Block { stmts: vec![taken] })
+                            }
+                            _ => taken,
+                        };
+                    }
+                }
+            }
+
+            StmtKind::For {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                self.push_scope_for_visit_pass();
+                if let Some(init) = init {
+                    self.visit_stmt(init);
+                }
+                if let Some(test) = test {
+                    self.visit_expr(test);
+                }
+                if let Some(update) = update {
+                    self.visit_expr(update);
+                }
+                self.visit_stmt(body);
+                self.pop_scope();
+            }
+
+            StmtKind::ForIn { init, value, body }
+            | StmtKind::ForOf {
+                init, value, body, ..
+            } => {
+                self.push_scope_for_visit_pass();
+                self.visit_stmt(init);
+                self.visit_expr(value);
+                self.visit_stmt(body);
+                self.pop_scope();
+            }
+
+            StmtKind::DoWhile { body, test } => {
+                self.visit_stmt(body);
+                self.visit_expr(test);
+            }
+
+            StmtKind::While { test, body } => {
+                self.visit_expr(test);
+                self.visit_stmt(body);
+            }
+
+            StmtKind::With { value, body, .. } => {
+                self.visit_expr(value);
+                self.push_scope_for_visit_pass();
+                self.visit_stmt(body);
+                self.pop_scope();
+            }
+
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.push_scope_for_visit_pass();
+                self.visit_stmts(body);
+                self.pop_scope();
+
+                if let Some(catch) = catch {
+                    self.push_scope_for_visit_pass();
+                    if let Some(binding) = &mut catch.binding {
+                        self.visit_binding(binding);
+                    }
+                    self.visit_stmts(&mut catch.body);
+                    self.pop_scope();
+                }
+
+                if let Some(finally) = finally {
+                    self.push_scope_for_visit_pass();
+                    self.visit_stmts(&mut finally.stmts);
+                    self.pop_scope();
+                }
+            }
+
+            StmtKind::Switch { test, cases, .. } => {
+                self.visit_expr(test);
+                self.push_scope_for_visit_pass();
+                for case in cases {
+                    if let Some(value) = &mut case.value {
+                        self.visit_expr(value);
+                    }
+                    self.visit_stmts(&mut case.body);
+                }
+                self.pop_scope();
+            }
+
+            StmtKind::Local { decls, .. } => {
+                for decl in decls {
+                    self.visit_binding(&mut decl.binding);
+                    if let Some(value) = &mut decl.value {
+                        self.visit_expr(value);
+                    }
+                }
+            }
+
+            StmtKind::Break { name } => {
+                if let Some(name) = name {
+                    self.visit_label_name(name, false);
+                }
+            }
+            StmtKind::Continue { name } => {
+                if let Some(name) = name {
+                    self.visit_label_name(name, true);
+                }
+            }
+        }
+    }
+
+    // Labels can't be referenced across function boundaries, and "continue"
+    // can only go to a label on a loop
+    fn visit_label_name(&mut self, name: &mut LocationRef, is_continue: bool) {
+        let text = self
+            .load_name_from_ref(name.reference)
+            .unwrap_or_default()
+            .to_owned();
+        for scope in self.scopes.ancestors(self.current_scope) {
+            let data = &self.scopes[scope];
+            if data.kind == ScopeKind::Label && self.symbols[data.label_ref.inner].name == text {
+                if is_continue && !self.loop_labels.contains(&scope) {
+                    self.add_range_error(
+                        self.source.range_of_identifier(name.loc),
+                        format!("Cannot continue to label \"{}\"", text),
+                    );
+                }
+                name.reference = data.label_ref;
+                self.symbols[data.label_ref.inner].use_count_estimate += 1;
+                return;
+            }
+            if data.kind.stops_hoisting() {
+                break;
+            }
+        }
+
+        self.add_range_error(
+            self.source.range_of_identifier(name.loc),
+            format!("There is no containing label named \"{}\"", text),
+        );
+        name.reference = INVALID_REF;
+    }
+
+    // The identifiers in bindings were declared in the first pass, but default
+    // values are expressions that still need to be bound
+    fn visit_binding(&mut self, binding: &mut Binding) {
+        ensure_sufficient_stack(|| self.visit_binding_inner(binding))
+    }
+
+    fn visit_binding_inner(&mut self, binding: &mut Binding) {
+        match binding.data.as_mut() {
+            BindingKind::Missing => {}
+            BindingKind::Identifier { reference } => self.record_declared_symbol(*reference),
+            BindingKind::Array { items, .. } => {
+                for item in items {
+                    self.visit_binding(&mut item.binding);
+                    if let Some(default_value) = &mut item.default_value {
+                        self.visit_expr(default_value);
+                    }
+                }
+            }
+            BindingKind::Object { properties } => {
+                for property in properties {
+                    if property.is_computed {
+                        self.visit_expr(&mut property.key);
+                    }
+                    self.visit_binding(&mut property.value);
+                    if let Some(default_value) = &mut property.default_value {
+                        self.visit_expr(default_value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_args(&mut self, args: &mut [Arg]) {
+        for arg in args {
+            self.visit_binding(&mut arg.binding);
+            if let Some(default) = &mut arg.default_ {
+                self.visit_expr(default);
+            }
+        }
+    }
+
+    fn visit_fn(&mut self, function: &mut Function) {
+        let old_is_this_nested = std::mem::replace(&mut self.is_this_nested, true);
+        self.push_scope_for_visit_pass();
+        self.visit_args(&mut function.args);
+        self.push_scope_for_visit_pass();
+        self.visit_stmts(&mut function.body.stmts);
+        self.pop_scope();
+        self.pop_scope();
+        self.is_this_nested = old_is_this_nested;
+    }
+
+    fn visit_class(&mut self, class: &mut Class) {
+        self.push_scope_for_visit_pass();
+        if let Some(extends) = &mut class.extends {
+            self.visit_expr(extends);
+        }
+        let old_is_this_nested = std::mem::replace(&mut self.is_this_nested, true);
+        for property in &mut class.properties {
+            self.visit_property(property);
+        }
+        self.is_this_nested = old_is_this_nested;
+        self.pop_scope();
+    }
+
+    fn visit_property(&mut self, property: &mut Property) {
+        if property.is_computed {
+            self.visit_expr(&mut property.key);
+        }
+        if let Some(value) = &mut property.value {
+            self.visit_expr(value);
+        }
+        if let Some(initializer) = &mut property.initializer {
+            self.visit_expr(initializer);
+        }
+    }
+
+    fn visit_exprs(&mut self, exprs: &mut [Expr]) {
+        for expr in exprs {
+            self.visit_expr(expr);
+        }
+    }
+
+    // Replaces a global name or a property chain on one with its define
+    fn substitute_define(&mut self, expr: &mut Expr) {
+        if self.options.defines.is_empty() {
+            return;
+        }
+        let name = match self.dotted_global_name(expr) {
+            Some(name) => name,
+            None => return,
+        };
+        let value = match self.options.defines.get(&name) {
+            Some(value) => value,
+            None => return,
+        };
+
+        let location = expr.location;
+        *expr = match value {
+            DefineValue::Null => self::expr(location, ExprKind::Null),
+            DefineValue::Boolean(value) => {
+                self::expr(location, ExprKind::Boolean { value: *value })
+            }
+            DefineValue::Number(value) => self::expr(location, ExprKind::Number { value: *value }),
+            DefineValue::String(value) => self::expr(
+                location,
+                ExprKind::String {
+                    value: string_to_utf16(value),
+                },
+            ),
+            DefineValue::Name(name) => {
+                let mut parts = name.split('.');
+                let first = parts.next().unwrap_or_default();
+                let reference = self.find_symbol(first);
+                let mut value = self::expr(location, ExprKind::Identifier { reference });
+                for part in parts {
+                    value = self::expr(
+                        location,
+                        ExprKind::Dot {
+                            target: value,
+                            name: part.to_owned(),
+                            name_location: location,
+                            optional_chain: OptionalChain::None,
+                            is_parenthesized: false,
+                        },
+                    );
+                }
+                value
+            }
+        };
+    }
+
+    // The globals "Infinity" and "NaN" can't be changed, so they're numbers
+    // unless something in this file has the same name. Names that may be a
+    // property of the object of a "with" statement are left alone.
+    fn substitute_global_number(&mut self, expr: &mut Expr) {
+        let symbol = match expr.data.as_ref() {
+            ExprKind::Identifier { reference } => &self.symbols[reference.inner],
+            _ => return,
+        };
+        if symbol.kind != SymbolKind::Unbound || symbol.must_not_be_renamed {
+            return;
+        }
+        let value = match symbol.name.as_str() {
+            "Infinity" => f64::INFINITY,
+            "NaN" => f64::NAN,
+            _ => return,
+        };
+        *expr.data = ExprKind::Number { value };
+    }
+
+    // "process.env.NODE_ENV" => Some("process.env.NODE_ENV") if "process"
+    // isn't declared anywhere in this file
+    fn dotted_global_name(&self, expr: &Expr) -> Option<String> {
+        ensure_sufficient_stack(|| self.dotted_global_name_inner(expr))
+    }
+
+    fn dotted_global_name_inner(&self, expr: &Expr) -> Option<String> {
+        match expr.data.as_ref() {
+            ExprKind::Identifier { reference } => {
+                let symbol = &self.symbols[reference.inner];
+                if symbol.kind == SymbolKind::Unbound {
+                    Some(symbol.name.clone())
+                } else {
+                    None
+                }
+            }
+            ExprKind::Dot {
+                target,
+                name,
+                optional_chain: OptionalChain::None,
+                ..
+            } => {
+                let mut dotted = self.dotted_global_name(target)?;
+                dotted.push('.');
+                dotted.push_str(name);
+                Some(dotted)
+            }
+            _ => None,
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        ensure_sufficient_stack(|| self.visit_expr_inner(expr))
+    }
+
+    fn visit_expr_inner(&mut self, expr: &mut Expr) {
+        let location = expr.location;
+        let is_assign_target = std::mem::take(&mut self.is_assign_target);
+
+        match expr.data.as_mut() {
+            ExprKind::Identifier { reference } => {
+                if let Some(name) = self.load_name_from_ref(*reference) {
+                    let name = name.to_owned();
+                    let found = self.find_symbol(&name);
+                    if found == self.exports_ref || found == self.module_ref {
+                        self.warn_about_commonjs_variable(location, &name);
+                    }
+                    *expr.data = if self.import_items.contains(&found) {
+                        ExprKind::ImportIdentifier { reference: found }
+                    } else {
+                        ExprKind::Identifier { reference: found }
+                    };
+                }
+                if !is_assign_target {
+                    self.substitute_define(expr);
+                    self.substitute_global_number(expr);
+                }
+            }
+
+            ExprKind::PrivateIdentifier { reference } => {
+                if let Some(name) = self.load_name_from_ref(*reference) {
+                    let name = name.to_owned();
+                    *reference = self.find_private_symbol(location, &name);
+                }
+            }
+
+            ExprKind::Dot { target, name, .. } => {
+                if name == "url" && matches!(target.data.as_ref(), ExprKind::ImportMeta) {
+                    self.lower_import_meta(target, true);
+                } else {
+                    self.visit_expr(target);
+                }
+                if !is_assign_target {
+                    match self.css_module_item(target, name) {
+                        Some(reference) => *expr.data = ExprKind::ImportIdentifier { reference },
+                        None => self.substitute_define(expr),
+                    }
+                }
+            }
+
+            ExprKind::ImportMeta => self.lower_import_meta(expr, false),
+
+            ExprKind::Array { items } => self.visit_exprs(items),
+
+            ExprKind::Unary { op_code, value } => {
+                self.is_assign_target = op_code.is_unary_update();
+                self.visit_expr(value);
+                fold_constant(expr);
+            }
+
+            ExprKind::Spread { value }
+            | ExprKind::Await { value }
+            | ExprKind::Yield { value, .. } => self.visit_expr(value),
+
+            ExprKind::Binary {
+                op_code,
+                left,
+                right,
+            } => {
+                self.is_assign_target = op_code.is_binary_assign();
+                self.visit_expr(left);
+                self.visit_expr(right);
+                self.warn_about_equality_with_nan(*op_code, left, right);
+                fold_constant(expr);
+            }
+
+            ExprKind::Index { target, index, .. } => {
+                self.visit_expr(target);
+                self.visit_expr(index);
+            }
+
+            ExprKind::New { target, args, .. } => {
+                self.visit_expr(target);
+                self.visit_exprs(args);
+            }
+
+            ExprKind::Call {
+                target,
+                args,
+                is_direct_eval,
+                ..
+            } => {
+                self.visit_expr(target);
+                self.visit_exprs(args);
+
+                if let ExprKind::Identifier { reference } = target.data.as_ref() {
+                    let symbol = &self.symbols[reference.inner];
+                    if symbol.kind == SymbolKind::Unbound {
+                        // A direct "eval" can see every variable in scope, so
+                        // none of them can be renamed
+                        if symbol.name == "eval" {
+                            *is_direct_eval = true;
+                            let scopes: Vec<ScopeId> =
+                                self.scopes.ancestors(self.current_scope).collect();
+                            for scope in scopes {
+                                self.scopes[scope].contains_direct_eval = true;
+                            }
+                        }
+
+                        // "require('path')" is an import when bundling
+                        if symbol.name == "require" && self.options.is_bundling && args.len() == 1 {
+                            if let ExprKind::String { value } = args[0].data.as_ref() {
+                                let path = Path {
+                                    loc: args[0].location,
+                                    text: utf16_to_string_lossy(value),
+                                    use_source_index: false,
+                                    source_index: 0,
+                                };
+                                self.record_import_path(&path, ImportKind::Require, false);
+                                *expr.data = ExprKind::Require {
+                                    path,
+                                    is_es6_import: false,
+                                };
+                            }
+                        }
+                    }
+                }
+            }
+
+            ExprKind::RuntimeCall { args, .. } => self.visit_exprs(args),
+
+            ExprKind::Arrow { args, body, .. } => {
+                self.push_scope_for_visit_pass();
+                self.visit_args(args);
+                self.push_scope_for_visit_pass();
+                self.visit_stmts(&mut body.stmts);
+                self.pop_scope();
+                self.pop_scope();
+            }
+
+            ExprKind::Function { function } => self.visit_fn(function),
+
+            ExprKind::Class { class } => self.visit_class(class),
+
+            ExprKind::JSXElement {
+                tag,
+                properties,
+                children,
+            } => {
+                if let Some(tag) = tag {
+                    self.visit_expr(tag);
+                }
+                for property in properties.iter_mut() {
+                    self.visit_property(property);
+                }
+                self.visit_exprs(children);
+                let tag = tag.take();
+                let properties = std::mem::take(properties);
+                let children = std::mem::take(children);
+                *expr.data = self.lower_jsx_element(location, tag, properties, children);
+            }
+
+            ExprKind::Object { properties } => {
+                for property in properties.iter_mut() {
+                    self.visit_property(property);
+                }
+                self.warn_about_duplicate_keys(properties);
+            }
+
+            ExprKind::Template { tag, parts, .. } => {
+                self.visit_expr(tag);
+                for part in parts {
+                    self.visit_expr(&mut part.value);
+                }
+            }
+
+            ExprKind::If { test, yes, no } => {
+                self.visit_expr(test);
+                self.visit_expr(yes);
+                self.visit_expr(no);
+                fold_constant(expr);
+            }
+
+            ExprKind::Import { expr: value } => {
+                self.visit_expr(value);
+
+                // "import('path')" is a dynamic import when bundling
+                if self.options.is_bundling {
+                    if let ExprKind::String { value } = value.data.as_ref() {
+                        let path = Path {
+                            loc: location,
+                            text: utf16_to_string_lossy(value),
+                            use_source_index: false,
+                            source_index: 0,
+                        };
+                        self.record_import_path(&path, ImportKind::Dynamic, false);
+                    }
+                }
+            }
+
+            ExprKind::Boolean { .. }
+            | ExprKind::Super
+            | ExprKind::Null
+            | ExprKind::Undefined
+            | ExprKind::This
+            | ExprKind::NewTarget
+            | ExprKind::ImportIdentifier { .. }
+            | ExprKind::Missing
+            | ExprKind::Number { .. }
+            | ExprKind::BigInt { .. }
+            | ExprKind::String { .. }
+            | ExprKind::RegExp { .. }
+            | ExprKind::Require { .. } => {}
+        }
+    }
+
+    // When bundling, "styles.a" imports "a" on its own if "styles" is the
+    // default import of a CSS modules file. The bundle then only keeps the
+    // local names that are used, instead of the object with all of them.
+    // Returns the import that the property access becomes.
+    fn css_module_item(&mut self, target: &Expr, name: &str) -> Option<Reference> {
+        let default_ref = match target.data.as_ref() {
+            ExprKind::ImportIdentifier { reference } if self.options.is_bundling => *reference,
+            _ => return None,
+        };
+        let named_import = self.named_imports.get(&default_ref)?;
+        if named_import.alias != "default"
+            || loader_for_path(&named_import.import_path.text, self.options)
+                != Some(Loader::LocalCSS)
+        {
+            return None;
+        }
+
+        let key = (default_ref, name.to_owned());
+        let reference = match self.css_module_items.get(&key) {
+            Some(&reference) => reference,
+            None => {
+                let named_import = NamedImport {
+                    alias: name.to_owned(),
+                    alias_loc: target.location,
+                    local_parts_with_uses: vec![],
+                    ..named_import.clone()
+                };
+                let reference = self.new_symbol(SymbolKind::Other, name);
+                self.scopes[ScopeTree::ROOT].generated.push(reference);
+                self.import_items.insert(reference);
+                self.named_imports.insert(reference, named_import);
+                self.css_module_items.insert(key, reference);
+                reference
+            }
+        };
+        self.ignore_usage(default_ref);
+        self.record_usage(reference);
+        Some(reference)
+    }
+
+    // The imports made by "css_module_item" are added to the import statement
+    // too, for when the file isn't bundled after all because it's external
+    fn add_css_module_import_items(&self, stmts: &mut [Stmt]) {
+        if self.css_module_items.is_empty() {
+            return;
+        }
+        for stmt in stmts {
+            if let StmtKind::Import {
+                default_name: Some(default_name),
+                namespace_symbol: NamespaceSymbol::Clause { items },
+                ..
+            } = stmt.data.as_mut()
+            {
+                let mut added: Vec<_> = self
+                    .css_module_items
+                    .iter()
+                    .filter(|((default_ref, _), _)| *default_ref == default_name.reference)
+                    .map(|((_, name), &reference)| ClauseItem {
+                        alias: name.clone(),
+                        alias_location: default_name.loc,
+                        name: LocationRef {
+                            loc: default_name.loc,
+                            reference,
+                        },
+                    })
+                    .collect();
+                added.sort_by(|a, b| a.alias.cmp(&b.alias));
+                items.extend(added);
+            }
+        }
+    }
+
+    // An ES6 module doesn't have its own "module" and "exports" like a CommonJS
+    // module does. Using them anyway means using the global ones, if any.
+    fn warn_about_commonjs_variable(&self, location: Location, name: &str) {
+        let (keyword_location, keyword) = match self.es6_keyword {
+            Some(keyword) => keyword,
+            None => return,
+        };
+        self.log.add_id_warning_with_notes(
+            MsgId::CommonJSVariableInESM,
+            self.source,
+            self.source.range_of_identifier(location),
+            format!(
+                "The CommonJS \"{}\" variable is treated as a global variable in an \
+                 ECMAScript module and may not work as expected",
+                name
+            ),
+            vec![MsgNote::new(
+                self.source,
+                keyword_location..keyword_location + keyword.len(),
+                format!(
+                    "This file is considered to be an ECMAScript module because of the \
+                     \"{}\" keyword here:",
+                    keyword
+                ),
+            )],
+        );
+    }
+
+    // Nothing is equal to NaN, not even NaN itself, so "a === NaN" is always
+    // false. This was almost certainly meant to be "Number.isNaN(a)".
+    fn warn_about_equality_with_nan(&self, op_code: OperatorCode, left: &Expr, right: &Expr) {
+        let (op, result) = match op_code {
+            OperatorCode::BinOpLooseEq => ("==", false),
+            OperatorCode::BinOpStrictEq => ("===", false),
+            OperatorCode::BinOpLooseNe => ("!=", true),
+            OperatorCode::BinOpStrictNe => ("!==", true),
+            OperatorCode::BinOpLt => ("<", false),
+            OperatorCode::BinOpLe => ("<=", false),
+            OperatorCode::BinOpGt => (">", false),
+            OperatorCode::BinOpGe => (">=", false),
+            _ => return,
+        };
+        let nan = if self.is_nan(left) {
+            left
+        } else if self.is_nan(right) {
+            right
+        } else {
+            return;
+        };
+        let range = match nan.data.as_ref() {
+            ExprKind::Identifier { .. } => nan.location..nan.location + "NaN".len(),
+            ExprKind::Dot { name_location, .. } => nan.location..name_location + "NaN".len(),
+            _ => nan.location..nan.location,
+        };
+        self.log.add_id_warning_with_notes(
+            MsgId::EqualityWithNaN,
+            self.source,
+            range,
+            format!(
+                "Comparison with NaN using the \"{}\" operator here is always {}",
+                op, result
+            ),
+            vec![MsgNote::new(
+                &Source::default(),
+                0..0,
+                "Floating-point equality is defined such that NaN is never equal to anything, \
+                 so \"x === NaN\" always returns false. You need to use \"Number.isNaN(x)\" \
+                 instead to test for NaN."
+                    .to_owned(),
+            )],
+        );
+    }
+
+    // "NaN" and "Number.NaN", unless something in this file is named "NaN" or
+    // "Number"
+    fn is_nan(&self, expr: &Expr) -> bool {
+        ensure_sufficient_stack(|| self.is_nan_inner(expr))
+    }
+
+    fn is_nan_inner(&self, expr: &Expr) -> bool {
+        let is_global = |target: &Expr, global: &str| match target.data.as_ref() {
+            ExprKind::Identifier { reference } => {
+                let symbol = &self.symbols[reference.inner];
+                symbol.kind == SymbolKind::Unbound && symbol.name == global
+            }
+            _ => false,
+        };
+        match expr.data.as_ref() {
+            ExprKind::Number { value } => value.is_nan(),
+            ExprKind::Dot { target, name, .. } => name == "NaN" && is_global(target, "Number"),
+            _ => is_global(expr, "NaN"),
+        }
+    }
+
+    // Only the last of two properties with the same name is kept, unless one
+    // is a getter and the other is a setter
+    fn warn_about_duplicate_keys(&self, properties: &[Property]) {
+        let mut keys: HashMap<&[u16], &Property> = HashMap::new();
+        for property in properties {
+            if property.is_computed {
+                continue;
+            }
+            let key = match property.key.data.as_ref() {
+                ExprKind::String { value } => value.as_slice(),
+                _ => continue,
+            };
+            if let Some(previous) = keys.insert(key, property) {
+                let is_accessor_pair = matches!(
+                    (previous.kind, property.kind),
+                    (PropertyKind::PropertyGet, PropertyKind::PropertySet)
+                        | (PropertyKind::PropertySet, PropertyKind::PropertyGet)
+                );
+                if is_accessor_pair {
+                    continue;
+                }
+                let name = utf16_to_string_lossy(key);
+                self.log.add_id_warning_with_notes(
+                    MsgId::DuplicateObjectKey,
+                    self.source,
+                    self.key_range(property.key.location),
+                    format!("Duplicate key {:?} in object literal", name),
+                    vec![MsgNote::new(
+                        self.source,
+                        self.key_range(previous.key.location),
+                        format!("The original key {:?} is here:", name),
+                    )],
+                );
+            }
+        }
+    }
+
+    // Keys are identifiers, quoted strings, or numbers
+    fn key_range(&self, location: Location) -> Range<usize> {
+        match self.source.contents.as_bytes().get(location) {
+            Some(b'"') | Some(b'\'') => self.source.range_of_string(location),
+            Some(c) if c.is_ascii_digit() || *c == b'.' => self.source.range_of_number(location),
+            _ => self.source.range_of_identifier(location),
+        }
+    }
+
+    // Only ESM has "import.meta". The other formats get an object with just a
+    // "url", which is computed from where the code runs. That's only the URL
+    // of this file if the file isn't bundled.
+    fn lower_import_meta(&mut self, expr: &mut Expr, is_url: bool) {
+        let format = match self.options.output_format {
+            OutputFormat::Esm => return,
+            OutputFormat::Iife => "iife",
+            OutputFormat::Cjs => "cjs",
+        };
+        let range = expr.location..expr.location + "import.meta".len();
+        if !is_url {
+            self.log.add_id_warning(
+                MsgId::EmptyImportMeta,
+                self.source,
+                range,
+                format!(
+                    "Only \"import.meta.url\" is available in the \"{}\" output format",
+                    format
+                ),
+            );
+        } else if self.options.is_bundling {
+            self.log.add_range_warning(
+                self.source,
+                range,
+                format!(
+                    "\"import.meta.url\" is the URL of the bundle instead of this file \
+                     in the \"{}\" output format",
+                    format
+                ),
+            );
+        }
+
+        if self.import_meta_ref == INVALID_REF {
+            self.import_meta_ref = self.new_symbol(SymbolKind::Other, "import_meta");
+            self.scopes[ScopeTree::ROOT]
+                .generated
+                .push(self.import_meta_ref);
+        }
+        *expr.data = ExprKind::Identifier {
+            reference: self.import_meta_ref,
+        };
+        self.record_usage(self.import_meta_ref);
+    }
+
+    // The parts of the element have been visited already
+    fn lower_jsx_element(
+        &mut self,
+        location: Location,
+        tag: Option<Expr>,
+        properties: Vec<Property>,
+        mut children: Vec<Expr>,
+    ) -> ExprKind {
+        let jsx = &self.options.jsx;
+        if jsx.runtime == JSXRuntime::Classic {
+            let factory =
+                self.jsx_property_chain(location, &jsx.factory, &["React", "createElement"]);
+            let tag = match tag {
+                Some(tag) => tag,
+                None => self.jsx_property_chain(location, &jsx.fragment, &["React", "Fragment"]),
+            };
+            return jsx_call(
+                factory,
+                classic_jsx_args(location, tag, properties, children),
+            );
+        }
+
+        let function = automatic_jsx_function(&properties, children.len(), jsx.development);
+        let target = self.jsx_runtime_identifier(location, function);
+        let tag = match tag {
+            Some(tag) => tag,
+            None => self.jsx_runtime_identifier(location, "Fragment"),
+        };
+        if function == "createElement" {
+            return jsx_call(
+                target,
+                classic_jsx_args(location, tag, properties, children),
+            );
+        }
+
+        // The key is passed on its own, and the children are a prop. More than
+        // one child is an array, which "jsxs" knows is never changed.
+        let mut key = None;
+        let mut props = vec![];
+        for property in properties {
+            if is_jsx_key(&property) {
+                key = property.value;
+            } else {
+                props.push(property);
+            }
+        }
+        let is_static_children = children.len() > 1;
+        let children = match children.len() {
+            0 => None,
+            1 => children.pop(),
+            _ => Some(expr(
+                children[0].location,
+                ExprKind::Array { items: children },
+            )),
+        };
+        if let Some(children) = children {
+            let key = expr(
+                children.location,
+                ExprKind::String {
+                    value: string_to_utf16("children"),
+                },
+            );
+            props.push(jsx_property(PropertyKind::PropertyNormal, key, children));
+        }
+
+        let mut args = vec![tag, expr(location, ExprKind::Object { properties: props })];
+        if jsx.development {
+            args.push(key.unwrap_or_else(|| expr(location, ExprKind::Undefined)));
+            args.push(expr(
+                location,
+                ExprKind::Boolean {
+                    value: is_static_children,
+                },
+            ));
+            args.push(self.jsx_source_object(location));
+            let this = if self.is_this_nested {
+                ExprKind::This
+            } else {
+                ExprKind::Undefined
+            };
+            args.push(expr(location, this));
+        } else if let Some(key) = key {
+            args.push(key);
+        }
+        jsx_call(target, args)
+    }
+
+    // The factories of the classic runtime are looked up like any other name
+    // in the file, so they can be imported or replaced by a define
+    fn jsx_property_chain(
+        &mut self,
+        location: Location,
+        chain: &[String],
+        default: &[&str],
+    ) -> Expr {
+        let mut names: Vec<&str> = chain.iter().map(String::as_str).collect();
+        if names.is_empty() {
+            names = default.to_vec();
+        }
+        let reference = self.store_name_in_ref(names[0].to_owned());
+        let mut value = expr(location, ExprKind::Identifier { reference });
+        for name in &names[1..] {
+            value = expr(
+                location,
+                ExprKind::Dot {
+                    target: value,
+                    name: (*name).to_owned(),
+                    name_location: location,
+                    optional_chain: OptionalChain::None,
+                    is_parenthesized: false,
+                },
+            );
+        }
+        self.visit_expr(&mut value);
+        value
+    }
+
+    fn jsx_runtime_identifier(&mut self, location: Location, name: &str) -> Expr {
+        let reference = self.jsx_runtime_refs[name];
+        self.record_usage(reference);
+        expr(location, ExprKind::ImportIdentifier { reference })
+    }
+
+    // This is where the element is for React's warnings in development.
+    // Lines and columns start at 1, and columns count UTF-16 code units.
+    fn jsx_source_object(&self, location: Location) -> Expr {
+        let (line, _, line_start) = self.source.line_offset_table().line_and_column(location);
+        let column = self.source.contents[line_start..location]
+            .encode_utf16()
+            .count();
+        let field = |name: &str, value| {
+            let key = expr(
+                location,
+                ExprKind::String {
+                    value: string_to_utf16(name),
+                },
+            );
+            jsx_property(PropertyKind::PropertyNormal, key, expr(location, value))
+        };
+        let properties = vec![
+            field(
+                "fileName",
+                ExprKind::String {
+                    value: string_to_utf16(&self.source.pretty_path),
+                },
+            ),
+            field(
+                "lineNumber",
+                ExprKind::Number {
+                    value: (line + 1) as f64,
+                },
+            ),
+            field(
+                "columnNumber",
+                ExprKind::Number {
+                    value: (column + 1) as f64,
+                },
+            ),
+        ];
+        expr(location, ExprKind::Object { properties })
+    }
+
+    // The object that "import.meta" was replaced with. A CommonJS module knows
+    // its path, and a script in a browser knows the URL it was loaded from:
+    //
+    //   var import_meta = {url: new URL("file:" + __filename).href};
+    //   var import_meta = {url: document.currentScript && document.currentScript.src || document.baseURI};
+    //
+    // The URL constructor is used since a module may replace "require" with
+    // its own, which wouldn't be initialized yet up here.
+    fn import_meta_shim(&mut self) -> Option<Stmt> {
+        if self.import_meta_ref == INVALID_REF {
+            return None;
+        }
+        let location = 0;
+        let identifier = |reference| expr(location, ExprKind::Identifier { reference });
+        let dot = |target, name: &str| {
+            expr(
+                location,
+                ExprKind::Dot {
+                    target,
+                    name: name.to_owned(),
+                    name_location: location,
+                    optional_chain: OptionalChain::None,
+                    is_parenthesized: false,
+                },
+            )
+        };
+        let binary = |op_code, left, right| {
+            expr(
+                location,
+                ExprKind::Binary {
+                    op_code,
+                    left,
+                    right,
+                },
+            )
+        };
+
+        self.current_scope = ScopeTree::ROOT;
+        let url = if self.options.output_format == OutputFormat::Cjs {
+            let url_ref = self.find_symbol("URL");
+            let filename_ref = self.find_symbol("__filename");
+            let path = binary(
+                OperatorCode::BinOpAdd,
+                expr(
+                    location,
+                    ExprKind::String {
+                        value: string_to_utf16("file:"),
+                    },
+                ),
+                identifier(filename_ref),
+            );
+            let url = expr(
+                location,
+                ExprKind::New {
+                    target: identifier(url_ref),
+                    args: vec![path],
+                    can_be_unwrapped_if_unused: false,
+                },
+            );
+            dot(url, "href")
+        } else {
+            let document_ref = self.find_symbol("document");
+            let script = dot(identifier(document_ref), "currentScript");
+            binary(
+                OperatorCode::BinOpLogicalOr,
+                binary(
+                    OperatorCode::BinOpLogicalAnd,
+                    script.clone(),
+                    dot(script, "src"),
+                ),
+                dot(identifier(document_ref), "baseURI"),
+            )
+        };
+
+        let object = expr(
+            location,
+            ExprKind::Object {
+                properties: vec![Property {
+                    kind: PropertyKind::PropertyNormal,
+                    is_computed: false,
+                    is_method: false,
+                    is_static: false,
+                    key: expr(
+                        location,
+                        ExprKind::String {
+                            value: string_to_utf16("url"),
+                        },
+                    ),
+                    prefer_quoted_key: false,
+                    value: Some(url),
+                    initializer: None,
+                }],
+            },
+        );
+        let decl = Decl {
+            binding: binding(
+                location,
+                BindingKind::Identifier {
+                    reference: self.import_meta_ref,
+                },
+            ),
+            value: Some(object),
+        };
+        Some(stmt(location, local(vec![decl], LocalKind::Var, false)))
+    }
+}
+
+fn new_part(stmts: Vec<Stmt>, info: PartInfo) -> Part {
+    Part {
+        import_paths: info.import_paths,
+        stmts,
+        declared_symbols: info.declared_symbols,
+        use_count_estimates: info.use_count_estimates,
+        local_dependencies: HashMap::new(),
+        can_be_removed_if_unused: false,
+        is_namespace_export: false,
+        force_tree_shaking: false,
+    }
+}
+
+// A part can be removed by tree shaking if nothing uses the symbols it
+// declares and evaluating it has no side effects. Import and export statements
+// are kept here since whether they can be removed depends on the other files
+// in the bundle. The tree shaker decides that.
+fn stmt_can_be_removed_if_unused(stmt: &Stmt, symbols: &SymbolMap) -> bool {
+    ensure_sufficient_stack(|| stmt_can_be_removed_if_unused_inner(stmt, symbols))
+}
+
+fn stmt_can_be_removed_if_unused_inner(stmt: &Stmt, symbols: &SymbolMap) -> bool {
+    match stmt.data.as_ref() {
+        StmtKind::Empty | StmtKind::TypeScript | StmtKind::Function { .. } => true,
+        StmtKind::Class { class, .. } => class.can_be_removed_if_unused(symbols),
+        StmtKind::Local { decls, .. } => decls.iter().all(|decl| match &decl.value {
+            Some(value) => value.can_be_removed_if_unused(symbols),
+            None => true,
+        }),
+        StmtKind::Expr { value } => value.can_be_removed_if_unused(symbols),
+        StmtKind::ExportDefault { value, .. } => match value {
+            ExprOrStmt::Expr(value) => value.can_be_removed_if_unused(symbols),
+            ExprOrStmt::Stmt(stmt) => stmt_can_be_removed_if_unused(stmt, symbols),
+        },
+        _ => false,
+    }
+}
+
+// Returns the value of an expression when it is converted to a boolean, if
+// that is known at compile time and evaluating it has no side effects
+fn to_boolean(expr: &Expr) -> Option<bool> {
+    match expr.data.as_ref() {
+        ExprKind::Null | ExprKind::Undefined => Some(false),
+        ExprKind::Boolean { value } => Some(*value),
+        ExprKind::Number { value } => Some(*value != 0.0 && !value.is_nan()),
+        ExprKind::String { value } => Some(!value.is_empty()),
+        ExprKind::Function { .. } | ExprKind::Arrow { .. } => Some(true),
+        _ => None,
+    }
+}
+
+// Two primitive literals are equal if they have the same kind and value.
+// Loose equality only differs from strict equality for literals of different
+// kinds, and of those only "null == undefined" is folded.
+fn literals_are_equal(left: &Expr, right: &Expr, is_loose: bool) -> Option<bool> {
+    match (left.data.as_ref(), right.data.as_ref()) {
+        (ExprKind::Null, ExprKind::Null) | (ExprKind::Undefined, ExprKind::Undefined) => Some(true),
+        (ExprKind::Null, ExprKind::Undefined) | (ExprKind::Undefined, ExprKind::Null) => {
+            Some(is_loose)
+        }
+        (ExprKind::Boolean { value: a }, ExprKind::Boolean { value: b }) => Some(a == b),
+        (ExprKind::Number { value: a }, ExprKind::Number { value: b }) => Some(a == b),
+        (ExprKind::String { value: a }, ExprKind::String { value: b }) => Some(a == b),
+        _ if is_loose => None,
+        (ExprKind::Null, _)
+        | (ExprKind::Undefined, _)
+        | (ExprKind::Boolean { .. }, _)
+        | (ExprKind::Number { .. }, _)
+        | (ExprKind::String { .. }, _)
+            if right.is_primitive_literal() =>
+        {
+            Some(false)
+        }
+        _ => None,
+    }
+}
+
+// Folds the operators that conditions are usually made of when their operands
+// are constants, so that the branches they guard can be removed:
+//
+//   "production" !== "production" && check()  =>  false
+//
+// The result of "&&", "||", and "?:" is only replaced by one of the operands
+// when that can't be a property access, since "(0, a.b)()" calls "a.b" with a
+// different "this" than "a.b()".
+fn fold_constant(expr: &mut Expr) {
+    let location = expr.location;
+    let folded = match expr.data.as_mut() {
+        ExprKind::Unary {
+            op_code: OperatorCode::UnOpNot,
+            value,
+        } => match to_boolean(value) {
+            Some(boolean) => ExprKind::Boolean { value: !boolean },
+            None => return,
+        },
+
+        ExprKind::Binary {
+            op_code,
+            left,
+            right,
+        } => {
+            let equals = |is_loose| literals_are_equal(left, right, is_loose);
+            let kept = match op_code {
+                OperatorCode::BinOpStrictEq => {
+                    equals(false).map(|value| ExprKind::Boolean { value })
+                }
+                OperatorCode::BinOpStrictNe => {
+                    equals(false).map(|value| ExprKind::Boolean { value: !value })
+                }
+                OperatorCode::BinOpLooseEq => equals(true).map(|value| ExprKind::Boolean { value }),
+                OperatorCode::BinOpLooseNe => {
+                    equals(true).map(|value| ExprKind::Boolean { value: !value })
+                }
+                OperatorCode::BinOpLogicalAnd | OperatorCode::BinOpLogicalOr => {
+                    let keeps_left = match to_boolean(left) {
+                        Some(boolean) => boolean == (*op_code == OperatorCode::BinOpLogicalOr),
+                        None => return,
+                    };
+                    let kept = if keeps_left { left } else { right };
+                    if matches!(
+                        kept.data.as_ref(),
+                        ExprKind::Dot { .. } | ExprKind::Index { .. }
+                    ) {
+                        return;
+                    }
+                    Some(std::mem::replace(kept.data.as_mut(), ExprKind::Missing))
+                }
+                _ => None,
+            };
+            match kept {
+                Some(kept) => kept,
+                None => return,
+            }
+        }
+
+        ExprKind::If { test, yes, no } => {
+            let kept = match to_boolean(test) {
+                Some(true) => yes,
+                Some(false) => no,
+                None => return,
+            };
+            if matches!(
+                kept.data.as_ref(),
+                ExprKind::Dot { .. } | ExprKind::Index { .. }
+            ) {
+                return;
+            }
+            std::mem::replace(kept.data.as_mut(), ExprKind::Missing)
+        }
+
+        _ => return,
+    };
+    *expr = self::expr(location, folded);
+}
+
+// Removing a dead branch would also remove the declarations in it that are
+// visible outside of it
+// A label on another label also labels whatever that one labels
+fn is_loop_stmt(stmt: &Stmt) -> bool {
+    ensure_sufficient_stack(|| is_loop_stmt_inner(stmt))
+}
+
+fn is_loop_stmt_inner(stmt: &Stmt) -> bool {
+    match stmt.data.as_ref() {
+        StmtKind::For { .. }
+        | StmtKind::ForIn { .. }
+        | StmtKind::ForOf { .. }
+        | StmtKind::DoWhile { .. }
+        | StmtKind::While { .. } => true,
+        StmtKind::Label { stmt, .. } => is_loop_stmt(stmt),
+        _ => false,
+    }
+}
+
+fn has_hoisted_decls(stmt: &Stmt) -> bool {
+    ensure_sufficient_stack(|| has_hoisted_decls_inner(stmt))
+}
+
+fn has_hoisted_decls_inner(stmt: &Stmt) -> bool {
+    let any = |stmts: &[Stmt]| stmts.iter().any(has_hoisted_decls);
+    match stmt.data.as_ref() {
+        StmtKind::Local { kind, .. } => *kind == LocalKind::Var,
+        StmtKind::Function { .. } => true,
+        StmtKind::Block { stmts } => any(stmts),
+        StmtKind::Label { stmt, .. } => has_hoisted_decls(stmt),
+        StmtKind::If { yes, no, .. } => {
+            has_hoisted_decls(yes) || no.as_ref().is_some_and(has_hoisted_decls)
+        }
+        StmtKind::For { init, body, .. } => {
+            init.as_ref().is_some_and(has_hoisted_decls) || has_hoisted_decls(body)
+        }
+        StmtKind::ForIn { init, body, .. } | StmtKind::ForOf { init, body, .. } => {
+            has_hoisted_decls(init) || has_hoisted_decls(body)
+        }
+        StmtKind::DoWhile { body, .. }
+        | StmtKind::While { body, .. }
+        | StmtKind::With { body, .. } => has_hoisted_decls(body),
+        StmtKind::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            any(body)
+                || catch.as_ref().is_some_and(|catch| any(&catch.body))
+                || finally.as_ref().is_some_and(|finally| any(&finally.stmts))
+        }
+        StmtKind::Switch { cases, .. } => cases.iter().any(|case| any(&case.body)),
+        _ => false,
+    }
+}
+
+// Postfix updates are handled along with calls and member accesses
+fn jsx_property(kind: PropertyKind, key: Expr, value: Expr) -> Property {
+    Property {
+        kind,
+        is_computed: false,
+        is_method: false,
+        is_static: false,
+        key,
+        prefer_quoted_key: false,
+        value: Some(value),
+        initializer: None,
+    }
+}
+
+fn is_jsx_key(property: &Property) -> bool {
+    property.kind != PropertyKind::PropertySpread
+        && matches!(property.key.data.as_ref(), ExprKind::String { value } if utf16_equals_string(value, "key"))
+}
+
+// The function of the automatic runtime that an element becomes a call to.
+// The others take "key" apart from the props, so it would come before any
+// spread even if it's after one. That only matters if the spread has a key
+// too, but it can't be known, so "createElement" is called instead.
+fn automatic_jsx_function(
+    properties: &[Property],
+    child_count: usize,
+    development: bool,
+) -> &'static str {
+    let mut has_spread = false;
+    for property in properties {
+        if property.kind == PropertyKind::PropertySpread {
+            has_spread = true;
+        } else if has_spread && is_jsx_key(property) {
+            return "createElement";
+        }
+    }
+    if development {
+        "jsxDEV"
+    } else if child_count > 1 {
+        "jsxs"
+    } else {
+        "jsx"
+    }
+}
+
+// The props are null if there aren't any, and each child is an argument
+fn classic_jsx_args(
+    location: Location,
+    tag: Expr,
+    properties: Vec<Property>,
+    children: Vec<Expr>,
+) -> Vec<Expr> {
+    let props = if properties.is_empty() {
+        ExprKind::Null
+    } else {
+        ExprKind::Object { properties }
+    };
+    let mut args = vec![tag, expr(location, props)];
+    args.extend(children);
+    args
+}
+
+// Creating an element has no side effects, so one that isn't used can go
+fn jsx_call(target: Expr, args: Vec<Expr>) -> ExprKind {
+    ExprKind::Call {
+        target,
+        args,
+        optional_chain: OptionalChain::None,
+        is_parenthesized: false,
+        is_direct_eval: false,
+        can_be_unwrapped_if_unused: true,
+    }
+}
+
+fn binary_operator(token: Token) -> Option<(OperatorCode, Operator)> {
+    let op_code = token
+        .to_operator_code(false)
+        .filter(|op_code| !op_code.is_unary_update())?;
+    Some((op_code, op_code.entry().level))
+}
+
+// The level just below that of a right-associative operator
+fn level_below(level: Operator) -> Operator {
+    match level {
+        Operator::Assign => Operator::Yield,
+        Operator::Exponentiation => Operator::Multiply,
+        level => level,
+    }
+}
+
+fn expr(location: Location, data: ExprKind) -> Expr {
+    Expr {
+        location,
+        data: Box::new(data),
+    }
+}
+
+fn stmt(location: Location, data: StmtKind) -> Stmt {
+    Stmt {
+        location,
+        data: Box::new(data),
+    }
+}
+
+fn binding(location: Location, data: BindingKind) -> Binding {
+    Binding {
+        location,
+        data: Box::new(data),
+    }
+}
+
+fn local(decls: Vec<Decl>, kind: LocalKind, is_export: bool) -> StmtKind {
+    StmtKind::Local {
+        decls,
+        kind,
+        is_export,
+        was_ts_import_equals_in_namespace: false,
+    }
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Default)]
+pub enum DropConsole {
+    // Calls to "console" methods are left alone
+    #[default]
+    Never = 0,
+
+    // "console.log(a(), 'x')" becomes "a()". Arguments that may have side
+    // effects are still evaluated in order.
+    KeepSideEffects,
+
+    // The whole call is removed including all of its arguments, which matches
+    // what esbuild's "--drop:console" does
+    All,
+}
+
+// Global names and property chains on them can be replaced with a constant at
+// compile time. Only names that aren't declared anywhere in the file are
+// replaced, so a local variable named "process" is left alone. Conditions made
+// of constants are folded, and the branches they make dead are removed:
+//
+//   if (process.env.NODE_ENV !== "production") checkInvariants()
+//
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefineValue {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+
+    // Another global name or property chain, such as "window" for "global"
+    Name(String),
+}
+
+// Parses the "name=value" form used on the command line. The value is either
+// JSON or a name, so a string value has to be quoted.
+pub fn parse_define(text: &str) -> Result<(String, DefineValue), Error> {
+    let is_dotted_name = |text: &str| text.split('.').all(is_identifier);
+    let (name, value) = match text.find('=') {
+        Some(equals) => (&text[..equals], &text[equals + 1..]),
+        None => return Err(Error::Config(format!("Missing \"=\" in define: {}", text))),
+    };
+    if !is_dotted_name(name) {
+        return Err(Error::Config(format!("Invalid define name: {}", name)));
+    }
+
+    let value = match value {
+        "null" => DefineValue::Null,
+        "true" => DefineValue::Boolean(true),
+        "false" => DefineValue::Boolean(false),
+        _ if is_dotted_name(value) => DefineValue::Name(value.to_owned()),
+        _ => {
+            let source = Source {
+                index: 0,
+                is_stdin: false,
+                absolute_path: String::new(),
+                pretty_path: String::new(),
+                contents: value.to_owned(),
+                ..Source::default()
+            };
+            let json = parse_json(&Log::default(), &source, JsonOptions::default());
+            match json.as_ref().map(|json| json.data.as_ref()) {
+                Some(ExprKind::Number { value }) => DefineValue::Number(*value),
+                Some(ExprKind::String { value }) => {
+                    DefineValue::String(utf16_to_string_lossy(value))
+                }
+                _ => return Err(Error::Config(format!("Invalid define value: {}", value))),
+            }
+        }
+    };
+    Ok((name.to_owned(), value))
+}
+
+// Code that only exists for development builds can be removed at compile time
+// instead of being shipped and skipped at run time. Labeled blocks are the
+// only way to mark an arbitrary range of statements without new syntax:
+//
+//   DEV: {
+//     checkInvariants()
+//   }
+//
+#[derive(Debug, Clone, Default)]
+pub struct DropOptions {
+    pub console: DropConsole,
+    pub debugger: bool,
+    pub labels: Vec<String>,
+}
+
+impl DropOptions {
+    pub fn is_empty(&self) -> bool {
+        self.console == DropConsole::Never && !self.debugger && self.labels.is_empty()
+    }
+}
+
+pub fn drop_stmts(stmts: &mut Vec<Stmt>, symbols: &SymbolMap, options: &DropOptions) {
+    if options.is_empty() {
+        return;
+    }
+
+    Dropper { symbols, options }.visit_stmts(stmts);
+}
+
+struct Dropper<'a> {
+    symbols: &'a SymbolMap,
+    options: &'a DropOptions,
+}
+
+impl<'a> Dropper<'a> {
+    fn visit_stmts(&self, stmts: &mut Vec<Stmt>) {
+        stmts.retain_mut(|stmt| self.visit_stmt(stmt));
+    }
+
+    // Returns false if the statement should be removed
+    fn visit_stmt(&self, stmt: &mut Stmt) -> bool {
+        ensure_sufficient_stack(|| self.visit_stmt_inner(stmt))
+    }
+
+    fn visit_stmt_inner(&self, stmt: &mut Stmt) -> bool {
+        match stmt.data.as_mut() {
+            StmtKind::Debugger => return !self.options.debugger,
+            StmtKind::Label { name, stmt } => {
+                let label = &self.symbols[name.reference].name;
+                if self.options.labels.iter().any(|l| l == label) {
+                    return false;
+                }
+                self.visit_nested_stmt(stmt);
+            }
+            StmtKind::Expr { value } => {
+                if let Some(replacement) = self.drop_expr(value) {
+                    match replacement {
+                        Some(expr) => *value = expr,
+                        None => return false,
+                    }
+                }
+                self.visit_expr(value);
+            }
+
+            StmtKind::Block { stmts } | StmtKind::Namespace { stmts, .. } => {
+                self.visit_stmts(stmts)
+            }
+            StmtKind::ExportDefault { value, .. } => match value {
+                ExprOrStmt::Expr(expr) => self.visit_expr(expr),
+                ExprOrStmt::Stmt(stmt) => self.visit_nested_stmt(stmt),
+            },
+            StmtKind::ExportEquals { value } | StmtKind::Throw { value } => self.visit_expr(value),
+            StmtKind::Return { value } => {
+                if let Some(value) = value {
+                    self.visit_expr(value);
+                }
+            }
+            StmtKind::Enum { values, .. } => {
+                for value in values.iter_mut().filter_map(|v| v.value.as_mut()) {
+                    self.visit_expr(value);
+                }
+            }
+            StmtKind::Function { function, .. } => self.visit_stmts(&mut function.body.stmts),
+            StmtKind::Class { class, .. } => self.visit_class(class),
+            StmtKind::If { test, yes, no } => {
+                self.visit_expr(test);
+                self.visit_nested_stmt(yes);
+                if let Some(no) = no {
+                    self.visit_nested_stmt(no);
+                }
+            }
+            StmtKind::For {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                if let Some(init) = init {
+                    self.visit_nested_stmt(init);
+                }
+                if let Some(test) = test {
+                    self.visit_expr(test);
+                }
+                if let Some(update) = update {
+                    self.visit_expr(update);
+                }
+                self.visit_nested_stmt(body);
+            }
+            StmtKind::ForIn { init, value, body }
+            | StmtKind::ForOf {
+                init, value, body, ..
+            } => {
+                self.visit_nested_stmt(init);
+                self.visit_expr(value);
+                self.visit_nested_stmt(body);
+            }
+            StmtKind::DoWhile { body, test } | StmtKind::While { test, body } => {
+                self.visit_nested_stmt(body);
+                self.visit_expr(test);
+            }
+            StmtKind::With { value, body, .. } => {
+                self.visit_expr(value);
+                self.visit_nested_stmt(body);
+            }
+            StmtKind::Catch(catch) => self.visit_stmts(&mut catch.body),
+            StmtKind::Finally(finally) => self.visit_stmts(&mut finally.stmts),
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.visit_stmts(body);
+                if let Some(catch) = catch {
+                    self.visit_stmts(&mut catch.body);
+                }
+                if let Some(finally) = finally {
+                    self.visit_stmts(&mut finally.stmts);
+                }
+            }
+            StmtKind::Switch { test, cases, .. } => {
+                self.visit_expr(test);
+                for case in cases {
+                    if let Some(value) = &mut case.value {
+                        self.visit_expr(value);
+                    }
+                    self.visit_stmts(&mut case.body);
+                }
+            }
+            StmtKind::Local { decls, .. } => {
+                for value in decls.iter_mut().filter_map(|d| d.value.as_mut()) {
+                    self.visit_expr(value);
+                }
+            }
+            StmtKind::Empty
+            | StmtKind::TypeScript
+            | StmtKind::SyntaxError
+            | StmtKind::Directive { .. }
+            | StmtKind::Comment { .. }
+            | StmtKind::ExportClause { .. }
+            | StmtKind::ExportFrom { .. }
+            | StmtKind::ExportStar { .. }
+            | StmtKind::Import { .. }
+            | StmtKind::Break { .. }
+            | StmtKind::Continue { .. } => {}
+        }
+
+        true
+    }
+
+    // A statement in a position that requires exactly one statement (e.g. the
+    // body of an "if") can't be removed, so it's replaced by an empty one
+    fn visit_nested_stmt(&self, stmt: &mut Stmt) {
+        if !self.visit_stmt(stmt) {
+            *stmt.data = StmtKind::Empty;
+        }
+    }
+
+    fn visit_class(&self, class: &mut Class) {
+        if let Some(extends) = &mut class.extends {
+            self.visit_expr(extends);
+        }
+        for property in &mut class.properties {
+            self.visit_property(property);
+        }
+    }
+
+    fn visit_property(&self, property: &mut Property) {
+        self.visit_expr(&mut property.key);
+        if let Some(value) = &mut property.value {
+            self.visit_expr(value);
+        }
+        if let Some(initializer) = &mut property.initializer {
+            self.visit_expr(initializer);
+        }
+    }
+
+    fn visit_exprs(&self, exprs: &mut [Expr]) {
+        for expr in exprs {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_expr(&self, expr: &mut Expr) {
+        ensure_sufficient_stack(|| self.visit_expr_inner(expr))
+    }
+
+    fn visit_expr_inner(&self, expr: &mut Expr) {
+        if let Some(replacement) = self.drop_expr(expr) {
+            *expr = replacement.unwrap_or_else(|| Expr {
+                location: expr.location,
+                data: Box::new(ExprKind::Undefined),
+            });
+        }
+
+        match expr.data.as_mut() {
+            ExprKind::Array { items } => self.visit_exprs(items),
+            ExprKind::Unary { value, .. }
+            | ExprKind::Spread { value }
+            | ExprKind::Await { value }
+            | ExprKind::Yield { value, .. }
+            | ExprKind::Import { expr: value } => self.visit_expr(value),
+            ExprKind::Binary { left, right, .. } => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+            }
+            ExprKind::New { target, args, .. } | ExprKind::Call { target, args, .. } => {
+                self.visit_expr(target);
+                self.visit_exprs(args);
+            }
+            ExprKind::RuntimeCall { args, .. } => self.visit_exprs(args),
+            ExprKind::Dot { target, .. } => self.visit_expr(target),
+            ExprKind::Index { target, index, .. } => {
+                self.visit_expr(target);
+                self.visit_expr(index);
+            }
+            ExprKind::Arrow { args, body, .. } => {
+                for default in args.iter_mut().filter_map(|a| a.default_.as_mut()) {
+                    self.visit_expr(default);
+                }
+                self.visit_stmts(&mut body.stmts);
+            }
+            ExprKind::Function { function } => self.visit_stmts(&mut function.body.stmts),
+            ExprKind::Class { class } => self.visit_class(class),
+            ExprKind::Object { properties } => {
+                for property in properties {
+                    self.visit_property(property);
+                }
+            }
+            ExprKind::Template { tag, parts, .. } => {
+                self.visit_expr(tag);
+                for part in parts {
+                    self.visit_expr(&mut part.value);
+                }
+            }
+            ExprKind::If { test, yes, no } => {
+                self.visit_expr(test);
+                self.visit_expr(yes);
+                self.visit_expr(no);
+            }
+            _ => {}
+        }
+    }
+
+    // Returns None if the expression is kept. Otherwise returns the expression
+    // that should be evaluated in its place, if there is anything left to
+    // evaluate at all.
+    fn drop_expr(&self, expr: &Expr) -> Option<Option<Expr>> {
+        if self.options.console == DropConsole::Never {
+            return None;
+        }
+
+        let args = match expr.data.as_ref() {
+            ExprKind::Call { target, args, .. } if self.is_console_method(target) => args,
+            _ => return None,
+        };
+
+        if self.options.console == DropConsole::All {
+            return Some(None);
+        }
+
+        let mut kept = args
+            .iter()
+            .filter(|arg| arg.has_side_effects())
+            .cloned()
+            .map(|arg| match *arg.data {
+                // "console.log(...a)" still iterates over "a"
+                ExprKind::Spread { .. } => Expr {
+                    location: arg.location,
+                    data: Box::new(ExprKind::Array { items: vec![arg] }),
+                },
+                _ => arg,
+            });
+        let first = kept.next();
+        Some(first.map(|first| {
+            kept.fold(first, |a, b| Expr {
+                location: a.location,
+                data: Box::new(ExprKind::Binary {
+                    op_code: OperatorCode::BinOpComma,
+                    left: a,
+                    right: b,
+                }),
+            })
+        }))
+    }
+
+    // Matches "console.log" and "console['log']" where "console" is the global
+    fn is_console_method(&self, target: &Expr) -> bool {
+        let object = match target.data.as_ref() {
+            ExprKind::Dot { target, .. } | ExprKind::Index { target, .. } => target,
+            _ => return false,
+        };
+
+        match object.data.as_ref() {
+            ExprKind::Identifier { reference } => {
+                let symbol = &self.symbols[*reference];
+                symbol.kind == SymbolKind::Unbound && symbol.name == "console"
+            }
+            _ => false,
+        }
+    }
+}
+
+// ES5 doesn't allow function declarations inside blocks in strict mode. Since
+// strict mode block-level functions are scoped to the block and initialized
+// when the block is entered, they behave the same as a "var" holding a
+// function expression at the top of the block. The new "var" still refers to
+// the same symbol, so the renamer takes care of any name collisions caused by
+// the binding now living in the enclosing function scope.
+//
+// Sloppy mode block-level functions are left alone. Their Annex B semantics
+// are what ES5 engines already implement.
+pub fn lower_block_level_functions(stmts: &mut Vec<Stmt>, strict: StrictModeKind) {
+    let strict = directive_strict_mode(stmts, strict);
+    BlockFunctionLowerer.visit_stmts(stmts, strict, false);
+}
+
+fn directive_strict_mode(stmts: &[Stmt], strict: StrictModeKind) -> StrictModeKind {
+    for stmt in stmts {
+        match stmt.data.as_ref() {
+            StmtKind::Directive { value } => {
+                if utf16_equals_string(value, "use strict") {
+                    return StrictModeKind::ExplicitStrict;
+                }
+            }
+            StmtKind::Comment { .. } => {}
+            _ => break,
+        }
+    }
+    strict
+}
+
+struct BlockFunctionLowerer;
+
+impl BlockFunctionLowerer {
+    fn visit_stmts(&self, stmts: &mut Vec<Stmt>, strict: StrictModeKind, is_block_level: bool) {
+        for stmt in stmts.iter_mut() {
+            self.visit_stmt(stmt, strict);
+        }
+
+        if !is_block_level || !strict.is_strict() {
+            return;
+        }
+
+        let mut hoisted = Vec::new();
+        let mut rest = Vec::with_capacity(stmts.len());
+        for stmt in stmts.drain(..) {
+            let location = stmt.location;
+            match *stmt.data {
+                StmtKind::Function {
+                    function,
+                    is_export,
+                } if function.name.is_some() => {
+                    let name = function.name.clone().unwrap();
+                    hoisted.push(Stmt {
+                        location,
+                        data: Box::new(StmtKind::Local {
+                            decls: vec![Decl {
+                                binding: Binding {
+                                    location: name.loc,
+                                    data: Box::new(BindingKind::Identifier {
+                                        reference: name.reference,
+                                    }),
+                                },
+                                value: Some(Expr {
+                                    location,
+                                    data: Box::new(ExprKind::Function { function }),
+                                }),
+                            }],
+                            kind: LocalKind::Var,
+                            is_export,
+                            was_ts_import_equals_in_namespace: false,
+                        }),
+                    });
+                }
+                data => rest.push(Stmt {
+                    location,
+                    data: Box::new(data),
+                }),
+            }
+        }
+        hoisted.append(&mut rest);
+        *stmts = hoisted;
+    }
+
+    // Statements in a single-statement position (e.g. "if (x) function f() {}")
+    // are only valid in sloppy mode, so there's nothing to lower for them
+    fn visit_stmt(&self, stmt: &mut Stmt, strict: StrictModeKind) {
+        ensure_sufficient_stack(|| self.visit_stmt_inner(stmt, strict))
+    }
+
+    fn visit_stmt_inner(&self, stmt: &mut Stmt, strict: StrictModeKind) {
+        match stmt.data.as_mut() {
+            StmtKind::Block { stmts } => self.visit_stmts(stmts, strict, true),
+            StmtKind::Namespace { stmts, .. } => self.visit_stmts(stmts, strict, false),
+            StmtKind::Function { function, .. } => self.visit_function(function, strict),
+            StmtKind::Class { class, .. } => self.visit_class(class),
+            StmtKind::Label { stmt, .. }
+            | StmtKind::ExportDefault {
+                value: ExprOrStmt::Stmt(stmt),
+                ..
+            } => self.visit_stmt(stmt, strict),
+            StmtKind::If { test, yes, no } => {
+                self.visit_expr(test, strict);
+                self.visit_stmt(yes, strict);
+                if let Some(no) = no {
+                    self.visit_stmt(no, strict);
+                }
+            }
+            StmtKind::For {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                if let Some(init) = init {
+                    self.visit_stmt(init, strict);
+                }
+                if let Some(test) = test {
+                    self.visit_expr(test, strict);
+                }
+                if let Some(update) = update {
+                    self.visit_expr(update, strict);
+                }
+                self.visit_stmt(body, strict);
+            }
+            StmtKind::ForIn { init, value, body }
+            | StmtKind::ForOf {
+                init, value, body, ..
+            } => {
+                self.visit_stmt(init, strict);
+                self.visit_expr(value, strict);
+                self.visit_stmt(body, strict);
+            }
+            StmtKind::DoWhile { body, test } | StmtKind::While { test, body } => {
+                self.visit_stmt(body, strict);
+                self.visit_expr(test, strict);
+            }
+            StmtKind::With { value, body, .. } => {
+                self.visit_expr(value, strict);
+                self.visit_stmt(body, strict);
+            }
+            StmtKind::Catch(catch) => self.visit_stmts(&mut catch.body, strict, true),
+            StmtKind::Finally(finally) => self.visit_stmts(&mut finally.stmts, strict, true),
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.visit_stmts(body, strict, true);
+                if let Some(catch) = catch {
+                    self.visit_stmts(&mut catch.body, strict, true);
+                }
+                if let Some(finally) = finally {
+                    self.visit_stmts(&mut finally.stmts, strict, true);
+                }
+            }
+            StmtKind::Switch { test, cases, .. } => {
+                self.visit_expr(test, strict);
+                for case in cases {
+                    if let Some(value) = &mut case.value {
+                        self.visit_expr(value, strict);
+                    }
+                    self.visit_stmts(&mut case.body, strict, true);
+                }
+            }
+            StmtKind::ExportDefault {
+                value: ExprOrStmt::Expr(value),
+                ..
+            }
+            | StmtKind::ExportEquals { value }
+            | StmtKind::Expr { value }
+            | StmtKind::Throw { value }
+            | StmtKind::Return { value: Some(value) } => self.visit_expr(value, strict),
+            StmtKind::Local { decls, .. } => {
+                for value in decls.iter_mut().filter_map(|d| d.value.as_mut()) {
+                    self.visit_expr(value, strict);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_function(&self, function: &mut Function, strict: StrictModeKind) {
+        let strict = directive_strict_mode(&function.body.stmts, strict);
+        self.visit_stmts(&mut function.body.stmts, strict, false);
+    }
+
+    fn visit_class(&self, class: &mut Class) {
+        let strict = StrictModeKind::ImplicitStrictClass;
+        if let Some(extends) = &mut class.extends {
+            self.visit_expr(extends, strict);
+        }
+        for property in &mut class.properties {
+            self.visit_expr(&mut property.key, strict);
+            if let Some(value) = &mut property.value {
+                self.visit_expr(value, strict);
+            }
+            if let Some(initializer) = &mut property.initializer {
+                self.visit_expr(initializer, strict);
+            }
+        }
+    }
+
+    fn visit_expr(&self, expr: &mut Expr, strict: StrictModeKind) {
+        ensure_sufficient_stack(|| self.visit_expr_inner(expr, strict))
+    }
+
+    fn visit_expr_inner(&self, expr: &mut Expr, strict: StrictModeKind) {
+        match expr.data.as_mut() {
+            ExprKind::Function { function } => self.visit_function(function, strict),
+            ExprKind::Arrow { args, body, .. } => {
+                for default in args.iter_mut().filter_map(|a| a.default_.as_mut()) {
+                    self.visit_expr(default, strict);
+                }
+                let strict = directive_strict_mode(&body.stmts, strict);
+                self.visit_stmts(&mut body.stmts, strict, false);
+            }
+            ExprKind::Class { class } => self.visit_class(class),
+            ExprKind::Array { items: exprs } | ExprKind::RuntimeCall { args: exprs, .. } => {
+                for expr in exprs {
+                    self.visit_expr(expr, strict);
+                }
+            }
+            ExprKind::New { target, args, .. } | ExprKind::Call { target, args, .. } => {
+                self.visit_expr(target, strict);
+                for arg in args {
+                    self.visit_expr(arg, strict);
+                }
+            }
+            ExprKind::Unary { value, .. }
+            | ExprKind::Spread { value }
+            | ExprKind::Await { value }
+            | ExprKind::Yield { value, .. }
+            | ExprKind::Import { expr: value }
+            | ExprKind::Dot { target: value, .. } => self.visit_expr(value, strict),
+            ExprKind::Binary { left, right, .. }
+            | ExprKind::Index {
+                target: left,
+                index: right,
+                ..
+            } => {
+                self.visit_expr(left, strict);
+                self.visit_expr(right, strict);
+            }
+            ExprKind::Object { properties } => {
+                for property in properties {
+                    self.visit_expr(&mut property.key, strict);
+                    if let Some(value) = &mut property.value {
+                        self.visit_expr(value, strict);
+                    }
+                    if let Some(initializer) = &mut property.initializer {
+                        self.visit_expr(initializer, strict);
+                    }
+                }
+            }
+            ExprKind::Template { tag, parts, .. } => {
+                self.visit_expr(tag, strict);
+                for part in parts {
+                    self.visit_expr(&mut part.value, strict);
+                }
+            }
+            ExprKind::If { test, yes, no } => {
+                self.visit_expr(test, strict);
+                self.visit_expr(yes, strict);
+                self.visit_expr(no, strict);
+            }
+            _ => {}
+        }
+    }
+}
+
+// TypeScript has two CommonJS-only forms of import and export:
+//
+//   import fs = require("fs")
+//   export = value
+//
+// The import is parsed into a "const" declaration with a require() call, which
+// works as-is for every format that supports require(). The export becomes an
+// assignment to "module.exports", which has no equivalent in ES6 modules, so
+// that is an error when the output format is ESM.
+//
+// Without require(), "import x = a.b" is just an alias. The TypeScript
+// compiler doesn't generate anything for it when the alias is never used
+// since it may refer to a type, so those declarations are removed here. That
+// includes the ones in namespaces, which often leaves nothing but types in
+// them.
+pub fn lower_typescript_equals(
+    stmts: &mut Vec<Stmt>,
+    symbols: &SymbolMap,
+    module_ref: Reference,
+    output_format: OutputFormat,
+    source: &Source,
+    log: &Log,
+) {
+    for stmt in stmts.iter_mut() {
+        let location = stmt.location;
+        if let StmtKind::ExportEquals { value } = stmt.data.as_mut() {
+            if output_format == OutputFormat::Esm {
+                log.add_error(
+                    source,
+                    location,
+                    "\"export =\" cannot be used when the output format is ESM".to_owned(),
+                );
+                continue;
+            }
+
+            let value = std::mem::replace(
+                value,
+                Expr {
+                    location,
+                    data: Box::new(ExprKind::Missing),
+                },
+            );
+            *stmt.data = StmtKind::Expr {
+                value: assign_module_exports(location, module_ref, value),
+            };
+        }
+    }
+
+    remove_unused_namespace_aliases(stmts, symbols);
+    remove_type_only_namespaces(stmts, source, log);
+}
+
+// A namespace with nothing but types in it has no code. Generating the code
+// for any other namespace isn't supported yet.
+fn remove_type_only_namespaces(stmts: &mut [Stmt], source: &Source, log: &Log) {
+    for stmt in stmts {
+        if let StmtKind::Namespace { stmts, .. } = stmt.data.as_ref() {
+            if is_type_only_namespace(stmts) {
+                *stmt.data = StmtKind::TypeScript;
+            } else {
+                log.add_error(
+                    source,
+                    stmt.location,
+                    "TypeScript namespaces with values are not supported yet".to_owned(),
+                );
+            }
+        }
+    }
+}
+
+fn is_type_only_namespace(stmts: &[Stmt]) -> bool {
+    stmts.iter().all(|stmt| match stmt.data.as_ref() {
+        StmtKind::TypeScript | StmtKind::Empty | StmtKind::Comment { .. } => true,
+        StmtKind::Namespace { stmts, .. } => is_type_only_namespace(stmts),
+        _ => false,
+    })
+}
+
+fn assign_module_exports(location: Location, module_ref: Reference, value: Expr) -> Expr {
+    let module_exports = Expr {
+        location,
+        data: Box::new(ExprKind::Dot {
+            target: Expr {
+                location,
+                data: Box::new(ExprKind::Identifier {
+                    reference: module_ref,
+                }),
+            },
+            name: "exports".to_owned(),
+            name_location: location,
+            optional_chain: OptionalChain::None,
+            is_parenthesized: false,
+        }),
+    };
+
+    Expr {
+        location,
+        data: Box::new(ExprKind::Binary {
+            op_code: OperatorCode::BinOpAssign,
+            left: module_exports,
+            right: value,
+        }),
+    }
+}
+
+fn remove_unused_namespace_aliases(stmts: &mut Vec<Stmt>, symbols: &SymbolMap) {
+    ensure_sufficient_stack(|| remove_unused_namespace_aliases_inner(stmts, symbols))
+}
+
+fn remove_unused_namespace_aliases_inner(stmts: &mut Vec<Stmt>, symbols: &SymbolMap) {
+    stmts.retain_mut(|stmt| match stmt.data.as_mut() {
+        StmtKind::Namespace { stmts, .. } => {
+            remove_unused_namespace_aliases(stmts, symbols);
+            true
+        }
+        StmtKind::Local {
+            decls,
+            is_export: false,
+            was_ts_import_equals_in_namespace: true,
+            ..
+        } => decls.iter().any(|decl| match decl.binding.data.as_ref() {
+            BindingKind::Identifier { reference } => symbols[*reference].use_count_estimate > 0,
+            _ => true,
+        }),
+        _ => true,
+    });
+}
+
+// This corresponds to TypeScript's "importsNotUsedAsValues" setting and
+// decides what happens to an import statement when every name it imports
+// turns out to only be used as a type
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Default)]
+pub enum ImportsNotUsedAsValues {
+    // The whole statement is removed, so the imported file isn't even part of
+    // the bundle unless something else imports it
+    #[default]
+    Remove = 0,
+
+    // The statement is kept as "import 'path'" for its side effects
+    Preserve,
+
+    // Like "Preserve" but also reports an error, so that type-only imports
+    // have to be written as "import type"
+    Error,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TypeScriptOptions {
+    // Only files that use the "ts" or "tsx" loader are parsed as TypeScript
+    pub parse: bool,
+    pub imports_not_used_as_values: ImportsNotUsedAsValues,
+
+    // With "isolatedModules" each file must compile the same way without
+    // looking at any other file. An import that is only re-exported may be a
+    // type, which can't be known from this file alone, so that's a warning.
+    pub isolated_modules: bool,
+}
+
+// Types are erased while parsing, so identifiers in type positions are never
+// bound and don't count towards "use_count_estimate". An imported name with a
+// use count of zero was therefore only used as a type (or not at all), and the
+// TypeScript compiler removes it since the file it comes from may not export
+// a value with that name.
+//
+// Names in "export {T}" that are unbound were declared in this file with a
+// type-only declaration such as "interface T {}", so those are removed too.
+pub fn elide_type_only_imports(
+    ast: &mut AST,
+    source: &Source,
+    log: &Log,
+    options: &TypeScriptOptions,
+) {
+    if !ast.was_typescript {
+        return;
+    }
+
+    let symbols = &ast.symbols;
+    let named_imports = &mut ast.named_imports;
+    let named_exports = &mut ast.named_exports;
+
+    // Count how often each name is re-exported, so that imports whose only use
+    // is a re-export can be found
+    let mut export_counts: HashMap<Reference, u32> = HashMap::new();
+    for part in &ast.parts {
+        for stmt in &part.stmts {
+            if let StmtKind::ExportClause { items } = stmt.data.as_ref() {
+                for item in items {
+                    *export_counts.entry(item.name.reference).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    for part in ast.parts.iter_mut() {
+        let import_paths = &mut part.import_paths;
+
+        part.stmts.retain_mut(|stmt| match stmt.data.as_mut() {
+            StmtKind::Import {
+                namespace_symbol,
+                default_name,
+                path,
+            } => {
+                let mut had_names = false;
+                let mut is_used = |reference: Reference| {
+                    had_names = true;
+                    let is_used = symbols[reference].use_count_estimate > 0;
+                    if !is_used {
+                        named_imports.remove(&reference);
+                    }
+                    is_used
+                };
+
+                if let Some(name) = default_name {
+                    if !is_used(name.reference) {
+                        *default_name = None;
+                    }
+                }
+                let has_names = match namespace_symbol {
+                    NamespaceSymbol::Clause { items } => {
+                        items.retain(|item| is_used(item.name.reference));
+                        !items.is_empty()
+                    }
+                    NamespaceSymbol::Star { namespace_ref, .. } => is_used(*namespace_ref),
+                } || default_name.is_some();
+
+                if !had_names || has_names {
+                    return true;
+                }
+
+                match options.imports_not_used_as_values {
+                    ImportsNotUsedAsValues::Remove => {
+                        import_paths.retain(|import_path| {
+                            import_path.kind != ImportKind::Stmt || import_path.path.loc != path.loc
+                        });
+                        return false;
+                    }
+                    ImportsNotUsedAsValues::Error => log.add_error(
+                        source,
+                        path.loc,
+                        "This import is never used as a value and must use \"import type\" \
+                         because \"importsNotUsedAsValues\" is set to \"error\""
+                            .to_owned(),
+                    ),
+                    ImportsNotUsedAsValues::Preserve => {}
+                }
+
+                // A star import with an unused namespace is kept as a
+                // side-effect import, which is always a clause import
+                *namespace_symbol = NamespaceSymbol::Clause { items: vec![] };
+                for import_path in import_paths.iter_mut() {
+                    if import_path.kind == ImportKind::Stmt && import_path.path.loc == path.loc {
+                        import_path.does_not_use_exports = true;
+                    }
+                }
+                true
+            }
+
+            StmtKind::ExportClause { items } => {
+                items.retain(|item| {
+                    let symbol = &symbols[item.name.reference];
+                    if symbol.kind == SymbolKind::Unbound {
+                        named_exports.remove(&item.alias);
+                        return false;
+                    }
+
+                    if options.isolated_modules
+                        && symbol.kind == SymbolKind::TSImport
+                        && export_counts.get(&item.name.reference).copied()
+                            == Some(symbol.use_count_estimate)
+                    {
+                        log.add_warning(
+                            source,
+                            item.name.loc,
+                            format!(
+                                "\"{}\" is only re-exported, which needs \"export type\" if it's \
+                                 a type because \"isolatedModules\" is enabled",
+                                symbol.name
+                            ),
+                        );
+                    }
+                    true
+                });
+                !items.is_empty()
+            }
+
+            _ => true,
+        });
+    }
+}
+
+// Assigning to an import or to a "const" throws a TypeError at run time. Both
+// are almost always mistakes, so they are reported as warnings pointing at the
+// assignment with a note pointing at the declaration.
+//
+// Identifiers have already been bound to symbols at this point, so shadowing
+// is handled for free: an assignment to a local "x" refers to a different
+// symbol than an imported "x" in an outer scope.
+pub fn check_assignments(stmts: &[Stmt], symbols: &SymbolMap, source: &Source, log: &Log) {
+    let mut checker = AssignmentChecker {
+        declarations: HashMap::new(),
+        namespaces: HashMap::new(),
+        assignments: vec![],
+    };
+    checker.visit_stmts(stmts);
+
+    for (reference, location, is_property) in checker.assignments {
+        let (declaration, kind) = if is_property {
+            match checker.namespaces.get(&reference) {
+                Some(declaration) => (*declaration, ImmutableKind::Namespace),
+                None => continue,
+            }
+        } else {
+            match checker.declarations.get(&reference) {
+                Some(declaration) => *declaration,
+                None => continue,
+            }
+        };
+
+        let name = &symbols[reference].name;
+        let (text, note) = match kind {
+            ImmutableKind::Import => (
+                format!(
+                    "This assignment will throw because \"{}\" is an import",
+                    name
+                ),
+                format!("The symbol \"{}\" was imported here:", name),
+            ),
+            ImmutableKind::Namespace => (
+                format!(
+                    "This assignment will throw because \"{}\" is an import namespace, which \
+                     is frozen",
+                    name
+                ),
+                format!("The namespace \"{}\" was imported here:", name),
+            ),
+            ImmutableKind::Const => (
+                format!(
+                    "This assignment will throw because \"{}\" is a constant",
+                    name
+                ),
+                format!("The symbol \"{}\" was declared a constant here:", name),
+            ),
+        };
+        let id = match kind {
+            ImmutableKind::Import | ImmutableKind::Namespace => MsgId::AssignToImport,
+            ImmutableKind::Const => MsgId::AssignToConstant,
+        };
+        log.add_id_warning_with_notes(
+            id,
+            source,
+            location..location + name.len(),
+            text,
+            vec![MsgNote::new(
+                source,
+                declaration..declaration + name.len(),
+                note,
+            )],
+        );
+    }
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+enum ImmutableKind {
+    Import,
+    Namespace,
+    Const,
+}
+
+struct AssignmentChecker {
+    declarations: HashMap<Reference, (Location, ImmutableKind)>,
+
+    // "import * as ns" namespaces, whose properties can't be assigned to
+    namespaces: HashMap<Reference, Location>,
+
+    // The symbol, where it was assigned to, and whether a property of the
+    // symbol was assigned to instead of the symbol itself
+    assignments: Vec<(Reference, Location, bool)>,
+}
+
+impl AssignmentChecker {
+    fn declare_const(&mut self, binding: &Binding) {
+        ensure_sufficient_stack(|| self.declare_const_inner(binding))
+    }
+
+    fn declare_const_inner(&mut self, binding: &Binding) {
+        match binding.data.as_ref() {
+            BindingKind::Identifier { reference } => {
+                self.declarations
+                    .insert(*reference, (binding.location, ImmutableKind::Const));
+            }
+            BindingKind::Array { items, .. } => {
+                for item in items {
+                    self.declare_const(&item.binding);
+                }
+            }
+            BindingKind::Object { properties } => {
+                for property in properties {
+                    self.declare_const(&property.value);
+                }
+            }
+            BindingKind::Missing => {}
+        }
+    }
+
+    // Records every identifier that is written to by an assignment target,
+    // including the ones inside destructuring patterns such as "[a, {b}] = c"
+    fn visit_assign_target(&mut self, target: &Expr) {
+        ensure_sufficient_stack(|| self.visit_assign_target_inner(target))
+    }
+
+    fn visit_assign_target_inner(&mut self, target: &Expr) {
+        match target.data.as_ref() {
+            ExprKind::Identifier { reference } | ExprKind::ImportIdentifier { reference } => {
+                self.assignments.push((*reference, target.location, false))
+            }
+            ExprKind::Dot { target: object, .. } | ExprKind::Index { target: object, .. } => {
+                if let ExprKind::Identifier { reference } = object.data.as_ref() {
+                    self.assignments.push((*reference, object.location, true));
+                }
+            }
+            ExprKind::Array { items } => {
+                for item in items {
+                    self.visit_assign_target(item);
+                }
+            }
+            ExprKind::Object { properties } => {
+                for property in properties {
+                    if let Some(value) = &property.value {
+                        self.visit_assign_target(value);
+                    }
+                }
+            }
+            ExprKind::Spread { value } => self.visit_assign_target(value),
+
+            // "[a = 1] = b"
+            ExprKind::Binary {
+                op_code: OperatorCode::BinOpAssign,
+                left,
+                ..
+            } => self.visit_assign_target(left),
+            _ => {}
+        }
+    }
+
+    fn visit_stmt_inner(&mut self, stmt: &Stmt) {
+        match stmt.data.as_ref() {
+            StmtKind::Import {
+                namespace_symbol,
+                default_name,
+                ..
+            } => {
+                if let Some(name) = default_name {
+                    self.declarations
+                        .insert(name.reference, (name.loc, ImmutableKind::Import));
+                }
+                match namespace_symbol {
+                    NamespaceSymbol::Clause { items } => {
+                        for item in items {
+                            self.declarations.insert(
+                                item.name.reference,
+                                (item.name.loc, ImmutableKind::Import),
+                            );
+                        }
+                    }
+                    NamespaceSymbol::Star {
+                        location,
+                        namespace_ref,
+                    } => {
+                        self.declarations
+                            .insert(*namespace_ref, (*location, ImmutableKind::Import));
+                        self.namespaces.insert(*namespace_ref, *location);
+                    }
+                }
+            }
+
+            // "for (a of b)" assigns to "a" on every iteration
+            StmtKind::ForIn { init, .. } | StmtKind::ForOf { init, .. } => {
+                if let StmtKind::Expr { value } = init.data.as_ref() {
+                    self.visit_assign_target(value);
+                }
+            }
+            StmtKind::Local {
+                decls,
+                kind: LocalKind::Const,
+                ..
+            } => {
+                for decl in decls {
+                    self.declare_const(&decl.binding);
+                }
+            }
+            _ => {}
+        }
+        self.walk_stmt(stmt);
+    }
+
+    fn visit_expr_inner(&mut self, expr: &Expr) {
+        match expr.data.as_ref() {
+            ExprKind::Binary { op_code, left, .. } if op_code.is_binary_assign() => {
+                self.visit_assign_target(left)
+            }
+            ExprKind::Unary { op_code, value } if op_code.is_unary_update() => {
+                self.visit_assign_target(value)
+            }
+            _ => {}
+        }
+        self.walk_expr(expr);
+    }
+}
+
+impl Visit for AssignmentChecker {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        ensure_sufficient_stack(|| self.visit_stmt_inner(stmt))
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        ensure_sufficient_stack(|| self.visit_expr_inner(expr))
+    }
+}
@@ -1 +1,193 @@
+// JSON is parsed with the JavaScript lexer in its JSON mode, which rejects
+// everything JSON doesn't allow such as single quotes and comments. The result
+// is a JavaScript expression so that it can be printed like any other code.
+//
+// Some JSON files are read by tools that are more lenient than the spec, such
+// as "tsconfig.json" which may contain comments and trailing commas. Those are
+// only accepted when asked for.
 
+use crate::ast::{Expr, ExprKind, Property, PropertyKind};
+use crate::lexer::Lexer;
+use crate::logging::{Log, MsgId, Source};
+use crate::strings::utf16_to_string_lossy;
+use crate::tables::Token;
+use std::collections::HashSet;
+
+// The lexer has already logged the error when this is returned
+struct SyntaxError;
+
+type PResult<T> = Result<T, SyntaxError>;
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Default)]
+pub struct JsonOptions {
+    pub allow_comments: bool,
+    pub allow_trailing_commas: bool,
+}
+
+struct JsonParser<'a> {
+    log: &'a Log,
+    source: &'a Source,
+    lexer: Lexer<'a>,
+    options: JsonOptions,
+}
+
+// Returns None if there was a syntax error. The error has already been logged.
+pub fn parse_json(log: &Log, source: &Source, options: JsonOptions) -> Option<Expr> {
+    let mut lexer = Lexer::new(log, source);
+    lexer.json.parse = true;
+    lexer.json.allow_comments = options.allow_comments;
+    let mut p = JsonParser {
+        log,
+        source,
+        lexer,
+        options,
+    };
+
+    p.next().ok()?;
+    let value = p.parse_expr().ok()?;
+    p.expect(Token::EndOfFile).ok()?;
+    Some(value)
+}
+
+impl<'a> JsonParser<'a> {
+    fn check(&self) -> PResult<()> {
+        if self.lexer.token == Token::SyntaxError {
+            return Err(SyntaxError);
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> PResult<()> {
+        self.lexer.next();
+        self.check()
+    }
+
+    fn expect(&mut self, token: Token) -> PResult<()> {
+        self.lexer.expect(token);
+        self.check()
+    }
+
+    fn parse_expr(&mut self) -> PResult<Expr> {
+        let location = self.lexer.start;
+
+        let data = match self.lexer.token {
+            Token::False => {
+                self.next()?;
+                ExprKind::Boolean { value: false }
+            }
+
+            Token::True => {
+                self.next()?;
+                ExprKind::Boolean { value: true }
+            }
+
+            Token::Null => {
+                self.next()?;
+                ExprKind::Null
+            }
+
+            Token::StringLiteral => {
+                let value = std::mem::take(&mut self.lexer.string_literal);
+                self.next()?;
+                ExprKind::String { value }
+            }
+
+            Token::NumericLiteral => {
+                let value = self.lexer.number;
+                self.next()?;
+                ExprKind::Number { value }
+            }
+
+            Token::Minus => {
+                self.next()?;
+                let value = self.lexer.number;
+                self.expect(Token::NumericLiteral)?;
+                ExprKind::Number { value: -value }
+            }
+
+            Token::OpenBracket => {
+                self.next()?;
+                let mut items = vec![];
+
+                while self.lexer.token != Token::CloseBracket {
+                    if !items.is_empty() {
+                        self.expect(Token::Comma)?;
+                        if self.lexer.token == Token::CloseBracket
+                            && self.options.allow_trailing_commas
+                        {
+                            break;
+                        }
+                    }
+                    items.push(self.parse_expr()?);
+                }
+
+                self.next()?;
+                ExprKind::Array { items }
+            }
+
+            Token::OpenBrace => {
+                self.next()?;
+                let mut properties = vec![];
+                let mut keys = HashSet::new();
+
+                while self.lexer.token != Token::CloseBrace {
+                    if !properties.is_empty() {
+                        self.expect(Token::Comma)?;
+                        if self.lexer.token == Token::CloseBrace
+                            && self.options.allow_trailing_commas
+                        {
+                            break;
+                        }
+                    }
+
+                    let key_location = self.lexer.start;
+                    let key_range = self.lexer.range();
+                    let value = self.lexer.string_literal.clone();
+                    self.expect(Token::StringLiteral)?;
+
+                    // Warn about duplicate keys since only the last one is used
+                    if !keys.insert(value.clone()) {
+                        self.log.add_id_warning(
+                            MsgId::DuplicateObjectKey,
+                            self.source,
+                            key_range,
+                            format!(
+                                "Duplicate key {:?} in object literal",
+                                utf16_to_string_lossy(&value)
+                            ),
+                        );
+                    }
+
+                    self.expect(Token::Colon)?;
+                    let value_expr = self.parse_expr()?;
+                    properties.push(Property {
+                        kind: PropertyKind::PropertyNormal,
+                        is_computed: false,
+                        is_method: false,
+                        is_static: false,
+                        key: Expr {
+                            location: key_location,
+                            data: Box::new(ExprKind::String { value }),
+                        },
+                        prefer_quoted_key: true,
+                        value: Some(value_expr),
+                        initializer: None,
+                    });
+                }
+
+                self.next()?;
+                ExprKind::Object { properties }
+            }
+
+            _ => {
+                self.lexer.unexpected();
+                return Err(SyntaxError);
+            }
+        };
+
+        Ok(Expr {
+            location,
+            data: Box::new(data),
+        })
+    }
+}
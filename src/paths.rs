@@ -0,0 +1,179 @@
+// Path handling is done on strings with forward slashes instead of on
+// "std::path::Path". Output paths end up in generated code, source maps, and
+// the metafile, and those must look the same no matter which platform the
+// build ran on. None of these functions touch the file system: ".." is
+// resolved lexically, which is also what node does when resolving imports.
+//
+// Windows paths are understood on every platform. A path may start with a
+// drive letter ("C:/"), a UNC prefix ("//server/share/"), or a single slash.
+
+pub fn to_slash(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+// Splits a slash-normalized path into the root (which keeps its trailing
+// slash, if any) and the rest of the path
+fn split_root(path: &str) -> (&str, &str) {
+    let bytes = path.as_bytes();
+
+    // "C:/foo" or "C:foo"
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        if bytes.len() >= 3 && bytes[2] == b'/' {
+            return path.split_at(3);
+        }
+        return path.split_at(2);
+    }
+
+    // "//server/share/foo"
+    if path.starts_with("//") && !path.starts_with("///") {
+        let rest = &path[2..];
+        if let Some(server_end) = rest.find('/') {
+            let share = &rest[server_end + 1..];
+            let share_end = share.find('/').map_or(share.len(), |i| i + 1);
+            return path.split_at(2 + server_end + 1 + share_end);
+        }
+        return (path, "");
+    }
+
+    if path.starts_with('/') {
+        return path.split_at(1);
+    }
+
+    ("", path)
+}
+
+pub fn is_absolute(path: &str) -> bool {
+    let path = to_slash(path);
+    let (root, _) = split_root(&path);
+    root.ends_with('/') || (root.starts_with("//") && root.len() > 2)
+}
+
+// Collapses repeated slashes, removes "." segments, and resolves ".." against
+// the preceding segment. Leading ".." segments are kept for relative paths and
+// dropped for absolute ones since you can't go above the root.
+pub fn normalize(path: &str) -> String {
+    let path = to_slash(path);
+    let (root, rest) = split_root(&path);
+    let is_absolute = root.ends_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+
+    for part in rest.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => match parts.last() {
+                Some(&last) if last != ".." => {
+                    parts.pop();
+                }
+                _ if is_absolute => {}
+                _ => parts.push(".."),
+            },
+            part => parts.push(part),
+        }
+    }
+
+    let mut result = String::with_capacity(path.len());
+    result.push_str(root);
+    if !root.is_empty() && !root.ends_with('/') && root.starts_with("//") {
+        result.push('/');
+    }
+    result.push_str(&parts.join("/"));
+
+    if result.is_empty() {
+        return ".".to_owned();
+    }
+    result
+}
+
+pub fn join(base: &str, path: &str) -> String {
+    if is_absolute(path) || base.is_empty() {
+        normalize(path)
+    } else if base.ends_with('/') || base.ends_with('\\') {
+        // Don't turn a root like "/" into "//", which would be a UNC prefix
+        normalize(&format!("{}{}", base, path))
+    } else {
+        normalize(&format!("{}/{}", base, path))
+    }
+}
+
+// The directory containing the path, like "path.dirname" in node
+pub fn dir(path: &str) -> String {
+    let path = normalize(path);
+    let (root, rest) = split_root(&path);
+    match rest.rfind('/') {
+        Some(i) => format!("{}{}", root, &rest[..i]),
+        None if !root.is_empty() => root.to_owned(),
+        None => ".".to_owned(),
+    }
+}
+
+// The last path segment, like "path.basename" in node
+pub fn base(path: &str) -> String {
+    let path = normalize(path);
+    let (_, rest) = split_root(&path);
+    match rest.rfind('/') {
+        Some(i) => rest[i + 1..].to_owned(),
+        None => rest.to_owned(),
+    }
+}
+
+// The extension including the dot, or an empty string. Dot files such as
+// ".gitignore" don't have an extension.
+pub fn ext(path: &str) -> String {
+    let base = base(path);
+    match base.rfind('.') {
+        Some(i) if i > 0 => base[i..].to_owned(),
+        _ => String::new(),
+    }
+}
+
+// Returns a path that leads from the directory "from" to "to". Both paths must
+// either be absolute with the same root or both be relative, otherwise there
+// is no relative path between them.
+pub fn relative(from: &str, to: &str) -> Option<String> {
+    let from = normalize(from);
+    let to = normalize(to);
+    let (from_root, from_rest) = split_root(&from);
+    let (to_root, to_rest) = split_root(&to);
+
+    // Windows drive letters and UNC server names are case-insensitive
+    if !from_root.eq_ignore_ascii_case(to_root) {
+        return None;
+    }
+
+    let from_parts: Vec<&str> = from_rest
+        .split('/')
+        .filter(|p| *p != "." && !p.is_empty())
+        .collect();
+    let to_parts: Vec<&str> = to_rest
+        .split('/')
+        .filter(|p| *p != "." && !p.is_empty())
+        .collect();
+    let common = from_parts
+        .iter()
+        .zip(&to_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    // Can't go up through a ".." of the "from" path since it's unknown what
+    // directory that is
+    if from_parts[common..].contains(&"..") {
+        return None;
+    }
+
+    let mut parts = vec![".."; from_parts.len() - common];
+    parts.extend_from_slice(&to_parts[common..]);
+    if parts.is_empty() {
+        return Some(".".to_owned());
+    }
+    Some(parts.join("/"))
+}
+
+// This is the path shown to the user in log messages and written to the
+// metafile. It's relative to the current directory if the file is inside it
+// and absolute otherwise, and always uses forward slashes.
+pub fn pretty_path(cwd: &str, absolute_path: &str) -> String {
+    match relative(cwd, absolute_path) {
+        Some(relative) if !relative.starts_with("..") => relative,
+        _ => normalize(absolute_path),
+    }
+}
@@ -0,0 +1,2462 @@
+// The printer appends everything to one buffer. Output for a large bundle is
+// several megabytes, and growing a buffer by doubling copies the whole thing
+// every time it fills up, so the buffer is sized up front from an estimate
+// based on the size of the tree.
+
+use crate::ast::{
+    Arg, Binding, BindingKind, Class, ClauseItem, Decl, Expr, ExprKind, ExprOrStmt, Function,
+    LocalKind, NamespaceAlias, NamespaceSymbol, Operator, OperatorCode, OptionalChain, Property,
+    PropertyKind, Reference, Stmt, StmtKind, SymbolMap, AST, INVALID_REF,
+};
+use crate::lexer::{is_identifier, is_identifier_utf16, is_legal_comment};
+use crate::lower::{Feature, Target};
+use crate::number;
+use crate::renamer::Renamer;
+use crate::stack::ensure_sufficient_stack;
+use crate::strings::{
+    escape_utf16, quote_utf16, string_to_utf16, utf16_equals_string, utf16_to_string_lossy,
+};
+
+// Most nodes print as a few bytes of punctuation, keywords, and whitespace
+// plus whatever names and literals they contain, which are counted separately.
+// These are rough averages, erring on the side of overestimating.
+const BYTES_PER_NODE: usize = 4;
+const BYTES_PER_NODE_PRETTY: usize = 7;
+
+// Estimates the number of bytes the printer will produce for the AST. This is
+// only used as the initial capacity of the output buffer, so being a little
+// off just means one extra reallocation at the end.
+pub fn estimate_output_size(ast: &AST, minify_whitespace: bool) -> usize {
+    let mut counter = NodeCounter {
+        symbols: &ast.symbols,
+        nodes: 0,
+        bytes: 0,
+    };
+    for part in &ast.parts {
+        counter.visit_stmts(&part.stmts);
+    }
+
+    let per_node = if minify_whitespace {
+        BYTES_PER_NODE
+    } else {
+        BYTES_PER_NODE_PRETTY
+    };
+    ast.hash_bang.len() + counter.nodes * per_node + counter.bytes
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PrintOptions {
+    // Leaves out all whitespace and newlines that aren't needed to keep
+    // tokens apart, and omits semicolons before a "}"
+    pub minify_whitespace: bool,
+
+    // Prints shorter forms of some expressions that mean the same thing, such
+    // as "!0" instead of "true". The tree itself is shortened by the mangler.
+    pub minify_syntax: bool,
+
+    // Newer syntax is only printed when the tree contains it, except that
+    // strings may be quoted with backticks if the target supports that
+    pub target: Target,
+
+    pub legal_comments: LegalComments,
+}
+
+// What to do with legal comments, which are comments at the top level of a
+// file that start with "//!" or "/*!" or contain "@license" or "@preserve"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LegalComments {
+    None,
+
+    // Each comment stays where it was
+    #[default]
+    Inline,
+
+    // The comments are moved to the end of the output file without duplicates
+    EndOfFile,
+
+    // The comments are left out of the output file. The bundler writes them
+    // to a separate file next to it instead, see "legal_comments".
+    External,
+}
+
+// The legal comments in the order they appear, without duplicates
+pub fn legal_comments<'b, I: IntoIterator<Item = &'b [Stmt]>>(parts: I) -> Vec<String> {
+    let mut comments: Vec<String> = vec![];
+    for stmts in parts {
+        for stmt in stmts {
+            if let StmtKind::Comment { text, .. } = stmt.data.as_ref() {
+                if is_legal_comment(text) && !comments.contains(text) {
+                    comments.push(text.clone());
+                }
+            }
+        }
+    }
+    comments
+}
+
+// Identifiers are printed with the names the renamer gives them. Use
+// "Renamer::no_op()" to keep the original names.
+pub fn print_ast(ast: &AST, renamer: &Renamer, options: PrintOptions) -> String {
+    let parts = ast.parts.iter().map(|part| part.stmts.as_slice());
+    print_parts(ast, parts, renamer, options)
+}
+
+// Like "print_ast" but with the statements of each part passed in separately.
+// The linker uses this to print a file with some of its parts changed or left
+// out without touching the tree itself.
+pub fn print_parts<'b, I: IntoIterator<Item = &'b [Stmt]>>(
+    ast: &AST,
+    parts: I,
+    renamer: &Renamer,
+    options: PrintOptions,
+) -> String {
+    let capacity = estimate_output_size(ast, options.minify_whitespace);
+    print_stmts(
+        &ast.symbols,
+        &ast.hash_bang,
+        capacity,
+        parts,
+        renamer,
+        options,
+    )
+}
+
+// Prints the statements of several files as one file. The symbols of all of
+// them must be in "symbols", which is how the linker joins files together
+// after renaming them apart.
+pub fn print_bundle<'b, I: IntoIterator<Item = &'b [Stmt]>>(
+    symbols: &SymbolMap,
+    hash_bang: &str,
+    parts: I,
+    renamer: &Renamer,
+    options: PrintOptions,
+) -> String {
+    print_stmts(symbols, hash_bang, 0, parts, renamer, options)
+}
+
+fn print_stmts<'b, I: IntoIterator<Item = &'b [Stmt]>>(
+    symbols: &SymbolMap,
+    hash_bang: &str,
+    capacity: usize,
+    parts: I,
+    renamer: &Renamer,
+    options: PrintOptions,
+) -> String {
+    let mut p = Printer {
+        symbols,
+        renamer,
+        js: PrintBuffer::with_capacity(capacity),
+        minify_whitespace: options.minify_whitespace,
+        minify_syntax: options.minify_syntax,
+        target: options.target,
+        legal_comments: options.legal_comments,
+        end_of_file_comments: vec![],
+        indent: 0,
+        needs_semicolon: false,
+        stmt_start: None,
+        export_default_start: None,
+        arrow_expr_start: None,
+        prev_op: None,
+        prev_op_end: 0,
+        prev_num_end: None,
+        prev_reg_exp_end: None,
+    };
+
+    if !hash_bang.is_empty() {
+        p.js.print(hash_bang);
+        p.js.print_ascii(b'\n');
+    }
+    for stmts in parts {
+        for stmt in stmts {
+            p.print_stmt(stmt);
+        }
+    }
+    if !p.end_of_file_comments.is_empty() {
+        p.print_semicolon_if_needed();
+    }
+    for text in std::mem::take(&mut p.end_of_file_comments) {
+        p.print(&text);
+        p.js.print_ascii(b'\n');
+    }
+    p.js.into_string()
+}
+
+// Output is built with these helpers instead of going through "fmt::Write",
+// which has to format its arguments and can't be inlined well. Appending a
+// "&str" or a "char" never revalidates UTF-8, and "print_ascii" lets
+// punctuation be written one byte at a time without encoding a "char" first.
+#[derive(Debug, Clone, Default)]
+pub struct PrintBuffer {
+    js: String,
+}
+
+impl PrintBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            js: String::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.js.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.js.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.js.capacity()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.js
+    }
+
+    pub fn into_string(self) -> String {
+        self.js
+    }
+
+    #[inline]
+    pub fn print(&mut self, text: &str) {
+        self.js.push_str(text);
+    }
+
+    #[inline]
+    pub fn print_char(&mut self, c: char) {
+        self.js.push(c);
+    }
+
+    #[inline]
+    pub fn print_ascii(&mut self, byte: u8) {
+        debug_assert!(byte.is_ascii());
+        self.js.push(byte as char);
+    }
+
+    // Takes back the newline at the end, if there is one
+    pub fn pop_newline(&mut self) -> bool {
+        if self.js.ends_with('\n') {
+            self.js.pop();
+            return true;
+        }
+        false
+    }
+
+    // The last byte printed, which decides whether a space is needed to keep
+    // two tokens apart (e.g. "a - -b" and "a in b")
+    #[inline]
+    pub fn last_byte(&self) -> Option<u8> {
+        self.js.as_bytes().last().copied()
+    }
+}
+
+// Flags for expressions that may not appear unparenthesized in the current
+// position. An "in" operator can't appear in the initializer of a "for" loop,
+// the target of "new" can't contain a call, and an optional chain used by a
+// property access or call that isn't part of it must keep its parentheses.
+const FORBID_IN: u8 = 1 << 0;
+const FORBID_CALL: u8 = 1 << 1;
+const HAS_NON_OPTIONAL_CHAIN_PARENT: u8 = 1 << 2;
+
+struct Printer<'a> {
+    symbols: &'a SymbolMap,
+    renamer: &'a Renamer,
+    js: PrintBuffer,
+    minify_whitespace: bool,
+    minify_syntax: bool,
+    target: Target,
+    indent: usize,
+
+    legal_comments: LegalComments,
+    end_of_file_comments: Vec<String>,
+
+    // When minifying, the semicolon after a statement is only printed once
+    // it's known that another statement follows, since it isn't needed
+    // before a "}" or at the end of the file
+    needs_semicolon: bool,
+
+    // These are the positions in the output where an expression statement,
+    // the value of "export default", and the body of an arrow function start.
+    // Objects, functions, and classes at these positions would be parsed as
+    // something else and must be wrapped in parentheses.
+    stmt_start: Option<usize>,
+    export_default_start: Option<usize>,
+    arrow_expr_start: Option<usize>,
+
+    // These are used to avoid gluing tokens together, such as "a + +b" into
+    // "a++b", "1 .toString()" into "1.toString()", and "/x/ in y" into
+    // "/x/in y"
+    prev_op: Option<OperatorCode>,
+    prev_op_end: usize,
+    prev_num_end: Option<usize>,
+    prev_reg_exp_end: Option<usize>,
+}
+
+impl<'a> Printer<'a> {
+    fn print(&mut self, text: &str) {
+        self.js.print(text);
+    }
+
+    fn print_space(&mut self) {
+        if !self.minify_whitespace {
+            self.js.print_ascii(b' ');
+        }
+    }
+
+    fn print_newline(&mut self) {
+        if !self.minify_whitespace {
+            self.js.print_ascii(b'\n');
+        }
+    }
+
+    fn print_indent(&mut self) {
+        if !self.minify_whitespace {
+            for _ in 0..self.indent {
+                self.js.print("  ");
+            }
+        }
+    }
+
+    fn print_semicolon_after_statement(&mut self) {
+        if self.minify_whitespace {
+            self.needs_semicolon = true;
+        } else {
+            self.js.print(";\n");
+        }
+    }
+
+    fn print_semicolon_if_needed(&mut self) {
+        if self.needs_semicolon {
+            self.js.print_ascii(b';');
+            self.needs_semicolon = false;
+        }
+    }
+
+    // Keeps the annotation so that a minifier that runs over the output can
+    // still remove the call
+    fn print_pure_comment(&mut self) {
+        if self.minify_whitespace {
+            // "a / /*@__PURE__*/b()" must not turn into the comment "a //*..."
+            if self.js.last_byte() == Some(b'/') {
+                self.print(" ");
+            }
+            self.print("/*@__PURE__*/");
+        } else {
+            self.print("/* @__PURE__ */ ");
+        }
+    }
+
+    fn print_space_before_identifier(&mut self) {
+        let needs_space = match self.js.last_byte() {
+            Some(c) => {
+                c.is_ascii_alphanumeric()
+                    || c == b'_'
+                    || c == b'$'
+                    || c >= 0x80
+                    || self.prev_reg_exp_end == Some(self.js.len())
+            }
+            None => false,
+        };
+        if needs_space {
+            self.js.print_ascii(b' ');
+        }
+    }
+
+    fn print_space_before_operator(&mut self, next: OperatorCode) {
+        let prev = match self.prev_op {
+            Some(prev) if self.prev_op_end == self.js.len() => prev,
+            _ => return,
+        };
+
+        // "+ + y" => "+ +y"
+        // "+ ++ y" => "+ ++y"
+        // "x + + y" => "x+ +y"
+        // "x ++ + y" => "x+++y"
+        // "x + ++ y" => "x+ ++y"
+        // "-- >" => "-- >"
+        // "< ! --" => "<! --"
+        let is_plus = |op| op == OperatorCode::BinOpAdd || op == OperatorCode::UnOpPos;
+        let is_minus = |op| op == OperatorCode::BinOpSub || op == OperatorCode::UnOpNeg;
+        let js = self.js.as_str().as_bytes();
+        if (is_plus(prev) && (is_plus(next) || next == OperatorCode::UnOpPreInc))
+            || (is_minus(prev) && (is_minus(next) || next == OperatorCode::UnOpPreDec))
+            || (prev == OperatorCode::UnOpPostDec && next == OperatorCode::BinOpGt)
+            || (prev == OperatorCode::UnOpNot
+                && next == OperatorCode::UnOpPreDec
+                && js.len() > 1
+                && js[js.len() - 2] == b'<')
+        {
+            self.js.print_ascii(b' ');
+        }
+    }
+
+    fn print_operator(&mut self, op_code: OperatorCode) {
+        let entry = op_code.entry();
+        if entry.is_keyword {
+            self.print_space_before_identifier();
+            self.print(entry.text);
+        } else {
+            self.print_space_before_operator(op_code);
+            self.print(entry.text);
+            self.prev_op = Some(op_code);
+            self.prev_op_end = self.js.len();
+        }
+    }
+
+    fn name_for_symbol(&self, reference: Reference) -> &'a str {
+        self.renamer.name_for_symbol(self.symbols, reference)
+    }
+
+    fn print_symbol(&mut self, reference: Reference) {
+        self.print_space_before_identifier();
+        let name = self.name_for_symbol(reference);
+        self.print(name);
+    }
+
+    // Import items that are read off of a namespace object are printed as a
+    // property access. This is used by the CommonJS output format.
+    fn namespace_alias(&self, mut reference: Reference) -> Option<&'a NamespaceAlias> {
+        while self.symbols[reference].link != INVALID_REF {
+            reference = self.symbols[reference].link;
+        }
+        self.symbols[reference].namespace_alias.as_deref()
+    }
+
+    // Calling "ns.a()" would pass "ns" as "this", which a call to a plain
+    // import doesn't do, so that case is printed as "(0, ns.a)()"
+    fn print_call_target(&mut self, target: &Expr, flags: u8) {
+        if let ExprKind::ImportIdentifier { reference } = target.data.as_ref() {
+            if self.namespace_alias(*reference).is_some() {
+                self.print("(0,");
+                self.print_space();
+                self.print_expr(target, Operator::Comma, 0);
+                self.print(")");
+                return;
+            }
+        }
+        self.print_expr(target, Operator::Postfix, flags);
+    }
+
+    fn print_quoted_utf16(&mut self, text: &[u16]) {
+        self.print(&quote_utf16(text, false));
+    }
+
+    fn print_quoted_utf8(&mut self, text: &str) {
+        self.print_quoted_utf16(&string_to_utf16(text));
+    }
+
+    fn print_escaped_utf16(&mut self, text: &[u16], quote: u8) {
+        let mut escaped = String::with_capacity(text.len());
+        escape_utf16(&mut escaped, text, quote);
+        self.print(&escaped);
+    }
+
+    // When minifying syntax, "Infinity" and "NaN" are printed as divisions
+    // since those are shorter (or as short) and can't be shadowed by a local
+    // variable with the same name
+    fn print_number(&mut self, value: f64, level: Operator) {
+        if !value.is_finite() && self.minify_syntax {
+            let text = if value.is_nan() {
+                "0/0"
+            } else if value.is_sign_positive() {
+                "1/0"
+            } else {
+                "-1/0"
+            };
+            let wrap = level >= Operator::Multiply;
+            if wrap {
+                self.print("(");
+            } else if value == f64::NEG_INFINITY {
+                self.print_space_before_operator(OperatorCode::UnOpNeg);
+            } else {
+                self.print_space_before_identifier();
+            }
+            self.print(text);
+            if wrap {
+                self.print(")");
+            }
+        } else if value.is_nan() {
+            self.print_space_before_identifier();
+            self.print("NaN");
+        } else if value.is_infinite() && value.is_sign_positive() {
+            self.print_space_before_identifier();
+            self.print("Infinity");
+        } else if value.is_infinite() {
+            if level >= Operator::Prefix {
+                self.print("(-Infinity)");
+            } else {
+                self.print_space_before_operator(OperatorCode::UnOpNeg);
+                self.print("-Infinity");
+            }
+        } else {
+            // The exponent doesn't need its "+" even when not minifying
+            let text = if self.minify_whitespace || self.minify_syntax {
+                number::shortest_number_to_string(value.abs())
+            } else {
+                number::number_to_string(value.abs()).replacen("e+", "e", 1)
+            };
+            if !value.is_sign_negative() {
+                if !text.starts_with('.') {
+                    self.print_space_before_identifier();
+                }
+                self.print(&text);
+            } else if level >= Operator::Prefix {
+                self.print("(-");
+                self.print(&text);
+                self.print(")");
+            } else {
+                self.print_space_before_operator(OperatorCode::UnOpNeg);
+                self.print("-");
+                self.print(&text);
+            }
+
+            // "1.toString()" is a syntax error but "1.5.toString()" and
+            // "1e3.toString()" aren't
+            if text.bytes().all(|c| c.is_ascii_digit()) {
+                self.prev_num_end = Some(self.js.len());
+            }
+        }
+    }
+
+    // Property names that are valid identifiers don't need to be quoted
+    fn can_print_identifier(name: &str) -> bool {
+        name.is_ascii() && is_identifier(name)
+    }
+
+    fn can_print_identifier_utf16(name: &[u16]) -> bool {
+        name.iter().all(|c| *c < 0x80) && is_identifier_utf16(name)
+    }
+
+    fn print_property_key(&mut self, key: &Expr, prefer_quoted_key: bool) {
+        match key.data.as_ref() {
+            ExprKind::String { value }
+                if !prefer_quoted_key && Self::can_print_identifier_utf16(value) =>
+            {
+                self.print_space_before_identifier();
+                self.print(&utf16_to_string_lossy(value));
+            }
+
+            // Template literals can't be used as property keys
+            ExprKind::String { value } => self.print_quoted_utf16(value),
+            _ => self.print_expr(key, Operator::Lowest, 0),
+        }
+    }
+
+    fn print_property(&mut self, property: &Property) {
+        if property.kind == PropertyKind::PropertySpread {
+            self.print("...");
+            if let Some(value) = &property.value {
+                self.print_expr(value, Operator::Comma, 0);
+            }
+            return;
+        }
+
+        if property.is_static {
+            self.print_space_before_identifier();
+            self.print("static");
+            self.print_space();
+        }
+
+        if property.kind == PropertyKind::PropertyStaticBlock {
+            if let Some(ExprKind::Function { function }) =
+                property.value.as_ref().map(|v| v.data.as_ref())
+            {
+                self.print_block(&function.body.stmts);
+            }
+            return;
+        }
+
+        let function = match &property.value {
+            Some(value) if property.is_method => match value.data.as_ref() {
+                ExprKind::Function { function } => Some(function),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(function) = function {
+            match property.kind {
+                PropertyKind::PropertyGet => {
+                    self.print_space_before_identifier();
+                    self.print("get");
+                    self.print_space();
+                }
+                PropertyKind::PropertySet => {
+                    self.print_space_before_identifier();
+                    self.print("set");
+                    self.print_space();
+                }
+                _ => {}
+            }
+            if function.is_async {
+                self.print_space_before_identifier();
+                self.print("async");
+                self.print_space();
+            }
+            if function.is_generator {
+                self.print("*");
+            }
+        }
+
+        if property.is_computed {
+            self.print("[");
+            self.print_expr(&property.key, Operator::Comma, 0);
+            self.print("]");
+        } else {
+            // "{a: a}" can be printed as "{a}" if the target has shorthand
+            // properties
+            if let (ExprKind::String { value: key }, Some(value), false, true) = (
+                property.key.data.as_ref(),
+                &property.value,
+                property.prefer_quoted_key,
+                self.target.supports(Feature::ShorthandProperty),
+            ) {
+                if let ExprKind::Identifier { reference } = value.data.as_ref() {
+                    let name = self.name_for_symbol(*reference);
+                    if utf16_equals_string(key, name) {
+                        self.print_symbol(*reference);
+                        if let Some(initializer) = &property.initializer {
+                            self.print_initializer(initializer);
+                        }
+                        return;
+                    }
+                }
+            }
+            self.print_property_key(&property.key, property.prefer_quoted_key);
+        }
+
+        if let Some(function) = function {
+            self.print_fn(function);
+            return;
+        }
+
+        if let Some(value) = &property.value {
+            self.print(":");
+            self.print_space();
+            self.print_expr(value, Operator::Comma, 0);
+        }
+        if let Some(initializer) = &property.initializer {
+            self.print_initializer(initializer);
+        }
+    }
+
+    fn print_initializer(&mut self, value: &Expr) {
+        self.print_space();
+        self.print("=");
+        self.print_space();
+        self.print_expr(value, Operator::Comma, 0);
+    }
+
+    fn print_binding(&mut self, binding: &Binding) {
+        ensure_sufficient_stack(|| self.print_binding_inner(binding))
+    }
+
+    fn print_binding_inner(&mut self, binding: &Binding) {
+        match binding.data.as_ref() {
+            BindingKind::Missing => {}
+
+            BindingKind::Identifier { reference } => self.print_symbol(*reference),
+
+            BindingKind::Array { items, has_spread } => {
+                self.print("[");
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        self.print(",");
+                        self.print_space();
+                    }
+                    if *has_spread && i + 1 == items.len() {
+                        self.print("...");
+                    }
+                    self.print_binding(&item.binding);
+                    if let Some(default_value) = &item.default_value {
+                        self.print_initializer(default_value);
+                    }
+                }
+
+                // "[a, , ]" needs the extra comma to keep the hole at the end
+                if let Some(last) = items.last() {
+                    if let BindingKind::Missing = last.binding.data.as_ref() {
+                        self.print(",");
+                    }
+                }
+                self.print("]");
+            }
+
+            BindingKind::Object { properties } => {
+                self.print("{");
+                for (i, property) in properties.iter().enumerate() {
+                    if i != 0 {
+                        self.print(",");
+                        self.print_space();
+                    }
+
+                    if property.is_spread {
+                        self.print("...");
+                    } else if property.is_computed {
+                        self.print("[");
+                        self.print_expr(&property.key, Operator::Comma, 0);
+                        self.print("]:");
+                        self.print_space();
+                    } else {
+                        // "{a: a}" can be printed as "{a}"
+                        let is_shorthand =
+                            match (property.key.data.as_ref(), property.value.data.as_ref()) {
+                                (
+                                    ExprKind::String { value: key },
+                                    BindingKind::Identifier { reference },
+                                ) => {
+                                    self.target.supports(Feature::ShorthandProperty)
+                                        && utf16_equals_string(
+                                            key,
+                                            self.name_for_symbol(*reference),
+                                        )
+                                }
+                                _ => false,
+                            };
+                        if !is_shorthand {
+                            self.print_property_key(&property.key, false);
+                            self.print(":");
+                            self.print_space();
+                        }
+                    }
+
+                    self.print_binding(&property.value);
+                    if let Some(default_value) = &property.default_value {
+                        self.print_initializer(default_value);
+                    }
+                }
+                self.print("}");
+            }
+        }
+    }
+
+    fn print_fn_args(&mut self, args: &[Arg], has_rest_arg: bool, can_omit_parens: bool) {
+        // "(x) => x" can be printed as "x => x" if there were no parentheses
+        // in the source, or as "x=>x" when minifying
+        let wrap = !(can_omit_parens
+            && !has_rest_arg
+            && args.len() == 1
+            && args[0].default_.is_none()
+            && matches!(
+                args[0].binding.data.as_ref(),
+                BindingKind::Identifier { .. }
+            ));
+
+        if wrap {
+            self.print("(");
+        }
+        for (i, arg) in args.iter().enumerate() {
+            if i != 0 {
+                self.print(",");
+                self.print_space();
+            }
+            if has_rest_arg && i + 1 == args.len() {
+                self.print("...");
+            }
+            self.print_binding(&arg.binding);
+            if let Some(default) = &arg.default_ {
+                self.print_initializer(default);
+            }
+        }
+        if wrap {
+            self.print(")");
+        }
+    }
+
+    fn print_fn(&mut self, function: &Function) {
+        self.print_fn_args(&function.args, function.has_rest_arg, false);
+        self.print_space();
+        self.print_block(&function.body.stmts);
+    }
+
+    fn print_class(&mut self, class: &Class) {
+        if let Some(extends) = &class.extends {
+            self.print_space();
+            self.print_space_before_identifier();
+            self.print("extends");
+            self.print_space();
+            self.print_expr(extends, Operator::Postfix, 0);
+        }
+        self.print_space();
+
+        self.print("{");
+        self.print_newline();
+        self.indent += 1;
+        for property in &class.properties {
+            self.print_semicolon_if_needed();
+            self.print_indent();
+            self.print_property(property);
+
+            // Fields need a semicolon, methods don't
+            if property.value.is_none() {
+                self.print_semicolon_after_statement();
+            } else {
+                self.print_newline();
+            }
+        }
+        self.needs_semicolon = false;
+        self.indent -= 1;
+        self.print_indent();
+        self.print("}");
+    }
+
+    fn print_expr(&mut self, expr: &Expr, level: Operator, flags: u8) {
+        ensure_sufficient_stack(|| self.print_expr_inner(expr, level, flags))
+    }
+
+    fn print_expr_inner(&mut self, expr: &Expr, level: Operator, flags: u8) {
+        match expr.data.as_ref() {
+            ExprKind::Missing => {}
+
+            ExprKind::Undefined => {
+                if level >= Operator::Prefix {
+                    self.print("(void 0)");
+                } else {
+                    self.print_space_before_identifier();
+                    self.print("void 0");
+                }
+            }
+
+            ExprKind::Super => {
+                self.print_space_before_identifier();
+                self.print("super");
+            }
+
+            ExprKind::Null => {
+                self.print_space_before_identifier();
+                self.print("null");
+            }
+
+            ExprKind::This => {
+                self.print_space_before_identifier();
+                self.print("this");
+            }
+
+            ExprKind::Boolean { value } => {
+                if self.minify_syntax {
+                    // "true" => "!0"
+                    // "false" => "!1"
+                    let wrap = level >= Operator::Prefix;
+                    if wrap {
+                        self.print("(");
+                    }
+                    self.print_operator(OperatorCode::UnOpNot);
+                    self.print(if *value { "0" } else { "1" });
+                    if wrap {
+                        self.print(")");
+                    }
+                } else {
+                    self.print_space_before_identifier();
+                    self.print(if *value { "true" } else { "false" });
+                }
+            }
+
+            ExprKind::NewTarget => {
+                self.print_space_before_identifier();
+                self.print("new.target");
+            }
+
+            ExprKind::ImportMeta => {
+                self.print_space_before_identifier();
+                self.print("import.meta");
+            }
+
+            ExprKind::Spread { value } => {
+                self.print("...");
+                self.print_expr(value, Operator::Comma, 0);
+            }
+
+            ExprKind::New {
+                target,
+                args,
+                can_be_unwrapped_if_unused,
+            } => {
+                let wrap = level >= Operator::Call
+                    || (*can_be_unwrapped_if_unused && level >= Operator::Postfix);
+                if wrap {
+                    self.print("(");
+                }
+                if *can_be_unwrapped_if_unused {
+                    self.print_pure_comment();
+                }
+                self.print_space_before_identifier();
+                self.print("new");
+                self.print_space();
+                self.print_expr(
+                    target,
+                    Operator::New,
+                    FORBID_CALL | HAS_NON_OPTIONAL_CHAIN_PARENT,
+                );
+
+                // "new a()" can be printed as "new a" when minifying, but not
+                // inside a member expression since "new a().b" and "new a.b"
+                // mean different things
+                if !self.minify_whitespace || !args.is_empty() || level >= Operator::Postfix {
+                    self.print_args(args);
+                }
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Call {
+                target,
+                args,
+                optional_chain,
+                can_be_unwrapped_if_unused,
+                ..
+            } => {
+                // The comment applies to everything up to the end of the call,
+                // so a pure call that's the target of a member expression needs
+                // parentheses
+                let wrap = level >= Operator::New
+                    || flags & FORBID_CALL != 0
+                    || (*optional_chain != OptionalChain::None
+                        && flags & HAS_NON_OPTIONAL_CHAIN_PARENT != 0)
+                    || (*can_be_unwrapped_if_unused && level >= Operator::Postfix);
+                if wrap {
+                    self.print("(");
+                }
+                if *can_be_unwrapped_if_unused {
+                    self.print_pure_comment();
+                }
+                self.print_call_target(target, chain_target_flags(*optional_chain, 0));
+                if *optional_chain == OptionalChain::Start {
+                    self.print("?.");
+                }
+                self.print_args(args);
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::RuntimeCall { .. } => {
+                unreachable!("Runtime calls are resolved to symbols before printing")
+            }
+
+            ExprKind::Require { path, .. } => {
+                let wrap = level >= Operator::New || flags & FORBID_CALL != 0;
+                if wrap {
+                    self.print("(");
+                }
+                self.print_space_before_identifier();
+                self.print("require(");
+                self.print_quoted_utf8(&path.text);
+                self.print(")");
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Import { expr: value } => {
+                let wrap = level >= Operator::New || flags & FORBID_CALL != 0;
+                if wrap {
+                    self.print("(");
+                }
+                self.print_space_before_identifier();
+                self.print("import(");
+                self.print_expr(value, Operator::Comma, 0);
+                self.print(")");
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Dot {
+                target,
+                name,
+                optional_chain,
+                ..
+            } => {
+                let wrap = *optional_chain != OptionalChain::None
+                    && flags & HAS_NON_OPTIONAL_CHAIN_PARENT != 0;
+                if wrap {
+                    self.print("(");
+                }
+                self.print_expr(
+                    target,
+                    Operator::Postfix,
+                    chain_target_flags(*optional_chain, flags),
+                );
+                let is_optional_chain = *optional_chain == OptionalChain::Start;
+                if Self::can_print_identifier(name) {
+                    if is_optional_chain {
+                        self.print("?.");
+                    } else {
+                        if self.prev_num_end == Some(self.js.len()) {
+                            self.print(" ");
+                        }
+                        self.print(".");
+                    }
+                    self.print(name);
+                } else {
+                    if is_optional_chain {
+                        self.print("?.");
+                    }
+                    self.print("[");
+                    self.print_quoted_utf8(name);
+                    self.print("]");
+                }
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Index {
+                target,
+                index,
+                optional_chain,
+                ..
+            } => {
+                let wrap = *optional_chain != OptionalChain::None
+                    && flags & HAS_NON_OPTIONAL_CHAIN_PARENT != 0;
+                if wrap {
+                    self.print("(");
+                }
+                self.print_expr(
+                    target,
+                    Operator::Postfix,
+                    chain_target_flags(*optional_chain, flags),
+                );
+                let is_optional_chain = *optional_chain == OptionalChain::Start;
+                if is_optional_chain {
+                    self.print("?.");
+                }
+                if let ExprKind::PrivateIdentifier { reference } = index.data.as_ref() {
+                    if !is_optional_chain {
+                        self.print(".");
+                    }
+                    self.print_symbol(*reference);
+                } else {
+                    self.print("[");
+                    self.print_expr(index, Operator::Lowest, 0);
+                    self.print("]");
+                }
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::If { test, yes, no } => {
+                let wrap = level >= Operator::Conditional;
+                let flags = if wrap { flags & !FORBID_IN } else { flags };
+                if wrap {
+                    self.print("(");
+                }
+                self.print_expr(test, Operator::Conditional, flags & FORBID_IN);
+                self.print_space();
+                self.print("?");
+                self.print_space();
+                self.print_expr(yes, Operator::Yield, 0);
+                self.print_space();
+                self.print(":");
+                self.print_space();
+                self.print_expr(no, Operator::Yield, flags & FORBID_IN);
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Arrow {
+                is_async,
+                args,
+                has_rest_arg,
+                is_parenthesized,
+                prefer_expr,
+                body,
+            } => {
+                let wrap = level >= Operator::Assign;
+                let flags = if wrap { flags & !FORBID_IN } else { flags };
+                if wrap {
+                    self.print("(");
+                }
+                if *is_async {
+                    self.print_space_before_identifier();
+                    self.print("async");
+                    self.print_space();
+                }
+                self.print_fn_args(
+                    args,
+                    *has_rest_arg,
+                    self.minify_whitespace || !*is_parenthesized,
+                );
+                self.print_space();
+                self.print("=>");
+                self.print_space();
+
+                let mut was_printed = false;
+                if let ([stmt], true) = (body.stmts.as_slice(), *prefer_expr) {
+                    if let StmtKind::Return { value: Some(value) } = stmt.data.as_ref() {
+                        // The body of an arrow function in a for loop
+                        // initializer can't contain a bare "in" either
+                        self.arrow_expr_start = Some(self.js.len());
+                        self.print_expr(value, Operator::Comma, flags & FORBID_IN);
+                        was_printed = true;
+                    }
+                }
+                if !was_printed {
+                    self.print_block(&body.stmts);
+                }
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Function { function } => {
+                let n = Some(self.js.len());
+                let wrap = self.stmt_start == n || self.export_default_start == n;
+                if wrap {
+                    self.print("(");
+                }
+                self.print_space_before_identifier();
+                if function.is_async {
+                    self.print("async ");
+                }
+                self.print("function");
+                if function.is_generator {
+                    self.print("*");
+                    self.print_space();
+                }
+                if let Some(name) = &function.name {
+                    self.print_symbol(name.reference);
+                }
+                self.print_fn(function);
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Class { class } => {
+                let n = Some(self.js.len());
+                let wrap = self.stmt_start == n || self.export_default_start == n;
+                if wrap {
+                    self.print("(");
+                }
+                self.print_space_before_identifier();
+                self.print("class");
+                if let Some(name) = &class.name {
+                    self.print_symbol(name.reference);
+                }
+                self.print_class(class);
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Array { items } => {
+                self.print("[");
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        self.print(",");
+                        self.print_space();
+                    }
+                    self.print_expr(item, Operator::Comma, 0);
+                }
+
+                // "[a, , ]" needs the extra comma to keep the hole at the end
+                if let Some(last) = items.last() {
+                    if let ExprKind::Missing = last.data.as_ref() {
+                        self.print(",");
+                    }
+                }
+                self.print("]");
+            }
+
+            ExprKind::Object { properties } => {
+                let n = Some(self.js.len());
+                let wrap = self.stmt_start == n || self.arrow_expr_start == n;
+                if wrap {
+                    self.print("(");
+                }
+                self.print("{");
+                if !properties.is_empty() {
+                    self.indent += 1;
+                    for (i, property) in properties.iter().enumerate() {
+                        if i != 0 {
+                            self.print(",");
+                        }
+                        self.print_newline();
+                        self.print_indent();
+                        self.print_property(property);
+                    }
+                    self.indent -= 1;
+                    self.print_newline();
+                    self.print_indent();
+                }
+                self.print("}");
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            // A string can be printed as a template literal if that needs
+            // fewer escapes
+            ExprKind::String { value } => {
+                let allow_backtick = self.target.supports(Feature::TemplateLiteral);
+                self.print(&quote_utf16(value, allow_backtick));
+            }
+
+            ExprKind::Template {
+                tag,
+                head,
+                head_raw,
+                parts,
+            } => {
+                let is_tagged = !matches!(tag.data.as_ref(), ExprKind::Missing);
+                if is_tagged {
+                    self.print_call_target(tag, HAS_NON_OPTIONAL_CHAIN_PARENT);
+                }
+
+                // Tagged templates can see the raw text, so it has to be
+                // printed as written
+                self.print("`");
+                if is_tagged {
+                    self.print(head_raw);
+                } else {
+                    self.print_escaped_utf16(head, b'`');
+                }
+                for part in parts {
+                    self.print("${");
+                    self.print_expr(&part.value, Operator::Lowest, 0);
+                    self.print("}");
+                    if is_tagged {
+                        self.print(&part.tail_raw);
+                    } else {
+                        self.print_escaped_utf16(&part.tail, b'`');
+                    }
+                }
+                self.print("`");
+            }
+
+            ExprKind::RegExp { value } => {
+                // "a / /b/" must not turn into the comment "a //b/"
+                if self.js.last_byte() == Some(b'/') {
+                    self.print(" ");
+                }
+                self.print(value);
+                self.prev_reg_exp_end = Some(self.js.len());
+            }
+
+            ExprKind::BigInt { value } => {
+                self.print_space_before_identifier();
+                self.print(value);
+                self.print("n");
+            }
+
+            ExprKind::Number { value } => self.print_number(*value, level),
+
+            ExprKind::Identifier { reference } | ExprKind::PrivateIdentifier { reference } => {
+                self.print_symbol(*reference)
+            }
+
+            ExprKind::ImportIdentifier { reference } => match self.namespace_alias(*reference) {
+                Some(alias) => {
+                    self.print_symbol(alias.namespace_ref);
+                    self.print(".");
+                    self.print(&alias.alias);
+                }
+                None => self.print_symbol(*reference),
+            },
+
+            ExprKind::Await { value } => {
+                let wrap = level >= Operator::Prefix;
+                if wrap {
+                    self.print("(");
+                }
+                self.print_space_before_identifier();
+                self.print("await");
+                self.print_space();
+                self.print_expr(value, Operator::Exponentiation, 0);
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Yield { value, is_star } => {
+                let wrap = level >= Operator::Assign;
+                if wrap {
+                    self.print("(");
+                }
+                self.print_space_before_identifier();
+                self.print("yield");
+                if *is_star {
+                    self.print("*");
+                }
+                if !matches!(value.data.as_ref(), ExprKind::Missing) {
+                    self.print_space();
+                    self.print_expr(value, Operator::Yield, 0);
+                }
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            // "-(1/0)" => "-1/0"
+            ExprKind::Unary {
+                op_code: OperatorCode::UnOpNeg,
+                value,
+            } if self.minify_syntax
+                && matches!(value.data.as_ref(), ExprKind::Number { value } if !value.is_finite()) =>
+            {
+                if let ExprKind::Number { value } = value.data.as_ref() {
+                    self.print_number(-value, level);
+                }
+            }
+
+            ExprKind::Unary { op_code, value } => {
+                let entry = op_code.entry();
+                let wrap = level >= entry.level;
+                if wrap {
+                    self.print("(");
+                }
+
+                if !op_code.is_prefix() {
+                    self.print_expr(value, Operator::Prefix, 0);
+                }
+                self.print_operator(*op_code);
+                if entry.is_keyword {
+                    self.print_space();
+                }
+                if op_code.is_prefix() {
+                    self.print_expr(value, Operator::Exponentiation, 0);
+                }
+
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::Binary {
+                op_code,
+                left,
+                right,
+            } => {
+                let entry = op_code.entry();
+                let mut wrap = level >= entry.level
+                    || (*op_code == OperatorCode::BinOpIn && flags & FORBID_IN != 0);
+
+                // Destructuring assignments must be parenthesized
+                let n = Some(self.js.len());
+                if (self.stmt_start == n || self.arrow_expr_start == n)
+                    && matches!(left.data.as_ref(), ExprKind::Object { .. })
+                {
+                    wrap = true;
+                }
+
+                let flags = if wrap { flags & !FORBID_IN } else { flags };
+                if wrap {
+                    self.print("(");
+                }
+
+                let mut left_level = level_below(entry.level);
+                let mut right_level = level_below(entry.level);
+                if op_code.is_right_associative() {
+                    left_level = entry.level;
+                }
+                if op_code.is_left_associative() {
+                    right_level = entry.level;
+                }
+
+                match op_code {
+                    // "??" can't directly contain "||" or "&&" without being
+                    // wrapped in parentheses
+                    OperatorCode::BinOpNullishCoalescing => {
+                        if is_logical_or_and(left) {
+                            left_level = Operator::Prefix;
+                        }
+                        if is_logical_or_and(right) {
+                            right_level = Operator::Prefix;
+                        }
+                    }
+
+                    // "**" can't contain certain unary expressions
+                    OperatorCode::BinOpPow => match left.data.as_ref() {
+                        ExprKind::Unary { op_code, .. } if !op_code.is_unary_update() => {
+                            left_level = Operator::Call
+                        }
+                        ExprKind::Await { .. } => left_level = Operator::Call,
+                        ExprKind::Number { value } if value.is_sign_negative() => {
+                            left_level = Operator::Call
+                        }
+                        _ => {}
+                    },
+
+                    _ => {}
+                }
+
+                self.print_expr(left, left_level, flags & FORBID_IN);
+                if *op_code != OperatorCode::BinOpComma {
+                    self.print_space();
+                }
+                self.print_operator(*op_code);
+                self.print_space();
+                self.print_expr(right, right_level, flags & FORBID_IN);
+
+                if wrap {
+                    self.print(")");
+                }
+            }
+
+            ExprKind::JSXElement { .. } => unreachable!("JSX is lowered before printing"),
+        }
+    }
+
+    fn print_args(&mut self, args: &[Expr]) {
+        self.print("(");
+        for (i, arg) in args.iter().enumerate() {
+            if i != 0 {
+                self.print(",");
+                self.print_space();
+            }
+            self.print_expr(arg, Operator::Comma, 0);
+        }
+        self.print(")");
+    }
+
+    fn print_block(&mut self, stmts: &[Stmt]) {
+        self.print("{");
+        self.print_newline();
+
+        self.indent += 1;
+        for stmt in stmts {
+            self.print_stmt(stmt);
+        }
+        self.indent -= 1;
+        self.needs_semicolon = false;
+
+        self.print_indent();
+        self.print("}");
+    }
+
+    // The body of a control flow statement, which only gets braces if it was
+    // a block to begin with
+    fn print_body(&mut self, body: &Stmt) {
+        if let StmtKind::Block { stmts } = body.data.as_ref() {
+            self.print_space();
+            self.print_block(stmts);
+            self.print_newline();
+        } else {
+            self.print_newline();
+            self.indent += 1;
+            self.print_stmt(body);
+            self.indent -= 1;
+        }
+    }
+
+    fn print_decls(&mut self, keyword: &str, decls: &[Decl], flags: u8) {
+        self.print_space_before_identifier();
+        self.print(keyword);
+        self.print_space();
+        for (i, decl) in decls.iter().enumerate() {
+            if i != 0 {
+                self.print(",");
+                self.print_space();
+            }
+            self.print_binding(&decl.binding);
+            if let Some(value) = &decl.value {
+                self.print_space();
+                self.print("=");
+                self.print_space();
+                self.print_expr(value, Operator::Comma, flags);
+            }
+        }
+    }
+
+    fn print_for_loop_init(&mut self, init: &Stmt) {
+        match init.data.as_ref() {
+            StmtKind::Expr { value } => self.print_expr(value, Operator::Lowest, FORBID_IN),
+            StmtKind::Local { decls, kind, .. } => {
+                self.print_decls(local_keyword(*kind), decls, FORBID_IN)
+            }
+            _ => unreachable!("Unexpected statement in a for loop initializer"),
+        }
+    }
+
+    fn print_if(&mut self, test: &Expr, yes: &Stmt, no: &Option<Stmt>) {
+        ensure_sufficient_stack(|| self.print_if_inner(test, yes, no))
+    }
+
+    fn print_if_inner(&mut self, test: &Expr, yes: &Stmt, no: &Option<Stmt>) {
+        self.print_space_before_identifier();
+        self.print("if");
+        self.print_space();
+        self.print("(");
+        self.print_expr(test, Operator::Lowest, 0);
+        self.print(")");
+
+        if let StmtKind::Block { stmts } = yes.data.as_ref() {
+            self.print_space();
+            self.print_block(stmts);
+            if no.is_some() {
+                self.print_space();
+            } else {
+                self.print_newline();
+            }
+        } else if no.is_some() && wrap_to_avoid_ambiguous_else(yes) {
+            // "if (a) if (b) c; else d" would attach the "else" to the inner
+            // "if", so the inner "if" has to be put in a block
+            self.print_space();
+            self.print("{");
+            self.print_newline();
+            self.indent += 1;
+            self.print_stmt(yes);
+            self.indent -= 1;
+            self.needs_semicolon = false;
+            self.print_indent();
+            self.print("}");
+            self.print_space();
+        } else {
+            self.print_newline();
+            self.indent += 1;
+            self.print_stmt(yes);
+            self.indent -= 1;
+            if no.is_some() {
+                self.print_indent();
+            }
+        }
+
+        if let Some(no) = no {
+            self.print_semicolon_if_needed();
+            self.print_space_before_identifier();
+            self.print("else");
+
+            match no.data.as_ref() {
+                StmtKind::Block { stmts } => {
+                    self.print_space();
+                    self.print_block(stmts);
+                    self.print_newline();
+                }
+                StmtKind::If { test, yes, no } => {
+                    self.print_space();
+                    self.print_if(test, yes, no);
+                }
+                _ => {
+                    self.print_newline();
+                    self.indent += 1;
+                    self.print_stmt(no);
+                    self.indent -= 1;
+                }
+            }
+        }
+    }
+
+    fn print_clause_items(&mut self, items: &[ClauseItem]) {
+        self.print("{");
+        for (i, item) in items.iter().enumerate() {
+            if i != 0 {
+                self.print(",");
+                self.print_space();
+            }
+            let name = self.name_for_symbol(item.name.reference);
+            self.print(name);
+            if name != item.alias {
+                self.print(" as ");
+                self.print(&item.alias);
+            }
+        }
+        self.print("}");
+    }
+
+    fn print_stmt(&mut self, stmt: &Stmt) {
+        ensure_sufficient_stack(|| self.print_stmt_inner(stmt))
+    }
+
+    fn print_stmt_inner(&mut self, stmt: &Stmt) {
+        // Comments that aren't printed here must not leave a semicolon behind.
+        // Other comments are only there when they were preserved on purpose
+        // and are left out when minifying.
+        if let StmtKind::Comment { text, .. } = stmt.data.as_ref() {
+            if !is_legal_comment(text) {
+                if self.minify_whitespace {
+                    return;
+                }
+            } else {
+                match self.legal_comments {
+                    LegalComments::Inline => {}
+                    LegalComments::EndOfFile => {
+                        if !self.end_of_file_comments.contains(text) {
+                            self.end_of_file_comments.push(text.clone());
+                        }
+                        return;
+                    }
+                    LegalComments::None | LegalComments::External => return,
+                }
+            }
+        }
+        self.print_semicolon_if_needed();
+
+        match stmt.data.as_ref() {
+            // These only exist in TypeScript and are either removed or lowered
+            // to JavaScript by the parser
+            StmtKind::TypeScript
+            | StmtKind::Enum { .. }
+            | StmtKind::Namespace { .. }
+            | StmtKind::ExportEquals { .. } => {}
+
+            // These only appear inside a "try" statement
+            StmtKind::Catch(_) | StmtKind::Finally(_) => {}
+
+            // A file with a syntax error is never printed
+            StmtKind::SyntaxError => {}
+
+            StmtKind::Empty => {
+                self.print_indent();
+                self.print(";");
+                self.print_newline();
+            }
+
+            StmtKind::Block { stmts } => {
+                self.print_indent();
+                self.print_block(stmts);
+                self.print_newline();
+            }
+
+            StmtKind::Debugger => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("debugger");
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::Directive { value } => {
+                self.print_indent();
+                self.print_quoted_utf16(value);
+                self.print_semicolon_after_statement();
+            }
+
+            // The newline is needed even when minifying since the comment may
+            // be a single-line comment. A trailing comment goes back on the
+            // line that the statement before it ended on.
+            StmtKind::Comment { text, is_trailing } => {
+                if *is_trailing && !self.minify_whitespace && self.js.pop_newline() {
+                    self.print(" ");
+                } else {
+                    self.print_indent();
+                }
+                self.print(text);
+                self.js.print_ascii(b'\n');
+            }
+
+            StmtKind::Function {
+                function,
+                is_export,
+            } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                if *is_export {
+                    self.print("export ");
+                }
+                if function.is_async {
+                    self.print("async ");
+                }
+                self.print("function");
+                if function.is_generator {
+                    self.print("*");
+                    self.print_space();
+                }
+                if let Some(name) = &function.name {
+                    self.print_symbol(name.reference);
+                }
+                self.print_fn(function);
+                self.print_newline();
+            }
+
+            StmtKind::Class { class, is_export } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                if *is_export {
+                    self.print("export ");
+                }
+                self.print("class");
+                if let Some(name) = &class.name {
+                    self.print_symbol(name.reference);
+                }
+                self.print_class(class);
+                self.print_newline();
+            }
+
+            StmtKind::ExportDefault { value, .. } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("export default");
+                self.print_space();
+
+                match value {
+                    ExprOrStmt::Expr(value) => {
+                        // Functions and classes must be parenthesized to avoid
+                        // being parsed as declarations
+                        self.export_default_start = Some(self.js.len());
+                        self.print_expr(value, Operator::Comma, 0);
+                        self.print_semicolon_after_statement();
+                    }
+                    ExprOrStmt::Stmt(stmt) => match stmt.data.as_ref() {
+                        StmtKind::Function { function, .. } => {
+                            self.print_space_before_identifier();
+                            if function.is_async {
+                                self.print("async ");
+                            }
+                            self.print("function");
+                            if function.is_generator {
+                                self.print("*");
+                                self.print_space();
+                            }
+                            if let Some(name) = &function.name {
+                                self.print_symbol(name.reference);
+                            }
+                            self.print_fn(function);
+                            self.print_newline();
+                        }
+                        StmtKind::Class { class, .. } => {
+                            self.print_space_before_identifier();
+                            self.print("class");
+                            if let Some(name) = &class.name {
+                                self.print_symbol(name.reference);
+                            }
+                            self.print_class(class);
+                            self.print_newline();
+                        }
+                        _ => unreachable!("Unexpected statement in \"export default\""),
+                    },
+                }
+            }
+
+            StmtKind::ExportStar { item, path } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("export");
+                self.print_space();
+                self.print("*");
+                self.print_space();
+                if let Some(item) = item {
+                    self.print("as ");
+                    self.print(&item.alias);
+                    self.print(" from");
+                } else {
+                    self.print("from");
+                }
+                self.print_space();
+                self.print_quoted_utf8(&path.text);
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::ExportClause { items } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("export");
+                self.print_space();
+                self.print_clause_items(items);
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::ExportFrom { items, path, .. } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("export");
+                self.print_space();
+                self.print_clause_items(items);
+                self.print_space();
+                self.print("from");
+                self.print_space();
+                self.print_quoted_utf8(&path.text);
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::Import {
+                namespace_symbol,
+                default_name,
+                path,
+            } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("import");
+                self.print_space();
+
+                let mut item_count = 0;
+                if let Some(name) = default_name {
+                    self.print_symbol(name.reference);
+                    item_count += 1;
+                }
+                match namespace_symbol {
+                    NamespaceSymbol::Clause { items } => {
+                        if !items.is_empty() {
+                            if item_count > 0 {
+                                self.print(",");
+                                self.print_space();
+                            }
+                            self.print("{");
+                            for (i, item) in items.iter().enumerate() {
+                                if i != 0 {
+                                    self.print(",");
+                                    self.print_space();
+                                }
+                                self.print(&item.alias);
+                                let name = self.name_for_symbol(item.name.reference);
+                                if name != item.alias {
+                                    self.print(" as ");
+                                    self.print(name);
+                                }
+                            }
+                            self.print("}");
+                            item_count += 1;
+                        }
+                    }
+                    NamespaceSymbol::Star { namespace_ref, .. } => {
+                        if item_count > 0 {
+                            self.print(",");
+                            self.print_space();
+                        }
+                        self.print("*");
+                        self.print_space();
+                        self.print("as ");
+                        self.print_symbol(*namespace_ref);
+                        item_count += 1;
+                    }
+                }
+                if item_count > 0 {
+                    self.print_space();
+                    self.print_space_before_identifier();
+                    self.print("from");
+                    self.print_space();
+                }
+
+                self.print_quoted_utf8(&path.text);
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::Local {
+                decls,
+                kind,
+                is_export,
+                ..
+            } => {
+                self.print_indent();
+                if *is_export {
+                    self.print_space_before_identifier();
+                    self.print("export ");
+                }
+                self.print_decls(local_keyword(*kind), decls, 0);
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::If { test, yes, no } => {
+                self.print_indent();
+                self.print_if(test, yes, no);
+            }
+
+            StmtKind::DoWhile { body, test } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("do");
+                if let StmtKind::Block { stmts } = body.data.as_ref() {
+                    self.print_space();
+                    self.print_block(stmts);
+                    self.print_space();
+                } else {
+                    self.print_newline();
+                    self.indent += 1;
+                    self.print_stmt(body);
+                    self.print_semicolon_if_needed();
+                    self.indent -= 1;
+                    self.print_indent();
+                }
+                self.print_space_before_identifier();
+                self.print("while");
+                self.print_space();
+                self.print("(");
+                self.print_expr(test, Operator::Lowest, 0);
+                self.print(")");
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::ForIn { init, value, body } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("for");
+                self.print_space();
+                self.print("(");
+                self.print_for_loop_init(init);
+                self.print_space();
+                self.print_space_before_identifier();
+                self.print("in");
+                self.print_space();
+                self.print_expr(value, Operator::Lowest, 0);
+                self.print(")");
+                self.print_body(body);
+            }
+
+            StmtKind::ForOf {
+                is_await,
+                init,
+                value,
+                body,
+            } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("for");
+                if *is_await {
+                    self.print(" await");
+                }
+                self.print_space();
+                self.print("(");
+                self.print_for_loop_init(init);
+                self.print_space();
+                self.print_space_before_identifier();
+                self.print("of");
+                self.print_space();
+                self.print_expr(value, Operator::Comma, 0);
+                self.print(")");
+                self.print_body(body);
+            }
+
+            StmtKind::While { test, body } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("while");
+                self.print_space();
+                self.print("(");
+                self.print_expr(test, Operator::Lowest, 0);
+                self.print(")");
+                self.print_body(body);
+            }
+
+            StmtKind::With { value, body, .. } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("with");
+                self.print_space();
+                self.print("(");
+                self.print_expr(value, Operator::Lowest, 0);
+                self.print(")");
+                self.print_body(body);
+            }
+
+            StmtKind::Label { name, stmt } => {
+                self.print_indent();
+                self.print_symbol(name.reference);
+                self.print(":");
+                self.print_body(stmt);
+            }
+
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("try");
+                self.print_space();
+                self.print_block(body);
+
+                if let Some(catch) = catch {
+                    self.print_space();
+                    self.print("catch");
+                    if let Some(binding) = &catch.binding {
+                        self.print_space();
+                        self.print("(");
+                        self.print_binding(binding);
+                        self.print(")");
+                    }
+                    self.print_space();
+                    self.print_block(&catch.body);
+                }
+
+                if let Some(finally) = finally {
+                    self.print_space();
+                    self.print("finally");
+                    self.print_space();
+                    self.print_block(&finally.stmts);
+                }
+
+                self.print_newline();
+            }
+
+            StmtKind::For {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("for");
+                self.print_space();
+                self.print("(");
+                if let Some(init) = init {
+                    self.print_for_loop_init(init);
+                }
+                self.print(";");
+                if let Some(test) = test {
+                    self.print_space();
+                    self.print_expr(test, Operator::Lowest, 0);
+                }
+                self.print(";");
+                if let Some(update) = update {
+                    self.print_space();
+                    self.print_expr(update, Operator::Lowest, 0);
+                }
+                self.print(")");
+                self.print_body(body);
+            }
+
+            StmtKind::Switch { test, cases, .. } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("switch");
+                self.print_space();
+                self.print("(");
+                self.print_expr(test, Operator::Lowest, 0);
+                self.print(")");
+                self.print_space();
+                self.print("{");
+                self.print_newline();
+                self.indent += 1;
+
+                for case in cases {
+                    self.print_semicolon_if_needed();
+                    self.print_indent();
+                    if let Some(value) = &case.value {
+                        self.print("case");
+                        self.print_space();
+                        self.print_expr(value, Operator::Lowest, 0);
+                    } else {
+                        self.print("default");
+                    }
+                    self.print(":");
+
+                    if let [stmt] = case.body.as_slice() {
+                        if let StmtKind::Block { stmts } = stmt.data.as_ref() {
+                            self.print_space();
+                            self.print_block(stmts);
+                            self.print_newline();
+                            continue;
+                        }
+                    }
+
+                    self.print_newline();
+                    self.indent += 1;
+                    for stmt in &case.body {
+                        self.print_stmt(stmt);
+                    }
+                    self.indent -= 1;
+                }
+
+                self.indent -= 1;
+                self.needs_semicolon = false;
+                self.print_indent();
+                self.print("}");
+                self.print_newline();
+            }
+
+            StmtKind::Break { name } | StmtKind::Continue { name } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                if let StmtKind::Break { .. } = stmt.data.as_ref() {
+                    self.print("break");
+                } else {
+                    self.print("continue");
+                }
+                if let Some(name) = name {
+                    self.print(" ");
+                    self.print_symbol(name.reference);
+                }
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::Return { value } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("return");
+                if let Some(value) = value {
+                    self.print_space();
+                    self.print_expr(value, Operator::Lowest, 0);
+                }
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::Throw { value } => {
+                self.print_indent();
+                self.print_space_before_identifier();
+                self.print("throw");
+                self.print_space();
+                self.print_expr(value, Operator::Lowest, 0);
+                self.print_semicolon_after_statement();
+            }
+
+            StmtKind::Expr { value } => {
+                self.print_indent();
+                self.stmt_start = Some(self.js.len());
+                self.print_expr(value, Operator::Lowest, 0);
+                self.print_semicolon_after_statement();
+            }
+        }
+    }
+}
+
+// The quote character that needs the fewest escapes, preferring double quotes
+fn local_keyword(kind: LocalKind) -> &'static str {
+    match kind {
+        LocalKind::Var => "var",
+        LocalKind::Let => "let",
+        LocalKind::Const => "const",
+    }
+}
+
+fn level_below(level: Operator) -> Operator {
+    OPERATORS_BY_LEVEL[(level as usize).saturating_sub(1)]
+}
+
+const OPERATORS_BY_LEVEL: [Operator; 22] = [
+    Operator::Lowest,
+    Operator::Comma,
+    Operator::Spread,
+    Operator::Yield,
+    Operator::Assign,
+    Operator::Conditional,
+    Operator::NullishCoalescing,
+    Operator::LogicalOr,
+    Operator::LogicalAnd,
+    Operator::BitwiseOr,
+    Operator::BitwiseXor,
+    Operator::BitwiseAnd,
+    Operator::Equals,
+    Operator::Compare,
+    Operator::Shift,
+    Operator::Add,
+    Operator::Multiply,
+    Operator::Exponentiation,
+    Operator::Prefix,
+    Operator::Postfix,
+    Operator::New,
+    Operator::Call,
+];
+
+fn is_logical_or_and(expr: &Expr) -> bool {
+    matches!(
+        expr.data.as_ref(),
+        ExprKind::Binary {
+            op_code: OperatorCode::BinOpLogicalOr | OperatorCode::BinOpLogicalAnd,
+            ..
+        }
+    )
+}
+
+// The target of a link that isn't part of an optional chain can't be printed
+// as one, since "(a?.b).c" and "a?.b.c" are different
+fn chain_target_flags(optional_chain: OptionalChain, flags: u8) -> u8 {
+    let flags = flags & FORBID_CALL;
+    if optional_chain == OptionalChain::None {
+        flags | HAS_NON_OPTIONAL_CHAIN_PARENT
+    } else {
+        flags
+    }
+}
+
+// Returns true if the "else" of an "if" statement containing this statement
+// would bind to an "if" inside it instead
+fn wrap_to_avoid_ambiguous_else(mut stmt: &Stmt) -> bool {
+    loop {
+        stmt = match stmt.data.as_ref() {
+            StmtKind::If { no: None, .. } => return true,
+            StmtKind::If { no: Some(no), .. } => no,
+            StmtKind::For { body, .. }
+            | StmtKind::ForIn { body, .. }
+            | StmtKind::ForOf { body, .. }
+            | StmtKind::While { body, .. }
+            | StmtKind::With { body, .. } => body,
+            StmtKind::Label { stmt, .. } => stmt,
+            _ => return false,
+        }
+    }
+}
+
+struct NodeCounter<'a> {
+    symbols: &'a SymbolMap,
+    nodes: usize,
+    bytes: usize,
+}
+
+impl<'a> NodeCounter<'a> {
+    // Identifiers are printed using their symbol name. The renamer may shorten
+    // it but never makes it much longer, so this is a good upper bound.
+    fn count_name(&mut self, reference: Reference) {
+        self.bytes += self
+            .symbols
+            .outer
+            .get(reference.outer)
+            .and_then(|symbols| symbols.get(reference.inner))
+            .map_or(1, |symbol| symbol.name.len());
+    }
+
+    fn visit_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.visit_stmt(stmt);
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        ensure_sufficient_stack(|| self.visit_stmt_inner(stmt))
+    }
+
+    fn visit_stmt_inner(&mut self, stmt: &Stmt) {
+        self.nodes += 1;
+
+        match stmt.data.as_ref() {
+            StmtKind::Block { stmts } | StmtKind::Namespace { stmts, .. } => {
+                self.visit_stmts(stmts)
+            }
+            StmtKind::Directive { value } => self.bytes += value.len() + 2,
+            StmtKind::Comment { text, .. } => self.bytes += text.len() + 1,
+            StmtKind::ExportClause { items } | StmtKind::ExportFrom { items, .. } => {
+                self.nodes += items.len();
+                self.bytes += items.iter().map(|item| item.alias.len()).sum::<usize>();
+            }
+            StmtKind::Import { path, .. } | StmtKind::ExportStar { path, .. } => {
+                self.bytes += path.text.len() + 2
+            }
+            StmtKind::ExportDefault {
+                value: ExprOrStmt::Expr(value),
+                ..
+            }
+            | StmtKind::ExportEquals { value }
+            | StmtKind::Expr { value }
+            | StmtKind::Throw { value }
+            | StmtKind::Return { value: Some(value) } => self.visit_expr(value),
+            StmtKind::Label { stmt, .. }
+            | StmtKind::ExportDefault {
+                value: ExprOrStmt::Stmt(stmt),
+                ..
+            } => self.visit_stmt(stmt),
+            StmtKind::Enum { values, .. } => {
+                for value in values {
+                    self.nodes += 1;
+                    self.bytes += value.name.len();
+                    if let Some(value) = &value.value {
+                        self.visit_expr(value);
+                    }
+                }
+            }
+            StmtKind::Function { function, .. } => self.visit_function(function),
+            StmtKind::Class { class, .. } => self.visit_class(class),
+            StmtKind::If { test, yes, no } => {
+                self.visit_expr(test);
+                self.visit_stmt(yes);
+                if let Some(no) = no {
+                    self.visit_stmt(no);
+                }
+            }
+            StmtKind::For {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                if let Some(init) = init {
+                    self.visit_stmt(init);
+                }
+                if let Some(test) = test {
+                    self.visit_expr(test);
+                }
+                if let Some(update) = update {
+                    self.visit_expr(update);
+                }
+                self.visit_stmt(body);
+            }
+            StmtKind::ForIn { init, value, body }
+            | StmtKind::ForOf {
+                init, value, body, ..
+            } => {
+                self.visit_stmt(init);
+                self.visit_expr(value);
+                self.visit_stmt(body);
+            }
+            StmtKind::DoWhile { body, test } | StmtKind::While { test, body } => {
+                self.visit_stmt(body);
+                self.visit_expr(test);
+            }
+            StmtKind::With { value, body, .. } => {
+                self.visit_expr(value);
+                self.visit_stmt(body);
+            }
+            StmtKind::Catch(catch) => self.visit_stmts(&catch.body),
+            StmtKind::Finally(finally) => self.visit_stmts(&finally.stmts),
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.visit_stmts(body);
+                if let Some(catch) = catch {
+                    self.visit_stmts(&catch.body);
+                }
+                if let Some(finally) = finally {
+                    self.visit_stmts(&finally.stmts);
+                }
+            }
+            StmtKind::Switch { test, cases, .. } => {
+                self.visit_expr(test);
+                for case in cases {
+                    self.nodes += 1;
+                    if let Some(value) = &case.value {
+                        self.visit_expr(value);
+                    }
+                    self.visit_stmts(&case.body);
+                }
+            }
+            StmtKind::Local { decls, .. } => {
+                for decl in decls {
+                    self.visit_binding(&decl.binding);
+                    if let Some(value) = &decl.value {
+                        self.visit_expr(value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_binding(&mut self, binding: &Binding) {
+        ensure_sufficient_stack(|| self.visit_binding_inner(binding))
+    }
+
+    fn visit_binding_inner(&mut self, binding: &Binding) {
+        self.nodes += 1;
+
+        match binding.data.as_ref() {
+            BindingKind::Array { items, .. } => {
+                for item in items {
+                    self.visit_binding(&item.binding);
+                    if let Some(default) = &item.default_value {
+                        self.visit_expr(default);
+                    }
+                }
+            }
+            BindingKind::Object { properties } => {
+                for property in properties {
+                    self.visit_expr(&property.key);
+                    self.visit_binding(&property.value);
+                    if let Some(default) = &property.default_value {
+                        self.visit_expr(default);
+                    }
+                }
+            }
+            BindingKind::Identifier { reference } => self.count_name(*reference),
+            BindingKind::Missing => {}
+        }
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        for arg in &function.args {
+            self.visit_binding(&arg.binding);
+            if let Some(default) = &arg.default_ {
+                self.visit_expr(default);
+            }
+        }
+        self.visit_stmts(&function.body.stmts);
+    }
+
+    fn visit_class(&mut self, class: &Class) {
+        if let Some(extends) = &class.extends {
+            self.visit_expr(extends);
+        }
+        for property in &class.properties {
+            self.visit_property(property);
+        }
+    }
+
+    fn visit_property(&mut self, property: &Property) {
+        self.visit_expr(&property.key);
+        if let Some(value) = &property.value {
+            self.visit_expr(value);
+        }
+        if let Some(initializer) = &property.initializer {
+            self.visit_expr(initializer);
+        }
+    }
+
+    fn visit_exprs(&mut self, exprs: &[Expr]) {
+        for expr in exprs {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        ensure_sufficient_stack(|| self.visit_expr_inner(expr))
+    }
+
+    fn visit_expr_inner(&mut self, expr: &Expr) {
+        self.nodes += 1;
+
+        match expr.data.as_ref() {
+            ExprKind::String { value } | ExprKind::Template { head: value, .. } => {
+                self.bytes += value.len() + 2
+            }
+            ExprKind::RegExp { value } | ExprKind::BigInt { value } => self.bytes += value.len(),
+            ExprKind::Require { path, .. } => self.bytes += path.text.len() + 2,
+            ExprKind::Identifier { reference } | ExprKind::ImportIdentifier { reference } => {
+                self.count_name(*reference)
+            }
+            _ => {}
+        }
+
+        match expr.data.as_ref() {
+            ExprKind::Array { items } => self.visit_exprs(items),
+            ExprKind::Unary { value, .. }
+            | ExprKind::Spread { value }
+            | ExprKind::Await { value }
+            | ExprKind::Yield { value, .. }
+            | ExprKind::Import { expr: value } => self.visit_expr(value),
+            ExprKind::Dot { target, name, .. } => {
+                self.bytes += name.len();
+                self.visit_expr(target);
+            }
+            ExprKind::Binary { left, right, .. }
+            | ExprKind::Index {
+                target: left,
+                index: right,
+                ..
+            } => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+            }
+            ExprKind::New { target, args, .. } | ExprKind::Call { target, args, .. } => {
+                self.visit_expr(target);
+                self.visit_exprs(args);
+            }
+            ExprKind::RuntimeCall { args, .. } => self.visit_exprs(args),
+            ExprKind::Arrow { args, body, .. } => {
+                for arg in args {
+                    self.visit_binding(&arg.binding);
+                    if let Some(default) = &arg.default_ {
+                        self.visit_expr(default);
+                    }
+                }
+                self.visit_stmts(&body.stmts);
+            }
+            ExprKind::Function { function } => self.visit_function(function),
+            ExprKind::Class { class } => self.visit_class(class),
+            ExprKind::Object { properties } => {
+                for property in properties {
+                    self.visit_property(property);
+                }
+            }
+            ExprKind::Template { tag, parts, .. } => {
+                self.visit_expr(tag);
+                for part in parts {
+                    self.bytes += part.tail.len();
+                    self.visit_expr(&part.value);
+                }
+            }
+            ExprKind::If { test, yes, no } => {
+                self.visit_expr(test);
+                self.visit_expr(yes);
+                self.visit_expr(no);
+            }
+            _ => {}
+        }
+    }
+}
@@ -1 +1,457 @@
+use crate::ast::{
+    follow_symbols, Reference, Scope, ScopeTree, SymbolKind, SymbolMap, AST, INVALID_REF,
+};
+use crate::tables::Token;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 
+// The exports of an entry point are its public API. Other code imports them by
+// name, so the symbols behind them must keep their names even when the
+// minifier renames everything else. Re-exported imports are followed to the
+// symbol they were merged with since that's the one that gets printed. The
+// linker has already reported any cycles.
+pub fn protect_entry_point_exports(ast: &AST, symbols: &mut SymbolMap) {
+    for reference in ast.named_exports.values() {
+        if let Ok(reference) = follow_symbols(symbols, *reference) {
+            symbols[reference].must_not_be_renamed = true;
+        }
+    }
+}
+
+// Some names are referenced from outside the bundle without being exported,
+// for example by a global that another script reads or by code that looks up
+// functions by name. Every symbol in the file with one of these names keeps it.
+pub fn preserve_names(symbols: &mut SymbolMap, source_index: usize, names: &HashSet<String>) {
+    if names.is_empty() {
+        return;
+    }
+
+    for symbol in symbols.outer[source_index].iter_mut() {
+        if names.contains(&symbol.name) {
+            symbol.must_not_be_renamed = true;
+        }
+    }
+}
+
+// Maps symbols to the names they are printed with. Symbols without an entry
+// keep their original name, so an empty renamer leaves everything as-is.
+#[derive(Debug, Clone, Default)]
+pub struct Renamer {
+    names: HashMap<Reference, String>,
+}
+
+impl Renamer {
+    pub fn no_op() -> Self {
+        Self::default()
+    }
+
+    pub fn name_for_symbol<'a>(
+        &'a self,
+        symbols: &'a SymbolMap,
+        mut reference: Reference,
+    ) -> &'a str {
+        loop {
+            let symbol = &symbols[reference];
+            if symbol.link == INVALID_REF {
+                break;
+            }
+            reference = symbol.link;
+        }
+        match self.names.get(&reference) {
+            Some(name) => name,
+            None => &symbols[reference].name,
+        }
+    }
+}
+
+// A file that isn't bundled keeps the names of its own symbols, but the names of
+// generated symbols such as "import_fs" may be taken by code in the file. These
+// get a number after the name instead, the way the linker's renamer does it.
+pub fn rename_generated_symbols(
+    scope_tree: &ScopeTree,
+    symbols: &SymbolMap,
+    source_index: usize,
+) -> Renamer {
+    let mut generated = vec![];
+    for id in scope_tree.descendants(ScopeTree::ROOT) {
+        generated.extend(scope_tree[id].generated.iter().copied());
+    }
+    let is_generated: HashSet<Reference> = generated.iter().copied().collect();
+    let mut used_names: HashSet<String> = symbols.outer[source_index]
+        .iter()
+        .enumerate()
+        .filter(|(inner, _)| !is_generated.contains(&Reference::new(source_index, *inner)))
+        .map(|(_, symbol)| symbol.name.clone())
+        .collect();
+
+    let mut renamer = Renamer::no_op();
+    for reference in generated {
+        let symbol = &symbols[reference];
+        if symbol.link != INVALID_REF || symbol.must_not_be_renamed {
+            continue;
+        }
+        let mut name = symbol.name.clone();
+        let mut i = 2;
+        while used_names.contains(&name) {
+            name = format!("{}{}", symbol.name, i);
+            i += 1;
+        }
+        if name != symbol.name {
+            renamer.names.insert(reference, name.clone());
+        }
+        used_names.insert(name);
+    }
+    renamer
+}
+
+// These can't be used as the name of a variable even though they aren't
+// keywords as far as the lexer is concerned. Unbound references to "eval" and
+// "arguments" already reserve their names, but a minified name must not
+// accidentally create a new one either.
+const EXTRA_RESERVED_NAMES: &[&str] = &["arguments", "await", "eval"];
+
+// A minified name must not be a keyword or any name that stays the same after
+// minifying. Anything that isn't renamed is reserved, not just unbound
+// symbols, because a renamed symbol in an inner scope would otherwise shadow
+// it.
+pub fn compute_reserved_names(scope_trees: &[&ScopeTree], symbols: &SymbolMap) -> HashSet<String> {
+    compute_reserved_names_of(scope_trees, symbols, |_| true)
+}
+
+// The same, but only the symbols that "is_printed" accepts reserve their
+// names. The others aren't in the output, so they can't be shadowed.
+pub fn compute_reserved_names_of<F>(
+    scope_trees: &[&ScopeTree],
+    symbols: &SymbolMap,
+    is_printed: F,
+) -> HashSet<String>
+where
+    F: Fn(Reference) -> bool,
+{
+    let renamable = collect_renamable_symbols(scope_trees, symbols);
+    let mut names: HashSet<String> = EXTRA_RESERVED_NAMES
+        .iter()
+        .map(|name| (*name).to_owned())
+        .collect();
+
+    for (outer, inner_symbols) in symbols.outer.iter().enumerate() {
+        for (inner, symbol) in inner_symbols.iter().enumerate() {
+            let reference = Reference::new(outer, inner);
+            if symbol.link == INVALID_REF
+                && !renamable.contains(&reference)
+                && is_printed(reference)
+            {
+                names.insert(symbol.name.clone());
+            }
+        }
+    }
+
+    names
+}
+
+// Every symbol declared in one of these scopes (including labels) is renamed unless it's marked
+// as "must_not_be_renamed" or a direct "eval" can see it. Scopes can declare
+// the same symbol more than once because hoisted symbols are also added to
+// the enclosing function scope.
+fn collect_renamable_symbols(
+    scope_trees: &[&ScopeTree],
+    symbols: &SymbolMap,
+) -> HashSet<Reference> {
+    let mut result = HashSet::new();
+    for tree in scope_trees {
+        for id in tree.descendants(ScopeTree::ROOT) {
+            let scope = &tree[id];
+            if scope.contains_direct_eval {
+                continue;
+            }
+            let label_ref = Some(scope.label_ref).filter(|r| *r != INVALID_REF);
+            for reference in scope
+                .members
+                .values()
+                .chain(&scope.generated)
+                .chain(&label_ref)
+            {
+                let symbol = &symbols[*reference];
+                if symbol.kind != SymbolKind::Unbound
+                    && !symbol.must_not_be_renamed
+                    && symbol.link == INVALID_REF
+                {
+                    result.insert(*reference);
+                }
+            }
+        }
+    }
+    result
+}
+
+// Gives every renamable symbol the shortest name that isn't reserved. Each
+// top-level symbol gets a name of its own, but symbols in nested scopes are
+// given numbered slots first and every symbol in the same slot gets the same
+// name. A scope numbers its symbols after the slots used by its parent
+// scopes, so sibling scopes such as two functions next to each other reuse
+// the same slots while a nested symbol can never shadow one it might refer
+// to. Names are handed out by how often the symbols in each slot are used so
+// the most common ones get the one-letter names.
+pub fn minify_all_symbols(
+    reserved_names: &HashSet<String>,
+    scope_trees: &[&ScopeTree],
+    symbols: &SymbolMap,
+) -> Renamer {
+    let renamable = collect_renamable_symbols(scope_trees, symbols);
+    let mut r = SlotAssigner {
+        symbols,
+        renamable: &renamable,
+        slots: HashMap::new(),
+        slot_counts: vec![],
+    };
+
+    // Top-level symbols are also members of nested scopes when a "var" is
+    // hoisted out of them, so they must be known before assigning any slots.
+    // Their slots come after the nested ones.
+    let mut top_level_refs = vec![];
+    for tree in scope_trees {
+        top_level_refs.extend(r.scope_symbols(tree.root()));
+    }
+    top_level_refs.sort_unstable();
+    top_level_refs.dedup();
+    for reference in &top_level_refs {
+        r.slots.insert(*reference, usize::MAX);
+    }
+
+    // The files of a bundle share one top-level scope, so their nested scopes
+    // are siblings too. Parents come before their children, so the first free
+    // slot of the parent is always known.
+    for tree in scope_trees {
+        let mut next_slots = vec![0; tree.len()];
+        for id in tree.descendants(ScopeTree::ROOT).skip(1) {
+            let next_slot = match tree[id].parent {
+                Some(parent) if parent != ScopeTree::ROOT => next_slots[parent.0],
+                _ => 0,
+            };
+            next_slots[id.0] = r.assign_nested_slots(&tree[id], next_slot);
+        }
+    }
+    for reference in top_level_refs {
+        r.slots.insert(reference, r.slot_counts.len());
+        r.slot_counts
+            .push(symbols[reference].use_count_estimate as usize);
+    }
+
+    // Sort by the number of uses, then by slot so the output is deterministic
+    let mut order: Vec<usize> = (0..r.slot_counts.len()).collect();
+    order.sort_by(|a, b| r.slot_counts[*b].cmp(&r.slot_counts[*a]).then(a.cmp(b)));
+
+    let mut slot_names = vec![String::new(); order.len()];
+    let mut next_name = 0;
+    for slot in order {
+        slot_names[slot] = loop {
+            let name = number_to_minified_name(next_name);
+            next_name += 1;
+            if Token::try_from(name.as_str()).is_err() && !reserved_names.contains(&name) {
+                break name;
+            }
+        };
+    }
+
+    let names = r
+        .slots
+        .into_iter()
+        .map(|(reference, slot)| (reference, slot_names[slot].clone()))
+        .collect();
+    Renamer { names }
+}
+
+struct SlotAssigner<'a> {
+    symbols: &'a SymbolMap,
+    renamable: &'a HashSet<Reference>,
+    slots: HashMap<Reference, usize>,
+
+    // The total number of uses of the symbols in each slot
+    slot_counts: Vec<usize>,
+}
+
+impl<'a> SlotAssigner<'a> {
+    // Sorted so that the slots don't depend on hash map order
+    fn scope_symbols(&self, scope: &Scope) -> Vec<Reference> {
+        let label_ref = Some(scope.label_ref).filter(|r| *r != INVALID_REF);
+        let mut refs: Vec<Reference> = scope
+            .members
+            .values()
+            .chain(&scope.generated)
+            .chain(&label_ref)
+            .copied()
+            .filter(|reference| self.renamable.contains(reference))
+            .collect();
+        refs.sort_unstable();
+        refs.dedup();
+        refs
+    }
+
+    // A symbol that is a member of more than one scope keeps the slot from
+    // the outermost one, which is where it was declared or hoisted to. This
+    // returns the first slot that the children of the scope can use.
+    fn assign_nested_slots(&mut self, scope: &Scope, mut next_slot: usize) -> usize {
+        for reference in self.scope_symbols(scope) {
+            if self.slots.contains_key(&reference) {
+                continue;
+            }
+            if next_slot == self.slot_counts.len() {
+                self.slot_counts.push(0);
+            }
+            self.slot_counts[next_slot] += self.symbols[reference].use_count_estimate as usize;
+            self.slots.insert(reference, next_slot);
+            next_slot += 1;
+        }
+        next_slot
+    }
+}
+
+// The first character of a name can't be a digit, so it's in base 54 and the
+// remaining characters are in base 64
+const NAME_HEAD: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_$";
+const NAME_TAIL: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_$0123456789";
+
+pub fn number_to_minified_name(mut i: usize) -> String {
+    let mut name = String::new();
+    name.push(NAME_HEAD[i % NAME_HEAD.len()] as char);
+    i /= NAME_HEAD.len();
+
+    while i > 0 {
+        i -= 1;
+        name.push(NAME_TAIL[i % NAME_TAIL.len()] as char);
+        i /= NAME_TAIL.len();
+    }
+
+    name
+}
+
+// Without minification, symbols keep their names unless that would change
+// which declaration a name refers to once the files of a bundle share one
+// top-level scope. A top-level symbol whose name is already taken gets a
+// number added to it. A nested symbol is renamed the same way if it has the
+// name of a top-level symbol from another file, since code in that scope may
+// refer to the top-level symbol through an import after linking.
+pub fn rename_all_symbols(
+    reserved_names: &HashSet<String>,
+    scope_trees: &[&ScopeTree],
+    symbols: &SymbolMap,
+) -> Renamer {
+    let renamable = collect_renamable_symbols(scope_trees, symbols);
+
+    // New names must not match any original name either, or a symbol that
+    // keeps its name could end up being shadowed by one that was renamed
+    let original_names: HashSet<&str> = symbols
+        .outer
+        .iter()
+        .flatten()
+        .map(|symbol| symbol.name.as_str())
+        .collect();
+
+    let mut r = SymbolRenamer {
+        symbols,
+        renamable,
+        original_names,
+        taken_names: reserved_names.clone(),
+        top_level_names: HashMap::new(),
+        top_level_refs: HashSet::new(),
+        names: HashMap::new(),
+    };
+
+    // Top-level symbols that can't be renamed keep their names, but nested
+    // symbols in other files must still avoid them
+    for tree in scope_trees {
+        let scope = tree.root();
+        for reference in scope.members.values().chain(&scope.generated) {
+            let symbol = &symbols[*reference];
+            if symbol.kind != SymbolKind::Unbound
+                && symbol.link == INVALID_REF
+                && !r.renamable.contains(reference)
+            {
+                r.top_level_names
+                    .insert(symbol.name.clone(), reference.outer);
+            }
+        }
+    }
+
+    for tree in scope_trees {
+        for reference in r.scope_symbols(tree.root()) {
+            let name = &symbols[reference].name;
+            let new_name = r.available_name(name);
+            r.taken_names.insert(new_name.clone());
+            r.top_level_names.insert(new_name.clone(), reference.outer);
+            r.top_level_refs.insert(reference);
+            if new_name != *name {
+                r.names.insert(reference, new_name);
+            }
+        }
+    }
+
+    for tree in scope_trees {
+        for id in tree.descendants(ScopeTree::ROOT).skip(1) {
+            r.rename_nested_symbols(&tree[id]);
+        }
+    }
+
+    Renamer { names: r.names }
+}
+
+struct SymbolRenamer<'a> {
+    symbols: &'a SymbolMap,
+    renamable: HashSet<Reference>,
+    original_names: HashSet<&'a str>,
+    taken_names: HashSet<String>,
+
+    // The file that declares the top-level symbol with each name
+    top_level_names: HashMap<String, usize>,
+    top_level_refs: HashSet<Reference>,
+    names: HashMap<Reference, String>,
+}
+
+impl<'a> SymbolRenamer<'a> {
+    // Sorted so that the output doesn't depend on hash map order
+    fn scope_symbols(&self, scope: &Scope) -> Vec<Reference> {
+        let mut refs: Vec<Reference> = scope
+            .members
+            .values()
+            .chain(&scope.generated)
+            .copied()
+            .filter(|reference| self.renamable.contains(reference))
+            .collect();
+        refs.sort();
+        refs.dedup();
+        refs
+    }
+
+    fn rename_nested_symbols(&mut self, scope: &Scope) {
+        for reference in self.scope_symbols(scope) {
+            // Hoisted symbols are also members of the module scope and have
+            // already been handled there
+            let name = &self.symbols[reference].name;
+            let is_shadowing = match self.top_level_names.get(name) {
+                Some(&outer) => outer != reference.outer,
+                None => false,
+            };
+            if is_shadowing && !self.top_level_refs.contains(&reference) {
+                let new_name = self.available_name(name);
+                self.taken_names.insert(new_name.clone());
+                self.names.insert(reference, new_name);
+            }
+        }
+    }
+
+    fn available_name(&self, name: &str) -> String {
+        if !self.taken_names.contains(name) {
+            return name.to_owned();
+        }
+        let mut i = 2;
+        loop {
+            let candidate = format!("{}{}", name, i);
+            if !self.taken_names.contains(&candidate)
+                && !self.original_names.contains(candidate.as_str())
+            {
+                return candidate;
+            }
+            i += 1;
+        }
+    }
+}
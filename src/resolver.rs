@@ -1 +1,815 @@
+// This implements the module resolution algorithm from node, which is
+// described here: https://nodejs.org/api/modules.html#modules_all_together
+//
+// Import paths that aren't relative are first looked up with the "paths" and
+// "baseUrl" settings of the nearest "tsconfig.json", like the TypeScript
+// compiler does.
+//
+// Packages can decide which of their files are used on which platform. The
+// "exports" field maps paths in the package to files depending on the
+// platform and on whether the package is imported or required, and in
+// browsers the "browser" field can replace files and packages with other ones
+// or with nothing at all.
+//
+// Paths are handled as strings with forward slashes (see the "paths" module)
+// and the file system is only used to read directories and files, so the
+// resolver works the same on top of the real file system and the mock one.
 
+use crate::ast::{Expr, ExprKind, ImportKind};
+use crate::error::Error;
+use crate::fs::{glob_matches, Entry, EntryKind, FileSystem};
+use crate::logging::{Log, Source};
+use crate::parser_json::{parse_json, JsonOptions};
+use crate::paths;
+use crate::strings::{string_to_utf16, utf16_to_string_lossy};
+use crate::tsconfig::{self, TSConfig};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+pub struct ResolveOptions {
+    // These are tried in order for import paths without an extension, and for
+    // the "index" file of a directory
+    pub extension_order: Vec<String>,
+
+    // The fields in "package.json" that can point to the entry point of the
+    // package, in order of preference. Only string values are used. If this
+    // is empty, the fields for the platform are used.
+    pub main_fields: Vec<String>,
+
+    pub platform: Platform,
+
+    // Import paths that are left as they are instead of being bundled. A "*"
+    // matches anything, like in "node:*", and a package name also matches
+    // the paths of the files in the package.
+    pub external: Vec<String>,
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Default)]
+pub enum Platform {
+    // The "browser" field of "package.json" files is used, and so is the
+    // "browser" condition in "exports"
+    #[default]
+    Browser,
+
+    // The "node" condition in "exports" is used
+    Node,
+
+    // Neither, and packages are only found through "exports" or an "index"
+    // file since no main fields are used either
+    Neutral,
+}
+
+impl Platform {
+    pub fn main_fields(self) -> &'static [&'static str] {
+        match self {
+            Platform::Browser => &["browser", "module", "main"],
+            Platform::Node => &["main", "module"],
+            Platform::Neutral => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveResult {
+    pub path: String,
+
+    // The "browser" field of a "package.json" file maps this to false, so it
+    // should be an empty module. The path is the import path for packages
+    // that are disabled this way.
+    pub is_disabled: bool,
+
+    // The import path was marked as external, so it's the path here and
+    // nothing was looked up
+    pub is_external: bool,
+
+    // The "sideEffects" field of the "package.json" file for the package
+    // says that the file has no side effects. The file can be left out of
+    // the bundle if nothing that it exports is used.
+    pub ignore_if_unused: bool,
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        Self {
+            extension_order: vec![
+                ".js".to_owned(),
+                ".jsx".to_owned(),
+                ".ts".to_owned(),
+                ".tsx".to_owned(),
+                ".json".to_owned(),
+            ],
+            main_fields: vec![],
+            platform: Platform::Browser,
+            external: vec![],
+        }
+    }
+}
+
+// Everything the resolver needs to know about one directory. These are never
+// mutated once they are cached, so they can point to their parent directory
+// directly instead of going through the cache again.
+#[derive(Debug)]
+struct DirInfo {
+    parent: Option<Arc<DirInfo>>,
+    abs_path: String,
+    entries: HashMap<String, Entry>,
+    has_node_modules: bool,
+
+    // The "index" file of this directory, if there is one
+    abs_path_index: Option<String>,
+
+    // The "package.json" file of this directory, if there is one
+    package_json: Option<Arc<PackageJson>>,
+
+    // The "tsconfig.json" of this directory or the nearest parent directory
+    // that has one
+    tsconfig: Option<Arc<TSConfig>>,
+}
+
+#[derive(Debug, Default)]
+struct PackageJson {
+    // The entry point from the first main field that has one
+    abs_path_main: Option<String>,
+
+    // The object form of the "browser" field. Keys that are relative paths
+    // are made absolute, and so are values. The other keys and values are
+    // package names. A value of None means the key was mapped to false.
+    browser_map: HashMap<String, Option<String>>,
+
+    exports: Option<ExportsEntry>,
+
+    // The "sideEffects" field. None means that every file may have side
+    // effects, which is what "true" means too. Otherwise only the files that
+    // match one of these absolute glob patterns do, so "false" is an empty
+    // list.
+    side_effects: Option<Vec<String>>,
+}
+
+// A value in the "exports" field of a "package.json" file
+#[derive(Debug)]
+enum ExportsEntry {
+    // Nothing can be imported from here. Values of the wrong type are also
+    // treated like this.
+    Null,
+    Path(String),
+
+    // The first entry that works is used
+    Array(Vec<ExportsEntry>),
+
+    // Either paths in the package that start with "." or conditions, in the
+    // order they were written
+    Map(Vec<(String, ExportsEntry)>),
+}
+
+pub struct Resolver<F: FileSystem> {
+    fs: F,
+    options: ResolveOptions,
+    dir_cache: Mutex<HashMap<String, Arc<DirInfo>>>,
+}
+
+// Paths that start with "/", "./", or "../" refer to a file. Everything else
+// is the name of a package in a "node_modules" directory.
+pub fn is_non_module_path(path: &str) -> bool {
+    path.starts_with('/')
+        || path.starts_with("./")
+        || path.starts_with("../")
+        || path == "."
+        || path == ".."
+        || paths::is_absolute(path)
+}
+
+impl<F: FileSystem> Resolver<F> {
+    pub fn new(fs: F, options: ResolveOptions) -> Self {
+        Self {
+            fs,
+            options,
+            dir_cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn fs(&self) -> &F {
+        &self.fs
+    }
+
+    // Everything the resolver knows about a directory is cached, so this must
+    // be called when files may have been added to or removed from one
+    pub fn clear_cache(&self) {
+        self.dir_cache.lock().unwrap().clear();
+        self.fs.clear_cache();
+    }
+
+    // Finds the file that "import_path" refers to when it's imported from the
+    // file at "source_path", or returns None if there is no such file. The
+    // kind of import matters since packages can have different files for
+    // "import" and "require()".
+    pub fn resolve(
+        &self,
+        source_path: &str,
+        import_path: &str,
+        kind: ImportKind,
+    ) -> Option<ResolveResult> {
+        if self.is_external(import_path) {
+            return Some(ResolveResult {
+                path: import_path.to_owned(),
+                is_disabled: false,
+                is_external: true,
+                ignore_if_unused: false,
+            });
+        }
+        let source_dir = paths::dir(source_path);
+
+        let path = if is_non_module_path(import_path) {
+            self.load_as_file_or_directory(&paths::join(&source_dir, import_path))?
+        } else {
+            let source_dir_info = self.dir_info_cached(&source_dir);
+            match self.browser_map_entry(&source_dir_info, import_path) {
+                Some(None) => {
+                    return Some(ResolveResult {
+                        path: import_path.to_owned(),
+                        is_disabled: true,
+                        is_external: false,
+                        ignore_if_unused: false,
+                    })
+                }
+                Some(Some(path)) if is_non_module_path(&path) => {
+                    self.load_as_file_or_directory(&path)?
+                }
+                Some(Some(package)) => self.load_package(&package, source_dir_info, kind)?,
+                None => self.load_package(import_path, source_dir_info, kind)?,
+            }
+        };
+
+        // The package the file is in can also replace it
+        let dir_info = self.dir_info_cached(&paths::dir(&path));
+        let (path, is_disabled) = match self.browser_map_entry(&dir_info, &path) {
+            Some(None) => (path, true),
+            Some(Some(other)) if is_non_module_path(&other) => (
+                self.load_as_file_or_directory(&other).unwrap_or(path),
+                false,
+            ),
+            _ => (path, false),
+        };
+        let ignore_if_unused = !is_disabled && self.is_side_effect_free(&path);
+        Some(ResolveResult {
+            path,
+            is_disabled,
+            is_external: false,
+            ignore_if_unused,
+        })
+    }
+
+    // Like "resolve", but says which import couldn't be resolved
+    pub fn try_resolve(
+        &self,
+        source_path: &str,
+        import_path: &str,
+        kind: ImportKind,
+    ) -> Result<ResolveResult, Error> {
+        self.resolve(source_path, import_path, kind)
+            .ok_or_else(|| Error::Resolve {
+                import_path: import_path.to_owned(),
+                importer: source_path.to_owned(),
+            })
+    }
+
+    // Uses the "sideEffects" field of the nearest "package.json" file
+    fn is_side_effect_free(&self, path: &str) -> bool {
+        let mut dir_info = Some(self.dir_info_cached(&paths::dir(path)));
+        while let Some(info) = dir_info {
+            if let Some(package_json) = &info.package_json {
+                return match &package_json.side_effects {
+                    Some(patterns) => !patterns.iter().any(|pattern| glob_matches(pattern, path)),
+                    None => false,
+                };
+            }
+            dir_info = info.parent.clone();
+        }
+        false
+    }
+
+    fn is_external(&self, import_path: &str) -> bool {
+        self.options
+            .external
+            .iter()
+            .any(|pattern| match pattern.split_once('*') {
+                Some((prefix, suffix)) => {
+                    import_path.len() >= prefix.len() + suffix.len()
+                        && import_path.starts_with(prefix)
+                        && import_path.ends_with(suffix)
+                }
+                None => {
+                    import_path == pattern
+                        || (!is_non_module_path(pattern)
+                            && import_path.starts_with(pattern.as_str())
+                            && import_path[pattern.len()..].starts_with('/'))
+                }
+            })
+    }
+
+    fn load_package(
+        &self,
+        import_path: &str,
+        source_dir_info: Arc<DirInfo>,
+        kind: ImportKind,
+    ) -> Option<String> {
+        if let Some(tsconfig) = &source_dir_info.tsconfig {
+            if let Some(path) = self.load_tsconfig_paths(tsconfig, import_path) {
+                return Some(path);
+            }
+        }
+        self.load_node_modules(import_path, source_dir_info, kind)
+    }
+
+    // Looks "key" up in the "browser" map of the package that contains the
+    // directory. A key that is a file matches the path of the file with or
+    // without its extension, and with or without "/index".
+    fn browser_map_entry(&self, dir_info: &DirInfo, key: &str) -> Option<Option<String>> {
+        if self.options.platform != Platform::Browser {
+            return None;
+        }
+        let mut dir_info = Some(dir_info);
+        let package_json = loop {
+            let info = dir_info?;
+            if let Some(package_json) = &info.package_json {
+                break package_json;
+            }
+            dir_info = info.parent.as_deref();
+        };
+
+        if !is_non_module_path(key) {
+            return package_json.browser_map.get(key).cloned();
+        }
+        package_json
+            .browser_map
+            .iter()
+            .find(|(map_key, _)| {
+                is_non_module_path(map_key)
+                    && (*map_key == key
+                        || self.options.extension_order.iter().any(|ext| {
+                            key == format!("{}{}", map_key, ext)
+                                || key == format!("{}/index{}", map_key, ext)
+                        }))
+            })
+            .map(|(_, value)| value.clone())
+    }
+
+    // The "tsconfig.json" that applies to the file at "source_path", if any
+    pub fn tsconfig(&self, source_path: &str) -> Option<Arc<TSConfig>> {
+        self.dir_info_cached(&paths::dir(source_path))
+            .tsconfig
+            .clone()
+    }
+
+    // If a pattern in "paths" matches, each of its substitutions is tried in
+    // order. Otherwise, or if none of them exist, the path is looked up
+    // relative to "baseUrl".
+    fn load_tsconfig_paths(&self, tsconfig: &TSConfig, import_path: &str) -> Option<String> {
+        if let Some((substitutions, star)) = tsconfig::match_paths(tsconfig, import_path) {
+            for substitution in substitutions {
+                let path = paths::join(&tsconfig.paths_base_dir, &substitution.replace('*', &star));
+                if let Some(path) = self.load_as_file_or_directory(&path) {
+                    return Some(path);
+                }
+            }
+        }
+        let base_url = tsconfig.base_url.as_ref()?;
+        self.load_as_file_or_directory(&paths::join(base_url, import_path))
+    }
+
+    fn load_as_file(&self, path: &str) -> Option<String> {
+        let dir_info = self.dir_info_cached(&paths::dir(path));
+        let base = paths::base(path);
+        let is_file = |name: &str| match dir_info.entries.get(name) {
+            Some(entry) => entry.kind == EntryKind::File,
+            None => false,
+        };
+
+        // Try the plain path first
+        if is_file(&base) {
+            return Some(path.to_owned());
+        }
+
+        // Then try the path with each extension
+        for ext in &self.options.extension_order {
+            if is_file(&format!("{}{}", base, ext)) {
+                return Some(format!("{}{}", path, ext));
+            }
+        }
+
+        None
+    }
+
+    fn load_as_file_or_directory(&self, path: &str) -> Option<String> {
+        if let Some(path) = self.load_as_file(path) {
+            return Some(path);
+        }
+
+        let dir_info = self.dir_info_cached(path);
+
+        // The entry point in "package.json" may be a file or a directory
+        let main = dir_info
+            .package_json
+            .as_ref()
+            .and_then(|package_json| package_json.abs_path_main.as_ref());
+        if let Some(main) = main {
+            if let Some(path) = self.load_as_file(main) {
+                return Some(path);
+            }
+            if let Some(index) = &self.dir_info_cached(main).abs_path_index {
+                return Some(index.clone());
+            }
+        }
+
+        dir_info.abs_path_index.clone()
+    }
+
+    fn load_node_modules(
+        &self,
+        path: &str,
+        dir_info: Arc<DirInfo>,
+        kind: ImportKind,
+    ) -> Option<String> {
+        let (package_name, subpath) = split_package_path(path);
+        let mut dir_info = Some(dir_info);
+
+        // Search the "node_modules" directories from the innermost outward
+        while let Some(info) = dir_info {
+            if info.has_node_modules {
+                // A package with "exports" can only be imported through them
+                let package_dir =
+                    paths::join(&info.abs_path, &format!("node_modules/{}", package_name));
+                let package_json = self.dir_info_cached(&package_dir).package_json.clone();
+                if let Some(exports) = package_json.as_ref().and_then(|p| p.exports.as_ref()) {
+                    return self.load_exports(&package_dir, exports, &subpath, kind);
+                }
+
+                let abs_path = paths::join(&info.abs_path, &format!("node_modules/{}", path));
+                if let Some(path) = self.load_as_file_or_directory(&abs_path) {
+                    return Some(path);
+                }
+            }
+            dir_info = info.parent.clone();
+        }
+
+        None
+    }
+
+    // Subpaths can end in "*" to match any path that starts with the rest of
+    // the subpath. The "*" in the target is then replaced with what it matched.
+    // An exact match wins, and otherwise the longest prefix does.
+    fn load_exports(
+        &self,
+        package_dir: &str,
+        exports: &ExportsEntry,
+        subpath: &str,
+        kind: ImportKind,
+    ) -> Option<String> {
+        // Conditions at the top level are for the "." subpath
+        let subpaths = match exports {
+            ExportsEntry::Map(entries) if entries.iter().all(|(key, _)| key.starts_with('.')) => {
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value))
+                    .collect()
+            }
+            _ => vec![(".", exports)],
+        };
+
+        let mut best: Option<(&ExportsEntry, &str, usize)> = None;
+        for (key, target) in subpaths {
+            if key == subpath {
+                best = Some((target, "", usize::MAX));
+                break;
+            }
+            if let Some(prefix) = key.strip_suffix('*') {
+                let is_longer = best.is_none_or(|best| prefix.len() > best.2);
+                if subpath.starts_with(prefix) && is_longer {
+                    best = Some((target, &subpath[prefix.len()..], prefix.len()));
+                }
+            }
+        }
+        let (target, star, _) = best?;
+        let target = self.exports_target(target, star, kind)?;
+
+        // Targets can't point outside of the package
+        if !target.starts_with("./") {
+            return None;
+        }
+        let path = paths::join(package_dir, &target);
+        if !path.starts_with(package_dir) {
+            return None;
+        }
+        self.load_as_file(&path)
+    }
+
+    fn exports_target(
+        &self,
+        target: &ExportsEntry,
+        star: &str,
+        kind: ImportKind,
+    ) -> Option<String> {
+        match target {
+            ExportsEntry::Null => None,
+            ExportsEntry::Path(path) => Some(path.replace('*', star)),
+            ExportsEntry::Array(targets) => targets
+                .iter()
+                .find_map(|target| self.exports_target(target, star, kind)),
+            // The first condition that applies decides, unless it's a map
+            // in which no condition applies
+            ExportsEntry::Map(conditions) => {
+                for (condition, target) in conditions {
+                    if !self.is_condition_active(condition, kind) {
+                        continue;
+                    }
+                    match target {
+                        ExportsEntry::Map(_) => {
+                            if let Some(path) = self.exports_target(target, star, kind) {
+                                return Some(path);
+                            }
+                        }
+                        _ => return self.exports_target(target, star, kind),
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    fn is_condition_active(&self, condition: &str, kind: ImportKind) -> bool {
+        match condition {
+            "default" => true,
+            "import" => kind != ImportKind::Require,
+            "require" => kind == ImportKind::Require,
+            "browser" => self.options.platform == Platform::Browser,
+            "node" => self.options.platform == Platform::Node,
+            _ => false,
+        }
+    }
+
+    fn dir_info_cached(&self, path: &str) -> Arc<DirInfo> {
+        if let Some(info) = self.dir_cache.lock().unwrap().get(path) {
+            return info.clone();
+        }
+
+        // The lock isn't held while the directory is read, since that also
+        // reads the parent directories. Two threads may end up reading the
+        // same directory, but both get the same information.
+        let info = Arc::new(self.dir_info_uncached(path));
+        self.dir_cache
+            .lock()
+            .unwrap()
+            .entry(path.to_owned())
+            .or_insert(info)
+            .clone()
+    }
+
+    fn dir_info_uncached(&self, path: &str) -> DirInfo {
+        let parent_path = paths::dir(path);
+        let parent = if parent_path != path {
+            Some(self.dir_info_cached(&parent_path))
+        } else {
+            None
+        };
+
+        // A missing directory reads as an empty one
+        let entries = self.fs.read_directory(path);
+        let has_node_modules = match entries.get("node_modules") {
+            Some(entry) => entry.kind == EntryKind::Dir,
+            None => false,
+        };
+
+        let abs_path_index = self.options.extension_order.iter().find_map(|ext| {
+            let base = format!("index{}", ext);
+            match entries.get(&base) {
+                Some(entry) if entry.kind == EntryKind::File => Some(paths::join(path, &base)),
+                _ => None,
+            }
+        });
+
+        let package_json = match entries.get("package.json") {
+            Some(entry) if entry.kind == EntryKind::File => {
+                self.parse_package_json(path).map(Arc::new)
+            }
+            _ => None,
+        };
+
+        // Packages are already compiled, so their "tsconfig.json" files are
+        // for their own build and don't apply here
+        let tsconfig = match entries.get("tsconfig.json") {
+            _ if path.split('/').any(|part| part == "node_modules") => None,
+            Some(entry) if entry.kind == EntryKind::File => self
+                .load_tsconfig(&paths::join(path, "tsconfig.json"), &mut vec![])
+                .map(Arc::new),
+            _ => parent.as_ref().and_then(|parent| parent.tsconfig.clone()),
+        };
+
+        DirInfo {
+            parent,
+            abs_path: path.to_owned(),
+            entries,
+            has_node_modules,
+            abs_path_index,
+            package_json,
+            tsconfig,
+        }
+    }
+
+    // Errors in "tsconfig.json" files aren't reported here either. A file
+    // that can't be parsed is treated as if it didn't exist, and so is a
+    // chain of "extends" that loops back on itself.
+    fn load_tsconfig(&self, abs_path: &str, visited: &mut Vec<String>) -> Option<TSConfig> {
+        if visited.iter().any(|path| path == abs_path) {
+            return None;
+        }
+        visited.push(abs_path.to_owned());
+        let contents = self.fs.read_file(abs_path)?;
+        let source = Source {
+            index: 0,
+            is_stdin: false,
+            absolute_path: abs_path.to_owned(),
+            pretty_path: paths::pretty_path(&self.fs.cwd(), abs_path),
+            contents,
+            ..Source::default()
+        };
+        let (config, extends) = tsconfig::parse_tsconfig(&Log::default(), &source)?;
+        let extends = match extends {
+            Some(extends) => extends,
+            None => return Some(config),
+        };
+        let base = self
+            .find_extended_tsconfig(&paths::dir(abs_path), &extends)
+            .and_then(|base_path| self.load_tsconfig(&base_path, visited));
+        match base {
+            Some(base) => Some(TSConfig::extend(base, config)),
+            None => Some(config),
+        }
+    }
+
+    // "extends" is either a path relative to the file, or a path inside a
+    // package which defaults to the "tsconfig.json" of the package. The
+    // ".json" extension can be left off in both cases.
+    fn find_extended_tsconfig(&self, dir: &str, extends: &str) -> Option<String> {
+        // This can't use the directory cache since it runs while the
+        // directory of the "tsconfig.json" file is being added to it
+        let find = |path: String| {
+            let candidates = [
+                format!("{}.json", path),
+                paths::join(&path, "tsconfig.json"),
+            ];
+            std::iter::once(path)
+                .chain(candidates)
+                .find(|path| self.fs.read_file(path).is_some())
+        };
+
+        if is_non_module_path(extends) {
+            return find(paths::join(dir, extends));
+        }
+
+        let mut dir = dir.to_owned();
+        loop {
+            let path = paths::join(&dir, &format!("node_modules/{}", extends));
+            if let Some(path) = find(path) {
+                return Some(path);
+            }
+            let parent = paths::dir(&dir);
+            if parent == dir {
+                return None;
+            }
+            dir = parent;
+        }
+    }
+
+    // Errors in "package.json" files aren't reported here. A file that can't
+    // be parsed is treated as if it didn't exist.
+    fn parse_package_json(&self, dir_path: &str) -> Option<PackageJson> {
+        let abs_path = paths::join(dir_path, "package.json");
+        let contents = self.fs.read_file(&abs_path)?;
+        let source = Source {
+            index: 0,
+            is_stdin: false,
+            pretty_path: paths::pretty_path(&self.fs.cwd(), &abs_path),
+            absolute_path: abs_path,
+            contents,
+            ..Source::default()
+        };
+        let json = parse_json(&Log::default(), &source, JsonOptions::default())?;
+        let properties = match json.into_data() {
+            ExprKind::Object { properties } => properties,
+            _ => return None,
+        };
+        let get = |name: &str| {
+            let name = string_to_utf16(name);
+            properties
+                .iter()
+                .rev()
+                .find_map(|property| match property.key.data.as_ref() {
+                    ExprKind::String { value } if *value == name => property.value.as_ref(),
+                    _ => None,
+                })
+        };
+        let get_string = |expr: &Expr| match expr.data.as_ref() {
+            ExprKind::String { value } => Some(utf16_to_string_lossy(value)),
+            _ => None,
+        };
+        let mut package_json = PackageJson::default();
+
+        let platform_fields = self.options.platform.main_fields();
+        let main_fields: Vec<&str> = if self.options.main_fields.is_empty() {
+            platform_fields.to_vec()
+        } else {
+            self.options
+                .main_fields
+                .iter()
+                .map(String::as_str)
+                .collect()
+        };
+        package_json.abs_path_main = main_fields.iter().find_map(|field| {
+            let main = get(field).and_then(get_string)?;
+            Some(paths::join(dir_path, &main))
+        });
+
+        if let Some(ExprKind::Object { properties }) = get("browser").map(|e| e.data.as_ref()) {
+            let normalize = |path: String| {
+                if path.starts_with("./") || path.starts_with("../") {
+                    paths::join(dir_path, &path)
+                } else {
+                    path
+                }
+            };
+            for property in properties {
+                let key = match get_string(&property.key) {
+                    Some(key) => normalize(key),
+                    None => continue,
+                };
+                let value = match property.value.as_ref().map(|e| e.data.as_ref()) {
+                    Some(ExprKind::String { value }) => {
+                        Some(normalize(utf16_to_string_lossy(value)))
+                    }
+                    Some(ExprKind::Boolean { value: false }) => None,
+                    _ => continue,
+                };
+                package_json.browser_map.insert(key, value);
+            }
+        }
+
+        package_json.exports = get("exports").map(parse_exports_entry);
+
+        // Patterns without a slash can match a file in any directory
+        package_json.side_effects = match get("sideEffects").map(|e| e.data.as_ref()) {
+            Some(ExprKind::Boolean { value: false }) => Some(Vec::new()),
+            Some(ExprKind::Array { items }) => Some(
+                items
+                    .iter()
+                    .filter_map(get_string)
+                    .map(|pattern| {
+                        let pattern = if pattern.contains('/') {
+                            pattern
+                        } else {
+                            format!("**/{}", pattern)
+                        };
+                        paths::join(dir_path, &pattern)
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        Some(package_json)
+    }
+}
+
+fn parse_exports_entry(expr: &Expr) -> ExportsEntry {
+    match expr.data.as_ref() {
+        ExprKind::String { value } => ExportsEntry::Path(utf16_to_string_lossy(value)),
+        ExprKind::Array { items } => {
+            ExportsEntry::Array(items.iter().map(parse_exports_entry).collect())
+        }
+        ExprKind::Object { properties } => ExportsEntry::Map(
+            properties
+                .iter()
+                .filter_map(|property| {
+                    let key = match property.key.data.as_ref() {
+                        ExprKind::String { value } => utf16_to_string_lossy(value),
+                        _ => return None,
+                    };
+                    Some((key, parse_exports_entry(property.value.as_ref()?)))
+                })
+                .collect(),
+        ),
+        _ => ExportsEntry::Null,
+    }
+}
+
+// Splits "@scope/name/a/b" into "@scope/name" and "./a/b". The subpath of
+// the package itself is ".".
+fn split_package_path(path: &str) -> (&str, String) {
+    let name_len = if path.starts_with('@') {
+        path.match_indices('/').nth(1).map(|(i, _)| i)
+    } else {
+        path.find('/')
+    };
+    match name_len {
+        Some(i) => (&path[..i], format!(".{}", &path[i..])),
+        None => (path, ".".to_owned()),
+    }
+}
@@ -1 +1,165 @@
+// The IIFE format has no native module system, so code splitting needs a tiny
+// loader of its own. Every chunk is a plain script that registers its modules
+// in a registry stored on the global object. The entry chunk installs the
+// registry first, and dynamic "import()" calls are turned into calls to
+// "import()" on the registry. That uses "load()", which injects a script tag
+// for the chunk and resolves once the chunk has registered itself, and then
+// runs the chunk's module to get its exports.
+//
+// The loader is written in ES5 so it doesn't need to be lowered for any
+// target. Installing it twice is harmless since every chunk may include it.
+const IIFE_CHUNK_LOADER: &str = r#"(function() {
+  var g = typeof globalThis !== "undefined" ? globalThis : typeof self !== "undefined" ? self : this;
+  var r = g[REGISTRY] || (g[REGISTRY] = {modules: {}, cache: {}, loading: {}});
+  r.define = r.define || function(modules) {
+    for (var id in modules) r.modules[id] = modules[id];
+  };
+  r.require = r.require || function __require(id) {
+    var cached = r.cache[id];
+    if (cached) return cached.exports;
+    var module = r.cache[id] = {exports: {}};
+    r.modules[id](module, module.exports, __require);
+    return module.exports;
+  };
+  r.load = r.load || function(chunk) {
+    return r.loading[chunk] || (r.loading[chunk] = new Promise(function(resolve, reject) {
+      var script = document.createElement("script");
+      script.src = PUBLIC_PATH + chunk;
+      script.onload = resolve;
+      script.onerror = function() {
+        delete r.loading[chunk];
+        reject(new Error("Failed to load chunk " + chunk));
+      };
+      document.head.appendChild(script);
+    }));
+  };
+  r.import = r.import || function(chunk) {
+    return r.load(chunk).then(function() {
+      return r.require(chunk);
+    });
+  };
+})();
+"#;
 
+pub const DEFAULT_CHUNK_REGISTRY: &str = "__esbuild_chunks";
+
+#[derive(Debug, Clone)]
+pub struct ChunkLoader {
+    pub code: String,
+}
+
+impl ChunkLoader {
+    pub fn iife(registry: &str, public_path: &str) -> Self {
+        Self {
+            code: IIFE_CHUNK_LOADER
+                .replace("REGISTRY", &js_string(registry))
+                .replace("PUBLIC_PATH", &js_string(public_path)),
+        }
+    }
+
+    // The metafile reports this as the "<runtime>" input of the entry point, so
+    // users can see what splitting costs them
+    pub fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+}
+
+// An IIFE chunk registers a CommonJS module body under the chunk's file name
+pub fn iife_define_chunk(registry: &str, chunk: &str, code: &str) -> String {
+    format!(
+        "{}.define({{{}: function(module, exports, require) {{\n{}}}}});\n",
+        registry,
+        js_string(chunk),
+        code
+    )
+}
+
+fn js_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+// In the HMR development mode every module is wrapped in a call that registers
+// its factory function under a stable module ID:
+//
+//   __hmr.register("src/app.js", function(module, exports, require) { ... });
+//
+// Registering an ID that was already evaluated replaces the factory and
+// re-evaluates it, after running the dispose handlers of the old instance. An
+// external HMR client only needs to fetch the re-emitted module and evaluate
+// it. Modules opt into being swapped with "module.hot.accept()". When a
+// module doesn't accept an update, the "__hmr.onReload" callback is run,
+// which reloads the page by default.
+const HMR_REGISTRY: &str = r#"var __hmr = (function() {
+  var g = typeof globalThis !== "undefined" ? globalThis : typeof self !== "undefined" ? self : this;
+  var r = g[REGISTRY];
+  if (r) return r;
+  r = g[REGISTRY] = {factories: {}, cache: {}};
+  r.onReload = function() {
+    if (typeof location !== "undefined") location.reload();
+  };
+  r.require = function(id) {
+    var cached = r.cache[id];
+    if (cached) return cached.exports;
+    var module = r.cache[id] = {id: id, exports: {}, hot: {
+      accepted: false,
+      disposers: [],
+      accept: function() { module.hot.accepted = true; },
+      dispose: function(callback) { module.hot.disposers.push(callback); }
+    }};
+    r.factories[id](module, module.exports, r.require);
+    return module.exports;
+  };
+  r.register = function(id, factory) {
+    var old = r.cache[id];
+    r.factories[id] = factory;
+    if (!old) return;
+    if (!old.hot.accepted) return r.onReload(id);
+    for (var i = 0; i < old.hot.disposers.length; i++) old.hot.disposers[i]();
+    delete r.cache[id];
+    r.require(id);
+  };
+  return r;
+})();
+"#;
+
+pub const DEFAULT_HMR_REGISTRY: &str = "__esbuild_hmr";
+
+pub fn hmr_registry(registry: &str) -> String {
+    HMR_REGISTRY.replace("REGISTRY", &js_string(registry))
+}
+
+pub fn hmr_require(module_id: &str) -> String {
+    format!("__hmr.require({});\n", js_string(module_id))
+}
+
+// Wraps the printed code of a single module in its registration call
+pub fn hmr_register_module(module_id: &str, code: &str) -> String {
+    let mut result = String::with_capacity(code.len() + module_id.len() + 80);
+    result.push_str("__hmr.register(");
+    result.push_str(&js_string(module_id));
+    result.push_str(", function(module, exports, require) {\n");
+    for line in code.lines() {
+        if !line.is_empty() {
+            result.push_str("  ");
+            result.push_str(line);
+        }
+        result.push('\n');
+    }
+    result.push_str("});\n");
+    result
+}
@@ -0,0 +1,135 @@
+// Every file of a build has a source index, which is how trees, symbols, and
+// log messages refer to it. The registry hands out the indices in the order
+// the files are found and makes the source of each one, with the path that's
+// shown for it. A file that's found again gets the index it already has, and
+// its contents are only read once.
+
+use crate::fs::FileSystem;
+use crate::logging::Source;
+use crate::paths;
+use std::collections::HashMap;
+use std::ops::Index;
+
+#[derive(Debug, Default)]
+pub struct SourceRegistry {
+    sources: Vec<Source>,
+
+    // Disabled files are under their path with "(disabled):" in front, since
+    // the file itself can also be imported from somewhere it isn't disabled
+    indices: HashMap<String, usize>,
+
+    // Whether the contents of each source have been read. A file can be
+    // empty, so empty contents don't say anything.
+    is_read: Vec<bool>,
+}
+
+impl SourceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A source that isn't a file, such as the runtime. It always gets an index
+    // of its own.
+    pub fn add_virtual(&mut self, pretty_path: &str, contents: String) -> usize {
+        let source_index = self.push(Source {
+            pretty_path: pretty_path.to_owned(),
+            contents,
+            ..Source::default()
+        });
+        self.is_read[source_index] = true;
+        source_index
+    }
+
+    // Returns the index of the file, and whether the file is new
+    pub fn add_file<F: FileSystem>(&mut self, fs: &F, absolute_path: &str) -> (usize, bool) {
+        if let Some(&source_index) = self.indices.get(absolute_path) {
+            return (source_index, false);
+        }
+        let source_index = self.push(Source {
+            absolute_path: absolute_path.to_owned(),
+            pretty_path: paths::pretty_path(&fs.cwd(), absolute_path),
+            ..Source::default()
+        });
+        self.indices.insert(absolute_path.to_owned(), source_index);
+        (source_index, true)
+    }
+
+    // A disabled file is an empty module, so there's nothing to read
+    pub fn add_disabled_file<F: FileSystem>(&mut self, fs: &F, path: &str) -> (usize, bool) {
+        let key = format!("(disabled):{}", path);
+        if let Some(&source_index) = self.indices.get(&key) {
+            return (source_index, false);
+        }
+        let source_index = self.push(Source {
+            absolute_path: path.to_owned(),
+            pretty_path: format!("(disabled):{}", paths::pretty_path(&fs.cwd(), path)),
+            ..Source::default()
+        });
+        self.is_read[source_index] = true;
+        self.indices.insert(key, source_index);
+        (source_index, true)
+    }
+
+    fn push(&mut self, mut source: Source) -> usize {
+        let source_index = self.sources.len();
+        source.index = source_index as u32;
+        self.sources.push(source);
+        self.is_read.push(false);
+        source_index
+    }
+
+    // Reads the file of the source the first time this is called for it.
+    // Returns false if the file couldn't be read, which is tried again the
+    // next time.
+    pub fn read_contents<F: FileSystem>(&mut self, fs: &F, source_index: usize) -> bool {
+        if self.is_read[source_index] {
+            return true;
+        }
+        let source = &mut self.sources[source_index];
+        match fs.read_file(&source.absolute_path) {
+            Some(contents) => {
+                source.contents = contents;
+                self.is_read[source_index] = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Replaces what was read, such as with the code that an extension hook
+    // made from it
+    pub fn set_contents(&mut self, source_index: usize, contents: String) {
+        let source = &mut self.sources[source_index];
+        source.contents = contents;
+        source.line_offsets = Default::default();
+        self.is_read[source_index] = true;
+    }
+
+    pub fn index_of(&self, absolute_path: &str) -> Option<usize> {
+        self.indices.get(absolute_path).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    pub fn into_sources(self) -> Vec<Source> {
+        self.sources
+    }
+}
+
+impl Index<usize> for SourceRegistry {
+    type Output = Source;
+
+    fn index(&self, source_index: usize) -> &Source {
+        &self.sources[source_index]
+    }
+}
@@ -0,0 +1,21 @@
+// The parser, the passes over the tree, and the printer are all recursive, so
+// deeply nested code like "((((a))))" with thousands of parentheses needs a
+// lot of stack. Threads in the thread pool only have a few megabytes of it.
+// Instead of overflowing, the recursive functions call "ensure_sufficient_stack"
+// which moves to a new stack segment on the heap whenever the current one is
+// close to running out.
+//
+// How deep code can nest is still limited by the parser (see
+// "MAX_NESTING_DEPTH"), since code that Rust generates, like dropping a tree,
+// is recursive too and can't grow the stack. Expressions are the exception
+// (see "Expr"), since chains of operators aren't limited.
+
+// Every function that calls "ensure_sufficient_stack" must use less than this
+// much stack before it calls it again
+const RED_ZONE: usize = 256 * 1024;
+
+const SEGMENT_SIZE: usize = 4 * 1024 * 1024;
+
+pub fn ensure_sufficient_stack<R, F: FnOnce() -> R>(f: F) -> R {
+    stacker::maybe_grow(RED_ZONE, SEGMENT_SIZE, f)
+}
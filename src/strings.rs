@@ -0,0 +1,109 @@
+// JavaScript strings are sequences of UTF-16 code units, so that's how string
+// literals are stored in the tree. Escapes such as "\uD800" can produce
+// unpaired surrogates, which UTF-8 can't represent, so converting back to a
+// Rust string isn't always possible.
+
+pub fn string_to_utf16(text: &str) -> Vec<u16> {
+    text.encode_utf16().collect()
+}
+
+// Returns "None" for text with an unpaired surrogate instead of silently
+// replacing it, so a round trip through this never changes the string
+pub fn utf16_to_string(text: &[u16]) -> Option<String> {
+    String::from_utf16(text).ok()
+}
+
+// For messages and names where a replacement character is good enough
+pub fn utf16_to_string_lossy(text: &[u16]) -> String {
+    String::from_utf16_lossy(text)
+}
+
+// This compares code units, so a surrogate pair in "text" is equal to the
+// character it encodes in "other"
+pub fn utf16_equals_string(text: &[u16], other: &str) -> bool {
+    text.iter().copied().eq(other.encode_utf16())
+}
+
+// Concatenation works on code units just like it does at run time. A string
+// that ends in a high surrogate followed by one that starts with a low
+// surrogate joins into a single character: "\uD83D" + "\uDE00" is "😀".
+pub fn utf16_concat(a: &[u16], b: &[u16]) -> Vec<u16> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    result.extend_from_slice(a);
+    result.extend_from_slice(b);
+    result
+}
+
+// Picks the quote character that needs the fewest escapes. Backticks also
+// need "${" escaped, and they are only an option where a template literal
+// can be used, which isn't the case for property keys or import paths.
+pub fn best_quote_char(text: &[u16], allow_backtick: bool) -> u8 {
+    let mut single_cost = 0;
+    let mut double_cost = 0;
+    let mut backtick_cost = 0;
+    for (i, &c) in text.iter().enumerate() {
+        if c == u16::from(b'\'') {
+            single_cost += 1;
+        } else if c == u16::from(b'"') {
+            double_cost += 1;
+        } else if c == u16::from(b'`')
+            || (c == u16::from(b'$') && text.get(i + 1) == Some(&u16::from(b'{')))
+        {
+            backtick_cost += 1;
+        }
+    }
+
+    let mut best = (double_cost, b'"');
+    if single_cost < best.0 {
+        best = (single_cost, b'\'');
+    }
+    if allow_backtick && backtick_cost < best.0 {
+        best = (backtick_cost, b'`');
+    }
+    best.1
+}
+
+pub fn quote_utf16(text: &[u16], allow_backtick: bool) -> String {
+    let quote = best_quote_char(text, allow_backtick);
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push(quote as char);
+    escape_utf16(&mut result, text, quote);
+    result.push(quote as char);
+    result
+}
+
+// Everything outside of printable ASCII is escaped so that the output can
+// be served with any character encoding
+pub fn escape_utf16(out: &mut String, text: &[u16], quote: u8) {
+    for (i, &c) in text.iter().enumerate() {
+        let next = text.get(i + 1).copied();
+        match c {
+            // "\0" followed by a digit would be parsed as an octal escape
+            0 => {
+                if matches!(next, Some(n) if n >= u16::from(b'0') && n <= u16::from(b'9')) {
+                    out.push_str("\\x00");
+                } else {
+                    out.push_str("\\0");
+                }
+            }
+            0x08 => out.push_str("\\b"),
+            0x09 => out.push_str("\\t"),
+            0x0A => out.push_str("\\n"),
+            0x0B => out.push_str("\\v"),
+            0x0C => out.push_str("\\f"),
+            0x0D => out.push_str("\\r"),
+            0x5C => out.push_str("\\\\"),
+            _ if c == u16::from(quote) => {
+                out.push('\\');
+                out.push(quote as char);
+            }
+
+            // "${" starts a substitution in a template literal
+            0x24 if quote == b'`' && next == Some(u16::from(b'{')) => out.push_str("\\$"),
+
+            _ if c < 0x20 || c == 0x7F => out.push_str(&format!("\\x{:02X}", c)),
+            _ if c >= 0x80 => out.push_str(&format!("\\u{:04X}", c)),
+            _ => out.push(c as u8 as char),
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use crate::ast::OperatorCode;
 use crate::error::Error;
+use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::ops::RangeInclusive;
 
@@ -81,8 +83,9 @@ pub enum Token {
     SlashEquals,
 
     // Identifiers,
-    Identifier,     // Contents are in lexer.Identifier (string),
-    EscapedKeyword, // A keyword that has been escaped as an identifer,
+    Identifier,        // Contents are in lexer.Identifier (string),
+    EscapedKeyword,    // A keyword that has been escaped as an identifer,
+    PrivateIdentifier, // Contents are in lexer.Identifier (string), with the "#",
 
     // Reserved words,
     Break,
@@ -214,6 +217,7 @@ impl Token {
             // Identifiers
             Token::Identifier => "identifier",
             Token::EscapedKeyword => "escaped keyword",
+            Token::PrivateIdentifier => "private identifier",
 
             // Reserved words
             Token::Break => r#""break""#,
@@ -265,6 +269,87 @@ impl Token {
             Token::Yield => r#""yield""#,
         }
     }
+
+    // Reserved words can't be used as identifiers at all. Strict mode
+    // reserved words such as "let" are identifiers in sloppy mode, so they
+    // don't count.
+    pub fn is_keyword(self) -> bool {
+        self >= Token::Break && self <= Token::With
+    }
+
+    pub fn is_assignment(self) -> bool {
+        self >= Token::AmpersandEquals && self <= Token::SlashEquals
+    }
+
+    // The operator that this token is in an expression. Some tokens are both
+    // a prefix and a binary operator, like "-" in "-a" and "a - b", and "++"
+    // is an update either way. Anything else is only a binary operator, so
+    // there is nothing for it in a prefix position.
+    pub fn to_operator_code(self, prefix: bool) -> Option<OperatorCode> {
+        if prefix {
+            return Some(match self {
+                Token::Plus => OperatorCode::UnOpPos,
+                Token::Minus => OperatorCode::UnOpNeg,
+                Token::Tilde => OperatorCode::UnOpCpl,
+                Token::Exclamation => OperatorCode::UnOpNot,
+                Token::Void => OperatorCode::UnOpVoid,
+                Token::Typeof => OperatorCode::UnOpTypeof,
+                Token::Delete => OperatorCode::UnOpDelete,
+                Token::MinusMinus => OperatorCode::UnOpPreDec,
+                Token::PlusPlus => OperatorCode::UnOpPreInc,
+                _ => return None,
+            });
+        }
+
+        Some(match self {
+            Token::MinusMinus => OperatorCode::UnOpPostDec,
+            Token::PlusPlus => OperatorCode::UnOpPostInc,
+
+            Token::Plus => OperatorCode::BinOpAdd,
+            Token::Minus => OperatorCode::BinOpSub,
+            Token::Asterisk => OperatorCode::BinOpMul,
+            Token::Slash => OperatorCode::BinOpDiv,
+            Token::Percent => OperatorCode::BinOpRem,
+            Token::AsteriskAsterisk => OperatorCode::BinOpPow,
+            Token::LessThan => OperatorCode::BinOpLt,
+            Token::LessThanEquals => OperatorCode::BinOpLe,
+            Token::GreaterThan => OperatorCode::BinOpGt,
+            Token::GreaterThanEquals => OperatorCode::BinOpGe,
+            Token::In => OperatorCode::BinOpIn,
+            Token::Instanceof => OperatorCode::BinOpInstanceof,
+            Token::LessThanLessThan => OperatorCode::BinOpShl,
+            Token::GreaterThanGreaterThan => OperatorCode::BinOpShr,
+            Token::GreaterThanGreaterThanGreaterThan => OperatorCode::BinOpUShr,
+            Token::EqualsEquals => OperatorCode::BinOpLooseEq,
+            Token::ExclamationEquals => OperatorCode::BinOpLooseNe,
+            Token::EqualsEqualsEquals => OperatorCode::BinOpStrictEq,
+            Token::ExclamationEqualsEquals => OperatorCode::BinOpStrictNe,
+            Token::QuestionQuestion => OperatorCode::BinOpNullishCoalescing,
+            Token::BarBar => OperatorCode::BinOpLogicalOr,
+            Token::AmpersandAmpersand => OperatorCode::BinOpLogicalAnd,
+            Token::Bar => OperatorCode::BinOpBitwiseOr,
+            Token::Ampersand => OperatorCode::BinOpBitwiseAnd,
+            Token::Caret => OperatorCode::BinOpBitwiseXor,
+
+            Token::Comma => OperatorCode::BinOpComma,
+
+            Token::Equals => OperatorCode::BinOpAssign,
+            Token::PlusEquals => OperatorCode::BinOpAddAssign,
+            Token::MinusEquals => OperatorCode::BinOpSubAssign,
+            Token::AsteriskEquals => OperatorCode::BinOpMulAssign,
+            Token::SlashEquals => OperatorCode::BinOpDivAssign,
+            Token::PercentEquals => OperatorCode::BinOpRemAssign,
+            Token::AsteriskAsteriskEquals => OperatorCode::BinOpPowAssign,
+            Token::LessThanLessThanEquals => OperatorCode::BinOpShlAssign,
+            Token::GreaterThanGreaterThanEquals => OperatorCode::BinOpShrAssign,
+            Token::GreaterThanGreaterThanGreaterThanEquals => OperatorCode::BinOpUShrAssign,
+            Token::BarEquals => OperatorCode::BinOpBitwiseOrAssign,
+            Token::AmpersandEquals => OperatorCode::BinOpBitwiseAndAssign,
+            Token::CaretEquals => OperatorCode::BinOpBitwiseXorAssign,
+
+            _ => return None,
+        })
+    }
 }
 
 impl TryFrom<&str> for Token {
@@ -587,17 +672,48 @@ pub fn jsx_entry(s: &str) -> Option<char> {
     .and_then(|u| char::try_from(u).ok())
 }
 
+// These work like the tables in Go's "unicode" package. "latin_offset" is the
+// number of ranges in "r16" that end at or below U+00FF. Those are scanned
+// linearly since there are only a few, and the rest are binary searched.
 pub trait RangeTable {
     fn latin_offset() -> usize;
     fn r16() -> &'static [RangeInclusive<u16>];
     fn r32() -> &'static [RangeInclusive<u32>];
+
+    fn contains(code_point: char) -> bool {
+        let c = code_point as u32;
+        let r16 = Self::r16();
+        if c <= 0xFF {
+            return r16[..Self::latin_offset()]
+                .iter()
+                .any(|r| u32::from(*r.start()) <= c && c <= u32::from(*r.end()));
+        }
+        let compare = |start: u32, end: u32| {
+            if end < c {
+                Ordering::Less
+            } else if start > c {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        };
+        if c <= 0xFFFF {
+            r16[Self::latin_offset()..]
+                .binary_search_by(|r| compare(u32::from(*r.start()), u32::from(*r.end())))
+                .is_ok()
+        } else {
+            Self::r32()
+                .binary_search_by(|r| compare(*r.start(), *r.end()))
+                .is_ok()
+        }
+    }
 }
 
 pub struct IdStart;
 
 impl RangeTable for IdStart {
     fn latin_offset() -> usize {
-        117
+        7
     }
 
     fn r16() -> &'static [RangeInclusive<u16>] {
@@ -1258,7 +1374,7 @@ pub struct IdContinue;
 
 impl RangeTable for IdContinue {
     fn latin_offset() -> usize {
-        129
+        7
     }
 
     fn r16() -> &'static [RangeInclusive<u16>] {
@@ -0,0 +1,97 @@
+// Colors in terminal output are ANSI escape sequences. Terminals on Unix all
+// understand them. The Windows console only does once it has been asked to
+// with "ENABLE_VIRTUAL_TERMINAL_PROCESSING", and versions of Windows from
+// before that flag existed would print the escapes as garbage instead.
+
+pub const COLOR_RESET: &str = "\x1b[0m";
+pub const COLOR_RED: &str = "\x1b[31m";
+pub const COLOR_GREEN: &str = "\x1b[32m";
+pub const COLOR_MAGENTA: &str = "\x1b[35m";
+pub const COLOR_BOLD: &str = "\x1b[1m";
+pub const COLOR_RESET_BOLD: &str = "\x1b[0;1m";
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+pub enum StderrColor {
+    IfTerminal = 0,
+    Never,
+    Always,
+}
+
+impl StderrColor {
+    // Colors that were asked for explicitly are used even when "NO_COLOR" is
+    // set, like https://no-color.org says
+    pub fn is_enabled(self, terminal_info: &TerminalInfo) -> bool {
+        match self {
+            StderrColor::IfTerminal => terminal_info.use_color_escapes,
+            StderrColor::Never => false,
+            StderrColor::Always => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TerminalInfo {
+    pub is_tty: bool,
+
+    // Whether stderr is a terminal that can show colors, and colors haven't
+    // been turned off with the "NO_COLOR" environment variable
+    pub use_color_escapes: bool,
+
+    pub width: usize,
+}
+
+impl TerminalInfo {
+    // This turns on the escapes on Windows, so it's only done once the output
+    // is known to go to a terminal
+    pub fn for_stderr() -> Self {
+        let is_tty = atty::is(atty::Stream::Stderr);
+        Self {
+            is_tty,
+            use_color_escapes: is_tty && !is_no_color_set() && enable_color_escapes(),
+            width: terminal_size::terminal_size()
+                .map(|(w, _)| w.0 as usize)
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl Default for TerminalInfo {
+    fn default() -> Self {
+        Self::for_stderr()
+    }
+}
+
+// An empty value doesn't count
+fn is_no_color_set() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+#[cfg(windows)]
+fn enable_color_escapes() -> bool {
+    use winapi::um::consoleapi::{GetConsoleMode, SetConsoleMode};
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_ERROR_HANDLE;
+    use winapi::um::wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+
+    unsafe {
+        let handle = GetStdHandle(STD_ERROR_HANDLE);
+        if handle.is_null() || handle == INVALID_HANDLE_VALUE {
+            return false;
+        }
+
+        // Terminals that aren't the console, such as the one that comes with
+        // Git, fail this but understand the escapes already
+        let mut mode = 0;
+        if GetConsoleMode(handle, &mut mode) == 0 {
+            return true;
+        }
+        mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+            || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+    }
+}
+
+#[cfg(not(windows))]
+fn enable_color_escapes() -> bool {
+    true
+}
@@ -0,0 +1,241 @@
+// This is the part of "tsconfig.json" that changes how files are resolved and
+// compiled. A file uses the nearest "tsconfig.json" in its directory or any
+// parent directory, which the resolver finds. The file may inherit settings
+// from another one with "extends", which the resolver also loads since that
+// can refer to a package.
+//
+// The file is JSON with comments and trailing commas, which is what the
+// TypeScript compiler accepts.
+
+use crate::ast::{Expr, ExprKind, Property};
+use crate::logging::{Log, Source};
+use crate::lower::Target;
+use crate::parser::{ImportsNotUsedAsValues, JSXRuntime, ParseOptions};
+use crate::parser_json::{parse_json, JsonOptions};
+use crate::paths;
+use crate::strings::utf16_to_string_lossy;
+
+#[derive(Debug, Clone, Default)]
+pub struct TSConfig {
+    pub abs_path: String,
+
+    // Import paths that aren't relative are also looked up in this directory
+    pub base_url: Option<String>,
+
+    // The patterns in "paths" in the order they were written, with what each
+    // one maps to. A pattern has at most one "*", which matches anything.
+    pub paths: Vec<(String, Vec<String>)>,
+
+    // Relative paths in "paths" are relative to "baseUrl", or to the
+    // directory of the file that declared them if there is no "baseUrl"
+    pub paths_base_dir: String,
+
+    // Property chains like ["React", "createElement"]
+    pub jsx_factory: Option<Vec<String>>,
+    pub jsx_fragment_factory: Option<Vec<String>>,
+
+    // The runtime from "jsx", and whether it's the one for development
+    pub jsx: Option<(JSXRuntime, bool)>,
+    pub jsx_import_source: Option<String>,
+
+    pub target: Option<Target>,
+    pub imports_not_used_as_values: Option<ImportsNotUsedAsValues>,
+}
+
+impl TSConfig {
+    // Settings from "tsconfig.json" only replace options that are still at
+    // their defaults, so options given on the command line win
+    pub fn parse_options(&self, options: &ParseOptions) -> ParseOptions {
+        let mut options = options.clone();
+        if let (Some(factory), true) = (&self.jsx_factory, options.jsx.factory.is_empty()) {
+            options.jsx.factory = factory.clone();
+        }
+        if let (Some(fragment), true) =
+            (&self.jsx_fragment_factory, options.jsx.fragment.is_empty())
+        {
+            options.jsx.fragment = fragment.clone();
+        }
+        if let (Some((runtime, development)), JSXRuntime::Classic, false) =
+            (self.jsx, options.jsx.runtime, options.jsx.development)
+        {
+            options.jsx.runtime = runtime;
+            options.jsx.development = development;
+        }
+        if let (Some(source), true) = (
+            &self.jsx_import_source,
+            options.jsx.import_source.is_empty(),
+        ) {
+            options.jsx.import_source = source.clone();
+        }
+        if let (Some(target), Target::ESNext) = (self.target, options.lower.target) {
+            options.lower.target = target;
+        }
+        if let (Some(value), ImportsNotUsedAsValues::Remove) = (
+            self.imports_not_used_as_values,
+            options.typescript.imports_not_used_as_values,
+        ) {
+            options.typescript.imports_not_used_as_values = value;
+        }
+        options
+    }
+
+    // Settings in this file replace the ones it inherited
+    pub fn extend(base: TSConfig, config: TSConfig) -> TSConfig {
+        let (paths, paths_base_dir) = if config.paths.is_empty() {
+            (base.paths, base.paths_base_dir)
+        } else {
+            (config.paths, config.paths_base_dir)
+        };
+        TSConfig {
+            abs_path: config.abs_path,
+            base_url: config.base_url.or(base.base_url),
+            paths,
+            paths_base_dir,
+            jsx_factory: config.jsx_factory.or(base.jsx_factory),
+            jsx_fragment_factory: config.jsx_fragment_factory.or(base.jsx_fragment_factory),
+            jsx: config.jsx.or(base.jsx),
+            jsx_import_source: config.jsx_import_source.or(base.jsx_import_source),
+            target: config.target.or(base.target),
+            imports_not_used_as_values: config
+                .imports_not_used_as_values
+                .or(base.imports_not_used_as_values),
+        }
+    }
+}
+
+// Returns the settings of the file and the path in its "extends" field, or
+// None if it couldn't be parsed. Values of the wrong type and values that
+// aren't supported are ignored like the TypeScript compiler ignores options it
+// doesn't know.
+pub fn parse_tsconfig(log: &Log, source: &Source) -> Option<(TSConfig, Option<String>)> {
+    let options = JsonOptions {
+        allow_comments: true,
+        allow_trailing_commas: true,
+    };
+    let json = parse_json(log, source, options)?;
+    let dir = paths::dir(&source.absolute_path);
+    let mut config = TSConfig {
+        abs_path: source.absolute_path.clone(),
+        paths_base_dir: dir.clone(),
+        ..TSConfig::default()
+    };
+
+    let properties = match json.data.as_ref() {
+        ExprKind::Object { properties } => properties,
+        _ => return Some((config, None)),
+    };
+    let extends = get_property(properties, "extends").and_then(get_string);
+    let compiler_options =
+        match get_property(properties, "compilerOptions").map(|e| e.data.as_ref()) {
+            Some(ExprKind::Object { properties }) => properties,
+            _ => return Some((config, extends)),
+        };
+
+    if let Some(base_url) = get_property(compiler_options, "baseUrl").and_then(get_string) {
+        let base_url = paths::join(&dir, &base_url);
+        config.paths_base_dir = base_url.clone();
+        config.base_url = Some(base_url);
+    }
+
+    if let Some(ExprKind::Object { properties }) =
+        get_property(compiler_options, "paths").map(|e| e.data.as_ref())
+    {
+        for property in properties {
+            let pattern = match get_string(&property.key) {
+                Some(pattern) if pattern.matches('*').count() <= 1 => pattern,
+                _ => continue,
+            };
+            let substitutions = match property.value.as_ref().map(|e| e.data.as_ref()) {
+                Some(ExprKind::Array { items }) => items
+                    .iter()
+                    .filter_map(get_string)
+                    .filter(|path| path.matches('*').count() <= 1)
+                    .collect(),
+                _ => continue,
+            };
+            config.paths.push((pattern, substitutions));
+        }
+    }
+
+    let property_chain = |name| {
+        let value = get_property(compiler_options, name).and_then(get_string)?;
+        let parts: Vec<String> = value.split('.').map(str::to_owned).collect();
+        if parts.iter().any(|part| part.is_empty()) {
+            return None;
+        }
+        Some(parts)
+    };
+    config.jsx_factory = property_chain("jsxFactory");
+    config.jsx_fragment_factory = property_chain("jsxFragmentFactory");
+
+    // JSX can't be kept as it is, so "preserve" and "react-native" are ignored
+    config.jsx = get_property(compiler_options, "jsx")
+        .and_then(get_string)
+        .and_then(|jsx| match jsx.to_ascii_lowercase().as_str() {
+            "react" => Some((JSXRuntime::Classic, false)),
+            "react-jsx" => Some((JSXRuntime::Automatic, false)),
+            "react-jsxdev" => Some((JSXRuntime::Automatic, true)),
+            _ => None,
+        });
+    config.jsx_import_source =
+        get_property(compiler_options, "jsxImportSource").and_then(get_string);
+
+    // There's nothing to lower past ES2020 yet, so later versions are the
+    // same as "ESNext"
+    config.target = get_property(compiler_options, "target")
+        .and_then(get_string)
+        .and_then(|target| match target.to_ascii_lowercase().as_str() {
+            "es2021" | "es2022" | "es2023" => Some(Target::ESNext),
+            target => Target::from_name(target),
+        });
+
+    config.imports_not_used_as_values = get_property(compiler_options, "importsNotUsedAsValues")
+        .and_then(get_string)
+        .and_then(|value| match value.to_ascii_lowercase().as_str() {
+            "remove" => Some(ImportsNotUsedAsValues::Remove),
+            "preserve" => Some(ImportsNotUsedAsValues::Preserve),
+            "error" => Some(ImportsNotUsedAsValues::Error),
+            _ => None,
+        });
+
+    Some((config, extends))
+}
+
+// The last property wins if there's more than one with the same key
+fn get_property<'a>(properties: &'a [Property], name: &str) -> Option<&'a Expr> {
+    properties
+        .iter()
+        .rev()
+        .find(|property| get_string(&property.key).as_deref() == Some(name))
+        .and_then(|property| property.value.as_ref())
+}
+
+fn get_string(expr: &Expr) -> Option<String> {
+    match expr.data.as_ref() {
+        ExprKind::String { value } => Some(utf16_to_string_lossy(value)),
+        _ => None,
+    }
+}
+
+// The pattern in "paths" that matches the import path and the text that its
+// "*" matched. Exact matches win, and then the pattern with the longest
+// prefix before its "*" like in the TypeScript compiler.
+pub fn match_paths<'a>(config: &'a TSConfig, import_path: &str) -> Option<(&'a [String], String)> {
+    let mut best: Option<(&[String], String, usize)> = None;
+    for (pattern, substitutions) in &config.paths {
+        match pattern.split_once('*') {
+            None if pattern == import_path => return Some((substitutions, String::new())),
+            None => {}
+            Some((prefix, suffix)) => {
+                let matches = import_path.len() >= prefix.len() + suffix.len()
+                    && import_path.starts_with(prefix)
+                    && import_path.ends_with(suffix);
+                if matches && best.as_ref().is_none_or(|best| prefix.len() > best.2) {
+                    let star = &import_path[prefix.len()..import_path.len() - suffix.len()];
+                    best = Some((substitutions, star.to_owned(), prefix.len()));
+                }
+            }
+        }
+    }
+    best.map(|(substitutions, star, _)| (substitutions, star))
+}
@@ -0,0 +1,309 @@
+// Passes over the tree are written as visitors. A visitor implements "Visit"
+// (which borrows the tree) or "VisitMut" (which can change it in place) and
+// overrides only the "visit_*" methods for the nodes it cares about. Every
+// "visit_*" method defaults to the matching "walk_*" method, which visits the
+// children of the node. An overridden method calls "walk_*" itself to keep
+// going into the children:
+//
+//   struct CountCalls(usize);
+//
+//   impl Visit for CountCalls {
+//       fn visit_expr(&mut self, expr: &Expr) {
+//           if let ExprKind::Call { .. } = expr.data.as_ref() {
+//               self.0 += 1;
+//           }
+//           self.walk_expr(expr);
+//       }
+//   }
+//
+// Symbol references such as the names of imports, labels, and exports are
+// part of the statements that contain them and aren't visited separately.
+
+use crate::ast::{
+    Binding, BindingKind, Class, Expr, ExprKind, ExprOrStmt, Function, Property, Stmt, StmtKind,
+};
+use crate::stack::ensure_sufficient_stack;
+
+// Both traits are generated from the same definition so that they can't get
+// out of sync. The only differences are the kind of reference and that lists
+// of statements are vectors in "VisitMut", so that a pass can add and remove
+// statements.
+macro_rules! visitor {
+    ($name:ident, $stmts:ty $(, $mut:tt)?) => {
+        pub trait $name {
+            fn visit_stmts(&mut self, stmts: &$($mut)? $stmts) {
+                self.walk_stmts(stmts)
+            }
+
+            fn visit_stmt(&mut self, stmt: &$($mut)? Stmt) {
+                self.walk_stmt(stmt)
+            }
+
+            fn visit_expr(&mut self, expr: &$($mut)? Expr) {
+                self.walk_expr(expr)
+            }
+
+            fn visit_binding(&mut self, binding: &$($mut)? Binding) {
+                self.walk_binding(binding)
+            }
+
+            fn visit_function(&mut self, function: &$($mut)? Function) {
+                self.walk_function(function)
+            }
+
+            fn visit_class(&mut self, class: &$($mut)? Class) {
+                self.walk_class(class)
+            }
+
+            // Class members and object literal properties are both properties
+            fn visit_property(&mut self, property: &$($mut)? Property, is_class: bool) {
+                self.walk_property(property, is_class)
+            }
+
+            fn walk_stmts(&mut self, stmts: &$($mut)? $stmts) {
+                for stmt in stmts {
+                    self.visit_stmt(stmt);
+                }
+            }
+
+            fn walk_stmt(&mut self, stmt: &$($mut)? Stmt) {
+                ensure_sufficient_stack(|| {
+                    match &$($mut)? *stmt.data {
+                        StmtKind::Block { stmts } | StmtKind::Namespace { stmts, .. } => {
+                            self.visit_stmts(stmts)
+                        }
+                        StmtKind::ExportDefault {
+                            value: ExprOrStmt::Expr(value),
+                            ..
+                        }
+                        | StmtKind::ExportEquals { value }
+                        | StmtKind::Expr { value }
+                        | StmtKind::Throw { value }
+                        | StmtKind::Return { value: Some(value) } => self.visit_expr(value),
+                        StmtKind::Label { stmt, .. }
+                        | StmtKind::ExportDefault {
+                            value: ExprOrStmt::Stmt(stmt),
+                            ..
+                        } => self.visit_stmt(stmt),
+                        StmtKind::Enum { values, .. } => {
+                            for value in values {
+                                if let Some(value) = &$($mut)? value.value {
+                                    self.visit_expr(value);
+                                }
+                            }
+                        }
+                        StmtKind::Function { function, .. } => self.visit_function(function),
+                        StmtKind::Class { class, .. } => self.visit_class(class),
+                        StmtKind::If { test, yes, no } => {
+                            self.visit_expr(test);
+                            self.visit_stmt(yes);
+                            if let Some(no) = no {
+                                self.visit_stmt(no);
+                            }
+                        }
+                        StmtKind::For {
+                            init,
+                            test,
+                            update,
+                            body,
+                        } => {
+                            if let Some(init) = init {
+                                self.visit_stmt(init);
+                            }
+                            if let Some(test) = test {
+                                self.visit_expr(test);
+                            }
+                            if let Some(update) = update {
+                                self.visit_expr(update);
+                            }
+                            self.visit_stmt(body);
+                        }
+                        StmtKind::ForIn { init, value, body }
+                        | StmtKind::ForOf {
+                            init, value, body, ..
+                        } => {
+                            self.visit_stmt(init);
+                            self.visit_expr(value);
+                            self.visit_stmt(body);
+                        }
+                        StmtKind::DoWhile { body, test } | StmtKind::While { test, body } => {
+                            self.visit_stmt(body);
+                            self.visit_expr(test);
+                        }
+                        StmtKind::With { value, body, .. } => {
+                            self.visit_expr(value);
+                            self.visit_stmt(body);
+                        }
+                        StmtKind::Catch(catch) => {
+                            if let Some(binding) = &$($mut)? catch.binding {
+                                self.visit_binding(binding);
+                            }
+                            self.visit_stmts(&$($mut)? catch.body);
+                        }
+                        StmtKind::Finally(finally) => self.visit_stmts(&$($mut)? finally.stmts),
+                        StmtKind::Try {
+                            body,
+                            catch,
+                            finally,
+                        } => {
+                            self.visit_stmts(body);
+                            if let Some(catch) = catch {
+                                if let Some(binding) = &$($mut)? catch.binding {
+                                    self.visit_binding(binding);
+                                }
+                                self.visit_stmts(&$($mut)? catch.body);
+                            }
+                            if let Some(finally) = finally {
+                                self.visit_stmts(&$($mut)? finally.stmts);
+                            }
+                        }
+                        StmtKind::Switch { test, cases, .. } => {
+                            self.visit_expr(test);
+                            for case in cases {
+                                if let Some(value) = &$($mut)? case.value {
+                                    self.visit_expr(value);
+                                }
+                                self.visit_stmts(&$($mut)? case.body);
+                            }
+                        }
+                        StmtKind::Local { decls, .. } => {
+                            for decl in decls {
+                                self.visit_binding(&$($mut)? decl.binding);
+                                if let Some(value) = &$($mut)? decl.value {
+                                    self.visit_expr(value);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                })
+            }
+
+            fn walk_expr(&mut self, expr: &$($mut)? Expr) {
+                ensure_sufficient_stack(|| {
+                    match &$($mut)? *expr.data {
+                        ExprKind::Array { items } => {
+                            for item in items {
+                                self.visit_expr(item);
+                            }
+                        }
+                        ExprKind::Unary { value, .. }
+                        | ExprKind::Spread { value }
+                        | ExprKind::Await { value }
+                        | ExprKind::Yield { value, .. }
+                        | ExprKind::Import { expr: value }
+                        | ExprKind::Dot { target: value, .. } => self.visit_expr(value),
+                        ExprKind::Binary { left, right, .. }
+                        | ExprKind::Index {
+                            target: left,
+                            index: right,
+                            ..
+                        } => {
+                            self.visit_expr(left);
+                            self.visit_expr(right);
+                        }
+                        ExprKind::New { target, args, .. } | ExprKind::Call { target, args, .. } => {
+                            self.visit_expr(target);
+                            for arg in args {
+                                self.visit_expr(arg);
+                            }
+                        }
+                        ExprKind::RuntimeCall { args, .. } => {
+                            for arg in args {
+                                self.visit_expr(arg);
+                            }
+                        }
+                        ExprKind::Arrow { args, body, .. } => {
+                            for arg in args {
+                                self.visit_binding(&$($mut)? arg.binding);
+                                if let Some(default) = &$($mut)? arg.default_ {
+                                    self.visit_expr(default);
+                                }
+                            }
+                            self.visit_stmts(&$($mut)? body.stmts);
+                        }
+                        ExprKind::Function { function } => self.visit_function(function),
+                        ExprKind::Class { class } => self.visit_class(class),
+                        ExprKind::Object { properties } => {
+                            for property in properties {
+                                self.visit_property(property, false);
+                            }
+                        }
+                        ExprKind::Template { tag, parts, .. } => {
+                            self.visit_expr(tag);
+                            for part in parts {
+                                self.visit_expr(&$($mut)? part.value);
+                            }
+                        }
+                        ExprKind::If { test, yes, no } => {
+                            self.visit_expr(test);
+                            self.visit_expr(yes);
+                            self.visit_expr(no);
+                        }
+                        _ => {}
+                    }
+                })
+            }
+
+            // Default values and computed keys in patterns are expressions
+            fn walk_binding(&mut self, binding: &$($mut)? Binding) {
+                ensure_sufficient_stack(|| {
+                    match &$($mut)? *binding.data {
+                        BindingKind::Array { items, .. } => {
+                            for item in items {
+                                self.visit_binding(&$($mut)? item.binding);
+                                if let Some(default_value) = &$($mut)? item.default_value {
+                                    self.visit_expr(default_value);
+                                }
+                            }
+                        }
+                        BindingKind::Object { properties } => {
+                            for property in properties {
+                                if property.is_computed {
+                                    self.visit_expr(&$($mut)? property.key);
+                                }
+                                self.visit_binding(&$($mut)? property.value);
+                                if let Some(default_value) = &$($mut)? property.default_value {
+                                    self.visit_expr(default_value);
+                                }
+                            }
+                        }
+                        BindingKind::Identifier { .. } | BindingKind::Missing => {}
+                    }
+                })
+            }
+
+            fn walk_function(&mut self, function: &$($mut)? Function) {
+                for arg in &$($mut)? function.args {
+                    self.visit_binding(&$($mut)? arg.binding);
+                    if let Some(default) = &$($mut)? arg.default_ {
+                        self.visit_expr(default);
+                    }
+                }
+                self.visit_stmts(&$($mut)? function.body.stmts);
+            }
+
+            fn walk_class(&mut self, class: &$($mut)? Class) {
+                if let Some(extends) = &$($mut)? class.extends {
+                    self.visit_expr(extends);
+                }
+                for property in &$($mut)? class.properties {
+                    self.visit_property(property, true);
+                }
+            }
+
+            fn walk_property(&mut self, property: &$($mut)? Property, _is_class: bool) {
+                self.visit_expr(&$($mut)? property.key);
+                if let Some(value) = &$($mut)? property.value {
+                    self.visit_expr(value);
+                }
+                if let Some(initializer) = &$($mut)? property.initializer {
+                    self.visit_expr(initializer);
+                }
+            }
+        }
+    };
+}
+
+visitor!(Visit, [Stmt]);
+visitor!(VisitMut, Vec<Stmt>, mut);
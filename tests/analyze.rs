@@ -0,0 +1,97 @@
+use esbuild_rs::analyze::{analyze_symbols, SymbolUsage};
+use esbuild_rs::ast::{StmtKind, SymbolKind};
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::{parse, ParseOptions};
+
+fn analyze(contents: &str) -> Vec<SymbolUsage> {
+    let source = Source {
+        index: 1,
+        contents: contents.to_owned(),
+        ..Source::default()
+    };
+    let ast = parse(&Log::default(), &source, &ParseOptions::default()).unwrap();
+    analyze_symbols(&ast)
+}
+
+fn find<'a>(usages: &'a [SymbolUsage], name: &str) -> &'a SymbolUsage {
+    usages.iter().find(|usage| usage.name == name).unwrap()
+}
+
+#[test]
+fn symbol_usage() {
+    let code = "let a = 1; export let b = () => a; function f(c) { let d = c; return d + g } f(a)";
+    let usages = analyze(code);
+    let names: Vec<&str> = usages.iter().map(|usage| usage.name.as_str()).collect();
+    assert_eq!(names, ["a", "b", "f", "c", "d", "g"]);
+
+    let a = find(&usages, "a");
+    assert_eq!(a.declarations, [code.find("a =").unwrap()]);
+    assert_eq!(
+        a.references,
+        [code.find("a;").unwrap(), code.rfind('a').unwrap()]
+    );
+    assert!(!a.is_exported);
+    assert!(a.is_captured_by_closure);
+
+    let b = find(&usages, "b");
+    assert!(b.is_exported);
+    assert!(b.references.is_empty());
+
+    // Uses in the declaring function aren't captures, and globals are only
+    // referenced
+    assert!(!find(&usages, "c").is_captured_by_closure);
+    assert!(!find(&usages, "d").is_captured_by_closure);
+    let g = find(&usages, "g");
+    assert_eq!(g.kind, SymbolKind::Unbound);
+    assert!(g.declarations.is_empty());
+    assert_eq!(g.references, [code.find('g').unwrap()]);
+}
+
+#[test]
+fn symbol_usage_in_nested_scopes() {
+    let usages = analyze(
+        "var x; var x; (function h() { return h }); class A { #p = x; m() { return this.#p } }",
+    );
+    let x = find(&usages, "x");
+    assert_eq!(x.declarations.len(), 2);
+    assert!(x.is_captured_by_closure);
+
+    // A function expression's name belongs to the function itself
+    let h = find(&usages, "h");
+    assert_eq!(h.references.len(), 1);
+    assert!(!h.is_captured_by_closure);
+
+    let p = find(&usages, "#p");
+    assert_eq!(p.declarations.len(), 1);
+    assert_eq!(p.references.len(), 1);
+    assert!(p.is_captured_by_closure);
+}
+
+#[test]
+fn side_effects() {
+    let source = Source {
+        index: 1,
+        contents: "let a; [1, a, () => b]; b; /* @__PURE__ */ f(a); /* @__PURE__ */ f(b); a == 1"
+            .to_owned(),
+        ..Source::default()
+    };
+    let ast = parse(&Log::default(), &source, &ParseOptions::default()).unwrap();
+    let exprs: Vec<_> = ast
+        .parts
+        .iter()
+        .flat_map(|part| &part.stmts)
+        .filter_map(|stmt| match stmt.data.as_ref() {
+            StmtKind::Expr { value } => Some(value),
+            _ => None,
+        })
+        .collect();
+
+    // Only declared symbols can be read without side effects, and "==" can
+    // call "valueOf"
+    let removable: Vec<bool> = exprs
+        .iter()
+        .map(|expr| expr.can_be_removed_if_unused(&ast.symbols))
+        .collect();
+    assert_eq!(removable, [true, false, true, false, false]);
+    assert!(exprs.iter().all(|expr| expr.has_side_effects()));
+}
@@ -0,0 +1,243 @@
+use esbuild_rs::api::{build, BuildContext, BuildOptions};
+use esbuild_rs::bundler::{BundleOptions, OutputFormat};
+use esbuild_rs::fs::{expand_glob, MockFileSystem};
+use esbuild_rs::parser::ParseOptions;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+fn options(dir: &str) -> BuildOptions {
+    BuildOptions {
+        entry_paths: vec![format!("{}/entry.js", dir)],
+        parse_options: ParseOptions {
+            is_bundling: true,
+            ..ParseOptions::default()
+        },
+        bundle_options: BundleOptions {
+            bundle: true,
+            abs_output_dir: "/out".to_owned(),
+            ..BundleOptions::default()
+        },
+        ..BuildOptions::default()
+    }
+}
+
+fn code(result: &esbuild_rs::api::BuildResult) -> String {
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    String::from_utf8(result.output_files[0].contents.clone()).unwrap()
+}
+
+#[test]
+fn rebuild() {
+    let dir = std::env::temp_dir().join(format!("esbd-api-test-{}", std::process::id()));
+    let dir_str = dir.to_string_lossy().into_owned();
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("entry.js"),
+        "import {a} from './a'; console.log(a)",
+    )
+    .unwrap();
+    fs::write(dir.join("a.js"), "export let a = 1").unwrap();
+
+    let mut ctx = BuildContext::new(options(&dir_str));
+    let first = code(&ctx.rebuild());
+    assert!(first.contains("let a = 1;"), "{}", first);
+    assert_eq!(code(&ctx.rebuild()), first);
+    assert_eq!(code(&build(options(&dir_str))), first);
+
+    // Only the changed file is parsed again, but the output must still match
+    // a build from scratch
+    fs::write(dir.join("a.js"), "export let a = 2").unwrap();
+    let second = code(&ctx.rebuild());
+    assert!(second.contains("let a = 2;"), "{}", second);
+    assert_eq!(code(&build(options(&dir_str))), second);
+
+    ctx.dispose();
+    assert_eq!(ctx.rebuild().errors.len(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn watch_invalidation() {
+    let dir = std::env::temp_dir().join(format!("esbd-watch-test-{}", std::process::id()));
+    let dir_str = dir.to_string_lossy().into_owned();
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("entry.js"),
+        "import {a} from './a'; import {c} from './c'; console.log(a, c)",
+    )
+    .unwrap();
+    fs::write(dir.join("a.js"), "import {b} from './b'; export let a = b").unwrap();
+    fs::write(dir.join("b.js"), "export let b = 1").unwrap();
+    fs::write(dir.join("c.js"), "export let c = 1").unwrap();
+
+    let mut ctx = BuildContext::new(options(&dir_str));
+    let events = ctx.subscribe();
+    code(&ctx.rebuild());
+    let path = |name: &str| format!("{}/{}", dir_str, name);
+    let graph = ctx.module_graph();
+    let b = graph.source_index(&path("b.js")).unwrap();
+    let a = graph.source_index(&path("a.js")).unwrap();
+    assert_eq!(graph.dependents(b), [a]);
+
+    // The first check only notes when each file was changed
+    assert!(ctx.rebuild_if_changed().is_none());
+    assert!(ctx.rebuild_if_changed().is_none());
+    assert!(events.try_recv().is_err());
+
+    fs::write(dir.join("b.js"), "export let b = 2").unwrap();
+    let later = SystemTime::now() + Duration::from_secs(10);
+    fs::File::options()
+        .write(true)
+        .open(dir.join("b.js"))
+        .unwrap()
+        .set_modified(later)
+        .unwrap();
+    let result = ctx.rebuild_if_changed().unwrap();
+    assert!(code(&result).contains("let b = 2;"));
+
+    // Only "b.js" and the files that import it are affected, not "c.js"
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.changed, [path("b.js")]);
+    let mut affected = event.affected;
+    affected.sort();
+    assert_eq!(affected, [path("a.js"), path("b.js"), path("entry.js")]);
+    assert!(events.try_recv().is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn glob_entry_points() {
+    let mut input = HashMap::new();
+    for path in &[
+        "/src/a.js",
+        "/src/lib/b.js",
+        "/src/lib/deep/c.js",
+        "/src/lib/d.ts",
+        "/src/.hidden/e.js",
+    ] {
+        input.insert(PathBuf::from(path), format!("console.log('{}')", path));
+    }
+    let fs = MockFileSystem::new(input);
+    assert_eq!(
+        expand_glob(&fs, "/src/**/*.js"),
+        ["/src/a.js", "/src/lib/b.js", "/src/lib/deep/c.js"]
+    );
+    assert_eq!(expand_glob(&fs, "/src/*/?.ts"), ["/src/lib/d.ts"]);
+
+    // The directories of the entry points are kept under the output directory
+    let options = BuildOptions {
+        entry_paths: vec!["/src/lib/**/*.js".to_owned()],
+        bundle_options: BundleOptions {
+            abs_output_dir: "/out".to_owned(),
+            abs_outbase: "/src".to_owned(),
+            ..BundleOptions::default()
+        },
+        ..BuildOptions::default()
+    };
+    let result = BuildContext::with_fs(fs, options).rebuild();
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    let paths: Vec<&str> = result
+        .output_files
+        .iter()
+        .map(|file| file.path.as_str())
+        .collect();
+    assert_eq!(paths, ["/out/lib/b.js", "/out/lib/deep/c.js"]);
+}
+
+#[test]
+fn build_result() {
+    let mut input = HashMap::new();
+    input.insert(
+        PathBuf::from("/src/entry.js"),
+        "import {b} from './b'\nexport let a = b".to_owned(),
+    );
+    input.insert(PathBuf::from("/src/b.js"), "export let b = 1".to_owned());
+    let build = |bundle_options: BundleOptions| {
+        let options = BuildOptions {
+            entry_paths: vec!["/src/entry.js".to_owned()],
+            bundle_options: BundleOptions {
+                bundle: true,
+                abs_output_dir: "/out".to_owned(),
+                ..bundle_options
+            },
+            ..options("/src")
+        };
+        BuildContext::with_fs(MockFileSystem::new(input.clone()), options).rebuild()
+    };
+
+    let result = build(BundleOptions {
+        metafile: true,
+        ..BundleOptions::default()
+    });
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    let exports: Vec<&String> = result.entry_point_exports[0].exports.iter().collect();
+    assert_eq!(exports, ["a"]);
+    let metafile = result.metafile.unwrap();
+    assert!(metafile.contains("\"/src/b.js\""), "{}", metafile);
+    assert!(metafile.contains("\"out/entry.js\""), "{}", metafile);
+
+    // The options are checked before anything is built
+    let errors = |options| -> Vec<String> {
+        let result = build(options);
+        assert!(result.output_files.is_empty());
+        result.errors.into_iter().map(|msg| msg.text).collect()
+    };
+    assert_eq!(
+        errors(BundleOptions {
+            output_format: OutputFormat::Cjs,
+            code_splitting: true,
+            ..BundleOptions::default()
+        }),
+        ["Splitting is not supported for the Cjs format, only for the Esm and Iife formats"]
+    );
+    assert_eq!(
+        errors(BundleOptions {
+            global_name: "lib".to_owned(),
+            ..BundleOptions::default()
+        }),
+        ["A global name can only be used with the Iife format"]
+    );
+}
+
+#[test]
+fn module_graph_chunks() {
+    let mut input = HashMap::new();
+    input.insert(
+        PathBuf::from("/src/entry.js"),
+        "import {a} from './a'\nexport let load = () => import('./page').then((p) => p.b + a)"
+            .to_owned(),
+    );
+    input.insert(
+        PathBuf::from("/src/page.js"),
+        "import {a} from './a'\nexport let b = a + 1".to_owned(),
+    );
+    input.insert(PathBuf::from("/src/a.js"), "export let a = 1".to_owned());
+    let options = BuildOptions {
+        entry_paths: vec!["/src/entry.js".to_owned()],
+        bundle_options: BundleOptions {
+            code_splitting: true,
+            ..options("/src").bundle_options
+        },
+        ..options("/src")
+    };
+    let mut ctx = BuildContext::with_fs(MockFileSystem::new(input), options);
+    let result = ctx.rebuild();
+    let paths: Vec<&str> = result
+        .output_files
+        .iter()
+        .map(|file| file.path.as_str())
+        .collect();
+    assert_eq!(paths.len(), 3);
+    assert!(paths[1].starts_with("/out/page-"), "{:?}", paths);
+    assert!(paths[2].starts_with("/out/chunk-"), "{:?}", paths);
+
+    // Each module knows the output files that have its code, which is the
+    // common chunk for the file that both of the others import
+    let graph = ctx.module_graph();
+    let chunks = |path| graph.chunks(graph.source_index(path).unwrap()).to_vec();
+    assert_eq!(chunks("/src/entry.js"), [0]);
+    assert_eq!(chunks("/src/page.js"), [1]);
+    assert_eq!(chunks("/src/a.js"), [2]);
+}
@@ -0,0 +1,114 @@
+use esbuild_rs::ast::{
+    follow_all_symbols, follow_symbols, Binding, BindingKind, Decl, Expr, ExprKind, LocalKind,
+    OperatorCode, Reference, Stmt, StmtKind, Symbol, SymbolKind, SymbolMap,
+};
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::{parse, ParseOptions};
+use esbuild_rs::printer::{print_ast, PrintOptions};
+use esbuild_rs::renamer::Renamer;
+
+#[test]
+fn builders() {
+    // The symbols come from parsing, and the statements are replaced with
+    // ones that are built out of them
+    let source = Source {
+        index: 1,
+        contents: "let x; f".to_owned(),
+        ..Source::default()
+    };
+    let mut ast = parse(&Log::default(), &source, &ParseOptions::default()).unwrap();
+    let mut refs: Vec<Reference> = Vec::new();
+    for stmt in ast.parts.iter().flat_map(|part| &part.stmts) {
+        match stmt.data.as_ref() {
+            StmtKind::Local { decls, .. } => match decls[0].binding.data.as_ref() {
+                BindingKind::Identifier { reference } => refs.push(*reference),
+                _ => unreachable!(),
+            },
+            StmtKind::Expr { value } => match value.data.as_ref() {
+                ExprKind::Identifier { reference } => refs.push(*reference),
+                _ => unreachable!(),
+            },
+            _ => {}
+        }
+    }
+    let (x, f) = (refs[0], refs[1]);
+    let x_expr = || Expr::identifier(0, x);
+    let f_expr = || Expr::identifier(0, f);
+
+    // Operators of the wrong kind and bad assignment targets aren't allowed
+    assert!(Expr::binary(OperatorCode::UnOpNot, x_expr(), f_expr()).is_none());
+    assert!(Expr::unary(OperatorCode::BinOpAdd, x_expr()).is_none());
+    assert!(Expr::binary(OperatorCode::BinOpAssign, Expr::number(0, 1.0), x_expr()).is_none());
+    assert!(Expr::unary(OperatorCode::UnOpPostInc, Expr::call(f_expr(), vec![])).is_none());
+    assert!(Expr::dot(f_expr(), "a-b").is_none());
+    assert!(Expr::comma(vec![]).is_none());
+    assert!(Stmt::local(LocalKind::Const, vec![]).is_none());
+
+    let decl = Decl {
+        binding: Binding::identifier(0, x),
+        value: Some(Expr::number(0, 1.0)),
+    };
+    let sum = Expr::binary(OperatorCode::BinOpAdd, x_expr(), Expr::string(0, "a")).unwrap();
+    let call = Expr::call(Expr::dot(f_expr(), "if").unwrap(), vec![sum]);
+    let update = Expr::unary(OperatorCode::UnOpPostInc, x_expr()).unwrap();
+    let stmts = vec![
+        Stmt::local(LocalKind::Let, vec![decl]).unwrap(),
+        Stmt::if_(
+            x_expr(),
+            Stmt::block(
+                0,
+                vec![Stmt::expr(Expr::comma(vec![call, update]).unwrap())],
+            ),
+            Some(Stmt::return_(0, Some(Expr::index(f_expr(), x_expr())))),
+        ),
+    ];
+    ast.parts.truncate(1);
+    ast.parts[0].stmts = stmts;
+
+    let options = PrintOptions {
+        minify_whitespace: true,
+        ..PrintOptions::default()
+    };
+    assert_eq!(
+        print_ast(&ast, &Renamer::no_op(), options),
+        "let x=1;if(x){f.if(x+\"a\"),x++}else return f[x]"
+    );
+}
+
+#[test]
+fn symbol_links() {
+    // A long chain in one file, linked from the last symbol to the first, and
+    // one symbol in another file that is linked into the middle of it. The
+    // first file is left empty since its first symbol is "INVALID_REF".
+    let count = 100_000;
+    let mut symbols = SymbolMap::new(3);
+    symbols.outer[1] = (0..count)
+        .map(|i| Symbol::new(SymbolKind::Other, format!("s{}", i)))
+        .collect();
+    for i in 1..count {
+        symbols.outer[1][i].link = Reference::new(1, i - 1);
+    }
+    symbols.outer[2] = vec![Symbol::new(SymbolKind::Other, "t")];
+    symbols.outer[2][0].link = Reference::new(1, count / 2);
+
+    let first = Reference::new(1, 0);
+    let last = Reference::new(1, count - 1);
+    assert_eq!(follow_symbols(&mut symbols, last), Ok(first));
+    assert_eq!(symbols[Reference::new(1, count / 2)].link, first);
+    assert_eq!(follow_symbols(&mut symbols, first), Ok(first));
+
+    // Every link points straight to the end of its chain afterwards
+    symbols.outer[1][count - 1].link = Reference::new(1, count - 2);
+    follow_all_symbols(&mut symbols).unwrap();
+    assert_eq!(symbols[last].link, first);
+    assert_eq!(symbols[Reference::new(2, 0)].link, first);
+
+    symbols.outer[1][0].link = Reference::new(1, 2);
+    let cycle = follow_symbols(&mut symbols, Reference::new(2, 0)).unwrap_err();
+    assert_eq!(cycle.references.len(), 2);
+    assert!(follow_all_symbols(&mut symbols).is_err());
+    assert_eq!(
+        follow_symbols(&mut symbols, first).unwrap_err().to_string(),
+        "Symbols are linked to each other in a cycle: \"s0\" -> \"s2\" -> \"s0\""
+    );
+}
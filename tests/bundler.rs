@@ -0,0 +1,1505 @@
+use esbuild_rs::bundler::{
+    base64_encode, parse_files_parallel, scan_bundle, AstCache, BundleOptions, ImportPathRewriter,
+    Loader, OutputFormat,
+};
+use esbuild_rs::fs::MockFileSystem;
+use esbuild_rs::hooks::HookOutput;
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::ParseOptions;
+use esbuild_rs::printer::LegalComments;
+use esbuild_rs::resolver::{ResolveOptions, Resolver};
+use esbuild_rs::runtime::ChunkLoader;
+use esbuild_rs::MinifyOptions;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+fn bundle(files: &[(&str, &str)], entry_path: &str) -> Result<String, Vec<String>> {
+    let options = BundleOptions {
+        bundle: true,
+        ..BundleOptions::default()
+    };
+    build(files, entry_path, options)
+}
+
+fn build(
+    files: &[(&str, &str)],
+    entry_path: &str,
+    bundle_options: BundleOptions,
+) -> Result<String, Vec<String>> {
+    let output_files = build_files(files, entry_path, bundle_options)?;
+    Ok(output_files.into_iter().next().unwrap().1)
+}
+
+// Returns the path and contents of every output file
+fn build_files(
+    files: &[(&str, &str)],
+    entry_path: &str,
+    bundle_options: BundleOptions,
+) -> Result<Vec<(String, String)>, Vec<String>> {
+    let log = Log::default();
+    let output_files = build_with_log(&log, files, entry_path, bundle_options);
+    let errors: Vec<String> = log.take_msgs().into_iter().map(|msg| msg.text).collect();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(output_files)
+}
+
+fn build_with_log(
+    log: &Log,
+    files: &[(&str, &str)],
+    entry_path: &str,
+    bundle_options: BundleOptions,
+) -> Vec<(String, String)> {
+    let mut input = HashMap::new();
+    for (path, contents) in files {
+        input.insert(PathBuf::from(path), contents.to_string());
+    }
+    let resolver = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+    let parse_options = ParseOptions {
+        is_bundling: bundle_options.bundle,
+        output_format: bundle_options.output_format,
+        ..ParseOptions::default()
+    };
+    let bundle = scan_bundle(
+        log,
+        &resolver,
+        &[entry_path.to_owned()],
+        &parse_options,
+        None,
+    );
+    let result = bundle.compile(log, &bundle_options);
+    result
+        .output_files
+        .into_iter()
+        .map(|file| (file.path, String::from_utf8(file.contents).unwrap()))
+        .collect()
+}
+
+#[test]
+fn bundle_esm() {
+    let js = bundle(
+        &[
+            (
+                "/src/entry.js",
+                "import {count, bump as inc} from './lib/counter'\n\
+                 import config from './config.json'\n\
+                 let count2 = 0\n\
+                 function run() { let count = inc(); return count + count2 }\n\
+                 export {count}\n\
+                 export default run() + config.step",
+            ),
+            (
+                "/src/lib/counter.js",
+                "export let count = 0\n\
+                 export function bump() { return ++count }\n\
+                 export function unused() {}",
+            ),
+            ("/src/config.json", "{\"step\": 2}"),
+        ],
+        "/src/entry.js",
+    );
+
+    // The imported "count" keeps its name since the entry point exports it.
+    // The local "count" in the entry point would shadow it, so it's renamed.
+    assert_eq!(
+        js.unwrap(),
+        "let count = 0;\n\
+         function bump() {\n  return ++count;\n}\n\
+         var config_default = {\n  \"step\": 2\n};\n\
+         let count2 = 0;\n\
+         function run() {\n  let count3 = bump();\n  return count3 + count2;\n}\n\
+         export {count};\n\
+         var entry_default = run() + config_default.step;\n\
+         export {entry_default as default};\n"
+    );
+}
+
+#[test]
+fn bundle_errors() {
+    let errors = bundle(
+        &[
+            ("/entry.js", "import {missing} from './lib'\nimport 'pkg'"),
+            ("/lib.js", "export let found = 1"),
+        ],
+        "/entry.js",
+    );
+    assert_eq!(
+        errors.unwrap_err(),
+        vec![
+            "Could not resolve \"pkg\"",
+            "No matching export in \"/lib.js\" for import \"missing\"",
+        ]
+    );
+}
+
+#[test]
+fn parse_in_parallel() {
+    let sources: Vec<Source> = ["let a = 1", "let b = ", "let c = 3"]
+        .iter()
+        .enumerate()
+        .map(|(i, contents)| Source {
+            absolute_path: format!("/{}.js", i),
+            pretty_path: format!("{}.js", i),
+            contents: contents.to_string(),
+            ..Source::default()
+        })
+        .collect();
+
+    let log = Log::default();
+    let (files, symbols) = parse_files_parallel(&log, sources, &ParseOptions::default());
+    assert_eq!(log.take_msgs().len(), 1);
+
+    // Each file's symbols are at its own outer index in the merged map
+    assert_eq!(files.len(), 3);
+    let a = files[0].module_scope().members["a"];
+    let c = files[2].module_scope().members["c"];
+    assert_eq!(a.outer, 0);
+    assert_eq!(c.outer, 2);
+    assert_eq!(symbols[a].name, "a");
+    assert_eq!(symbols[c].name, "c");
+    assert!(!files[1].module_scope().members.contains_key("b"));
+}
+
+#[test]
+fn cjs_format() {
+    let files = [
+        (
+            "/entry.js",
+            "import {a as b} from './lib'\n\
+             export * from './lib'\n\
+             export let c = b()\n\
+             export default {b}",
+        ),
+        ("/lib.js", "export let a = () => 1"),
+    ];
+
+    // Imports are read off of the namespace every time so they stay live. The
+    // module was strict, so the output says so.
+    let js = build(
+        &files,
+        "/entry.js",
+        BundleOptions {
+            output_format: OutputFormat::Cjs,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        },
+    );
+    assert_eq!(
+        js.unwrap(),
+        "\"use strict\";Object.defineProperty(exports,\"__esModule\",{value:true});\
+         Object.defineProperty(exports,\"c\",{enumerable:true,get:function(){return c}});\
+         Object.defineProperty(exports,\"default\",{enumerable:true,get:function(){return entry_default}});\
+         var import_lib=require(\"./lib\");\
+         (function(from){for(var key in from)if(key!==\"default\"&&!(key in exports))\
+         Object.defineProperty(exports,key,Object.getOwnPropertyDescriptor(from,key))})(require(\"./lib\"));\
+         let c=(0,import_lib.a)();\
+         var entry_default={b:import_lib.a}"
+    );
+
+    // Files in the bundle are linked as usual and only the entry point exports
+    let js = build(
+        &files,
+        "/entry.js",
+        BundleOptions {
+            bundle: true,
+            output_format: OutputFormat::Cjs,
+            ..BundleOptions::default()
+        },
+    );
+    assert_eq!(
+        js.unwrap(),
+        "\"use strict\";\n\
+         Object.defineProperty(exports, \"__esModule\", {\n  value: true\n});\n\
+         Object.defineProperty(exports, \"a\", {\n  enumerable: true,\n  get: function() {\n    return a;\n  }\n});\n\
+         Object.defineProperty(exports, \"c\", {\n  enumerable: true,\n  get: function() {\n    return c;\n  }\n});\n\
+         Object.defineProperty(exports, \"default\", {\n  enumerable: true,\n  get: function() {\n    return entry_default;\n  }\n});\n\
+         let a = () => 1;\n\
+         let c = a();\n\
+         var entry_default = {\n  b: a\n};\n"
+    );
+
+    // A default import may be of a CommonJS file. Generated names don't take
+    // the names of the file's own symbols.
+    let cjs = |contents| {
+        let options = BundleOptions {
+            output_format: OutputFormat::Cjs,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        };
+        build(&[("/entry.js", contents)], "/entry.js", options).unwrap()
+    };
+    assert_eq!(
+        cjs("import fs from 'fs'; fs.readFileSync()"),
+        "\"use strict\";var __toModule=function(mod){return mod&&mod.__esModule?mod:\
+         Object.create(Object(mod),{default:{value:mod,enumerable:true}})};\
+         var import_fs=__toModule(require(\"fs\"));import_fs.default.readFileSync()"
+    );
+    assert_eq!(
+        cjs("var import_fs = 1; import {readFileSync} from 'fs'; readFileSync(import_fs)"),
+        "\"use strict\";var import_fs=1;var import_fs2=require(\"fs\");\
+         (0,import_fs2.readFileSync)(import_fs)"
+    );
+}
+
+#[test]
+fn typescript_equals() {
+    let files = [
+        ("/entry.ts", "import lib = require('./lib')\nexport = lib.a"),
+        ("/lib.js", "exports.a = 1"),
+    ];
+    let js = build(
+        &files,
+        "/entry.ts",
+        BundleOptions {
+            output_format: OutputFormat::Cjs,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        },
+    );
+    assert_eq!(
+        js.unwrap(),
+        "const lib=require(\"./lib\");module.exports=lib.a"
+    );
+}
+
+#[test]
+fn iife_format() {
+    let files = [
+        ("/entry.js", "import {a} from './lib'\nexport let b = a + 1"),
+        ("/lib.js", "export let a = 1"),
+    ];
+    let options = |global_name: &str| BundleOptions {
+        bundle: true,
+        output_format: OutputFormat::Iife,
+        global_name: global_name.to_owned(),
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        ..BundleOptions::default()
+    };
+
+    // Without a global name, nothing can see the exports
+    let js = build(&files, "/entry.js", options(""));
+    assert_eq!(
+        js.unwrap(),
+        "(function(){\"use strict\";let a=1;let b=a+1})()"
+    );
+
+    let js = build(&files, "/entry.js", options("Lib"));
+    assert_eq!(
+        js.unwrap(),
+        "var Lib=function(){\"use strict\";var exports={};\
+         Object.defineProperty(exports,\"__esModule\",{value:true});\
+         Object.defineProperty(exports,\"b\",{enumerable:true,get:function(){return b}});\
+         let a=1;let b=a+1;return exports}()"
+    );
+}
+
+#[test]
+fn commonjs_interop() {
+    let files = [
+        (
+            "/entry.js",
+            "import add, {version} from './add'\n\
+             const log = require('./log')\n\
+             log(add(1, 2), version)",
+        ),
+        (
+            "/add.js",
+            "module.exports = (a, b) => a + b\nmodule.exports.version = 1",
+        ),
+        ("/log.js", "module.exports = console.log"),
+    ];
+
+    // Each CommonJS file runs the first time it's required. An import sees
+    // "module.exports" as the default export.
+    let js = build(
+        &files,
+        "/entry.js",
+        BundleOptions {
+            bundle: true,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        },
+    );
+    assert_eq!(
+        js.unwrap(),
+        "var __commonJS=function(cb,mod){return function(){\
+         return mod||cb((mod={exports:{}}).exports,mod),mod.exports}};\
+         var __toModule=function(mod){return mod&&mod.__esModule?mod:\
+         Object.create(Object(mod),{default:{value:mod,enumerable:true}})};\
+         var require_add=__commonJS(function(exports,module){\
+         module.exports=(a,b)=>a+b;module.exports.version=1});\
+         var require_log=__commonJS(function(exports,module){module.exports=console.log});\
+         var import_add=__toModule(require_add());\
+         const log=require_log();\
+         log((0,import_add.default)(1,2),import_add.version)"
+    );
+
+    // ESM exports must be known up front, even through another file
+    let errors = bundle(
+        &[
+            ("/entry.js", "export * from './mid'"),
+            ("/mid.js", "export * from './cjs'"),
+            ("/cjs.js", "exports.a = 1"),
+        ],
+        "/entry.js",
+    );
+    assert_eq!(
+        errors.unwrap_err(),
+        vec![
+            "Re-exporting everything from a CommonJS file isn't possible in an ESM entry point \
+             since the names aren't known until it runs"
+        ]
+    );
+}
+
+#[test]
+fn reexports() {
+    // A namespace object has a getter for every export, including the ones
+    // from "export * from". "y" is left out since it's ambiguous.
+    let js = bundle(
+        &[
+            (
+                "/entry.js",
+                "import * as ns from './lib'\n\
+                 import {ns as ns2} from './mid'\n\
+                 console.log(ns, ns2.x)",
+            ),
+            ("/mid.js", "export * as ns from './lib'"),
+            (
+                "/lib.js",
+                "export let x = 1\nexport * from './a'\nexport * from './b'",
+            ),
+            ("/a.js", "export let y = 1, z = 2"),
+            ("/b.js", "export let y = 3\nexport {z} from './a'"),
+        ],
+        "/entry.js",
+    );
+    assert_eq!(
+        js.unwrap(),
+        "let y = 1, z = 2;\n\
+         let y2 = 3;\n\
+         var lib_exports = {\n  get x() {\n    return x;\n  },\n  \
+         get z() {\n    return z;\n  }\n};\n\
+         let x = 1;\n\
+         console.log(lib_exports, lib_exports.x);\n"
+    );
+
+    let errors = bundle(
+        &[
+            ("/entry.js", "import {y} from './lib'"),
+            ("/lib.js", "export * from './a'\nexport * from './b'"),
+            ("/a.js", "export let y = 1"),
+            ("/b.js", "export let y = 3"),
+        ],
+        "/entry.js",
+    );
+    assert_eq!(
+        errors.unwrap_err(),
+        vec!["Ambiguous import \"y\" has multiple matching exports"]
+    );
+
+    // Names re-exported from a CommonJS file are read off of its namespace.
+    // The entry point exports a copy since an export must be a variable.
+    let js = build(
+        &[
+            ("/entry.js", "export {a, ns} from './mid'"),
+            (
+                "/mid.js",
+                "export {a} from './cjs'\nexport * as ns from './cjs'",
+            ),
+            ("/cjs.js", "exports.a = 1"),
+        ],
+        "/entry.js",
+        BundleOptions {
+            bundle: true,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        },
+    );
+    let js = js.unwrap();
+    assert!(js.ends_with(
+        "var require_cjs=__commonJS(function(exports,module){exports.a=1});\
+         var import_cjs=__toModule(require_cjs());\
+         var ns=__toModule(require_cjs());\
+         export{a2 as a,ns};\
+         var a2=import_cjs.a"
+    ));
+
+    // The entry point can do the same itself
+    let js = build(
+        &[
+            (
+                "/entry.js",
+                "export {a} from './cjs'\nexport * as ns from './cjs'",
+            ),
+            ("/cjs.js", "exports.a = 1"),
+        ],
+        "/entry.js",
+        BundleOptions {
+            bundle: true,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        },
+    );
+    assert!(js.unwrap().ends_with(
+        "var import_cjs=__toModule(require_cjs());export{a2 as a};\
+         var ns=__toModule(require_cjs());export{ns};\
+         var a2=import_cjs.a"
+    ));
+
+    // The names of "export * from" a CommonJS file aren't known until it runs.
+    // The names that no ES6 file exports are read off of its exports, which
+    // are copied onto the namespace object too.
+    let reexport_helper = "var __reExport=function(target,from){for(var key in from)\
+                           if(key!==\"default\"&&!(key in target))\
+                           Object.defineProperty(target,key,\
+                           Object.getOwnPropertyDescriptor(from,key))};";
+    let js = build(
+        &[
+            (
+                "/entry.js",
+                "import {a, b} from './mid'\n\
+                 import * as mid from './mid'\n\
+                 console.log(a, b, mid)",
+            ),
+            ("/mid.js", "export * from './cjs'\nexport let b = 2"),
+            ("/cjs.js", "exports.a = 1"),
+        ],
+        "/entry.js",
+        BundleOptions {
+            bundle: true,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        },
+    );
+    let js = js.unwrap();
+    assert!(js.contains(reexport_helper));
+    assert!(js.ends_with(
+        "var require_cjs=__commonJS(function(exports,module){exports.a=1});\
+         var mid_exports={get b(){return b}};\
+         var import_cjs=require_cjs();__reExport(mid_exports,import_cjs);\
+         let b=2;\
+         console.log(import_cjs.a,b,mid_exports)"
+    ));
+
+    // The exports of the other output formats can be copied at run time
+    let js = build(
+        &[
+            ("/entry.js", "export * from './cjs'"),
+            ("/cjs.js", "exports.a = 1"),
+        ],
+        "/entry.js",
+        BundleOptions {
+            bundle: true,
+            output_format: OutputFormat::Cjs,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        },
+    );
+    let js = js.unwrap();
+    assert!(js.starts_with("Object.defineProperty(exports,\"__esModule\",{value:true});"));
+    assert!(js.contains(reexport_helper));
+    assert!(js.ends_with("var import_cjs=require_cjs();__reExport(exports,import_cjs)"));
+}
+
+#[test]
+fn import_cycles() {
+    // Reading "a" in "b" is an error since "a" runs after "b", which is why
+    // the default export stays a "let"
+    let log = Log::default();
+    let output_files = build_with_log(
+        &log,
+        &[
+            ("/entry.js", "import a from './a'\nconsole.log(a)"),
+            ("/a.js", "import b from './b'\nexport default b + 1"),
+            (
+                "/b.js",
+                "import a from './a'\nconsole.log(a)\nexport default 2",
+            ),
+        ],
+        "/entry.js",
+        BundleOptions {
+            bundle: true,
+            ..BundleOptions::default()
+        },
+    );
+    let warnings: Vec<String> = log.take_msgs().into_iter().map(|msg| msg.text).collect();
+    assert_eq!(
+        warnings,
+        ["Import cycle: \"/a.js\" -> \"/b.js\" -> \"/a.js\""]
+    );
+    assert_eq!(
+        output_files[0].1,
+        "console.log(a_default);\n\
+         let b_default = 2;\n\
+         let a_default = b_default + 1;\n\
+         console.log(a_default);\n"
+    );
+
+    // An ES6 file loaded with "require()" runs when it's first required, so
+    // the cycle through it ends when the file is already running
+    let js = build(
+        &[
+            (
+                "/entry.js",
+                "import {b} from './b'\nconsole.log(require('./a').a, b)",
+            ),
+            (
+                "/a.js",
+                "import {b} from './b'\nexport let a = b, [c] = [1]",
+            ),
+            ("/b.js", "export let b = 2"),
+        ],
+        "/entry.js",
+        BundleOptions {
+            bundle: true,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        },
+    );
+    assert_eq!(
+        js.unwrap(),
+        "var __esm=function(fn,res){return function(){return fn&&(res=fn(fn=0)),res}};\
+         var b;var init_b=__esm(function(){b=2});\
+         var a_exports={get a(){return a},get c(){return c}};\
+         var a,c;var init_a=__esm(function(){init_b();a=b,[c]=[1]});\
+         init_b();\
+         console.log((init_a(),a_exports).a,b)"
+    );
+}
+
+#[test]
+fn import_meta() {
+    let files = [("/entry.js", "console.log(import.meta.url)")];
+    let options = |bundle, output_format| BundleOptions {
+        bundle,
+        output_format,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        ..BundleOptions::default()
+    };
+
+    let js = build(&files, "/entry.js", options(false, OutputFormat::Esm));
+    assert_eq!(js.unwrap(), "console.log(import.meta.url)");
+
+    let js = build(&files, "/entry.js", options(false, OutputFormat::Cjs));
+    assert_eq!(
+        js.unwrap(),
+        "var import_meta={url:new URL(\"file:\"+__filename).href};console.log(import_meta.url)"
+    );
+
+    // Every file in a bundle has the same URL
+    let errors = build(&files, "/entry.js", options(true, OutputFormat::Iife));
+    assert_eq!(
+        errors.unwrap_err(),
+        vec!["\"import.meta.url\" is the URL of the bundle instead of this file in the \"iife\" output format"]
+    );
+}
+
+#[test]
+fn asset_loaders() {
+    let files = [
+        (
+            "/src/entry.js",
+            "import a from './a.txt'\nimport b from './b.svg'\nimport c from './c.bin'\n\
+             import d from './d.png'\nimport e from './e.dat'\nconsole.log(a, b, c, d, e)",
+        ),
+        ("/src/a.txt", "it's \"text\""),
+        ("/src/b.svg", "<svg/>"),
+        ("/src/c.bin", "abcd"),
+        ("/src/d.png", "PNG"),
+        ("/src/e.dat", "xy"),
+    ];
+    let mut input = HashMap::new();
+    for (path, contents) in &files {
+        input.insert(PathBuf::from(path), contents.to_string());
+    }
+    let resolver = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+    let log = Log::default();
+    let mut parse_options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    for (ext, loader) in &[
+        (".svg", Loader::DataURL),
+        (".bin", Loader::Base64),
+        (".png", Loader::File),
+        (".dat", Loader::Binary),
+    ] {
+        parse_options.loaders.insert(ext.to_string(), *loader);
+    }
+    let bundle = scan_bundle(
+        &log,
+        &resolver,
+        &["/src/entry.js".to_owned()],
+        &parse_options,
+        None,
+    );
+    let options = BundleOptions {
+        bundle: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        abs_output_dir: "/out".to_owned(),
+        ..BundleOptions::default()
+    };
+    let result = bundle.compile(&log, &options);
+    assert!(log.take_msgs().is_empty());
+
+    assert_eq!(result.output_files.len(), 2);
+    assert_eq!(
+        String::from_utf8(result.output_files[0].contents.clone()).unwrap(),
+        "var a_default=`it's \"text\"`;\
+         var b_default=\"data:image/svg+xml;base64,PHN2Zy8+\";\
+         var c_default=\"YWJjZA==\";\
+         var d_default=\"./d-VBBO4KNN.png\";\
+         var e_default=Uint8Array.from(atob(\"eHk=\"),c=>c.charCodeAt(0));\
+         console.log(a_default,b_default,c_default,d_default,e_default)"
+    );
+
+    // The file loader copies the file to the output directory
+    assert_eq!(result.output_files[1].path, "/out/d-VBBO4KNN.png");
+    assert_eq!(result.output_files[1].contents, b"PNG");
+
+    assert_eq!(base64_encode(b""), "");
+    assert_eq!(base64_encode(b"a"), "YQ==");
+    assert_eq!(base64_encode(b"ab"), "YWI=");
+    assert_eq!(base64_encode(&[0xFB, 0xFF]), "+/8=");
+}
+
+#[test]
+fn code_splitting() {
+    let files = [
+        (
+            "/src/entry.js",
+            "import {a} from './a'\nexport let load = () => import('./page').then((p) => p.b + a)",
+        ),
+        (
+            "/src/page.js",
+            "import {a} from './a'\nexport let b = a + 1",
+        ),
+        ("/src/a.js", "export let a = 1"),
+    ];
+    let options = |output_format| BundleOptions {
+        bundle: true,
+        code_splitting: true,
+        output_format,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        abs_output_dir: "/out".to_owned(),
+        ..BundleOptions::default()
+    };
+
+    // Without splitting the imported file is wrapped in the same bundle
+    let bundled = BundleOptions {
+        bundle: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        ..BundleOptions::default()
+    };
+    assert_eq!(
+        build(&files, "/src/entry.js", bundled).unwrap(),
+        "var __esm=function(fn,res){return function(){return fn&&(res=fn(fn=0)),res}};\
+         var a;var init_a=__esm(function(){a=1});\
+         var page_exports={get b(){return b}};\
+         var b;var init_page=__esm(function(){init_a();b=a+1});\
+         init_a();\
+         export let load=()=>Promise.resolve().then(function(){return init_page(),page_exports})\
+         .then(p=>p.b+a)"
+    );
+
+    // Code that both chunks need is only defined once in a common chunk
+    let output = build_files(&files, "/src/entry.js", options(OutputFormat::Esm)).unwrap();
+    assert_eq!(
+        output,
+        [
+            (
+                "/out/entry.js".to_owned(),
+                "import{a}from\"./chunk-H52UOYGC.js\";\
+                 export let load=()=>import(\"./page-PL3FQWBJ.js\").then(p=>p.b+a)"
+                    .to_owned()
+            ),
+            (
+                "/out/page-PL3FQWBJ.js".to_owned(),
+                "import{a}from\"./chunk-H52UOYGC.js\";export let b=a+1".to_owned()
+            ),
+            (
+                "/out/chunk-H52UOYGC.js".to_owned(),
+                "let a=1;export{a}".to_owned()
+            ),
+        ]
+    );
+
+    // IIFE chunks are loaded through the registry that the entry point installs
+    let output = build_files(&files, "/src/entry.js", options(OutputFormat::Iife)).unwrap();
+    assert_eq!(output.len(), 2);
+    assert_eq!(output[1].0, "/out/page-WTWPU3TR.js");
+    assert!(output[0]
+        .1
+        .contains("r.import = r.import || function(chunk)"));
+    assert!(output[0].1.ends_with(
+        "(function(){\"use strict\";let a=1;let load=()=>__esbuild_chunks.import(\"page-WTWPU3TR.js\").then(p=>p.b+a)})()"
+    ));
+    assert_eq!(
+        output[1].1,
+        "__esbuild_chunks.define({\"page-WTWPU3TR.js\": function(module, exports, require) {\n\
+         \"use strict\";Object.defineProperty(exports,\"__esModule\",{value:true});\
+         Object.defineProperty(exports,\"b\",{enumerable:true,get:function(){return b}});\
+         let a=1;let b=a+1}});\n"
+    );
+}
+
+#[test]
+fn output_names() {
+    let files = |page: &str| {
+        vec![
+            (
+                "/src/entry.js",
+                "export let load = () => import('./lib/page')",
+            ),
+            ("/src/lib/page.js", page),
+        ]
+        .into_iter()
+        .map(|(path, contents)| (path, contents.to_owned()))
+        .collect::<Vec<_>>()
+    };
+    let build_names = |page: &str, entry_names: &str, chunk_names: &str| {
+        let files = files(page);
+        let files: Vec<(&str, &str)> = files.iter().map(|(p, c)| (*p, c.as_str())).collect();
+        let options = BundleOptions {
+            bundle: true,
+            code_splitting: true,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            abs_output_dir: "/out".to_owned(),
+            entry_names: entry_names.to_owned(),
+            chunk_names: chunk_names.to_owned(),
+            ..BundleOptions::default()
+        };
+        build_files(&files, "/src/entry.js", options)
+    };
+
+    let output = build_names(
+        "export let a = 1",
+        "[name]-[hash]",
+        "chunks/[dir]/[name]-[hash]",
+    )
+    .unwrap();
+    assert_eq!(
+        output,
+        [
+            (
+                "/out/entry-4Z55SKT5.js".to_owned(),
+                "export let load=()=>import(\"./chunks/lib/page-SPWI2U7S.js\")".to_owned()
+            ),
+            (
+                "/out/chunks/lib/page-SPWI2U7S.js".to_owned(),
+                "export let a=1".to_owned()
+            ),
+        ]
+    );
+
+    // The hash of a file changes when a file it loads changes, even though
+    // its own contents only differ in the name of that file
+    let changed = build_names(
+        "export let a = 2",
+        "[name]-[hash]",
+        "chunks/[dir]/[name]-[hash]",
+    )
+    .unwrap();
+    assert_ne!(changed[0].0, output[0].0);
+    assert_ne!(changed[1].0, output[1].0);
+
+    assert_eq!(
+        build_names("export let a = 1", "[name]", "entry").unwrap_err(),
+        ["Two output files share the same path: /out/entry.js"]
+    );
+    assert_eq!(
+        BundleOptions {
+            asset_names: "[name].[ext]".to_owned(),
+            ..BundleOptions::default()
+        }
+        .validate()
+        .unwrap_err()
+        .to_string(),
+        "Invalid placeholder [ext] in the name template [name].[ext] \
+         (only [name], [dir] and [hash] are supported)"
+    );
+    assert_eq!(
+        BundleOptions {
+            source_map: true,
+            ..BundleOptions::default()
+        }
+        .validate()
+        .unwrap_err()
+        .to_string(),
+        "Source maps aren't supported yet"
+    );
+}
+
+#[test]
+fn tsconfig_options() {
+    let files = [
+        (
+            "/src/entry.js",
+            "import {b} from '@/lib/b'\nexport let f = (a) => a ** b",
+        ),
+        ("/src/lib/b.js", "export let b = 2"),
+        (
+            "/src/tsconfig.json",
+            "{\"compilerOptions\": {\"target\": \"ES2015\", \"paths\": {\"@/*\": [\"./*\"]}}}",
+        ),
+    ];
+    let options = BundleOptions {
+        bundle: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        ..BundleOptions::default()
+    };
+    assert_eq!(
+        build(&files, "/src/entry.js", options.clone()).unwrap(),
+        "let b=2;export let f=a=>Math.pow(a,b)"
+    );
+
+    // Compiler options that only matter for TypeScript apply to ".ts" files.
+    // The import of a type is kept for its side effects here.
+    let files = [
+        ("/src/entry.ts", "import {T} from './types'\nlet x: T"),
+        ("/src/types.ts", "console.log(1)\nexport interface T {}"),
+        (
+            "/src/tsconfig.json",
+            "{\"compilerOptions\": {\"importsNotUsedAsValues\": \"preserve\"}}",
+        ),
+    ];
+    assert_eq!(
+        build(&files, "/src/entry.ts", options.clone()).unwrap(),
+        "console.log(1)"
+    );
+    assert_eq!(build(&files[..2], "/src/entry.ts", options).unwrap(), "");
+}
+
+#[test]
+fn disabled_modules() {
+    let files = [
+        ("/src/entry.js", "import 'pkg'"),
+        (
+            "/node_modules/pkg/package.json",
+            r#"{"browser": {"fs": false}}"#,
+        ),
+        ("/node_modules/pkg/index.js", "import 'fs'\nconsole.log(1)"),
+    ];
+    let options = BundleOptions {
+        bundle: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        ..BundleOptions::default()
+    };
+    assert_eq!(
+        build(&files, "/src/entry.js", options).unwrap(),
+        "console.log(1)"
+    );
+}
+
+#[test]
+fn hmr() {
+    let files = [
+        (
+            "/src/entry.js",
+            "import {a} from './lib/a'\nexport let b = a + 1\n\
+             import('./lazy').then(x => console.log(x.l, b))",
+        ),
+        ("/src/lib/a.js", "export let a = 1"),
+        ("/src/lazy.js", "module.exports = {l: 2}"),
+    ];
+    let options = BundleOptions {
+        bundle: true,
+        hmr: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        ..BundleOptions::default()
+    };
+    let js = build(&files, "/src/entry.js", options.clone()).unwrap();
+
+    // Every module is registered under its path relative to the outbase, and
+    // imports of other modules go through the registry
+    assert!(js.starts_with("var __hmr = (function() {"));
+    assert!(js.contains("r = g[\"__esbuild_hmr\"] = {factories: {}, cache: {}};"));
+    assert!(js.ends_with(
+        "__hmr.register(\"entry.js\", function(module, exports, require) {\n  \
+         \"use strict\";Object.defineProperty(exports,\"__esModule\",{value:true});\
+         Object.defineProperty(exports,\"b\",{enumerable:true,get:function(){return b}});\
+         var __toModule=function(mod){return mod&&mod.__esModule?mod:\
+         Object.create(Object(mod),{default:{value:mod,enumerable:true}})};\
+         var import_a=require(\"lib/a.js\");let b=import_a.a+1;\
+         Promise.resolve().then(function(){return __toModule(require(\"lazy.js\"))})\
+         .then(x=>console.log(x.l,b))\n});\n\
+         __hmr.register(\"lib/a.js\", function(module, exports, require) {\n  \
+         \"use strict\";Object.defineProperty(exports,\"__esModule\",{value:true});\
+         Object.defineProperty(exports,\"a\",{enumerable:true,get:function(){return a}});\
+         let a=1\n});\n\
+         __hmr.register(\"lazy.js\", function(module, exports, require) {\n  \
+         module.exports={l:2}\n});\n\
+         __hmr.require(\"entry.js\");\n"
+    ));
+
+    // A module may be a CommonJS file, which an import sees as the default
+    // export
+    let files = [
+        (
+            "/src/entry.js",
+            "import cjs, {named} from './c'\nconsole.log(cjs, named)",
+        ),
+        ("/src/c.js", "exports.named = 1"),
+    ];
+    let js = build(&files, "/src/entry.js", options).unwrap();
+    assert!(js.contains(
+        "var import_c=__toModule(require(\"c.js\"));\
+         console.log(import_c.default,import_c.named)"
+    ));
+
+    let options = BundleOptions {
+        hmr: true,
+        ..BundleOptions::default()
+    };
+    assert_eq!(
+        options.validate().unwrap_err().to_string(),
+        "HMR mode currently only works when bundling"
+    );
+}
+
+#[test]
+fn shared_trees() {
+    let mut input = HashMap::new();
+    input.insert(
+        PathBuf::from("/src/entry.js"),
+        "import {a} from './a'\nif (a) console.log(require('./b'))".to_owned(),
+    );
+    input.insert(PathBuf::from("/src/a.js"), "export let a = 1".to_owned());
+    input.insert(PathBuf::from("/src/b.js"), "module.exports = 2".to_owned());
+    let resolver = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+    let log = Log::default();
+    let parse_options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    let cache = Mutex::new(AstCache::default());
+    let scan = || {
+        scan_bundle(
+            &log,
+            &resolver,
+            &["/src/entry.js".to_owned()],
+            &parse_options,
+            Some(&cache),
+        )
+    };
+    let options = BundleOptions {
+        bundle: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            syntax: true,
+            ..MinifyOptions::default()
+        },
+        ..BundleOptions::default()
+    };
+    let first = scan();
+    let first_js = first.compile(&log, &options).output_files[0]
+        .contents
+        .clone();
+
+    // The second build gets the same trees, which linking didn't change
+    let second = scan();
+    assert!(log.take_msgs().is_empty());
+    assert_eq!(first.files.len(), second.files.len());
+    for (a, b) in first.files.iter().zip(&second.files).skip(1) {
+        assert!(Arc::ptr_eq(a, b));
+    }
+    let second_js = second.compile(&log, &options).output_files[0]
+        .contents
+        .clone();
+    assert_eq!(first_js, second_js);
+}
+
+#[test]
+fn external_imports() {
+    let mut input = HashMap::new();
+    input.insert(
+        PathBuf::from("/src/entry.js"),
+        "import {a} from 'pkg/a'\nimport {b} from 'node:b'\nexport let c = a + b".to_owned(),
+    );
+    let resolve_options = ResolveOptions {
+        external: vec!["pkg".to_owned(), "node:*".to_owned()],
+        ..ResolveOptions::default()
+    };
+    let resolver = Resolver::new(MockFileSystem::new(input), resolve_options);
+    let log = Log::default();
+    let parse_options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    let bundle = scan_bundle(
+        &log,
+        &resolver,
+        &["/src/entry.js".to_owned()],
+        &parse_options,
+        None,
+    );
+    assert!(log.take_msgs().is_empty());
+
+    let graph = bundle.module_graph();
+    let externals: Vec<&str> = graph
+        .external_dependencies(1)
+        .iter()
+        .map(|dependency| dependency.path.as_str())
+        .collect();
+    assert_eq!(externals, ["pkg/a", "node:b"]);
+
+    let compile = |output_format| {
+        let options = BundleOptions {
+            bundle: true,
+            output_format,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..BundleOptions::default()
+        };
+        let result = bundle.compile(&log, &options);
+        String::from_utf8(result.output_files[0].contents.clone()).unwrap()
+    };
+    assert_eq!(
+        compile(OutputFormat::Esm),
+        "import{a}from\"pkg/a\";import{b}from\"node:b\";export let c=a+b"
+    );
+    assert!(compile(OutputFormat::Cjs).ends_with(
+        "var import_a=require(\"pkg/a\");var import_node_b=require(\"node:b\");\
+         let c=import_a.a+import_node_b.b"
+    ));
+}
+
+#[test]
+fn rewrite_external_paths() {
+    let mut input = HashMap::new();
+    input.insert(
+        PathBuf::from("/src/entry.js"),
+        "import React from 'react'\nimport {jsx} from 'react/jsx-runtime'\n\
+         import {b} from './b'\nexport * from 'reactive'\n\
+         export let load = () => import('react-dom')\nconsole.log(React, jsx, b, require('./c'))"
+            .to_owned(),
+    );
+    input.insert(PathBuf::from("/src/b.js"), "export let b = 1".to_owned());
+    input.insert(
+        PathBuf::from("/src/c.js"),
+        "module.exports = require('react')".to_owned(),
+    );
+    let resolve_options = ResolveOptions {
+        external: vec!["react*".to_owned()],
+        ..ResolveOptions::default()
+    };
+    let resolver = Resolver::new(MockFileSystem::new(input), resolve_options);
+    let log = Log::default();
+    let parse_options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    let bundle = scan_bundle(
+        &log,
+        &resolver,
+        &["/src/entry.js".to_owned()],
+        &parse_options,
+        None,
+    );
+    assert!(log.take_msgs().is_empty());
+
+    // "." would match "./b" and "./c", but those are in the bundle
+    let rewriter = ImportPathRewriter::with_prefixes(vec![
+        ("react".to_owned(), "https://esm.sh/react@18".to_owned()),
+        ("react/jsx-runtime".to_owned(), "/jsx.js".to_owned()),
+        (
+            "react-dom".to_owned(),
+            "https://esm.sh/react-dom@18".to_owned(),
+        ),
+        (".".to_owned(), "/nowhere".to_owned()),
+    ]);
+    let options = BundleOptions {
+        bundle: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        rewrite_external_paths: Some(rewriter),
+        ..BundleOptions::default()
+    };
+    let result = bundle.compile(&log, &options);
+    assert!(log.take_msgs().is_empty());
+    let js = String::from_utf8(result.output_files[0].contents.clone()).unwrap();
+    assert!(js.contains("import React from\"https://esm.sh/react@18\";"));
+    assert!(js.contains("import{jsx}from\"/jsx.js\";"));
+    assert!(js.contains("export*from\"reactive\";"));
+    assert!(js.contains("import(\"https://esm.sh/react-dom@18\")"));
+    assert!(js.contains("module.exports=require(\"https://esm.sh/react@18\")"));
+    assert!(!js.contains("nowhere"));
+
+    // Without bundling every import path is external
+    let options = BundleOptions {
+        rewrite_external_paths: Some(ImportPathRewriter::with_prefixes(vec![(
+            ".".to_owned(),
+            "https://example.com".to_owned(),
+        )])),
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        ..BundleOptions::default()
+    };
+    assert_eq!(
+        build(
+            &[(
+                "/entry.js",
+                "import {a} from './a'\nexport * from 'a'\nimport('./b')"
+            )],
+            "/entry.js",
+            options
+        )
+        .unwrap(),
+        "import{a}from\"https://example.com/a\";export*from\"a\";import(\"https://example.com/b\")"
+    );
+}
+
+#[test]
+fn legal_comments() {
+    let files = [
+        (
+            "/entry.js",
+            "/*! entry */ import {a} from './lib'\n// @license MIT\nconsole.log(a)",
+        ),
+        (
+            "/lib.js",
+            "/*! lib */\n/* ordinary */ export let a = 1 /*! lib */",
+        ),
+    ];
+    let build_legal = |legal_comments| {
+        let options = BundleOptions {
+            bundle: true,
+            abs_output_dir: "/out".to_owned(),
+            legal_comments,
+            ..BundleOptions::default()
+        };
+        build_files(&files, "/entry.js", options).unwrap()
+    };
+
+    assert_eq!(
+        build_legal(LegalComments::Inline)[0].1,
+        "/*! lib */\nlet a = 1;\n/*! lib */\n/*! entry */\n// @license MIT\nconsole.log(a);\n"
+    );
+    assert_eq!(
+        build_legal(LegalComments::None)[0].1,
+        "let a = 1;\nconsole.log(a);\n"
+    );
+
+    // Both of these leave out duplicates
+    assert_eq!(
+        build_legal(LegalComments::EndOfFile)[0].1,
+        "let a = 1;\nconsole.log(a);\n/*! lib */\n/*! entry */\n// @license MIT\n"
+    );
+    assert_eq!(
+        build_legal(LegalComments::External),
+        [
+            (
+                "/out/entry.js".to_owned(),
+                "let a = 1;\nconsole.log(a);\n".to_owned()
+            ),
+            (
+                "/out/entry.js.LEGAL.txt".to_owned(),
+                "/*! lib */\n/*! entry */\n// @license MIT\n".to_owned()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn metafile() {
+    let files = [
+        ("/src/entry.js", "import {a} from './a'\nconsole.log(a)"),
+        ("/src/a.js", "export let a = 1\nexport let unused = 2"),
+    ];
+    let mut input = HashMap::new();
+    for (path, contents) in &files {
+        input.insert(PathBuf::from(path), contents.to_string());
+    }
+    let resolver = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+    let log = Log::default();
+    let parse_options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    let bundle = scan_bundle(
+        &log,
+        &resolver,
+        &["/src/entry.js".to_owned()],
+        &parse_options,
+        None,
+    );
+    let options = BundleOptions {
+        bundle: true,
+        metafile: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        abs_output_dir: "/out".to_owned(),
+        ..BundleOptions::default()
+    };
+    let result = bundle.compile(&log, &options);
+    assert!(log.take_msgs().is_empty());
+
+    // The unused export isn't part of what "a.js" contributes
+    assert_eq!(
+        bundle.metafile(&result, "/"),
+        r#"{
+  "inputs": {
+    "/src/entry.js": {
+      "bytes": 36,
+      "imports": [
+        {
+          "path": "/src/a.js",
+          "kind": "import-statement"
+        }
+      ]
+    },
+    "/src/a.js": {
+      "bytes": 38,
+      "imports": []
+    }
+  },
+  "outputs": {
+    "out/entry.js": {
+      "bytes": 22,
+      "inputs": {
+        "/src/a.js": {
+          "bytesInOutput": 7
+        },
+        "/src/entry.js": {
+          "bytesInOutput": 14
+        }
+      },
+      "entryPoint": "/src/entry.js"
+    }
+  }
+}
+"#
+    );
+
+    // The loader that IIFE chunks need is part of the entry point
+    let mut input = HashMap::new();
+    input.insert(
+        PathBuf::from("/src/entry.js"),
+        "import('./page')".to_owned(),
+    );
+    input.insert(PathBuf::from("/src/page.js"), "export let a = 1".to_owned());
+    let resolver = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+    let bundle = scan_bundle(
+        &log,
+        &resolver,
+        &["/src/entry.js".to_owned()],
+        &parse_options,
+        None,
+    );
+    let options = BundleOptions {
+        output_format: OutputFormat::Iife,
+        code_splitting: true,
+        ..options
+    };
+    let result = bundle.compile(&log, &options);
+    assert!(log.take_msgs().is_empty());
+    let loader = ChunkLoader::iife(&options.chunk_registry, &options.public_path);
+    let metafile = bundle.metafile(&result, "/");
+    assert!(
+        metafile.contains(&format!(
+            "\"<runtime>\": {{\n          \"bytesInOutput\": {}\n        }}",
+            loader.len()
+        )),
+        "{}",
+        metafile
+    );
+}
+
+#[test]
+fn side_effects() {
+    let js = bundle(
+        &[
+            (
+                "/src/entry.js",
+                "import './pkg/unused'\n\
+                 import {used} from './pkg/used'\n\
+                 import './pkg/polyfill'\n\
+                 let a = /* @__PURE__ */ make()\n\
+                 let b = /* @__PURE__ */ make(sideEffect())\n\
+                 console.log(used)",
+            ),
+            (
+                "/src/pkg/package.json",
+                "{\"sideEffects\": [\"polyfill.js\"]}",
+            ),
+            ("/src/pkg/unused.js", "console.log('unused')"),
+            (
+                "/src/pkg/used.js",
+                "console.log('used'); export let used = 1",
+            ),
+            ("/src/pkg/polyfill.js", "console.log('polyfill')"),
+        ],
+        "/src/entry.js",
+    );
+
+    // Unused pure calls are dropped unless an argument has side effects, and
+    // so are imported files without side effects unless something they
+    // export is used
+    assert_eq!(
+        js.unwrap(),
+        "console.log(\"used\");\n\
+         let used = 1;\n\
+         console.log(\"polyfill\");\n\
+         let b = /* @__PURE__ */ make(sideEffect());\n\
+         console.log(used);\n"
+    );
+}
+
+#[test]
+fn extension_hooks() {
+    let files = [
+        (
+            "/src/entry.js",
+            "import App from './App.vue'\nimport './theme.vue'\nconsole.log(App)",
+        ),
+        (
+            "/src/App.vue",
+            "<template><p/></template>\n<script>export default {name: <b/>}</script>",
+        ),
+        ("/src/theme.vue", "<style>a { color: red }</style>"),
+        ("/src/bad.vue", "<template/>"),
+    ];
+    let mut input = HashMap::new();
+    for (path, contents) in &files {
+        input.insert(PathBuf::from(path), contents.to_string());
+    }
+    let resolver = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+    let mut parse_options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+
+    // The script of a component is JSX and its style is CSS
+    parse_options
+        .extension_hooks
+        .register(".vue", |path, contents| {
+            let block = |tag: &str| {
+                let start = contents.find(&format!("<{}>", tag))? + tag.len() + 2;
+                let end = contents.find(&format!("</{}>", tag))?;
+                Some(contents[start..end].to_owned())
+            };
+            if let Some(script) = block("script") {
+                Ok(HookOutput::new(script, Loader::JSX))
+            } else if let Some(style) = block("style") {
+                Ok(HookOutput::new(style, Loader::CSS))
+            } else {
+                Err(format!("{} has no script or style", path))
+            }
+        });
+    let scan = |entry_path: &str, log: &Log| {
+        scan_bundle(
+            log,
+            &resolver,
+            &[entry_path.to_owned()],
+            &parse_options,
+            None,
+        )
+    };
+
+    let log = Log::default();
+    let bundle = scan("/src/entry.js", &log);
+    let options = BundleOptions {
+        bundle: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        abs_output_dir: "/out".to_owned(),
+        ..BundleOptions::default()
+    };
+    let result = bundle.compile(&log, &options);
+    assert!(log.take_msgs().is_empty());
+    let outputs: Vec<(&str, String)> = result
+        .output_files
+        .iter()
+        .map(|file| {
+            let contents = String::from_utf8(file.contents.clone()).unwrap();
+            (file.path.as_str(), contents)
+        })
+        .collect();
+    assert_eq!(
+        outputs,
+        [
+            (
+                "/out/entry.js",
+                "var App_default={name:/*@__PURE__*/React.createElement(\"b\",null)};\
+                 console.log(App_default)"
+                    .to_owned()
+            ),
+            ("/out/entry.css", "a{color:red}".to_owned()),
+        ]
+    );
+
+    let log = Log::default();
+    scan("/src/bad.vue", &log);
+    let errors: Vec<String> = log.take_msgs().into_iter().map(|msg| msg.text).collect();
+    assert_eq!(
+        errors,
+        ["Could not load /src/bad.vue: /src/bad.vue has no script or style"]
+    );
+}
@@ -0,0 +1,62 @@
+use esbuild_rs::ast::AST;
+use esbuild_rs::bundler::{print_file, BundleOptions};
+use esbuild_rs::cache::{decode_ast, encode_ast, DiskCache, ParseCache};
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::{self, ParseOptions};
+use std::sync::Arc;
+
+fn source(contents: &str) -> Source {
+    Source {
+        index: 1,
+        is_stdin: false,
+        absolute_path: "/entry.js".to_owned(),
+        pretty_path: "entry.js".to_owned(),
+        contents: contents.to_owned(),
+        ..Source::default()
+    }
+}
+
+// The trees can't be compared directly since they contain hash maps
+fn print(ast: &AST) -> String {
+    print_file(ast.clone(), &BundleOptions::default())
+}
+
+#[test]
+fn encode_and_decode() {
+    let source = source("import {a} from './a'; export let b = a + 1");
+    let ast = parser::parse(&Log::default(), &source, &ParseOptions::default()).unwrap();
+    let bytes = encode_ast(&ast);
+    let decoded = decode_ast(&bytes).unwrap();
+    assert_eq!(print(&decoded), print(&ast));
+
+    // Corrupted and truncated entries are misses
+    let mut corrupted = bytes.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    assert!(decode_ast(&corrupted).is_none());
+    assert!(decode_ast(&bytes[..bytes.len() / 2]).is_none());
+}
+
+#[test]
+fn disk_cache() {
+    let dir = std::env::temp_dir().join(format!("esbd-cache-test-{}", std::process::id()));
+    let cache = DiskCache::new(&dir);
+    let options = ParseOptions::default();
+    let source = source("let a = 1");
+    assert!(cache.load(&source, &options).is_none());
+
+    let ast = Arc::new(parser::parse(&Log::default(), &source, &options).unwrap());
+    cache.store(&source, &options, &ast);
+    let loaded = cache.load(&source, &options).unwrap();
+    assert_eq!(print(&loaded), print(&ast));
+
+    // Anything the tree depends on is part of the key
+    let changed = self::source("let a = 2");
+    assert!(cache.load(&changed, &options).is_none());
+    let bundling = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    assert!(cache.load(&source, &bundling).is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,190 @@
+use esbuild_rs::bundler::{scan_bundle, BundleOptions, Loader};
+use esbuild_rs::fs::MockFileSystem;
+use esbuild_rs::logging::Log;
+use esbuild_rs::parser::ParseOptions;
+use esbuild_rs::resolver::{ResolveOptions, Resolver};
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn minify(contents: &str) -> String {
+    let result = transform(
+        contents,
+        TransformOptions {
+            loader: Loader::CSS,
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            ..TransformOptions::default()
+        },
+    );
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    result.code
+}
+
+#[test]
+fn minify_whitespace() {
+    assert_eq!(
+        minify("a , b > c { color : red ! important ; margin: 0  auto; }"),
+        "a,b>c{color:red!important;margin:0 auto}"
+    );
+
+    // Whitespace that changes the meaning is kept
+    assert_eq!(
+        minify("a :hover, a/**/b { margin: calc(1px + 2px) -1px }"),
+        "a :hover,a b{margin:calc(1px + 2px) -1px}"
+    );
+    assert_eq!(
+        minify("@media (min-width: 1px) and (max-width: 2px) { .a {} .b { c: d } }"),
+        "@media (min-width:1px) and (max-width:2px){.b{c:d}}"
+    );
+    assert_eq!(
+        minify("@import url( 'a.css' ) print; @font-face { src: url(a.woff) }"),
+        "@import\"a.css\" print;@font-face{src:url(a.woff)}"
+    );
+}
+
+#[test]
+fn bundle_css() {
+    let files = [
+        ("/src/entry.js", "import './a.css'; import './b.js'"),
+        ("/src/b.js", "import './b.css'"),
+        (
+            "/src/a.css",
+            "@import 'https://example.com/x.css';\n\
+             @import 'shared.css';\n\
+             @import './print.css' print;\n\
+             .a { color: red }",
+        ),
+        ("/src/b.css", "@import './shared.css'; .b { color: blue }"),
+        ("/src/shared.css", "body { margin: 0 }"),
+        ("/src/print.css", ".p { display: none }"),
+    ];
+    let mut input = HashMap::new();
+    for (path, contents) in &files {
+        input.insert(PathBuf::from(path), contents.to_string());
+    }
+    let resolver = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+    let log = Log::default();
+    let parse_options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    let bundle = scan_bundle(
+        &log,
+        &resolver,
+        &["/src/entry.js".to_owned()],
+        &parse_options,
+        None,
+    );
+    let options = BundleOptions {
+        bundle: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        abs_output_dir: "/out".to_owned(),
+        ..BundleOptions::default()
+    };
+    let result = bundle.compile(&log, &options);
+    assert!(log.take_msgs().is_empty());
+
+    let output: Vec<(&str, String)> = result
+        .output_files
+        .iter()
+        .map(|file| {
+            let contents = String::from_utf8(file.contents.clone()).unwrap();
+            (file.path.as_str(), contents)
+        })
+        .collect();
+    assert_eq!(output.len(), 2);
+    assert_eq!(output[0].0, "/out/entry.js");
+    assert_eq!(output[1].0, "/out/entry.css");
+    assert_eq!(
+        output[1].1,
+        "@import\"https://example.com/x.css\";body{margin:0}@media print{.p{display:none}}\
+         .a{color:red}.b{color:blue}"
+    );
+}
+
+#[test]
+fn css_modules() {
+    let files = [
+        (
+            "/src/entry.js",
+            "import styles from './a.module.css';\n\
+             import all from './b.module.css';\n\
+             console.log(styles.title, all)",
+        ),
+        (
+            "/src/a.module.css",
+            ".title, .unused { color: red }\n\
+             .unused:hover, :not(.unused) { margin: 0 }\n\
+             :global(.page) .title, :global .app :local(#main) { padding: 0 }\n\
+             @media print { .unused { display: none } }",
+        ),
+        ("/src/b.module.css", ".a-b { color: blue }"),
+    ];
+    let mut input = HashMap::new();
+    for (path, contents) in &files {
+        input.insert(PathBuf::from(path), contents.to_string());
+    }
+    let resolver = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+    let log = Log::default();
+    let parse_options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    let bundle = scan_bundle(
+        &log,
+        &resolver,
+        &["/src/entry.js".to_owned()],
+        &parse_options,
+        None,
+    );
+    let options = BundleOptions {
+        bundle: true,
+        minify: MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        },
+        abs_output_dir: "/out".to_owned(),
+        ..BundleOptions::default()
+    };
+    let result = bundle.compile(&log, &options);
+    assert!(log.take_msgs().is_empty());
+
+    // Every name ends in the same hash of the path of its file
+    let local = |name: &str, source_index: usize| {
+        bundle.local_names[source_index]
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, local)| local.clone())
+            .unwrap()
+    };
+    let title = local("title", 2);
+    let main = local("main", 2);
+    let a_b = local("a-b", 3);
+    assert!(title.starts_with("title_") && main.starts_with("main_"));
+    assert_eq!(title[6..], main[5..]);
+    assert_ne!(title[6..], a_b[4..]);
+
+    // Only the names that are used are left in the code and the stylesheet.
+    // Selectors inside ":not()" don't need the name to match.
+    let js = String::from_utf8(result.output_files[0].contents.clone()).unwrap();
+    let css = String::from_utf8(result.output_files[1].contents.clone()).unwrap();
+    assert!(js.contains(&format!("\"{}\"", title)));
+    assert!(js.contains("\"a-b\":"));
+    assert!(!js.contains("unused"));
+    assert_eq!(
+        css,
+        format!(
+            ".{title}{{color:red}}:not(.{unused}){{margin:0}}\
+             .page .{title}{{padding:0}}.{a_b}{{color:blue}}",
+            title = title,
+            unused = local("unused", 2),
+            a_b = a_b,
+        )
+    );
+}
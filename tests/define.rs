@@ -0,0 +1,69 @@
+use esbuild_rs::parser::parse_define;
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+
+fn define(contents: &str, defines: &[&str]) -> String {
+    let result = transform(
+        contents,
+        TransformOptions {
+            minify: MinifyOptions {
+                whitespace: true,
+                syntax: false,
+                identifiers: false,
+            },
+            defines: defines.iter().map(|d| parse_define(d).unwrap()).collect(),
+            ..TransformOptions::default()
+        },
+    );
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    result.code
+}
+
+#[test]
+fn define_values() {
+    let defines = [
+        "process.env.NODE_ENV=\"production\"",
+        "DEBUG=false",
+        "global=window",
+        "VERSION=2",
+    ];
+    assert_eq!(
+        define(
+            "x = [process.env.NODE_ENV, DEBUG, global.a, VERSION]",
+            &defines
+        ),
+        "x=[\"production\",false,window.a,2]"
+    );
+
+    // Declared names and assignment targets are left alone
+    assert_eq!(
+        define("let DEBUG = 1; x = DEBUG", &defines),
+        "let DEBUG=1;x=DEBUG"
+    );
+    assert_eq!(define("global = 1", &defines), "global=1");
+}
+
+#[test]
+fn define_dead_branches() {
+    let defines = ["process.env.NODE_ENV=\"production\"", "DEBUG=false"];
+    assert_eq!(
+        define(
+            "if (process.env.NODE_ENV !== 'production') check(); else run()",
+            &defines
+        ),
+        "run()"
+    );
+    assert_eq!(
+        define("if (DEBUG) log(); x = DEBUG && a()", &defines),
+        ";x=false"
+    );
+
+    // A "var" in a dead branch is still declared
+    assert_eq!(define("if (DEBUG) { var a }", &defines), "if(false){var a}");
+}
+
+#[test]
+fn define_errors() {
+    assert!(parse_define("a").is_err());
+    assert!(parse_define("a.1=b").is_err());
+    assert!(parse_define("a=1+2").is_err());
+}
@@ -0,0 +1,73 @@
+use esbuild_rs::parser::{DropConsole, DropOptions};
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+
+fn drop(contents: &str, drop: DropOptions) -> String {
+    let result = transform(
+        contents,
+        TransformOptions {
+            minify: MinifyOptions {
+                whitespace: true,
+                ..MinifyOptions::default()
+            },
+            drop,
+            ..TransformOptions::default()
+        },
+    );
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    result.code
+}
+
+#[test]
+fn console() {
+    let all = || DropOptions {
+        console: DropConsole::All,
+        ..DropOptions::default()
+    };
+    assert_eq!(drop("console.log(f(), 1); g()", all()), "g()");
+    assert_eq!(drop("console['warn'](1)", all()), "");
+    assert_eq!(drop("x = (0, console.log(f()))", all()), "x=(0,void 0)");
+
+    let keep_side_effects = DropOptions {
+        console: DropConsole::KeepSideEffects,
+        ..DropOptions::default()
+    };
+    assert_eq!(
+        drop("console.log(f(), 1); g()", keep_side_effects),
+        "f();g()"
+    );
+
+    // Only calls on the global are removed
+    assert_eq!(
+        drop("let console = {}; console.log(1)", all()),
+        "let console={};console.log(1)"
+    );
+    assert_eq!(
+        drop("console.log.call(f())", all()),
+        "console.log.call(f())"
+    );
+}
+
+#[test]
+fn debugger_and_labels() {
+    let options = || DropOptions {
+        debugger: true,
+        labels: vec!["DEV".to_owned()],
+        ..DropOptions::default()
+    };
+    assert_eq!(
+        drop("debugger; DEV: { f() } PROD: g()", options()),
+        "PROD:g()"
+    );
+    assert_eq!(
+        drop("function f() { debugger; DEV: return }", options()),
+        "function f(){}"
+    );
+    assert_eq!(
+        drop("if (x) debugger; else DEV: f()", options()),
+        "if(x);else;"
+    );
+    assert_eq!(
+        drop("debugger; DEV: f()", DropOptions::default()),
+        "debugger;DEV:f()"
+    );
+}
@@ -0,0 +1,58 @@
+use esbuild_rs::ast::ImportKind;
+use esbuild_rs::error::Error;
+use esbuild_rs::fs::MockFileSystem;
+use esbuild_rs::lexer::try_tokens;
+use esbuild_rs::logging::Source;
+use esbuild_rs::parser::{parse_define, try_parse, ParseOptions};
+use esbuild_rs::resolver::{ResolveOptions, Resolver};
+use esbuild_rs::tables::Token;
+use std::collections::HashMap;
+use std::error::Error as _;
+use std::io;
+use std::path::PathBuf;
+
+fn source(contents: &str) -> Source {
+    Source {
+        index: 1,
+        pretty_path: "a.js".to_owned(),
+        contents: contents.to_owned(),
+        ..Source::default()
+    }
+}
+
+#[test]
+fn errors() {
+    let tokens = try_tokens(&source("a + 1")).unwrap();
+    assert_eq!(tokens.len(), 3);
+    assert_eq!(tokens[0].0, Token::Identifier);
+    let err = try_tokens(&source("a\n  'b")).unwrap_err();
+    assert!(matches!(err, Error::Lex(_)));
+    assert_eq!(err.to_string(), "a.js:2:2: Unterminated string literal");
+
+    assert!(try_parse(&source("let a = 1"), &ParseOptions::default()).is_ok());
+    let err = try_parse(&source("let a = ;"), &ParseOptions::default()).unwrap_err();
+    assert!(matches!(err, Error::Parse(_)));
+    assert_eq!(err.to_string(), "a.js:1:8: Unexpected \";\"");
+
+    let mut input = HashMap::new();
+    input.insert(PathBuf::from("/src/b.js"), String::new());
+    let r = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+    assert!(r.try_resolve("/src/a.js", "./b", ImportKind::Stmt).is_ok());
+    assert_eq!(
+        r.try_resolve("/src/a.js", "./c", ImportKind::Stmt)
+            .unwrap_err()
+            .to_string(),
+        "Could not resolve \"./c\" from \"/src/a.js\""
+    );
+
+    let err = Error::io("out/a.js", io::Error::other("disk full"));
+    assert_eq!(err.to_string(), "out/a.js: disk full");
+    assert_eq!(err.source().unwrap().to_string(), "disk full");
+    let err: Error = io::Error::other("disk full").into();
+    assert_eq!(err.to_string(), "disk full");
+
+    let err = parse_define("a").unwrap_err();
+    assert!(matches!(err, Error::Config(_)));
+    assert_eq!(err.to_string(), "Missing \"=\" in define: a");
+    assert!(err.source().is_none());
+}
@@ -0,0 +1,46 @@
+use esbuild_rs::estree::ast_to_json;
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::{parse, ParseOptions};
+
+fn to_json(contents: &str) -> String {
+    let source = Source {
+        index: 1,
+        contents: contents.to_owned(),
+        ..Source::default()
+    };
+    let ast = parse(&Log::default(), &source, &ParseOptions::default()).unwrap();
+    ast_to_json(&ast)
+}
+
+#[test]
+fn estree() {
+    assert_eq!(
+        to_json("let {a = 1} = b"),
+        "{\"type\":\"Program\",\"start\":0,\"sourceType\":\"script\",\"body\":[\
+         {\"type\":\"VariableDeclaration\",\"start\":0,\"kind\":\"let\",\"declarations\":[\
+         {\"type\":\"VariableDeclarator\",\"start\":4,\
+         \"id\":{\"type\":\"ObjectPattern\",\"start\":4,\"properties\":[\
+         {\"type\":\"Property\",\"start\":5,\
+         \"key\":{\"type\":\"Identifier\",\"start\":5,\"name\":\"a\"},\
+         \"computed\":false,\"method\":false,\"shorthand\":true,\"kind\":\"init\",\
+         \"value\":{\"type\":\"AssignmentPattern\",\"start\":5,\
+         \"left\":{\"type\":\"Identifier\",\"start\":5,\"name\":\"a\"},\
+         \"right\":{\"type\":\"Literal\",\"start\":9,\"value\":1}}}]},\
+         \"init\":{\"type\":\"Identifier\",\"start\":14,\"name\":\"b\"}}]}]}"
+    );
+
+    // A chain continues through "?." but not through parentheses
+    assert_eq!(
+        to_json("(a?.b)?.()"),
+        "{\"type\":\"Program\",\"start\":0,\"sourceType\":\"script\",\"body\":[\
+         {\"type\":\"ExpressionStatement\",\"start\":0,\"expression\":\
+         {\"type\":\"ChainExpression\",\"start\":1,\"expression\":\
+         {\"type\":\"CallExpression\",\"start\":1,\"callee\":\
+         {\"type\":\"ChainExpression\",\"start\":1,\"expression\":\
+         {\"type\":\"MemberExpression\",\"start\":1,\
+         \"object\":{\"type\":\"Identifier\",\"start\":1,\"name\":\"a\"},\
+         \"property\":{\"type\":\"Identifier\",\"start\":4,\"name\":\"b\"},\
+         \"computed\":false,\"optional\":true}},\
+         \"arguments\":[],\"optional\":true}}}]}"
+    );
+}
@@ -0,0 +1,4 @@
+import { greet, unused } from './greet'
+import { square } from './math'
+
+export let message = greet('world') + square(2)
@@ -0,0 +1,2 @@
+export let square = x => x * x
+export let cube = x => x * x * x
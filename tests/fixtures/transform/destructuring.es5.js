@@ -0,0 +1,13 @@
+var {a, b: [c, d = 1]} = e;
+({a, b} = c);
+
+function defaults({a} = {}, [b] = [], c) {
+  return a + b + c;
+}
+
+function rest(a, {b} = {}, ...c) {
+  return c;
+}
+
+var {i, [j()]: k, ...l} = m;
+({n, ...o.p} = q);
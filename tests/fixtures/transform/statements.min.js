@@ -0,0 +1,15 @@
+function search(items, target) {
+  let low = 0, high = items.length - 1
+  outer: while (low <= high) {
+    const mid = (low + high) >> 1
+    switch (true) {
+      case items[mid] === target: return mid
+      case items[mid] < target: low = mid + 1; continue outer
+      default: high = mid - 1
+    }
+  }
+  try { throw new Error('not found') } catch { return -1 } finally { done = true }
+}
+if (a) b(); else if (c) d(); else { e() }
+for (var i = 0, j; i < 10; i++) j = i
+do x--; while (x > 0)
@@ -0,0 +1,378 @@
+// Property tests for the lexer, parser and printer. Random programs are
+// generated as trees, written out as source with every subexpression in
+// parentheses, and printed twice: printing what the printer produced must
+// give back the exact same output, or else printing changed the meaning of
+// the program. The fuzz target in "fuzz/" feeds the lexer arbitrary bytes like
+// "lexer_terminates" does here, but for much longer.
+
+use esbuild_rs::lexer::Lexer;
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::tables::Token;
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+use proptest::prelude::*;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Identifier(&'static str),
+    Number(f64),
+    String(String),
+    Literal(&'static str),
+    Array(Vec<Node>),
+    Object(Vec<(&'static str, Node)>),
+    Unary(&'static str, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    Call(Box<Node>, Vec<Node>),
+    New(Box<Node>, Vec<Node>),
+    Dot(Box<Node>, &'static str),
+    Index(Box<Node>, Box<Node>),
+    Arrow(Vec<&'static str>, Box<Node>),
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Expr(Node),
+    Let(&'static str, Node),
+    If(Node, Vec<Statement>, Option<Vec<Statement>>),
+    While(Node, Vec<Statement>),
+    Function(&'static str, Vec<&'static str>, Vec<Statement>, Node),
+}
+
+const NAMES: &[&str] = &["a", "b", "c", "$", "_x", "\u{e9}"];
+
+// Keywords are valid property names
+const PROPERTIES: &[&str] = &["x", "length", "if", "class", "new", "default"];
+
+const UNARY: &[&str] = &[
+    "-", "+", "!", "~", "typeof ", "void ", "delete ", "++", "--",
+];
+
+// Assignments are "=" and the ones that end in "=" but not in "=="
+const BINARY: &str = "+ - * / % ** < > <= >= == != === !== && || ?? & | ^ << >> >>> , \
+                      in instanceof = += **= >>>=";
+
+fn is_assignment(op: &str) -> bool {
+    op.ends_with('=') && !op.ends_with("==") && !matches!(op, "<=" | ">=" | "!=")
+}
+
+fn name() -> impl Strategy<Value = &'static str> {
+    proptest::sample::select(NAMES)
+}
+
+fn node() -> impl Strategy<Value = Node> {
+    let leaf = prop_oneof![
+        name().prop_map(Node::Identifier),
+        prop_oneof![
+            (0u32..1000).prop_map(f64::from),
+            Just(0.5),
+            Just(1e21),
+            Just(123_456_789.125),
+            Just(f64::MAX),
+        ]
+        .prop_map(Node::Number),
+        "[a-z'\"`\\\\\n\u{0}\u{e9}\u{1f600}$]{0,6}".prop_map(Node::String),
+        proptest::sample::select(&["true", "false", "null", "this", "/x[/]y/g"][..])
+            .prop_map(Node::Literal),
+    ];
+    leaf.prop_recursive(6, 48, 4, |inner| {
+        let list = proptest::collection::vec(inner.clone(), 0..3);
+        prop_oneof![
+            list.clone().prop_map(Node::Array),
+            proptest::collection::vec((proptest::sample::select(PROPERTIES), inner.clone()), 0..3)
+                .prop_map(Node::Object),
+            (proptest::sample::select(UNARY), inner.clone())
+                .prop_map(|(op, value)| Node::Unary(op, Box::new(value))),
+            (
+                proptest::sample::select(BINARY.split_whitespace().collect::<Vec<_>>()),
+                inner.clone(),
+                inner.clone()
+            )
+                .prop_map(|(op, left, right)| Node::Binary(
+                    op,
+                    Box::new(left),
+                    Box::new(right)
+                )),
+            (inner.clone(), inner.clone(), inner.clone()).prop_map(|(test, yes, no)| {
+                Node::Conditional(Box::new(test), Box::new(yes), Box::new(no))
+            }),
+            (inner.clone(), list.clone())
+                .prop_map(|(target, args)| Node::Call(Box::new(target), args)),
+            (inner.clone(), list).prop_map(|(target, args)| Node::New(Box::new(target), args)),
+            (inner.clone(), proptest::sample::select(PROPERTIES))
+                .prop_map(|(target, name)| Node::Dot(Box::new(target), name)),
+            (inner.clone(), inner.clone())
+                .prop_map(|(target, index)| Node::Index(Box::new(target), Box::new(index))),
+            (proptest::sample::subsequence(NAMES, 0..3), inner)
+                .prop_map(|(args, body)| Node::Arrow(args, Box::new(body))),
+        ]
+    })
+}
+
+fn statements() -> impl Strategy<Value = Vec<Statement>> {
+    let leaf = prop_oneof![
+        node().prop_map(Statement::Expr),
+        (name(), node()).prop_map(|(name, value)| Statement::Let(name, value)),
+    ];
+    let statement = leaf.prop_recursive(3, 16, 3, |inner| {
+        let body = proptest::collection::vec(inner, 0..3);
+        prop_oneof![
+            (node(), body.clone(), proptest::option::of(body.clone()))
+                .prop_map(|(test, yes, no)| Statement::If(test, yes, no)),
+            (node(), body.clone()).prop_map(|(test, body)| Statement::While(test, body)),
+            (
+                name(),
+                proptest::sample::subsequence(NAMES, 0..3),
+                body,
+                node()
+            )
+                .prop_map(|(name, args, body, value)| Statement::Function(name, args, body, value)),
+        ]
+    });
+    proptest::collection::vec(statement, 1..4)
+}
+
+// Every subexpression is in parentheses so the source means exactly what the
+// tree says. Only assignment targets and operands of "++", "--" and "delete"
+// are left bare, since those need to stay references.
+fn write_node(out: &mut String, node: &Node) {
+    match node {
+        Node::Identifier(name) => out.push_str(name),
+        Node::Number(value) => out.push_str(&value.to_string()),
+        Node::String(value) => {
+            out.push('"');
+            for c in value.chars() {
+                match c {
+                    '"' | '\\' => {
+                        out.push('\\');
+                        out.push(c);
+                    }
+                    '\n' => out.push_str("\\n"),
+                    '\0' => out.push_str("\\x00"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+        Node::Literal(text) => out.push_str(text),
+        Node::Array(items) => {
+            out.push('[');
+            write_list(out, items);
+            out.push(']');
+        }
+        Node::Object(properties) => {
+            out.push('{');
+            for (i, (key, value)) in properties.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                out.push_str(key);
+                out.push_str(": ");
+                write_operand(out, value);
+            }
+            out.push('}');
+        }
+        Node::Unary(op, value) if matches!(*op, "++" | "--" | "delete ") => {
+            out.push_str(op);
+            write_reference(out, value);
+        }
+        Node::Unary(op, value) => {
+            out.push_str(op);
+            write_operand(out, value);
+        }
+        Node::Binary(op, left, right) if is_assignment(op) => {
+            write_reference(out, left);
+            out.push_str(op);
+            write_operand(out, right);
+        }
+        Node::Binary(op, left, right) => {
+            write_operand(out, left);
+            out.push(' ');
+            out.push_str(op);
+            out.push(' ');
+            write_operand(out, right);
+        }
+        Node::Conditional(test, yes, no) => {
+            write_operand(out, test);
+            out.push_str(" ? ");
+            write_operand(out, yes);
+            out.push_str(" : ");
+            write_operand(out, no);
+        }
+        Node::Call(target, args) => {
+            write_operand(out, target);
+            out.push('(');
+            write_list(out, args);
+            out.push(')');
+        }
+        Node::New(target, args) => {
+            out.push_str("new ");
+            write_operand(out, target);
+            out.push('(');
+            write_list(out, args);
+            out.push(')');
+        }
+        Node::Dot(target, name) => {
+            write_operand(out, target);
+            out.push('.');
+            out.push_str(name);
+        }
+        Node::Index(target, index) => {
+            write_operand(out, target);
+            out.push('[');
+            write_node(out, index);
+            out.push(']');
+        }
+        Node::Arrow(args, body) => {
+            out.push('(');
+            out.push_str(&args.join(", "));
+            out.push_str(") => ");
+            write_operand(out, body);
+        }
+    }
+}
+
+fn write_operand(out: &mut String, node: &Node) {
+    out.push('(');
+    write_node(out, node);
+    out.push(')');
+}
+
+fn write_list(out: &mut String, nodes: &[Node]) {
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_operand(out, node);
+    }
+}
+
+// Anything else becomes a property of a parenthesized value, which can be
+// assigned to
+fn write_reference(out: &mut String, node: &Node) {
+    match node {
+        Node::Identifier(name) => out.push_str(name),
+        node => {
+            write_operand(out, node);
+            out.push_str(".x");
+        }
+    }
+}
+
+fn write_statements(out: &mut String, statements: &[Statement]) {
+    for statement in statements {
+        match statement {
+            Statement::Expr(value) => write_operand(out, value),
+            Statement::Let(name, value) => {
+                out.push_str("var ");
+                out.push_str(name);
+                out.push_str(" = ");
+                write_operand(out, value);
+            }
+            Statement::If(test, yes, no) => {
+                out.push_str("if ");
+                write_operand(out, test);
+                write_block(out, yes);
+                if let Some(no) = no {
+                    out.push_str(" else ");
+                    write_block(out, no);
+                }
+            }
+            Statement::While(test, body) => {
+                out.push_str("while ");
+                write_operand(out, test);
+                write_block(out, body);
+            }
+            Statement::Function(name, args, body, value) => {
+                out.push_str("function ");
+                out.push_str(name);
+                out.push('(');
+                out.push_str(&args.join(", "));
+                out.push(')');
+                out.push('{');
+                write_statements(out, body);
+                out.push_str("return ");
+                write_operand(out, value);
+                out.push('}');
+            }
+        }
+        out.push_str(";\n");
+    }
+}
+
+fn write_block(out: &mut String, statements: &[Statement]) {
+    out.push('{');
+    write_statements(out, statements);
+    out.push('}');
+}
+
+fn print(contents: &str, minify: MinifyOptions) -> String {
+    let result = transform(
+        contents,
+        TransformOptions {
+            minify,
+            ..TransformOptions::default()
+        },
+    );
+    assert!(
+        result.errors.is_empty(),
+        "{:?} in:\n{}",
+        result.errors,
+        contents
+    );
+    result.code
+}
+
+fn lex_all(contents: &str, json: bool) {
+    let log = Log::default();
+    let source = Source {
+        contents: contents.to_owned(),
+        ..Source::default()
+    };
+    let mut lexer = Lexer::new(&log, &source);
+    lexer.json.parse = json;
+    lexer.next();
+
+    // Every token but the last one consumes at least one character
+    let mut tokens = 0;
+    while lexer.token != Token::EndOfFile && lexer.token != Token::SyntaxError {
+        assert!(tokens <= contents.len(), "the lexer is stuck");
+        tokens += 1;
+        lexer.next();
+    }
+}
+
+proptest! {
+    // Failures are printed with the smallest input found, which is enough to
+    // turn them into regular tests
+    #![proptest_config(ProptestConfig {
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn print_parse_print(statements in statements()) {
+        let mut source = String::new();
+        write_statements(&mut source, &statements);
+        let minify_whitespace = MinifyOptions {
+            whitespace: true,
+            ..MinifyOptions::default()
+        };
+        for &minify in &[MinifyOptions::default(), minify_whitespace] {
+            let printed = print(&source, minify);
+            prop_assert_eq!(print(&printed, minify), printed);
+        }
+    }
+
+    #[test]
+    fn lexer_terminates(contents in "[a-z0-9 \n\"'`/*\\\\{}()<>=!?.#@$-]{0,64}|\\PC{0,32}") {
+        lex_all(&contents, false);
+        lex_all(&contents, true);
+    }
+
+    // Recovering from syntax errors has to make progress no matter where in
+    // a statement the error is
+    #[test]
+    fn parser_terminates(contents in "([a-z ;\n{}()=+]|if|let|function|=>|\u{1}){0,48}") {
+        transform(&contents, TransformOptions::default());
+    }
+}
@@ -0,0 +1,103 @@
+use esbuild_rs::bundler::Loader;
+use esbuild_rs::parser::{JSXOptions, JSXRuntime};
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+
+// Every call is marked as pure, which is left out here
+fn print(contents: &str, jsx: JSXOptions) -> Result<String, String> {
+    let result = transform(
+        contents,
+        TransformOptions {
+            loader: Loader::JSX,
+            minify: MinifyOptions {
+                whitespace: true,
+                syntax: false,
+                identifiers: false,
+            },
+            jsx,
+            sourcefile: "a.jsx".to_owned(),
+            ..TransformOptions::default()
+        },
+    );
+    match result.errors.first() {
+        Some(error) => Err(error.text.clone()),
+        None => Ok(result.code.replace("/*@__PURE__*/", "")),
+    }
+}
+
+fn automatic() -> JSXOptions {
+    JSXOptions {
+        runtime: JSXRuntime::Automatic,
+        ..JSXOptions::default()
+    }
+}
+
+#[test]
+fn classic() {
+    let classic = JSXOptions::default;
+    assert_eq!(
+        print("<div a='&lt;' b {...c}>d {e}<F.G/>{/* h */}</div>", classic()),
+        Ok("React.createElement(\"div\",{a:\"<\",b:true,...c},\"d \",e,React.createElement(F.G,null))"
+            .to_owned())
+    );
+    assert_eq!(
+        print(
+            "<><x-y/></>",
+            JSXOptions {
+                factory: vec!["h".to_owned()],
+                fragment: vec!["Fragment".to_owned()],
+                ..classic()
+            }
+        ),
+        Ok("h(Fragment,null,h(\"x-y\",null))".to_owned())
+    );
+    assert_eq!(
+        print("<a></b>", classic()),
+        Err("Unexpected closing \"b\" tag does not match opening \"a\" tag".to_owned())
+    );
+
+    // "<" is only JSX with the "jsx" loader
+    let result = transform("<a/>", TransformOptions::default());
+    assert_eq!(result.errors[0].text, "Unexpected \"<\"");
+}
+
+#[test]
+fn automatic_runtime() {
+    assert_eq!(
+        print("<a key={1}>{b}</a>; <><c/>d</>", automatic()),
+        Ok(
+            "import{Fragment as _Fragment,jsx as _jsx,jsxs as _jsxs}from\"react/jsx-runtime\";\
+            _jsx(\"a\",{children:b},1);_jsxs(_Fragment,{children:[_jsx(\"c\",{}),\"d\"]})"
+                .to_owned()
+        )
+    );
+
+    // A key after a spread has to win over a key in the spread
+    assert_eq!(
+        print(
+            "<a {...b} key='c'>d</a>",
+            JSXOptions {
+                import_source: "preact".to_owned(),
+                ..automatic()
+            }
+        ),
+        Ok("import{createElement as _createElement}from\"preact\";\
+            _createElement(\"a\",{...b,key:\"c\"},\"d\")"
+            .to_owned())
+    );
+
+    // Development gets where each element is and the "this" of functions
+    assert_eq!(
+        print(
+            "x = <a/>;\nfunction f() { y = <b key='k'>é{c}{d}</b> }",
+            JSXOptions {
+                development: true,
+                ..automatic()
+            }
+        ),
+        Ok("import{jsxDEV as _jsxDEV}from\"react/jsx-dev-runtime\";\
+            x=_jsxDEV(\"a\",{},void 0,false,{fileName:\"a.jsx\",lineNumber:1,columnNumber:5},void 0);\
+            function f(){y=_jsxDEV(\"b\",{children:[\"\\u00E9\",c,d]},\"k\",true,\
+            {fileName:\"a.jsx\",lineNumber:2,columnNumber:20},this)}"
+            .to_owned())
+    );
+}
@@ -0,0 +1,383 @@
+use esbuild_rs::lexer::{self, is_identifier_continue, is_identifier_start};
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::tables::Token;
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+
+fn print(contents: &str) -> Result<String, String> {
+    let result = transform(
+        contents,
+        TransformOptions {
+            minify: MinifyOptions {
+                whitespace: true,
+                syntax: false,
+                identifiers: false,
+            },
+            ..TransformOptions::default()
+        },
+    );
+    match result.errors.first() {
+        Some(error) => Err(error.text.clone()),
+        None => Ok(result.code),
+    }
+}
+
+#[test]
+fn regexp() {
+    assert_eq!(
+        print("x = /a\\/[/]b/gi / 2"),
+        Ok("x=/a\\/[/]b/gi/2".to_owned())
+    );
+    assert_eq!(print("x = a / b / c"), Ok("x=a/b/c".to_owned()));
+    assert_eq!(print("x = /=/"), Ok("x=/=/".to_owned()));
+    assert_eq!(
+        print("x = /a/gg"),
+        Err("Duplicate flag \"g\" in regular expression".to_owned())
+    );
+    assert_eq!(
+        print("x = /a/x"),
+        Err("Invalid flag \"x\" in regular expression".to_owned())
+    );
+    assert_eq!(
+        print("x = /a\n/"),
+        Err("Unterminated regular expression".to_owned())
+    );
+}
+
+// Returns the kind and the text of every token
+fn tokens(contents: &str) -> Vec<(Token, &str)> {
+    let source = Source {
+        contents: contents.to_owned(),
+        ..Source::default()
+    };
+    lexer::tokens(&Log::default(), &source)
+        .map(|(token, range)| (token, &contents[range]))
+        .collect()
+}
+
+#[test]
+fn token_stream() {
+    assert_eq!(
+        tokens("a / b /g; (/b/g) / `${ {c} }d${e}`"),
+        [
+            (Token::Identifier, "a"),
+            (Token::Slash, "/"),
+            (Token::Identifier, "b"),
+            (Token::Slash, "/"),
+            (Token::Identifier, "g"),
+            (Token::Semicolon, ";"),
+            (Token::OpenParen, "("),
+            (Token::Slash, "/b/g"),
+            (Token::CloseParen, ")"),
+            (Token::Slash, "/"),
+            (Token::TemplateHead, "`${"),
+            (Token::OpenBrace, "{"),
+            (Token::Identifier, "c"),
+            (Token::CloseBrace, "}"),
+            (Token::TemplateMiddle, "}d${"),
+            (Token::Identifier, "e"),
+            (Token::TemplateTail, "}`"),
+        ]
+    );
+
+    // The stream ends at the first syntax error
+    assert_eq!(
+        tokens("x = 'a\n y"),
+        [
+            (Token::Identifier, "x"),
+            (Token::Equals, "="),
+            (Token::SyntaxError, "'a"),
+        ]
+    );
+}
+
+#[test]
+fn token_metadata() {
+    // Every operator maps to the entry with its own text. The operators are
+    // between identifiers so that "/" isn't read as a regular expression.
+    let operators =
+        "+ - * / % ** < <= > >= in instanceof << >> >>> == != === !== ?? || && | & ^ , \
+         = += -= *= /= %= **= <<= >>= >>>= |= &= ^= ++ --";
+    let operators = operators.split_whitespace().collect::<Vec<_>>().join(" a ");
+    for (token, text) in tokens(&operators) {
+        if token == Token::Identifier {
+            continue;
+        }
+        let op_code = token.to_operator_code(false).unwrap();
+        assert_eq!(op_code.entry().text, text);
+        assert_eq!(token.is_assignment(), op_code.is_binary_assign());
+    }
+    for (token, text) in tokens("+ - ~ ! void typeof delete -- ++") {
+        let op_code = token.to_operator_code(true).unwrap();
+        assert!(op_code.is_prefix());
+        assert_eq!(op_code.entry().text, text);
+    }
+    assert_eq!(Token::Asterisk.to_operator_code(true), None);
+    assert_eq!(Token::Dot.to_operator_code(false), None);
+
+    assert!(Token::Break.is_keyword() && Token::With.is_keyword());
+    assert!(!Token::Let.is_keyword() && !Token::Identifier.is_keyword());
+}
+
+#[test]
+fn preserved_comments() {
+    let result = transform(
+        "// a\nlet x = 1 // b\nfunction f(/* c */) {\n  /* d */ return\n  // e\n}",
+        TransformOptions {
+            preserve_comments: true,
+            ..TransformOptions::default()
+        },
+    );
+    assert_eq!(
+        result.code,
+        "// a\nlet x = 1; // b\nfunction f() {\n  /* d */\n  return;\n  // e\n}\n"
+    );
+}
+
+#[test]
+fn syntax_error_recovery() {
+    let errors = |contents: &str| -> Vec<String> {
+        let result = transform(contents, TransformOptions::default());
+        result.errors.into_iter().map(|msg| msg.text).collect()
+    };
+
+    // Each statement is checked even after an earlier one fails to parse
+    assert_eq!(
+        errors("let a = ;\nfunction f() { b c; return }\nlet d = 1 +;"),
+        [
+            "Unexpected \";\"",
+            "Expected \";\" but found \"c\"",
+            "Unexpected \";\"",
+        ]
+    );
+    assert_eq!(
+        errors("} x = {; y +"),
+        [
+            "Unexpected \"}\"",
+            "Unexpected \";\"",
+            "Unexpected end of file"
+        ]
+    );
+    assert_eq!(
+        errors("if (a) { b; \u{1} } else {"),
+        ["Syntax error \"\\x01\"", "Unexpected end of file"]
+    );
+
+    // Skipping stops at the end of a block, at a new line and after templates
+    assert_eq!(
+        errors("if (a) { b c }\nlet d = 1 +;"),
+        ["Expected \";\" but found \"c\"", "Unexpected \";\""]
+    );
+    assert_eq!(
+        errors("let a = b c\nlet d = 1 +;"),
+        ["Expected \";\" but found \"c\"", "Unexpected \";\""]
+    );
+    assert_eq!(
+        errors("let a = b `${ {} }` c\nlet d = 1 +;"),
+        ["Expected \";\" but found \"c\"", "Unexpected \";\""]
+    );
+
+    // Statements that did parse still get the checks of the second pass
+    assert_eq!(errors("\"use strict\"; with (a) {} var q = 1 +;").len(), 2);
+    assert_eq!(
+        errors("x: { continue x } let d = 1 +;"),
+        ["Unexpected \";\"", "Cannot continue to label \"x\"",]
+    );
+}
+
+#[test]
+fn arrow_functions() {
+    // Parentheses are only arguments if they parse as bindings and "=>" follows
+    assert_eq!(
+        print("(a = (b) => b, [c] = [d], ...e) => c; (a, b); ((a), b)"),
+        Ok("(a=b=>b,[c]=[d],...e)=>c;a,b;a,b".to_owned())
+    );
+    assert_eq!(print("((a)) => 1"), Err("Unexpected \"=>\"".to_owned()));
+    assert_eq!(print("(a.b) => 1"), Err("Unexpected \"=>\"".to_owned()));
+    assert_eq!(
+        print("()"),
+        Err("Expected \"=>\" but found end of file".to_owned())
+    );
+
+    // "async" is only a keyword in front of arrow function arguments
+    assert_eq!(
+        print("async (a) => a; async (a, b); async => async; x = async"),
+        Ok("async a=>a;async(a,b);async=>async;x=async".to_owned())
+    );
+    assert_eq!(
+        print("async (a)\n=> a"),
+        Err("Unexpected newline before \"=>\"".to_owned())
+    );
+}
+
+#[test]
+fn deep_nesting() {
+    // Workers have small stacks, so this runs on a thread like theirs
+    std::thread::Builder::new()
+        .stack_size(2 << 20)
+        .spawn(|| {
+            let parens = format!("x = {}a{}", "(".repeat(2000), ")".repeat(2000));
+            assert_eq!(print(&parens), Ok("x=a".to_owned()));
+            let functions = format!("x = {}1", "() => ".repeat(1000));
+            assert!(print(&functions).is_ok());
+            let blocks = format!("{}{}", "{".repeat(2000), "}".repeat(2000));
+            assert_eq!(print(&blocks), Ok(blocks.clone()));
+
+            let arrays = format!("x = {}{}", "[".repeat(100_000), "]".repeat(100_000));
+            assert_eq!(print(&arrays), Err("Too many levels of nesting".to_owned()));
+
+            // Chains are as deep as they are long, but they don't nest
+            let chains = [
+                format!("x = a{}", " + a".repeat(100_000)),
+                format!("x = a{}", ".a".repeat(100_000)),
+                format!("x = a{}", "()".repeat(100_000)),
+                format!("x = {}a", "a = ".repeat(100_000)),
+                format!("x = {}a", "!".repeat(100_000)),
+                format!("x = {}a", "a ? a : ".repeat(100_000)),
+            ];
+            for chain in &chains {
+                assert!(print(chain).is_ok());
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn template() {
+    assert_eq!(
+        print("x = `a${b + `c${d}e`}f\\x41${g}`"),
+        Ok("x=`a${b+`c${d}e`}fA${g}`".to_owned())
+    );
+    assert_eq!(print("x = `a\r\nb`"), Ok("x=`a\\nb`".to_owned()));
+
+    // Tagged templates keep the raw text, including invalid escapes
+    assert_eq!(
+        print("tag`\\u{zz}${a}\\01\r\n`"),
+        Ok("tag`\\u{zz}${a}\\01\n`".to_owned())
+    );
+    assert_eq!(
+        print("x = `\\u{zz}`"),
+        Err("Invalid escape sequence".to_owned())
+    );
+    assert_eq!(
+        print("x = `\\01`"),
+        Err("Invalid escape sequence".to_owned())
+    );
+    assert_eq!(
+        print("x = `a${b"),
+        Err("Expected \"}\" but found end of file".to_owned())
+    );
+    assert_eq!(
+        print("x = `a"),
+        Err("Unterminated template literal".to_owned())
+    );
+}
+
+#[test]
+fn numbers() {
+    assert_eq!(
+        print("x = [1_000, 0b101, 0O17, 0xF_F, 1_0.2_5e1, .5, 010, 08, 9.5]"),
+        Ok("x=[1e3,5,15,255,102.5,.5,8,8,9.5]".to_owned())
+    );
+    assert_eq!(
+        print("x = [123n, 0xFFn, 1_0n, {1n: a}]"),
+        Ok("x=[123n,0xFFn,10n,{1n:a}]".to_owned())
+    );
+    for text in &["1__0", "1_", "0_1", "1._5", "1.5n", "1e1n", "010n", "0x"] {
+        assert!(print(text).is_err(), "{}", text);
+    }
+    assert_eq!(
+        print("'use strict'; x = 010"),
+        Err("Legacy octal literals are not allowed in strict mode".to_owned())
+    );
+    assert_eq!(
+        print("class A { m() { return 08 } }"),
+        Err("Legacy octal literals are not allowed in strict mode".to_owned())
+    );
+}
+
+#[test]
+fn identifiers() {
+    assert!(is_identifier_start('é') && is_identifier_start('变') && is_identifier_start('𠀀'));
+    assert!(!is_identifier_start('0') && !is_identifier_start('·'));
+    assert!(is_identifier_continue('0') && is_identifier_continue('\u{200C}'));
+
+    assert_eq!(
+        print("var caf\\u00e9 = \\u{62}, x = a.\\u0069f"),
+        Ok("var café=b,x=a.if".to_owned())
+    );
+    assert_eq!(print("\\u006cet = 1"), Ok("let=1".to_owned()));
+    assert_eq!(
+        print("var \\u0069f"),
+        Err("Expected identifier but found \"\\\\u0069f\"".to_owned())
+    );
+    assert_eq!(
+        print("var \\u0030a"),
+        Err("Invalid identifier: \"0a\"".to_owned())
+    );
+    assert_eq!(
+        print("var a\\x41"),
+        Err("Invalid escape sequence".to_owned())
+    );
+}
+
+#[test]
+fn token_ranges() {
+    let source = Source {
+        contents: "x = 'a\\'b' + `c${d}` + 0x1_0n + caf\\u00e9 >>>= #y / 2; 'e".to_owned(),
+        ..Source::default()
+    };
+    let text = |range: std::ops::Range<usize>| &source.contents[range];
+    let at = |text: &str| source.contents.find(text).unwrap();
+
+    assert_eq!(text(source.range_of_string(at("'a"))), "'a\\'b'");
+    assert_eq!(text(source.range_of_string(at("`c"))), "`c${");
+    assert_eq!(text(source.range_of_number(at("0x"))), "0x1_0n");
+    assert_eq!(text(source.range_of_identifier(at("caf"))), "caf\\u00e9");
+    assert_eq!(text(source.range_of_identifier(at("#y"))), "#y");
+    assert_eq!(text(source.range_of_operator(at(">>>="))), ">>>=");
+    assert_eq!(text(source.range_of_operator(at("/ 2"))), "/");
+
+    // The range is empty if the token is of another kind or can't be read
+    assert_eq!(source.range_of_number(at("caf")), at("caf")..at("caf"));
+    assert_eq!(source.range_of_string(at("'e")), at("'e")..at("'e"));
+    assert_eq!(source.range_of_string(1000), 1000..1000);
+}
+
+#[test]
+fn checkpoint_restore() {
+    let log = Log::default();
+    let source = Source {
+        contents: "a < b > (c) \"d\" while".to_owned(),
+        ..Source::default()
+    };
+    let mut lexer = lexer::Lexer::new(&log, &source);
+    lexer.next();
+    lexer.next();
+    let checkpoint = lexer.checkpoint();
+    for _ in 0..6 {
+        lexer.next();
+    }
+    assert_eq!((lexer.token, lexer.raw()), (Token::StringLiteral, "\"d\""));
+
+    // The string and the identifier are put back too
+    let later = lexer.checkpoint();
+    lexer.restore(checkpoint);
+    assert_eq!((lexer.token, lexer.raw()), (Token::LessThan, "<"));
+    lexer.next();
+    assert_eq!(lexer.identifier, "b");
+    lexer.restore(later);
+    assert_eq!(lexer.string_literal, "d".encode_utf16().collect::<Vec<_>>());
+    lexer.next();
+
+    // Keywords keep their text as an identifier
+    let keyword = lexer.checkpoint();
+    assert_eq!(
+        (lexer.token, lexer.identifier.as_str()),
+        (Token::While, "while")
+    );
+    lexer.identifier.clear();
+    lexer.restore(keyword);
+    assert_eq!(lexer.identifier, "while");
+}
@@ -0,0 +1,137 @@
+use esbuild_rs::ast::AST;
+use esbuild_rs::bundler::{parse_file, FileOverlay, Loader};
+use esbuild_rs::linker::{remove_dead_parts, tree_shake, LinkerFile};
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::{parse, ParseOptions};
+use esbuild_rs::printer::{print_parts, PrintOptions};
+use esbuild_rs::renamer::Renamer;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn parse_js(index: u32, contents: &str) -> Arc<AST> {
+    let log = Log::default();
+    let source = Source {
+        index,
+        is_stdin: false,
+        absolute_path: format!("/{}.js", index),
+        pretty_path: format!("{}.js", index),
+        contents: contents.to_owned(),
+        ..Source::default()
+    };
+    let options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    Arc::new(parse(&log, &source, &options).unwrap())
+}
+
+fn print_live_parts(ast: &Arc<AST>, is_part_live: &[bool]) -> String {
+    let mut overlay = FileOverlay::new(ast.clone());
+    remove_dead_parts(&mut overlay, is_part_live);
+    let parts = (0..ast.parts.len()).map(|i| overlay.part_stmts(i));
+    let options = PrintOptions {
+        minify_whitespace: true,
+        ..PrintOptions::default()
+    };
+    print_parts(ast, parts, &Renamer::no_op(), options)
+}
+
+#[test]
+fn tree_shaking() {
+    let asts = [
+        parse_js(
+            0,
+            "import {used} from './lib'; import * as ns from './ns'; import './side';
+             export let out = used() + ns.x",
+        ),
+        parse_js(
+            1,
+            "export function used() { return helper() } function helper() {}
+             export function unused() {} export const k = 1; console.log('lib')",
+        ),
+        parse_js(2, "export const x = 1, y = 2; var z = 3"),
+        parse_js(3, "window.side = 1; export const nope = 1"),
+    ];
+    let mut resolved_imports = HashMap::new();
+    resolved_imports.insert("./lib".to_owned(), 1);
+    resolved_imports.insert("./ns".to_owned(), 2);
+    resolved_imports.insert("./side".to_owned(), 3);
+    let files: Vec<LinkerFile> = asts
+        .iter()
+        .enumerate()
+        .map(|(i, ast)| LinkerFile {
+            ast,
+            resolved_imports: if i == 0 {
+                resolved_imports.clone()
+            } else {
+                HashMap::new()
+            },
+            ignore_if_unused: false,
+        })
+        .collect();
+
+    let result = tree_shake(&files, &[0], false);
+    assert_eq!(result.is_file_live, vec![true; 4]);
+    assert!(result.used_exports[1].contains("used"));
+    assert!(!result.used_exports[1].contains("unused"));
+    assert!(result.used_exports[3].is_empty());
+
+    // Imports of other files in the bundle are left for the linker to replace
+    assert_eq!(
+        print_live_parts(&asts[0], &result.is_part_live[0]),
+        "import{used}from\"./lib\";import*as ns from\"./ns\";export let out=used()+ns.x"
+    );
+    assert_eq!(
+        print_live_parts(&asts[1], &result.is_part_live[1]),
+        "export function used(){return helper()}function helper(){}console.log(\"lib\")"
+    );
+    assert_eq!(
+        print_live_parts(&asts[2], &result.is_part_live[2]),
+        "export const x=1,y=2"
+    );
+    assert_eq!(
+        print_live_parts(&asts[3], &result.is_part_live[3]),
+        "window.side=1"
+    );
+}
+
+#[test]
+fn import_json() {
+    let log = Log::default();
+    let source = Source {
+        index: 1,
+        is_stdin: false,
+        absolute_path: "/data.json".to_owned(),
+        pretty_path: "data.json".to_owned(),
+        contents: r#"{"a": [1, 2], "b": null}"#.to_owned(),
+        ..Source::default()
+    };
+    let options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    let json = Arc::new(parse_file(&log, &source, Loader::JSON, &options).unwrap());
+    let entry = parse_js(0, "import data from './data.json'; console.log(data.a)");
+
+    let mut resolved_imports = HashMap::new();
+    resolved_imports.insert("./data.json".to_owned(), 1);
+    let files = [
+        LinkerFile {
+            ast: &entry,
+            resolved_imports,
+            ignore_if_unused: false,
+        },
+        LinkerFile {
+            ast: &json,
+            resolved_imports: HashMap::new(),
+            ignore_if_unused: false,
+        },
+    ];
+
+    let result = tree_shake(&files, &[0], false);
+    assert!(result.used_exports[1].contains("default"));
+    assert_eq!(
+        print_live_parts(&json, &result.is_part_live[1]),
+        "export default{\"a\":[1,2],\"b\":null}"
+    );
+}
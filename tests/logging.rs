@@ -0,0 +1,254 @@
+use esbuild_rs::logging::{
+    DiagnosticSink, LineOffsetTable, Log, LogLevel, MsgId, MsgKind, MsgNote, Source, StderrOptions,
+};
+use esbuild_rs::terminal::{StderrColor, TerminalInfo};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn msgs_to_json() {
+    let source = Source {
+        index: 0,
+        is_stdin: false,
+        absolute_path: "/a.js".to_owned(),
+        pretty_path: "a.js".to_owned(),
+        contents: "let a\nlet a".to_owned(),
+        ..Source::default()
+    };
+    let log = Log::default();
+    log.add_range_warning_with_notes(
+        &source,
+        10..11,
+        "Duplicate \"a\"".to_owned(),
+        vec![MsgNote::new(&source, 4..5, "Declared here".to_owned())],
+    );
+    log.add_error(&Source::default(), 0, "Tab\there".to_owned());
+
+    assert_eq!(
+        log.take_msgs_as_json(),
+        "[{\"kind\":\"warning\",\"id\":\"\",\"path\":\"a.js\",\"line\":2,\"column\":4,\"length\":1,\
+         \"text\":\"Duplicate \\\"a\\\"\",\"notes\":[{\"path\":\"a.js\",\"line\":1,\
+         \"column\":4,\"length\":1,\"text\":\"Declared here\"}]},\
+         {\"kind\":\"error\",\"id\":\"\",\"path\":\"\",\"line\":0,\"column\":0,\"length\":0,\
+         \"text\":\"Tab\\there\",\"notes\":[]}]"
+    );
+}
+
+#[test]
+fn diagnostic_sink() {
+    let mut sink = DiagnosticSink::new(&StderrOptions {
+        include_source: true,
+        error_limit: 2,
+        exit_when_limit_is_hit: false,
+        color: StderrColor::Never,
+    });
+    let log = Log::default();
+    for text in &["a", "b", "a", "c", "d"] {
+        log.add_error(&Source::default(), 0, text.to_string());
+    }
+    log.add_warning(&Source::default(), 0, "e".to_owned());
+
+    // The duplicate is dropped and the errors past the limit are counted
+    let shown: Vec<String> = sink.drain(&log).into_iter().map(|msg| msg.text).collect();
+    assert_eq!(shown, ["a", "b", "e"]);
+    assert_eq!(sink.counts().errors, 4);
+    assert_eq!(sink.counts().warnings, 1);
+    assert!(!sink.should_stop());
+    assert_eq!(
+        sink.finish().unwrap().text,
+        "2 errors shown, 2 omitted (disable the limit with --error-limit=0)"
+    );
+}
+
+#[test]
+fn duplicate_declaration_note() {
+    let source = Source {
+        index: 0,
+        is_stdin: false,
+        absolute_path: "/a.js".to_owned(),
+        pretty_path: "a.js".to_owned(),
+        contents: "let a = 1\nvar a".to_owned(),
+        ..Source::default()
+    };
+    let log = Log::default();
+    assert!(esbuild_rs::parser::parse(&log, &source, &Default::default()).is_some());
+    let msgs = log.take_msgs();
+    assert_eq!(msgs.len(), 1);
+
+    let options = StderrOptions {
+        include_source: true,
+        error_limit: 0,
+        exit_when_limit_is_hit: false,
+        color: StderrColor::Never,
+    };
+    let terminal_info = TerminalInfo {
+        is_tty: false,
+        use_color_escapes: false,
+        width: 0,
+    };
+    assert_eq!(
+        msgs[0].to_terminal_string(&options, &terminal_info),
+        "a.js:2:4: error: \"a\" has already been declared\n\
+         var a\n    ^\n  \
+         a.js:1:4: note: \"a\" was originally declared here:\n  \
+         let a = 1\n      ^\n"
+    );
+}
+
+// Nothing takes messages out of the log while a file is parsed, so there's no
+// limit on how many can be waiting in it
+#[test]
+fn many_msgs() {
+    let contents = format!("const c = 1;\n{}", "c = 2;\n".repeat(1100));
+    let result = esbuild_rs::transform(&contents, Default::default());
+    assert_eq!(result.warnings.len(), 1100);
+
+    let result = esbuild_rs::transform(&"x = 1 +;\n".repeat(1100), Default::default());
+    assert_eq!(result.errors.len(), 1100);
+}
+
+#[test]
+fn line_offset_table() {
+    let table = LineOffsetTable::new("a\r\nb\rc\u{2028}d\n\u{e9}e");
+    assert_eq!(table.line_count(), 5);
+    assert_eq!(table.line_and_column(0), (0, 0, 0));
+    assert_eq!(table.line_and_column(3), (1, 0, 3));
+    assert_eq!(table.line_and_column(5), (2, 0, 5));
+    assert_eq!(table.line_and_column(9), (3, 0, 9));
+
+    // Columns are in bytes, and the end of the text is on the last line
+    assert_eq!(table.line_and_column(13), (4, 2, 11));
+    assert_eq!(table.line_start(5), None);
+}
+
+#[test]
+fn log_overrides() {
+    let source = Source {
+        pretty_path: "a.js".to_owned(),
+        contents: "if (a === NaN) b = {c, c}\nexport {}".to_owned(),
+        ..Source::default()
+    };
+    let parse = |overrides| {
+        let log = Log::with_overrides(overrides);
+        esbuild_rs::parser::parse(&log, &source, &Default::default()).unwrap();
+        log.take_msgs()
+    };
+
+    let msgs = parse(Default::default());
+    let ids: Vec<MsgId> = msgs.iter().map(|msg| msg.id).collect();
+    assert_eq!(ids, [MsgId::EqualityWithNaN, MsgId::DuplicateObjectKey]);
+    assert!(msgs.iter().all(|msg| msg.kind == MsgKind::Warning));
+
+    let msgs = parse(
+        vec![
+            (MsgId::EqualityWithNaN, LogLevel::Error),
+            (MsgId::DuplicateObjectKey, LogLevel::Silent),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    assert_eq!(msgs.len(), 1);
+    assert_eq!(msgs[0].kind, MsgKind::Error);
+    assert_eq!(
+        MsgId::from_name("equality-with-nan"),
+        Some(MsgId::EqualityWithNaN)
+    );
+}
+
+#[test]
+fn colors() {
+    let log = Log::default();
+    log.add_error(&Source::default(), 0, "a".to_owned());
+    let msg = log.take_msgs().pop().unwrap();
+    let print = |color, use_color_escapes| {
+        let options = StderrOptions {
+            include_source: true,
+            error_limit: 0,
+            exit_when_limit_is_hit: false,
+            color,
+        };
+        let terminal_info = TerminalInfo {
+            is_tty: true,
+            use_color_escapes,
+            width: 0,
+        };
+        msg.to_terminal_string(&options, &terminal_info)
+    };
+
+    // Only "IfTerminal" depends on the terminal
+    let colored = "\x1b[1m\x1b[31merror: \x1b[0;1ma\x1b[0m\n";
+    assert_eq!(print(StderrColor::IfTerminal, true), colored);
+    assert_eq!(print(StderrColor::IfTerminal, false), "error: a\n");
+    assert_eq!(print(StderrColor::Always, false), colored);
+    assert_eq!(print(StderrColor::Never, true), "error: a\n");
+}
+
+// A writer whose output can still be read after it has been moved to the
+// drain thread
+#[derive(Clone, Default)]
+struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn drain() {
+    let source = |index, path: &str| Source {
+        index,
+        pretty_path: path.to_owned(),
+        contents: "a b c".to_owned(),
+        ..Source::default()
+    };
+    let (a, b) = (source(1, "a.js"), source(2, "b.js"));
+    let options = StderrOptions {
+        include_source: false,
+        error_limit: 3,
+        exit_when_limit_is_hit: false,
+        color: StderrColor::Never,
+    };
+    let terminal_info = TerminalInfo {
+        is_tty: false,
+        use_color_escapes: false,
+        width: 0,
+    };
+
+    // Messages from another thread are mixed in with the ones from this one.
+    // They're all logged before the drain starts so that they end up in the
+    // same batch.
+    let mut log = Log::default();
+    let sender = log.clone_sender();
+    let (other_a, other_b) = (a.clone(), b.clone());
+    std::thread::spawn(move || {
+        let log = Log::from_sender(sender);
+        log.add_error(&other_b, 4, "b2".to_owned());
+        log.add_error(&other_a, 2, "a1".to_owned());
+    })
+    .join()
+    .unwrap();
+    log.add_warning(&b, 0, "b0".to_owned());
+    log.add_error(&a, 0, "a0".to_owned());
+    log.add_error(&a, 0, "a0".to_owned());
+    log.add_error(&Source::default(), 0, "build".to_owned());
+    let output = SharedOutput::default();
+    let drain = log.spawn_drain(&options, terminal_info, output.clone());
+    assert!(log.take_msgs().is_empty());
+
+    let counts = drain.finish(log);
+    assert_eq!((counts.errors, counts.warnings), (4, 1));
+    assert_eq!(
+        String::from_utf8(output.0.lock().unwrap().clone()).unwrap(),
+        "error: build\n\
+         a.js: error: a0\n\
+         a.js: error: a1\n\
+         b.js: warning: b0\n\
+         error: 3 errors shown, 1 omitted (disable the limit with --error-limit=0)\n"
+    );
+}
@@ -0,0 +1,247 @@
+use esbuild_rs::lower::{Target, UnsupportedSyntax};
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+
+fn lower(contents: &str, target: Target) -> String {
+    let result = transform(
+        contents,
+        TransformOptions {
+            target,
+            minify: MinifyOptions {
+                whitespace: true,
+                syntax: false,
+                identifiers: false,
+            },
+            ..TransformOptions::default()
+        },
+    );
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    result.code
+}
+
+#[test]
+fn lower_operators() {
+    assert_eq!(lower("a ?? b", Target::ES2019), "a!=null?a:b");
+    assert_eq!(
+        lower("f() ?? b", Target::ES2019),
+        "var _a;(_a=f())!=null?_a:b"
+    );
+    assert_eq!(lower("a ?? b", Target::ES2020), "a??b");
+    assert_eq!(lower("a ** b", Target::ES2015), "Math.pow(a,b)");
+    assert_eq!(
+        lower("a.b[c()] **= 2", Target::ES2015),
+        "var _a,_b;(_a=a.b)[_b=c()]=Math.pow(_a[_b],2)"
+    );
+    assert_eq!(
+        lower("x = {a, ...b, c: 1}", Target::ES2017),
+        "x=Object.assign({a},b,{c:1})"
+    );
+}
+
+#[test]
+fn lower_optional_chain() {
+    assert_eq!(lower("a?.b.c", Target::ES2019), "a==null?void 0:a.b.c");
+    assert_eq!(
+        lower("a?.b?.c", Target::ES2019),
+        "var _a;a==null?void 0:(_a=a.b)==null?void 0:_a.c"
+    );
+    assert_eq!(
+        lower("f().b?.(1)", Target::ES2019),
+        "var _a,_b;(_b=(_a=f()).b)==null?void 0:_b.call(_a,1)"
+    );
+    assert_eq!(
+        lower("delete a?.b", Target::ES2019),
+        "a==null?true:delete a.b"
+    );
+    assert_eq!(lower("(a?.b).c", Target::ES2019), "(a==null?void 0:a.b).c");
+    assert_eq!(
+        lower("(a?.b.c)().d", Target::ES2019),
+        "(a==null?void 0:a.b.c)().d"
+    );
+    assert_eq!(lower("(a?.b).c", Target::ES2020), "(a?.b).c");
+    assert_eq!(lower("(a?.b)?.c", Target::ES2020), "a?.b?.c");
+
+    // Temporaries don't take the names of symbols in the file
+    assert_eq!(
+        lower(
+            "var _a = 5; function f() { return x?.y.z?.w + _a }",
+            Target::ES2019
+        ),
+        "var _a=5;function f(){var _b;return(x==null?void 0:(_b=x.y.z)==null?void 0:_b.w)+_a}"
+    );
+}
+
+#[test]
+fn lower_class_fields() {
+    assert_eq!(
+        lower("class A extends B { a = 1; static b = 2 }", Target::ES2019),
+        "class A extends B{constructor(){super(...arguments);this.a=1}}A.b=2"
+    );
+    assert_eq!(
+        lower("x = class { static a }", Target::ES2019),
+        "var _a;x=(_a=class{},_a.a=void 0,_a)"
+    );
+
+    let result = transform(
+        "class A { [a] = 1 }",
+        TransformOptions {
+            target: Target::ES2019,
+            ..TransformOptions::default()
+        },
+    );
+    assert_eq!(result.errors.len(), 1);
+}
+
+#[test]
+fn lower_private_members() {
+    let source = "class A { #x = 1; get() { return this.#x } static has(o) { return #x in o } }";
+    assert_eq!(
+        lower(source, Target::ESNext),
+        "class A{#x=1;get(){return this.#x}static has(o){return #x in o}}"
+    );
+    // The helpers are only checked by name since their bodies are long
+    let lowered = lower(source, Target::ES2020);
+    assert!(lowered.starts_with("var _a,__privateGet=function(obj,member,getter){"));
+    assert!(lowered.ends_with(
+        "_a=new WeakMap;class A{constructor(){_a.set(this,1)}\
+         get(){return __privateGet(this,_a)}static has(o){return _a.has(o)}}"
+    ));
+    assert_eq!(
+        lower("class A { static { this.b = 2 } }", Target::ES2020),
+        "class A{}(function(){this.b=2}).call(A)"
+    );
+}
+
+#[test]
+fn lower_destructuring() {
+    assert_eq!(
+        lower("var {a, b: {c = 1}} = d", Target::ES5),
+        "var _a;var a=d.a,c=(_a=d.b.c)===void 0?1:_a"
+    );
+    assert_eq!(
+        lower("({a, b} = c)", Target::ES5),
+        "var _a;_a=c,a=_a.a,b=_a.b,_a"
+    );
+    assert_eq!(
+        lower("function f({a}) { return a }", Target::ES5),
+        "function f(_a){var a=_a.a;return a}"
+    );
+
+    // Arrays are read with a helper for the iterator protocol
+    assert!(lower("[a, b] = [b, a]", Target::ES5)
+        .ends_with(";_a=[b,a],a=(_b=__read(_a,2))[0],b=_b[1],_a"));
+    assert_eq!(lower("var [a] = b", Target::ES2015), "var[a]=b");
+
+    // Defaults of lowered patterns are read from "arguments"
+    assert_eq!(
+        lower("function f(a, {b} = {}) { return b }", Target::ES5),
+        "function f(a){var b=(arguments.length>1&&arguments[1]!==void 0?arguments[1]:{}).b;return b}"
+    );
+    assert_eq!(
+        lower("function f({a} = {}) { return a }", Target::ES2015),
+        "function f({a}={}){return a}"
+    );
+
+    // Object rest lowers the whole pattern, leaving out the keys before it
+    assert!(lower("var {a, [b()]: c, ...d} = e", Target::ES2017)
+        .ends_with(";var a=e.a,c=e[_a=b()],d=__objRest(e,[\"a\",_a])"));
+    assert!(lower("({a, ...b} = c)", Target::ES2017)
+        .ends_with(";_a=c,a=_a.a,b=__objRest(_a,[\"a\"]),_a"));
+    assert!(lower("function f({...a}) {}", Target::ES2017)
+        .ends_with(";function f(_a){var a=__objRest(_a,[])}"));
+    assert_eq!(lower("var {...a} = b", Target::ES2018), "var{...a}=b");
+}
+
+#[test]
+fn lower_generators() {
+    assert!(
+        lower("function* f(a) { var b = yield a; return b }", Target::ES5).ends_with(
+            ";function f(a){var b;return __generator(this,function(_a){switch(_a.label){\
+             case 0:return[4,a];case 1:b=_a.sent();return[2,b]}})}"
+        )
+    );
+    assert!(lower(
+        "function* f() { while (a) { try { yield 1 } finally { b() } } }",
+        Target::ES5
+    )
+    .ends_with(
+        ";function f(){return __generator(this,function(_a){switch(_a.label){\
+         case 0:if(!a)return[3,5];_a.label=1;\
+         case 1:_a.trys.push([1,,3,4]);return[4,1];\
+         case 2:_a.sent();return[3,4];\
+         case 3:b();return[7];\
+         case 4:return[3,0];\
+         case 5:return[2]}})}"
+    ));
+    assert_eq!(
+        lower("function* f() { yield arguments }", Target::ES2015),
+        "function*f(){yield arguments}"
+    );
+}
+
+#[test]
+fn lower_es2015_syntax() {
+    assert_eq!(
+        lower("function f(a, b = 1, ...c) {}", Target::ES5),
+        "function f(a){var b=arguments.length>1&&arguments[1]!==void 0?arguments[1]:1,\
+         c=Array.prototype.slice.call(arguments,2)}"
+    );
+    assert_eq!(
+        lower("function f(a, b = 1) {}", Target::ES2015),
+        "function f(a,b=1){}"
+    );
+    assert!(lower("f(a, ...b)", Target::ES5).ends_with(";f.apply(void 0,__spread([a],b))"));
+    assert!(lower("a.f(...b)", Target::ES5).ends_with(";a.f.apply(a,__spread([],b))"));
+    assert!(
+        lower("a?.b(...c)", Target::ES5).ends_with(";a==null?void 0:a.b.apply(a,__spread([],c))")
+    );
+    assert!(lower("[a, ...b, c]", Target::ES5).ends_with(";__spread([a],b,[c])"));
+    assert!(lower("new A(...b)", Target::ES5)
+        .ends_with(";new(Function.prototype.bind.apply(A,__spread([void 0],b)))"));
+    assert_eq!(lower("({a})", Target::ES5), "({a:a})");
+    assert_eq!(lower("({a() {}})", Target::ES5), "({a:function(){}})");
+    assert_eq!(
+        lower("({a: 1, [b]: 2, get c() {}})", Target::ES5),
+        "var _a;_a={a:1},_a[b]=2,Object.defineProperty(_a,\"c\",\
+         {get:function(){},configurable:true,enumerable:true}),_a"
+    );
+    assert_eq!(lower("({a, [b]: c})", Target::ES2015), "({a,[b]:c})");
+}
+
+#[test]
+fn unsupported_syntax() {
+    let transform_with = |unsupported_syntax| {
+        transform(
+            "a => a",
+            TransformOptions {
+                target: Target::ES5,
+                unsupported_syntax,
+                ..TransformOptions::default()
+            },
+        )
+    };
+
+    let result = transform_with(UnsupportedSyntax::Error);
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(result.code, "");
+
+    let result = transform_with(UnsupportedSyntax::Warn);
+    assert_eq!(result.warnings.len(), 1);
+    assert_eq!(result.code, "a => a;\n");
+
+    let result = transform_with(UnsupportedSyntax::Throw);
+    assert_eq!(result.warnings.len(), 1);
+    assert!(result.code.contains("throw new Error("));
+
+    // A method can't be a function if it uses "super"
+    let result = transform(
+        "({a() { super.a() }})",
+        TransformOptions {
+            target: Target::ES5,
+            ..TransformOptions::default()
+        },
+    );
+    assert_eq!(
+        result.errors[0].text,
+        "Object literal methods are not available in the configured target environment (es5)"
+    );
+}
@@ -0,0 +1,157 @@
+use esbuild_rs::mangler::MangleOptions;
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+
+fn minify(contents: &str, minify: MinifyOptions) -> String {
+    let result = transform(
+        contents,
+        TransformOptions {
+            minify,
+            ..TransformOptions::default()
+        },
+    );
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    result.code
+}
+
+#[test]
+fn minify_syntax() {
+    let options = MinifyOptions {
+        whitespace: true,
+        syntax: true,
+        identifiers: false,
+    };
+    assert_eq!(
+        minify(
+            "var a = true; var b = false;; a = a + 1; a.b = a.b + 1",
+            options
+        ),
+        "var a=!0,b=!1;a+=1;a.b=a.b+1"
+    );
+    assert_eq!(minify("x = true.toString()", options), "x=(!0).toString()");
+
+    // Only the last value of a comma expression is kept if the others do
+    // nothing, except in the target of a call where the comma changes "this"
+    assert_eq!(
+        minify("x = (1, typeof y, () => {}, z); x = (y, z)", options),
+        "x=z;x=(y,z)"
+    );
+    assert_eq!(
+        minify("(0, a.b)(); (0, eval)(c)", options),
+        "(0,a.b)();(0,eval)(c)"
+    );
+}
+
+#[test]
+fn minify_switch() {
+    let options = MinifyOptions {
+        whitespace: true,
+        syntax: true,
+        identifiers: false,
+    };
+
+    // Only the cases that can run are kept when the case that matches is known
+    assert_eq!(
+        minify(
+            "switch (1) { case 0: a(); case 1: b(); break; case 2: c() }",
+            options
+        ),
+        "{b()}"
+    );
+    assert_eq!(
+        minify(
+            "switch (3) { case 1: a(); default: b(); case 2: c(); break; case 4: d() }",
+            options
+        ),
+        "{b();c()}"
+    );
+    assert_eq!(minify("switch ('a') { case 'b': a() }", options), "");
+
+    // The value is still evaluated when it isn't known
+    assert_eq!(minify("switch (f()) {}", options), "f()");
+    assert_eq!(
+        minify("switch (x) { case 1: a(); break }", options),
+        "if(x===1){a()}"
+    );
+    assert_eq!(minify("switch (x) { default: a() }", options), "{x;{a()}}");
+    assert_eq!(
+        minify("switch (x) { case 1: if (y) break; a() }", options),
+        "switch(x){case 1:if(y)break;a()}"
+    );
+    assert_eq!(
+        minify("switch (1) { case x: a(); case 1: b() }", options),
+        "switch(1){case x:a();case 1:b()}"
+    );
+}
+
+#[test]
+fn minify_constant_folding() {
+    let options = MinifyOptions {
+        whitespace: true,
+        syntax: true,
+        identifiers: false,
+    };
+    let fold = |code: &str| minify(code, options);
+
+    assert_eq!(fold("x = 'a' + 'b' + 1 + null"), "x=\"ab1null\"");
+    assert_eq!(fold("x = y + 'a' + 'b'"), "x=y+\"ab\"");
+    assert_eq!(fold("x = `a${1}b${'c'}${y}d${true}`"), "x=`a1bc${y}dtrue`");
+    assert_eq!(fold("x = `a${.5}`"), "x=\"a0.5\"");
+    assert_eq!(
+        fold("x = [+true, +null, +'', +' 12 ', +'0x1f', +'1e3', +'-Infinity']"),
+        "x=[1,0,0,12,31,1e3,-1/0]"
+    );
+    assert_eq!(fold("x = [+'1_0', +'abc']"), "x=[0/0,0/0]");
+    assert_eq!(
+        fold("x = [typeof 'a', typeof 1, typeof null, typeof (() => {})]"),
+        "x=[\"string\",\"number\",\"object\",\"function\"]"
+    );
+    assert_eq!(fold("x = ['a' < 'b', 'b' <= 'a', 2 > 1]"), "x=[!0,!1,!0]");
+
+    // Objects can run code when converted to primitives
+    assert_eq!(fold("x = 1 + 2 + y"), "x=1+2+y");
+    assert_eq!(fold("x = 'a' + {} + +[]"), "x=\"a\"+{}+ +[]");
+    assert_eq!(fold("x = tag`a${1}`"), "x=tag`a${1}`");
+    assert_eq!(fold("x = 'a' + 1n"), "x=\"a\"+1n");
+}
+
+#[test]
+fn minify_identifiers() {
+    let options = MinifyOptions {
+        whitespace: true,
+        syntax: false,
+        identifiers: true,
+    };
+    assert_eq!(
+        minify(
+            "var top; function f(arg) { let local = arg; return local }",
+            options
+        ),
+        "var top;function f(a){let b=a;return b}"
+    );
+}
+
+#[test]
+fn preserve_quoted_keys() {
+    let print = |preserve_quoted_keys| {
+        let result = transform(
+            "x = {'a': a['b'], c: c.d}",
+            TransformOptions {
+                minify: MinifyOptions {
+                    whitespace: true,
+                    syntax: true,
+                    identifiers: false,
+                },
+                mangle: MangleOptions {
+                    preserve_quoted_keys,
+                },
+                ..TransformOptions::default()
+            },
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        result.code
+    };
+    assert_eq!(print(false), "x={a:a.b,c:c.d}");
+
+    // Closure Compiler only renames the properties that aren't quoted
+    assert_eq!(print(true), "x={\"a\":a[\"b\"],c:c.d}");
+}
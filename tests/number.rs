@@ -0,0 +1,85 @@
+use esbuild_rs::number::{number_to_string, shortest_number_to_string};
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+
+#[test]
+fn number_to_string_matches_javascript() {
+    for (value, text) in &[
+        (0.0, "0"),
+        (0.5, "0.5"),
+        (123.456, "123.456"),
+        (1e21, "1e+21"),
+        (1e20, "100000000000000000000"),
+        (1e-6, "0.000001"),
+        (1.5e-7, "1.5e-7"),
+        (-2.5, "-2.5"),
+        (5e-324, "5e-324"),
+        (1.7976931348623157e308, "1.7976931348623157e+308"),
+    ] {
+        assert_eq!(number_to_string(*value), *text);
+    }
+}
+
+#[test]
+fn shortest_number() {
+    for (value, text) in &[
+        (0.0, "0"),
+        (0.5, ".5"),
+        (0.05, ".05"),
+        (100.0, "100"),
+        (1000.0, "1e3"),
+        (1.5e-7, "15e-8"),
+        (123.456, "123.456"),
+        (281474976710655.0, "0xffffffffffff"),
+        (1e300, "1e300"),
+    ] {
+        assert_eq!(shortest_number_to_string(*value), *text);
+    }
+}
+
+#[test]
+fn print_numbers() {
+    let print = |contents: &str, minify: MinifyOptions| {
+        let result = transform(
+            contents,
+            TransformOptions {
+                minify,
+                ..TransformOptions::default()
+            },
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        result.code
+    };
+    assert_eq!(
+        print(
+            "x = [0.5, 1e21, 1e-7, -0, 1e999, NaN, 1..a]",
+            MinifyOptions::default()
+        ),
+        "x = [0.5, 1e21, 1e-7, -0, Infinity, NaN, 1 .a];\n"
+    );
+
+    // Infinity is a division when minifying, which needs parentheses in
+    // another multiplication or as the target of a member expression
+    let minify = MinifyOptions {
+        whitespace: true,
+        syntax: true,
+        identifiers: false,
+    };
+    assert_eq!(
+        print(
+            "x = [0.5, 1e21, -0, 1e999, 2 * 1e999, 1e999.a, 1e3.a]",
+            minify
+        ),
+        "x=[.5,1e21,-0,1/0,2*(1/0),(1/0).a,1e3.a]"
+    );
+
+    // Negative infinity doesn't need parentheses around the division, and
+    // the globals are numbers unless they're declared or assigned to
+    assert_eq!(
+        print("x = [-1e400, -Infinity, NaN, 2 * -Infinity, -NaN]", minify),
+        "x=[-1/0,-1/0,0/0,2*(-1/0),0/0]"
+    );
+    assert_eq!(
+        print("Infinity = 1; { let NaN = 2; x = [Infinity, NaN] }", minify),
+        "Infinity=1;{let NaN=2;x=[1/0,NaN]}"
+    );
+}
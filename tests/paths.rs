@@ -0,0 +1,62 @@
+use esbuild_rs::paths::{base, dir, ext, is_absolute, join, normalize, pretty_path, relative};
+
+#[test]
+fn normalization() {
+    assert_eq!(normalize("a//b/./c/../d/"), "a/b/d");
+    assert_eq!(normalize("./"), ".");
+    assert_eq!(normalize("a\\b\\..\\c"), "a/c");
+
+    // You can't go above the root, but a relative path keeps its ".."
+    assert_eq!(normalize("/a/../../b"), "/b");
+    assert_eq!(normalize("C:/a/../../b"), "C:/b");
+    assert_eq!(normalize("//server/share/../b"), "//server/share/b");
+    assert_eq!(normalize("../a/../../b"), "../../b");
+}
+
+#[test]
+fn roots() {
+    assert!(is_absolute("/a"));
+    assert!(is_absolute("C:\\a"));
+    assert!(is_absolute("c:/"));
+    assert!(is_absolute("//server/share"));
+    assert!(!is_absolute("C:a"));
+    assert!(!is_absolute("a/b"));
+    assert!(!is_absolute("<stdin>"));
+
+    assert_eq!(join("C:\\a", "..\\b"), "C:/b");
+    assert_eq!(join("/", "a"), "/a");
+    assert_eq!(join("/a", "D:/b"), "D:/b");
+    assert_eq!(dir("C:/a"), "C:/");
+    assert_eq!(dir("//server/share/a"), "//server/share/");
+    assert_eq!(dir("a"), ".");
+    assert_eq!(base("C:\\a\\b.js"), "b.js");
+    assert_eq!(ext("/a/b.min.js"), ".js");
+    assert_eq!(ext("/a/.gitignore"), "");
+}
+
+#[test]
+fn relative_paths() {
+    assert_eq!(relative("/a/b", "/a/c/d"), Some("../c/d".to_owned()));
+    assert_eq!(relative("/a", "/a"), Some(".".to_owned()));
+    assert_eq!(relative("C:/a", "c:\\a\\b"), Some("b".to_owned()));
+    assert_eq!(
+        relative("//server/share/a", "//SERVER/share/b"),
+        Some("../b".to_owned())
+    );
+
+    // There's no relative path between different roots or through an
+    // unknown directory
+    assert_eq!(relative("C:/a", "D:/a"), None);
+    assert_eq!(relative("/a", "a"), None);
+    assert_eq!(relative("../a", "b"), None);
+}
+
+#[test]
+fn pretty_paths() {
+    assert_eq!(pretty_path("/home", "/home/src/a.js"), "src/a.js");
+    assert_eq!(pretty_path("/home/src", "/home/a.js"), "/home/a.js");
+    assert_eq!(pretty_path("C:\\home", "C:\\home\\src\\a.js"), "src/a.js");
+    assert_eq!(pretty_path("C:\\home", "D:\\src\\a.js"), "D:/src/a.js");
+    assert_eq!(pretty_path("", "/src/a.js"), "/src/a.js");
+    assert_eq!(pretty_path("/home", "<stdin>"), "<stdin>");
+}
@@ -0,0 +1,56 @@
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::{parse, ParseOptions};
+use esbuild_rs::printer::{print_ast, PrintOptions};
+use esbuild_rs::renamer::{compute_reserved_names, minify_all_symbols, number_to_minified_name};
+
+fn minify(contents: &str) -> String {
+    let log = Log::default();
+    let source = Source {
+        index: 0,
+        is_stdin: false,
+        absolute_path: "<stdin>".to_owned(),
+        pretty_path: "<stdin>".to_owned(),
+        contents: contents.to_owned(),
+        ..Source::default()
+    };
+    let ast = parse(&log, &source, &ParseOptions::default()).unwrap();
+    let scope_trees = [&ast.scopes];
+    let reserved_names = compute_reserved_names(&scope_trees, &ast.symbols);
+    let renamer = minify_all_symbols(&reserved_names, &scope_trees, &ast.symbols);
+    let options = PrintOptions {
+        minify_whitespace: true,
+        ..PrintOptions::default()
+    };
+    print_ast(&ast, &renamer, options)
+}
+
+#[test]
+fn minified_names() {
+    assert_eq!(number_to_minified_name(0), "a");
+    assert_eq!(number_to_minified_name(53), "$");
+    assert_eq!(number_to_minified_name(54), "aa");
+    assert_eq!(number_to_minified_name(55), "ba");
+}
+
+#[test]
+fn rename_symbols() {
+    // The most used symbol gets the first name, and names that stay the same
+    // are never reused
+    assert_eq!(
+        minify("function f(x, y) { return a(y, y) }"),
+        "function d(c,b){return a(b,b)}"
+    );
+
+    // Symbols in sibling scopes share names, but a nested scope never reuses
+    // the name of a symbol from a scope around it
+    assert_eq!(
+        minify("function f(x) { return x } function g(y) { return () => { let z; return y + z } }"),
+        "function c(a){return a}function d(a){return()=>{let b;return a+b}}"
+    );
+
+    // Nothing a direct "eval" can see is renamed
+    assert_eq!(
+        minify("function f(x) { return eval('x') }"),
+        "function f(x){return eval(\"x\")}"
+    );
+}
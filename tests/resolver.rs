@@ -0,0 +1,320 @@
+use esbuild_rs::ast::ImportKind;
+use esbuild_rs::fs::{EntryKind, FileSystem, MockFileSystem};
+use esbuild_rs::resolver::{Platform, ResolveOptions, Resolver};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn mock_fs(files: &[(&str, &str)]) -> MockFileSystem {
+    let mut input = HashMap::new();
+    for (path, contents) in files {
+        input.insert(PathBuf::from(path), contents.to_string());
+    }
+    MockFileSystem::new(input)
+}
+
+fn resolve(r: &Resolver<MockFileSystem>, source_path: &str, import_path: &str) -> Option<String> {
+    r.resolve(source_path, import_path, ImportKind::Stmt)
+        .map(|result| result.path)
+}
+
+#[test]
+fn mock_file_system() {
+    let fs = mock_fs(&[("/src/entry.js", "entry"), ("/src/lib/util.js", "util")]);
+
+    let root = fs.read_directory("/");
+    assert_eq!(root["src"].kind, EntryKind::Dir);
+    let src = fs.read_directory("/src");
+    assert_eq!(src["entry.js"].kind, EntryKind::File);
+    assert_eq!(src["lib"].kind, EntryKind::Dir);
+    assert!(fs.read_directory("/missing").is_empty());
+
+    assert_eq!(fs.read_file("/src/lib/util.js").as_deref(), Some("util"));
+    assert_eq!(fs.read_file("/src/lib"), None);
+}
+
+#[test]
+fn resolve_relative_paths() {
+    let fs = mock_fs(&[
+        ("/src/entry.js", ""),
+        ("/src/a.ts", ""),
+        ("/src/dir/index.jsx", ""),
+    ]);
+    let r = Resolver::new(fs, ResolveOptions::default());
+
+    assert_eq!(
+        resolve(&r, "/src/entry.js", "./a").as_deref(),
+        Some("/src/a.ts")
+    );
+    assert_eq!(
+        resolve(&r, "/src/entry.js", "../src/a.ts").as_deref(),
+        Some("/src/a.ts")
+    );
+    assert_eq!(
+        resolve(&r, "/src/entry.js", "./dir").as_deref(),
+        Some("/src/dir/index.jsx")
+    );
+    assert_eq!(resolve(&r, "/src/entry.js", "./missing"), None);
+}
+
+#[test]
+fn resolve_node_modules() {
+    let fs = mock_fs(&[
+        ("/src/entry.js", ""),
+        ("/src/node_modules/inner/index.js", ""),
+        ("/node_modules/inner/index.js", ""),
+        (
+            "/node_modules/pkg/package.json",
+            r#"{"main": "./lib/main", "module": "esm"}"#,
+        ),
+        ("/node_modules/pkg/lib/main.js", ""),
+        ("/node_modules/pkg/esm/index.js", ""),
+        ("/node_modules/bad/package.json", "{"),
+        ("/node_modules/bad/index.json", ""),
+    ]);
+    let r = Resolver::new(fs, ResolveOptions::default());
+
+    // The innermost "node_modules" directory wins
+    assert_eq!(
+        resolve(&r, "/src/entry.js", "inner").as_deref(),
+        Some("/src/node_modules/inner/index.js")
+    );
+
+    // "module" comes before "main" and can point to a directory
+    assert_eq!(
+        resolve(&r, "/src/entry.js", "pkg").as_deref(),
+        Some("/node_modules/pkg/esm/index.js")
+    );
+    assert_eq!(
+        resolve(&r, "/src/entry.js", "pkg/lib/main").as_deref(),
+        Some("/node_modules/pkg/lib/main.js")
+    );
+
+    // A broken "package.json" file is ignored
+    assert_eq!(
+        resolve(&r, "/src/entry.js", "bad").as_deref(),
+        Some("/node_modules/bad/index.json")
+    );
+    assert_eq!(resolve(&r, "/src/entry.js", "missing"), None);
+}
+
+#[test]
+fn resolve_tsconfig_paths() {
+    let fs = mock_fs(&[
+        ("/src/entry.ts", ""),
+        ("/src/lib/util.ts", ""),
+        ("/src/lib/special/util.ts", ""),
+        ("/src/vendor/pkg.ts", ""),
+        ("/src/nested/entry.ts", ""),
+        (
+            "/src/tsconfig.json",
+            "{\n\
+             // Comments and trailing commas are allowed\n\
+             \"compilerOptions\": {\n\
+             \"baseUrl\": \".\",\n\
+             \"paths\": {\"@lib/*\": [\"missing/*\", \"lib/*\"], \"@lib/special/*\": [\"lib/special/*\"]},\n\
+             },\n\
+             }",
+        ),
+        (
+            "/src/nested/tsconfig.json",
+            "{\"extends\": \"../tsconfig\", \"compilerOptions\": {\"target\": \"ES2017\"}}",
+        ),
+        ("/node_modules/@lib/util/index.js", ""),
+    ]);
+    let r = Resolver::new(fs, ResolveOptions::default());
+
+    // The first substitution that exists wins, and so does the pattern with
+    // the longest prefix
+    assert_eq!(
+        resolve(&r, "/src/entry.ts", "@lib/util").as_deref(),
+        Some("/src/lib/util.ts")
+    );
+    assert_eq!(
+        resolve(&r, "/src/entry.ts", "@lib/special/util").as_deref(),
+        Some("/src/lib/special/util.ts")
+    );
+    assert_eq!(
+        resolve(&r, "/src/entry.ts", "vendor/pkg").as_deref(),
+        Some("/src/vendor/pkg.ts")
+    );
+
+    // The settings are inherited through "extends"
+    assert_eq!(
+        resolve(&r, "/src/nested/entry.ts", "@lib/util").as_deref(),
+        Some("/src/lib/util.ts")
+    );
+    let tsconfig = r.tsconfig("/src/nested/entry.ts").unwrap();
+    assert_eq!(tsconfig.abs_path, "/src/nested/tsconfig.json");
+    assert_eq!(tsconfig.base_url.as_deref(), Some("/src"));
+
+    // Files outside of the directory don't use it
+    assert_eq!(
+        resolve(&r, "/entry.ts", "@lib/util").as_deref(),
+        Some("/node_modules/@lib/util/index.js")
+    );
+}
+
+#[test]
+fn resolve_browser_field() {
+    let fs = mock_fs(&[
+        ("/src/entry.js", ""),
+        (
+            "/node_modules/pkg/package.json",
+            r#"{
+                "main": "./index.js",
+                "browser": {"./lib/node.js": "./lib/browser.js", "fs": false, "./lib/debug": false}
+            }"#,
+        ),
+        ("/node_modules/pkg/index.js", ""),
+        ("/node_modules/pkg/lib/node.js", ""),
+        ("/node_modules/pkg/lib/browser.js", ""),
+        ("/node_modules/pkg/lib/debug.js", ""),
+        ("/node_modules/fs/index.js", ""),
+    ]);
+    let r = Resolver::new(fs, ResolveOptions::default());
+    let result = |source_path, import_path| {
+        let result = r.resolve(source_path, import_path, ImportKind::Stmt)?;
+        Some((result.path, result.is_disabled))
+    };
+
+    // Files in the package are replaced no matter how they're imported
+    assert_eq!(
+        result("/src/entry.js", "pkg/lib/node"),
+        Some(("/node_modules/pkg/lib/browser.js".to_owned(), false))
+    );
+    assert_eq!(
+        result("/node_modules/pkg/index.js", "./lib/node.js"),
+        Some(("/node_modules/pkg/lib/browser.js".to_owned(), false))
+    );
+
+    // Files and packages mapped to false are disabled, but only from within
+    // the package for packages
+    assert_eq!(
+        result("/node_modules/pkg/index.js", "./lib/debug"),
+        Some(("/node_modules/pkg/lib/debug.js".to_owned(), true))
+    );
+    assert_eq!(
+        result("/node_modules/pkg/index.js", "fs"),
+        Some(("fs".to_owned(), true))
+    );
+    assert_eq!(
+        result("/src/entry.js", "fs"),
+        Some(("/node_modules/fs/index.js".to_owned(), false))
+    );
+
+    // The field is only for browsers
+    let fs = mock_fs(&[
+        (
+            "/node_modules/pkg/package.json",
+            r#"{"browser": {"./a.js": "./b.js"}}"#,
+        ),
+        ("/node_modules/pkg/a.js", ""),
+        ("/node_modules/pkg/b.js", ""),
+    ]);
+    let options = ResolveOptions {
+        platform: Platform::Node,
+        ..ResolveOptions::default()
+    };
+    let r = Resolver::new(fs, options);
+    assert_eq!(
+        resolve(&r, "/entry.js", "pkg/a").as_deref(),
+        Some("/node_modules/pkg/a.js")
+    );
+}
+
+#[test]
+fn resolve_exports_conditions() {
+    let files = [
+        (
+            "/node_modules/pkg/package.json",
+            r#"{
+                "main": "./main.js",
+                "exports": {
+                    ".": {"browser": "./browser.js", "import": "./esm.js", "default": "./cjs.js"},
+                    "./features/*": {"node": null, "default": "./src/features/*.js"},
+                    "./features/private/*": null
+                }
+            }"#,
+        ),
+        ("/node_modules/pkg/main.js", ""),
+        ("/node_modules/pkg/browser.js", ""),
+        ("/node_modules/pkg/esm.js", ""),
+        ("/node_modules/pkg/cjs.js", ""),
+        ("/node_modules/pkg/src/features/a.js", ""),
+        ("/node_modules/pkg/src/features/private/b.js", ""),
+        (
+            "/node_modules/@scope/short/package.json",
+            r#"{"exports": "./index.js"}"#,
+        ),
+        ("/node_modules/@scope/short/index.js", ""),
+    ];
+    let resolver = |platform| {
+        let options = ResolveOptions {
+            platform,
+            ..ResolveOptions::default()
+        };
+        Resolver::new(mock_fs(&files), options)
+    };
+    let resolve = |r: &Resolver<MockFileSystem>, import_path, kind| {
+        r.resolve("/entry.js", import_path, kind)
+            .map(|result| result.path)
+    };
+
+    let browser = resolver(Platform::Browser);
+    assert_eq!(
+        resolve(&browser, "pkg", ImportKind::Require).as_deref(),
+        Some("/node_modules/pkg/browser.js")
+    );
+    assert_eq!(
+        resolve(&browser, "pkg/features/a", ImportKind::Stmt).as_deref(),
+        Some("/node_modules/pkg/src/features/a.js")
+    );
+
+    // The longest matching subpath wins, and files that aren't exported
+    // can't be imported even though they exist
+    assert_eq!(
+        resolve(&browser, "pkg/features/private/b", ImportKind::Stmt),
+        None
+    );
+    assert_eq!(resolve(&browser, "pkg/main.js", ImportKind::Stmt), None);
+
+    let node = resolver(Platform::Node);
+    assert_eq!(
+        resolve(&node, "pkg", ImportKind::Stmt).as_deref(),
+        Some("/node_modules/pkg/esm.js")
+    );
+    assert_eq!(
+        resolve(&node, "pkg", ImportKind::Require).as_deref(),
+        Some("/node_modules/pkg/cjs.js")
+    );
+    assert_eq!(resolve(&node, "pkg/features/a", ImportKind::Stmt), None);
+
+    // A string is the same as a map with only "."
+    let neutral = resolver(Platform::Neutral);
+    assert_eq!(
+        resolve(&neutral, "@scope/short", ImportKind::Stmt).as_deref(),
+        Some("/node_modules/@scope/short/index.js")
+    );
+}
+
+#[test]
+fn resolve_external() {
+    let fs = mock_fs(&[("/node_modules/react/index.js", "")]);
+    let options = ResolveOptions {
+        external: vec!["react".to_owned(), "node:*".to_owned(), "*.png".to_owned()],
+        ..ResolveOptions::default()
+    };
+    let r = Resolver::new(fs, options);
+    let is_external = |import_path| {
+        r.resolve("/entry.js", import_path, ImportKind::Stmt)
+            .is_some_and(|result| result.is_external)
+    };
+
+    // A package name also covers the files in the package
+    assert!(is_external("react"));
+    assert!(is_external("react/jsx-runtime"));
+    assert!(!is_external("react-dom"));
+    assert!(is_external("node:fs"));
+    assert!(is_external("./logo.png"));
+    assert!(!is_external("fs"));
+}
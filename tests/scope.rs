@@ -0,0 +1,209 @@
+use esbuild_rs::ast::{ScopeKind, ScopeTree, StrictModeKind, SymbolKind, AST};
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::{parse, ParseOptions};
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+
+fn parse_js(contents: &str) -> AST {
+    let source = Source {
+        index: 1,
+        contents: contents.to_owned(),
+        ..Source::default()
+    };
+    parse(&Log::default(), &source, &ParseOptions::default()).unwrap()
+}
+
+#[test]
+fn hoisting() {
+    let ast = parse_js("for (let i = 0;;) { var v } function f(a) { { var w } let x }");
+    let scopes = &ast.scopes;
+    let module_scope = ast.module_scope();
+
+    // "var" is hoisted out of blocks but not out of functions
+    let v = module_scope.members["v"];
+    assert_eq!(ast.symbols[v].kind, SymbolKind::Hoisted);
+    assert!(!module_scope.members.contains_key("i"));
+    assert!(!module_scope.members.contains_key("w"));
+    let for_loop = module_scope.children[0];
+    assert_eq!(scopes[for_loop].members["v"], v);
+    assert_eq!(scopes[scopes[for_loop].children[0]].members["v"], v);
+
+    let function_body = scopes[module_scope.children[1]].children[0];
+    assert_eq!(scopes[function_body].kind, ScopeKind::FunctionBody);
+    assert!(scopes[function_body].members.contains_key("w"));
+    assert!(scopes[function_body].members.contains_key("x"));
+
+    let kinds: Vec<ScopeKind> = scopes
+        .ancestors(function_body)
+        .map(|id| scopes[id].kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ScopeKind::FunctionBody,
+            ScopeKind::FunctionArgs,
+            ScopeKind::Entry
+        ]
+    );
+    let kinds: Vec<ScopeKind> = scopes
+        .descendants(ScopeTree::ROOT)
+        .map(|id| scopes[id].kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ScopeKind::Entry,
+            ScopeKind::Block,
+            ScopeKind::Block,
+            ScopeKind::FunctionArgs,
+            ScopeKind::FunctionBody,
+            ScopeKind::Block,
+        ]
+    );
+}
+
+#[test]
+fn var_in_catch_clause() {
+    let ast = parse_js("try {} catch (e) { var e = 2 } e");
+
+    // Inside the catch clause "e" is the catch variable, but the "var" still
+    // declares "e" in the enclosing scope
+    let e = ast.module_scope().members["e"];
+    assert_eq!(ast.symbols[e].kind, SymbolKind::Hoisted);
+    let catch_e = ast.scopes[ast.module_scope().children[1]].members["e"];
+    assert_eq!(ast.symbols[catch_e].kind, SymbolKind::CatchIdentifier);
+
+    // Both are printed with the same name, so neither is renamed
+    let result = transform(
+        "function f() { try {} catch (e) { var e = 2; let x = e } return e }",
+        TransformOptions {
+            minify: MinifyOptions {
+                whitespace: true,
+                syntax: false,
+                identifiers: true,
+            },
+            ..TransformOptions::default()
+        },
+    );
+    assert_eq!(
+        result.code,
+        "function f(){try{}catch(e){var e=2;let a=e}return e}"
+    );
+}
+
+#[test]
+fn strict_mode() {
+    let errors = |contents: &str| -> Vec<String> {
+        let result = transform(contents, TransformOptions::default());
+        result.errors.into_iter().map(|msg| msg.text).collect()
+    };
+
+    // Code is sloppy unless it says otherwise or is in a module or a class
+    assert_eq!(
+        parse_js("with (a) b; let implements")
+            .module_scope()
+            .strict_mode,
+        StrictModeKind::Sloppy
+    );
+    assert_eq!(
+        errors("'use strict'; with (a) b"),
+        ["With statements are not allowed in strict mode"]
+    );
+    assert_eq!(
+        errors("function f() { 'use strict'; return private }"),
+        ["Using \"private\" as a name is not allowed in strict mode"]
+    );
+    assert_eq!(
+        errors("class A { m(implements) {} }"),
+        ["Using \"implements\" as a name is not allowed in strict mode"]
+    );
+
+    // A module is strict from the start even though that's only known later
+    assert_eq!(
+        parse_js("export let a").module_scope().strict_mode,
+        StrictModeKind::ImplicitStrictModule
+    );
+    assert_eq!(
+        errors("with (a) b; import 'c'"),
+        ["With statements are not allowed in an ECMAScript module"]
+    );
+}
+
+#[test]
+fn labels() {
+    let errors = |contents: &str| -> Vec<String> {
+        let result = transform(contents, TransformOptions::default());
+        result.errors.into_iter().map(|msg| msg.text).collect()
+    };
+
+    assert!(errors("a: while (x) { b: { if (y) continue a; break b } }").is_empty());
+    assert!(errors("a: b: for (;;) continue a").is_empty());
+    assert!(errors("a: { function f() { a: x } }").is_empty());
+    assert_eq!(errors("a: a: x"), ["Duplicate label \"a\""]);
+    assert_eq!(
+        errors("a: { continue a }"),
+        ["Cannot continue to label \"a\""]
+    );
+    assert_eq!(
+        errors("a: x; break a"),
+        ["There is no containing label named \"a\""]
+    );
+
+    // Neither kind of jump crosses a function boundary
+    assert!(errors("switch (x) { case 1: break }").is_empty());
+    assert_eq!(
+        errors("switch (x) { default: continue }"),
+        ["Cannot use \"continue\" here"]
+    );
+    assert_eq!(
+        errors("while (x) { () => { break } }"),
+        ["Cannot use \"break\" here"]
+    );
+}
+
+#[test]
+fn block_level_functions() {
+    let minify = |contents: &str| -> String {
+        let result = transform(
+            contents,
+            TransformOptions {
+                minify: MinifyOptions {
+                    whitespace: true,
+                    syntax: false,
+                    identifiers: true,
+                },
+                ..TransformOptions::default()
+            },
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        result.code
+    };
+
+    // In sloppy mode the function is also a "var" in the enclosing function,
+    // unless a lexical declaration in between has the same name
+    assert_eq!(
+        minify("function f() { { function g() {} } { function g() {} } return g }"),
+        "function f(){{function a(){}}{function a(){}}return a}"
+    );
+    assert_eq!(
+        minify("function f() { let g; { function g() {} } return g }"),
+        "function f(){let a;{function b(){}}return a}"
+    );
+    let ast = parse_js("if (x) { function g() {} } g()");
+    let g = ast.module_scope().members["g"];
+    assert_eq!(ast.symbols[g].kind, SymbolKind::HoistedFunction);
+
+    // In strict mode it's only visible in the block
+    assert!(!parse_js("{ function g() {} } export {}")
+        .module_scope()
+        .members
+        .contains_key("g"));
+    assert!(!parse_js("{ async function g() {} }")
+        .module_scope()
+        .members
+        .contains_key("g"));
+
+    // A lexical declaration in the same block is still an error
+    let result = transform("{ let g; function g() {} }", TransformOptions::default());
+    assert_eq!(result.errors[0].text, "\"g\" has already been declared");
+    assert_eq!(result.errors[0].notes.len(), 1);
+}
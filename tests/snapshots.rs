@@ -0,0 +1,164 @@
+// Runs every fixture in "tests/fixtures" through the pipeline and compares
+// the output with its snapshot at the same place in "tests/snapshots". Run with
+// UPDATE_SNAPSHOTS=1 to write the current output instead, and review the
+// change to the snapshots like any other diff. Source maps aren't supported
+// yet, so only the code is compared.
+//
+//   fixtures/transform/NAME.js   Transformed on its own, minified if NAME
+//                                ends in ".min" and lowered to ES5 if NAME
+//                                ends in ".es5"
+//   fixtures/transform/NAME.css  Transformed on its own
+//   fixtures/bundle/NAME/        A bundle of "entry.js" and the files it
+//                                imports. Every output file is in the
+//                                snapshot NAME.txt under a line with its
+//                                path.
+
+use esbuild_rs::bundler::{scan_bundle, BundleOptions, Loader};
+use esbuild_rs::fs::MockFileSystem;
+use esbuild_rs::logging::Log;
+use esbuild_rs::lower::Target;
+use esbuild_rs::parser::ParseOptions;
+use esbuild_rs::resolver::{ResolveOptions, Resolver};
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn tests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+// Sorted so that failures are reported in the same order every time
+fn read_dir(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+fn transform_fixture(path: &Path) -> String {
+    let name = file_name(path);
+    let (stem, ext) = name.rsplit_once('.').unwrap();
+    let minify = if stem.ends_with(".min") {
+        MinifyOptions {
+            whitespace: true,
+            syntax: true,
+            identifiers: true,
+        }
+    } else {
+        MinifyOptions::default()
+    };
+    let target = if stem.ends_with(".es5") {
+        Target::ES5
+    } else {
+        Target::default()
+    };
+    let result = transform(
+        &fs::read_to_string(path).unwrap(),
+        TransformOptions {
+            loader: Loader::from_extension(&format!(".{}", ext)).unwrap(),
+            minify,
+            target,
+            sourcefile: name.clone(),
+            ..TransformOptions::default()
+        },
+    );
+    let mut output = result.code;
+    for msg in result.errors.iter().chain(&result.warnings) {
+        output.push_str(&format!("// {:?}: {}\n", msg.kind, msg.text));
+    }
+    output
+}
+
+fn bundle_fixture(dir: &Path) -> String {
+    let mut input = HashMap::new();
+    for path in read_dir(dir) {
+        let contents = fs::read_to_string(&path).unwrap();
+        input.insert(Path::new("/src").join(file_name(&path)), contents);
+    }
+    let resolver = Resolver::new(MockFileSystem::new(input), ResolveOptions::default());
+
+    let log = Log::default();
+    let options = BundleOptions {
+        bundle: true,
+        abs_output_dir: "/out".to_owned(),
+        ..BundleOptions::default()
+    };
+    let parse_options = ParseOptions {
+        is_bundling: true,
+        ..ParseOptions::default()
+    };
+    let bundle = scan_bundle(
+        &log,
+        &resolver,
+        &["/src/entry.js".to_owned()],
+        &parse_options,
+        None,
+    );
+    let result = bundle.compile(&log, &options);
+
+    let mut output = String::new();
+    for file in result.output_files {
+        output.push_str(&format!("---------- {} ----------\n", file.path));
+        output.push_str(&String::from_utf8_lossy(&file.contents));
+    }
+    for msg in log.take_msgs() {
+        output.push_str(&format!("// {:?}: {}\n", msg.kind, msg.text));
+    }
+    output
+}
+
+#[test]
+fn snapshots() {
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value == "1");
+    let fixtures = tests_dir().join("fixtures");
+    let snapshots = tests_dir().join("snapshots");
+
+    let mut cases = vec![];
+    for path in read_dir(&fixtures.join("transform")) {
+        let snapshot = snapshots.join("transform").join(file_name(&path));
+        cases.push((snapshot, transform_fixture(&path)));
+    }
+    for path in read_dir(&fixtures.join("bundle")) {
+        let snapshot = snapshots
+            .join("bundle")
+            .join(format!("{}.txt", file_name(&path)));
+        cases.push((snapshot, bundle_fixture(&path)));
+    }
+
+    let mut failures = vec![];
+    for (snapshot, actual) in cases {
+        if update {
+            fs::create_dir_all(snapshot.parent().unwrap()).unwrap();
+            fs::write(&snapshot, &actual).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&snapshot) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => {
+                eprintln!(
+                    "{} changed\n--- expected\n{}--- actual\n{}",
+                    snapshot.display(),
+                    expected,
+                    actual
+                );
+                failures.push(snapshot);
+            }
+            Err(_) => {
+                eprintln!("{} is missing", snapshot.display());
+                failures.push(snapshot);
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} snapshots didn't match, run with UPDATE_SNAPSHOTS=1 to update them",
+        failures.len()
+    );
+}
@@ -0,0 +1,38 @@
+var _a, _b, _c, _d, _e, __read = function(value, count) {
+  var method = typeof Symbol == "function" && value[Symbol.iterator];
+  if (!method)
+    return value;
+  var iterator = method.call(value), items = [], step;
+  while ((count === void 0 || count-- > 0) && !(step = iterator.next()).done)
+    items.push(step.value);
+  if ((!step || !step.done) && iterator.return)
+    iterator.return();
+  return items;
+}, __objRest = function(source, exclude) {
+  var target = {}, key, symbols, i;
+  if (source == null)
+    throw TypeError("Cannot destructure " + source);
+  for (i = 0; i < exclude.length; i++)
+    if (typeof exclude[i] != "symbol")
+      exclude[i] += "";
+  for (key in source)
+    if (Object.prototype.hasOwnProperty.call(source, key) && exclude.indexOf(key) < 0)
+      target[key] = source[key];
+  if (typeof Object.getOwnPropertySymbols == "function")
+    for (symbols = Object.getOwnPropertySymbols(source), i = 0; i < symbols.length; i++)
+      if (exclude.indexOf(symbols[i]) < 0 && Object.prototype.propertyIsEnumerable.call(source, symbols[i]))
+        target[symbols[i]] = source[symbols[i]];
+  return target;
+};
+var a = e.a, c = (_a = __read(e.b, 2))[0], d = (_b = _a[1]) === void 0 ? 1 : _b;
+_c = c, a = _c.a, b = _c.b, _c;
+function defaults() {
+  var a = (arguments.length > 0 && arguments[0] !== void 0 ? arguments[0] : {}).a, b = __read(arguments.length > 1 && arguments[1] !== void 0 ? arguments[1] : [], 1)[0], c = arguments.length > 2 ? arguments[2] : void 0;
+  return a + b + c;
+}
+function rest(a) {
+  var b = (arguments.length > 1 && arguments[1] !== void 0 ? arguments[1] : {}).b, c = Array.prototype.slice.call(arguments, 2);
+  return c;
+}
+var i = m.i, k = m[_d = j()], l = __objRest(m, ["i", _d]);
+_e = q, n = _e.n, o.p = __objRest(_e, ["n"]), _e;
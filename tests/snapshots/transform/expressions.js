@@ -0,0 +1,24 @@
+/*! Kept since it's a legal comment */
+let a = (1 + 2) * 3 ** -x;
+let b = (a, b) => ({
+  a,
+  b,
+  [a + b]: `${a} and ${b}`
+});
+let c = typeof a === "string" ? a.length : a?.[0] ?? null;
+let d = new (f())(), e = new f().g;
+class Point extends Base {
+  static origin = new Point(0, 0);
+  constructor(x, y) {
+    super();
+    this.x = x;
+    this.y = y;
+  }
+  get length() {
+    return Math.hypot(this.x, this.y);
+  }
+}
+async function* items() {
+  for await (const item of source())
+    yield item;
+}
@@ -0,0 +1,44 @@
+a, b;
+f((a, b), c);
+x = () => ({});
+x = () => ({}).x;
+x = () => ({} = a);
+for (var i = ("x" in y);;)
+  ;
+for (var i = () => ("x" in y);;)
+  ;
+for ((a in b);;)
+  ;
+for (a = (b in c) ? 1 : 2;;)
+  ;
+a - (b - c);
+a - b - c;
+a ** b ** c;
+(a ** b) ** c;
+a = b = c;
+(a ? b : c) ? d : e;
+a ? (b, c) : d;
+(a?.b).c;
+(a?.b)();
+new (a())();
+new (a.b())();
+(function() {
+})();
+(async () => 1)();
+(() => 1)``;
+(-a) ** 2;
+(a && b) ?? c;
+x = (a, b) => c, d;
+(a, b).c;
+({} = a);
+x = y => y;
+x = (y) => y;
+x = async y => y;
+x = async (y) => y;
+for (x = a => (b in c);;)
+  ;
+(a?.b)``;
+/* @__PURE__ */ a().b();
+(/* @__PURE__ */ a()).b();
+/* @__PURE__ */ new a.b();
+(/* @__PURE__ */ new a()).b;
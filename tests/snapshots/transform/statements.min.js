@@ -0,0 +1 @@
+function search(g,l){let h=0,k=g.length-1;m:while(h<=k){const f=h+k>>1;switch(!0){case g[f]===l:return f;case g[f]<l:h=f+1;continue m;default:k=f-1}}try{throw new Error("not found")}catch{return-1}finally{done=!0}}if(a)b();else if(c)d();else{e()}for(var i=0,j;i<10;i++)j=i;do x--;while(x>0)
\ No newline at end of file
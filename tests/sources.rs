@@ -0,0 +1,32 @@
+use esbuild_rs::fs::MockFileSystem;
+use esbuild_rs::sources::SourceRegistry;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[test]
+fn source_registry() {
+    let mut input = HashMap::new();
+    input.insert(PathBuf::from("/src/a.js"), "a".to_owned());
+    let fs = MockFileSystem::new(input);
+
+    let mut sources = SourceRegistry::new();
+    assert_eq!(sources.add_virtual("<runtime>", String::new()), 0);
+    assert_eq!(sources.add_file(&fs, "/src/a.js"), (1, true));
+    assert_eq!(sources.add_disabled_file(&fs, "/src/a.js"), (2, true));
+    assert_eq!(sources.add_file(&fs, "/src/b.js"), (3, true));
+    assert_eq!(sources.add_file(&fs, "/src/a.js"), (1, false));
+    assert_eq!(sources.add_disabled_file(&fs, "/src/a.js"), (2, false));
+    assert_eq!(sources.index_of("/src/b.js"), Some(3));
+    assert_eq!(sources.len(), 4);
+
+    // There's no current directory in the mock file system
+    assert_eq!(sources[1].index, 1);
+    assert_eq!(sources[1].pretty_path, "/src/a.js");
+    assert_eq!(sources[2].pretty_path, "(disabled):/src/a.js");
+
+    assert!(sources.read_contents(&fs, 1));
+    assert_eq!(sources[1].contents, "a");
+    assert!(sources.read_contents(&fs, 2));
+    assert_eq!(sources[2].contents, "");
+    assert!(!sources.read_contents(&fs, 3));
+}
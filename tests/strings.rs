@@ -0,0 +1,36 @@
+use esbuild_rs::strings::{
+    best_quote_char, quote_utf16, string_to_utf16, utf16_concat, utf16_equals_string,
+    utf16_to_string,
+};
+
+#[test]
+fn conversion() {
+    let text = string_to_utf16("a😀");
+    assert_eq!(text, [0x61, 0xD83D, 0xDE00]);
+    assert_eq!(utf16_to_string(&text), Some("a😀".to_owned()));
+    assert!(utf16_equals_string(&text, "a😀"));
+
+    // Unpaired surrogates can't be converted, but joining the halves works
+    assert_eq!(utf16_to_string(&[0xD83D]), None);
+    assert!(utf16_equals_string(
+        &utf16_concat(&[0xD83D], &[0xDE00]),
+        "😀"
+    ));
+}
+
+#[test]
+fn quotes() {
+    let quote =
+        |text: &str, allow_backtick| best_quote_char(&string_to_utf16(text), allow_backtick);
+    assert_eq!(quote("a", true), b'"');
+    assert_eq!(quote("\"", true), b'\'');
+    assert_eq!(quote("'\"", true), b'`');
+    assert_eq!(quote("'\"", false), b'"');
+    assert_eq!(quote("'\"${`", true), b'"');
+
+    assert_eq!(
+        quote_utf16(&string_to_utf16("'\"'\"${a}\n"), true),
+        "`'\"'\"\\${a}\\n`"
+    );
+    assert_eq!(quote_utf16(&[0xD83D, 0], false), "\"\\uD83D\\0\"");
+}
@@ -0,0 +1,210 @@
+use esbuild_rs::bundler::Loader;
+use esbuild_rs::parser::{ImportsNotUsedAsValues, TypeScriptOptions};
+use esbuild_rs::{transform, MinifyOptions, TransformOptions};
+
+fn print(contents: &str, loader: Loader, typescript: TypeScriptOptions) -> Result<String, String> {
+    let result = transform(
+        contents,
+        TransformOptions {
+            loader,
+            minify: MinifyOptions {
+                whitespace: true,
+                syntax: false,
+                identifiers: false,
+            },
+            typescript,
+            sourcefile: "a.ts".to_owned(),
+            ..TransformOptions::default()
+        },
+    );
+    match result.errors.first() {
+        Some(error) => Err(error.text.clone()),
+        None => Ok(result.code),
+    }
+}
+
+fn ts(contents: &str) -> Result<String, String> {
+    print(contents, Loader::TS, TypeScriptOptions::default())
+}
+
+#[test]
+fn annotations() {
+    assert_eq!(
+        ts("let a: number = 1, b!: string; const c: Array<Array<number>>= []"),
+        Ok("let a=1,b;const c=[]".to_owned())
+    );
+    assert_eq!(
+        ts("function f<T extends {} = {}>(this: T, a?: T, ...b: T[]): a is T { return a! as T }"),
+        Ok("function f(a,...b){return a}".to_owned())
+    );
+    assert_eq!(
+        ts("let f = async <T>(a: T): Promise<T> => <T>a satisfies T"),
+        Ok("let f=async a=>a".to_owned())
+    );
+    assert_eq!(
+        ts("try {} catch (e: unknown) {} for (const x of [] as any[]) {}"),
+        Ok("try{}catch(e){}for(const x of[]){}".to_owned())
+    );
+    assert_eq!(
+        ts("function f(a: string): void;\nfunction f(a: any) { return a }"),
+        Ok("function f(a){return a}".to_owned())
+    );
+}
+
+#[test]
+fn type_declarations() {
+    assert_eq!(
+        ts(
+            "interface A<T> extends B<T> { a: T; b?(): void; [k: string]: any }\n\
+            type C<T> = T extends (infer U)[] ? U : keyof T | `a-${string}`;\n\
+            declare const d: number; declare module 'm' { export let e: C<1> }\n\
+            export type { A }; export interface F {} x(d)"
+        ),
+        Ok("x(d)".to_owned())
+    );
+    assert_eq!(
+        ts("abstract class A<T> extends B<T> implements C, D {\n\
+            declare a: T; private readonly b?: T; c!: T = 1; static [k: string]: any;\n\
+            constructor(public d: T, readonly e = 2) { super() }\n\
+            abstract f(): void; g(): void; g(a?: T) {}\n\
+            }"),
+        Ok(
+            "class A extends B{b;c=1;constructor(d,e=2){super();this.d=d;this.e=e}g(a){}}"
+                .to_owned()
+        )
+    );
+    assert_eq!(
+        ts("enum E { A }"),
+        Err("TypeScript enums are not supported yet".to_owned())
+    );
+    assert_eq!(
+        ts("namespace N { export let a = 1 }"),
+        Err("TypeScript namespaces with values are not supported yet".to_owned())
+    );
+
+    // These are still allowed as names
+    assert_eq!(
+        ts("let type = 1, declare = 2, abstract = 3, namespace = 4; type = declare"),
+        Ok("let type=1,declare=2,abstract=3,namespace=4;type=declare".to_owned())
+    );
+}
+
+#[test]
+fn type_only_imports() {
+    assert_eq!(
+        ts(
+            "import {A, type B, C} from 'a'; import type D from 'd'; import E, * as F from 'e';\n\
+            let x: A = C; export {C, B}"
+        ),
+        Ok("import{C}from\"a\";let x=C;export{C}".to_owned())
+    );
+    assert_eq!(
+        ts("import type from 'a'; type()"),
+        Ok("import type from\"a\";type()".to_owned())
+    );
+
+    let preserve = TypeScriptOptions {
+        imports_not_used_as_values: ImportsNotUsedAsValues::Preserve,
+        ..TypeScriptOptions::default()
+    };
+    assert_eq!(
+        print("import {A} from 'a'; let x: A", Loader::TS, preserve),
+        Ok("import\"a\";let x".to_owned())
+    );
+
+    let error = TypeScriptOptions {
+        imports_not_used_as_values: ImportsNotUsedAsValues::Error,
+        ..TypeScriptOptions::default()
+    };
+    assert!(print("import {A} from 'a'; let x: A", Loader::TS, error).is_err());
+
+    // The same import in a JavaScript file is kept
+    assert_eq!(
+        print(
+            "import {A} from 'a'",
+            Loader::JS,
+            TypeScriptOptions::default()
+        ),
+        Ok("import{A}from\"a\"".to_owned())
+    );
+}
+
+#[test]
+fn import_and_export_equals() {
+    // Aliases that aren't used may be types, so they are removed
+    assert_eq!(
+        ts("import fs = require('fs'); import a = fs.a.b; import c = fs.c; f(fs, a)"),
+        Ok("const fs=require(\"fs\");const a=fs.a.b;f(fs,a)".to_owned())
+    );
+    assert_eq!(
+        ts("export import a = b.c"),
+        Ok("export const a=b.c".to_owned())
+    );
+    assert_eq!(
+        ts("export = 1"),
+        Err("\"export =\" cannot be used when the output format is ESM".to_owned())
+    );
+
+    // Inside a namespace too, which leaves nothing in it in this case
+    assert_eq!(
+        ts("namespace N.M { import x = a.b; export type T = x.Y } export namespace O {} f()"),
+        Ok("f()".to_owned())
+    );
+    assert_eq!(
+        ts("namespace N { import x = a.b; let y = x }"),
+        Err("TypeScript namespaces with values are not supported yet".to_owned())
+    );
+
+    // Only TypeScript has these
+    assert!(print(
+        "import fs = require('fs')",
+        Loader::JS,
+        TypeScriptOptions::default()
+    )
+    .is_err());
+}
+
+#[test]
+fn generic_calls() {
+    // Type arguments are only taken when what follows can't continue an
+    // expression, the same way the TypeScript compiler decides
+    assert_eq!(
+        ts("f<T>(x); a < b > (c); new C<T>(); g<A, B>`t`; h<T>"),
+        Ok("f(x);a(c);new C;g`t`;h".to_owned())
+    );
+    assert_eq!(ts("a < b > c"), Ok("a<b>c".to_owned()));
+    assert_eq!(ts("a < b >= c"), Ok("a<b>=c".to_owned()));
+    assert_eq!(ts("f < T > -x"), Ok("f<T>-x".to_owned()));
+
+    // JavaScript doesn't have type arguments
+    assert_eq!(
+        print("f<T>(x)", Loader::JS, TypeScriptOptions::default()),
+        Ok("f<T>x".to_owned())
+    );
+}
+
+#[test]
+fn generic_arrows() {
+    assert_eq!(ts("let f = <T>(x: T) => x"), Ok("let f=x=>x".to_owned()));
+    assert_eq!(ts("let f = <T,>(x) => x"), Ok("let f=x=>x".to_owned()));
+
+    // In ".tsx" files "<T>" starts an element, so a generic arrow function
+    // needs a trailing comma or a constraint
+    let tsx = |contents| print(contents, Loader::TSX, TypeScriptOptions::default());
+    assert_eq!(tsx("let f = <T,>(x: T) => x"), Ok("let f=x=>x".to_owned()));
+    assert_eq!(
+        tsx("let f = async <T extends X>(x: T) => x"),
+        Ok("let f=async x=>x".to_owned())
+    );
+    assert_eq!(
+        tsx("let f = <T extends/>"),
+        Ok("let f=/*@__PURE__*/React.createElement(T,{extends:true})".to_owned())
+    );
+    assert_eq!(
+        tsx("let f = <T>(x) => x</T>"),
+        Err(
+            "The character \">\" is not valid inside a JSX element, use \"{'>'}\" instead"
+                .to_owned()
+        )
+    );
+}
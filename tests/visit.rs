@@ -0,0 +1,64 @@
+use esbuild_rs::ast::{Expr, ExprKind, Stmt};
+use esbuild_rs::logging::{Log, Source};
+use esbuild_rs::parser::{parse, ParseOptions};
+use esbuild_rs::visit::{Visit, VisitMut};
+
+fn parse_stmts(contents: &str) -> Vec<Stmt> {
+    let source = Source {
+        index: 1,
+        contents: contents.to_owned(),
+        ..Source::default()
+    };
+    let ast = parse(&Log::default(), &source, &ParseOptions::default()).unwrap();
+    ast.parts.into_iter().flat_map(|part| part.stmts).collect()
+}
+
+struct CountCalls(usize);
+
+impl Visit for CountCalls {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let ExprKind::Call { .. } = expr.data.as_ref() {
+            self.0 += 1;
+        }
+        self.walk_expr(expr);
+    }
+}
+
+struct DoubleNumbers;
+
+impl VisitMut for DoubleNumbers {
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        if let ExprKind::Number { value } = expr.data.as_mut() {
+            *value *= 2.0;
+        }
+        self.walk_expr(expr);
+    }
+}
+
+#[test]
+fn visit() {
+    let stmts = parse_stmts(
+        "a(); function f(x = b()) { return () => c(d()) } class A { [e()] = f() } try {} catch ({ y = g() }) {}",
+    );
+    let mut counter = CountCalls(0);
+    counter.visit_stmts(&stmts);
+    assert_eq!(counter.0, 7);
+}
+
+#[test]
+fn visit_mut() {
+    let mut stmts = parse_stmts("let [a = 1] = [2]; if (a) { switch (a) { case 3: f(4) } }");
+    DoubleNumbers.visit_stmts(&mut stmts);
+    let mut numbers = vec![];
+    struct CollectNumbers<'a>(&'a mut Vec<f64>);
+    impl<'a> Visit for CollectNumbers<'a> {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let ExprKind::Number { value } = expr.data.as_ref() {
+                self.0.push(*value);
+            }
+            self.walk_expr(expr);
+        }
+    }
+    CollectNumbers(&mut numbers).visit_stmts(&stmts);
+    assert_eq!(numbers, [2.0, 4.0, 6.0, 8.0]);
+}